@@ -118,6 +118,9 @@ struct RawRepoConfig {
     // Name of this repository in hgsql for globalrevs. Required for syncing
     // globalrevs through the sync job.
     37: optional string hgsql_globalrevs_name,
+
+    // Quotas on the wireproto requests a single client can make
+    38: optional list<RawWireprotoQuota> wireproto_quotas,
 }
 
 struct RawDerivedDataConfig {
@@ -126,6 +129,9 @@ struct RawDerivedDataConfig {
   // Defaults to v1
   3: optional RawUnodeVersion raw_unode_version,
   4: optional i64 override_blame_filesize_limit,
+  // Path to a file in the repo listing commits which blame may be asked
+  // to skip (one commit hash per line, `#` starts a comment)
+  5: optional string blame_ignore_revs_file,
 }
 
 union RawUnodeVersion {
@@ -170,6 +176,11 @@ struct RawBlobstoreLogging {
 struct RawBlobstorePack {
     1: RawBlobstoreConfig blobstore (rust.box),
 }
+struct RawBlobstoreLocalCache {
+    1: RawBlobstoreConfig blobstore (rust.box),
+    2: string path,
+    3: i64 max_size_bytes,
+}
 
 // Configuration for a single blobstore. These are intended to be defined in a
 // separate blobstore.toml config file, and then referenced by name from a
@@ -188,6 +199,7 @@ union RawBlobstoreConfig {
     8: RawBlobstoreManifoldWithTtl manifold_with_ttl,
     9: RawBlobstoreLogging logging,
     10: RawBlobstorePack pack,
+    11: RawBlobstoreLocalCache local_cache,
 }
 
 struct RawBlobstoreIdConfig {
@@ -285,6 +297,7 @@ struct RawCacheWarmupConfig {
     1: string bookmark,
     2: optional i64 commit_limit,
     3: optional bool microwave_preload,
+    4: optional bool file_content_warmup,
 }
 
 struct RawBookmarkHook {
@@ -348,11 +361,36 @@ struct RawCommitSyncConfig {
     4: optional string version_name,
 }
 
+struct RawWireprotoQuota {
+    // Name of the quota, used in errors and monitoring
+    1: string name,
+    // Wireproto commands the quota applies to. If not set, the quota applies
+    // to all commands.
+    2: optional list<string> commands,
+    // Interval (in seconds) over which requests and bytes are counted
+    3: i64 interval,
+    // Maximum number of requests per client in an interval
+    4: optional i64 max_requests,
+    // Maximum number of requests per client in flight at once
+    5: optional i64 max_concurrent_requests,
+    // Maximum number of bytes served to a client in an interval
+    6: optional i64 max_bytes,
+    // Only log requests that exceed the quota instead of rejecting them
+    // (default: false)
+    7: optional bool log_only,
+}
+
  struct RawWireprotoLoggingConfig {
      1: optional string scribe_category,
      2: optional string storage_config,
      3: optional i64 remote_arg_size_threshold,
      4: optional string local_path,
+     // Capture one in this many requests to local_path (default: 1)
+     5: optional i64 local_sample_rate,
+     // Rotate local_path once it grows beyond this many bytes
+     6: optional i64 local_max_file_size,
+     // Number of rotated files to keep (default: 10)
+     7: optional i64 local_max_files,
  }
 
 struct RawSourceControlServiceParams {
@@ -41,15 +41,17 @@ use mononoke_types::RepositoryId;
 use newfilenodes::NewFilenodesBuilder;
 use phases::SqlPhasesFactory;
 use readonlyblob::ReadOnlyBlobstore;
-use redactedblobstore::SqlRedactedContentStore;
+use redactedblobstore::{RedactedMetadata, SqlRedactedContentStore};
 use repo_blobstore::RepoBlobstoreArgs;
 use scuba_ext::{ScubaSampleBuilder, ScubaSampleBuilderExt};
+use skeleton_manifest::RootSkeletonManifestId;
 use slog::Logger;
 use sql::{rusqlite::Connection as SqliteConnection, Connection};
 use sql_construct::SqlConstruct;
 use sql_ext::{facebook::MysqlOptions, SqlConnections};
 use std::num::NonZeroUsize;
 use std::{collections::HashMap, sync::Arc, time::Duration};
+use trigram_index::RootTrigramIndexId;
 use type_map::TypeMap;
 use unodes::RootUnodeManifestId;
 use virtually_sharded_blobstore::VirtuallyShardedBlobstore;
@@ -273,7 +275,7 @@ pub async fn open_blobrepo_given_datasources(
 pub struct TestRepoBuilder {
     repo_id: RepositoryId,
     blobstore: Arc<dyn Blobstore>,
-    redacted: Option<HashMap<String, String>>,
+    redacted: Option<HashMap<String, RedactedMetadata>>,
 }
 
 impl TestRepoBuilder {
@@ -290,7 +292,7 @@ impl TestRepoBuilder {
         self
     }
 
-    pub fn redacted(mut self, redacted: Option<HashMap<String, String>>) -> Self {
+    pub fn redacted(mut self, redacted: Option<HashMap<String, RedactedMetadata>>) -> Self {
         self.redacted = redacted;
         self
     }
@@ -393,10 +395,13 @@ pub fn init_all_derived_data() -> DerivedDataConfig {
             RootFsnodeId::NAME.to_string(),
             RootDeletedManifestId::NAME.to_string(),
             RootUnodeManifestId::NAME.to_string(),
+            RootSkeletonManifestId::NAME.to_string(),
+            RootTrigramIndexId::NAME.to_string(),
             TreeHandle::NAME.to_string(),
         },
         unode_version: UnodeVersion::V2,
         override_blame_filesize_limit: None,
+        blame_ignore_revs_file: None,
     }
 }
 
@@ -476,7 +481,7 @@ async fn new_development(
     fb: FacebookInit,
     sql_factory: &MetadataSqlFactory,
     blobstore: Arc<dyn Blobstore>,
-    redacted_blobs: Option<HashMap<String, String>>,
+    redacted_blobs: Option<HashMap<String, RedactedMetadata>>,
     scuba_censored_table: Option<String>,
     repoid: RepositoryId,
     filestore_config: FilestoreConfig,
@@ -609,7 +614,7 @@ async fn new_production(
     fb: FacebookInit,
     sql_factory: &MetadataSqlFactory,
     blobstore: Arc<dyn Blobstore>,
-    redacted_blobs: Option<HashMap<String, String>>,
+    redacted_blobs: Option<HashMap<String, RedactedMetadata>>,
     scuba_censored_table: Option<String>,
     repoid: RepositoryId,
     bookmarks_cache_ttl: Option<Duration>,
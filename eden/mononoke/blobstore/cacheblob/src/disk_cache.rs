@@ -0,0 +1,215 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Error;
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreMetadata, CountedBlobstore};
+use context::PerfCounterType;
+use futures::future::{FutureExt, TryFutureExt};
+use futures_ext::{BoxFuture, FutureExt as OldFutureExt};
+use mononoke_types::BlobstoreBytes;
+use stats::prelude::*;
+use tokio::task;
+
+use crate::dummy::DummyLease;
+use crate::in_process_lease::InProcessLease;
+use crate::locking_cache::{CacheBlobstore, CacheOps};
+
+mod store;
+use self::store::DiskStore;
+
+define_stats! {
+    prefix = "mononoke.blobstore.cacheblob.disk_cache";
+    put_bytes: timeseries(Rate, Sum),
+    evictions: timeseries(Rate, Sum),
+    errors: timeseries(Rate, Sum),
+}
+
+/// A caching layer over an existing blobstore, backed by a size-limited directory on local disk
+/// (e.g. an SSD).  Unlike cachelib, the cache survives restarts.
+///
+/// The cache directory is owned by a single process, so leases (if wanted) are in-process: there
+/// is no disk-backed lease, and processes must not share a cache directory.
+#[derive(Clone)]
+pub struct DiskCacheOps {
+    path: PathBuf,
+    store: Arc<DiskStore>,
+}
+
+impl DiskCacheOps {
+    /// Open the cache in `path`, creating it if needed.  The cache holds at most `max_size`
+    /// bytes, evicting the least recently used blobs to stay within that limit.
+    pub fn open(path: impl AsRef<Path>, max_size: u64) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let store = Arc::new(DiskStore::open(&path, max_size)?);
+        Ok(Self { path, store })
+    }
+
+    /// The number of bytes currently used by cached blobs.
+    pub fn size(&self) -> u64 {
+        self.store.size()
+    }
+
+    /// The number of cached blobs.
+    pub fn num_blobs(&self) -> usize {
+        self.store.num_blobs()
+    }
+}
+
+/// Run blocking disk I/O off the async executor.  Errors are counted and otherwise dropped, as
+/// the cache is best-effort.
+fn run_blocking<T, F>(f: F) -> BoxFuture<T, ()>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+{
+    async move {
+        match task::spawn_blocking(f).await {
+            Ok(Ok(value)) => Ok(value),
+            _ => {
+                STATS::errors.add_value(1);
+                Err(())
+            }
+        }
+    }
+    .boxed()
+    .compat()
+    .boxify()
+}
+
+pub fn new_disk_cache_blobstore_no_lease<T>(
+    blobstore: T,
+    path: impl AsRef<Path>,
+    max_size: u64,
+) -> Result<CountedBlobstore<CacheBlobstore<DiskCacheOps, DummyLease, T>>, Error>
+where
+    T: Blobstore + Clone,
+{
+    let cache_ops = DiskCacheOps::open(path, max_size)?;
+    Ok(CountedBlobstore::new(
+        "disk_cache".to_string(),
+        CacheBlobstore::new(cache_ops, DummyLease {}, blobstore),
+    ))
+}
+
+/// Like `new_disk_cache_blobstore_no_lease`, but concurrent misses for the same key within this
+/// process are coalesced with an `InProcessLease`.  No other lease is supported, as the cache
+/// directory is not shared with other processes.
+pub fn new_disk_cache_blobstore<T>(
+    blobstore: T,
+    path: impl AsRef<Path>,
+    max_size: u64,
+) -> Result<CountedBlobstore<CacheBlobstore<DiskCacheOps, InProcessLease, T>>, Error>
+where
+    T: Blobstore + Clone,
+{
+    let cache_ops = DiskCacheOps::open(path, max_size)?;
+    Ok(CountedBlobstore::new(
+        "disk_cache".to_string(),
+        CacheBlobstore::new(cache_ops, InProcessLease::new(), blobstore),
+    ))
+}
+
+impl CacheOps for DiskCacheOps {
+    const HIT_COUNTER: Option<PerfCounterType> = Some(PerfCounterType::DiskCacheHits);
+    const MISS_COUNTER: Option<PerfCounterType> = Some(PerfCounterType::DiskCacheMisses);
+    const CACHE_NAME: &'static str = "disk_cache";
+
+    fn get(&self, key: &str) -> BoxFuture<Option<BlobstoreGetData>, ()> {
+        let store = self.store.clone();
+        let key = key.to_string();
+        run_blocking(move || {
+            let blob = store.get(&key)?.map(|blob| {
+                BlobstoreGetData::new(
+                    BlobstoreMetadata::new(blob.ctime),
+                    BlobstoreBytes::from_bytes(blob.data),
+                )
+            });
+            Ok(blob)
+        })
+    }
+
+    fn put(&self, key: &str, value: BlobstoreGetData) -> BoxFuture<(), ()> {
+        let store = self.store.clone();
+        let key = key.to_string();
+        run_blocking(move || {
+            let ctime = *value.as_meta().as_ctime();
+            let data = value.into_raw_bytes();
+            let evicted = store.put(&key, ctime, data.as_ref())?;
+            STATS::put_bytes.add_value(data.len() as i64);
+            STATS::evictions.add_value(evicted as i64);
+            Ok(())
+        })
+    }
+
+    fn check_present(&self, key: &str) -> BoxFuture<bool, ()> {
+        let store = self.store.clone();
+        let key = key.to_string();
+        run_blocking(move || store.contains(&key))
+    }
+}
+
+impl fmt::Debug for DiskCacheOps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DiskCacheOps({})", self.path.display())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bytes::Bytes;
+    use context::CoreContext;
+    use fbinit::FacebookInit;
+    use futures::compat::Future01CompatExt;
+    use memblob::EagerMemblob;
+
+    #[fbinit::compat_test]
+    async fn read_through(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let dir = tempfile::tempdir()?;
+        let inner = EagerMemblob::new();
+        inner
+            .put(
+                ctx.clone(),
+                "foo".to_string(),
+                BlobstoreBytes::from_bytes("foobar"),
+            )
+            .await?;
+
+        let cache = DiskCacheOps::open(dir.path(), 1024 * 1024)?;
+        let outer = CacheBlobstore::new(cache.clone(), DummyLease {}, inner.clone());
+
+        let value = outer.get(ctx.clone(), "foo".to_string()).await?;
+        assert_eq!(
+            value.map(BlobstoreGetData::into_raw_bytes),
+            Some(Bytes::from("foobar"))
+        );
+
+        // The miss filled the cache in the background.
+        let cached = loop {
+            match cache.get("foo").compat().await {
+                Ok(Some(cached)) => break cached,
+                _ => tokio::time::delay_for(std::time::Duration::from_millis(10)).await,
+            }
+        };
+        assert_eq!(cached.into_raw_bytes(), Bytes::from("foobar"));
+
+        // The cache persists across reopening.
+        drop(outer);
+        drop(cache);
+        let cache = DiskCacheOps::open(dir.path(), 1024 * 1024)?;
+        assert!(cache.check_present("foo").compat().await.unwrap());
+        assert!(!cache.check_present("bar").compat().await.unwrap());
+        assert_eq!(cache.num_blobs(), 1);
+
+        Ok(())
+    }
+}
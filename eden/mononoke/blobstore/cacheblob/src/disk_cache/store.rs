@@ -0,0 +1,744 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! On-disk storage for the disk cache.
+//!
+//! Each cached blob is stored in its own file under `blobs/`, named after a hash of its key.
+//! Blob files are written to `tmp/` and renamed into place, and carry a checksum, so a crash
+//! can at worst leave a truncated or missing file behind, both of which are treated as misses.
+//!
+//! The index is an append-only log of fixed-size, checksummed records describing inserts,
+//! accesses and removals, in order.  Replaying it on startup restores both the set of cached
+//! blobs and their LRU order.  A torn record at the end of the log is truncated away.  Inserts
+//! are logged before the blob is renamed into place, and removals are logged after the blob is
+//! deleted, so stale entries are the only inconsistency a clean shutdown can leave behind.  They
+//! cost nothing but some accounted space, and are dropped when they are evicted or found to be
+//! missing.
+//!
+//! The index is only synced every `SYNC_INTERVAL` inserts, so a crash can lose the last inserts.
+//! Their blob files, like those left behind by racing puts and removals of the same blob, are
+//! not known to the index and are deleted on startup.
+//!
+//! Blob files are created, renamed and deleted without holding the lock on the in-memory state,
+//! so slow disk operations do not serialize unrelated requests.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::hash::Hasher;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::{Context, Error};
+use twox_hash::XxHash64;
+
+const INDEX_FILE: &str = "index";
+const BLOBS_DIR: &str = "blobs";
+const TMP_DIR: &str = "tmp";
+
+const BLOB_MAGIC: &[u8; 4] = b"MDC1";
+const BLOB_HEADER_SIZE: usize = 4 + 4;
+const CHECKSUM_SIZE: usize = 8;
+
+const RECORD_SIZE: usize = 1 + 8 + 8 + CHECKSUM_SIZE;
+const RECORD_INSERT: u8 = 1;
+const RECORD_TOUCH: u8 = 2;
+const RECORD_REMOVE: u8 = 3;
+
+/// The index is rewritten once it holds this many times more records than live entries.
+const COMPACT_RATIO: u64 = 4;
+/// Small indexes are never compacted.
+const COMPACT_MIN_RECORDS: u64 = 64 * 1024;
+/// The index is synced to disk once this many inserts have been logged since the last sync.
+const SYNC_INTERVAL: u64 = 1024;
+
+fn checksum(data: &[u8]) -> u64 {
+    let mut hasher = XxHash64::with_seed(0);
+    hasher.write(data);
+    hasher.finish()
+}
+
+fn key_hash(key: &str) -> u64 {
+    checksum(key.as_bytes())
+}
+
+/// A cached blob, as returned by `DiskStore::get`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredBlob {
+    pub ctime: Option<i64>,
+    pub data: Vec<u8>,
+}
+
+struct IndexEntry {
+    size: u64,
+    tick: u64,
+}
+
+struct State {
+    entries: HashMap<u64, IndexEntry>,
+    /// Entries by last access, oldest first.
+    lru: BTreeMap<u64, u64>,
+    next_tick: u64,
+    total_size: u64,
+    index: File,
+    index_records: u64,
+    /// Inserts logged since the index was last synced.
+    unsynced_inserts: u64,
+}
+
+impl State {
+    fn touch(&mut self, name: u64) -> bool {
+        let tick = self.next_tick;
+        match self.entries.get_mut(&name) {
+            Some(entry) => {
+                self.lru.remove(&entry.tick);
+                entry.tick = tick;
+                self.lru.insert(tick, name);
+                self.next_tick += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, name: u64, size: u64) {
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        if let Some(old) = self.entries.insert(name, IndexEntry { size, tick }) {
+            self.lru.remove(&old.tick);
+            self.total_size -= old.size;
+        }
+        self.lru.insert(tick, name);
+        self.total_size += size;
+    }
+
+    fn remove(&mut self, name: u64) -> bool {
+        match self.entries.remove(&name) {
+            Some(entry) => {
+                self.lru.remove(&entry.tick);
+                self.total_size -= entry.size;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn apply(&mut self, kind: u8, name: u64, size: u64) {
+        match kind {
+            RECORD_INSERT => self.insert(name, size),
+            RECORD_TOUCH => {
+                self.touch(name);
+            }
+            RECORD_REMOVE => {
+                self.remove(name);
+            }
+            _ => {}
+        }
+    }
+
+    fn append(&mut self, kind: u8, name: u64, size: u64) -> Result<(), Error> {
+        self.index.write_all(&encode_record(kind, name, size))?;
+        self.index_records += 1;
+        if kind == RECORD_INSERT {
+            self.unsynced_inserts += 1;
+        }
+        Ok(())
+    }
+
+    /// Return a handle to the index if it is due to be synced.  The sync itself can then be
+    /// done without holding the lock.
+    fn index_to_sync(&mut self) -> Result<Option<File>, Error> {
+        if self.unsynced_inserts < SYNC_INTERVAL {
+            return Ok(None);
+        }
+        self.unsynced_inserts = 0;
+        Ok(Some(self.index.try_clone()?))
+    }
+}
+
+fn encode_record(kind: u8, name: u64, size: u64) -> [u8; RECORD_SIZE] {
+    let mut record = [0u8; RECORD_SIZE];
+    record[0] = kind;
+    record[1..9].copy_from_slice(&name.to_le_bytes());
+    record[9..17].copy_from_slice(&size.to_le_bytes());
+    let sum = checksum(&record[..17]);
+    record[17..].copy_from_slice(&sum.to_le_bytes());
+    record
+}
+
+fn decode_record(record: &[u8]) -> Option<(u8, u64, u64)> {
+    let mut sum = [0u8; CHECKSUM_SIZE];
+    sum.copy_from_slice(&record[17..RECORD_SIZE]);
+    if checksum(&record[..17]) != u64::from_le_bytes(sum) {
+        return None;
+    }
+    let mut name = [0u8; 8];
+    name.copy_from_slice(&record[1..9]);
+    let mut size = [0u8; 8];
+    size.copy_from_slice(&record[9..17]);
+    Some((
+        record[0],
+        u64::from_le_bytes(name),
+        u64::from_le_bytes(size),
+    ))
+}
+
+fn encode_blob(key: &str, ctime: Option<i64>, data: &[u8]) -> Vec<u8> {
+    let mut blob =
+        Vec::with_capacity(BLOB_HEADER_SIZE + key.len() + 1 + 8 + data.len() + CHECKSUM_SIZE);
+    blob.extend_from_slice(BLOB_MAGIC);
+    blob.extend_from_slice(&(key.len() as u32).to_le_bytes());
+    blob.extend_from_slice(key.as_bytes());
+    blob.push(ctime.is_some() as u8);
+    blob.extend_from_slice(&ctime.unwrap_or(0).to_le_bytes());
+    blob.extend_from_slice(data);
+    let sum = checksum(&blob);
+    blob.extend_from_slice(&sum.to_le_bytes());
+    blob
+}
+
+enum DecodedBlob {
+    Valid(StoredBlob),
+    /// The file holds a different key with the same hash.
+    OtherKey,
+    Corrupt,
+}
+
+fn decode_blob(key: &str, mut blob: Vec<u8>) -> DecodedBlob {
+    let key_end = BLOB_HEADER_SIZE + key.len();
+    let data_start = key_end + 1 + 8;
+    if blob.len() < BLOB_HEADER_SIZE + CHECKSUM_SIZE || &blob[..4] != BLOB_MAGIC {
+        return DecodedBlob::Corrupt;
+    }
+    let body_len = blob.len() - CHECKSUM_SIZE;
+    let mut sum = [0u8; CHECKSUM_SIZE];
+    sum.copy_from_slice(&blob[body_len..]);
+    if checksum(&blob[..body_len]) != u64::from_le_bytes(sum) {
+        return DecodedBlob::Corrupt;
+    }
+    let mut key_len = [0u8; 4];
+    key_len.copy_from_slice(&blob[4..8]);
+    if u32::from_le_bytes(key_len) as usize != key.len()
+        || body_len < data_start
+        || &blob[BLOB_HEADER_SIZE..key_end] != key.as_bytes()
+    {
+        return DecodedBlob::OtherKey;
+    }
+    let mut ctime = [0u8; 8];
+    ctime.copy_from_slice(&blob[key_end + 1..data_start]);
+    let ctime = if blob[key_end] != 0 {
+        Some(i64::from_le_bytes(ctime))
+    } else {
+        None
+    };
+    blob.truncate(body_len);
+    blob.drain(..data_start);
+    DecodedBlob::Valid(StoredBlob { ctime, data: blob })
+}
+
+/// A size-bounded, LRU-evicted store of blobs in a local directory.
+///
+/// The directory must not be shared with other processes.
+pub struct DiskStore {
+    root: PathBuf,
+    max_size: u64,
+    tmp_counter: AtomicU64,
+    state: Mutex<State>,
+}
+
+impl DiskStore {
+    /// Open the store in `root`, creating it if needed, and replay its index.  If the store
+    /// holds more than `max_size` bytes (e.g. because the limit was lowered), the excess is
+    /// evicted straight away.
+    pub fn open(root: impl AsRef<Path>, max_size: u64) -> Result<Self, Error> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(root.join(BLOBS_DIR))
+            .with_context(|| format!("While creating disk cache in {}", root.display()))?;
+
+        // Anything in tmp/ is from an interrupted put.
+        let tmp = root.join(TMP_DIR);
+        match fs::remove_dir_all(&tmp) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        fs::create_dir_all(&tmp)?;
+
+        let index_path = root.join(INDEX_FILE);
+        let mut index = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&index_path)
+            .with_context(|| format!("While opening {}", index_path.display()))?;
+        let mut log = Vec::new();
+        index.read_to_end(&mut log)?;
+
+        let mut state = State {
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            next_tick: 0,
+            total_size: 0,
+            index,
+            index_records: 0,
+            unsynced_inserts: 0,
+        };
+        let mut valid_len = 0;
+        for record in log.chunks(RECORD_SIZE) {
+            if record.len() != RECORD_SIZE {
+                break;
+            }
+            match decode_record(record) {
+                Some((kind, name, size)) => state.apply(kind, name, size),
+                None => break,
+            }
+            valid_len += RECORD_SIZE;
+            state.index_records += 1;
+        }
+        if valid_len != log.len() {
+            // Drop a torn write at the end of the log.
+            state.index.set_len(valid_len as u64)?;
+        }
+        remove_orphans(&root, &state.entries)?;
+
+        let store = Self {
+            root,
+            max_size,
+            tmp_counter: AtomicU64::new(0),
+            state: Mutex::new(state),
+        };
+        let evicted = store.evict(&mut store.lock_state());
+        store.remove_blobs(&evicted)?;
+        store.maybe_compact(&mut store.lock_state())?;
+        Ok(store)
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("lock poisoned")
+    }
+
+    fn blob_path(&self, name: u64) -> PathBuf {
+        blob_path(&self.root, name)
+    }
+
+    /// The number of bytes currently accounted to cached blobs.
+    pub fn size(&self) -> u64 {
+        self.lock_state().total_size
+    }
+
+    /// The number of cached blobs.
+    pub fn num_blobs(&self) -> usize {
+        self.lock_state().entries.len()
+    }
+
+    /// Fetch a blob, marking it as recently used.
+    pub fn get(&self, key: &str) -> Result<Option<StoredBlob>, Error> {
+        let name = key_hash(key);
+        if !self.lock_state().entries.contains_key(&name) {
+            return Ok(None);
+        }
+
+        let blob = match fs::read(self.blob_path(name)) {
+            Ok(blob) => blob,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.forget(name)?;
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        match decode_blob(key, blob) {
+            DecodedBlob::Valid(blob) => {
+                let mut state = self.lock_state();
+                if state.touch(name) {
+                    state.append(RECORD_TOUCH, name, 0)?;
+                    self.maybe_compact(&mut state)?;
+                }
+                Ok(Some(blob))
+            }
+            DecodedBlob::OtherKey => Ok(None),
+            DecodedBlob::Corrupt => {
+                self.forget(name)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Check whether a blob is cached, without reading its contents.
+    pub fn contains(&self, key: &str) -> Result<bool, Error> {
+        let name = key_hash(key);
+        if !self.lock_state().entries.contains_key(&name) {
+            return Ok(false);
+        }
+
+        // Check the stored key, to guard against hash collisions.
+        let mut header = vec![0u8; BLOB_HEADER_SIZE + key.len()];
+        let res = File::open(self.blob_path(name)).and_then(|mut f| f.read_exact(&mut header));
+        match res {
+            Ok(()) => {}
+            Err(e)
+                if e.kind() == io::ErrorKind::NotFound
+                    || e.kind() == io::ErrorKind::UnexpectedEof =>
+            {
+                return Ok(false);
+            }
+            Err(e) => return Err(e.into()),
+        }
+        let mut expected = Vec::with_capacity(header.len());
+        expected.extend_from_slice(BLOB_MAGIC);
+        expected.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        expected.extend_from_slice(key.as_bytes());
+        Ok(header == expected)
+    }
+
+    /// Store a blob, evicting the least recently used blobs to stay within the size limit.
+    /// Returns the number of evicted blobs.  Blobs that are too large for the cache are
+    /// skipped.
+    pub fn put(&self, key: &str, ctime: Option<i64>, data: &[u8]) -> Result<usize, Error> {
+        let name = key_hash(key);
+        let blob = encode_blob(key, ctime, data);
+        let size = blob.len() as u64;
+        if size > self.max_size {
+            return Ok(0);
+        }
+
+        let tmp_path = self.root.join(TMP_DIR).join(format!(
+            "{:016x}-{}",
+            name,
+            self.tmp_counter.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&tmp_path, &blob)?;
+
+        let path = self.blob_path(name);
+        let res = (|| -> Result<(), Error> {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let index = {
+                let mut state = self.lock_state();
+                state.append(RECORD_INSERT, name, size)?;
+                state.insert(name, size);
+                state.index_to_sync()?
+            };
+            if let Some(index) = index {
+                index.sync_data()?;
+            }
+            fs::rename(&tmp_path, &path)?;
+            Ok(())
+        })();
+        if res.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        res?;
+
+        let (orphaned, evicted) = {
+            let mut state = self.lock_state();
+            // The entry was evicted or forgotten while the blob was being renamed into place.
+            let orphaned = !state.entries.contains_key(&name);
+            let evicted = self.evict(&mut state);
+            self.maybe_compact(&mut state)?;
+            (orphaned, evicted)
+        };
+        if orphaned {
+            remove_if_exists(&path)?;
+        }
+        self.remove_blobs(&evicted)?;
+        Ok(evicted.len())
+    }
+
+    /// Drop an entry whose blob file is missing or corrupt.
+    fn forget(&self, name: u64) -> Result<(), Error> {
+        if self.lock_state().remove(name) {
+            self.remove_blobs(&[name])?;
+        }
+        Ok(())
+    }
+
+    /// Drop the least recently used entries until the store is within its size limit.  Their
+    /// blobs must then be deleted with `remove_blobs`.
+    fn evict(&self, state: &mut State) -> Vec<u64> {
+        let mut evicted = Vec::new();
+        while state.total_size > self.max_size {
+            let name = match state.lru.values().next() {
+                Some(name) => *name,
+                None => break,
+            };
+            state.remove(name);
+            evicted.push(name);
+        }
+        evicted
+    }
+
+    /// Delete the blobs of entries dropped from the state, then log their removal.
+    fn remove_blobs(&self, names: &[u64]) -> Result<(), Error> {
+        if names.is_empty() {
+            return Ok(());
+        }
+        for name in names {
+            remove_if_exists(&self.blob_path(*name))?;
+        }
+        let mut state = self.lock_state();
+        for name in names {
+            state.append(RECORD_REMOVE, *name, 0)?;
+        }
+        self.maybe_compact(&mut state)
+    }
+
+    /// Rewrite the index with a single insert record per live entry, in LRU order, once it is
+    /// mostly made up of superseded records.
+    fn maybe_compact(&self, state: &mut State) -> Result<(), Error> {
+        if state.index_records < COMPACT_MIN_RECORDS
+            || state.index_records < COMPACT_RATIO * state.entries.len() as u64
+        {
+            return Ok(());
+        }
+
+        let tmp_path = self.root.join(TMP_DIR).join(INDEX_FILE);
+        let mut log = Vec::with_capacity(state.entries.len() * RECORD_SIZE);
+        for name in state.lru.values() {
+            let size = state.entries[name].size;
+            log.extend_from_slice(&encode_record(RECORD_INSERT, *name, size));
+        }
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(&log)?;
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, self.root.join(INDEX_FILE))?;
+
+        state.index = OpenOptions::new()
+            .append(true)
+            .open(self.root.join(INDEX_FILE))?;
+        state.index_records = state.entries.len() as u64;
+        state.unsynced_inserts = 0;
+        Ok(())
+    }
+}
+
+fn blob_path(root: &Path, name: u64) -> PathBuf {
+    root.join(BLOBS_DIR)
+        .join(format!("{:02x}", name >> 56))
+        .join(format!("{:016x}", name))
+}
+
+/// Delete the files in `blobs/` that are not cached blobs.
+fn remove_orphans(root: &Path, entries: &HashMap<u64, IndexEntry>) -> Result<(), Error> {
+    for dir in fs::read_dir(root.join(BLOBS_DIR))? {
+        let dir = dir?.path();
+        if !dir.is_dir() {
+            remove_if_exists(&dir)?;
+            continue;
+        }
+        for file in fs::read_dir(&dir)? {
+            let file = file?.path();
+            let name = file
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| u64::from_str_radix(name, 16).ok());
+            match name {
+                Some(name) if entries.contains_key(&name) && file == blob_path(root, name) => {}
+                _ => remove_if_exists(&file)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+fn remove_if_exists(path: &Path) -> Result<(), Error> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_put() -> Result<(), Error> {
+        let dir = tempfile::tempdir()?;
+        let store = DiskStore::open(dir.path(), 1024 * 1024)?;
+
+        assert_eq!(store.get("foo")?, None);
+        assert!(!store.contains("foo")?);
+
+        store.put("foo", Some(123), b"foo data")?;
+        store.put("bar", None, b"bar data")?;
+        assert!(store.contains("foo")?);
+        assert_eq!(
+            store.get("foo")?,
+            Some(StoredBlob {
+                ctime: Some(123),
+                data: b"foo data".to_vec()
+            })
+        );
+        assert_eq!(
+            store.get("bar")?,
+            Some(StoredBlob {
+                ctime: None,
+                data: b"bar data".to_vec()
+            })
+        );
+
+        // Overwriting replaces the blob and its accounted size.
+        let size = store.size();
+        store.put("bar", None, b"new bar data")?;
+        assert_eq!(store.size(), size + 4);
+        assert_eq!(store.get("bar")?.unwrap().data, b"new bar data".to_vec());
+        assert_eq!(store.num_blobs(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lru_eviction() -> Result<(), Error> {
+        let dir = tempfile::tempdir()?;
+        let blob_size = encode_blob("key0", None, &[0u8; 100]).len() as u64;
+        let store = DiskStore::open(dir.path(), blob_size * 3)?;
+
+        store.put("key0", None, &[0u8; 100])?;
+        store.put("key1", None, &[1u8; 100])?;
+        store.put("key2", None, &[2u8; 100])?;
+        // Make key0 the most recently used, so key1 is evicted next.
+        assert!(store.get("key0")?.is_some());
+        assert_eq!(store.put("key3", None, &[3u8; 100])?, 1);
+
+        assert!(store.get("key0")?.is_some());
+        assert!(store.get("key1")?.is_none());
+        assert!(store.get("key2")?.is_some());
+        assert!(store.get("key3")?.is_some());
+        assert_eq!(store.size(), blob_size * 3);
+
+        // Blobs larger than the whole cache are not stored.
+        assert_eq!(store.put("huge", None, &[0u8; 1000])?, 0);
+        assert!(store.get("huge")?.is_none());
+        assert_eq!(store.num_blobs(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reopen() -> Result<(), Error> {
+        let dir = tempfile::tempdir()?;
+        let blob_size = encode_blob("key0", None, &[0u8; 100]).len() as u64;
+        {
+            let store = DiskStore::open(dir.path(), blob_size * 3)?;
+            store.put("key0", None, &[0u8; 100])?;
+            store.put("key1", None, &[1u8; 100])?;
+            store.put("key2", None, &[2u8; 100])?;
+            store.get("key0")?;
+        }
+
+        // Simulate a torn index write.
+        let mut index = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(INDEX_FILE))?;
+        index.write_all(&[RECORD_INSERT, 1, 2, 3])?;
+        drop(index);
+
+        // Reopening with a smaller limit evicts in the persisted LRU order.
+        let store = DiskStore::open(dir.path(), blob_size * 2)?;
+        assert_eq!(store.num_blobs(), 2);
+        assert!(store.get("key1")?.is_none());
+        assert_eq!(store.get("key0")?.unwrap().data, vec![0u8; 100]);
+        assert_eq!(store.get("key2")?.unwrap().data, vec![2u8; 100]);
+        assert_eq!(
+            fs::metadata(dir.path().join(INDEX_FILE))?.len() % RECORD_SIZE as u64,
+            0
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_orphans() -> Result<(), Error> {
+        let dir = tempfile::tempdir()?;
+        let (foo_path, bar_path) = {
+            let store = DiskStore::open(dir.path(), 1024 * 1024)?;
+            store.put("foo", None, b"foo data")?;
+            store.put("bar", None, b"bar data")?;
+            (
+                store.blob_path(key_hash("foo")),
+                store.blob_path(key_hash("bar")),
+            )
+        };
+
+        // Leave blob files the index does not know about, as a crash that loses the last,
+        // unsynced insert does.
+        let index_len = fs::metadata(dir.path().join(INDEX_FILE))?.len();
+        OpenOptions::new()
+            .write(true)
+            .open(dir.path().join(INDEX_FILE))?
+            .set_len(index_len - RECORD_SIZE as u64)?;
+        let stray_path = dir.path().join(BLOBS_DIR).join("00").join("stray");
+        fs::create_dir_all(stray_path.parent().unwrap())?;
+        fs::write(&stray_path, b"stray")?;
+
+        let store = DiskStore::open(dir.path(), 1024 * 1024)?;
+        assert_eq!(store.num_blobs(), 1);
+        assert!(foo_path.exists());
+        assert!(!bar_path.exists());
+        assert!(!stray_path.exists());
+        assert_eq!(store.get("foo")?.unwrap().data, b"foo data".to_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_interval() -> Result<(), Error> {
+        let dir = tempfile::tempdir()?;
+        let store = DiskStore::open(dir.path(), 1024 * 1024)?;
+        store.put("foo", None, b"foo data")?;
+        store.get("foo")?;
+        assert_eq!(store.lock_state().unsynced_inserts, 1);
+
+        store.lock_state().unsynced_inserts = SYNC_INTERVAL - 1;
+        store.put("bar", None, b"bar data")?;
+        assert_eq!(store.lock_state().unsynced_inserts, 0);
+        assert_eq!(store.get("bar")?.unwrap().data, b"bar data".to_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compaction() -> Result<(), Error> {
+        let dir = tempfile::tempdir()?;
+        let store = DiskStore::open(dir.path(), 1024 * 1024)?;
+        store.put("foo", None, b"foo data")?;
+        store.put("bar", None, b"bar data")?;
+        store.put("baz", None, b"baz data")?;
+        store.get("foo")?;
+
+        // Pretend the index has grown large, so the next access compacts it.
+        store.state.lock().unwrap().index_records = COMPACT_MIN_RECORDS;
+        store.get("bar")?;
+        assert_eq!(
+            fs::metadata(dir.path().join(INDEX_FILE))?.len(),
+            3 * RECORD_SIZE as u64
+        );
+        store.put("qux", None, b"qux data")?;
+        drop(store);
+
+        let blob_size = encode_blob("foo", None, b"foo data").len() as u64;
+        let store = DiskStore::open(dir.path(), blob_size * 3)?;
+        assert_eq!(store.num_blobs(), 3);
+        assert!(store.get("baz")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_blob() -> Result<(), Error> {
+        let dir = tempfile::tempdir()?;
+        let store = DiskStore::open(dir.path(), 1024 * 1024)?;
+        store.put("foo", None, b"foo data")?;
+
+        let path = store.blob_path(key_hash("foo"));
+        let mut blob = fs::read(&path)?;
+        let last = blob.len() - 1;
+        blob[last] ^= 0xff;
+        fs::write(&path, blob)?;
+
+        assert!(store.get("foo")?.is_none());
+        assert_eq!(store.num_blobs(), 0);
+        assert!(!path.exists());
+        Ok(())
+    }
+}
@@ -10,6 +10,11 @@
 mod cachelib_cache;
 pub use crate::cachelib_cache::{new_cachelib_blobstore, new_cachelib_blobstore_no_lease};
 
+mod disk_cache;
+pub use crate::disk_cache::{
+    new_disk_cache_blobstore, new_disk_cache_blobstore_no_lease, DiskCacheOps,
+};
+
 pub mod dummy;
 
 mod in_process_lease;
@@ -8,6 +8,7 @@
 use anyhow::{Context, Error};
 use blobstore::{Blobstore, DisabledBlob, ErrorKind};
 use blobstore_sync_queue::SqlBlobstoreSyncQueue;
+use cacheblob::new_disk_cache_blobstore;
 use chaosblob::{ChaosBlobstore, ChaosOptions};
 use fbinit::FacebookInit;
 use fileblob::Fileblob;
@@ -275,6 +276,25 @@ pub fn make_blobstore<'a>(
                 Arc::new(PackBlob::new(store, blobstore_options.pack_options.clone()))
                     as Arc<dyn Blobstore>
             }
+            LocalCache {
+                blobconfig,
+                path,
+                max_size,
+            } => {
+                let store = make_blobstore(
+                    fb,
+                    *blobconfig,
+                    mysql_options,
+                    readonly_storage,
+                    &blobstore_options,
+                    logger,
+                )
+                .await?;
+
+                new_disk_cache_blobstore(store, path, max_size.get())
+                    .context(ErrorKind::StateOpen)
+                    .map(|store| Arc::new(store) as Arc<dyn Blobstore>)?
+            }
         };
 
         let store = if readonly_storage.0 {
@@ -9,8 +9,29 @@ CREATE TABLE  `censored_contents` (
 	`id` INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
 	`content_key` VARCHAR(255) NOT NULL,
 	`task` VARCHAR(64) NOT NULL,
-	`add_timestamp` BIGINT(20) NOT NULL
+	`add_timestamp` BIGINT(20) NOT NULL,
+	`creator` VARCHAR(255) NOT NULL DEFAULT '',
+	`reason` TEXT NOT NULL DEFAULT '',
+	`expiry_timestamp` BIGINT(20) NULL,
+	`log_only` BOOLEAN NOT NULL DEFAULT FALSE
 );
 
 CREATE INDEX `content_key`
 ON `censored_contents` (`content_key`);
+
+-- Every change made to `censored_contents` is recorded here.  Rows in this
+-- table are never updated or deleted.
+CREATE TABLE  `censored_contents_audit_log` (
+	`id` INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	`content_key` VARCHAR(255) NOT NULL,
+	`action` VARCHAR(32) NOT NULL,
+	`task` VARCHAR(64) NOT NULL,
+	`creator` VARCHAR(255) NOT NULL,
+	`reason` TEXT NOT NULL,
+	`timestamp` BIGINT(20) NOT NULL,
+	`expiry_timestamp` BIGINT(20) NULL,
+	`log_only` BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX `audit_content_key`
+ON `censored_contents_audit_log` (`content_key`);
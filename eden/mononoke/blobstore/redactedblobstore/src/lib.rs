@@ -17,7 +17,6 @@ use slog::debug;
 use std::collections::HashMap;
 mod errors;
 pub use crate::errors::ErrorKind;
-use cloned::cloned;
 use std::{
     ops::Deref,
     sync::{
@@ -26,7 +25,9 @@ use std::{
     },
 };
 mod store;
-pub use crate::store::SqlRedactedContentStore;
+pub use crate::store::{
+    RedactedMetadata, RedactionAction, RedactionAuditEntry, RedactionEntry, SqlRedactedContentStore,
+};
 
 pub mod config {
     pub const GET_OPERATION: &str = "GET";
@@ -36,7 +37,7 @@ pub mod config {
 
 #[derive(Debug, Clone)]
 pub struct RedactedBlobstoreConfigInner {
-    redacted: Option<HashMap<String, String>>,
+    redacted: Option<HashMap<String, RedactedMetadata>>,
     scuba_builder: ScubaSampleBuilder,
 }
 
@@ -55,7 +56,7 @@ impl Deref for RedactedBlobstoreConfig {
 
 impl RedactedBlobstoreConfig {
     pub fn new(
-        redacted: Option<HashMap<String, String>>,
+        redacted: Option<HashMap<String, RedactedMetadata>>,
         scuba_builder: ScubaSampleBuilder,
     ) -> Self {
         Self {
@@ -117,16 +118,51 @@ impl<T: Blobstore + Clone> RedactedBlobstoreInner<T> {
     }
 
     // Checks for access to this key, then yields the blobstore if access is allowed.
-    pub fn access_blobstore(&self, key: &str) -> Result<&T, Error> {
-        match &self.config.redacted {
-            Some(redacted) => redacted.get(key).map_or(Ok(&self.blobstore), |task| {
-                Err(ErrorKind::Censored(key.to_string(), task.to_string()).into())
-            }),
-            None => Ok(&self.blobstore),
+    // Accesses to redacted keys are logged, but only denied if the redaction
+    // is not log-only. Redactions that have expired since they were loaded are
+    // ignored.
+    pub fn access_blobstore(
+        &self,
+        ctx: &CoreContext,
+        key: &str,
+        operation: &'static str,
+    ) -> Result<&T, Error> {
+        self.access_blobstore_at(ctx, key, operation, Timestamp::now())
+    }
+
+    fn access_blobstore_at(
+        &self,
+        ctx: &CoreContext,
+        key: &str,
+        operation: &'static str,
+        now: Timestamp,
+    ) -> Result<&T, Error> {
+        if let Some(metadata) = self
+            .config
+            .redacted
+            .as_ref()
+            .and_then(|redacted| redacted.get(key))
+            .filter(|metadata| !metadata.is_expired(now))
+        {
+            debug!(
+                ctx.logger(),
+                "{} operation with redacted blobstore with key {:?}", operation, key
+            );
+            self.to_scuba_redacted_blob_accessed(ctx, key, operation, metadata.log_only);
+            if !metadata.log_only {
+                return Err(ErrorKind::Censored(key.to_string(), metadata.task.clone()).into());
+            }
         }
+        Ok(&self.blobstore)
     }
 
-    pub fn to_scuba_redacted_blob_accessed(&self, ctx: &CoreContext, key: &str, operation: &str) {
+    pub fn to_scuba_redacted_blob_accessed(
+        &self,
+        ctx: &CoreContext,
+        key: &str,
+        operation: &str,
+        log_only: bool,
+    ) {
         let curr_timestamp = Timestamp::now().timestamp_nanos();
         let last_timestamp = self.timestamp.load(Ordering::Acquire);
         if config::MIN_REPORT_TIME_DIFFERENCE_NS < curr_timestamp - last_timestamp {
@@ -144,6 +180,7 @@ impl<T: Blobstore + Clone> RedactedBlobstoreInner<T> {
                     .add("time", curr_timestamp)
                     .add("operation", operation)
                     .add("key", key.to_string())
+                    .add("log_only", log_only as i32)
                     .add("session_uuid", session.to_string());
 
                 if let Some(unix_username) = ctx.user_unix_name().clone() {
@@ -163,18 +200,7 @@ impl<T: Blobstore + Clone> Blobstore for RedactedBlobstoreInner<T> {
         key: String,
     ) -> BoxFuture<'static, Result<Option<BlobstoreGetData>, Error>> {
         let get = self
-            .access_blobstore(&key)
-            .map_err({
-                cloned!(ctx, key);
-                move |err| {
-                    debug!(
-                        ctx.logger(),
-                        "Accessing redacted blobstore with key {:?}", key
-                    );
-                    self.to_scuba_redacted_blob_accessed(&ctx, &key, config::GET_OPERATION);
-                    err
-                }
-            })
+            .access_blobstore(&ctx, &key, config::GET_OPERATION)
             .map(move |blobstore| blobstore.get(ctx, key));
         async move { get?.await }.boxed()
     }
@@ -186,19 +212,7 @@ impl<T: Blobstore + Clone> Blobstore for RedactedBlobstoreInner<T> {
         value: BlobstoreBytes,
     ) -> BoxFuture<'static, Result<(), Error>> {
         let put = self
-            .access_blobstore(&key)
-            .map_err({
-                cloned!(ctx, key);
-                move |err| {
-                    debug!(
-                        ctx.logger(),
-                        "Updating redacted blobstore with key {:?}", key
-                    );
-
-                    self.to_scuba_redacted_blob_accessed(&ctx, &key, config::PUT_OPERATION);
-                    err
-                }
-            })
+            .access_blobstore(&ctx, &key, config::PUT_OPERATION)
             .map(move |blobstore| blobstore.put(ctx, key, value));
         async move { put?.await }.boxed()
     }
@@ -275,7 +289,11 @@ mod test {
 
         let inner = EagerMemblob::new();
         let redacted_pairs = hashmap! {
-            redacted_key.clone() => redacted_task.clone(),
+            redacted_key.clone() => RedactedMetadata {
+                task: redacted_task.clone(),
+                log_only: false,
+                expiry_timestamp: None,
+            },
         };
 
         let blob = RedactedBlobstore::new(
@@ -319,4 +337,103 @@ mod test {
         let res = blob.get(ctx.clone(), unredacted_key.clone()).await;
         assert!(res.is_ok(), "the key should be found and available");
     }
+
+    #[fbinit::compat_test]
+    async fn test_log_only_redacted_key(fb: FacebookInit) {
+        let redacted_key = "bar".to_string();
+        let ctx = CoreContext::test_mock(fb);
+
+        let redacted_pairs = hashmap! {
+            redacted_key.clone() => RedactedMetadata {
+                task: "bar task".to_string(),
+                log_only: true,
+                expiry_timestamp: None,
+            },
+        };
+        let blob = RedactedBlobstore::new(
+            EagerMemblob::new(),
+            RedactedBlobstoreConfig::new(Some(redacted_pairs), ScubaSampleBuilder::with_discard()),
+        );
+
+        // Log-only redactions still allow access to the content.
+        blob.put(
+            ctx.clone(),
+            redacted_key.clone(),
+            BlobstoreBytes::from_bytes("test bar"),
+        )
+        .await
+        .expect("log-only redacted key should be writable");
+        let res = blob
+            .get(ctx.clone(), redacted_key.clone())
+            .await
+            .expect("log-only redacted key should be readable");
+        assert_eq!(
+            res.map(|data| data.into_bytes()),
+            Some(BlobstoreBytes::from_bytes("test bar"))
+        );
+    }
+
+    #[fbinit::compat_test]
+    async fn test_expired_redacted_key(fb: FacebookInit) {
+        let redacted_key = "bar".to_string();
+        let expired_key = "baz".to_string();
+        let ctx = CoreContext::test_mock(fb);
+
+        // Far enough in the future for the config to be loaded before it.
+        let expiry = Timestamp::from_timestamp_secs(Timestamp::now().timestamp_seconds() + 3600);
+        let redacted_pairs = hashmap! {
+            redacted_key.clone() => RedactedMetadata {
+                task: "bar task".to_string(),
+                log_only: false,
+                expiry_timestamp: Some(expiry),
+            },
+            expired_key.clone() => RedactedMetadata {
+                task: "baz task".to_string(),
+                log_only: false,
+                expiry_timestamp: Some(Timestamp::from_timestamp_secs(1)),
+            },
+        };
+        let inner = EagerMemblob::new();
+        for key in &[&redacted_key, &expired_key] {
+            inner
+                .put(
+                    ctx.clone(),
+                    key.to_string(),
+                    BlobstoreBytes::from_bytes("test"),
+                )
+                .await
+                .expect("put to the inner blobstore failed");
+        }
+        let blob = RedactedBlobstore::new(
+            inner,
+            RedactedBlobstoreConfig::new(Some(redacted_pairs), ScubaSampleBuilder::with_discard()),
+        );
+
+        // Redactions that have already expired do not apply.
+        let res = blob.get(ctx.clone(), expired_key.clone()).await;
+        assert!(res.is_ok(), "expired redaction should not deny access");
+
+        // The redaction applies until its expiry time ...
+        let res = blob.get(ctx.clone(), redacted_key.clone()).await;
+        assert_matches!(
+            res.expect_err("the key should be redacted").downcast::<ErrorKind>(),
+            Ok(ErrorKind::Censored(_, ref task)) if task == "bar task"
+        );
+        let just_before = Timestamp::from_timestamp_nanos(expiry.timestamp_nanos() - 1);
+        assert_matches!(
+            blob.access_blobstore_at(&ctx, &redacted_key, config::GET_OPERATION, just_before)
+                .map(|_| ())
+                .expect_err("the key should be redacted")
+                .downcast::<ErrorKind>(),
+            Ok(ErrorKind::Censored(_, ref task)) if task == "bar task"
+        );
+
+        // ... and no longer afterwards, even though the config was loaded
+        // before it expired.
+        assert!(
+            blob.access_blobstore_at(&ctx, &redacted_key, config::GET_OPERATION, expiry)
+                .is_ok(),
+            "redaction should have expired"
+        );
+    }
 }
@@ -6,15 +6,16 @@
  */
 
 #![deny(warnings)]
-use anyhow::Error;
+use anyhow::{format_err, Error};
+use cloned::cloned;
 use futures_ext::{BoxFuture, FutureExt};
 use futures_old::future::Future;
 use mononoke_types::Timestamp;
-use sql::{queries, Connection};
+use sql::{queries, Connection, Transaction};
 use sql_construct::{SqlConstruct, SqlConstructFromMetadataDatabaseConfig};
 use sql_ext::SqlConnections;
-use std::collections::HashMap;
-use std::iter::FromIterator;
+use std::collections::{hash_map::Entry, HashMap};
+use std::fmt;
 
 #[derive(Clone)]
 pub struct SqlRedactedContentStore {
@@ -22,18 +23,147 @@ pub struct SqlRedactedContentStore {
     write_connection: Connection,
 }
 
+/// What is known about a redacted blob when it is accessed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RedactedMetadata {
+    /// Task tracking the redaction.
+    pub task: String,
+    /// If set, accesses to the blob are logged but the content is still
+    /// served.
+    pub log_only: bool,
+    /// After this time the redaction no longer applies.
+    pub expiry_timestamp: Option<Timestamp>,
+}
+
+impl RedactedMetadata {
+    pub fn is_expired(&self, now: Timestamp) -> bool {
+        is_expired(self.expiry_timestamp, now)
+    }
+
+    /// Whether this redaction applies rather than `other`, if a key has
+    /// both. Enforced redactions win over log-only ones. Otherwise, the
+    /// redaction that stays in force longer wins.
+    fn takes_precedence_over(&self, other: &RedactedMetadata) -> bool {
+        match (self.log_only, other.log_only) {
+            (false, true) => true,
+            (true, false) => false,
+            _ => match (&self.expiry_timestamp, &other.expiry_timestamp) {
+                (None, Some(_)) => true,
+                (Some(expiry), Some(other_expiry)) => expiry > other_expiry,
+                _ => false,
+            },
+        }
+    }
+}
+
+/// A redacted blob, as stored in the redaction table.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RedactionEntry {
+    pub content_key: String,
+    pub task: String,
+    /// Who redacted the blob.
+    pub creator: String,
+    /// Why the blob was redacted.
+    pub reason: String,
+    pub add_timestamp: Timestamp,
+    /// After this time the redaction no longer applies.
+    pub expiry_timestamp: Option<Timestamp>,
+    pub log_only: bool,
+}
+
+impl RedactionEntry {
+    pub fn is_expired(&self, now: Timestamp) -> bool {
+        is_expired(self.expiry_timestamp, now)
+    }
+}
+
+fn is_expired(expiry_timestamp: Option<Timestamp>, now: Timestamp) -> bool {
+    expiry_timestamp.map_or(false, |expiry| {
+        expiry.timestamp_nanos() <= now.timestamp_nanos()
+    })
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RedactionAction {
+    Add,
+    Remove,
+}
+
+impl RedactionAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RedactionAction::Add => "add",
+            RedactionAction::Remove => "remove",
+        }
+    }
+
+    fn from_str(action: &str) -> Result<Self, Error> {
+        match action {
+            "add" => Ok(RedactionAction::Add),
+            "remove" => Ok(RedactionAction::Remove),
+            _ => Err(format_err!("Unknown redaction action: {}", action)),
+        }
+    }
+}
+
+impl fmt::Display for RedactionAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An entry in the redaction audit log.
+///
+/// For additions, `creator`, `reason` and `timestamp` describe the
+/// redaction.  For removals, they describe the removal, and the other
+/// fields are those of the redaction that was removed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RedactionAuditEntry {
+    pub content_key: String,
+    pub action: RedactionAction,
+    pub task: String,
+    pub creator: String,
+    pub reason: String,
+    pub timestamp: Timestamp,
+    pub expiry_timestamp: Option<Timestamp>,
+    pub log_only: bool,
+}
+
 queries! {
 
     write InsertRedactedBlobs(
-        values: (content_key: String, task: String, add_timestamp: Timestamp)
+        values: (
+            content_key: String,
+            task: String,
+            add_timestamp: Timestamp,
+            creator: String,
+            reason: String,
+            expiry_timestamp: Option<Timestamp>,
+            log_only: bool,
+        )
     ) {
         none,
-        "INSERT into censored_contents(content_key, task, add_timestamp) VALUES {values}"
+        "INSERT into censored_contents(content_key, task, add_timestamp, creator, reason, expiry_timestamp, log_only) VALUES {values}"
     }
 
-    read GetAllRedactedBlobs() -> (String, String) {
-        "SELECT content_key, task
-        FROM censored_contents"
+    read GetAllRedactedBlobs(now: Timestamp) -> (String, String, bool, Option<Timestamp>) {
+        "SELECT content_key, task, log_only, expiry_timestamp
+        FROM censored_contents
+        WHERE expiry_timestamp IS NULL OR expiry_timestamp > {now}
+        ORDER BY id"
+    }
+
+    read GetRedactionEntries() -> (String, String, Timestamp, String, String, Option<Timestamp>, bool) {
+        "SELECT content_key, task, add_timestamp, creator, reason, expiry_timestamp, log_only
+        FROM censored_contents
+        ORDER BY id"
+    }
+
+    read GetRedactionEntriesForKeys(>list content_keys: String) -> (String, String, Timestamp, String, String, Option<Timestamp>, bool) {
+        "SELECT content_key, task, add_timestamp, creator, reason, expiry_timestamp, log_only
+        FROM censored_contents
+        WHERE content_key IN {content_keys}
+        ORDER BY id"
     }
 
     write DeleteRedactedBlobs(>list content_keys: String) {
@@ -41,6 +171,28 @@ queries! {
         "DELETE FROM censored_contents
          WHERE content_key IN {content_keys}"
     }
+
+    write InsertAuditLog(
+        values: (
+            content_key: String,
+            action: str,
+            task: String,
+            creator: String,
+            reason: String,
+            timestamp: Timestamp,
+            expiry_timestamp: Option<Timestamp>,
+            log_only: bool,
+        )
+    ) {
+        none,
+        "INSERT into censored_contents_audit_log(content_key, action, task, creator, reason, timestamp, expiry_timestamp, log_only) VALUES {values}"
+    }
+
+    read GetAuditLog() -> (String, String, String, String, String, Timestamp, Option<Timestamp>, bool) {
+        "SELECT content_key, action, task, creator, reason, timestamp, expiry_timestamp, log_only
+        FROM censored_contents_audit_log
+        ORDER BY id"
+    }
 }
 
 impl SqlConstruct for SqlRedactedContentStore {
@@ -58,36 +210,212 @@ impl SqlConstruct for SqlRedactedContentStore {
 
 impl SqlConstructFromMetadataDatabaseConfig for SqlRedactedContentStore {}
 
+fn redaction_entry_from_row(
+    (content_key, task, add_timestamp, creator, reason, expiry_timestamp, log_only): (
+        String,
+        String,
+        Timestamp,
+        String,
+        String,
+        Option<Timestamp>,
+        bool,
+    ),
+) -> RedactionEntry {
+    RedactionEntry {
+        content_key,
+        task,
+        creator,
+        reason,
+        add_timestamp,
+        expiry_timestamp,
+        log_only,
+    }
+}
+
 impl SqlRedactedContentStore {
-    pub fn get_all_redacted_blobs(&self) -> BoxFuture<HashMap<String, String>, Error> {
-        GetAllRedactedBlobs::query(&self.read_connection)
-            .map(HashMap::from_iter)
+    /// Fetch the redactions that are currently in force, i.e. that have
+    /// not expired yet. They keep their expiry time, as they may expire
+    /// while the result is in use.
+    ///
+    /// A key can be redacted more than once. If any of its redactions is
+    /// enforced, an enforced one applies, so adding a log-only redaction
+    /// never makes redacted content accessible.
+    pub fn get_all_redacted_blobs(&self) -> BoxFuture<HashMap<String, RedactedMetadata>, Error> {
+        GetAllRedactedBlobs::query(&self.read_connection, &Timestamp::now())
+            .map(|rows| {
+                let mut redacted: HashMap<String, RedactedMetadata> = HashMap::new();
+                for (content_key, task, log_only, expiry_timestamp) in rows {
+                    let metadata = RedactedMetadata {
+                        task,
+                        log_only,
+                        expiry_timestamp,
+                    };
+                    match redacted.entry(content_key) {
+                        Entry::Occupied(mut entry) => {
+                            if metadata.takes_precedence_over(entry.get()) {
+                                entry.insert(metadata);
+                            }
+                        }
+                        Entry::Vacant(entry) => {
+                            entry.insert(metadata);
+                        }
+                    }
+                }
+                redacted
+            })
             .boxify()
     }
 
-    pub fn insert_redacted_blobs(
-        &self,
-        content_keys: &Vec<String>,
-        task: &String,
-        add_timestamp: &Timestamp,
-    ) -> impl Future<Item = (), Error = Error> {
-        let redacted_inserts: Vec<_> = content_keys
-            .iter()
-            .map(move |key| (key, task, add_timestamp))
-            .collect();
-
-        InsertRedactedBlobs::query(&self.write_connection, &redacted_inserts[..])
-            .map_err(Error::from)
-            .map(|_| ())
+    /// Fetch every entry in the redaction table, including expired ones.
+    pub fn get_redaction_entries(&self) -> BoxFuture<Vec<RedactionEntry>, Error> {
+        GetRedactionEntries::query(&self.read_connection)
+            .map(|rows| rows.into_iter().map(redaction_entry_from_row).collect())
             .boxify()
     }
 
-    pub fn delete_redacted_blobs(&self, content_keys: &[String]) -> BoxFuture<(), Error> {
-        DeleteRedactedBlobs::query(&self.write_connection, &content_keys[..])
-            .map_err(Error::from)
-            .map(|_| ())
+    /// Fetch the audit log of all changes made to the redaction table.
+    pub fn get_audit_log(&self) -> BoxFuture<Vec<RedactionAuditEntry>, Error> {
+        GetAuditLog::query(&self.read_connection)
+            .and_then(|rows| {
+                rows.into_iter()
+                    .map(
+                        |(
+                            content_key,
+                            action,
+                            task,
+                            creator,
+                            reason,
+                            timestamp,
+                            expiry_timestamp,
+                            log_only,
+                        )| {
+                            Ok(RedactionAuditEntry {
+                                content_key,
+                                action: RedactionAction::from_str(&action)?,
+                                task,
+                                creator,
+                                reason,
+                                timestamp,
+                                expiry_timestamp,
+                                log_only,
+                            })
+                        },
+                    )
+                    .collect()
+            })
             .boxify()
     }
+
+    /// Add redactions, recording them in the audit log.
+    pub fn insert_redacted_blobs(&self, entries: Vec<RedactionEntry>) -> BoxFuture<(), Error> {
+        self.write_connection
+            .start_transaction()
+            .and_then(move |txn| {
+                let insert = {
+                    let rows: Vec<_> = entries
+                        .iter()
+                        .map(|entry| {
+                            (
+                                &entry.content_key,
+                                &entry.task,
+                                &entry.add_timestamp,
+                                &entry.creator,
+                                &entry.reason,
+                                &entry.expiry_timestamp,
+                                &entry.log_only,
+                            )
+                        })
+                        .collect();
+                    InsertRedactedBlobs::query_with_transaction(txn, &rows[..])
+                };
+                insert.map(move |(txn, _)| (txn, entries))
+            })
+            .and_then(|(txn, entries)| {
+                let audit: Vec<_> = entries
+                    .into_iter()
+                    .map(|entry| RedactionAuditEntry {
+                        content_key: entry.content_key,
+                        action: RedactionAction::Add,
+                        task: entry.task,
+                        creator: entry.creator,
+                        reason: entry.reason,
+                        timestamp: entry.add_timestamp,
+                        expiry_timestamp: entry.expiry_timestamp,
+                        log_only: entry.log_only,
+                    })
+                    .collect();
+                insert_audit_log(txn, audit)
+            })
+            .and_then(|txn| txn.commit())
+            .boxify()
+    }
+
+    /// Remove redactions, recording the removal of each entry in the audit
+    /// log.
+    pub fn delete_redacted_blobs(
+        &self,
+        content_keys: &[String],
+        creator: &str,
+        reason: &str,
+    ) -> BoxFuture<(), Error> {
+        let content_keys = content_keys.to_vec();
+        let creator = creator.to_string();
+        let reason = reason.to_string();
+        let timestamp = Timestamp::now();
+        self.write_connection
+            .start_transaction()
+            .and_then({
+                cloned!(content_keys);
+                move |txn| {
+                    GetRedactionEntriesForKeys::query_with_transaction(txn, &content_keys[..])
+                }
+            })
+            .and_then(move |(txn, rows)| {
+                DeleteRedactedBlobs::query_with_transaction(txn, &content_keys[..])
+                    .map(move |(txn, _)| (txn, rows))
+            })
+            .and_then(move |(txn, rows)| {
+                let audit: Vec<_> = rows
+                    .into_iter()
+                    .map(redaction_entry_from_row)
+                    .map(|entry| RedactionAuditEntry {
+                        content_key: entry.content_key,
+                        action: RedactionAction::Remove,
+                        task: entry.task,
+                        creator: creator.clone(),
+                        reason: reason.clone(),
+                        timestamp,
+                        expiry_timestamp: entry.expiry_timestamp,
+                        log_only: entry.log_only,
+                    })
+                    .collect();
+                insert_audit_log(txn, audit)
+            })
+            .and_then(|txn| txn.commit())
+            .boxify()
+    }
+}
+
+fn insert_audit_log(
+    txn: Transaction,
+    audit: Vec<RedactionAuditEntry>,
+) -> impl Future<Item = Transaction, Error = Error> {
+    let rows: Vec<_> = audit
+        .iter()
+        .map(|entry| {
+            (
+                &entry.content_key,
+                entry.action.as_str(),
+                &entry.task,
+                &entry.creator,
+                &entry.reason,
+                &entry.timestamp,
+                &entry.expiry_timestamp,
+                &entry.log_only,
+            )
+        })
+        .collect();
+    InsertAuditLog::query_with_transaction(txn, &rows[..]).map(|(txn, _)| txn)
 }
 
 #[cfg(test)]
@@ -95,6 +423,20 @@ mod test {
     use super::*;
     use futures::compat::Future01CompatExt;
 
+    fn entries(keys: &[String], task: &str) -> Vec<RedactionEntry> {
+        keys.iter()
+            .map(|key| RedactionEntry {
+                content_key: key.clone(),
+                task: task.to_string(),
+                creator: "creator".to_string(),
+                reason: "reason".to_string(),
+                add_timestamp: Timestamp::now(),
+                expiry_timestamp: None,
+                log_only: false,
+            })
+            .collect()
+    }
+
     #[fbinit::compat_test]
     async fn test_redacted_store(_fb: fbinit::FacebookInit) {
         let key_a = "aaaaaaaaaaaaaaaaaaaa".to_string();
@@ -109,12 +451,12 @@ mod test {
         let store = SqlRedactedContentStore::with_sqlite_in_memory().unwrap();
 
         store
-            .insert_redacted_blobs(&redacted_keys1, &task1, &Timestamp::now())
+            .insert_redacted_blobs(entries(&redacted_keys1, &task1))
             .compat()
             .await
             .expect("insert failed");
         store
-            .insert_redacted_blobs(&redacted_keys2, &task2, &Timestamp::now())
+            .insert_redacted_blobs(entries(&redacted_keys2, &task2))
             .compat()
             .await
             .expect("insert failed");
@@ -127,7 +469,7 @@ mod test {
         assert_eq!(res.len(), 4);
 
         store
-            .delete_redacted_blobs(&redacted_keys1)
+            .delete_redacted_blobs(&redacted_keys1, "remover", "removed")
             .compat()
             .await
             .expect("delete failed");
@@ -141,4 +483,165 @@ mod test {
         assert_eq!(res.contains_key(&key_d), true);
         assert_eq!(res.len(), 2);
     }
+
+    #[fbinit::compat_test]
+    async fn test_redaction_metadata_and_audit_log(_fb: fbinit::FacebookInit) {
+        let store = SqlRedactedContentStore::with_sqlite_in_memory().unwrap();
+
+        let mut expired = entries(&["expired".to_string()], "task1");
+        expired[0].expiry_timestamp = Some(Timestamp::from_timestamp_secs(1));
+        let mut log_only = entries(&["log_only".to_string()], "task2");
+        log_only[0].log_only = true;
+        log_only[0].expiry_timestamp = Some(Timestamp::from_timestamp_secs(i32::MAX as i64));
+        store
+            .insert_redacted_blobs(expired.clone())
+            .compat()
+            .await
+            .expect("insert failed");
+        store
+            .insert_redacted_blobs(log_only.clone())
+            .compat()
+            .await
+            .expect("insert failed");
+
+        // Expired redactions are not in force, but are still listed.
+        let res = store
+            .get_all_redacted_blobs()
+            .compat()
+            .await
+            .expect("select failed");
+        assert_eq!(res.len(), 1);
+        assert_eq!(
+            res.get("log_only"),
+            Some(&RedactedMetadata {
+                task: "task2".to_string(),
+                log_only: true,
+                expiry_timestamp: log_only[0].expiry_timestamp,
+            })
+        );
+        let listed = store
+            .get_redaction_entries()
+            .compat()
+            .await
+            .expect("select failed");
+        assert_eq!(listed, vec![expired[0].clone(), log_only[0].clone()]);
+        assert!(listed[0].is_expired(Timestamp::now()));
+        assert!(!listed[1].is_expired(Timestamp::now()));
+
+        store
+            .delete_redacted_blobs(&["log_only".to_string()], "remover", "removed")
+            .compat()
+            .await
+            .expect("delete failed");
+
+        let log = store.get_audit_log().compat().await.expect("select failed");
+        assert_eq!(
+            log.iter()
+                .map(|entry| (
+                    entry.content_key.as_str(),
+                    entry.action,
+                    entry.task.as_str(),
+                    entry.creator.as_str(),
+                    entry.reason.as_str(),
+                    entry.log_only,
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "expired",
+                    RedactionAction::Add,
+                    "task1",
+                    "creator",
+                    "reason",
+                    false
+                ),
+                (
+                    "log_only",
+                    RedactionAction::Add,
+                    "task2",
+                    "creator",
+                    "reason",
+                    true
+                ),
+                (
+                    "log_only",
+                    RedactionAction::Remove,
+                    "task2",
+                    "remover",
+                    "removed",
+                    true
+                ),
+            ]
+        );
+        assert_eq!(log[2].expiry_timestamp, log_only[0].expiry_timestamp);
+    }
+
+    #[fbinit::compat_test]
+    async fn test_enforced_redaction_wins(_fb: fbinit::FacebookInit) {
+        let store = SqlRedactedContentStore::with_sqlite_in_memory().unwrap();
+        let far_future = Some(Timestamp::from_timestamp_secs(i32::MAX as i64));
+
+        // "before" is redacted before it is redacted log-only, "after" the
+        // other way around.
+        let mut enforced = entries(&["before".to_string()], "enforced");
+        let mut log_only = entries(&["before".to_string(), "after".to_string()], "log_only");
+        for entry in log_only.iter_mut() {
+            entry.log_only = true;
+        }
+        store
+            .insert_redacted_blobs(enforced.clone())
+            .compat()
+            .await
+            .expect("insert failed");
+        store
+            .insert_redacted_blobs(log_only.clone())
+            .compat()
+            .await
+            .expect("insert failed");
+        enforced[0].content_key = "after".to_string();
+        store
+            .insert_redacted_blobs(enforced.clone())
+            .compat()
+            .await
+            .expect("insert failed");
+
+        // An enforced redaction that has expired does not apply.
+        let mut expired = entries(&["expired".to_string()], "expired");
+        expired[0].expiry_timestamp = Some(Timestamp::from_timestamp_secs(1));
+        let mut live = entries(&["expired".to_string()], "live");
+        live[0].log_only = true;
+        live[0].expiry_timestamp = far_future;
+        store
+            .insert_redacted_blobs(expired)
+            .compat()
+            .await
+            .expect("insert failed");
+        store
+            .insert_redacted_blobs(live)
+            .compat()
+            .await
+            .expect("insert failed");
+
+        let res = store
+            .get_all_redacted_blobs()
+            .compat()
+            .await
+            .expect("select failed");
+        let enforced = RedactedMetadata {
+            task: "enforced".to_string(),
+            log_only: false,
+            expiry_timestamp: None,
+        };
+        assert_eq!(res.get("before"), Some(&enforced));
+        assert_eq!(res.get("after"), Some(&enforced));
+        assert_eq!(
+            res.get("expired"),
+            Some(&RedactedMetadata {
+                task: "live".to_string(),
+                log_only: true,
+                expiry_timestamp: far_future,
+            })
+        );
+        assert_eq!(res.len(), 3);
+    }
 }
@@ -17,6 +17,7 @@ use context::{CoreContext, PerfCounterType};
 use derived_data::BonsaiDerived;
 use derived_data_filenodes::FilenodesOnlyPublic;
 use filenodes::FilenodeResult;
+use filestore::FetchKey;
 use futures::{
     compat::{Future01CompatExt, Stream01CompatExt},
     future::{self, TryFutureExt},
@@ -56,6 +57,7 @@ pub struct CacheWarmupRequest {
     pub target: CacheWarmupTarget,
     pub commit_limit: usize,
     pub microwave_preload: bool,
+    pub file_content_warmup: bool,
 }
 
 impl From<CacheWarmupParams> for CacheWarmupRequest {
@@ -64,12 +66,14 @@ impl From<CacheWarmupParams> for CacheWarmupRequest {
             bookmark,
             commit_limit,
             microwave_preload,
+            file_content_warmup,
         } = other;
 
         Self {
             target: CacheWarmupTarget::Bookmark(bookmark),
             commit_limit,
             microwave_preload,
+            file_content_warmup,
         }
     }
 }
@@ -152,6 +156,50 @@ async fn blobstore_and_filenodes_warmup(
     Ok(())
 }
 
+// Fetches the contents of every file. This is only worthwhile for blobstore caches that are large
+// enough to hold the whole working copy, such as a local disk cache.
+async fn file_contents_warmup(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    hg_cs_id: HgChangesetId,
+) -> Result<(), Error> {
+    let buffer_size = 100usize;
+
+    let blobstore = repo.get_blobstore();
+    let cs = hg_cs_id.load(ctx.clone(), &blobstore).await?;
+
+    cs.manifestid()
+        .list_leaf_entries(ctx.clone(), blobstore.clone())
+        .compat()
+        .map_ok(|(_path, (_file_type, filenode_id))| {
+            cloned!(ctx, blobstore);
+            async move {
+                let envelope = filenode_id.load(ctx.clone(), &blobstore).await?;
+                let key = FetchKey::Canonical(envelope.content_id());
+                if let Some(stream) = filestore::fetch(&blobstore, ctx, &key).compat().await? {
+                    stream
+                        .compat()
+                        .try_for_each(|_| future::ready(Ok(())))
+                        .await?;
+                }
+                Ok::<_, Error>(())
+            }
+        })
+        .try_buffer_unordered(buffer_size)
+        .try_fold(0u64, |i, ()| {
+            let i = i + 1;
+            if i % 10000 == 0 {
+                debug!(ctx.logger(), "file contents warmup: fetched {}th file", i);
+            }
+            future::ready(Result::<_, Error>::Ok(i))
+        })
+        .await?;
+
+    debug!(ctx.logger(), "finished file contents warmup");
+
+    Ok(())
+}
+
 fn get_linknode_opt(
     repo: &BlobRepo,
     ctx: CoreContext,
@@ -188,6 +236,7 @@ async fn do_cache_warmup(
     repo: &BlobRepo,
     target: CacheWarmupTarget,
     commit_limit: usize,
+    file_content_warmup: bool,
 ) -> Result<(), Error> {
     let ctx = ctx.clone_and_reset();
 
@@ -223,10 +272,25 @@ async fn do_cache_warmup(
         }
     });
 
-    let (stats, res) = future::try_join(blobstore_warmup, cs_warmup).timed().await;
-    let (blobstore_warmup, cs_warmup) = res?;
+    let file_warmup = task::spawn({
+        cloned!(ctx, repo);
+        async move {
+            if file_content_warmup {
+                file_contents_warmup(&ctx, &repo, hg_cs_id)
+                    .await
+                    .context("While warming up file contents")?;
+            }
+            Ok::<_, Error>(())
+        }
+    });
+
+    let (stats, res) = future::try_join3(blobstore_warmup, cs_warmup, file_warmup)
+        .timed()
+        .await;
+    let (blobstore_warmup, cs_warmup, file_warmup) = res?;
     blobstore_warmup?;
     cs_warmup?;
+    file_warmup?;
 
     info!(ctx.logger(), "finished initial warmup");
 
@@ -262,9 +326,15 @@ pub async fn cache_warmup<T: Into<CacheWarmupRequest>>(
 
         microwave_preload(ctx, repo, &req).await;
 
-        do_cache_warmup(ctx, repo, req.target, req.commit_limit)
-            .await
-            .with_context(|| format!("while warming up repo {}", repo.get_repoid()))?;
+        do_cache_warmup(
+            ctx,
+            repo,
+            req.target,
+            req.commit_limit,
+            req.file_content_warmup,
+        )
+        .await
+        .with_context(|| format!("while warming up repo {}", repo.get_repoid()))?;
     }
 
     Ok(())
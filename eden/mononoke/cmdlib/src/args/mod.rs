@@ -70,6 +70,7 @@ const TUNABLES_CONFIG: &str = "tunables-config";
 const DISABLE_TUNABLES: &str = "disable-tunables";
 
 const DEFAULT_TUNABLES_PATH: &str = "signed-configerator:scm/mononoke/tunables/default";
+const DEFAULT_SCRIBE_ROTATE_SIZE: u64 = 64 * 1024 * 1024;
 
 const READ_QPS_ARG: &str = "blobstore-read-qps";
 const WRITE_QPS_ARG: &str = "blobstore-write-qps";
@@ -836,9 +837,49 @@ pub fn add_scribe_logging_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
             .takes_value(true)
             .help("Filesystem directory where to log all scribe writes"),
     )
+    .arg(
+        Arg::with_name("scribe-rotating-directory")
+            .long("scribe-rotating-directory")
+            .takes_value(true)
+            .conflicts_with_all(&["scribe-logging-directory", "scribe-socket"])
+            .help("Filesystem directory where to log all scribe writes to rotating files"),
+    )
+    .arg(
+        Arg::with_name("scribe-rotate-size")
+            .long("scribe-rotate-size")
+            .takes_value(true)
+            .requires("scribe-rotating-directory")
+            .help("Size in bytes at which scribe log files are rotated"),
+    )
+    .arg(
+        Arg::with_name("scribe-keep-files")
+            .long("scribe-keep-files")
+            .takes_value(true)
+            .requires("scribe-rotating-directory")
+            .help("Number of rotated scribe log files to keep for each category"),
+    )
+    .arg(
+        Arg::with_name("scribe-socket")
+            .long("scribe-socket")
+            .takes_value(true)
+            .conflicts_with("scribe-logging-directory")
+            .help("Unix socket where to send all scribe writes"),
+    )
 }
 
 pub fn get_scribe<'a>(fb: FacebookInit, matches: &ArgMatches<'a>) -> Result<Scribe> {
+    if let Some(dir) = matches.value_of("scribe-rotating-directory") {
+        let rotate_size = get_u64(matches, "scribe-rotate-size", DEFAULT_SCRIBE_ROTATE_SIZE);
+        let keep_files = get_usize_opt(matches, "scribe-keep-files");
+        return Ok(Scribe::new_to_rotating_files(
+            PathBuf::from(dir),
+            rotate_size,
+            keep_files,
+        ));
+    }
+    if let Some(socket) = matches.value_of("scribe-socket") {
+        return Ok(Scribe::new_to_socket(PathBuf::from(socket)));
+    }
     match matches.value_of("scribe-logging-directory") {
         Some(dir) => Ok(Scribe::new_to_file(PathBuf::from(dir))),
         None => Ok(Scribe::new(fb)),
@@ -18,6 +18,7 @@ use cloned::cloned;
 use cmdlib::{args, helpers};
 use context::CoreContext;
 use cross_repo_sync::{
+    divergence::{self, Divergence},
     validation::{self, BookmarkDiff},
     CommitSyncRepos, CommitSyncer,
 };
@@ -26,7 +27,9 @@ use futures::{
     future::{FutureExt as PreviewFutureExt, TryFutureExt},
 };
 use metaconfig_types::{CommitSyncConfig, RepoConfig};
+use mononoke_types::ChangesetId;
 use movers::{get_large_to_small_mover, get_small_to_large_mover};
+use serde_json::{json, to_string_pretty, Value};
 use slog::{info, warn, Logger};
 use synced_commit_mapping::{SqlSyncedCommitMapping, SyncedCommitMapping};
 
@@ -36,8 +39,12 @@ pub const CROSSREPO: &str = "crossrepo";
 const MAP_SUBCOMMAND: &str = "map";
 const VERIFY_WC_SUBCOMMAND: &str = "verify-wc";
 const VERIFY_BOOKMARKS_SUBCOMMAND: &str = "verify-bookmarks";
+const VERIFY_HISTORY_SUBCOMMAND: &str = "verify-history";
 const HASH_ARG: &str = "HASH";
 const LARGE_REPO_HASH_ARG: &str = "LARGE_REPO_HASH";
+const START_LARGE_REPO_HASH_ARG: &str = "START_LARGE_REPO_HASH";
+const END_LARGE_REPO_HASH_ARG: &str = "END_LARGE_REPO_HASH";
+const CONCURRENCY_ARG: &str = "concurrency";
 const UPDATE_LARGE_REPO_BOOKMARKS: &str = "update-large-repo-bookmarks";
 
 pub async fn subcommand_crossrepo<'a>(
@@ -121,6 +128,37 @@ pub async fn subcommand_crossrepo<'a>(
                 })
                 .boxify()
         }
+        (VERIFY_HISTORY_SUBCOMMAND, Some(sub_sub_m)) => {
+            let (_, source_repo_config) = args::get_config_by_repoid(fb, matches, source_repo_id)?;
+            let start_hash = sub_sub_m
+                .value_of(START_LARGE_REPO_HASH_ARG)
+                .unwrap()
+                .to_owned();
+            let end_hash = sub_sub_m
+                .value_of(END_LARGE_REPO_HASH_ARG)
+                .unwrap()
+                .to_owned();
+            let concurrency = args::get_usize(sub_sub_m, CONCURRENCY_ARG, 100);
+
+            source_repo
+                .join3(target_repo, mapping)
+                .from_err()
+                .and_then(move |(source_repo, target_repo, mapping)| {
+                    get_large_to_small_commit_sync_repos(
+                        source_repo,
+                        target_repo,
+                        &source_repo_config,
+                    )
+                    .map(move |commit_sync_repos| CommitSyncer::new(mapping, commit_sync_repos))
+                    .map_err(SubcommandError::from)
+                })
+                .and_then(move |commit_syncer| {
+                    subcommand_verify_history(ctx, commit_syncer, start_hash, end_hash, concurrency)
+                        .boxed()
+                        .compat()
+                })
+                .boxify()
+        }
         _ => Err(SubcommandError::InvalidArgs).into_future().boxify(),
     }
     .compat()
@@ -248,6 +286,93 @@ async fn subcommand_verify_bookmarks(
     }
 }
 
+async fn subcommand_verify_history(
+    ctx: CoreContext,
+    commit_syncer: CommitSyncer<SqlSyncedCommitMapping>,
+    start_hash: String,
+    end_hash: String,
+    concurrency: usize,
+) -> Result<(), SubcommandError> {
+    let large_repo = commit_syncer.get_large_repo();
+    let start_cs_id = helpers::csid_resolve(ctx.clone(), large_repo.clone(), start_hash)
+        .compat()
+        .await?;
+    let end_cs_id = helpers::csid_resolve(ctx.clone(), large_repo.clone(), end_hash)
+        .compat()
+        .await?;
+
+    let report = divergence::find_divergences_in_range(
+        &ctx,
+        &commit_syncer,
+        start_cs_id,
+        end_cs_id,
+        concurrency,
+    )
+    .await?;
+
+    let divergences: Vec<_> = report.divergences.iter().map(divergence_to_json).collect();
+    let output = json!({
+        "start": start_cs_id.to_string(),
+        "end": end_cs_id.to_string(),
+        "checked": report.checked,
+        "divergences": divergences,
+    });
+    println!("{}", to_string_pretty(&output).map_err(Error::from)?);
+
+    if report.divergences.is_empty() {
+        info!(ctx.logger(), "all is well!");
+        Ok(())
+    } else {
+        Err(format_err!(
+            "found {} divergences in {} commits",
+            report.divergences.len(),
+            report.checked
+        )
+        .into())
+    }
+}
+
+fn divergence_to_json(divergence: &Divergence) -> Value {
+    use Divergence::*;
+
+    let to_strings = |cs_ids: &Vec<ChangesetId>| -> Vec<String> {
+        cs_ids.iter().map(|cs_id| cs_id.to_string()).collect()
+    };
+    match divergence {
+        MissingMapping { source_cs_id } => json!({
+            "kind": "missing_mapping",
+            "source_cs_id": source_cs_id.to_string(),
+        }),
+        ContentMismatch {
+            source_cs_id,
+            target_cs_id,
+            count,
+            paths,
+        } => {
+            let paths: Vec<_> = paths.iter().map(|path| path.to_string()).collect();
+            json!({
+                "kind": "content_mismatch",
+                "source_cs_id": source_cs_id.to_string(),
+                "target_cs_id": target_cs_id.to_string(),
+                "count": count,
+                "paths": paths,
+            })
+        }
+        WrongParents {
+            source_cs_id,
+            target_cs_id,
+            expected,
+            actual,
+        } => json!({
+            "kind": "wrong_parents",
+            "source_cs_id": source_cs_id.to_string(),
+            "target_cs_id": target_cs_id.to_string(),
+            "expected": to_strings(expected),
+            "actual": to_strings(actual),
+        }),
+    }
+}
+
 async fn update_large_repo_bookmarks(
     ctx: CoreContext,
     diff: &Vec<BookmarkDiff>,
@@ -367,10 +492,34 @@ pub fn build_subcommand<'a, 'b>() -> App<'a, 'b> {
             .help("update any inconsistencies between bookmarks (except for the common bookmarks between large and small repo e.g. 'master')"),
     );
 
+    let verify_history_subcommand = SubCommand::with_name(VERIFY_HISTORY_SUBCOMMAND)
+        .about(
+            "verify that a range of large repo commits matches the small repo commits they are \
+             synced to, and print a JSON report of the divergences",
+        )
+        .arg(
+            Arg::with_name(START_LARGE_REPO_HASH_ARG)
+                .required(true)
+                .help("first large repo commit to verify"),
+        )
+        .arg(
+            Arg::with_name(END_LARGE_REPO_HASH_ARG)
+                .required(true)
+                .help("last large repo commit to verify, a descendant of the first one"),
+        )
+        .arg(
+            Arg::with_name(CONCURRENCY_ARG)
+                .long(CONCURRENCY_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("how many commits to verify concurrently (default: 100)"),
+        );
+
     SubCommand::with_name(CROSSREPO)
         .subcommand(map_subcommand)
         .subcommand(verify_wc_subcommand)
         .subcommand(verify_bookmarks_subcommand)
+        .subcommand(verify_history_subcommand)
 }
 
 fn get_large_to_small_commit_sync_repos(
@@ -0,0 +1,173 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{format_err, Error};
+use blobrepo::BlobRepo;
+use blobrepo_hg::BlobRepoHg;
+use bookmarks::Bookmark;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use cmdlib::{args, helpers::csid_resolve};
+use context::CoreContext;
+use fbinit::FacebookInit;
+use futures::{compat::Future01CompatExt, compat::Stream01CompatExt, TryStreamExt};
+use futures_old::Stream;
+use getbundle_response::{create_full_changegroup_part, create_phases_part, SessionLfsParams};
+use mercurial_bundles::{create_bundle_stream, parts};
+use mercurial_types::{HgChangesetId, NULL_CSID};
+use reachabilityindex::LeastCommonAncestorsHint;
+use skiplist::fetch_skiplist_index;
+use slog::{info, Logger};
+use std::fs::File;
+use std::io::Write;
+use std::sync::Arc;
+
+use crate::error::SubcommandError;
+
+pub const EXPORT_BUNDLE: &str = "export-bundle";
+const ARG_HEAD: &str = "head";
+const ARG_COMMON: &str = "common";
+const ARG_PHASES: &str = "phases";
+const ARG_BOOKMARKS: &str = "bookmarks";
+const ARG_LFS_THRESHOLD: &str = "lfs-threshold";
+const ARG_OUTPUT: &str = "OUTPUT";
+
+pub fn build_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(EXPORT_BUNDLE)
+        .about(
+            "write a bundle2 with the commits between the given heads and common commits, \
+             including all their manifests and files, to a file that can be applied with \
+             `hg unbundle`",
+        )
+        .arg(
+            Arg::with_name(ARG_HEAD)
+                .long(ARG_HEAD)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(true)
+                .help("{hg|bonsai} changeset id or bookmark name of a head to include"),
+        )
+        .arg(
+            Arg::with_name(ARG_COMMON)
+                .long(ARG_COMMON)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "{hg|bonsai} changeset id or bookmark name of a commit the client already \
+                     has. If not given, all ancestors of the heads are included",
+                ),
+        )
+        .arg(
+            Arg::with_name(ARG_PHASES)
+                .long(ARG_PHASES)
+                .help("include a phases part"),
+        )
+        .arg(
+            Arg::with_name(ARG_BOOKMARKS)
+                .long(ARG_BOOKMARKS)
+                .help("include a listkeys part with the bookmarks that are sent on pull"),
+        )
+        .arg(
+            Arg::with_name(ARG_LFS_THRESHOLD)
+                .long(ARG_LFS_THRESHOLD)
+                .takes_value(true)
+                .help(
+                    "send files larger than this many bytes as LFS pointers. Applying the \
+                     bundle then requires the lfs extension",
+                ),
+        )
+        .arg(
+            Arg::with_name(ARG_OUTPUT)
+                .help("file to write the bundle to")
+                .required(true)
+                .index(1),
+        )
+}
+
+pub async fn subcommand_export_bundle<'a>(
+    fb: FacebookInit,
+    logger: Logger,
+    matches: &'a ArgMatches<'_>,
+    sub_m: &'a ArgMatches<'_>,
+) -> Result<(), SubcommandError> {
+    args::init_cachelib(fb, &matches, None);
+    let ctx = CoreContext::new_with_logger(fb, logger.clone());
+    let repo = args::open_repo(fb, &logger, &matches).compat().await?;
+    let (_, repo_config) = args::get_config(fb, &matches)?;
+
+    let heads = resolve_hg_changesets(&ctx, &repo, sub_m.values_of(ARG_HEAD)).await?;
+    let mut common = resolve_hg_changesets(&ctx, &repo, sub_m.values_of(ARG_COMMON)).await?;
+    if common.is_empty() {
+        common.push(NULL_CSID);
+    }
+
+    let lca_hint: Arc<dyn LeastCommonAncestorsHint> = fetch_skiplist_index(
+        &ctx,
+        &repo_config.skiplist_index_blobstore_key,
+        &repo.get_blobstore().boxed(),
+    )
+    .await?;
+
+    let lfs_params = SessionLfsParams {
+        threshold: args::get_u64_opt(&sub_m, ARG_LFS_THRESHOLD),
+    };
+
+    let mut bundle2_parts = vec![
+        create_full_changegroup_part(&ctx, &repo, common, heads.clone(), lca_hint, &lfs_params)
+            .await?,
+    ];
+
+    // Phases part has to be after the changegroup part.
+    if sub_m.is_present(ARG_PHASES) {
+        bundle2_parts.push(create_phases_part(&ctx, &repo, &heads).await?);
+    }
+
+    if sub_m.is_present(ARG_BOOKMARKS) {
+        let bookmarks = repo
+            .get_pull_default_bookmarks_maybe_stale(ctx.clone())
+            .map(|(bookmark, cs_id): (Bookmark, HgChangesetId)| {
+                let hash: Vec<u8> = cs_id.into_nodehash().to_hex().into();
+                (bookmark.into_name().into_byte_vec(), hash)
+            });
+        bundle2_parts.push(parts::listkey_part("bookmarks", bookmarks)?);
+    }
+
+    let output_path = sub_m
+        .value_of(ARG_OUTPUT)
+        .ok_or_else(|| format_err!("{} argument expected", ARG_OUTPUT))?;
+    let mut output = File::create(output_path).map_err(Error::from)?;
+    let mut bundle = create_bundle_stream(bundle2_parts, None).compat();
+    let mut size = 0;
+    while let Some(chunk) = bundle.try_next().await? {
+        output.write_all(&chunk).map_err(Error::from)?;
+        size += chunk.len();
+    }
+    output.flush().map_err(Error::from)?;
+
+    info!(logger, "Wrote {} bytes to {}", size, output_path);
+    Ok(())
+}
+
+async fn resolve_hg_changesets<'a>(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    hashes_or_bookmarks: Option<impl Iterator<Item = &'a str>>,
+) -> Result<Vec<HgChangesetId>, Error> {
+    let mut hg_cs_ids = Vec::new();
+    for hash_or_bookmark in hashes_or_bookmarks.into_iter().flatten() {
+        let cs_id = csid_resolve(ctx.clone(), repo.clone(), hash_or_bookmark)
+            .compat()
+            .await?;
+        let hg_cs_id = repo
+            .get_hg_from_bonsai_changeset(ctx.clone(), cs_id)
+            .compat()
+            .await?;
+        hg_cs_ids.push(hg_cs_id);
+    }
+    Ok(hg_cs_ids)
+}
@@ -39,11 +39,13 @@ mod content_fetch;
 mod crossrepo;
 mod derived_data;
 mod error;
+mod export_bundle;
 mod filenodes;
 mod filestore;
 mod hash_convert;
 mod hg_changeset;
 mod hg_sync;
+mod megarepo;
 mod mutable_counters;
 mod phases;
 mod redaction;
@@ -77,6 +79,8 @@ fn setup_app<'a, 'b>() -> App<'a, 'b> {
         .subcommand(subcommand_blame::build_subcommand())
         .subcommand(subcommand_deleted_manifest::build_subcommand())
         .subcommand(derived_data::build_subcommand())
+        .subcommand(megarepo::build_subcommand())
+        .subcommand(export_bundle::build_subcommand())
 }
 
 #[fbinit::main]
@@ -153,6 +157,12 @@ fn main(fb: FacebookInit) -> ExitCode {
             (derived_data::DERIVED_DATA, Some(sub_m)) => {
                 derived_data::subcommand_derived_data(fb, logger, &matches, sub_m).await
             }
+            (megarepo::MEGAREPO, Some(sub_m)) => {
+                megarepo::subcommand_megarepo(fb, logger, &matches, sub_m).await
+            }
+            (export_bundle::EXPORT_BUNDLE, Some(sub_m)) => {
+                export_bundle::subcommand_export_bundle(fb, logger, &matches, sub_m).await
+            }
             _ => Err(SubcommandError::InvalidArgs),
         }
     });
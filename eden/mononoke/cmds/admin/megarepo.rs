@@ -0,0 +1,403 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{anyhow, format_err, Error};
+use blobrepo::BlobRepo;
+use blobstore::Loadable;
+use bookmarks::BookmarkName;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use cmdlib::{args, helpers::csid_resolve};
+use context::CoreContext;
+use fbinit::FacebookInit;
+use futures::{compat::Future01CompatExt, future::try_join_all};
+use megarepolib::{
+    common::ChangesetArgs, create_gradual_delete, create_merge_commit, create_pre_merge_delete,
+    gradual_merge, pushrebase_commits, validate_merge,
+};
+use mercurial_types::MPath;
+use metaconfig_types::RepoConfig;
+use mononoke_types::{ChangesetId, DateTime};
+use slog::{info, Logger};
+use std::fs;
+use std::num::NonZeroU64;
+use unbundle::get_pushrebase_hooks;
+
+use crate::error::SubcommandError;
+
+pub const MEGAREPO: &str = "megarepo";
+const SUBCOMMAND_PRE_MERGE_DELETE: &str = "pre-merge-delete";
+const SUBCOMMAND_GRADUAL_DELETE: &str = "gradual-delete";
+const SUBCOMMAND_MERGE: &str = "merge";
+const SUBCOMMAND_GRADUAL_MERGE: &str = "gradual-merge";
+
+const ARG_COMMIT: &str = "commit";
+const ARG_PARENTS: &str = "parents";
+const ARG_CHUNK_SIZE: &str = "chunk-size";
+const ARG_PATHS_FILE: &str = "paths-file";
+const ARG_PUSHREBASE_ONTO: &str = "pushrebase-onto";
+const ARG_BOOKMARK: &str = "bookmark";
+const ARG_PRE_DELETION_COMMIT: &str = "pre-deletion-commit";
+const ARG_LAST_DELETION_COMMIT: &str = "last-deletion-commit";
+const ARG_COMMIT_AUTHOR: &str = "commit-author";
+const ARG_COMMIT_MESSAGE: &str = "commit-message";
+const ARG_COMMIT_DATE: &str = "commit-date-rfc3339";
+const ARG_MARK_PUBLIC: &str = "mark-public";
+
+fn add_changeset_args<'a, 'b>(subcommand: App<'a, 'b>) -> App<'a, 'b> {
+    subcommand
+        .arg(
+            Arg::with_name(ARG_COMMIT_AUTHOR)
+                .long(ARG_COMMIT_AUTHOR)
+                .help("author of the created commits")
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name(ARG_COMMIT_MESSAGE)
+                .long(ARG_COMMIT_MESSAGE)
+                .help("message of the created commits")
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name(ARG_COMMIT_DATE)
+                .long(ARG_COMMIT_DATE)
+                .help("date of the created commits, defaults to now")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_MARK_PUBLIC)
+                .long(ARG_MARK_PUBLIC)
+                .help("mark the created commits as public"),
+        )
+}
+
+fn chunk_size_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(ARG_CHUNK_SIZE)
+        .long(ARG_CHUNK_SIZE)
+        .help("maximum number of files to delete in each commit")
+        .takes_value(true)
+        .required(true)
+}
+
+fn pushrebase_onto_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(ARG_PUSHREBASE_ONTO)
+        .long(ARG_PUSHREBASE_ONTO)
+        .help("pushrebase the created commits onto this bookmark")
+        .takes_value(true)
+}
+
+pub fn build_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(MEGAREPO)
+        .about("create commits that merge repos into a megarepo")
+        .subcommand(add_changeset_args(
+            SubCommand::with_name(SUBCOMMAND_PRE_MERGE_DELETE)
+                .about(
+                    "create a stack of commits that delete all files of a commit in chunks, to \
+                     be used by gradual-merge",
+                )
+                .arg(
+                    Arg::with_name(ARG_COMMIT)
+                        .help("(hg|bonsai) commit hash or bookmark to delete the files of")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(chunk_size_arg()),
+        ))
+        .subcommand(add_changeset_args(
+            SubCommand::with_name(SUBCOMMAND_GRADUAL_DELETE)
+                .about("create a stack of commits that delete a list of paths in chunks")
+                .arg(
+                    Arg::with_name(ARG_COMMIT)
+                        .help("(hg|bonsai) commit hash or bookmark to delete the paths from")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name(ARG_PATHS_FILE)
+                        .long(ARG_PATHS_FILE)
+                        .help("file with a newline-separated list of files or directories")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(chunk_size_arg())
+                .arg(pushrebase_onto_arg()),
+        ))
+        .subcommand(add_changeset_args(
+            SubCommand::with_name(SUBCOMMAND_MERGE)
+                .about("create a merge commit of commits that don't conflict")
+                .arg(
+                    Arg::with_name(ARG_PARENTS)
+                        .help("(hg|bonsai) commit hashes or bookmarks to merge")
+                        .takes_value(true)
+                        .multiple(true)
+                        .min_values(2)
+                        .required(true),
+                )
+                .arg(pushrebase_onto_arg()),
+        ))
+        .subcommand(add_changeset_args(
+            SubCommand::with_name(SUBCOMMAND_GRADUAL_MERGE)
+                .about(
+                    "merge a commit into a bookmark one chunk at a time, using the commits \
+                     created by pre-merge-delete",
+                )
+                .arg(
+                    Arg::with_name(ARG_PRE_DELETION_COMMIT)
+                        .long(ARG_PRE_DELETION_COMMIT)
+                        .help("the commit pre-merge-delete was run on")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name(ARG_LAST_DELETION_COMMIT)
+                        .long(ARG_LAST_DELETION_COMMIT)
+                        .help("the last commit created by pre-merge-delete")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name(ARG_BOOKMARK)
+                        .long(ARG_BOOKMARK)
+                        .help("bookmark to merge into")
+                        .takes_value(true)
+                        .required(true),
+                ),
+        ))
+}
+
+fn get_changeset_args(sub_m: &ArgMatches<'_>) -> Result<ChangesetArgs, Error> {
+    let author = sub_m
+        .value_of(ARG_COMMIT_AUTHOR)
+        .ok_or_else(|| format_err!("{} is not specified", ARG_COMMIT_AUTHOR))?;
+    let message = sub_m
+        .value_of(ARG_COMMIT_MESSAGE)
+        .ok_or_else(|| format_err!("{} is not specified", ARG_COMMIT_MESSAGE))?;
+    let datetime = match sub_m.value_of(ARG_COMMIT_DATE) {
+        Some(date) => DateTime::from_rfc3339(date)?,
+        None => DateTime::now(),
+    };
+
+    Ok(ChangesetArgs {
+        author: author.to_string(),
+        message: message.to_string(),
+        datetime,
+        bookmark: None,
+        mark_public: sub_m.is_present(ARG_MARK_PUBLIC),
+    })
+}
+
+/// Changeset args for the commits of a stack, numbering the commit messages.
+fn stack_changeset_args(changeset_args: ChangesetArgs) -> impl Fn(usize) -> ChangesetArgs {
+    move |num| ChangesetArgs {
+        message: format!("{} ({})", changeset_args.message, num),
+        ..changeset_args.clone()
+    }
+}
+
+fn get_chunk_size(sub_m: &ArgMatches<'_>) -> Result<NonZeroU64, Error> {
+    let chunk_size = sub_m
+        .value_of(ARG_CHUNK_SIZE)
+        .ok_or_else(|| format_err!("{} is not specified", ARG_CHUNK_SIZE))?
+        .parse::<u64>()?;
+    NonZeroU64::new(chunk_size).ok_or_else(|| format_err!("{} must be positive", ARG_CHUNK_SIZE))
+}
+
+fn read_paths(paths_file: &str) -> Result<Vec<MPath>, Error> {
+    fs::read_to_string(paths_file)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(MPath::new)
+        .collect()
+}
+
+async fn resolve(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    hash_or_bookmark: &str,
+) -> Result<ChangesetId, Error> {
+    csid_resolve(ctx.clone(), repo.clone(), hash_or_bookmark)
+        .compat()
+        .await
+}
+
+pub async fn subcommand_megarepo<'a>(
+    fb: FacebookInit,
+    logger: Logger,
+    matches: &'a ArgMatches<'_>,
+    sub_m: &'a ArgMatches<'_>,
+) -> Result<(), SubcommandError> {
+    args::init_cachelib(fb, &matches, None);
+    let ctx = CoreContext::new_with_logger(fb, logger.clone());
+    let repo = args::open_repo(fb, &logger, &matches).compat().await?;
+    let (_, repo_config) = args::get_config(fb, &matches)?;
+
+    match sub_m.subcommand() {
+        (SUBCOMMAND_PRE_MERGE_DELETE, Some(sub_m)) => {
+            let changeset_args = get_changeset_args(sub_m)?;
+            let chunk_size = get_chunk_size(sub_m)?;
+            let commit = resolve(&ctx, &repo, sub_m.value_of(ARG_COMMIT).unwrap()).await?;
+
+            let stack = create_pre_merge_delete(
+                &ctx,
+                &repo,
+                commit,
+                chunk_size,
+                stack_changeset_args(changeset_args),
+            )
+            .await?;
+            for bcs_id in stack {
+                println!("{}", bcs_id);
+            }
+            Ok(())
+        }
+        (SUBCOMMAND_GRADUAL_DELETE, Some(sub_m)) => {
+            let changeset_args = get_changeset_args(sub_m)?;
+            let chunk_size = get_chunk_size(sub_m)?;
+            let paths = read_paths(sub_m.value_of(ARG_PATHS_FILE).unwrap())?;
+            let commit = resolve(&ctx, &repo, sub_m.value_of(ARG_COMMIT).unwrap()).await?;
+
+            let stack = create_gradual_delete(
+                &ctx,
+                &repo,
+                commit,
+                paths,
+                chunk_size,
+                stack_changeset_args(changeset_args),
+            )
+            .await?;
+            for bcs_id in &stack {
+                println!("{}", bcs_id);
+            }
+
+            if let Some(bookmark) = sub_m.value_of(ARG_PUSHREBASE_ONTO) {
+                let bookmark = BookmarkName::new(bookmark)?;
+                let head = pushrebase(&ctx, &repo, &repo_config, &stack, &bookmark).await?;
+                info!(ctx.logger(), "{} now points to {}", bookmark, head);
+            }
+            Ok(())
+        }
+        (SUBCOMMAND_MERGE, Some(sub_m)) => {
+            let changeset_args = get_changeset_args(sub_m)?;
+            let parents = try_join_all(
+                sub_m
+                    .values_of(ARG_PARENTS)
+                    .unwrap()
+                    .map(|parent| resolve(&ctx, &repo, parent)),
+            )
+            .await?;
+
+            let merge = create_merge_commit(&ctx, &repo, parents.clone(), changeset_args).await?;
+            validate_merge(&ctx, &repo, merge, &parents).await?;
+            println!("{}", merge);
+
+            if let Some(bookmark) = sub_m.value_of(ARG_PUSHREBASE_ONTO) {
+                let bookmark = BookmarkName::new(bookmark)?;
+                let head = pushrebase(&ctx, &repo, &repo_config, &[merge], &bookmark).await?;
+                let sides: Vec<_> = head
+                    .load(ctx.clone(), repo.blobstore())
+                    .await?
+                    .parents()
+                    .collect();
+                validate_merge(&ctx, &repo, head, &sides).await?;
+                info!(ctx.logger(), "{} now points to {}", bookmark, head);
+            }
+            Ok(())
+        }
+        (SUBCOMMAND_GRADUAL_MERGE, Some(sub_m)) => {
+            let changeset_args = get_changeset_args(sub_m)?;
+            let bookmark = BookmarkName::new(sub_m.value_of(ARG_BOOKMARK).unwrap())?;
+            let pre_deletion_commit = resolve(
+                &ctx,
+                &repo,
+                sub_m.value_of(ARG_PRE_DELETION_COMMIT).unwrap(),
+            )
+            .await?;
+            let last_deletion_commit = resolve(
+                &ctx,
+                &repo,
+                sub_m.value_of(ARG_LAST_DELETION_COMMIT).unwrap(),
+            )
+            .await?;
+
+            let pre_merge_deletes =
+                find_pre_merge_deletes(&ctx, &repo, pre_deletion_commit, last_deletion_commit)
+                    .await?;
+            info!(
+                ctx.logger(),
+                "Merging {} in {} steps",
+                pre_deletion_commit,
+                pre_merge_deletes.len() + 1
+            );
+
+            let pushrebase_hooks = get_pushrebase_hooks(&repo, &repo_config.pushrebase);
+            let last_merge = gradual_merge(
+                &ctx,
+                &repo,
+                pre_deletion_commit,
+                &pre_merge_deletes,
+                &bookmark,
+                &repo_config.pushrebase.flags,
+                &pushrebase_hooks,
+                stack_changeset_args(changeset_args),
+            )
+            .await?;
+            println!("{}", last_merge);
+            Ok(())
+        }
+        _ => Err(SubcommandError::InvalidArgs),
+    }
+}
+
+async fn pushrebase(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    repo_config: &RepoConfig,
+    bcs_ids: &[ChangesetId],
+    bookmark: &BookmarkName,
+) -> Result<ChangesetId, Error> {
+    let pushrebase_hooks = get_pushrebase_hooks(&repo, &repo_config.pushrebase);
+    pushrebase_commits(
+        ctx,
+        repo,
+        bcs_ids,
+        bookmark,
+        &repo_config.pushrebase.flags,
+        &pushrebase_hooks,
+    )
+    .await
+}
+
+/// Find the stack of pre-merge delete commits between `pre_deletion_commit`
+/// (exclusive) and `last_deletion_commit` (inclusive), in creation order.
+async fn find_pre_merge_deletes(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    pre_deletion_commit: ChangesetId,
+    last_deletion_commit: ChangesetId,
+) -> Result<Vec<ChangesetId>, Error> {
+    let mut stack = vec![];
+    let mut current = last_deletion_commit;
+    while current != pre_deletion_commit {
+        stack.push(current);
+        let bcs = current.load(ctx.clone(), repo.blobstore()).await?;
+        let mut parents = bcs.parents();
+        current = match (parents.next(), parents.next()) {
+            (Some(parent), None) => parent,
+            _ => {
+                return Err(anyhow!(
+                    "{} is not a descendant of {} through pre-merge delete commits",
+                    last_deletion_commit,
+                    pre_deletion_commit
+                ));
+            }
+        };
+    }
+    stack.reverse();
+    Ok(stack)
+}
@@ -26,8 +26,10 @@ use futures_old::{
 };
 use itertools::{Either, Itertools};
 use mercurial_types::{blobs::HgBlobChangeset, HgChangesetId, HgEntryId, HgManifest, MPath};
-use mononoke_types::{typed_hash::MononokeId, ContentId, Timestamp};
-use redactedblobstore::SqlRedactedContentStore;
+use mononoke_types::{typed_hash::MononokeId, ContentId, DateTime, Timestamp};
+use redactedblobstore::{
+    RedactedMetadata, RedactionAuditEntry, RedactionEntry, SqlRedactedContentStore,
+};
 use slog::{info, Logger};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -38,6 +40,53 @@ pub const REDACTION: &str = "redaction";
 const REDACTION_ADD: &str = "add";
 const REDACTION_REMOVE: &str = "remove";
 const REDACTION_LIST: &str = "list";
+const REDACTION_LIST_ENTRIES: &str = "list-entries";
+const REDACTION_AUDIT_LOG: &str = "audit-log";
+
+const ARG_CREATOR: &str = "creator";
+const ARG_REASON: &str = "reason";
+const ARG_EXPIRES: &str = "expires";
+const ARG_LOG_ONLY: &str = "log-only";
+const ARG_ENFORCED: &str = "enforced";
+const ARG_INCLUDE_EXPIRED: &str = "include-expired";
+const ARG_TASK: &str = "task";
+const ARG_KEY: &str = "key";
+
+fn creator_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(ARG_CREATOR)
+        .long(ARG_CREATOR)
+        .takes_value(true)
+        .help("who is making the change (defaults to $USER)")
+}
+
+fn reason_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(ARG_REASON)
+        .long(ARG_REASON)
+        .takes_value(true)
+        .help("why the change is being made")
+}
+
+fn filter_args<'a, 'b>(subcommand: App<'a, 'b>) -> App<'a, 'b> {
+    subcommand
+        .arg(
+            Arg::with_name(ARG_TASK)
+                .long(ARG_TASK)
+                .takes_value(true)
+                .help("only show entries for this task"),
+        )
+        .arg(
+            Arg::with_name(ARG_CREATOR)
+                .long(ARG_CREATOR)
+                .takes_value(true)
+                .help("only show entries made by this user"),
+        )
+        .arg(
+            Arg::with_name(ARG_KEY)
+                .long(ARG_KEY)
+                .takes_value(true)
+                .help("only show entries for this blobstore key"),
+        )
+}
 
 pub fn build_subcommand<'a, 'b>() -> App<'a, 'b> {
     SubCommand::with_name(REDACTION)
@@ -62,6 +111,19 @@ pub fn build_subcommand<'a, 'b>() -> App<'a, 'b> {
                         <FILES_LIST>...                             'list of files to be be redacted'
                         "#,
                 )
+                .arg(creator_arg())
+                .arg(reason_arg())
+                .arg(
+                    Arg::with_name(ARG_EXPIRES)
+                        .long(ARG_EXPIRES)
+                        .takes_value(true)
+                        .help("RFC3339 date after which the redaction no longer applies"),
+                )
+                .arg(
+                    Arg::with_name(ARG_LOG_ONLY)
+                        .long(ARG_LOG_ONLY)
+                        .help("log accesses to the files, but continue to serve them"),
+                )
         )
         .subcommand(
             SubCommand::with_name(REDACTION_REMOVE)
@@ -77,6 +139,8 @@ pub fn build_subcommand<'a, 'b>() -> App<'a, 'b> {
                         <FILES_LIST>...                             'list of files to be be unredacted'
                         "#,
                 )
+                .arg(creator_arg())
+                .arg(reason_arg())
         )
         .subcommand(
             SubCommand::with_name(REDACTION_LIST)
@@ -88,6 +152,32 @@ pub fn build_subcommand<'a, 'b>() -> App<'a, 'b> {
                         .required(true),
                 )
         )
+        .subcommand(
+            filter_args(
+                SubCommand::with_name(REDACTION_LIST_ENTRIES)
+                    .about("list the entries in the redaction table"),
+            )
+            .arg(
+                Arg::with_name(ARG_LOG_ONLY)
+                    .long(ARG_LOG_ONLY)
+                    .conflicts_with(ARG_ENFORCED)
+                    .help("only show log-only entries"),
+            )
+            .arg(
+                Arg::with_name(ARG_ENFORCED)
+                    .long(ARG_ENFORCED)
+                    .help("only show entries that deny access"),
+            )
+            .arg(
+                Arg::with_name(ARG_INCLUDE_EXPIRED)
+                    .long(ARG_INCLUDE_EXPIRED)
+                    .help("also show entries that have expired"),
+            ),
+        )
+        .subcommand(filter_args(
+            SubCommand::with_name(REDACTION_AUDIT_LOG)
+                .about("show the log of all changes made to the redaction table"),
+        ))
 }
 
 fn find_files_with_given_content_id_blobstore_keys(
@@ -95,10 +185,10 @@ fn find_files_with_given_content_id_blobstore_keys(
     ctx: CoreContext,
     repo: BlobRepo,
     cs: HgBlobChangeset,
-    keys_to_tasks: HashMap<String, String>,
+    keys_to_tasks: HashMap<String, RedactedMetadata>,
 ) -> impl Future<Item = Vec<(String, String)>, Error = Error> {
     let manifest_id = cs.manifestid();
-    let keys_to_tasks: Arc<HashMap<String, String>> = Arc::new(keys_to_tasks);
+    let keys_to_tasks: Arc<HashMap<String, RedactedMetadata>> = Arc::new(keys_to_tasks);
     bounded_traversal_stream(4096, Some((repo.clone(), manifest_id, None)), {
         cloned!(ctx);
         move |(repo, manifest_id, path)| {
@@ -161,10 +251,14 @@ fn find_files_with_given_content_id_blobstore_keys(
                         if pfc % 100_000 == 0 {
                             info!(logger.clone(), "Processed files: {}", pfc);
                         }
-                        keys_to_tasks
-                            .clone()
-                            .get(&key)
-                            .map(|task| (task.clone(), full_path.clone()))
+                        keys_to_tasks.clone().get(&key).map(|metadata| {
+                            let task = if metadata.log_only {
+                                format!("{} (log-only)", metadata.task)
+                            } else {
+                                metadata.task.clone()
+                            };
+                            (task, full_path.clone())
+                        })
                     }
                 })
                 .map({
@@ -194,6 +288,12 @@ pub async fn subcommand_redaction<'a>(
         (REDACTION_ADD, Some(sub_sub_m)) => redaction_add(fb, logger, matches, sub_sub_m),
         (REDACTION_REMOVE, Some(sub_sub_m)) => redaction_remove(fb, logger, matches, sub_sub_m),
         (REDACTION_LIST, Some(sub_sub_m)) => redaction_list(fb, logger, matches, sub_sub_m),
+        (REDACTION_LIST_ENTRIES, Some(sub_sub_m)) => {
+            redaction_list_entries(fb, logger, matches, sub_sub_m)
+        }
+        (REDACTION_AUDIT_LOG, Some(sub_sub_m)) => {
+            redaction_audit_log(fb, logger, matches, sub_sub_m)
+        }
         _ => {
             eprintln!("{}", matches.usage());
             ::std::process::exit(1);
@@ -228,6 +328,35 @@ fn task_and_paths_parser(sub_m: &ArgMatches<'_>) -> Result<(String, Vec<MPath>),
     Ok((task, paths))
 }
 
+/// Fetch who is making a change, and why, from the subcommand cli matches
+fn creator_and_reason_parser(sub_m: &ArgMatches<'_>) -> Result<(String, String), Error> {
+    let creator = match sub_m.value_of(ARG_CREATOR) {
+        Some(creator) => creator.to_string(),
+        None => std::env::var("USER")
+            .map_err(|_| format_err!("--{} is needed when $USER is not set", ARG_CREATOR))?,
+    };
+    let reason = sub_m.value_of(ARG_REASON).unwrap_or("").to_string();
+    Ok((creator, reason))
+}
+
+/// Fetch the optional expiry date from the subcommand cli matches
+fn expiry_parser(sub_m: &ArgMatches<'_>) -> Result<Option<Timestamp>, Error> {
+    sub_m
+        .value_of(ARG_EXPIRES)
+        .map(|expires| {
+            let expires = DateTime::from_rfc3339(expires)?;
+            if expires.timestamp_secs() <= Timestamp::now().timestamp_seconds() {
+                return Err(format_err!("Expiry date {} is in the past", expires));
+            }
+            Ok(Timestamp::from(expires))
+        })
+        .transpose()
+}
+
+fn format_timestamp(timestamp: &Timestamp) -> String {
+    DateTime::from(*timestamp).as_chrono().to_rfc3339()
+}
+
 /// Boilerplate to prepare a bunch of prerequisites for the rest of blaclisting operations
 fn get_ctx_blobrepo_redacted_blobs_cs_id(
     fb: FacebookInit,
@@ -251,9 +380,7 @@ fn get_ctx_blobrepo_redacted_blobs_cs_id(
     args::init_cachelib(fb, &matches, None);
 
     let blobrepo = args::open_repo(fb, &logger, &matches);
-    let redacted_blobs = args::open_sql::<SqlRedactedContentStore>(fb, &matches)
-        .context("While opening SqlRedactedContentStore")
-        .from_err();
+    let redacted_blobs = open_redacted_blobs(fb, matches);
 
     let ctx = CoreContext::new_with_logger(fb, logger);
 
@@ -312,16 +439,27 @@ fn redaction_add(
     sub_m: &ArgMatches<'_>,
 ) -> BoxFuture<(), SubcommandError> {
     let (task, paths) = try_boxfuture!(task_and_paths_parser(sub_m));
+    let (creator, reason) = try_boxfuture!(creator_and_reason_parser(sub_m));
+    let expiry_timestamp = try_boxfuture!(expiry_parser(sub_m));
+    let log_only = sub_m.is_present(ARG_LOG_ONLY);
     get_ctx_blobrepo_redacted_blobs_cs_id(fb, logger.clone(), matches, sub_m)
         .and_then(move |(ctx, blobrepo, redacted_blobs, cs_id)| {
             content_ids_for_paths(ctx, logger, blobrepo, cs_id, paths)
                 .and_then(move |content_ids| {
-                    let blobstore_keys = content_ids
+                    let add_timestamp = Timestamp::now();
+                    let entries = content_ids
                         .iter()
-                        .map(|content_id| content_id.blobstore_key())
+                        .map(|content_id| RedactionEntry {
+                            content_key: content_id.blobstore_key(),
+                            task: task.clone(),
+                            creator: creator.clone(),
+                            reason: reason.clone(),
+                            add_timestamp,
+                            expiry_timestamp,
+                            log_only,
+                        })
                         .collect();
-                    let timestamp = Timestamp::now();
-                    redacted_blobs.insert_redacted_blobs(&blobstore_keys, &task, &timestamp)
+                    redacted_blobs.insert_redacted_blobs(entries)
                 })
                 .from_err()
         })
@@ -386,6 +524,7 @@ fn redaction_remove(
     sub_m: &ArgMatches<'_>,
 ) -> BoxFuture<(), SubcommandError> {
     let paths = try_boxfuture!(paths_parser(sub_m));
+    let (creator, reason) = try_boxfuture!(creator_and_reason_parser(sub_m));
     get_ctx_blobrepo_redacted_blobs_cs_id(fb, logger.clone(), matches, sub_m)
         .and_then(move |(ctx, blobrepo, redacted_blobs, cs_id)| {
             content_ids_for_paths(ctx, logger, blobrepo, cs_id, paths)
@@ -394,9 +533,131 @@ fn redaction_remove(
                         .into_iter()
                         .map(|content_id| content_id.blobstore_key())
                         .collect();
-                    redacted_blobs.delete_redacted_blobs(&blobstore_keys)
+                    redacted_blobs.delete_redacted_blobs(&blobstore_keys, &creator, &reason)
                 })
                 .from_err()
         })
         .boxify()
 }
+
+/// Filters on the common fields of redaction table and audit log entries
+struct EntryFilter {
+    task: Option<String>,
+    creator: Option<String>,
+    key: Option<String>,
+}
+
+impl EntryFilter {
+    fn from_matches(sub_m: &ArgMatches<'_>) -> Self {
+        Self {
+            task: sub_m.value_of(ARG_TASK).map(String::from),
+            creator: sub_m.value_of(ARG_CREATOR).map(String::from),
+            key: sub_m.value_of(ARG_KEY).map(String::from),
+        }
+    }
+
+    fn matches(&self, task: &str, creator: &str, key: &str) -> bool {
+        self.task.as_deref().map_or(true, |t| t == task)
+            && self.creator.as_deref().map_or(true, |c| c == creator)
+            && self.key.as_deref().map_or(true, |k| k == key)
+    }
+}
+
+fn open_redacted_blobs(
+    fb: FacebookInit,
+    matches: &ArgMatches<'_>,
+) -> impl Future<Item = SqlRedactedContentStore, Error = Error> {
+    args::open_sql::<SqlRedactedContentStore>(fb, &matches)
+        .context("While opening SqlRedactedContentStore")
+        .from_err()
+}
+
+fn redaction_list_entries(
+    fb: FacebookInit,
+    logger: Logger,
+    matches: &ArgMatches<'_>,
+    sub_m: &ArgMatches<'_>,
+) -> BoxFuture<(), SubcommandError> {
+    let filter = EntryFilter::from_matches(sub_m);
+    let log_only = sub_m.is_present(ARG_LOG_ONLY);
+    let enforced = sub_m.is_present(ARG_ENFORCED);
+    let include_expired = sub_m.is_present(ARG_INCLUDE_EXPIRED);
+    open_redacted_blobs(fb, matches)
+        .and_then(|redacted_blobs| redacted_blobs.get_redaction_entries())
+        .map(move |entries| {
+            let now = Timestamp::now();
+            let entries: Vec<_> = entries
+                .into_iter()
+                .filter(|entry| {
+                    filter.matches(&entry.task, &entry.creator, &entry.content_key)
+                        && (!log_only || entry.log_only)
+                        && (!enforced || !entry.log_only)
+                        && (include_expired || !entry.is_expired(now))
+                })
+                .collect();
+            if entries.is_empty() {
+                info!(logger, "No matching redaction entries");
+            }
+            for entry in entries {
+                let expiry = match &entry.expiry_timestamp {
+                    Some(expiry) if entry.is_expired(now) => {
+                        format!("expired {}", format_timestamp(expiry))
+                    }
+                    Some(expiry) => format!("expires {}", format_timestamp(expiry)),
+                    None => "never expires".to_string(),
+                };
+                info!(
+                    logger,
+                    "{} task: {} creator: {} added: {} {}{} reason: {}",
+                    entry.content_key,
+                    entry.task,
+                    entry.creator,
+                    format_timestamp(&entry.add_timestamp),
+                    expiry,
+                    if entry.log_only { " log-only" } else { "" },
+                    entry.reason,
+                );
+            }
+        })
+        .from_err()
+        .boxify()
+}
+
+fn redaction_audit_log(
+    fb: FacebookInit,
+    logger: Logger,
+    matches: &ArgMatches<'_>,
+    sub_m: &ArgMatches<'_>,
+) -> BoxFuture<(), SubcommandError> {
+    let filter = EntryFilter::from_matches(sub_m);
+    open_redacted_blobs(fb, matches)
+        .and_then(|redacted_blobs| redacted_blobs.get_audit_log())
+        .map(move |log| {
+            let log: Vec<RedactionAuditEntry> = log
+                .into_iter()
+                .filter(|entry| filter.matches(&entry.task, &entry.creator, &entry.content_key))
+                .collect();
+            if log.is_empty() {
+                info!(logger, "No matching audit log entries");
+            }
+            for entry in log {
+                info!(
+                    logger,
+                    "{} {} {} task: {} creator: {}{}{} reason: {}",
+                    format_timestamp(&entry.timestamp),
+                    entry.action,
+                    entry.content_key,
+                    entry.task,
+                    entry.creator,
+                    entry
+                        .expiry_timestamp
+                        .map(|expiry| format!(" expires: {}", format_timestamp(&expiry)))
+                        .unwrap_or_default(),
+                    if entry.log_only { " log-only" } else { "" },
+                    entry.reason,
+                );
+            }
+        })
+        .from_err()
+        .boxify()
+}
@@ -23,7 +23,7 @@ use cmdlib::{args, helpers};
 use context::CoreContext;
 use derived_data::BonsaiDerived;
 use derived_data_utils::{
-    derived_data_utils, derived_data_utils_unsafe, DerivedUtils, POSSIBLE_DERIVED_TYPES,
+    derived_data_graph_unsafe, derived_data_utils, DerivedUtils, POSSIBLE_DERIVED_TYPES,
 };
 use fbinit::FacebookInit;
 use fsnodes::RootFsnodeId;
@@ -119,10 +119,21 @@ fn main(fb: FacebookInit) -> Result<(), Error> {
         .subcommand(
             SubCommand::with_name(SUBCOMMAND_BACKFILL)
                 .about("backfill derived data for public commits")
+                .arg(
+                    Arg::with_name(ARG_ALL_TYPES)
+                        .long(ARG_ALL_TYPES)
+                        .required(false)
+                        .takes_value(false)
+                        .help(
+                            "backfill all derived data types enabled for this repo, \
+                             in dependency order",
+                        ),
+                )
                 .arg(
                     Arg::with_name(ARG_DERIVED_DATA_TYPE)
-                        .required(true)
+                        .required_unless(ARG_ALL_TYPES)
                         .index(1)
+                        .conflicts_with(ARG_ALL_TYPES)
                         .possible_values(POSSIBLE_DERIVED_TYPES)
                         .help("derived data type for which backfill will be run"),
                 )
@@ -227,11 +238,6 @@ async fn run_subcmd<'a>(
 ) -> Result<(), Error> {
     match matches.subcommand() {
         (SUBCOMMAND_BACKFILL, Some(sub_m)) => {
-            let derived_data_type = sub_m
-                .value_of(ARG_DERIVED_DATA_TYPE)
-                .ok_or_else(|| format_err!("missing required argument: {}", ARG_DERIVED_DATA_TYPE))?
-                .to_string();
-
             let prefetched_commits_path = sub_m
                 .value_of(ARG_PREFETCHED_COMMITS_PATH)
                 .ok_or_else(|| {
@@ -247,18 +253,41 @@ async fn run_subcmd<'a>(
                 .transpose()
                 .map(|skip| skip.unwrap_or(0))?;
 
-            let repo = open_repo_maybe_unredacted(fb, &logger, &matches, &derived_data_type)
-                .compat()
-                .await?;
+            let (repo, derived_data_types): (_, Vec<String>) = match sub_m
+                .value_of(ARG_DERIVED_DATA_TYPE)
+            {
+                Some(derived_data_type) => {
+                    let repo = open_repo_maybe_unredacted(fb, &logger, &matches, derived_data_type)
+                        .compat()
+                        .await?;
+                    (repo, vec![derived_data_type.to_string()])
+                }
+                None => {
+                    let repo = args::open_repo_unredacted(fb, logger, matches)
+                        .compat()
+                        .await?;
+                    let types = repo
+                        .get_derived_data_config()
+                        .derived_data_types
+                        .iter()
+                        .cloned()
+                        .collect();
+                    (repo, types)
+                }
+            };
 
             // Backfill is used when when a derived data type is not enabled yet, and so
             // any attempt to call BonsaiDerived::derive() fails. However calling
             // BonsaiDerived::derive() might be useful, and so the lines below explicitly
-            // enable `derived_data_type` to allow calling BonsaiDerived::derive() if necessary.
+            // enable `derived_data_types` and their dependencies to allow calling
+            // BonsaiDerived::derive() if necessary.
+            let enabled_types: Vec<_> =
+                derived_data_graph_unsafe(repo.clone(), &derived_data_types)?
+                    .types()
+                    .map(|derived_utils| derived_utils.name().to_string())
+                    .collect();
             let mut repo = repo.dangerous_override(|mut derived_data_config: DerivedDataConfig| {
-                derived_data_config
-                    .derived_data_types
-                    .insert(derived_data_type.clone());
+                derived_data_config.derived_data_types.extend(enabled_types);
                 derived_data_config
             });
             info!(
@@ -276,7 +305,7 @@ async fn run_subcmd<'a>(
                     return Err(anyhow!("--dry-run requires readonly storage!"));
                 }
 
-                if derived_data_type != "fsnodes" {
+                if derived_data_types != [RootFsnodeId::NAME] {
                     return Err(anyhow!("unsupported dry run data type"));
                 }
 
@@ -287,16 +316,10 @@ async fn run_subcmd<'a>(
                     }
                 }
 
-                if derived_data_type == "fsnodes" {
-                    let (new_cleaner, wrapped_repo) = dry_run::FsnodeCleaner::new(
-                        ctx.clone(),
-                        repo.clone(),
-                        children_count,
-                        10000,
-                    );
-                    repo = wrapped_repo;
-                    cleaner = Some(new_cleaner);
-                }
+                let (new_cleaner, wrapped_repo) =
+                    dry_run::FsnodeCleaner::new(ctx.clone(), repo.clone(), children_count, 10000);
+                repo = wrapped_repo;
+                cleaner = Some(new_cleaner);
             }
 
             let changesets: Vec<_> = changesets
@@ -308,7 +331,7 @@ async fn run_subcmd<'a>(
             subcommand_backfill(
                 &ctx,
                 &repo,
-                &derived_data_type,
+                &derived_data_types,
                 regenerate,
                 changesets,
                 cleaner,
@@ -402,16 +425,22 @@ fn parse_serialized_commits<P: AsRef<Path>>(file: P) -> Result<Vec<ChangesetEntr
 async fn subcommand_backfill(
     ctx: &CoreContext,
     repo: &BlobRepo,
-    derived_data_type: &String,
+    derived_data_types: &[String],
     regenerate: bool,
     changesets: Vec<ChangesetId>,
     mut cleaner: Option<impl dry_run::Cleaner>,
 ) -> Result<(), Error> {
-    let derived_utils = &derived_data_utils_unsafe(repo.clone(), derived_data_type.clone())?;
+    // Dependencies of the requested types are backfilled first, so that
+    // backfilling a type never derives its dependencies in the same batch.
+    let graph = &derived_data_graph_unsafe(repo.clone(), derived_data_types)?;
 
     info!(
         ctx.logger(),
-        "starting deriving data for {} changesets",
+        "starting deriving {:?} for {} changesets",
+        graph
+            .types()
+            .map(|derived_utils| derived_utils.name())
+            .collect::<Vec<_>>(),
         changesets.len()
     );
 
@@ -420,29 +449,38 @@ async fn subcommand_backfill(
     let total_duration = &Arc::new(Mutex::new(Duration::from_secs(0)));
 
     if regenerate {
-        derived_utils.regenerate(&changesets);
+        // Only the requested types are regenerated. Their dependencies are
+        // derived as usual, skipping changesets they already exist for.
+        for derived_utils in graph.types() {
+            if derived_data_types
+                .iter()
+                .any(|name| name == derived_utils.name())
+            {
+                derived_utils.regenerate(&changesets);
+            }
+        }
     }
 
     for chunk in changesets.chunks(CHUNK_SIZE) {
-        let (stats, chunk_size) = async {
-            let chunk = derived_utils
-                .pending(ctx.clone(), repo.clone(), chunk.to_vec())
-                .compat()
-                .await?;
-            let chunk_size = chunk.len();
+        let (stats, res) = graph
+            .try_for_each_type(|derived_utils| async move {
+                let chunk = derived_utils
+                    .pending(ctx.clone(), repo.clone(), chunk.to_vec())
+                    .compat()
+                    .await?;
 
-            warmup::warmup(ctx, repo, derived_data_type, &chunk).await?;
+                warmup::warmup(ctx, repo, derived_utils.name(), &chunk).await?;
 
-            derived_utils
-                .backfill_batch_dangerous(ctx.clone(), repo.clone(), chunk)
-                .compat()
-                .await?;
-            Result::<_, Error>::Ok(chunk_size)
-        }
-        .timed()
-        .await;
+                derived_utils
+                    .backfill_batch_dangerous(ctx.clone(), repo.clone(), chunk)
+                    .compat()
+                    .await
+            })
+            .timed()
+            .await;
 
-        let chunk_size = chunk_size?;
+        res?;
+        let chunk_size = chunk.len();
         generated_count.fetch_add(chunk_size, Ordering::SeqCst);
         let elapsed = total_duration.with(|total_duration| {
             *total_duration += stats.completion_time;
@@ -614,13 +652,14 @@ async fn subcommand_single(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use blobrepo_factory::new_memblob_empty;
     use blobrepo_hg::BlobRepoHg;
     use blobstore::{Blobstore, BlobstoreBytes, BlobstoreGetData};
     use fixtures::linear;
     use futures::future::{BoxFuture, FutureExt};
     use mercurial_types::HgChangesetId;
     use std::str::FromStr;
-    use tests_utils::resolve_cs_id;
+    use tests_utils::{resolve_cs_id, CreateCommitContext};
     use tokio_compat::runtime::Runtime;
     use unodes::RootUnodeManifestId;
 
@@ -671,6 +710,47 @@ mod tests {
         Ok(())
     }
 
+    #[fbinit::compat_test]
+    async fn test_backfill_with_dependencies(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = new_memblob_empty(None)?;
+        let first = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file("file", "content")
+            .commit()
+            .await?;
+        let second = CreateCommitContext::new(&ctx, &repo, vec![first])
+            .add_file("file", "content2")
+            .commit()
+            .await?;
+
+        let graph = derived_data_graph_unsafe(repo.clone(), &[BlameRoot::NAME])?;
+        let stages: Vec<Vec<_>> = graph
+            .stages()
+            .iter()
+            .map(|stage| stage.iter().map(|utils| utils.name()).collect())
+            .collect();
+        assert_eq!(
+            stages,
+            vec![vec![RootUnodeManifestId::NAME], vec![BlameRoot::NAME]]
+        );
+
+        subcommand_backfill(
+            &ctx,
+            &repo,
+            &[BlameRoot::NAME.to_string()],
+            false,
+            vec![first, second],
+            None::<dry_run::FsnodeCleaner>,
+        )
+        .await?;
+        for cs_id in &[first, second] {
+            assert!(RootUnodeManifestId::is_derived(&ctx, &repo, cs_id).await?);
+            assert!(BlameRoot::is_derived(&ctx, &repo, cs_id).await?);
+        }
+
+        Ok(())
+    }
+
     #[fbinit::test]
     fn test_backfill_data_latest(fb: FacebookInit) -> Result<(), Error> {
         let mut runtime = Runtime::new()?;
@@ -8,9 +8,12 @@
 use anyhow::{bail, Result};
 use fbinit::FacebookInit;
 use itertools::Itertools;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 
 use cmdlib::args;
+use live_commit_sync_config::{CfgrLiveCommitSyncConfig, LiveCommitSyncConfig};
+use metaconfig_types::CommitSyncConfig;
+use sync_config_validation::validate_commit_sync_config;
 
 #[fbinit::main]
 fn main(fb: FacebookInit) -> Result<()> {
@@ -29,6 +32,7 @@ fn main(fb: FacebookInit) -> Result<()> {
 
     let quiet = matches.is_present("quiet");
     let verbose = matches.is_present("verbose");
+    let logger = args::init_logging(fb, &matches);
 
     // Most of the work is done here - this validates that the files are present,
     // are correctly formed, and have the right fields (not too many, not too few).
@@ -87,6 +91,65 @@ fn main(fb: FacebookInit) -> Result<()> {
         }
     }
 
+    // Validate the commit sync config in the repo configs, and every version
+    // that is live in configerator, as old versions are still used to sync
+    // commits that were created while they were current.
+    let mut repo_names: Vec<_> = configs.repos.keys().collect();
+    repo_names.sort();
+    let mut commit_sync_configs: Vec<CommitSyncConfig> = repo_names
+        .iter()
+        .filter_map(|name| configs.repos[*name].commit_sync_config.clone())
+        .collect();
+    if let Some(config_store) = args::maybe_init_config_store(fb, &logger, &matches) {
+        let live_commit_sync_config = CfgrLiveCommitSyncConfig::new(&logger, &config_store)?;
+        for name in repo_names.iter() {
+            let repoid = configs.repos[*name].repoid;
+            match live_commit_sync_config.get_all_commit_sync_config_versions(repoid) {
+                Ok(versions) => {
+                    let mut versions: Vec<_> = versions.into_iter().collect();
+                    versions.sort_by(|(a, _), (b, _)| a.0.cmp(&b.0));
+                    commit_sync_configs.extend(versions.into_iter().map(|(_, config)| config));
+                }
+                Err(err) => {
+                    eprintln!(
+                        "ERROR: Failed to load commit sync configs for repo {}: {:#}",
+                        name, err
+                    );
+                    bad = true;
+                }
+            }
+        }
+    }
+
+    // Commit sync configs are shared between the large repo and all its small
+    // repos, so only validate each version once
+    let mut validated_versions = HashSet::new();
+    for commit_sync_config in commit_sync_configs.iter() {
+        if !validated_versions.insert((
+            commit_sync_config.large_repo_id,
+            commit_sync_config.version_name.clone(),
+        )) {
+            continue;
+        }
+
+        let errors = validate_commit_sync_config(commit_sync_config);
+        if !quiet || !errors.is_empty() {
+            println!(
+                "Commit sync config {} for large repo {}: {} problems",
+                commit_sync_config.version_name.0,
+                commit_sync_config.large_repo_id,
+                errors.len()
+            );
+        }
+        for error in errors {
+            eprintln!(
+                "ERROR: Commit sync config {} for large repo {}: {}",
+                commit_sync_config.version_name.0, commit_sync_config.large_repo_id, error
+            );
+            bad = true;
+        }
+    }
+
     if bad {
         bail!("Anomaly detected")
     } else {
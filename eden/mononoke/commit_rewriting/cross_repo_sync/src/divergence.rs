@@ -0,0 +1,571 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Checks that commits which were already synced still match the commits
+//! they were synced from. Unlike `validation::verify_working_copy`, which
+//! fails on the first problem with a single commit, this walks a whole range
+//! of source repo commits and collects every divergence it finds.
+
+use anyhow::Error;
+use blobrepo::BlobRepo;
+use blobstore::Loadable;
+use context::CoreContext;
+use futures::{
+    compat::{Future01CompatExt, Stream01CompatExt},
+    future, pin_mut,
+    stream::{self, Stream, StreamExt, TryStreamExt},
+    try_join,
+};
+use futures_ext::StreamExt as OldStreamExt;
+use futures_old::Stream as OldStream;
+use manifest::{Diff, Entry, ManifestOps};
+use mercurial_types::{FileType, HgFileNodeId, HgManifestId};
+use mononoke_types::{ChangesetId, MPath};
+use revset::RangeNodeStream;
+use slog::{info, warn};
+use stats::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use synced_commit_mapping::SyncedCommitMapping;
+
+use crate::validation::fetch_root_mf_id;
+use crate::{CommitSyncOutcome, CommitSyncer};
+
+define_stats! {
+    prefix = "mononoke.cross_repo_sync.divergence";
+    checked_commits: timeseries(Sum),
+    missing_mapping: timeseries(Sum),
+    content_mismatch: timeseries(Sum),
+    wrong_parents: timeseries(Sum),
+}
+
+/// At most this many mismatching paths are kept for a single commit
+const MAX_REPORTED_PATHS: usize = 10;
+/// How many file contents are compared concurrently for a single commit
+const CONTENT_FETCH_CONCURRENCY: usize = 100;
+/// How many changed paths of a single commit are looked up at once
+const PATH_BATCH_SIZE: usize = 1000;
+
+/// A way in which a synced commit differs from the commit it was synced from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Divergence {
+    /// The source commit has no sync outcome in the mapping
+    MissingMapping { source_cs_id: ChangesetId },
+    /// The files changed by the target commit differ from the files changed
+    /// by the source commit, once the source paths are moved into the target
+    /// repo
+    ContentMismatch {
+        source_cs_id: ChangesetId,
+        target_cs_id: ChangesetId,
+        /// Number of paths that differ
+        count: usize,
+        /// Up to `MAX_REPORTED_PATHS` of the paths that differ
+        paths: Vec<MPath>,
+    },
+    /// The parents of the target commit are not the commits that the parents
+    /// of the source commit were synced to
+    WrongParents {
+        source_cs_id: ChangesetId,
+        target_cs_id: ChangesetId,
+        expected: Vec<ChangesetId>,
+        actual: Vec<ChangesetId>,
+    },
+}
+
+impl Divergence {
+    pub fn source_cs_id(&self) -> ChangesetId {
+        use Divergence::*;
+        match self {
+            MissingMapping { source_cs_id } => *source_cs_id,
+            ContentMismatch { source_cs_id, .. } => *source_cs_id,
+            WrongParents { source_cs_id, .. } => *source_cs_id,
+        }
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Divergence::*;
+        match self {
+            MissingMapping { source_cs_id } => write!(f, "{} has no sync outcome", source_cs_id),
+            ContentMismatch {
+                source_cs_id,
+                target_cs_id,
+                count,
+                paths,
+            } => {
+                let paths: Vec<_> = paths.iter().map(|path| path.to_string()).collect();
+                write!(
+                    f,
+                    "{} files differ between {} and {} (e.g. {})",
+                    count,
+                    source_cs_id,
+                    target_cs_id,
+                    paths.join(", ")
+                )
+            }
+            WrongParents {
+                source_cs_id,
+                target_cs_id,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{} was synced as {} with parents {:?}, but its parents were synced as {:?}",
+                source_cs_id, target_cs_id, actual, expected
+            ),
+        }
+    }
+}
+
+/// Result of checking a range of source repo commits
+#[derive(Debug, Default)]
+pub struct DivergenceReport {
+    /// Number of source repo commits that were checked
+    pub checked: usize,
+    pub divergences: Vec<Divergence>,
+}
+
+/// Check every source repo commit between `start_cs_id` and `end_cs_id`
+/// (both inclusive) against the commit it was synced to. `start_cs_id` must
+/// be an ancestor of `end_cs_id`, otherwise nothing is checked.
+pub async fn find_divergences_in_range<M>(
+    ctx: &CoreContext,
+    commit_syncer: &CommitSyncer<M>,
+    start_cs_id: ChangesetId,
+    end_cs_id: ChangesetId,
+    concurrency: usize,
+) -> Result<DivergenceReport, Error>
+where
+    M: SyncedCommitMapping + Clone + 'static,
+{
+    let source_repo = commit_syncer.get_source_repo();
+    let report = RangeNodeStream::new(
+        ctx.clone(),
+        source_repo.get_changeset_fetcher(),
+        start_cs_id,
+        end_cs_id,
+    )
+    .compat()
+    .map(|cs_id| async move { check_commit_divergence(ctx, commit_syncer, cs_id?).await })
+    .buffered(concurrency)
+    .try_fold(DivergenceReport::default(), |mut report, divergence| {
+        report.checked += 1;
+        STATS::checked_commits.add_value(1);
+        if let Some(divergence) = divergence {
+            match divergence {
+                Divergence::MissingMapping { .. } => STATS::missing_mapping.add_value(1),
+                Divergence::ContentMismatch { .. } => STATS::content_mismatch.add_value(1),
+                Divergence::WrongParents { .. } => STATS::wrong_parents.add_value(1),
+            }
+            warn!(ctx.logger(), "{}", divergence);
+            report.divergences.push(divergence);
+        }
+        future::ok(report)
+    })
+    .await?;
+
+    info!(
+        ctx.logger(),
+        "checked {} commits between {} and {}, found {} divergences",
+        report.checked,
+        start_cs_id,
+        end_cs_id,
+        report.divergences.len()
+    );
+    Ok(report)
+}
+
+/// Check a single source repo commit against the commit it was synced to
+pub async fn check_commit_divergence<M>(
+    ctx: &CoreContext,
+    commit_syncer: &CommitSyncer<M>,
+    source_cs_id: ChangesetId,
+) -> Result<Option<Divergence>, Error>
+where
+    M: SyncedCommitMapping + Clone + 'static,
+{
+    use CommitSyncOutcome::*;
+
+    let outcome = commit_syncer
+        .get_commit_sync_outcome(ctx.clone(), source_cs_id)
+        .await?;
+    let (target_cs_id, is_rewritten) = match outcome {
+        None => return Ok(Some(Divergence::MissingMapping { source_cs_id })),
+        Some(NotSyncCandidate) => return Ok(None),
+        Some(RewrittenAs(target_cs_id)) => (target_cs_id, true),
+        Some(Preserved) => (source_cs_id, true),
+        // Only the working copy of the source commit has an equivalent in the
+        // target repo, the commit itself wasn't synced
+        Some(EquivalentWorkingCopyAncestor(target_cs_id)) => (target_cs_id, false),
+    };
+
+    let (count, paths) =
+        find_mismatching_paths(ctx, commit_syncer, source_cs_id, target_cs_id).await?;
+    if count > 0 {
+        return Ok(Some(Divergence::ContentMismatch {
+            source_cs_id,
+            target_cs_id,
+            count,
+            paths,
+        }));
+    }
+
+    if is_rewritten {
+        if let Some((expected, actual)) =
+            find_wrong_parents(ctx, commit_syncer, source_cs_id, target_cs_id).await?
+        {
+            return Ok(Some(Divergence::WrongParents {
+                source_cs_id,
+                target_cs_id,
+                expected,
+                actual,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// A changed path to compare between the repos
+struct PathToCheck {
+    source_path: MPath,
+    target_path: MPath,
+    /// The path was found in the changes of the target commit. If it changed
+    /// in the source commit too, it is compared as a source repo change.
+    target_only: bool,
+}
+
+/// Count the paths which differ between `source_cs_id` and `target_cs_id`,
+/// and return up to `MAX_REPORTED_PATHS` of them, in terms of the target repo.
+///
+/// Only the files changed by `source_cs_id` relative to its first parent,
+/// and by `target_cs_id` relative to what that parent was synced to, are
+/// compared, in batches of `PATH_BATCH_SIZE`. The rest of the working copy
+/// is covered by checking the parent. Commits without a synced parent are
+/// compared against an empty working copy.
+async fn find_mismatching_paths<M>(
+    ctx: &CoreContext,
+    commit_syncer: &CommitSyncer<M>,
+    source_cs_id: ChangesetId,
+    target_cs_id: ChangesetId,
+) -> Result<(usize, Vec<MPath>), Error>
+where
+    M: SyncedCommitMapping + Clone + 'static,
+{
+    let source_repo = commit_syncer.get_source_repo();
+    let target_repo = commit_syncer.get_target_repo();
+    // No need to move any paths if this commit was preserved as is
+    let preserved = source_cs_id == target_cs_id;
+    let mover = commit_syncer.get_mover();
+    let reverse_mover = commit_syncer.get_reverse_mover();
+
+    let source_parent = source_repo
+        .get_changeset_parents_by_bonsai(ctx.clone(), source_cs_id)
+        .compat()
+        .await?
+        .into_iter()
+        .next();
+    let parents = match source_parent {
+        Some(source_parent) => get_synced_cs_id(ctx, commit_syncer, source_parent)
+            .await?
+            .map(|target_parent| (source_parent, target_parent)),
+        None => None,
+    };
+
+    let (source_mf_id, target_mf_id, parent_mf_ids) = try_join!(
+        fetch_root_mf_id(ctx.clone(), source_repo, source_cs_id),
+        fetch_root_mf_id(ctx.clone(), target_repo, target_cs_id),
+        async {
+            match parents {
+                Some((source_parent, target_parent)) => Ok::<_, Error>(Some(try_join!(
+                    fetch_root_mf_id(ctx.clone(), source_repo, source_parent),
+                    fetch_root_mf_id(ctx.clone(), target_repo, target_parent),
+                )?)),
+                None => Ok(None),
+            }
+        },
+    )?;
+    let (source_parent_mf_id, target_parent_mf_id) = match parent_mf_ids {
+        Some((source_parent_mf_id, target_parent_mf_id)) => {
+            (Some(source_parent_mf_id), Some(target_parent_mf_id))
+        }
+        None => (None, None),
+    };
+
+    let source_changes = changed_files(ctx, source_repo, source_mf_id, source_parent_mf_id)
+        .try_filter_map(|source_path| {
+            let target_path = if preserved {
+                Ok(Some(source_path.clone()))
+            } else {
+                mover(&source_path)
+            };
+            future::ready(target_path.map(|target_path| {
+                target_path.map(|target_path| PathToCheck {
+                    source_path,
+                    target_path,
+                    target_only: false,
+                })
+            }))
+        });
+    // Files that only changed in the target repo are fine, as long as they
+    // don't sync back into the source repo
+    let target_changes = changed_files(ctx, target_repo, target_mf_id, target_parent_mf_id)
+        .try_filter_map(|target_path| {
+            let source_path = if preserved {
+                Ok(Some(target_path.clone()))
+            } else {
+                reverse_mover(&target_path)
+            };
+            future::ready(source_path.map(|source_path| {
+                source_path.map(|source_path| PathToCheck {
+                    source_path,
+                    target_path,
+                    target_only: true,
+                })
+            }))
+        });
+
+    let batches = source_changes.chain(target_changes).chunks(PATH_BATCH_SIZE);
+    pin_mut!(batches);
+    let mut count = 0;
+    let mut paths = vec![];
+    while let Some(batch) = batches.next().await {
+        let batch = batch.into_iter().collect::<Result<Vec<_>, Error>>()?;
+        let mismatches = check_paths(
+            ctx,
+            commit_syncer,
+            source_mf_id,
+            source_parent_mf_id,
+            target_mf_id,
+            batch,
+        )
+        .await?;
+        count += mismatches.len();
+        let room = MAX_REPORTED_PATHS.saturating_sub(paths.len());
+        paths.extend(mismatches.into_iter().take(room));
+    }
+    paths.sort();
+
+    Ok((count, paths))
+}
+
+/// The commit that `cs_id` was synced to, or what it is equivalent to, if any
+async fn get_synced_cs_id<M>(
+    ctx: &CoreContext,
+    commit_syncer: &CommitSyncer<M>,
+    cs_id: ChangesetId,
+) -> Result<Option<ChangesetId>, Error>
+where
+    M: SyncedCommitMapping + Clone + 'static,
+{
+    use CommitSyncOutcome::*;
+
+    let outcome = commit_syncer
+        .get_commit_sync_outcome(ctx.clone(), cs_id)
+        .await?;
+    Ok(match outcome {
+        Some(RewrittenAs(synced)) | Some(EquivalentWorkingCopyAncestor(synced)) => Some(synced),
+        Some(Preserved) => Some(cs_id),
+        Some(NotSyncCandidate) | None => None,
+    })
+}
+
+/// Files which differ between `mf_id` and `parent_mf_id`, or all files if
+/// there is no parent
+fn changed_files(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    mf_id: HgManifestId,
+    parent_mf_id: Option<HgManifestId>,
+) -> impl Stream<Item = Result<MPath, Error>> {
+    let blobstore = repo.get_blobstore();
+    let changed = match parent_mf_id {
+        Some(parent_mf_id) => mf_id
+            .diff(ctx.clone(), blobstore, parent_mf_id)
+            .filter_map(|diff| match diff {
+                Diff::Added(path, Entry::Leaf(_))
+                | Diff::Removed(path, Entry::Leaf(_))
+                | Diff::Changed(path, Entry::Leaf(_), _)
+                | Diff::Changed(path, _, Entry::Leaf(_)) => path,
+                _ => None,
+            })
+            .boxify(),
+        None => mf_id
+            .list_leaf_entries(ctx.clone(), blobstore)
+            .map(|(path, _)| path)
+            .boxify(),
+    };
+    changed.compat()
+}
+
+/// Paths in `batch` whose file in the target commit doesn't match the file
+/// in the source commit, in terms of the target repo
+async fn check_paths<M>(
+    ctx: &CoreContext,
+    commit_syncer: &CommitSyncer<M>,
+    source_mf_id: HgManifestId,
+    source_parent_mf_id: Option<HgManifestId>,
+    target_mf_id: HgManifestId,
+    batch: Vec<PathToCheck>,
+) -> Result<Vec<MPath>, Error>
+where
+    M: SyncedCommitMapping + Clone + 'static,
+{
+    let source_repo = commit_syncer.get_source_repo();
+    let target_repo = commit_syncer.get_target_repo();
+
+    let target_only_paths: Vec<_> = batch
+        .iter()
+        .filter(|path| path.target_only)
+        .map(|path| path.source_path.clone())
+        .collect();
+    let (source_files, source_parent_files, target_files) = try_join!(
+        find_files(
+            ctx,
+            source_repo,
+            source_mf_id,
+            batch.iter().map(|path| path.source_path.clone()).collect(),
+        ),
+        async {
+            match source_parent_mf_id {
+                Some(source_parent_mf_id) if !target_only_paths.is_empty() => {
+                    find_files(ctx, source_repo, source_parent_mf_id, target_only_paths).await
+                }
+                _ => Ok(HashMap::new()),
+            }
+        },
+        find_files(
+            ctx,
+            target_repo,
+            target_mf_id,
+            batch.iter().map(|path| path.target_path.clone()).collect(),
+        ),
+    )?;
+
+    let mut mismatches = vec![];
+    let mut different_filenodes = vec![];
+    for path in batch {
+        let source_file = source_files.get(&path.source_path);
+        if path.target_only && source_file != source_parent_files.get(&path.source_path) {
+            continue;
+        }
+        match (source_file, target_files.get(&path.target_path)) {
+            (None, None) => {}
+            (
+                Some((source_file_type, source_filenode_id)),
+                Some((target_file_type, target_filenode_id)),
+            ) if source_file_type == target_file_type => {
+                if source_filenode_id != target_filenode_id {
+                    different_filenodes.push((
+                        path.target_path,
+                        *source_filenode_id,
+                        *target_filenode_id,
+                    ));
+                }
+            }
+            _ => mismatches.push(path.target_path),
+        }
+    }
+
+    // Filenodes depend on the history of a file, so files with different
+    // filenodes may still have the same contents
+    let different_contents: Vec<_> = stream::iter(different_filenodes)
+        .map(
+            |(path, source_filenode_id, target_filenode_id)| async move {
+                let (source_envelope, target_envelope) = try_join!(
+                    source_filenode_id.load(ctx.clone(), source_repo.blobstore()),
+                    target_filenode_id.load(ctx.clone(), target_repo.blobstore()),
+                )?;
+                let differs = source_envelope.content_id() != target_envelope.content_id();
+                Ok::<_, Error>((path, differs))
+            },
+        )
+        .buffer_unordered(CONTENT_FETCH_CONCURRENCY)
+        .try_filter_map(|(path, differs)| future::ok(if differs { Some(path) } else { None }))
+        .try_collect()
+        .await?;
+    mismatches.extend(different_contents);
+
+    Ok(mismatches)
+}
+
+/// Files at the given paths in `mf_id`. Paths which are directories or
+/// don't exist are left out.
+async fn find_files(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    mf_id: HgManifestId,
+    paths: Vec<MPath>,
+) -> Result<HashMap<MPath, (FileType, HgFileNodeId)>, Error> {
+    mf_id
+        .find_entries(ctx.clone(), repo.get_blobstore(), paths)
+        .filter_map(|(path, entry)| match (path, entry) {
+            (Some(path), Entry::Leaf(leaf)) => Some((path, leaf)),
+            _ => None,
+        })
+        .collect_to()
+        .compat()
+        .await
+}
+
+/// If the parents of `target_cs_id` are not what the parents of
+/// `source_cs_id` were synced to, return the expected and the actual parents
+async fn find_wrong_parents<M>(
+    ctx: &CoreContext,
+    commit_syncer: &CommitSyncer<M>,
+    source_cs_id: ChangesetId,
+    target_cs_id: ChangesetId,
+) -> Result<Option<(Vec<ChangesetId>, Vec<ChangesetId>)>, Error>
+where
+    M: SyncedCommitMapping + Clone + 'static,
+{
+    use CommitSyncOutcome::*;
+
+    let source_parents = commit_syncer
+        .get_source_repo()
+        .get_changeset_parents_by_bonsai(ctx.clone(), source_cs_id)
+        .compat()
+        .await?;
+    // Root commits may be rebased onto any commit of the target repo when
+    // they are synced for the first time
+    if source_parents.is_empty() {
+        return Ok(None);
+    }
+
+    let mut expected = vec![];
+    for parent in source_parents {
+        let outcome = commit_syncer
+            .get_commit_sync_outcome(ctx.clone(), parent)
+            .await?;
+        let synced_parent = match outcome {
+            Some(RewrittenAs(cs_id)) | Some(EquivalentWorkingCopyAncestor(cs_id)) => cs_id,
+            Some(Preserved) => parent,
+            // Parents that don't sync into the target repo don't become
+            // parents of the target commit
+            Some(NotSyncCandidate) => continue,
+            // A parent without a sync outcome is a divergence of its own, and
+            // the parents of this commit can't be checked until it's fixed
+            None => return Ok(None),
+        };
+        if !expected.contains(&synced_parent) {
+            expected.push(synced_parent);
+        }
+    }
+
+    let actual = commit_syncer
+        .get_target_repo()
+        .get_changeset_parents_by_bonsai(ctx.clone(), target_cs_id)
+        .compat()
+        .await?;
+
+    if actual == expected {
+        Ok(None)
+    } else {
+        Ok(Some((expected, actual)))
+    }
+}
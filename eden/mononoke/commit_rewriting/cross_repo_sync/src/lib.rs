@@ -55,6 +55,7 @@ use pushrebase_hook::CrossRepoSyncPushrebaseHook;
 use types::{Source, Target};
 
 mod commit_syncer_args;
+pub mod divergence;
 mod pushrebase_hook;
 pub mod types;
 pub mod validation;
@@ -38,10 +38,14 @@ use movers::Mover;
 use sql_construct::SqlConstruct;
 use sql_ext::SqlConnections;
 use synced_commit_mapping::{
-    SqlSyncedCommitMapping, SyncedCommitMapping, SyncedCommitMappingEntry,
+    EquivalentWorkingCopyEntry, SqlSyncedCommitMapping, SyncedCommitMapping,
+    SyncedCommitMappingEntry,
 };
 
-use cross_repo_sync::{CommitSyncRepos, CommitSyncer};
+use cross_repo_sync::{
+    divergence::{check_commit_divergence, find_divergences_in_range, Divergence},
+    CommitSyncRepos, CommitSyncer,
+};
 use sql::rusqlite::Connection as SqliteConnection;
 
 fn identity_renamer(b: &BookmarkName) -> Option<BookmarkName> {
@@ -976,3 +980,116 @@ fn test_sync_parent_search(fb: FacebookInit) {
         sync_parent_search(fb).await;
     })
 }
+
+async fn sync_divergence(fb: FacebookInit) {
+    let ctx = CoreContext::test_mock(fb);
+    let (small_repo, megarepo, mapping) = prepare_repos_and_mapping().unwrap();
+    linear::initrepo(fb, &small_repo).await;
+    let linear = small_repo;
+    let repos = CommitSyncRepos::SmallToLarge {
+        small_repo: linear.clone(),
+        large_repo: megarepo.clone(),
+        mover: prefix_mover("linear"),
+        reverse_mover: reverse_prefix_mover("linear"),
+        bookmark_renamer: Arc::new(identity_renamer),
+        reverse_bookmark_renamer: Arc::new(identity_renamer),
+        version_name: CommitSyncConfigVersion("TEST_VERSION_NAME".to_string()),
+    };
+    let reverse_repos = CommitSyncRepos::LargeToSmall {
+        small_repo: linear.clone(),
+        large_repo: megarepo.clone(),
+        mover: reverse_prefix_mover("linear"),
+        reverse_mover: prefix_mover("linear"),
+        bookmark_renamer: Arc::new(identity_renamer),
+        reverse_bookmark_renamer: Arc::new(identity_renamer),
+        version_name: CommitSyncConfigVersion("TEST_VERSION_NAME".to_string()),
+    };
+    let config = CommitSyncer::new(mapping.clone(), repos);
+    let reverse_config = CommitSyncer::new(mapping.clone(), reverse_repos);
+
+    create_initial_commit(ctx.clone(), &megarepo).await;
+
+    // Sync the first two commits of linear into the megarepo
+    let linear_base_bcs_id = get_bcs_id(
+        ctx.clone(),
+        &config,
+        HgChangesetId::from_str("2d7d4ba9ce0a6ffd222de7785b249ead9c51c536").unwrap(),
+    )
+    .await;
+    let megarepo_base_bcs_id = rebase_root_on_master(ctx.clone(), &config, linear_base_bcs_id)
+        .await
+        .unwrap();
+    let linear_second_bcs_id = get_bcs_id(
+        ctx.clone(),
+        &config,
+        HgChangesetId::from_str("3e0e761030db6e479a7fb58b12881883f9f8c63f").unwrap(),
+    )
+    .await;
+    let megarepo_second_bcs_id = sync_to_master(ctx.clone(), &config, linear_second_bcs_id)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Everything that was synced matches
+    let report = find_divergences_in_range(
+        &ctx,
+        &reverse_config,
+        megarepo_base_bcs_id,
+        megarepo_second_bcs_id,
+        10,
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.checked, 2);
+    assert_eq!(report.divergences, vec![]);
+
+    // A commit that wasn't synced has no mapping
+    let megarepo_empty_bcs_id = create_empty_commit(ctx.clone(), &megarepo).await;
+    let report = find_divergences_in_range(
+        &ctx,
+        &reverse_config,
+        megarepo_base_bcs_id,
+        megarepo_empty_bcs_id,
+        10,
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.checked, 3);
+    assert_eq!(
+        report.divergences,
+        vec![Divergence::MissingMapping {
+            source_cs_id: megarepo_empty_bcs_id
+        }]
+    );
+
+    // And a commit whose working copy is mapped to another working copy differs
+    mapping
+        .insert_equivalent_working_copy(
+            ctx.clone(),
+            EquivalentWorkingCopyEntry {
+                large_repo_id: megarepo.get_repoid(),
+                large_bcs_id: megarepo_empty_bcs_id,
+                small_repo_id: linear.get_repoid(),
+                small_bcs_id: Some(linear_base_bcs_id),
+            },
+        )
+        .compat()
+        .await
+        .unwrap();
+    let divergence = check_commit_divergence(&ctx, &reverse_config, megarepo_empty_bcs_id)
+        .await
+        .unwrap();
+    match divergence {
+        Some(Divergence::ContentMismatch { target_cs_id, .. }) => {
+            assert_eq!(target_cs_id, linear_base_bcs_id)
+        }
+        _ => panic!("unexpected divergence {:?}", divergence),
+    }
+}
+
+#[fbinit::test]
+fn test_sync_divergence(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        sync_divergence(fb).await;
+    })
+}
@@ -0,0 +1,767 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+//! Validation of cross-repo commit sync configs.
+//!
+//! A commit sync config is only safe to use if every path and bookmark that
+//! is synced from a small repo to the large repo syncs back to where it came
+//! from, and if no two small repos claim the same part of the large repo.
+//! The checks here can't enumerate every possible path, so they probe the
+//! movers and bookmark renamers with paths and bookmarks derived from the
+//! config itself: the prefixes it mentions, their parent directories and a
+//! top-level path. Every problem found is reported with a concrete
+//! counterexample.
+
+use anyhow::Error;
+use ascii::AsciiString;
+use bookmark_renaming::{get_large_to_small_renamer, get_small_to_large_renamer, BookmarkRenamer};
+use bookmarks::BookmarkName;
+use mercurial_types::{MPath, MPathElement};
+use metaconfig_types::{
+    CommitSyncConfig, DefaultSmallToLargeCommitSyncPathAction, SmallRepoCommitSyncConfig,
+};
+use mononoke_types::RepositoryId;
+use movers::{get_large_to_small_mover, get_small_to_large_mover, Mover};
+use std::collections::{BTreeSet, HashMap};
+use thiserror::Error;
+
+/// File and bookmark name used to build the probe paths and bookmarks
+const PROBE: &str = "__sync_config_probe__";
+
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    #[error("Small repo {0}: cannot build movers: {1:#}")]
+    InvalidMovers(RepositoryId, Error),
+    #[error("Small repo {0}: cannot build bookmark renamers: {1:#}")]
+    InvalidBookmarkRenamers(RepositoryId, Error),
+    #[error("Small repo {small_repo_id}: {direction} mover failed on {path}: {error:#}")]
+    MoverFailure {
+        small_repo_id: RepositoryId,
+        direction: &'static str,
+        path: MPath,
+        error: Error,
+    },
+    #[error(
+        "Small repo {small_repo_id}: path {small_path} syncs to {large_path} in the large repo, which syncs back to {}",
+        MPath::display_opt(.back.as_ref())
+    )]
+    NonInvertibleSmallPath {
+        small_repo_id: RepositoryId,
+        small_path: MPath,
+        large_path: MPath,
+        back: Option<MPath>,
+    },
+    #[error(
+        "Small repo {small_repo_id}: large repo path {large_path} syncs to {small_path}, which syncs back to {}",
+        MPath::display_opt(.back.as_ref())
+    )]
+    NonInvertibleLargePath {
+        small_repo_id: RepositoryId,
+        large_path: MPath,
+        small_path: MPath,
+        back: Option<MPath>,
+    },
+    #[error(
+        "Small repos {first} and {second} both sync into {large_path} (from {first_path} and {second_path} respectively)"
+    )]
+    OverlappingPaths {
+        first: RepositoryId,
+        second: RepositoryId,
+        large_path: MPath,
+        first_path: MPath,
+        second_path: MPath,
+    },
+    #[error("Small repos {0} and {1} both preserve unmapped paths, e.g. {2}")]
+    MultiplePreservingRepos(RepositoryId, RepositoryId, MPath),
+    #[error("Small repos {first} and {second} both sync large repo bookmark {large_bookmark}")]
+    OverlappingBookmarks {
+        first: RepositoryId,
+        second: RepositoryId,
+        large_bookmark: BookmarkName,
+    },
+    #[error(
+        "Small repo {small_repo_id}: bookmark {small_bookmark} is renamed to {large_bookmark} in the large repo, which is renamed back to {}",
+        display_bookmark_opt(.back.as_ref())
+    )]
+    NonInvertibleSmallBookmark {
+        small_repo_id: RepositoryId,
+        small_bookmark: BookmarkName,
+        large_bookmark: BookmarkName,
+        back: Option<BookmarkName>,
+    },
+    #[error(
+        "Small repo {small_repo_id}: large repo bookmark {large_bookmark} is renamed to {small_bookmark}, which is renamed back to {}",
+        display_bookmark_opt(.back.as_ref())
+    )]
+    NonInvertibleLargeBookmark {
+        small_repo_id: RepositoryId,
+        large_bookmark: BookmarkName,
+        small_bookmark: BookmarkName,
+        back: Option<BookmarkName>,
+    },
+}
+
+fn display_bookmark_opt(bookmark: Option<&BookmarkName>) -> String {
+    match bookmark {
+        Some(bookmark) => bookmark.to_string(),
+        None => "(none)".to_string(),
+    }
+}
+
+/// Validate the movers and bookmark renamers of every small repo in
+/// `commit_sync_config`. Returns all the problems found, so an empty result
+/// means the config is valid.
+pub fn validate_commit_sync_config(commit_sync_config: &CommitSyncConfig) -> Vec<ValidationError> {
+    let mut small_repo_ids: Vec<_> = commit_sync_config.small_repos.keys().cloned().collect();
+    small_repo_ids.sort();
+
+    let mut errors = vec![];
+    let mut movers = HashMap::new();
+    let mut renamers = HashMap::new();
+    for small_repo_id in &small_repo_ids {
+        let small_repo_id = *small_repo_id;
+        // Overlaps between small repos are checked with small-to-large movers
+        // only, as no large-to-small mover can be built for a small repo that
+        // overlaps with another one
+        match get_small_to_large_mover(commit_sync_config, small_repo_id) {
+            Ok(small_to_large) => {
+                match get_large_to_small_mover(commit_sync_config, small_repo_id) {
+                    Ok(large_to_small) => errors.extend(validate_movers(
+                        commit_sync_config,
+                        small_repo_id,
+                        &small_to_large,
+                        &large_to_small,
+                    )),
+                    Err(e) => errors.push(ValidationError::InvalidMovers(small_repo_id, e)),
+                }
+                movers.insert(small_repo_id, small_to_large);
+            }
+            Err(e) => errors.push(ValidationError::InvalidMovers(small_repo_id, e)),
+        }
+
+        match (
+            get_small_to_large_renamer(commit_sync_config, small_repo_id),
+            get_large_to_small_renamer(commit_sync_config, small_repo_id),
+        ) {
+            (Ok(small_to_large), Ok(large_to_small)) => {
+                errors.extend(validate_bookmark_renamers(
+                    commit_sync_config,
+                    small_repo_id,
+                    &small_to_large,
+                    &large_to_small,
+                ));
+                renamers.insert(small_repo_id, large_to_small);
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                errors.push(ValidationError::InvalidBookmarkRenamers(small_repo_id, e));
+            }
+        }
+    }
+
+    for (idx, first) in small_repo_ids.iter().enumerate() {
+        for second in &small_repo_ids[idx + 1..] {
+            if let (Some(first_mover), Some(second_mover)) = (movers.get(first), movers.get(second))
+            {
+                errors.extend(validate_images(
+                    commit_sync_config,
+                    (*first, first_mover),
+                    (*second, second_mover),
+                ));
+            }
+            if let (Some(first_renamer), Some(second_renamer)) =
+                (renamers.get(first), renamers.get(second))
+            {
+                errors.extend(validate_bookmark_prefixes(
+                    commit_sync_config,
+                    (*first, first_renamer),
+                    (*second, second_renamer),
+                ));
+            }
+        }
+    }
+
+    errors
+}
+
+fn probe_element() -> MPathElement {
+    MPathElement::new(PROBE.as_bytes().to_vec()).expect("probe file name is a valid path")
+}
+
+fn probe_bookmark() -> BookmarkName {
+    BookmarkName::new(PROBE).expect("probe bookmark name is a valid bookmark")
+}
+
+/// Paths of a probe file at the top level and in each of `prefixes` and
+/// their parent directories
+fn probe_paths<'a>(prefixes: impl IntoIterator<Item = &'a MPath>) -> BTreeSet<MPath> {
+    let probe = probe_element();
+    let mut paths = BTreeSet::new();
+    paths.insert(MPath::join_opt_element(None, &probe));
+    for prefix in prefixes {
+        for dir in prefix.clone().into_parent_dir_iter() {
+            paths.insert(dir.join_element(Some(&probe)));
+        }
+    }
+    paths
+}
+
+/// A part of the large repo that a small repo syncs into
+struct Image<'a> {
+    large_prefix: &'a MPath,
+    /// The small repo directory that syncs into `large_prefix`, or `None` if
+    /// `large_prefix` is the prefix prepended to the unmapped paths
+    small_prefix: Option<&'a MPath>,
+}
+
+impl<'a> Image<'a> {
+    /// The small repo path that syncs into `large_path`, which must be under
+    /// `large_prefix`
+    fn small_path(&self, large_path: &MPath) -> Option<MPath> {
+        let suffix = large_path
+            .into_iter()
+            .skip(self.large_prefix.num_components());
+        MPath::join_opt(self.small_prefix, suffix)
+    }
+}
+
+fn get_images(small_repo_config: &SmallRepoCommitSyncConfig) -> Vec<Image> {
+    let mut images: Vec<_> = small_repo_config
+        .map
+        .iter()
+        .map(|(small_prefix, large_prefix)| Image {
+            large_prefix,
+            small_prefix: Some(small_prefix),
+        })
+        .collect();
+    if let DefaultSmallToLargeCommitSyncPathAction::PrependPrefix(prefix) =
+        &small_repo_config.default_action
+    {
+        images.push(Image {
+            large_prefix: prefix,
+            small_prefix: None,
+        });
+    }
+    images
+}
+
+/// Check that every probe path syncs back to itself after a roundtrip
+/// through the large repo and vice versa.
+///
+/// Small repo paths are only probed in the directories this small repo's
+/// config mentions. In particular, a small repo that preserves unmapped
+/// paths can't have files in the directories other small repos sync into,
+/// but that depends on the contents of the repo, not on the config.
+fn validate_movers(
+    commit_sync_config: &CommitSyncConfig,
+    small_repo_id: RepositoryId,
+    small_to_large: &Mover,
+    large_to_small: &Mover,
+) -> Vec<ValidationError> {
+    let small_repo_config = &commit_sync_config.small_repos[&small_repo_id];
+    let mut errors = vec![];
+
+    let small_prefixes = get_images(small_repo_config)
+        .into_iter()
+        .filter_map(|image| image.small_prefix);
+    for small_path in probe_paths(small_prefixes) {
+        let large_path = match small_to_large(&small_path) {
+            Ok(Some(large_path)) => large_path,
+            Ok(None) => continue,
+            Err(error) => {
+                errors.push(ValidationError::MoverFailure {
+                    small_repo_id,
+                    direction: "small-to-large",
+                    path: small_path,
+                    error,
+                });
+                continue;
+            }
+        };
+        match large_to_small(&large_path) {
+            Ok(back) if back.as_ref() == Some(&small_path) => {}
+            Ok(back) => errors.push(ValidationError::NonInvertibleSmallPath {
+                small_repo_id,
+                small_path,
+                large_path,
+                back,
+            }),
+            Err(error) => errors.push(ValidationError::MoverFailure {
+                small_repo_id,
+                direction: "large-to-small",
+                path: large_path,
+                error,
+            }),
+        }
+    }
+
+    // Unmapped small repo paths are preserved as they are, so the mapped
+    // small repo directories are probed in the large repo too
+    let large_prefixes = commit_sync_config
+        .small_repos
+        .values()
+        .flat_map(get_images)
+        .map(|image| image.large_prefix)
+        .chain(small_repo_config.map.keys());
+    for large_path in probe_paths(large_prefixes) {
+        let small_path = match large_to_small(&large_path) {
+            Ok(Some(small_path)) => small_path,
+            Ok(None) => continue,
+            Err(error) => {
+                errors.push(ValidationError::MoverFailure {
+                    small_repo_id,
+                    direction: "large-to-small",
+                    path: large_path,
+                    error,
+                });
+                continue;
+            }
+        };
+        match small_to_large(&small_path) {
+            Ok(back) if back.as_ref() == Some(&large_path) => {}
+            Ok(back) => errors.push(ValidationError::NonInvertibleLargePath {
+                small_repo_id,
+                large_path,
+                small_path,
+                back,
+            }),
+            Err(error) => errors.push(ValidationError::MoverFailure {
+                small_repo_id,
+                direction: "small-to-large",
+                path: small_path,
+                error,
+            }),
+        }
+    }
+
+    errors
+}
+
+/// Check that two small repos don't sync into overlapping parts of the large
+/// repo. The only allowed overlap is a directory that both small repos sync
+/// from the same small repo path into the same large repo path, as such a
+/// directory is identical in both small repos.
+fn validate_images(
+    commit_sync_config: &CommitSyncConfig,
+    (first, first_mover): (RepositoryId, &Mover),
+    (second, second_mover): (RepositoryId, &Mover),
+) -> Vec<ValidationError> {
+    let first_config = &commit_sync_config.small_repos[&first];
+    let second_config = &commit_sync_config.small_repos[&second];
+    let probe = probe_element();
+    let mut errors = vec![];
+
+    for first_image in get_images(first_config) {
+        for second_image in get_images(second_config) {
+            let longer_prefix = if first_image
+                .large_prefix
+                .is_prefix_of(second_image.large_prefix)
+            {
+                second_image.large_prefix
+            } else if second_image
+                .large_prefix
+                .is_prefix_of(first_image.large_prefix)
+            {
+                first_image.large_prefix
+            } else {
+                continue;
+            };
+            if first_image.large_prefix == second_image.large_prefix
+                && first_image.small_prefix.is_some()
+                && first_image.small_prefix == second_image.small_prefix
+            {
+                continue;
+            }
+
+            let large_path = longer_prefix.join_element(Some(&probe));
+            let (first_path, second_path) = match (
+                first_image.small_path(&large_path),
+                second_image.small_path(&large_path),
+            ) {
+                (Some(first_path), Some(second_path)) => (first_path, second_path),
+                _ => continue,
+            };
+
+            // A more specific mapping may take precedence over the image, so
+            // only report paths that both small repos really sync there
+            let syncs_to_large_path = |mover: &Mover, path: &MPath| match mover(path) {
+                Ok(moved) => moved.as_ref() == Some(&large_path),
+                Err(_) => false,
+            };
+            if syncs_to_large_path(first_mover, &first_path)
+                && syncs_to_large_path(second_mover, &second_path)
+            {
+                errors.push(ValidationError::OverlappingPaths {
+                    first,
+                    second,
+                    large_path,
+                    first_path,
+                    second_path,
+                });
+            }
+        }
+    }
+
+    if first_config.default_action == DefaultSmallToLargeCommitSyncPathAction::Preserve
+        && second_config.default_action == DefaultSmallToLargeCommitSyncPathAction::Preserve
+    {
+        errors.push(ValidationError::MultiplePreservingRepos(
+            first,
+            second,
+            MPath::join_opt_element(None, &probe),
+        ));
+    }
+
+    errors
+}
+
+fn prefixed_bookmark(prefix: &AsciiString, bookmark: &BookmarkName) -> BookmarkName {
+    let mut prefixed = prefix.clone();
+    prefixed.push_str(bookmark.as_ascii());
+    BookmarkName::new_ascii(prefixed)
+}
+
+/// Check that probe bookmarks are renamed back to themselves after a
+/// roundtrip through the large repo and vice versa
+fn validate_bookmark_renamers(
+    commit_sync_config: &CommitSyncConfig,
+    small_repo_id: RepositoryId,
+    small_to_large: &BookmarkRenamer,
+    large_to_small: &BookmarkRenamer,
+) -> Vec<ValidationError> {
+    let prefix = &commit_sync_config.small_repos[&small_repo_id].bookmark_prefix;
+    let common_bookmarks = &commit_sync_config.common_pushrebase_bookmarks;
+    let mut errors = vec![];
+
+    // A small repo bookmark which happens to be a common bookmark once
+    // prefixed can't be renamed back
+    let unprefixed_common_bookmarks = common_bookmarks.iter().filter_map(|bookmark| {
+        if bookmark.as_str().starts_with(prefix.as_str()) && bookmark.as_str() != prefix.as_str() {
+            Some(BookmarkName::new_ascii(
+                bookmark.as_ascii()[prefix.len()..].into(),
+            ))
+        } else {
+            None
+        }
+    });
+    let small_bookmarks: BTreeSet<_> = common_bookmarks
+        .iter()
+        .cloned()
+        .chain(unprefixed_common_bookmarks)
+        .chain(Some(probe_bookmark()))
+        .collect();
+    for small_bookmark in small_bookmarks {
+        let large_bookmark = match small_to_large(&small_bookmark) {
+            Some(large_bookmark) => large_bookmark,
+            None => continue,
+        };
+        let back = large_to_small(&large_bookmark);
+        if back.as_ref() != Some(&small_bookmark) {
+            errors.push(ValidationError::NonInvertibleSmallBookmark {
+                small_repo_id,
+                small_bookmark,
+                large_bookmark,
+                back,
+            });
+        }
+    }
+
+    let large_bookmarks: BTreeSet<_> = common_bookmarks
+        .iter()
+        .map(|bookmark| prefixed_bookmark(prefix, bookmark))
+        .chain(common_bookmarks.iter().cloned())
+        .chain(Some(prefixed_bookmark(prefix, &probe_bookmark())))
+        .collect();
+    for large_bookmark in large_bookmarks {
+        let small_bookmark = match large_to_small(&large_bookmark) {
+            Some(small_bookmark) => small_bookmark,
+            None => continue,
+        };
+        let back = small_to_large(&small_bookmark);
+        if back.as_ref() != Some(&large_bookmark) {
+            errors.push(ValidationError::NonInvertibleLargeBookmark {
+                small_repo_id,
+                large_bookmark,
+                small_bookmark,
+                back,
+            });
+        }
+    }
+
+    errors
+}
+
+/// Check that no prefixed large repo bookmark is synced to two small repos,
+/// i.e. that the bookmark prefixes are not prefixes of each other
+fn validate_bookmark_prefixes(
+    commit_sync_config: &CommitSyncConfig,
+    (first, first_renamer): (RepositoryId, &BookmarkRenamer),
+    (second, second_renamer): (RepositoryId, &BookmarkRenamer),
+) -> Vec<ValidationError> {
+    let probe = probe_bookmark();
+    let candidates = vec![
+        prefixed_bookmark(
+            &commit_sync_config.small_repos[&first].bookmark_prefix,
+            &probe,
+        ),
+        prefixed_bookmark(
+            &commit_sync_config.small_repos[&second].bookmark_prefix,
+            &probe,
+        ),
+    ];
+
+    candidates
+        .into_iter()
+        .find(|large_bookmark| {
+            !commit_sync_config
+                .common_pushrebase_bookmarks
+                .contains(large_bookmark)
+                && first_renamer(large_bookmark).is_some()
+                && second_renamer(large_bookmark).is_some()
+        })
+        .map(|large_bookmark| ValidationError::OverlappingBookmarks {
+            first,
+            second,
+            large_bookmark,
+        })
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use maplit::hashmap;
+    use metaconfig_types::{CommitSyncConfigVersion, CommitSyncDirection};
+
+    fn mp(s: &'static str) -> MPath {
+        MPath::new(s).unwrap()
+    }
+
+    fn bookmark(s: &'static str) -> BookmarkName {
+        BookmarkName::new(s).unwrap()
+    }
+
+    fn small_repo_config(
+        default_action: DefaultSmallToLargeCommitSyncPathAction,
+        map: HashMap<MPath, MPath>,
+        bookmark_prefix: &'static str,
+    ) -> SmallRepoCommitSyncConfig {
+        SmallRepoCommitSyncConfig {
+            default_action,
+            map,
+            bookmark_prefix: AsciiString::from_ascii(bookmark_prefix.to_string()).unwrap(),
+            direction: CommitSyncDirection::LargeToSmall,
+        }
+    }
+
+    fn commit_sync_config(
+        small_repos: HashMap<RepositoryId, SmallRepoCommitSyncConfig>,
+    ) -> CommitSyncConfig {
+        CommitSyncConfig {
+            large_repo_id: RepositoryId::new(0),
+            common_pushrebase_bookmarks: vec![bookmark("master")],
+            small_repos,
+            version_name: CommitSyncConfigVersion("TEST_VERSION_NAME".to_string()),
+        }
+    }
+
+    /// The non-overlapping config from the movers tests: repo 1 preserves
+    /// unmapped paths, repo 2 moves them into a subdirectory and shares a
+    /// single preserved directory with repo 1.
+    fn valid_config() -> CommitSyncConfig {
+        commit_sync_config(hashmap! {
+            RepositoryId::new(1) => small_repo_config(
+                DefaultSmallToLargeCommitSyncPathAction::Preserve,
+                hashmap! {
+                    mp("preserved2") => mp("repo1-rest/preserved2"),
+                },
+                "b1/",
+            ),
+            RepositoryId::new(2) => small_repo_config(
+                DefaultSmallToLargeCommitSyncPathAction::PrependPrefix(mp("shifted2")),
+                hashmap! {
+                    mp("preserved2") => mp("preserved2"),
+                    mp("sub1") => mp("repo2-rest/sub1"),
+                    mp("sub2") => mp("repo2-rest/sub2"),
+                },
+                "b2/",
+            ),
+        })
+    }
+
+    #[test]
+    fn test_valid_config() {
+        let errors = validate_commit_sync_config(&valid_config());
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_shared_identical_dir() {
+        let mut config = valid_config();
+        config
+            .small_repos
+            .get_mut(&RepositoryId::new(1))
+            .unwrap()
+            .map
+            .insert(mp("preserved2"), mp("preserved2"));
+        let errors = validate_commit_sync_config(&config);
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_non_invertible_mover() {
+        let config = commit_sync_config(hashmap! {
+            RepositoryId::new(1) => small_repo_config(
+                DefaultSmallToLargeCommitSyncPathAction::Preserve,
+                hashmap! {
+                    mp("a") => mp("b"),
+                },
+                "b1/",
+            ),
+        });
+        let errors = validate_commit_sync_config(&config);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        match &errors[0] {
+            ValidationError::NonInvertibleLargePath {
+                large_path,
+                small_path,
+                back,
+                ..
+            } => {
+                assert_eq!(large_path, &mp("a/__sync_config_probe__"));
+                assert_eq!(small_path, &mp("a/__sync_config_probe__"));
+                assert_eq!(back, &Some(mp("b/__sync_config_probe__")));
+            }
+            e => panic!("unexpected error {}", e),
+        }
+    }
+
+    #[test]
+    fn test_two_dirs_into_one() {
+        let config = commit_sync_config(hashmap! {
+            RepositoryId::new(1) => small_repo_config(
+                DefaultSmallToLargeCommitSyncPathAction::PrependPrefix(mp("repo1")),
+                hashmap! {
+                    mp("a") => mp("dir"),
+                    mp("b") => mp("dir"),
+                },
+                "b1/",
+            ),
+        });
+        let errors = validate_commit_sync_config(&config);
+        assert!(errors.iter().any(|e| match e {
+            ValidationError::NonInvertibleSmallPath { large_path, .. } =>
+                large_path == &mp("dir/__sync_config_probe__"),
+            _ => false,
+        }));
+    }
+
+    #[test]
+    fn test_overlapping_images() {
+        let config = commit_sync_config(hashmap! {
+            RepositoryId::new(1) => small_repo_config(
+                DefaultSmallToLargeCommitSyncPathAction::PrependPrefix(mp("repo1")),
+                hashmap! {
+                    mp("a") => mp("shared/a"),
+                },
+                "b1/",
+            ),
+            RepositoryId::new(2) => small_repo_config(
+                DefaultSmallToLargeCommitSyncPathAction::PrependPrefix(mp("repo2")),
+                hashmap! {
+                    mp("b") => mp("shared"),
+                },
+                "b2/",
+            ),
+        });
+        let errors = validate_commit_sync_config(&config);
+        let overlaps: Vec<_> = errors
+            .iter()
+            .filter_map(|e| match e {
+                ValidationError::OverlappingPaths {
+                    large_path,
+                    first_path,
+                    second_path,
+                    ..
+                } => Some((large_path.clone(), first_path.clone(), second_path.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            overlaps,
+            vec![(
+                mp("shared/a/__sync_config_probe__"),
+                mp("a/__sync_config_probe__"),
+                mp("b/a/__sync_config_probe__"),
+            )]
+        );
+    }
+
+    #[test]
+    fn test_multiple_preserving_repos() {
+        let config = commit_sync_config(hashmap! {
+            RepositoryId::new(1) => small_repo_config(
+                DefaultSmallToLargeCommitSyncPathAction::Preserve,
+                HashMap::new(),
+                "b1/",
+            ),
+            RepositoryId::new(2) => small_repo_config(
+                DefaultSmallToLargeCommitSyncPathAction::Preserve,
+                HashMap::new(),
+                "b2/",
+            ),
+        });
+        let errors = validate_commit_sync_config(&config);
+        assert!(errors.iter().any(|e| match e {
+            ValidationError::MultiplePreservingRepos(..) => true,
+            _ => false,
+        }));
+    }
+
+    #[test]
+    fn test_overlapping_bookmark_prefixes() {
+        let mut config = valid_config();
+        config
+            .small_repos
+            .get_mut(&RepositoryId::new(2))
+            .unwrap()
+            .bookmark_prefix = AsciiString::from_ascii("b1/2/".to_string()).unwrap();
+        let errors = validate_commit_sync_config(&config);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        match &errors[0] {
+            ValidationError::OverlappingBookmarks { large_bookmark, .. } => {
+                assert_eq!(large_bookmark, &bookmark("b1/2/__sync_config_probe__"));
+            }
+            e => panic!("unexpected error {}", e),
+        }
+    }
+
+    #[test]
+    fn test_non_invertible_bookmark() {
+        let mut config = valid_config();
+        config
+            .common_pushrebase_bookmarks
+            .push(bookmark("b1/release"));
+        let errors = validate_commit_sync_config(&config);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        match &errors[0] {
+            ValidationError::NonInvertibleSmallBookmark {
+                small_bookmark,
+                large_bookmark,
+                back,
+                ..
+            } => {
+                assert_eq!(small_bookmark, &bookmark("release"));
+                assert_eq!(large_bookmark, &bookmark("b1/release"));
+                assert_eq!(back, &Some(bookmark("b1/release")));
+            }
+            e => panic!("unexpected error {}", e),
+        }
+    }
+}
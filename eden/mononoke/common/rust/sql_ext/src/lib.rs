@@ -5,6 +5,8 @@
  * GNU General Public License version 2.
  */
 
+#[cfg(not(fbcode_build))]
+mod mysql;
 #[cfg(not(fbcode_build))]
 mod oss;
 pub mod replication;
@@ -12,6 +14,8 @@ mod sqlite;
 
 use sql::{Connection, Transaction};
 
+#[cfg(not(fbcode_build))]
+pub use mysql::{create_mysql_connections, create_mysql_schema, mysql_shard_address};
 pub use sqlite::{open_sqlite_in_memory, open_sqlite_path};
 
 #[derive(Clone)]
@@ -9,9 +9,12 @@ use crate::{facebook::*, *};
 
 use anyhow::Error;
 use fbinit::FacebookInit;
-use futures_ext::BoxFuture;
+use futures_ext::{BoxFuture, FutureExt};
+use futures_old::future::IntoFuture;
 use slog::Logger;
 
+use crate::mysql::create_mysql_connections;
+
 macro_rules! fb_unimplemented {
     () => {
         unimplemented!("This is implemented only for fbcode_build!")
@@ -20,11 +23,19 @@ macro_rules! fb_unimplemented {
 
 impl PoolSizeConfig {
     pub fn for_regular_connection() -> Self {
-        fb_unimplemented!()
+        Self {
+            write_pool_size: 5,
+            read_pool_size: 10,
+            read_master_pool_size: 5,
+        }
     }
 
     pub fn for_sharded_connection() -> Self {
-        fb_unimplemented!()
+        Self {
+            write_pool_size: 1,
+            read_pool_size: 2,
+            read_master_pool_size: 1,
+        }
     }
 }
 
@@ -40,15 +51,32 @@ pub fn create_myrouter_connections(
     fb_unimplemented!()
 }
 
-pub fn myrouter_ready(_: Option<String>, _: MysqlOptions, _: Logger) -> BoxFuture<(), Error> {
+pub fn myrouter_ready(
+    _: Option<String>,
+    mysql_options: MysqlOptions,
+    _: Logger,
+) -> BoxFuture<(), Error> {
+    // Direct MySQL connections don't go through MyRouter, so there is nothing to wait for.
+    if mysql_options.myrouter_port.is_none() {
+        return Ok::<_, Error>(()).into_future().boxify();
+    }
     fb_unimplemented!()
 }
 
+/// Connect directly to a MySQL server.  Outside of Facebook, the database address is a
+/// connection string rather than an XDB tier; see the `mysql` module for its format.
 pub fn create_raw_xdb_connections(
     _: FacebookInit,
-    _: String,
-    _: ReadConnectionType,
-    _: bool,
+    address: String,
+    read_con_type: ReadConnectionType,
+    readonly: bool,
 ) -> BoxFuture<SqlConnections, Error> {
-    fb_unimplemented!()
+    create_mysql_connections(
+        &address,
+        read_con_type,
+        PoolSizeConfig::for_regular_connection(),
+        readonly,
+    )
+    .into_future()
+    .boxify()
 }
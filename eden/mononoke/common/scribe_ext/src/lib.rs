@@ -20,16 +20,30 @@ use anyhow as _; // oss uses anyhow
 
 #[cfg(not(fbcode_build))]
 mod oss;
+mod rotating;
+mod socket;
+mod tail;
+
+pub use rotating::RotatingFileSink;
+pub use socket::UnixSocketSink;
+pub use tail::{ScribeEntry, ScribeOffset, ScribeTailer};
 
 #[cfg(not(fbcode_build))]
 pub use oss::ScribeClientImplementation;
 #[cfg(fbcode_build)]
 pub use scuba::ScribeClientImplementation;
 
+/// A destination for scribe samples.
+pub trait ScribeSink: Send + Sync {
+    /// Write a sample to a category.
+    fn offer(&self, category: &str, sample: &str) -> Result<(), Error>;
+}
+
 #[derive(Clone)]
 pub enum Scribe {
     Client(Arc<ScribeClientImplementation>),
     LogToFile(Arc<Mutex<PathBuf>>),
+    Sink(Arc<dyn ScribeSink>),
 }
 
 impl ::std::fmt::Debug for Scribe {
@@ -37,6 +51,7 @@ impl ::std::fmt::Debug for Scribe {
         match self {
             Self::Client(_) => f.debug_struct("Scribe::Client").finish(),
             Self::LogToFile(_) => f.debug_struct("Scribe::LogToFile").finish(),
+            Self::Sink(_) => f.debug_struct("Scribe::Sink").finish(),
         }
     }
 }
@@ -50,6 +65,22 @@ impl Scribe {
         Self::LogToFile(Arc::new(Mutex::new(dir_path)))
     }
 
+    pub fn new_to_rotating_files(
+        dir_path: PathBuf,
+        max_file_size: u64,
+        max_files: Option<usize>,
+    ) -> Self {
+        Self::new_with_sink(RotatingFileSink::new(dir_path, max_file_size, max_files))
+    }
+
+    pub fn new_to_socket(socket_path: PathBuf) -> Self {
+        Self::new_with_sink(UnixSocketSink::new(socket_path))
+    }
+
+    pub fn new_with_sink(sink: impl ScribeSink + 'static) -> Self {
+        Self::Sink(Arc::new(sink))
+    }
+
     pub fn offer(&self, category: &str, sample: &str) -> Result<(), Error> {
         use Scribe::*;
 
@@ -68,12 +99,7 @@ impl Scribe {
             }
             LogToFile(dir_path) => {
                 let dir_path = dir_path.lock().unwrap();
-                let is_valid_category = category
-                    .chars()
-                    .all(|c| char::is_alphanumeric(c) || c == '-' || c == '_');
-                if !is_valid_category {
-                    return Err(anyhow!("invalid category: {}", category));
-                }
+                validate_category(category)?;
                 let filename = dir_path.join(category);
                 let mut file = OpenOptions::new()
                     .create(true)
@@ -82,6 +108,19 @@ impl Scribe {
                 ::std::writeln!(file, "{}", sample)?;
                 Ok(())
             }
+            Sink(sink) => sink.offer(category, sample),
         }
     }
 }
+
+/// Check a category can safely be used as a file name.
+pub(crate) fn validate_category(category: &str) -> Result<(), Error> {
+    let is_valid_category = !category.is_empty()
+        && category
+            .chars()
+            .all(|c| char::is_alphanumeric(c) || c == '-' || c == '_');
+    if !is_valid_category {
+        return Err(anyhow!("invalid category: {}", category));
+    }
+    Ok(())
+}
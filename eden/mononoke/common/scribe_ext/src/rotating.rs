@@ -0,0 +1,151 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Scribe sink that appends samples to rotating local files.
+//!
+//! Each category is written to its own directory, as a sequence of numbered
+//! segment files.  Like `UnixSocketSink`, each sample is written as a single
+//! line containing a JSON object with `category` and `message` fields, so
+//! samples may contain newlines.  When the current segment
+//! reaches the maximum size a new segment is started, and the oldest
+//! segments are removed if there are more than the maximum number of files.
+//! Segments can be read back with `ScribeTailer`.
+//!
+//! A directory should only have a single writer process.
+
+use anyhow::{Context, Error};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::{validate_category, ScribeSink};
+
+const SEGMENT_EXTENSION: &str = "log";
+
+/// Path of the segment file `segment` for a category directory.
+pub(crate) fn segment_path(category_dir: &Path, segment: u64) -> PathBuf {
+    category_dir.join(format!("{:016}.{}", segment, SEGMENT_EXTENSION))
+}
+
+/// List the segment numbers in a category directory, in ascending order.
+pub(crate) fn list_segments(category_dir: &Path) -> Result<Vec<u64>, Error> {
+    if !category_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(category_dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some(SEGMENT_EXTENSION) {
+            continue;
+        }
+        if let Some(segment) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<u64>().ok())
+        {
+            segments.push(segment);
+        }
+    }
+    segments.sort_unstable();
+    Ok(segments)
+}
+
+struct CategoryWriter {
+    dir: PathBuf,
+    segment: u64,
+    size: u64,
+    file: File,
+}
+
+impl CategoryWriter {
+    fn open(dir: PathBuf) -> Result<Self, Error> {
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        let segment = list_segments(&dir)?.last().copied().unwrap_or(0);
+        Self::open_segment(dir, segment)
+    }
+
+    fn open_segment(dir: PathBuf, segment: u64) -> Result<Self, Error> {
+        let path = segment_path(&dir, segment);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            dir,
+            segment,
+            size,
+            file,
+        })
+    }
+
+    fn rotate(&mut self, max_files: Option<usize>) -> Result<(), Error> {
+        *self = Self::open_segment(self.dir.clone(), self.segment + 1)?;
+        if let Some(max_files) = max_files {
+            let segments = list_segments(&self.dir)?;
+            let excess = segments.len().saturating_sub(max_files.max(1));
+            for segment in &segments[..excess] {
+                fs::remove_file(segment_path(&self.dir, *segment))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Scribe sink that writes each category to rotating files in a directory.
+pub struct RotatingFileSink {
+    dir: PathBuf,
+    max_file_size: u64,
+    max_files: Option<usize>,
+    writers: Mutex<HashMap<String, CategoryWriter>>,
+}
+
+impl RotatingFileSink {
+    /// Create a sink writing to `dir`.  Segments are rotated once they
+    /// exceed `max_file_size` bytes, and at most `max_files` segments are
+    /// kept for each category.
+    pub fn new(dir: PathBuf, max_file_size: u64, max_files: Option<usize>) -> Self {
+        Self {
+            dir,
+            max_file_size,
+            max_files,
+            writers: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl ScribeSink for RotatingFileSink {
+    fn offer(&self, category: &str, sample: &str) -> Result<(), Error> {
+        validate_category(category)?;
+        // Write the record and newline together so that readers never see
+        // a partial line followed by another record.
+        let mut line = serde_json::to_vec(&json!({
+            "category": category,
+            "message": sample,
+        }))?;
+        line.push(b'\n');
+
+        let mut writers = self.writers.lock().expect("lock poisoned");
+        if !writers.contains_key(category) {
+            let writer = CategoryWriter::open(self.dir.join(category))?;
+            writers.insert(category.to_string(), writer);
+        }
+        let writer = writers.get_mut(category).expect("writer was just inserted");
+
+        if writer.size > 0 && writer.size + line.len() as u64 > self.max_file_size {
+            writer.rotate(self.max_files)?;
+        }
+
+        writer.file.write_all(&line)?;
+        writer.size += line.len() as u64;
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Scribe sink that sends samples to a Unix domain socket.
+//!
+//! Each sample is sent as a single line containing a JSON object with
+//! `category` and `message` fields.
+
+use anyhow::{Context, Error};
+use serde_json::json;
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::{validate_category, ScribeSink};
+
+/// Scribe sink that writes samples to a listener on a Unix socket.
+pub struct UnixSocketSink {
+    path: PathBuf,
+    stream: Mutex<Option<UnixStream>>,
+}
+
+impl UnixSocketSink {
+    /// Create a sink that connects to the socket at `path`.  The connection
+    /// is made when the first sample is offered, and remade if it fails.
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            stream: Mutex::new(None),
+        }
+    }
+
+    fn connect(&self) -> Result<UnixStream, Error> {
+        UnixStream::connect(&self.path)
+            .with_context(|| format!("failed to connect to {}", self.path.display()))
+    }
+}
+
+impl ScribeSink for UnixSocketSink {
+    fn offer(&self, category: &str, sample: &str) -> Result<(), Error> {
+        validate_category(category)?;
+        let mut line = serde_json::to_vec(&json!({
+            "category": category,
+            "message": sample,
+        }))?;
+        line.push(b'\n');
+
+        let mut stream = self.stream.lock().expect("lock poisoned");
+        if let Some(connected) = stream.as_mut() {
+            if connected.write_all(&line).is_ok() {
+                return Ok(());
+            }
+        }
+        // Not connected yet, or the listener has gone away.  Try again on
+        // a new connection.
+        let mut connected = self.connect()?;
+        let res = connected.write_all(&line);
+        *stream = Some(connected);
+        Ok(res?)
+    }
+}
@@ -0,0 +1,232 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Read samples written by `RotatingFileSink`.
+
+use anyhow::{anyhow, Context, Error};
+use serde_json::Value;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::rotating::{list_segments, segment_path};
+use crate::validate_category;
+
+/// Position of a sample in a category.  Offsets are ordered by the order
+/// the samples were written in.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ScribeOffset {
+    pub segment: u64,
+    pub offset: u64,
+}
+
+impl fmt::Display for ScribeOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.segment, self.offset)
+    }
+}
+
+impl FromStr for ScribeOffset {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut parts = s.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(segment), Some(offset)) => Ok(ScribeOffset {
+                segment: segment.parse()?,
+                offset: offset.parse()?,
+            }),
+            _ => Err(anyhow!("invalid scribe offset: {}", s)),
+        }
+    }
+}
+
+/// A sample read from a category, together with its position.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScribeEntry {
+    pub offset: ScribeOffset,
+    pub sample: String,
+}
+
+/// Reads the samples of a category written to a directory by
+/// `RotatingFileSink`.
+pub struct ScribeTailer {
+    category_dir: PathBuf,
+}
+
+impl ScribeTailer {
+    pub fn new(dir: PathBuf, category: &str) -> Result<Self, Error> {
+        validate_category(category)?;
+        Ok(Self {
+            category_dir: dir.join(category),
+        })
+    }
+
+    /// The offset of the oldest sample that is still available.
+    pub fn start(&self) -> Result<ScribeOffset, Error> {
+        let segment = list_segments(&self.category_dir)?
+            .first()
+            .copied()
+            .unwrap_or(0);
+        Ok(ScribeOffset { segment, offset: 0 })
+    }
+
+    /// Read up to `limit` samples starting at `from`.  Returns the samples
+    /// and the offset to continue reading from.  If there are no new
+    /// samples, this returns no samples and an offset that can be used to
+    /// retry later.
+    ///
+    /// If the segment `from` refers to has been removed, reading starts at
+    /// the oldest available segment.  Only complete lines are returned, so
+    /// a sample which is being written is returned by a later call.
+    pub fn read(
+        &self,
+        from: ScribeOffset,
+        limit: usize,
+    ) -> Result<(Vec<ScribeEntry>, ScribeOffset), Error> {
+        let segments = list_segments(&self.category_dir)?;
+        let mut position = match segments.first() {
+            Some(first) if *first > from.segment => ScribeOffset {
+                segment: *first,
+                offset: 0,
+            },
+            _ => from,
+        };
+
+        let mut entries = Vec::new();
+        while entries.len() < limit {
+            if !segments.contains(&position.segment) {
+                break;
+            }
+            let mut file = File::open(segment_path(&self.category_dir, position.segment))?;
+            file.seek(SeekFrom::Start(position.offset))?;
+            let mut reader = BufReader::new(file);
+            let mut complete = false;
+            while entries.len() < limit {
+                let mut line = String::new();
+                let len = reader.read_line(&mut line)?;
+                if len == 0 {
+                    complete = true;
+                    break;
+                }
+                if !line.ends_with('\n') {
+                    break;
+                }
+                let record: Value = serde_json::from_str(&line)
+                    .with_context(|| format!("invalid record at {}", position))?;
+                let sample = record
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| anyhow!("record at {} has no message", position))?;
+                entries.push(ScribeEntry {
+                    offset: position,
+                    sample: sample.to_string(),
+                });
+                position.offset += len as u64;
+            }
+
+            // Once the writer has moved on to a later segment, this one is
+            // complete and reading continues in the next segment.
+            let next = segments.iter().find(|segment| **segment > position.segment);
+            match next {
+                Some(next) if complete => {
+                    position = ScribeOffset {
+                        segment: *next,
+                        offset: 0,
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        Ok((entries, position))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{RotatingFileSink, ScribeSink};
+    use std::fs;
+    use tempdir::TempDir;
+
+    fn samples(entries: &[ScribeEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.sample.as_str()).collect()
+    }
+
+    #[test]
+    fn test_tail_rotating_files() -> Result<(), Error> {
+        let dir = TempDir::new("scribe_tail")?;
+        // Each record is 36 bytes, including the newline, plus the sample.
+        let sink = RotatingFileSink::new(dir.path().to_path_buf(), 85, Some(2));
+        let tailer = ScribeTailer::new(dir.path().to_path_buf(), "commits")?;
+
+        let (entries, next) = tailer.read(tailer.start()?, 10)?;
+        assert!(entries.is_empty());
+
+        sink.offer("commits", "first")?;
+        sink.offer("commits", "second")?;
+        sink.offer("other", "unrelated")?;
+        let (entries, next) = tailer.read(next, 10)?;
+        assert_eq!(samples(&entries), vec!["first", "second"]);
+
+        // This sample doesn't fit in the first segment.
+        sink.offer("commits", "third")?;
+        let (entries, next) = tailer.read(next, 10)?;
+        assert_eq!(samples(&entries), vec!["third"]);
+        assert_eq!(next.segment, 1);
+
+        let (entries, _) = tailer.read(tailer.start()?, 2)?;
+        assert_eq!(samples(&entries), vec!["first", "second"]);
+        let (entries, _) = tailer.read(entries[1].offset, 10)?;
+        assert_eq!(samples(&entries), vec!["second", "third"]);
+
+        // Writing a third segment removes the first.
+        sink.offer("commits", "fourth sample")?;
+        assert_eq!(tailer.start()?.segment, 1);
+        let (entries, next) = tailer.read(ScribeOffset::default(), 10)?;
+        assert_eq!(samples(&entries), vec!["third", "fourth sample"]);
+        assert_eq!(next.to_string().parse::<ScribeOffset>()?, next);
+
+        assert!(sink.offer("../escape", "sample").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<(), Error> {
+        let dir = TempDir::new("scribe_round_trip")?;
+        let sink = RotatingFileSink::new(dir.path().to_path_buf(), 1024, None);
+        let tailer = ScribeTailer::new(dir.path().to_path_buf(), "commits")?;
+
+        let sent = vec![
+            "{\"json\": \"sample\"}",
+            "two\nlines",
+            "tab\tand \\ backslash",
+            "unicode \u{1f600}",
+            "",
+        ];
+        for sample in sent.iter() {
+            sink.offer("commits", sample)?;
+        }
+
+        let (entries, _) = tailer.read(tailer.start()?, 10)?;
+        assert_eq!(samples(&entries), sent);
+
+        // Each record is a single line naming its category.
+        let segment = fs::read_to_string(segment_path(&dir.path().join("commits"), 0))?;
+        let lines: Vec<_> = segment.lines().collect();
+        assert_eq!(lines.len(), sent.len());
+        let record: Value = serde_json::from_str(lines[1])?;
+        assert_eq!(record["category"], "commits");
+        assert_eq!(record["message"], "two\nlines");
+
+        Ok(())
+    }
+}
@@ -5,10 +5,16 @@
  * GNU General Public License version 2.
  */
 
+//! Outside of Facebook, remote databases are plain MySQL servers, and the XDB tier or shard
+//! map in the database configuration is a MySQL connection string (see
+//! `sql_ext::create_mysql_connections`).  Unless opened read-only, the database schema is
+//! created from `CREATION_QUERY` if it does not already exist.
+
 use anyhow::Result;
 use async_trait::async_trait;
 use fbinit::FacebookInit;
-use sql_ext::facebook::{MysqlOptions, ReadConnectionType};
+use sql_ext::facebook::{MysqlOptions, PoolSizeConfig, ReadConnectionType};
+use sql_ext::{create_mysql_connections, create_mysql_schema, mysql_shard_address};
 
 use crate::{SqlConstruct, SqlShardedConstruct};
 
@@ -27,15 +33,29 @@ pub trait FbSqlConstruct: SqlConstruct + Sized + Send + Sync + 'static {
 
     async fn with_raw_xdb_tier(
         _: FacebookInit,
-        _: String,
-        _: ReadConnectionType,
-        _: bool,
+        tier: String,
+        read_con_type: ReadConnectionType,
+        readonly: bool,
     ) -> Result<Self> {
-        fb_unimplemented!()
+        if !readonly {
+            create_mysql_schema(&tier, Self::CREATION_QUERY).await?;
+        }
+        let connections = create_mysql_connections(
+            &tier,
+            read_con_type,
+            PoolSizeConfig::for_regular_connection(),
+            readonly,
+        )?;
+        Ok(Self::from_sql_connections(connections))
     }
 
-    async fn with_xdb(_: FacebookInit, _: String, _: MysqlOptions, _: bool) -> Result<Self> {
-        fb_unimplemented!()
+    async fn with_xdb(
+        fb: FacebookInit,
+        tier: String,
+        options: MysqlOptions,
+        readonly: bool,
+    ) -> Result<Self> {
+        Self::with_raw_xdb_tier(fb, tier, options.read_connection_type(), readonly).await
     }
 }
 
@@ -50,22 +70,42 @@ pub trait FbSqlShardedConstruct: SqlShardedConstruct + Sized + Send + Sync + 'st
 
     async fn with_sharded_raw_xdb_tier(
         _: FacebookInit,
-        _: String,
-        _: usize,
-        _: ReadConnectionType,
-        _: bool,
+        shardmap: String,
+        shard_num: usize,
+        read_con_type: ReadConnectionType,
+        readonly: bool,
     ) -> Result<Self> {
-        fb_unimplemented!()
+        let mut shard_connections = Vec::with_capacity(shard_num);
+        for shard_id in 0..shard_num {
+            let address = mysql_shard_address(&shardmap, shard_id);
+            if !readonly {
+                create_mysql_schema(&address, Self::CREATION_QUERY).await?;
+            }
+            shard_connections.push(create_mysql_connections(
+                &address,
+                read_con_type,
+                PoolSizeConfig::for_sharded_connection(),
+                readonly,
+            )?);
+        }
+        Ok(Self::from_sql_shard_connections(shard_connections))
     }
 
     async fn with_sharded_xdb(
-        _: FacebookInit,
-        _: String,
-        _: usize,
-        _: MysqlOptions,
-        _: bool,
+        fb: FacebookInit,
+        shardmap: String,
+        shard_num: usize,
+        options: MysqlOptions,
+        readonly: bool,
     ) -> Result<Self> {
-        fb_unimplemented!()
+        Self::with_sharded_raw_xdb_tier(
+            fb,
+            shardmap,
+            shard_num,
+            options.read_connection_type(),
+            readonly,
+        )
+        .await
     }
 }
 
@@ -35,6 +35,7 @@ pub struct BlameRoot(ChangesetId);
 
 impl BonsaiDerived for BlameRoot {
     const NAME: &'static str = "blame";
+    const DEPENDENCIES: &'static [&'static str] = &[RootUnodeManifestId::NAME];
     type Mapping = BlameRootMapping;
 
     fn mapping(_ctx: &CoreContext, repo: &BlobRepo) -> Self::Mapping {
@@ -0,0 +1,190 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::{derived::fetch_file_full_content, fetch_blame, BlameError};
+use anyhow::Error;
+use blobrepo::BlobRepo;
+use blobstore::Loadable;
+use bytes::Bytes;
+use cloned::cloned;
+use context::CoreContext;
+use derived_data::BonsaiDerived;
+use futures::{compat::Future01CompatExt, future::try_join_all};
+use manifest::ManifestOps;
+use mononoke_types::{
+    blame::{blame_lines_origins_in_parent, Blame, BlameId, BlameMaybeRejected},
+    ChangesetId, MPath,
+};
+use std::collections::{HashMap, HashSet};
+use unodes::{find_unode_renames, RootUnodeManifestId};
+
+type BlameLine = (ChangesetId, MPath, u32);
+
+/// Describes where the lines of a file introduced by some changeset came from.
+struct LinesOrigin {
+    /// For every parent of the file: the line of the parent each line of the
+    /// file was derived from, and the blame lines of that parent.
+    parents: Vec<(Vec<Option<u32>>, Vec<BlameLine>)>,
+}
+
+impl LinesOrigin {
+    /// Find the blame of the line this line replaced, if there is one.
+    fn find(&self, line: u32) -> Option<&BlameLine> {
+        self.parents.iter().find_map(|(origins, lines)| {
+            let origin = (*origins.get(line as usize)?)?;
+            lines.get(origin as usize)
+        })
+    }
+}
+
+/// Fetch content and blame for a file, attributing lines which were
+/// introduced by any of the `ignored` changesets to the lines they
+/// replaced.
+///
+/// This is computed at query time from already derived blame data, the
+/// same way as git's `--ignore-rev` does: lines modified by an ignored
+/// changeset are matched with the removed lines of the same diff hunk.
+/// Lines which were purely added by an ignored changeset are still
+/// attributed to it.
+pub async fn fetch_blame_ignoring(
+    ctx: CoreContext,
+    repo: BlobRepo,
+    csid: ChangesetId,
+    path: MPath,
+    ignored: HashSet<ChangesetId>,
+) -> Result<(Bytes, Blame), BlameError> {
+    let (content, blame) = fetch_blame(ctx.clone(), repo.clone(), csid, path)
+        .compat()
+        .await?;
+    if ignored.is_empty() {
+        return Ok((content, blame));
+    }
+
+    let mut lines: Vec<BlameLine> = blame
+        .lines()
+        .map(|(csid, path, origin_offset)| (csid, path.clone(), origin_offset))
+        .collect();
+    let mut origins: HashMap<(ChangesetId, MPath), Option<LinesOrigin>> = HashMap::new();
+
+    // Every re-attribution moves a line to an ancestor changeset, so this
+    // loop terminates once no line attributed to an ignored changeset can
+    // be moved further.
+    loop {
+        let missing: HashSet<_> = lines
+            .iter()
+            .filter(|(csid, path, _)| {
+                ignored.contains(csid) && !origins.contains_key(&(*csid, path.clone()))
+            })
+            .map(|(csid, path, _)| (*csid, path.clone()))
+            .collect();
+        let fetched = try_join_all(missing.into_iter().map(|(csid, path)| {
+            cloned!(ctx, repo);
+            async move {
+                let origin = fetch_lines_origin(&ctx, &repo, csid, &path).await?;
+                Ok::<_, Error>(((csid, path), origin))
+            }
+        }))
+        .await?;
+        origins.extend(fetched);
+
+        let mut changed = false;
+        for line in lines.iter_mut() {
+            if !ignored.contains(&line.0) {
+                continue;
+            }
+            let origin = origins
+                .get(&(line.0, line.1.clone()))
+                .and_then(|origin| origin.as_ref()?.find(line.2));
+            if let Some(origin) = origin {
+                *line = origin.clone();
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let blame = Blame::from_lines(
+        lines
+            .iter()
+            .map(|(csid, path, origin_offset)| (*csid, path, *origin_offset)),
+    )?;
+    Ok((content, blame))
+}
+
+/// Load the content of a file as of changeset `csid` and of all its parents
+/// (including the copy source), and match their lines.
+///
+/// Returns `None` if there is nothing to match against, e.g. the file
+/// does not exist or blame was rejected for it.
+async fn fetch_lines_origin(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    csid: ChangesetId,
+    path: &MPath,
+) -> Result<Option<LinesOrigin>, Error> {
+    let root_mf = RootUnodeManifestId::derive(ctx.clone(), repo.clone(), csid)
+        .compat()
+        .await?;
+    let entry = root_mf
+        .manifest_unode_id()
+        .clone()
+        .find_entry(ctx.clone(), repo.get_blobstore(), Some(path.clone()))
+        .compat()
+        .await?;
+    let file_unode_id = match entry.and_then(|entry| entry.into_leaf()) {
+        Some(file_unode_id) => file_unode_id,
+        None => return Ok(None),
+    };
+
+    let content = match fetch_file_full_content(ctx, repo, file_unode_id).await? {
+        Ok(content) => content,
+        Err(_rejected) => return Ok(None),
+    };
+
+    let content = &content;
+    let file_unode = file_unode_id.load(ctx.clone(), repo.blobstore()).await?;
+    let bonsai = csid.load(ctx.clone(), repo.blobstore()).await?;
+    let renames = find_unode_renames(ctx.clone(), repo.clone(), &bonsai)
+        .compat()
+        .await?;
+
+    let parents = try_join_all(
+        file_unode
+            .parents()
+            .iter()
+            .cloned()
+            .chain(renames.get(path).cloned())
+            .map(|parent| async move {
+                let parent_content = fetch_file_full_content(ctx, repo, parent).await?;
+                let parent_blame = BlameId::from(parent)
+                    .load(ctx.clone(), repo.blobstore())
+                    .await?;
+                match (parent_content, parent_blame) {
+                    (Ok(parent_content), BlameMaybeRejected::Blame(parent_blame)) => {
+                        let origins = blame_lines_origins_in_parent(
+                            parent_content.as_ref(),
+                            content.as_ref(),
+                        );
+                        let lines = parent_blame
+                            .lines()
+                            .map(|(csid, path, origin_offset)| (csid, path.clone(), origin_offset))
+                            .collect();
+                        Ok::<_, Error>(Some((origins, lines)))
+                    }
+                    _ => Ok(None),
+                }
+            }),
+    )
+    .await?
+    .into_iter()
+    .flatten()
+    .collect();
+
+    Ok(Some(LinesOrigin { parents }))
+}
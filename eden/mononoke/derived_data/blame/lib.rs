@@ -9,7 +9,9 @@
 #![type_length_limit = "1441792"]
 
 mod derived;
+mod ignore;
 pub use derived::{fetch_file_full_content, BlameRoot, BlameRootMapping};
+pub use ignore::fetch_blame_ignoring;
 
 #[cfg(test)]
 mod tests;
@@ -5,17 +5,17 @@
  * GNU General Public License version 2.
  */
 
-use crate::{fetch_blame, BlameError};
+use crate::{fetch_blame, fetch_blame_ignoring, BlameError};
 use anyhow::{anyhow, Error};
 use blobrepo_override::DangerousOverride;
 use bytes::Bytes;
 use context::CoreContext;
 use fbinit::FacebookInit;
 use futures::compat::Future01CompatExt;
-use maplit::{btreemap, hashmap};
+use maplit::{btreemap, hashmap, hashset};
 use metaconfig_types::DerivedDataConfig;
 use mononoke_types::{Blame, ChangesetId, MPath};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use tests_utils::{create_commit, store_files, store_rename, CreateCommitContext};
 
 // File with multiple changes and a merge
@@ -285,6 +285,66 @@ async fn test_blame_file_size_limit_rejected(fb: FacebookInit) -> Result<(), Err
     Ok(())
 }
 
+#[fbinit::compat_test]
+async fn test_blame_ignoring(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo = blobrepo_factory::new_memblob_empty(None)?;
+    let file = "file";
+
+    let c0 = CreateCommitContext::new_root(&ctx, &repo)
+        .add_file(file, "a\nb\nc\n")
+        .commit()
+        .await?;
+    let c1 = CreateCommitContext::new(&ctx, &repo, vec![c0])
+        .add_file(file, "a\nb\nc\nd\n")
+        .commit()
+        .await?;
+    // reformatting commit, which should be skipped
+    let c2 = CreateCommitContext::new(&ctx, &repo, vec![c1])
+        .add_file(file, "A\nb\nC\nD\ne\n")
+        .commit()
+        .await?;
+    let c3 = CreateCommitContext::new(&ctx, &repo, vec![c2])
+        .add_file(file, "A\nB\nC\nD\ne\n")
+        .commit()
+        .await?;
+
+    let names = hashmap! {
+        c0 => "c0",
+        c1 => "c1",
+        c2 => "c2",
+        c3 => "c3",
+    };
+
+    let (content, blame) = fetch_blame_ignoring(
+        ctx.clone(),
+        repo.clone(),
+        c3,
+        MPath::new(file)?,
+        HashSet::new(),
+    )
+    .await?;
+    assert_eq!(
+        annotate(content, blame, &names)?,
+        "c2: A\nc3: B\nc2: C\nc2: D\nc2: e\n"
+    );
+
+    let (content, blame) = fetch_blame_ignoring(
+        ctx.clone(),
+        repo.clone(),
+        c3,
+        MPath::new(file)?,
+        hashset! {c2},
+    )
+    .await?;
+    assert_eq!(
+        annotate(content, blame, &names)?,
+        "c0: A\nc3: B\nc0: C\nc1: D\nc2: e\n"
+    );
+
+    Ok(())
+}
+
 fn annotate(
     content: Bytes,
     blame: Blame,
@@ -25,6 +25,7 @@ use std::{
     convert::{TryFrom, TryInto},
     iter::FromIterator,
 };
+use unodes::RootUnodeManifestId;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct RootDeletedManifestId(DeletedManifestId);
@@ -57,6 +58,7 @@ impl From<RootDeletedManifestId> for BlobstoreBytes {
 
 impl BonsaiDerived for RootDeletedManifestId {
     const NAME: &'static str = "deleted_manifest";
+    const DEPENDENCIES: &'static [&'static str] = &[RootUnodeManifestId::NAME];
     type Mapping = RootDeletedManifestMapping;
 
     fn mapping(_ctx: &CoreContext, repo: &BlobRepo) -> Self::Mapping {
@@ -20,6 +20,11 @@
 /// store a history across deletions i.e. if a file was added, then deleted then added again in
 /// commit A, FastlogBatch in commit A will contain only one entry.
 ///
+/// Manifest unodes change whenever anything below them changes, so the FastlogBatch of a
+/// directory lists the commits that touched any file under that directory. This means
+/// `list_file_history` can serve directory history from fastlog data in the same way as file
+/// history, including history across deletions, which is found via deleted files manifests.
+///
 /// RootFastlog is a derived data which derives FastlogBatch for each unode
 /// that was created or modified in this commit.
 mod fastlog_impl;
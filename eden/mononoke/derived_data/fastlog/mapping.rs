@@ -53,6 +53,7 @@ pub struct RootFastlog(ChangesetId);
 
 impl BonsaiDerived for RootFastlog {
     const NAME: &'static str = "fastlog";
+    const DEPENDENCIES: &'static [&'static str] = &[RootUnodeManifestId::NAME];
     type Mapping = RootFastlogMapping;
 
     fn mapping(_ctx: &CoreContext, repo: &BlobRepo) -> Self::Mapping {
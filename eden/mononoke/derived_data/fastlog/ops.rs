@@ -1305,6 +1305,78 @@ mod test {
         Ok(())
     }
 
+    #[fbinit::compat_test]
+    async fn test_list_directory_history_across_deletions(fb: FacebookInit) -> Result<(), Error> {
+        let repo = new_memblob_empty(None).unwrap();
+        let ctx = CoreContext::test_mock(fb);
+
+        let a = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file("dir/sub/1", "content1")
+            .add_file("other", "other1")
+            .commit()
+            .await?;
+        let b = CreateCommitContext::new(&ctx, &repo, vec![a])
+            .add_file("dir/sub/1", "content2")
+            .commit()
+            .await?;
+        // Doesn't touch anything under "dir"
+        let c = CreateCommitContext::new(&ctx, &repo, vec![b])
+            .add_file("other", "other2")
+            .commit()
+            .await?;
+        // Deletes "dir" completely
+        let d = CreateCommitContext::new(&ctx, &repo, vec![c])
+            .delete_file("dir/sub/1")
+            .commit()
+            .await?;
+        let e = CreateCommitContext::new(&ctx, &repo, vec![d])
+            .add_file("dir/sub/2", "content3")
+            .commit()
+            .await?;
+
+        let terminator = Some(|_cs_id| future::ready(Ok(false)));
+        for dir in &["dir", "dir/sub"] {
+            let history_stream = list_file_history(
+                ctx.clone(),
+                repo.clone(),
+                MPath::new_opt(*dir)?,
+                e,
+                terminator,
+                HistoryAcrossDeletions::Track,
+            )
+            .await?;
+            let actual = history_stream.try_collect::<Vec<_>>().await?;
+            assert_eq!(actual, vec![e, d, b, a]);
+
+            let history_stream = list_file_history(
+                ctx.clone(),
+                repo.clone(),
+                MPath::new_opt(*dir)?,
+                e,
+                terminator,
+                HistoryAcrossDeletions::DontTrack,
+            )
+            .await?;
+            let actual = history_stream.try_collect::<Vec<_>>().await?;
+            assert_eq!(actual, vec![e]);
+
+            // Starting from the commit where the directory doesn't exist
+            let history_stream = list_file_history(
+                ctx.clone(),
+                repo.clone(),
+                MPath::new_opt(*dir)?,
+                d,
+                terminator,
+                HistoryAcrossDeletions::Track,
+            )
+            .await?;
+            let actual = history_stream.try_collect::<Vec<_>>().await?;
+            assert_eq!(actual, vec![d, b, a]);
+        }
+
+        Ok(())
+    }
+
     type TestCommitGraph = HashMap<ChangesetId, Vec<ChangesetId>>;
 
     async fn create_branch(
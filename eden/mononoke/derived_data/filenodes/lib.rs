@@ -25,6 +25,7 @@ use futures_old::{future as old_future, Future};
 use futures_util::try_join;
 use itertools::{Either, Itertools};
 use manifest::{find_intersection_of_diffs_and_parents, Entry};
+use mercurial_derived_data::MappedHgChangesetId;
 use mercurial_types::{
     blobs::File, fetch_manifest_envelope, HgChangesetId, HgFileEnvelope, HgFileNodeId,
     HgManifestEnvelope, HgManifestId, NULL_HASH,
@@ -106,6 +107,7 @@ pub enum FilenodesOnlyPublic {
 
 impl BonsaiDerived for FilenodesOnlyPublic {
     const NAME: &'static str = "filenodes";
+    const DEPENDENCIES: &'static [&'static str] = &[MappedHgChangesetId::NAME];
     type Mapping = FilenodesOnlyPublicMapping;
 
     fn mapping(_ctx: &CoreContext, repo: &BlobRepo) -> Self::Mapping {
@@ -0,0 +1,215 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use blobstore::Loadable;
+use context::CoreContext;
+use futures::future::{try_join, try_join_all};
+use mononoke_types::skeleton_manifest::SkeletonManifestEntry;
+use mononoke_types::{MPath, MPathElement, SkeletonManifestId};
+use repo_blobstore::RepoBlobstore;
+use std::collections::{BTreeMap, HashMap};
+
+/// Find all case conflicts in the tree rooted at `skeleton_manifest_id`.
+///
+/// Only the subtrees which are marked as containing case conflicts are
+/// loaded, so this is cheap for trees with few or no case conflicts.
+pub async fn find_case_conflicts(
+    ctx: &CoreContext,
+    blobstore: &RepoBlobstore,
+    skeleton_manifest_id: SkeletonManifestId,
+) -> Result<Vec<(MPath, MPath)>, Error> {
+    find_case_conflicts_not_in_parents(ctx, blobstore, skeleton_manifest_id, Vec::new(), None).await
+}
+
+/// Find the first case conflict in the tree rooted at
+/// `skeleton_manifest_id` that is not also present in one of the `parents`.
+///
+/// Subtrees which are shared with a parent are skipped, as they cannot
+/// contain new case conflicts.
+pub async fn find_new_case_conflict(
+    ctx: &CoreContext,
+    blobstore: &RepoBlobstore,
+    skeleton_manifest_id: SkeletonManifestId,
+    parents: Vec<SkeletonManifestId>,
+) -> Result<Option<(MPath, MPath)>, Error> {
+    let conflicts =
+        find_case_conflicts_not_in_parents(ctx, blobstore, skeleton_manifest_id, parents, Some(1))
+            .await?;
+    Ok(conflicts.into_iter().next())
+}
+
+async fn find_case_conflicts_not_in_parents(
+    ctx: &CoreContext,
+    blobstore: &RepoBlobstore,
+    skeleton_manifest_id: SkeletonManifestId,
+    parents: Vec<SkeletonManifestId>,
+    limit: Option<usize>,
+) -> Result<Vec<(MPath, MPath)>, Error> {
+    let mut conflicts = Vec::new();
+    let mut queue = vec![(None, skeleton_manifest_id, parents)];
+
+    while let Some((path, skeleton_id, parents)) = queue.pop() {
+        if parents.contains(&skeleton_id) {
+            continue;
+        }
+        let (skeleton, parent_skeletons) = try_join(
+            async { Ok::<_, Error>(skeleton_id.load(ctx.clone(), blobstore).await?) },
+            try_join_all(parents.iter().map(|parent_id| async move {
+                Ok::<_, Error>(parent_id.load(ctx.clone(), blobstore).await?)
+            })),
+        )
+        .await?;
+        if !skeleton.summary().has_case_conflicts() {
+            continue;
+        }
+
+        for (first, second) in skeleton.child_case_conflicts() {
+            let in_parent = parent_skeletons.iter().any(|parent_skeleton| {
+                parent_skeleton.lookup(first).is_some() && parent_skeleton.lookup(second).is_some()
+            });
+            if !in_parent {
+                conflicts.push((
+                    MPath::join_opt_element(path.as_ref(), first),
+                    MPath::join_opt_element(path.as_ref(), second),
+                ));
+                if limit.map_or(false, |limit| conflicts.len() >= limit) {
+                    return Ok(conflicts);
+                }
+            }
+        }
+
+        // Visit subdirectories in order by pushing them in reverse.
+        let subdirs: Vec<_> = skeleton
+            .list()
+            .filter_map(|(elem, entry)| match entry {
+                SkeletonManifestEntry::Directory(dir) if dir.summary().has_case_conflicts() => {
+                    Some((elem, dir))
+                }
+                _ => None,
+            })
+            .collect();
+        for (elem, dir) in subdirs.into_iter().rev() {
+            let subdir_parents = parent_skeletons
+                .iter()
+                .filter_map(|parent_skeleton| match parent_skeleton.lookup(elem) {
+                    Some(SkeletonManifestEntry::Directory(parent_dir)) => Some(*parent_dir.id()),
+                    _ => None,
+                })
+                .collect();
+            queue.push((
+                Some(MPath::join_opt_element(path.as_ref(), elem)),
+                *dir.id(),
+                subdir_parents,
+            ));
+        }
+    }
+
+    Ok(conflicts)
+}
+
+/// Find the first case conflict between the paths in `added` and the
+/// existing tree rooted at `skeleton_manifest_id`.
+///
+/// This allows a set of changes to be checked without deriving a skeleton
+/// manifest for them.  Only the directories leading to the added paths are
+/// loaded.  Only names which are new to the existing tree are checked, so
+/// modifying a path which is already in a case conflict is not reported.
+/// Existing files and directories which are entirely removed by the paths
+/// in `deleted` are not considered conflicts.  Conflicts between the added
+/// paths themselves are not detected.
+pub async fn find_case_conflict_with_changes(
+    ctx: &CoreContext,
+    blobstore: &RepoBlobstore,
+    skeleton_manifest_id: SkeletonManifestId,
+    added: Vec<MPath>,
+    deleted: Vec<MPath>,
+) -> Result<Option<(MPath, MPath)>, Error> {
+    let mut queue = vec![(None, skeleton_manifest_id, added)];
+
+    while let Some((path, skeleton_id, added)) = queue.pop() {
+        let depth = path.as_ref().map_or(0, MPath::num_components);
+        let skeleton = skeleton_id.load(ctx.clone(), blobstore).await?;
+
+        // Group the added paths by their name at this level.
+        let mut children: BTreeMap<&MPathElement, Vec<&MPath>> = BTreeMap::new();
+        for added_path in added.iter() {
+            if let Some(elem) = added_path.into_iter().nth(depth) {
+                children.entry(elem).or_default().push(added_path);
+            }
+        }
+
+        // Case-fold the existing names once if any of the names are new.
+        let mut existing_lowercase: HashMap<String, Vec<(&MPathElement, &SkeletonManifestEntry)>> =
+            HashMap::new();
+        if children.keys().any(|elem| skeleton.lookup(elem).is_none()) {
+            for (existing, entry) in skeleton.list() {
+                if let Some(lower) = existing.to_lowercase_utf8() {
+                    existing_lowercase
+                        .entry(lower)
+                        .or_default()
+                        .push((existing, entry));
+                }
+            }
+        }
+
+        let mut subdirs = Vec::new();
+        for (elem, child_added) in children {
+            match skeleton.lookup(elem) {
+                Some(SkeletonManifestEntry::Directory(dir)) => {
+                    let subdir_added: Vec<_> = child_added
+                        .into_iter()
+                        .filter(|added_path| added_path.num_components() > depth + 1)
+                        .cloned()
+                        .collect();
+                    if !subdir_added.is_empty() {
+                        subdirs.push((
+                            Some(MPath::join_opt_element(path.as_ref(), elem)),
+                            *dir.id(),
+                            subdir_added,
+                        ));
+                    }
+                }
+                Some(SkeletonManifestEntry::File) => {
+                    // The name already exists with the same case, so any
+                    // case conflict for it is already in the tree.
+                }
+                None => {
+                    let existing_entries = elem
+                        .to_lowercase_utf8()
+                        .and_then(|lower| existing_lowercase.get(&lower));
+                    for (existing, entry) in existing_entries.into_iter().flatten() {
+                        let existing_path = MPath::join_opt_element(path.as_ref(), existing);
+                        let existing_files = match entry {
+                            SkeletonManifestEntry::File => 1,
+                            SkeletonManifestEntry::Directory(dir) => {
+                                dir.summary().descendant_files_count
+                            }
+                        };
+                        let deleted_files = deleted
+                            .iter()
+                            .filter(|deleted_path| existing_path.is_prefix_of(*deleted_path))
+                            .count() as u64;
+                        if deleted_files < existing_files {
+                            let added_path = MPath::join_opt_element(path.as_ref(), elem);
+                            return Ok(Some(if existing_path < added_path {
+                                (existing_path, added_path)
+                            } else {
+                                (added_path, existing_path)
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Visit subdirectories in order by pushing them in reverse.
+        queue.extend(subdirs.into_iter().rev());
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,252 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::{format_err, Error};
+use blobrepo::BlobRepo;
+use blobstore::{Blobstore, Loadable};
+use cloned::cloned;
+use context::CoreContext;
+use failure_ext::FutureFailureExt;
+use futures::future::TryFutureExt;
+use futures_ext::{BoxFuture, FutureExt};
+use futures_old::{future, stream, sync::mpsc, Future, IntoFuture, Stream};
+use manifest::{derive_manifest_with_io_sender, Entry, LeafInfo, TreeInfo};
+use mononoke_types::skeleton_manifest::{
+    SkeletonManifest, SkeletonManifestDirectory, SkeletonManifestEntry, SkeletonManifestSummary,
+};
+use mononoke_types::{BlobstoreValue, MPath, MPathElement, MononokeId, SkeletonManifestId};
+use repo_blobstore::RepoBlobstore;
+
+use crate::ErrorKind;
+
+/// Derives skeleton manifests for bonsai_changeset `cs_id` given parent
+/// skeleton manifests. As with fsnodes, `derive_manifest()` does most of the
+/// work; this only needs to create single skeleton manifest nodes.
+pub(crate) fn derive_skeleton_manifest(
+    ctx: CoreContext,
+    repo: BlobRepo,
+    parents: Vec<SkeletonManifestId>,
+    changes: Vec<(MPath, Option<()>)>,
+) -> impl Future<Item = SkeletonManifestId, Error = Error> {
+    future::lazy(move || {
+        let blobstore = repo.get_blobstore();
+        derive_manifest_with_io_sender(
+            ctx.clone(),
+            blobstore.clone(),
+            parents.clone(),
+            changes,
+            {
+                cloned!(blobstore, ctx);
+                move |tree_info, sender| {
+                    create_skeleton_manifest(
+                        ctx.clone(),
+                        blobstore.clone(),
+                        Some(sender),
+                        tree_info,
+                    )
+                }
+            },
+            |leaf_info, _sender| check_skeleton_manifest_leaf(leaf_info),
+        )
+        .and_then(move |maybe_tree_id| match maybe_tree_id {
+            Some(tree_id) => future::ok(tree_id).left_future(),
+            None => {
+                // All files have been deleted, generate empty skeleton manifest
+                let tree_info = TreeInfo {
+                    path: None,
+                    parents,
+                    subentries: Default::default(),
+                };
+                create_skeleton_manifest(ctx, blobstore, None, tree_info)
+                    .map(|(_, tree_id)| tree_id)
+                    .right_future()
+            }
+        })
+    })
+}
+
+/// Collect all the subentries for a new skeleton manifest, re-using entries
+/// from the parent skeleton manifests to avoid fetching too much.
+fn collect_skeleton_subentries(
+    ctx: CoreContext,
+    blobstore: RepoBlobstore,
+    parents: Vec<SkeletonManifestId>,
+    subentries: BTreeMap<
+        MPathElement,
+        (
+            Option<Option<SkeletonManifestSummary>>,
+            Entry<SkeletonManifestId, ()>,
+        ),
+    >,
+) -> impl Future<Item = Vec<(MPathElement, SkeletonManifestEntry)>, Error = Error> {
+    // Load the parent skeleton manifests
+    stream::futures_unordered(parents.into_iter().map({
+        cloned!(ctx, blobstore);
+        move |skeleton_id| {
+            skeleton_id
+                .load(ctx.clone(), &blobstore)
+                .compat()
+                .context(ErrorKind::MissingParent(skeleton_id))
+        }
+    }))
+    .collect()
+    .from_err()
+    .and_then({
+        cloned!(ctx, blobstore);
+        move |parent_skeletons| {
+            // Collect all directory entries from the parent skeleton
+            // manifests as a cache.
+            let mut dir_cache = HashMap::new();
+            for parent_skeleton in parent_skeletons.into_iter() {
+                for (_elem, entry) in parent_skeleton.list() {
+                    if let SkeletonManifestEntry::Directory(dir) = entry {
+                        dir_cache.entry(*dir.id()).or_insert(dir.clone());
+                    }
+                }
+            }
+
+            // Find or fetch the `SkeletonManifestEntry` for each of the
+            // subentries.
+            stream::futures_ordered(subentries.into_iter().map(move |(elem, (summary, entry))| {
+                match entry {
+                    Entry::Tree(skeleton_id) => {
+                        if let Some(Some(summary)) = summary {
+                            // The subdirectory was just created. Use the
+                            // summary we just calculated.
+                            future::ok((
+                                elem.clone(),
+                                SkeletonManifestEntry::Directory(SkeletonManifestDirectory::new(
+                                    skeleton_id,
+                                    summary,
+                                )),
+                            ))
+                            .boxify()
+                        } else if let Some(entry) = dir_cache.get(&skeleton_id) {
+                            // The subdirectory was already in this
+                            // directory. Use the cached entry.
+                            future::ok((
+                                elem.clone(),
+                                SkeletonManifestEntry::Directory(entry.clone()),
+                            ))
+                            .boxify()
+                        } else {
+                            // Some other directory is being used. Fetch its
+                            // summary from the blobstore.
+                            skeleton_id
+                                .load(ctx.clone(), &blobstore)
+                                .compat()
+                                .with_context({
+                                    cloned!(elem);
+                                    move || {
+                                        ErrorKind::MissingSubentry(
+                                            String::from_utf8_lossy(elem.as_ref()).to_string(),
+                                            skeleton_id,
+                                        )
+                                    }
+                                })
+                                .from_err()
+                                .map({
+                                    cloned!(elem, skeleton_id);
+                                    move |skeleton| {
+                                        let entry = SkeletonManifestEntry::Directory(
+                                            SkeletonManifestDirectory::new(
+                                                skeleton_id,
+                                                skeleton.summary().clone(),
+                                            ),
+                                        );
+                                        (elem, entry)
+                                    }
+                                })
+                                .boxify()
+                        }
+                    }
+                    Entry::Leaf(()) => {
+                        future::ok((elem.clone(), SkeletonManifestEntry::File)).boxify()
+                    }
+                }
+            }))
+            .collect()
+        }
+    })
+}
+
+/// Create a new skeleton manifest for the tree described by `tree_info`.
+fn create_skeleton_manifest(
+    ctx: CoreContext,
+    blobstore: RepoBlobstore,
+    sender: Option<mpsc::UnboundedSender<BoxFuture<(), Error>>>,
+    tree_info: TreeInfo<SkeletonManifestId, (), Option<SkeletonManifestSummary>>,
+) -> impl Future<Item = (Option<SkeletonManifestSummary>, SkeletonManifestId), Error = Error> {
+    collect_skeleton_subentries(
+        ctx.clone(),
+        blobstore.clone(),
+        tree_info.parents,
+        tree_info.subentries,
+    )
+    .and_then(move |entries| {
+        // Build a summary of the entries and store it as the new skeleton
+        // manifest.
+        let entries: BTreeMap<_, _> = entries.into_iter().collect();
+        let mut summary = SkeletonManifestSummary::default();
+        let mut lower_entries = HashSet::new();
+        for (elem, entry) in entries.iter() {
+            if let Some(lower) = elem.to_lowercase_utf8() {
+                if !lower_entries.insert(lower) {
+                    summary.child_case_conflicts = true;
+                }
+            }
+            match entry {
+                SkeletonManifestEntry::File => {
+                    summary.child_files_count += 1;
+                    summary.descendant_files_count += 1;
+                }
+                SkeletonManifestEntry::Directory(dir) => {
+                    let subdir_summary = dir.summary();
+                    summary.child_dirs_count += 1;
+                    summary.descendant_dirs_count += 1 + subdir_summary.descendant_dirs_count;
+                    summary.descendant_files_count += subdir_summary.descendant_files_count;
+                    if subdir_summary.has_case_conflicts() {
+                        summary.descendant_case_conflicts = true;
+                    }
+                }
+            }
+        }
+        let skeleton = SkeletonManifest::new(entries, summary.clone());
+        let skeleton_id = skeleton.get_skeleton_manifest_id();
+        let key = skeleton_id.blobstore_key();
+        let blob = skeleton.into_blob();
+        let f = blobstore.put(ctx, key, blob.into()).compat().boxify();
+
+        let res = match sender {
+            Some(sender) => sender
+                .unbounded_send(f)
+                .into_future()
+                .map_err(|err| format_err!("failed to send skeleton manifest future {}", err))
+                .left_future(),
+            None => f.right_future(),
+        };
+        res.map(move |()| (Some(summary), skeleton_id))
+    })
+}
+
+/// Skeleton manifests don't store anything for leaves, so any merge of files
+/// is valid, but a file with a single parent must have been changed.
+fn check_skeleton_manifest_leaf(
+    leaf_info: LeafInfo<(), ()>,
+) -> impl Future<Item = (Option<SkeletonManifestSummary>, ()), Error = Error> {
+    if leaf_info.leaf.is_none() && leaf_info.parents.len() < 2 {
+        return future::err(
+            ErrorKind::InvalidBonsai(
+                "no change is provided, but file has only one parent".to_string(),
+            )
+            .into(),
+        );
+    }
+    future::ok((None, ()))
+}
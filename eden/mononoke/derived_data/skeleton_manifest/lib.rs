@@ -0,0 +1,32 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+use mononoke_types::SkeletonManifestId;
+use thiserror::Error;
+
+mod case_conflicts;
+mod derive;
+mod mapping;
+#[cfg(test)]
+mod tests;
+
+pub use case_conflicts::{
+    find_case_conflict_with_changes, find_case_conflicts, find_new_case_conflict,
+};
+pub use mapping::{RootSkeletonManifestId, RootSkeletonManifestMapping};
+
+#[derive(Debug, Error)]
+pub enum ErrorKind {
+    #[error("Invalid bonsai changeset: {0}")]
+    InvalidBonsai(String),
+    #[error("Missing skeleton manifest parent: {0}")]
+    MissingParent(SkeletonManifestId),
+    #[error("Missing skeleton manifest subentry for '{0}': {1}")]
+    MissingSubentry(String, SkeletonManifestId),
+}
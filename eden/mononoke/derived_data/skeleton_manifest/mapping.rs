@@ -0,0 +1,158 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::derive::derive_skeleton_manifest;
+use anyhow::{Error, Result};
+use blobrepo::BlobRepo;
+use blobstore::{Blobstore, BlobstoreGetData};
+use bytes::Bytes;
+use context::CoreContext;
+use derived_data::{BonsaiDerived, BonsaiDerivedMapping};
+use futures::TryFutureExt;
+use futures_ext::{BoxFuture, FutureExt, StreamExt};
+use futures_old::{
+    stream::{self, FuturesUnordered},
+    Future, Stream,
+};
+use mononoke_types::{BlobstoreBytes, BonsaiChangeset, ChangesetId, MPath, SkeletonManifestId};
+use repo_blobstore::RepoBlobstore;
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    iter::FromIterator,
+};
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RootSkeletonManifestId(SkeletonManifestId);
+
+impl RootSkeletonManifestId {
+    pub fn skeleton_manifest_id(&self) -> &SkeletonManifestId {
+        &self.0
+    }
+    pub fn into_skeleton_manifest_id(self) -> SkeletonManifestId {
+        self.0
+    }
+}
+
+impl TryFrom<BlobstoreBytes> for RootSkeletonManifestId {
+    type Error = Error;
+
+    fn try_from(blob_bytes: BlobstoreBytes) -> Result<Self> {
+        SkeletonManifestId::from_bytes(&blob_bytes.into_bytes()).map(RootSkeletonManifestId)
+    }
+}
+
+impl TryFrom<BlobstoreGetData> for RootSkeletonManifestId {
+    type Error = Error;
+
+    fn try_from(blob_get_data: BlobstoreGetData) -> Result<Self> {
+        blob_get_data.into_bytes().try_into()
+    }
+}
+
+impl From<RootSkeletonManifestId> for BlobstoreBytes {
+    fn from(root_skeleton_manifest_id: RootSkeletonManifestId) -> Self {
+        BlobstoreBytes::from_bytes(Bytes::copy_from_slice(
+            root_skeleton_manifest_id.0.blake2().as_ref(),
+        ))
+    }
+}
+
+impl BonsaiDerived for RootSkeletonManifestId {
+    const NAME: &'static str = "skeleton_manifests";
+    type Mapping = RootSkeletonManifestMapping;
+
+    fn mapping(_ctx: &CoreContext, repo: &BlobRepo) -> Self::Mapping {
+        RootSkeletonManifestMapping::new(repo.blobstore().clone())
+    }
+
+    fn derive_from_parents(
+        ctx: CoreContext,
+        repo: BlobRepo,
+        bonsai: BonsaiChangeset,
+        parents: Vec<Self>,
+    ) -> BoxFuture<Self, Error> {
+        derive_skeleton_manifest(
+            ctx,
+            repo,
+            parents
+                .into_iter()
+                .map(RootSkeletonManifestId::into_skeleton_manifest_id)
+                .collect(),
+            get_file_changes(&bonsai),
+        )
+        .map(RootSkeletonManifestId)
+        .boxify()
+    }
+}
+
+#[derive(Clone)]
+pub struct RootSkeletonManifestMapping {
+    blobstore: RepoBlobstore,
+}
+
+impl RootSkeletonManifestMapping {
+    pub fn new(blobstore: RepoBlobstore) -> Self {
+        Self { blobstore }
+    }
+
+    fn format_key(&self, cs_id: ChangesetId) -> String {
+        format!("derived_root_skeletonmanifest.{}", cs_id)
+    }
+
+    fn fetch_skeleton_manifest(
+        &self,
+        ctx: CoreContext,
+        cs_id: ChangesetId,
+    ) -> impl Future<Item = Option<(ChangesetId, RootSkeletonManifestId)>, Error = Error> {
+        self.blobstore
+            .get(ctx.clone(), self.format_key(cs_id))
+            .compat()
+            .and_then(|opt_blob| opt_blob.map(TryInto::try_into).transpose())
+            .map(move |maybe_root_skeleton_manifest_id| {
+                maybe_root_skeleton_manifest_id
+                    .map(|root_skeleton_manifest_id| (cs_id, root_skeleton_manifest_id))
+            })
+    }
+}
+
+impl BonsaiDerivedMapping for RootSkeletonManifestMapping {
+    type Value = RootSkeletonManifestId;
+
+    fn get(
+        &self,
+        ctx: CoreContext,
+        csids: Vec<ChangesetId>,
+    ) -> BoxFuture<HashMap<ChangesetId, Self::Value>, Error> {
+        let gets = csids.into_iter().map(|cs_id| {
+            self.fetch_skeleton_manifest(ctx.clone(), cs_id).map(
+                |maybe_root_skeleton_manifest_id| {
+                    stream::iter_ok(maybe_root_skeleton_manifest_id.into_iter())
+                },
+            )
+        });
+        FuturesUnordered::from_iter(gets)
+            .flatten()
+            .collect_to()
+            .boxify()
+    }
+
+    fn put(&self, ctx: CoreContext, csid: ChangesetId, id: Self::Value) -> BoxFuture<(), Error> {
+        self.blobstore
+            .put(ctx, self.format_key(csid), id.into())
+            .compat()
+            .boxify()
+    }
+}
+
+/// Skeleton manifests only record the presence of files, so the content of
+/// a file change is irrelevant.
+pub(crate) fn get_file_changes(bcs: &BonsaiChangeset) -> Vec<(MPath, Option<()>)> {
+    bcs.file_changes()
+        .map(|(mpath, file_change)| (mpath.clone(), file_change.map(|_| ())))
+        .collect()
+}
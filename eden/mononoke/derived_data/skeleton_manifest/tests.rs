@@ -0,0 +1,208 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::{
+    find_case_conflict_with_changes, find_case_conflicts, find_new_case_conflict,
+    RootSkeletonManifestId,
+};
+use anyhow::Error;
+use blobrepo_factory::new_memblob_empty;
+use blobstore::Loadable;
+use context::CoreContext;
+use derived_data::BonsaiDerived;
+use fbinit::FacebookInit;
+use futures::compat::Future01CompatExt;
+use mononoke_types::skeleton_manifest::SkeletonManifestSummary;
+use mononoke_types::MPath;
+use tests_utils::CreateCommitContext;
+
+fn conflict(first: &str, second: &str) -> Result<(MPath, MPath), Error> {
+    Ok((MPath::new(first)?, MPath::new(second)?))
+}
+
+fn paths(paths: &[&str]) -> Result<Vec<MPath>, Error> {
+    paths.iter().map(MPath::new).collect()
+}
+
+#[fbinit::compat_test]
+async fn test_skeleton_manifests(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo = new_memblob_empty(None)?;
+
+    let a = CreateCommitContext::new_root(&ctx, &repo)
+        .add_file("dir1/file", "a")
+        .add_file("dir1/sub/file", "a")
+        .add_file("dir2/FILE", "a")
+        .add_file("dir2/file", "a")
+        .add_file("top", "a")
+        .commit()
+        .await?;
+    let b = CreateCommitContext::new(&ctx, &repo, vec![a])
+        .add_file("dir1/sub/File", "b")
+        .add_file("Top", "b")
+        .add_file("dir2/file", "modified")
+        .commit()
+        .await?;
+    let c = CreateCommitContext::new(&ctx, &repo, vec![b])
+        .delete_file("dir1/sub/File")
+        .delete_file("dir2/FILE")
+        .delete_file("Top")
+        .commit()
+        .await?;
+
+    let skeleton_a = RootSkeletonManifestId::derive(ctx.clone(), repo.clone(), a)
+        .compat()
+        .await?
+        .into_skeleton_manifest_id();
+    let root_a = skeleton_a.load(ctx.clone(), repo.blobstore()).await?;
+    assert_eq!(
+        root_a.summary(),
+        &SkeletonManifestSummary {
+            child_files_count: 1,
+            child_dirs_count: 2,
+            descendant_files_count: 5,
+            descendant_dirs_count: 3,
+            child_case_conflicts: false,
+            descendant_case_conflicts: true,
+        }
+    );
+    assert_eq!(
+        find_case_conflicts(&ctx, repo.blobstore(), skeleton_a).await?,
+        vec![conflict("dir2/FILE", "dir2/file")?]
+    );
+
+    let skeleton_b = RootSkeletonManifestId::derive(ctx.clone(), repo.clone(), b)
+        .compat()
+        .await?
+        .into_skeleton_manifest_id();
+    assert_eq!(
+        find_case_conflicts(&ctx, repo.blobstore(), skeleton_b).await?,
+        vec![
+            conflict("Top", "top")?,
+            conflict("dir1/sub/File", "dir1/sub/file")?,
+            conflict("dir2/FILE", "dir2/file")?,
+        ]
+    );
+    assert_eq!(
+        find_new_case_conflict(&ctx, repo.blobstore(), skeleton_b, vec![skeleton_a]).await?,
+        Some(conflict("Top", "top")?)
+    );
+
+    // Changing file content does not change the skeleton manifest.
+    let dir2 = MPath::new("dir2")?;
+    let root_b = skeleton_b.load(ctx.clone(), repo.blobstore()).await?;
+    assert_eq!(
+        root_a.lookup(dir2.basename()),
+        root_b.lookup(dir2.basename())
+    );
+
+    let skeleton_c = RootSkeletonManifestId::derive(ctx.clone(), repo.clone(), c)
+        .compat()
+        .await?
+        .into_skeleton_manifest_id();
+    let root_c = skeleton_c.load(ctx.clone(), repo.blobstore()).await?;
+    assert!(!root_c.summary().has_case_conflicts());
+    assert_eq!(
+        find_case_conflicts(&ctx, repo.blobstore(), skeleton_c).await?,
+        vec![]
+    );
+    assert_eq!(
+        find_new_case_conflict(&ctx, repo.blobstore(), skeleton_c, vec![skeleton_b]).await?,
+        None
+    );
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn test_case_conflict_with_changes(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo = new_memblob_empty(None)?;
+
+    let a = CreateCommitContext::new_root(&ctx, &repo)
+        .add_file("dir/sub/file1", "a")
+        .add_file("dir/sub/file2", "a")
+        .add_file("other/file", "a")
+        .add_file("top", "a")
+        .commit()
+        .await?;
+    let skeleton_a = RootSkeletonManifestId::derive(ctx.clone(), repo.clone(), a)
+        .compat()
+        .await?
+        .into_skeleton_manifest_id();
+
+    let check = |added: &[&str], deleted: &[&str]| {
+        let added = paths(added);
+        let deleted = paths(deleted);
+        let ctx = &ctx;
+        let repo = &repo;
+        async move {
+            find_case_conflict_with_changes(ctx, repo.blobstore(), skeleton_a, added?, deleted?)
+                .await
+        }
+    };
+
+    assert_eq!(check(&["dir/sub/file3", "new"], &[]).await?, None);
+    assert_eq!(check(&["TOP"], &[]).await?, Some(conflict("TOP", "top")?));
+    assert_eq!(
+        check(&["dir/Sub/file1"], &[]).await?,
+        Some(conflict("dir/Sub", "dir/sub")?)
+    );
+    assert_eq!(
+        check(&["dir/sub/FILE2"], &[]).await?,
+        Some(conflict("dir/sub/FILE2", "dir/sub/file2")?)
+    );
+
+    // Renaming an entry to a different case is not a conflict, but only if
+    // everything under the old name is removed.
+    assert_eq!(check(&["Top"], &["top"]).await?, None);
+    assert_eq!(
+        check(&["dir/Sub/file1"], &["dir/sub/file1"]).await?,
+        Some(conflict("dir/Sub", "dir/sub")?)
+    );
+    assert_eq!(
+        check(
+            &["dir/Sub/file1", "dir/Sub/file2"],
+            &["dir/sub/file1", "dir/sub/file2"]
+        )
+        .await?,
+        None
+    );
+
+    // Modifying paths which are already in a case conflict is not a
+    // conflict, but adding a new name which conflicts still is.
+    let b = CreateCommitContext::new(&ctx, &repo, vec![a])
+        .add_file("Top", "b")
+        .add_file("dir/Sub/file3", "b")
+        .commit()
+        .await?;
+    let skeleton_b = RootSkeletonManifestId::derive(ctx.clone(), repo.clone(), b)
+        .compat()
+        .await?
+        .into_skeleton_manifest_id();
+    let check = |added: &[&str], deleted: &[&str]| {
+        let added = paths(added);
+        let deleted = paths(deleted);
+        let ctx = &ctx;
+        let repo = &repo;
+        async move {
+            find_case_conflict_with_changes(ctx, repo.blobstore(), skeleton_b, added?, deleted?)
+                .await
+        }
+    };
+    assert_eq!(
+        check(&["top", "Top", "dir/sub/file1", "dir/Sub/file3"], &[]).await?,
+        None
+    );
+    assert_eq!(check(&["TOP"], &[]).await?, Some(conflict("TOP", "Top")?));
+    assert_eq!(
+        check(&["dir/Sub/FILE3"], &[]).await?,
+        Some(conflict("dir/Sub/FILE3", "dir/Sub/file3")?)
+    );
+
+    Ok(())
+}
@@ -51,6 +51,13 @@ pub trait BonsaiDerived: Sized + 'static + Send + Sync + Clone {
     /// name data (for example lease keys) assoicated with particular derived data type.
     const NAME: &'static str;
 
+    /// Names of other derived data types that deriving this type requires
+    ///
+    /// Deriving this type for a changeset may derive these types for the same changeset,
+    /// so when backfilling several types these should be derived first. Dependencies
+    /// are not followed transitively here; each type only lists the types it uses directly.
+    const DEPENDENCIES: &'static [&'static str] = &[];
+
     type Mapping: BonsaiDerivedMapping<Value = Self>;
 
     /// Get mapping associated with this derived data type.
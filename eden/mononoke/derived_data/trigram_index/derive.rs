@@ -0,0 +1,320 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{format_err, Context, Error};
+use blobrepo::BlobRepo;
+use blobstore::{Blobstore, Loadable, LoadableError};
+use cloned::cloned;
+use context::CoreContext;
+use filestore::FetchKey;
+use futures::compat::Future01CompatExt;
+use futures::future::{try_join_all, FutureExt as _, TryFutureExt};
+use futures_ext::{BoxFuture, FutureExt};
+use futures_old::sync::mpsc;
+use manifest::{derive_manifest_with_io_sender, Entry, LeafInfo, TreeInfo};
+use mononoke_types::trigram_index::{
+    content_trigrams, ContentTrigrams, TrigramBloom, TrigramIndex, TrigramIndexDirectory,
+    TrigramIndexEntry, TrigramIndexFile, TrigramIndexSummary, MAX_INDEXED_FILE_SIZE,
+};
+use mononoke_types::{
+    BlobstoreBytes, BlobstoreValue, ContentId, ContentTrigramsId, FileType, MPath, MPathElement,
+    MononokeId, TrigramIndexId,
+};
+use repo_blobstore::RepoBlobstore;
+
+use crate::ErrorKind;
+
+type Sender = mpsc::UnboundedSender<BoxFuture<(), Error>>;
+
+/// Derives the trigram index for bonsai_changeset `cs_id` given parent
+/// trigram indexes.  As with fsnodes, `derive_manifest()` does most of the
+/// work; this only needs to create single trigram index nodes, and index the
+/// content of new files.
+pub(crate) async fn derive_trigram_index(
+    ctx: CoreContext,
+    repo: BlobRepo,
+    parents: Vec<TrigramIndexId>,
+    changes: Vec<(MPath, Option<(ContentId, FileType)>)>,
+) -> Result<TrigramIndexId, Error> {
+    let blobstore = repo.get_blobstore();
+    let root = derive_manifest_with_io_sender(
+        ctx.clone(),
+        blobstore.clone(),
+        parents.clone(),
+        changes,
+        {
+            cloned!(blobstore, ctx);
+            move |tree_info, sender| {
+                cloned!(blobstore, ctx);
+                async move { create_trigram_index(&ctx, &blobstore, Some(sender), tree_info).await }
+                    .boxed()
+                    .compat()
+            }
+        },
+        {
+            cloned!(blobstore, ctx);
+            move |leaf_info, sender| {
+                cloned!(blobstore, ctx);
+                async move { create_trigram_index_leaf(&ctx, &blobstore, sender, leaf_info).await }
+                    .boxed()
+                    .compat()
+            }
+        },
+    )
+    .compat()
+    .await?;
+
+    match root {
+        Some(root) => Ok(root),
+        None => {
+            // All files have been deleted, generate empty trigram index
+            let tree_info = TreeInfo {
+                path: None,
+                parents,
+                subentries: Default::default(),
+            };
+            let (_, root) = create_trigram_index(&ctx, &blobstore, None, tree_info).await?;
+            Ok(root)
+        }
+    }
+}
+
+/// Store a blob, either via the sender so that it is written before the
+/// mapping, or directly.
+async fn store_blob(
+    ctx: &CoreContext,
+    blobstore: &RepoBlobstore,
+    sender: Option<&Sender>,
+    key: String,
+    blob: impl Into<BlobstoreBytes>,
+) -> Result<(), Error> {
+    let f = blobstore.put(ctx.clone(), key, blob.into());
+    match sender {
+        Some(sender) => sender
+            .unbounded_send(f.compat().boxify())
+            .map_err(|err| format_err!("failed to send trigram index future {}", err)),
+        None => f.await,
+    }
+}
+
+/// Load the trigrams of some previously indexed content.
+async fn load_content_trigrams(
+    ctx: &CoreContext,
+    blobstore: &RepoBlobstore,
+    content_id: ContentId,
+) -> Result<Option<ContentTrigrams>, Error> {
+    match ContentTrigramsId::from(content_id)
+        .load(ctx.clone(), blobstore)
+        .await
+    {
+        Ok(trigrams) => Ok(Some(trigrams)),
+        Err(LoadableError::Missing(_)) => Ok(None),
+        Err(LoadableError::Error(err)) => Err(err),
+    }
+}
+
+/// Build a bloom filter containing the trigrams of the given files.
+async fn files_bloom(
+    ctx: &CoreContext,
+    blobstore: &RepoBlobstore,
+    files: Vec<(MPathElement, ContentId)>,
+) -> Result<TrigramBloom, Error> {
+    let trigrams = try_join_all(files.into_iter().map(|(elem, content_id)| async move {
+        load_content_trigrams(ctx, blobstore, content_id)
+            .await?
+            .ok_or_else(|| {
+                ErrorKind::MissingContentTrigrams(
+                    String::from_utf8_lossy(elem.as_ref()).to_string(),
+                    content_id,
+                )
+                .into()
+            })
+    }))
+    .await?;
+    let mut bloom = TrigramBloom::new();
+    for content_trigrams in trigrams {
+        for trigram in content_trigrams.trigrams() {
+            bloom.insert(*trigram);
+        }
+    }
+    Ok(bloom)
+}
+
+/// Create a new trigram index node for the tree described by `tree_info`.
+async fn create_trigram_index(
+    ctx: &CoreContext,
+    blobstore: &RepoBlobstore,
+    sender: Option<Sender>,
+    tree_info: TreeInfo<TrigramIndexId, TrigramIndexFile, Option<TrigramIndexSummary>>,
+) -> Result<(Option<TrigramIndexSummary>, TrigramIndexId), Error> {
+    // Collect all directory entries from the parent trigram indexes as a
+    // cache, so that unchanged directories don't need to be fetched.
+    let parents = try_join_all(tree_info.parents.into_iter().map(|id| async move {
+        id.load(ctx.clone(), blobstore)
+            .await
+            .context(ErrorKind::MissingParent(id))
+    }))
+    .await?;
+    let mut dir_cache = HashMap::new();
+    for parent in parents.iter() {
+        for (_elem, entry) in parent.list() {
+            if let TrigramIndexEntry::Directory(dir) = entry {
+                dir_cache.entry(*dir.id()).or_insert_with(|| dir.clone());
+            }
+        }
+    }
+
+    let mut summary = TrigramIndexSummary::default();
+    let mut entries = BTreeMap::new();
+    let mut unsummarized_files = Vec::new();
+    let mut unsummarized_dirs = Vec::new();
+    for (elem, (entry_summary, entry)) in tree_info.subentries {
+        match entry {
+            Entry::Leaf(file) => {
+                if file.indexed() {
+                    summary.indexed_files_count += 1;
+                    // New files have their trigrams in their summary.
+                    // Unchanged files must have their trigrams loaded.
+                    match entry_summary {
+                        Some(Some(file_summary)) => {
+                            summary.trigram_bloom.union(&file_summary.trigram_bloom)
+                        }
+                        _ => unsummarized_files.push((elem.clone(), *file.content_id())),
+                    }
+                } else {
+                    summary.unindexed_files_count += 1;
+                }
+                entries.insert(elem, TrigramIndexEntry::File(file));
+            }
+            Entry::Tree(id) => {
+                let dir = match entry_summary {
+                    Some(Some(dir_summary)) => Some(TrigramIndexDirectory::new(id, dir_summary)),
+                    _ => dir_cache.get(&id).cloned(),
+                };
+                match dir {
+                    Some(dir) => {
+                        entries.insert(elem, TrigramIndexEntry::Directory(dir));
+                    }
+                    None => unsummarized_dirs.push((elem, id)),
+                }
+            }
+        }
+    }
+
+    // Some other directory is being used.  Fetch its summary from the
+    // blobstore.
+    let fetched_dirs = try_join_all(unsummarized_dirs.into_iter().map(|(elem, id)| async move {
+        let index = id.load(ctx.clone(), blobstore).await.with_context(|| {
+            ErrorKind::MissingSubentry(String::from_utf8_lossy(elem.as_ref()).to_string(), id)
+        })?;
+        Ok::<_, Error>((
+            elem,
+            TrigramIndexDirectory::new(id, index.summary().clone()),
+        ))
+    }))
+    .await?;
+    for (elem, dir) in fetched_dirs {
+        entries.insert(elem, TrigramIndexEntry::Directory(dir));
+    }
+
+    for entry in entries.values() {
+        if let TrigramIndexEntry::Directory(dir) = entry {
+            let dir_summary = dir.summary();
+            summary.indexed_files_count += dir_summary.indexed_files_count;
+            summary.unindexed_files_count += dir_summary.unindexed_files_count;
+            summary.trigram_bloom.union(&dir_summary.trigram_bloom);
+        }
+    }
+    let files_bloom = files_bloom(ctx, blobstore, unsummarized_files).await?;
+    summary.trigram_bloom.union(&files_bloom);
+
+    let index = TrigramIndex::new(entries, summary.clone());
+    let index_id = index.get_trigram_index_id();
+    let key = index_id.blobstore_key();
+    store_blob(ctx, blobstore, sender.as_ref(), key, index.into_blob()).await?;
+    Ok((Some(summary), index_id))
+}
+
+/// Create the trigram index leaf for a file, indexing its content if it
+/// hasn't been indexed before.
+async fn create_trigram_index_leaf(
+    ctx: &CoreContext,
+    blobstore: &RepoBlobstore,
+    sender: Sender,
+    leaf_info: LeafInfo<TrigramIndexFile, (ContentId, FileType)>,
+) -> Result<(Option<TrigramIndexSummary>, TrigramIndexFile), Error> {
+    let (content_id, file_type) = match leaf_info.leaf {
+        Some(leaf) => leaf,
+        None => {
+            // This bonsai changeset is a merge.  If all parents agree on
+            // the file, then it is valid, and has already been indexed.
+            if leaf_info.parents.len() < 2 {
+                return Err(ErrorKind::InvalidBonsai(
+                    "no change is provided, but file has only one parent".to_string(),
+                )
+                .into());
+            }
+            let mut iter = leaf_info.parents.into_iter();
+            let first = iter.next();
+            return match first {
+                Some(first) if iter.all(|next| next == first) => Ok((None, first)),
+                _ => Err(ErrorKind::InvalidBonsai(
+                    "no change is provided, but file content or type is different".to_string(),
+                )
+                .into()),
+            };
+        }
+    };
+
+    let trigrams = match load_content_trigrams(ctx, blobstore, content_id).await? {
+        Some(content_trigrams) => Some(content_trigrams.trigrams().to_vec()),
+        None => {
+            let key = FetchKey::Canonical(content_id);
+            let metadata = filestore::get_metadata(blobstore, ctx.clone(), &key)
+                .compat()
+                .await?
+                .ok_or(ErrorKind::MissingContent(content_id))?;
+            if metadata.total_size > MAX_INDEXED_FILE_SIZE {
+                None
+            } else {
+                let content = filestore::fetch_concat(blobstore, ctx.clone(), key)
+                    .compat()
+                    .await?;
+                let trigrams = content_trigrams(content.as_ref());
+                if let Some(trigrams) = &trigrams {
+                    let content_trigrams = ContentTrigrams::new(content_id, trigrams.clone());
+                    let key = ContentTrigramsId::from(content_id).blobstore_key();
+                    store_blob(
+                        ctx,
+                        blobstore,
+                        Some(&sender),
+                        key,
+                        content_trigrams.into_blob(),
+                    )
+                    .await?;
+                }
+                trigrams
+            }
+        }
+    };
+
+    let file = TrigramIndexFile::new(content_id, file_type, trigrams.is_some());
+    let summary = trigrams.map(|trigrams| {
+        let mut trigram_bloom = TrigramBloom::new();
+        for trigram in trigrams {
+            trigram_bloom.insert(trigram);
+        }
+        TrigramIndexSummary {
+            indexed_files_count: 1,
+            unindexed_files_count: 0,
+            trigram_bloom,
+        }
+    });
+    Ok((summary, file))
+}
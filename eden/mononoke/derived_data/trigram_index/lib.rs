@@ -0,0 +1,36 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+use mononoke_types::{ContentId, TrigramIndexId};
+use thiserror::Error;
+
+mod derive;
+mod mapping;
+mod query;
+mod search;
+#[cfg(test)]
+mod tests;
+
+pub use mapping::{RootTrigramIndexId, RootTrigramIndexMapping};
+pub use query::TrigramQuery;
+pub use search::{find_candidate_files, CandidateFiles};
+
+#[derive(Debug, Error)]
+pub enum ErrorKind {
+    #[error("Invalid bonsai changeset: {0}")]
+    InvalidBonsai(String),
+    #[error("Missing trigram index parent: {0}")]
+    MissingParent(TrigramIndexId),
+    #[error("Missing trigram index subentry for '{0}': {1}")]
+    MissingSubentry(String, TrigramIndexId),
+    #[error("Missing content trigrams for '{0}': {1}")]
+    MissingContentTrigrams(String, ContentId),
+    #[error("Missing content: {0}")]
+    MissingContent(ContentId),
+}
@@ -0,0 +1,166 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::derive::derive_trigram_index;
+use anyhow::{Error, Result};
+use blobrepo::BlobRepo;
+use blobstore::{Blobstore, BlobstoreGetData};
+use bytes::Bytes;
+use context::CoreContext;
+use derived_data::{BonsaiDerived, BonsaiDerivedMapping};
+use futures::future::{FutureExt as _, TryFutureExt};
+use futures_ext::{BoxFuture, FutureExt, StreamExt};
+use futures_old::{
+    stream::{self, FuturesUnordered},
+    Future, Stream,
+};
+use mononoke_types::{
+    BlobstoreBytes, BonsaiChangeset, ChangesetId, ContentId, FileType, MPath, TrigramIndexId,
+};
+use repo_blobstore::RepoBlobstore;
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    iter::FromIterator,
+};
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RootTrigramIndexId(TrigramIndexId);
+
+impl RootTrigramIndexId {
+    pub fn trigram_index_id(&self) -> &TrigramIndexId {
+        &self.0
+    }
+    pub fn into_trigram_index_id(self) -> TrigramIndexId {
+        self.0
+    }
+}
+
+impl TryFrom<BlobstoreBytes> for RootTrigramIndexId {
+    type Error = Error;
+
+    fn try_from(blob_bytes: BlobstoreBytes) -> Result<Self> {
+        TrigramIndexId::from_bytes(&blob_bytes.into_bytes()).map(RootTrigramIndexId)
+    }
+}
+
+impl TryFrom<BlobstoreGetData> for RootTrigramIndexId {
+    type Error = Error;
+
+    fn try_from(blob_get_data: BlobstoreGetData) -> Result<Self> {
+        blob_get_data.into_bytes().try_into()
+    }
+}
+
+impl From<RootTrigramIndexId> for BlobstoreBytes {
+    fn from(root_trigram_index_id: RootTrigramIndexId) -> Self {
+        BlobstoreBytes::from_bytes(Bytes::copy_from_slice(
+            root_trigram_index_id.0.blake2().as_ref(),
+        ))
+    }
+}
+
+impl BonsaiDerived for RootTrigramIndexId {
+    const NAME: &'static str = "trigram_index";
+    type Mapping = RootTrigramIndexMapping;
+
+    fn mapping(_ctx: &CoreContext, repo: &BlobRepo) -> Self::Mapping {
+        RootTrigramIndexMapping::new(repo.blobstore().clone())
+    }
+
+    fn derive_from_parents(
+        ctx: CoreContext,
+        repo: BlobRepo,
+        bonsai: BonsaiChangeset,
+        parents: Vec<Self>,
+    ) -> BoxFuture<Self, Error> {
+        let parents = parents
+            .into_iter()
+            .map(RootTrigramIndexId::into_trigram_index_id)
+            .collect();
+        let changes = get_file_changes(&bonsai);
+        async move {
+            derive_trigram_index(ctx, repo, parents, changes)
+                .await
+                .map(RootTrigramIndexId)
+        }
+        .boxed()
+        .compat()
+        .boxify()
+    }
+}
+
+#[derive(Clone)]
+pub struct RootTrigramIndexMapping {
+    blobstore: RepoBlobstore,
+}
+
+impl RootTrigramIndexMapping {
+    pub fn new(blobstore: RepoBlobstore) -> Self {
+        Self { blobstore }
+    }
+
+    fn format_key(&self, cs_id: ChangesetId) -> String {
+        format!("derived_root_trigramindex.{}", cs_id)
+    }
+
+    fn fetch_trigram_index(
+        &self,
+        ctx: CoreContext,
+        cs_id: ChangesetId,
+    ) -> impl Future<Item = Option<(ChangesetId, RootTrigramIndexId)>, Error = Error> {
+        self.blobstore
+            .get(ctx.clone(), self.format_key(cs_id))
+            .compat()
+            .and_then(|opt_blob| opt_blob.map(TryInto::try_into).transpose())
+            .map(move |maybe_root_trigram_index_id| {
+                maybe_root_trigram_index_id
+                    .map(|root_trigram_index_id| (cs_id, root_trigram_index_id))
+            })
+    }
+}
+
+impl BonsaiDerivedMapping for RootTrigramIndexMapping {
+    type Value = RootTrigramIndexId;
+
+    fn get(
+        &self,
+        ctx: CoreContext,
+        csids: Vec<ChangesetId>,
+    ) -> BoxFuture<HashMap<ChangesetId, Self::Value>, Error> {
+        let gets = csids.into_iter().map(|cs_id| {
+            self.fetch_trigram_index(ctx.clone(), cs_id)
+                .map(|maybe_root_trigram_index_id| {
+                    stream::iter_ok(maybe_root_trigram_index_id.into_iter())
+                })
+        });
+        FuturesUnordered::from_iter(gets)
+            .flatten()
+            .collect_to()
+            .boxify()
+    }
+
+    fn put(&self, ctx: CoreContext, csid: ChangesetId, id: Self::Value) -> BoxFuture<(), Error> {
+        self.blobstore
+            .put(ctx, self.format_key(csid), id.into())
+            .compat()
+            .boxify()
+    }
+}
+
+pub(crate) fn get_file_changes(
+    bcs: &BonsaiChangeset,
+) -> Vec<(MPath, Option<(ContentId, FileType)>)> {
+    bcs.file_changes()
+        .map(|(mpath, file_change)| {
+            (
+                mpath.clone(),
+                file_change.map(|file_change| (file_change.content_id(), file_change.file_type())),
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,114 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use mononoke_types::trigram_index::trigrams;
+use regex_syntax::hir::{Group, Hir, HirKind, Literal};
+use regex_syntax::Parser;
+
+/// The trigrams that any file matching a regex must contain.
+///
+/// This is computed from the literal strings that must appear in any match.
+/// Only literals which are required regardless of which alternatives or
+/// repetitions match are used, so a file which does not contain all of the
+/// trigrams cannot match the regex.  A query with no trigrams places no
+/// constraints on the files that may match.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TrigramQuery {
+    trigrams: Vec<u32>,
+}
+
+impl TrigramQuery {
+    /// Build the query for a regex.
+    pub fn from_regex(regex: &str) -> Result<Self, Error> {
+        let hir = Parser::new().parse(regex)?;
+        let mut literals = Vec::new();
+        required_literals(&hir, &mut literals);
+        let mut trigrams: Vec<u32> = literals
+            .iter()
+            .flat_map(|literal| trigrams(literal))
+            .collect();
+        trigrams.sort_unstable();
+        trigrams.dedup();
+        Ok(TrigramQuery { trigrams })
+    }
+
+    /// The trigrams that a matching file must contain, in sorted order.
+    pub fn trigrams(&self) -> &[u32] {
+        &self.trigrams
+    }
+}
+
+/// Append the bytes of a literal to a string, or return `false` if this is
+/// not a literal.
+fn push_literal(hir: &Hir, literal: &mut Vec<u8>) -> bool {
+    match hir.kind() {
+        HirKind::Literal(Literal::Unicode(c)) => {
+            let mut buf = [0; 4];
+            literal.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            true
+        }
+        HirKind::Literal(Literal::Byte(b)) => {
+            literal.push(*b);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Collect the literal strings which must appear in any match of `hir`.
+fn required_literals(hir: &Hir, literals: &mut Vec<Vec<u8>>) {
+    match hir.kind() {
+        HirKind::Literal(_) => {
+            let mut literal = Vec::new();
+            push_literal(hir, &mut literal);
+            literals.push(literal);
+        }
+        HirKind::Group(Group { hir, .. }) => required_literals(hir, literals),
+        HirKind::Concat(hirs) => {
+            let mut literal = Vec::new();
+            for hir in hirs {
+                if !push_literal(hir, &mut literal) {
+                    if !literal.is_empty() {
+                        literals.push(std::mem::take(&mut literal));
+                    }
+                    required_literals(hir, literals);
+                }
+            }
+            if !literal.is_empty() {
+                literals.push(literal);
+            }
+        }
+        // Anything else (classes, repetitions, alternations, etc.) may
+        // match in many ways, so doesn't require any particular literal.
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mononoke_types::trigram_index::trigram;
+
+    fn query(regex: &str) -> Vec<u32> {
+        TrigramQuery::from_regex(regex).unwrap().trigrams().to_vec()
+    }
+
+    #[test]
+    fn test_trigram_query() {
+        let mut expected = vec![trigram(*b"abc"), trigram(*b"bcd")];
+        expected.sort();
+        assert_eq!(query("abcd"), expected);
+        assert_eq!(query("ab[cd]ef"), vec![]);
+        let mut expected = vec![trigram(*b"foo"), trigram(*b"bar")];
+        expected.sort();
+        assert_eq!(query("foo.*(bar)"), expected);
+        assert_eq!(query("foo|bar"), vec![]);
+        assert_eq!(query("(?i)foo"), vec![]);
+        assert_eq!(query("(foo)?"), vec![]);
+    }
+}
@@ -0,0 +1,148 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use blobstore::Loadable;
+use context::CoreContext;
+use futures::compat::Future01CompatExt;
+use futures::future::try_join_all;
+use manifest::{Entry, ManifestOps};
+use mononoke_types::trigram_index::{ContentTrigrams, TrigramIndexEntry, TrigramIndexFile};
+use mononoke_types::{ContentId, ContentTrigramsId, MPath, TrigramIndexId};
+use repo_blobstore::RepoBlobstore;
+
+use crate::query::TrigramQuery;
+
+/// The files which may match a trigram query.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CandidateFiles {
+    /// Indexed files which contain all of the query's trigrams, and their
+    /// content ids, sorted by path.
+    pub indexed: Vec<(MPath, ContentId)>,
+    /// Files which could not be indexed because they are binary or larger
+    /// than `MAX_INDEXED_FILE_SIZE`, sorted by path.  The index cannot tell
+    /// whether these match.
+    pub unindexed: Vec<MPath>,
+}
+
+impl CandidateFiles {
+    pub fn len(&self) -> usize {
+        self.indexed.len() + self.unindexed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indexed.is_empty() && self.unindexed.is_empty()
+    }
+}
+
+/// Find the files under any of the `prefixes` which may match `query`.
+///
+/// Directories are only descended into if their bloom filter contains all
+/// of the query's trigrams, and indexed files are only returned if they
+/// contain all of the query's trigrams.  Files which could not be indexed
+/// are returned separately.  The files' contents must still be searched to
+/// find whether they actually match.
+///
+/// Returns `None` as soon as more than `limit` candidate files are found.
+pub async fn find_candidate_files(
+    ctx: &CoreContext,
+    blobstore: &RepoBlobstore,
+    root: TrigramIndexId,
+    prefixes: Vec<Option<MPath>>,
+    query: &TrigramQuery,
+    limit: usize,
+) -> Result<Option<CandidateFiles>, Error> {
+    let entries = try_join_all(prefixes.into_iter().map(|prefix| async move {
+        let entry = root
+            .find_entry(ctx.clone(), blobstore.clone(), prefix.clone())
+            .compat()
+            .await?;
+        Ok::<_, Error>(entry.map(|entry| (prefix, entry)))
+    }))
+    .await?;
+
+    let mut candidates = CandidateFiles::default();
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    for (path, entry) in entries.into_iter().flatten() {
+        match (path, entry) {
+            (path, Entry::Tree(id)) => dirs.push((path, id)),
+            (Some(path), Entry::Leaf(file)) => files.push((path, file)),
+            (None, Entry::Leaf(_)) => {}
+        }
+    }
+
+    loop {
+        filter_files(ctx, blobstore, query, files, &mut candidates).await?;
+        if candidates.len() > limit {
+            return Ok(None);
+        }
+        if dirs.is_empty() {
+            break;
+        }
+        let indexes = try_join_all(dirs.into_iter().map(|(path, id)| async move {
+            let index = id.load(ctx.clone(), blobstore).await?;
+            Ok::<_, Error>((path, index))
+        }))
+        .await?;
+        files = Vec::new();
+        dirs = Vec::new();
+        for (path, index) in indexes {
+            for (elem, entry) in index.list() {
+                let path = MPath::join_opt_element(path.as_ref(), elem);
+                match entry {
+                    TrigramIndexEntry::File(file) => files.push((path, file.clone())),
+                    TrigramIndexEntry::Directory(dir) => {
+                        if dir.summary().may_contain_all(query.trigrams()) {
+                            dirs.push((Some(path), *dir.id()));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Overlapping prefixes may have found the same file more than once.
+    candidates.indexed.sort();
+    candidates.indexed.dedup();
+    candidates.unindexed.sort();
+    candidates.unindexed.dedup();
+    Ok(Some(candidates))
+}
+
+/// Add the files which may match the query to `candidates`.
+async fn filter_files(
+    ctx: &CoreContext,
+    blobstore: &RepoBlobstore,
+    query: &TrigramQuery,
+    files: Vec<(MPath, TrigramIndexFile)>,
+    candidates: &mut CandidateFiles,
+) -> Result<(), Error> {
+    let files = try_join_all(files.into_iter().map(|(path, file)| async move {
+        if !file.indexed() {
+            return Ok::<_, Error>(Some((path, None)));
+        }
+        let content_id = *file.content_id();
+        if !query.trigrams().is_empty() {
+            let content_trigrams: ContentTrigrams = ContentTrigramsId::from(content_id)
+                .load(ctx.clone(), blobstore)
+                .await?;
+            if !content_trigrams.contains_all(query.trigrams()) {
+                return Ok(None);
+            }
+        }
+        Ok(Some((path, Some(content_id))))
+    }))
+    .await?;
+    for (path, content_id) in files.into_iter().flatten() {
+        match content_id {
+            Some(content_id) => candidates.indexed.push((path, content_id)),
+            None => candidates.unindexed.push(path),
+        }
+    }
+    Ok(())
+}
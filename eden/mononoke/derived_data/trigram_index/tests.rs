@@ -0,0 +1,129 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::{find_candidate_files, CandidateFiles, RootTrigramIndexId, TrigramQuery};
+use anyhow::Error;
+use blobrepo_factory::new_memblob_empty;
+use blobstore::Loadable;
+use context::CoreContext;
+use derived_data::BonsaiDerived;
+use fbinit::FacebookInit;
+use futures::compat::Future01CompatExt;
+use mononoke_types::MPath;
+use tests_utils::CreateCommitContext;
+
+const LIMIT: usize = 100;
+
+fn paths(candidates: Option<CandidateFiles>) -> (Vec<String>, Vec<String>) {
+    let candidates = candidates.expect("candidates should be within the limit");
+    let indexed = candidates
+        .indexed
+        .into_iter()
+        .map(|(path, _)| path.to_string())
+        .collect();
+    let unindexed = candidates
+        .unindexed
+        .into_iter()
+        .map(|path| path.to_string())
+        .collect();
+    (indexed, unindexed)
+}
+
+fn expected(indexed: &[&str], unindexed: &[&str]) -> (Vec<String>, Vec<String>) {
+    let to_strings = |paths: &[&str]| paths.iter().map(|path| path.to_string()).collect();
+    (to_strings(indexed), to_strings(unindexed))
+}
+
+#[fbinit::compat_test]
+async fn test_trigram_index(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo = new_memblob_empty(None)?;
+
+    let a = CreateCommitContext::new_root(&ctx, &repo)
+        .add_file("dir1/file", "hello world\n")
+        .add_file("dir1/sub/file", "goodbye world\n")
+        .add_file("dir2/file", "hello there\n")
+        .add_file("dir2/binary", "hello\0binary\n")
+        .add_file("top", "nothing to see here\n")
+        .commit()
+        .await?;
+    let b = CreateCommitContext::new(&ctx, &repo, vec![a])
+        .add_file("dir1/sub/file", "hello again\n")
+        .delete_file("dir2/binary")
+        .commit()
+        .await?;
+
+    let index_a = RootTrigramIndexId::derive(ctx.clone(), repo.clone(), a)
+        .compat()
+        .await?
+        .into_trigram_index_id();
+    let root_a = index_a.load(ctx.clone(), repo.blobstore()).await?;
+    assert_eq!(root_a.summary().indexed_files_count, 4);
+    assert_eq!(root_a.summary().unindexed_files_count, 1);
+
+    let hello = TrigramQuery::from_regex("hello")?;
+    assert_eq!(
+        paths(
+            find_candidate_files(&ctx, repo.blobstore(), index_a, vec![None], &hello, LIMIT)
+                .await?
+        ),
+        expected(&["dir1/file", "dir2/file"], &["dir2/binary"])
+    );
+
+    let goodbye = TrigramQuery::from_regex("good.ye w")?;
+    assert_eq!(
+        paths(
+            find_candidate_files(&ctx, repo.blobstore(), index_a, vec![None], &goodbye, LIMIT)
+                .await?
+        ),
+        expected(&["dir1/sub/file"], &["dir2/binary"])
+    );
+    assert_eq!(
+        paths(
+            find_candidate_files(
+                &ctx,
+                repo.blobstore(),
+                index_a,
+                vec![Some(MPath::new("dir1")?)],
+                &goodbye,
+                LIMIT,
+            )
+            .await?
+        ),
+        expected(&["dir1/sub/file"], &[])
+    );
+
+    // Too many candidates.
+    assert_eq!(
+        find_candidate_files(&ctx, repo.blobstore(), index_a, vec![None], &hello, 2).await?,
+        None
+    );
+
+    let index_b = RootTrigramIndexId::derive(ctx.clone(), repo.clone(), b)
+        .compat()
+        .await?
+        .into_trigram_index_id();
+    let root_b = index_b.load(ctx.clone(), repo.blobstore()).await?;
+    assert_eq!(root_b.summary().indexed_files_count, 4);
+    assert_eq!(root_b.summary().unindexed_files_count, 0);
+    assert_eq!(
+        paths(
+            find_candidate_files(&ctx, repo.blobstore(), index_b, vec![None], &hello, LIMIT)
+                .await?
+        ),
+        expected(&["dir1/file", "dir1/sub/file", "dir2/file"], &[])
+    );
+    assert_eq!(
+        paths(
+            find_candidate_files(&ctx, repo.blobstore(), index_b, vec![None], &goodbye, LIMIT)
+                .await?
+        ),
+        expected(&[], &[])
+    );
+
+    Ok(())
+}
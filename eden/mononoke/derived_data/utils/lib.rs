@@ -25,19 +25,19 @@ use derived_data_filenodes::{FilenodesOnlyPublic, FilenodesOnlyPublicMapping};
 use fastlog::{RootFastlog, RootFastlogMapping};
 use fsnodes::{RootFsnodeId, RootFsnodeMapping};
 use futures::{
-    compat::Future01CompatExt,
-    future::ready,
-    stream::{self, futures_unordered::FuturesUnordered},
-    Future, FutureExt, StreamExt, TryFutureExt, TryStreamExt,
+    compat::Future01CompatExt, future::try_join_all, stream, Future, FutureExt, StreamExt,
+    TryFutureExt, TryStreamExt,
 };
 use futures_ext::{BoxFuture, FutureExt as OldFutureExt};
 use futures_old::{future, stream as stream_old, Future as OldFuture, Stream};
 use mercurial_derived_data::{HgChangesetIdMapping, MappedHgChangesetId};
 use mononoke_types::{BonsaiChangeset, ChangesetId};
+use skeleton_manifest::{RootSkeletonManifestId, RootSkeletonManifestMapping};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{Arc, Mutex},
 };
+use trigram_index::{RootTrigramIndexId, RootTrigramIndexMapping};
 use unodes::{RootUnodeManifestId, RootUnodeManifestMapping};
 
 pub const POSSIBLE_DERIVED_TYPES: &[&str] = &[
@@ -49,6 +49,8 @@ pub const POSSIBLE_DERIVED_TYPES: &[&str] = &[
     ChangesetInfo::NAME,
     RootDeletedManifestId::NAME,
     FilenodesOnlyPublic::NAME,
+    RootSkeletonManifestId::NAME,
+    RootTrigramIndexId::NAME,
 ];
 
 pub fn derive_data_for_csids(
@@ -57,34 +59,10 @@ pub fn derive_data_for_csids(
     csids: Vec<ChangesetId>,
     derived_data_types: &[String],
 ) -> Result<impl Future<Output = Result<(), Error>>, Error> {
-    let derivations = FuturesUnordered::new();
-
-    for data_type in derived_data_types {
-        let derived_utils = derived_data_utils(repo.clone(), data_type)?;
-
-        let mut futs = vec![];
-        for csid in &csids {
-            let fut = derived_utils
-                .derive(ctx.clone(), repo.clone(), *csid)
-                .map(|_| ())
-                .compat();
-            futs.push(fut);
-        }
-
-        derivations.push(async move {
-            // Call functions sequentially because derived data is sequential
-            // so there's no point in trying to derive it in parallel
-            for f in futs {
-                f.await?;
-            }
-            Result::<_, Error>::Ok(())
-        });
-    }
+    let graph = derived_data_graph(repo.clone(), derived_data_types)?;
 
-    Ok(async move {
-        derivations.try_for_each(|_| ready(Ok(()))).await?;
-        Ok(())
-    })
+    cloned!(ctx, repo);
+    Ok(async move { graph.derive(&ctx, &repo, &csids).await })
 }
 
 #[async_trait]
@@ -118,6 +96,9 @@ pub trait DerivedUtils: Send + Sync + 'static {
     /// Get a name for this type of derived data
     fn name(&self) -> &'static str;
 
+    /// Get names of the types of derived data this type depends on
+    fn dependencies(&self) -> &'static [&'static str];
+
     async fn find_oldest_underived<'a>(
         &'a self,
         ctx: &'a CoreContext,
@@ -289,6 +270,10 @@ where
     fn name(&self) -> &'static str {
         M::Value::NAME
     }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        M::Value::DEPENDENCIES
+    }
 }
 
 #[derive(Clone)]
@@ -405,6 +390,219 @@ fn derived_data_utils_impl(
             let mapping = FilenodesOnlyPublicMapping::new(repo);
             Ok(Arc::new(DerivedUtilsFromMapping::new(mapping, mode)))
         }
+        RootSkeletonManifestId::NAME => {
+            let mapping = RootSkeletonManifestMapping::new(repo.get_blobstore());
+            Ok(Arc::new(DerivedUtilsFromMapping::new(mapping, mode)))
+        }
+        RootTrigramIndexId::NAME => {
+            let mapping = RootTrigramIndexMapping::new(repo.get_blobstore());
+            Ok(Arc::new(DerivedUtilsFromMapping::new(mapping, mode)))
+        }
         name => Err(format_err!("Unsupported derived data type: {}", name)),
     }
 }
+
+/// A set of derived data types together with all of the types they depend on,
+/// grouped into stages so that each type's dependencies are in earlier stages.
+/// Types within the same stage don't depend on each other, so they can be
+/// derived in parallel.
+#[derive(Clone)]
+pub struct DeriveGraph {
+    stages: Vec<Vec<Arc<dyn DerivedUtils>>>,
+}
+
+impl DeriveGraph {
+    fn new(repo: BlobRepo, names: &[impl AsRef<str>], mode: DeriveMode) -> Result<Self, Error> {
+        let requested: HashSet<&str> = names.iter().map(|name| name.as_ref()).collect();
+        let mut depths = HashMap::new();
+        let mut utils = HashMap::new();
+        for name in names {
+            Self::visit(
+                &repo,
+                name.as_ref(),
+                mode,
+                &requested,
+                &mut Vec::new(),
+                &mut depths,
+                &mut utils,
+            )?;
+        }
+
+        let mut stages = Vec::new();
+        for (name, depth) in depths {
+            while stages.len() <= depth {
+                stages.push(Vec::new());
+            }
+            if let Some(derived_utils) = utils.remove(name) {
+                stages[depth].push(derived_utils);
+            }
+        }
+        for stage in stages.iter_mut() {
+            stage.sort_by_key(|derived_utils: &Arc<dyn DerivedUtils>| derived_utils.name());
+        }
+        Ok(Self { stages })
+    }
+
+    /// Visit a type and all its dependencies, returning the stage that the
+    /// type must be derived in.
+    ///
+    /// Only the `requested` types are derived with `mode`. Their dependencies
+    /// are always derived, as deriving the requested types would derive them
+    /// anyway, even if they are not enabled for this repo.
+    fn visit(
+        repo: &BlobRepo,
+        name: &str,
+        mode: DeriveMode,
+        requested: &HashSet<&str>,
+        visiting: &mut Vec<&'static str>,
+        depths: &mut HashMap<&'static str, usize>,
+        utils: &mut HashMap<&'static str, Arc<dyn DerivedUtils>>,
+    ) -> Result<usize, Error> {
+        if let Some(depth) = depths.get(name) {
+            return Ok(*depth);
+        }
+        let type_mode = if requested.contains(name) {
+            mode
+        } else {
+            DeriveMode::Unsafe
+        };
+        let derived_utils = derived_data_utils_impl(repo.clone(), name, type_mode)?;
+        let name = derived_utils.name();
+        if visiting.contains(&name) {
+            return Err(format_err!(
+                "Cycle in derived data dependencies: {} -> {}",
+                visiting.join(" -> "),
+                name
+            ));
+        }
+
+        visiting.push(name);
+        let mut depth = 0;
+        for dependency in derived_utils.dependencies() {
+            let dependency_depth =
+                Self::visit(repo, dependency, mode, requested, visiting, depths, utils)?;
+            depth = std::cmp::max(depth, dependency_depth + 1);
+        }
+        visiting.pop();
+
+        depths.insert(name, depth);
+        utils.insert(name, derived_utils);
+        Ok(depth)
+    }
+
+    /// Stages of derived data types, in the order they should be derived
+    pub fn stages(&self) -> &[Vec<Arc<dyn DerivedUtils>>] {
+        &self.stages
+    }
+
+    /// All derived data types in the graph, in the order they should be derived
+    pub fn types(&self) -> impl Iterator<Item = &Arc<dyn DerivedUtils>> {
+        self.stages.iter().flatten()
+    }
+
+    /// Run `f` for each derived data type. Each stage is only started once
+    /// all types in the previous stage have finished, and the types within
+    /// a stage are run concurrently.
+    pub async fn try_for_each_type<F, Fut>(&self, f: F) -> Result<(), Error>
+    where
+        F: Fn(Arc<dyn DerivedUtils>) -> Fut,
+        Fut: Future<Output = Result<(), Error>>,
+    {
+        for stage in &self.stages {
+            try_join_all(stage.iter().cloned().map(&f)).await?;
+        }
+        Ok(())
+    }
+
+    /// Derive all types for the changesets, which should be in
+    /// topological order.
+    pub async fn derive(
+        &self,
+        ctx: &CoreContext,
+        repo: &BlobRepo,
+        csids: &[ChangesetId],
+    ) -> Result<(), Error> {
+        self.try_for_each_type(|derived_utils| async move {
+            // Derive changesets sequentially because derived data is sequential
+            // so there's no point in trying to derive it in parallel
+            for csid in csids {
+                derived_utils
+                    .derive(ctx.clone(), repo.clone(), *csid)
+                    .compat()
+                    .await?;
+            }
+            Ok(())
+        })
+        .await
+    }
+}
+
+/// Build the graph for deriving `names` and all of their dependencies.
+/// Deriving fails if any of `names` is not enabled for this repo, but their
+/// dependencies are derived even if they are not enabled.
+pub fn derived_data_graph(repo: BlobRepo, names: &[impl AsRef<str>]) -> Result<DeriveGraph, Error> {
+    DeriveGraph::new(repo, names, DeriveMode::OnlyIfEnabled)
+}
+
+/// Build the graph for deriving `names` and all of their dependencies,
+/// even if they are not enabled for this repo.
+pub fn derived_data_graph_unsafe(
+    repo: BlobRepo,
+    names: &[impl AsRef<str>],
+) -> Result<DeriveGraph, Error> {
+    DeriveGraph::new(repo, names, DeriveMode::Unsafe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bookmarks::BookmarkName;
+    use fbinit::FacebookInit;
+    use fixtures::linear;
+    use maplit::btreeset;
+    use metaconfig_types::DerivedDataConfig;
+
+    #[fbinit::compat_test]
+    async fn test_derive_with_disabled_dependencies(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = linear::getrepo(fb).await.dangerous_override(
+            |mut derived_data_config: DerivedDataConfig| {
+                derived_data_config.derived_data_types =
+                    btreeset! {FilenodesOnlyPublic::NAME.to_string()};
+                derived_data_config
+            },
+        );
+        let master = repo
+            .get_bonsai_bookmark(ctx.clone(), &BookmarkName::new("master")?)
+            .compat()
+            .await?
+            .ok_or_else(|| anyhow!("master is missing"))?;
+
+        // Hg changesets are not enabled, but filenodes depend on them.
+        derive_data_for_csids(
+            &ctx,
+            &repo,
+            vec![master],
+            &[FilenodesOnlyPublic::NAME.to_string()],
+        )?
+        .await?;
+
+        let pending = derived_data_utils(repo.clone(), FilenodesOnlyPublic::NAME)?
+            .pending(ctx.clone(), repo.clone(), vec![master])
+            .compat()
+            .await?;
+        assert!(pending.is_empty());
+
+        // Types that are requested explicitly must still be enabled.
+        assert!(derive_data_for_csids(
+            &ctx,
+            &repo,
+            vec![master],
+            &[RootUnodeManifestId::NAME.to_string()],
+        )?
+        .await
+        .is_err());
+
+        Ok(())
+    }
+}
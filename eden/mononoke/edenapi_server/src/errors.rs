@@ -11,6 +11,7 @@ use anyhow::Error;
 use thiserror::Error;
 
 use gotham_ext::error::HttpError;
+use mercurial_types::HgChangesetId;
 use mononoke_api::MononokeError;
 use types::Key;
 
@@ -56,6 +57,12 @@ pub enum ErrorKind {
     HistoryFetchFailed(Key),
     #[error("Complete tree request failed")]
     CompleteTreeRequestFailed,
+    #[error("Invalid commit: {0}")]
+    InvalidCommit(String),
+    #[error("Commit does not exist: {0}")]
+    CommitDoesNotExist(HgChangesetId),
+    #[error("Failed to archive commit: {0}")]
+    ArchiveFailed(HgChangesetId),
 }
 
 /// Extension trait for converting `MononokeError`s into `HttpErrors`.
@@ -82,6 +89,8 @@ impl MononokeErrorExt for MononokeError {
             ServicePermissionDenied { .. } => HttpError::e403,
             ServiceRestricted { .. } => HttpError::e403,
             NotAvailable { .. } => HttpError::e503,
+            PushrebaseConflicts(_) => HttpError::e400,
+            HookFailure(_) => HttpError::e400,
             InternalError(_) => HttpError::e500,
         })(Error::from(self).context(context))
     }
@@ -0,0 +1,93 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use anyhow::{format_err, Context, Error};
+use futures::TryStreamExt;
+use gotham::state::{FromState, State};
+use gotham_derive::{StateData, StaticResponseExtender};
+use mime::Mime;
+use serde::Deserialize;
+
+use gotham_ext::{
+    error::HttpError,
+    response::{StreamBody, TryIntoResponse},
+};
+use mercurial_types::HgChangesetId;
+use mononoke_api::{path::MononokePath, ArchiveFormat};
+
+use crate::context::ServerContext;
+use crate::errors::{ErrorKind, MononokeErrorExt};
+use crate::middleware::RequestContext;
+use crate::utils::get_repo;
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct ArchiveParams {
+    repo: String,
+    commit: String,
+}
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct ArchiveQueryString {
+    /// One of `tar`, `tar.gz` or `zip`.  Defaults to `tar`.
+    format: Option<String>,
+    /// Only include files under these paths.
+    #[serde(default)]
+    path: Vec<String>,
+}
+
+fn parse_format(format: Option<&str>) -> Result<(ArchiveFormat, Mime), Error> {
+    let (format, mime) = match format.unwrap_or("tar") {
+        "tar" => (ArchiveFormat::Tar, "application/x-tar"),
+        "tar.gz" | "tgz" => (ArchiveFormat::TarGz, "application/gzip"),
+        "zip" => (ArchiveFormat::Zip, "application/zip"),
+        other => return Err(format_err!("Unsupported archive format: {}", other)),
+    };
+    Ok((format, mime.parse()?))
+}
+
+/// Stream an archive of the files in a commit, optionally restricted to
+/// the files under the paths given in the query string.
+pub async fn archive(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let rctx = RequestContext::borrow_from(state);
+    let sctx = ServerContext::borrow_from(state);
+    let params = ArchiveParams::borrow_from(state);
+    let query = ArchiveQueryString::borrow_from(state);
+
+    let (format, mime) = parse_format(query.format.as_deref()).map_err(HttpError::e400)?;
+    let prefixes = if query.path.is_empty() {
+        None
+    } else {
+        let prefixes = query
+            .path
+            .iter()
+            .map(|path| MononokePath::try_from(path))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.into_http_error("Invalid path"))?;
+        Some(prefixes)
+    };
+    let commit = HgChangesetId::from_str(&params.commit)
+        .context(ErrorKind::InvalidCommit(params.commit.clone()))
+        .map_err(HttpError::e400)?;
+
+    let repo = get_repo(&sctx, &rctx, &params.repo).await?;
+    let changeset = repo
+        .changeset(commit)
+        .await
+        .map_err(|e| e.into_http_error(ErrorKind::ArchiveFailed(commit)))?
+        .with_context(|| ErrorKind::CommitDoesNotExist(commit))
+        .map_err(HttpError::e404)?;
+    let stream = changeset
+        .archive(format, prefixes)
+        .await
+        .map_err(|e| e.into_http_error(ErrorKind::ArchiveFailed(commit)))?
+        .map_err(move |e| Error::from(e).context(ErrorKind::ArchiveFailed(commit)));
+
+    Ok(StreamBody::new(stream, mime))
+}
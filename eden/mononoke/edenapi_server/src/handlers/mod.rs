@@ -23,6 +23,7 @@ use gotham_ext::response::build_response;
 
 use crate::context::ServerContext;
 
+mod archive;
 mod complete_trees;
 mod data;
 mod history;
@@ -56,6 +57,7 @@ define_handler!(files_handler, data::files);
 define_handler!(trees_handler, data::trees);
 define_handler!(complete_trees_handler, complete_trees::complete_trees);
 define_handler!(history_handler, history::history);
+define_handler!(archive_handler, archive::archive);
 
 fn health_handler(state: State) -> (State, &'static str) {
     if ServerContext::borrow_from(&state).will_exit() {
@@ -88,5 +90,10 @@ pub fn build_router(ctx: ServerContext) -> Router {
             .post("/:repo/history")
             .with_path_extractor::<history::HistoryParams>()
             .to(history_handler);
+        route
+            .get("/:repo/archive/:commit")
+            .with_path_extractor::<archive::ArchiveParams>()
+            .with_query_string_extractor::<archive::ArchiveQueryString>()
+            .to(archive_handler);
     })
 }
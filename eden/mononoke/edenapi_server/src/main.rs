@@ -35,7 +35,10 @@ use cmdlib::{
 use fbinit::FacebookInit;
 use gotham_ext::{
     handler::MononokeHttpHandler,
-    middleware::{ClientIdentityMiddleware, ServerIdentityMiddleware, TlsSessionDataMiddleware},
+    middleware::{
+        ClientIdentityMiddleware, CompressionMiddleware, ServerIdentityMiddleware,
+        TlsSessionDataMiddleware,
+    },
     socket_data::TlsSocketData,
 };
 use mononoke_api::Mononoke;
@@ -149,6 +152,7 @@ async fn start(
             "edenapi_server",
         )))
         .add(RequestContextMiddleware::new(fb, logger.clone()))
+        .add(CompressionMiddleware::new())
         .build(router);
 
     // Set up socket and TLS acceptor that this server will listen on.
@@ -5,14 +5,18 @@
  * GNU General Public License version 2.
  */
 
+use futures::channel::oneshot;
 use gotham::state::{request_id, FromState, State};
 use gotham_derive::StateData;
 use hyper::{Body, Response};
-use slog::{o, Logger};
+use slog::{debug, o, Logger};
 
 use context::{CoreContext, SessionContainer};
 use fbinit::FacebookInit;
-use gotham_ext::middleware::{ClientIdentity, Middleware};
+use gotham_ext::{
+    middleware::{ClientIdentity, Middleware, ResponseCompression},
+    response::signal_body,
+};
 use permission_checker::MononokeIdentitySet;
 use scuba::ScubaSampleBuilder;
 
@@ -63,6 +67,33 @@ impl Middleware for RequestContextMiddleware {
 
         None
     }
+
+    async fn outbound(&self, state: &mut State, response: &mut Response<Body>) {
+        let compression = match ResponseCompression::try_borrow_from(&state) {
+            Some(compression) => compression.clone(),
+            None => return,
+        };
+        let logger = match RequestContext::try_borrow_from(&state) {
+            Some(rctx) => rctx.ctx.logger().clone(),
+            None => return,
+        };
+
+        // Log the compressed and uncompressed sizes once the whole response has been sent.
+        let (sender, receiver) = oneshot::channel();
+        signal_body(response, sender);
+        tokio::spawn(async move {
+            if let Ok(bytes_sent) = receiver.await {
+                debug!(
+                    logger,
+                    "Sent compressed response";
+                    "content_encoding" => compression.encoding().as_str(),
+                    "uncompressed_bytes" => compression.uncompressed_bytes(),
+                    "compressed_bytes" => compression.compressed_bytes(),
+                    "bytes_sent" => bytes_sent,
+                );
+            }
+        });
+    }
 }
 
 fn extract_identities(state: &State) -> Option<MononokeIdentitySet> {
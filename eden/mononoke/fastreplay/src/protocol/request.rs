@@ -66,3 +66,42 @@ impl<'a> RequestLine<'a> {
         self.normal.mononoke_session_uuid.is_some()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocol::{parse_command_and_args, Request};
+    use anyhow::Error;
+    use context::{CoreContext, SessionId};
+    use fbinit::FacebookInit;
+    use repo_client::WireprotoLogging;
+    use std::time::Duration;
+
+    #[fbinit::test]
+    fn test_parse_captured_line(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let wireproto = WireprotoLogging::new(fb, "repo".to_string(), None, None, None)?;
+        let mut sample = wireproto.sample(
+            &ctx,
+            "getpackv1".to_string(),
+            SessionId::from_string("session"),
+            Duration::from_millis(1),
+        );
+        sample.add("args", include_str!("./fixtures/getpack.json"));
+        let line = sample.get_sample().to_json()?.to_string();
+
+        let req = serde_json::from_str::<RequestLine>(&line)?;
+        assert_eq!(req.normal.command, "getpackv1");
+        assert_eq!(req.normal.reponame, "repo");
+        assert!(req.is_mononoke());
+        assert_eq!(req.duration_us(), 1000);
+
+        let args = req.normal.args.as_ref().expect("args are captured");
+        match parse_command_and_args(&req.normal.command, args)? {
+            Request::GetpackV1(_) => {}
+            _ => panic!("getpackv1 was parsed as another command"),
+        }
+
+        Ok(())
+    }
+}
@@ -11,10 +11,12 @@ use gotham::state::State;
 use hyper::{Body, Response};
 
 pub mod client_identity;
+pub mod compression;
 pub mod server_identity;
 pub mod tls_session_data;
 
 pub use client_identity::{ClientIdentity, ClientIdentityMiddleware};
+pub use compression::{CompressionMiddleware, ContentEncoding, ResponseCompression};
 pub use server_identity::ServerIdentityMiddleware;
 pub use tls_session_data::TlsSessionDataMiddleware;
 
@@ -0,0 +1,461 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::io::Write;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use anyhow::Error;
+use bytes::Bytes;
+use flate2::{write::GzEncoder, Compression};
+use futures::{
+    channel::mpsc,
+    stream::{self, StreamExt, TryStreamExt},
+};
+use gotham::state::{FromState, State};
+use gotham_derive::StateData;
+use hyper::{
+    body::HttpBody,
+    header::{
+        HeaderMap, HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE,
+        VARY,
+    },
+    Body, Method, Response, StatusCode,
+};
+use mime::Mime;
+
+use super::Middleware;
+
+/// Compression level used for zstd. Responses are compressed as they are streamed, so this favors
+/// speed over ratio.
+const ZSTD_LEVEL: i32 = 1;
+
+/// Content encodings the server can compress responses with, in order of preference.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ContentEncoding {
+    Zstd,
+    Gzip,
+}
+
+impl ContentEncoding {
+    const SUPPORTED: [ContentEncoding; 2] = [ContentEncoding::Zstd, ContentEncoding::Gzip];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentEncoding::Zstd => "zstd",
+            ContentEncoding::Gzip => "gzip",
+        }
+    }
+
+    fn matches(&self, coding: &str) -> bool {
+        match self {
+            ContentEncoding::Zstd => coding.eq_ignore_ascii_case("zstd"),
+            ContentEncoding::Gzip => {
+                coding.eq_ignore_ascii_case("gzip") || coding.eq_ignore_ascii_case("x-gzip")
+            }
+        }
+    }
+
+    /// Pick the encoding to use for a response from the client's Accept-Encoding headers. The
+    /// encoding with the highest quality value wins, and ties go to the server's preference.
+    /// Returns None if the client didn't ask for any encoding we support.
+    pub fn negotiate(headers: &HeaderMap) -> Option<Self> {
+        let mut codings = Vec::new();
+        for header in headers.get_all(ACCEPT_ENCODING) {
+            let header = match header.to_str() {
+                Ok(header) => header,
+                Err(_) => continue,
+            };
+            codings.extend(header.split(',').filter_map(parse_coding));
+        }
+
+        let quality = |encoding: &ContentEncoding| {
+            codings
+                .iter()
+                .find(|(coding, _)| encoding.matches(coding))
+                .or_else(|| codings.iter().find(|(coding, _)| *coding == "*"))
+                .map(|(_, quality)| *quality)
+                .unwrap_or(0.0)
+        };
+
+        let mut best: Option<(ContentEncoding, f32)> = None;
+        for encoding in Self::SUPPORTED.iter() {
+            let quality = quality(encoding);
+            if quality <= 0.0 {
+                continue;
+            }
+            match best {
+                Some((_, best_quality)) if best_quality >= quality => {}
+                _ => best = Some((*encoding, quality)),
+            }
+        }
+
+        best.map(|(encoding, _)| encoding)
+    }
+}
+
+/// Parse an element of an Accept-Encoding header into a coding and its quality value.
+fn parse_coding(element: &str) -> Option<(&str, f32)> {
+    let mut parts = element.split(';').map(str::trim);
+    let coding = parts.next().filter(|coding| !coding.is_empty())?;
+
+    let mut quality = 1.0;
+    for param in parts {
+        let mut kv = param.splitn(2, '=').map(str::trim);
+        if let (Some(key), Some(value)) = (kv.next(), kv.next()) {
+            if key.eq_ignore_ascii_case("q") {
+                quality = value.parse().ok()?;
+            }
+        }
+    }
+
+    Some((coding, quality))
+}
+
+/// Content types whose data is already compressed, and that won't get any smaller if we compress
+/// them again.
+fn is_compressed_mime(mime: &Mime) -> bool {
+    match (mime.type_(), mime.subtype().as_str()) {
+        (mime::IMAGE, _) | (mime::VIDEO, _) | (mime::AUDIO, _) => true,
+        (mime::APPLICATION, subtype) => match subtype {
+            "zip" | "gzip" | "x-gzip" | "zstd" | "x-bzip2" | "x-xz" | "x-7z-compressed" => true,
+            // Opaque binary data (such as LFS blobs) is often compressed already, and it's usually
+            // too large to be worth spending CPU on.
+            "octet-stream" => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// The compression applied to a response, along with the number of bytes that went into and
+/// came out of the compressor. The counts are updated as the response body is sent to the client,
+/// so they are only final once the body has been sent.
+#[derive(StateData, Clone)]
+pub struct ResponseCompression {
+    encoding: ContentEncoding,
+    uncompressed_bytes: Arc<AtomicU64>,
+    compressed_bytes: Arc<AtomicU64>,
+}
+
+impl ResponseCompression {
+    fn new(encoding: ContentEncoding) -> Self {
+        Self {
+            encoding,
+            uncompressed_bytes: Arc::new(AtomicU64::new(0)),
+            compressed_bytes: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn encoding(&self) -> ContentEncoding {
+        self.encoding
+    }
+
+    pub fn uncompressed_bytes(&self) -> u64 {
+        self.uncompressed_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn compressed_bytes(&self) -> u64 {
+        self.compressed_bytes.load(Ordering::Relaxed)
+    }
+}
+
+/// The encoding requested by the client, recorded on inbound.
+#[derive(StateData)]
+struct AcceptedEncoding(ContentEncoding);
+
+enum Encoder {
+    Zstd(zstd::stream::write::Encoder<Vec<u8>>),
+    Gzip(GzEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    fn new(encoding: ContentEncoding) -> Result<Self, Error> {
+        Ok(match encoding {
+            ContentEncoding::Zstd => {
+                Encoder::Zstd(zstd::stream::write::Encoder::new(Vec::new(), ZSTD_LEVEL)?)
+            }
+            ContentEncoding::Gzip => Encoder::Gzip(GzEncoder::new(Vec::new(), Compression::fast())),
+        })
+    }
+
+    /// Compress a chunk of data and flush the encoder, so that the client can decompress all of
+    /// the data sent so far without waiting for the rest of the response.
+    fn write(&mut self, chunk: &[u8]) -> Result<Bytes, Error> {
+        let output = match self {
+            Encoder::Zstd(encoder) => {
+                encoder.write_all(chunk)?;
+                encoder.flush()?;
+                encoder.get_mut()
+            }
+            Encoder::Gzip(encoder) => {
+                encoder.write_all(chunk)?;
+                encoder.flush()?;
+                encoder.get_mut()
+            }
+        };
+        Ok(Bytes::from(std::mem::replace(output, Vec::new())))
+    }
+
+    /// Flush the remaining compressed output.
+    fn finish(self) -> Result<Bytes, Error> {
+        let output = match self {
+            Encoder::Zstd(encoder) => encoder.finish()?,
+            Encoder::Gzip(encoder) => encoder.finish()?,
+        };
+        Ok(Bytes::from(output))
+    }
+}
+
+/// Compress a body as it is streamed, without buffering it in memory.
+fn compress_body(body: Body, encoder: Encoder, compression: &ResponseCompression) -> Body {
+    let uncompressed_bytes = compression.uncompressed_bytes.clone();
+    let compressed_bytes = compression.compressed_bytes.clone();
+
+    let stream = stream::try_unfold((body, Some(encoder)), move |(mut body, mut encoder)| {
+        let uncompressed_bytes = uncompressed_bytes.clone();
+        async move {
+            let output = loop {
+                let active = match encoder.as_mut() {
+                    Some(active) => active,
+                    None => return Ok(None),
+                };
+                match body.try_next().await? {
+                    Some(chunk) => {
+                        uncompressed_bytes.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                        let output = active.write(&chunk)?;
+                        if !output.is_empty() {
+                            break output;
+                        }
+                    }
+                    None => {
+                        let active = encoder.take().expect("presence checked above");
+                        break active.finish()?;
+                    }
+                }
+            };
+            Ok::<_, Error>(Some((output, (body, encoder))))
+        }
+    })
+    .inspect_ok(move |chunk| {
+        compressed_bytes.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+    });
+
+    // As in StreamBody, Hyper requires the body stream to be Sync, which the encoders are not, so
+    // we compress on a separate task and give Hyper a channel that receives from it.
+    let (sender, receiver) = mpsc::channel(0);
+    tokio::spawn(stream.map(Ok).forward(sender));
+
+    Body::wrap_stream(receiver)
+}
+
+fn should_compress(state: &State, response: &Response<Body>) -> bool {
+    if Method::try_borrow_from(state) == Some(&Method::HEAD) {
+        return false;
+    }
+
+    match response.status() {
+        StatusCode::NO_CONTENT | StatusCode::NOT_MODIFIED | StatusCode::PARTIAL_CONTENT => {
+            return false;
+        }
+        _ => {}
+    }
+
+    let headers = response.headers();
+    if headers.contains_key(CONTENT_ENCODING) {
+        return false;
+    }
+
+    let mime = headers
+        .get(CONTENT_TYPE)
+        .and_then(|mime| mime.to_str().ok())
+        .and_then(|mime| mime.parse::<Mime>().ok());
+    if mime.map_or(true, |mime| is_compressed_mime(&mime)) {
+        return false;
+    }
+
+    response.body().size_hint().exact() != Some(0)
+}
+
+/// Compresses response bodies with an encoding from the client's Accept-Encoding header. Bodies
+/// that are empty, or whose content type is already compressed, are sent as-is.
+pub struct CompressionMiddleware;
+
+impl CompressionMiddleware {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for CompressionMiddleware {
+    async fn inbound(&self, state: &mut State) -> Option<Response<Body>> {
+        let encoding = HeaderMap::try_borrow_from(&state).and_then(ContentEncoding::negotiate);
+        if let Some(encoding) = encoding {
+            state.put(AcceptedEncoding(encoding));
+        }
+
+        None
+    }
+
+    async fn outbound(&self, state: &mut State, response: &mut Response<Body>) {
+        // The response depends on Accept-Encoding whether or not we compress this one.
+        response
+            .headers_mut()
+            .append(VARY, HeaderValue::from_static("Accept-Encoding"));
+
+        let encoding = match AcceptedEncoding::try_borrow_from(&state) {
+            Some(accepted) => accepted.0,
+            None => return,
+        };
+
+        if !should_compress(state, response) {
+            return;
+        }
+
+        // If we can't set up the encoder, send the response uncompressed.
+        let encoder = match Encoder::new(encoding) {
+            Ok(encoder) => encoder,
+            Err(_) => return,
+        };
+
+        let compression = ResponseCompression::new(encoding);
+        let body = std::mem::replace(response.body_mut(), Body::empty());
+        *response.body_mut() = compress_body(body, encoder, &compression);
+
+        let headers = response.headers_mut();
+        headers.remove(CONTENT_LENGTH);
+        headers.insert(
+            CONTENT_ENCODING,
+            HeaderValue::from_static(encoding.as_str()),
+        );
+
+        state.put(compression);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::{read::GzDecoder, write::GzDecoder as GzWriteDecoder};
+    use std::io::Read;
+
+    const CHUNKS: [&str; 3] = ["first chunk, ", "second chunk, ", "and the last chunk"];
+
+    fn decompress(encoding: ContentEncoding, compressed: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(match encoding {
+            ContentEncoding::Zstd => zstd::stream::decode_all(compressed)?,
+            ContentEncoding::Gzip => {
+                let mut decompressed = Vec::new();
+                GzDecoder::new(compressed).read_to_end(&mut decompressed)?;
+                decompressed
+            }
+        })
+    }
+
+    fn negotiate(accept_encoding: &[&'static str]) -> Option<ContentEncoding> {
+        let mut headers = HeaderMap::new();
+        for value in accept_encoding {
+            headers.append(ACCEPT_ENCODING, HeaderValue::from_static(value));
+        }
+        ContentEncoding::negotiate(&headers)
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(&[]), None);
+        assert_eq!(negotiate(&["identity"]), None);
+        assert_eq!(negotiate(&["gzip"]), Some(ContentEncoding::Gzip));
+        assert_eq!(negotiate(&["x-gzip, br"]), Some(ContentEncoding::Gzip));
+        assert_eq!(negotiate(&["gzip, zstd"]), Some(ContentEncoding::Zstd));
+        assert_eq!(negotiate(&["gzip", "zstd"]), Some(ContentEncoding::Zstd));
+        assert_eq!(
+            negotiate(&["zstd;q=0.5, gzip;q=0.8"]),
+            Some(ContentEncoding::Gzip)
+        );
+        assert_eq!(negotiate(&["zstd;q=0, gzip;q=0"]), None);
+        assert_eq!(negotiate(&["*"]), Some(ContentEncoding::Zstd));
+        assert_eq!(negotiate(&["zstd;q=0, *"]), Some(ContentEncoding::Gzip));
+        assert_eq!(
+            negotiate(&["gzip;q=bogus, zstd"]),
+            Some(ContentEncoding::Zstd)
+        );
+    }
+
+    /// Check that everything written to an encoder can be decompressed as soon as it is written.
+    fn check_flushes_chunks<D: Write>(
+        encoding: ContentEncoding,
+        mut decoder: D,
+        decompressed: impl Fn(&D) -> &Vec<u8>,
+    ) -> Result<(), Error> {
+        let mut encoder = Encoder::new(encoding)?;
+        let mut expected = Vec::new();
+        for chunk in CHUNKS.iter() {
+            decoder.write_all(&encoder.write(chunk.as_bytes())?)?;
+            decoder.flush()?;
+            expected.extend_from_slice(chunk.as_bytes());
+            assert_eq!(decompressed(&decoder), &expected);
+        }
+        decoder.write_all(&encoder.finish()?)?;
+        decoder.flush()?;
+        assert_eq!(decompressed(&decoder), &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_encoder_flushes_chunks() -> Result<(), Error> {
+        check_flushes_chunks(
+            ContentEncoding::Zstd,
+            zstd::stream::write::Decoder::new(Vec::new())?,
+            |decoder| decoder.get_ref(),
+        )?;
+        check_flushes_chunks(
+            ContentEncoding::Gzip,
+            GzWriteDecoder::new(Vec::new()),
+            |decoder| decoder.get_ref(),
+        )?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compress_body_round_trip() -> Result<(), Error> {
+        for encoding in ContentEncoding::SUPPORTED.iter() {
+            let body = Body::wrap_stream(stream::iter(
+                CHUNKS
+                    .iter()
+                    .map(|chunk| Ok::<_, std::io::Error>(Bytes::from(*chunk))),
+            ));
+            let compression = ResponseCompression::new(*encoding);
+            let compressed = compress_body(body, Encoder::new(*encoding)?, &compression)
+                .map_ok(|chunk| chunk.to_vec())
+                .try_concat()
+                .await?;
+
+            assert_eq!(
+                decompress(*encoding, &compressed)?,
+                CHUNKS.concat().into_bytes()
+            );
+            assert_eq!(
+                compression.uncompressed_bytes(),
+                CHUNKS.concat().len() as u64
+            );
+            assert_eq!(compression.compressed_bytes(), compressed.len() as u64);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_compressed_mime() {
+        assert!(is_compressed_mime(&mime::IMAGE_PNG));
+        assert!(is_compressed_mime(&mime::APPLICATION_OCTET_STREAM));
+        assert!(is_compressed_mime(&"application/zstd".parse().unwrap()));
+        assert!(!is_compressed_mime(&"application/cbor".parse().unwrap()));
+        assert!(!is_compressed_mime(&mime::APPLICATION_JSON));
+    }
+}
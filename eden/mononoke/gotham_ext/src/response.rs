@@ -42,6 +42,14 @@ pub fn build_response<IR: TryIntoResponse>(
 #[derive(StateData)]
 pub struct ResponseContentLength(pub u64);
 
+/// Replace the body of a response with one that notifies `sender` with the number of bytes that
+/// were sent once the whole body has been sent to the client. Middleware that transforms the body
+/// (e.g. compression) should run before this so that the notification reflects what was sent.
+pub fn signal_body(response: &mut Response<Body>, sender: Sender<u64>) {
+    let body = std::mem::replace(response.body_mut(), Body::empty());
+    *response.body_mut() = Body::wrap_stream(SignalStream::new(body, sender));
+}
+
 pub struct EmptyBody;
 
 impl EmptyBody {
@@ -41,7 +41,7 @@ use metaconfig_parser::RepoConfigs;
 
 use crate::lfs_server_context::{LfsServerContext, ServerUris};
 use crate::middleware::{
-    ClientIdentityMiddleware, LoadMiddleware, LogMiddleware, OdsMiddleware,
+    ClientIdentityMiddleware, CompressionMiddleware, LoadMiddleware, LogMiddleware, OdsMiddleware,
     RequestContextMiddleware, ScubaMiddleware, ServerIdentityMiddleware, TimerMiddleware,
     TlsSessionDataMiddleware,
 };
@@ -362,6 +362,7 @@ fn main(fb: FacebookInit) -> Result<(), Error> {
         .add(ScubaMiddleware::new(scuba_logger))
         .add(OdsMiddleware::new())
         .add(TimerMiddleware::new())
+        .add(CompressionMiddleware::new())
         .build(router);
 
     let addr = format!("{}:{}", listen_host, listen_port);
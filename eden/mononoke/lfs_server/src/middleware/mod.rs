@@ -13,8 +13,8 @@ mod scuba;
 mod timer;
 
 pub use gotham_ext::middleware::{
-    ClientIdentity, ClientIdentityMiddleware, Middleware, ServerIdentityMiddleware,
-    TlsSessionDataMiddleware,
+    ClientIdentity, ClientIdentityMiddleware, CompressionMiddleware, Middleware,
+    ServerIdentityMiddleware, TlsSessionDataMiddleware,
 };
 
 pub use self::load::{LoadMiddleware, RequestLoad};
@@ -13,8 +13,8 @@ use futures::channel::oneshot::{self, Receiver, Sender};
 use gotham::state::{request_id, FromState, State};
 use gotham_derive::StateData;
 use gotham_ext::{
-    middleware::{ClientIdentity, Middleware},
-    response::ResponseContentLength,
+    middleware::{ClientIdentity, Middleware, ResponseCompression},
+    response::{signal_body, ResponseContentLength},
 };
 use hyper::{body::Body, Response};
 use scuba::ScubaSampleBuilder;
@@ -207,7 +207,15 @@ impl Middleware for RequestContextMiddleware {
         None
     }
 
-    async fn outbound(&self, state: &mut State, _response: &mut Response<Body>) {
+    async fn outbound(&self, state: &mut State, response: &mut Response<Body>) {
+        // If the response was compressed, wait for the compressed body to be sent, so that post
+        // request callbacks see the bytes that actually went to the client.
+        if ResponseCompression::try_borrow_from(&state).is_some() {
+            if let Some(ctx) = state.try_borrow_mut::<RequestContext>() {
+                signal_body(response, ctx.delay_post_request());
+            }
+        }
+
         let client_address = ClientIdentity::try_borrow_from(&state)
             .map(|client_identity| *client_identity.address())
             .flatten();
@@ -8,7 +8,7 @@
 use gotham::state::{request_id, FromState, State};
 use gotham_derive::StateData;
 use gotham_ext::{
-    middleware::{ClientIdentity, Middleware},
+    middleware::{ClientIdentity, Middleware, ResponseCompression},
     response::ResponseContentLength,
 };
 use hyper::{
@@ -65,6 +65,12 @@ pub enum ScubaKey {
     ResponseBytesSent,
     /// How many bytes were received from the client (should normally equal the content length)
     RequestBytesReceived,
+    /// The Content-Encoding the response was compressed with, if any.
+    ResponseContentEncoding,
+    /// How many bytes of the response went into the compressor.
+    ResponseUncompressedBytes,
+    /// How many bytes of the response came out of the compressor.
+    ResponseCompressedBytes,
     /// The order in which the response to a batch request was produced.
     BatchOrder,
     /// The number of objects in a batch request
@@ -106,6 +112,9 @@ impl AsRef<str> for ScubaKey {
             ClientHostname => "client_hostname",
             ResponseBytesSent => "response_bytes_sent",
             RequestBytesReceived => "request_bytes_received",
+            ResponseContentEncoding => "response_content_encoding",
+            ResponseUncompressedBytes => "response_uncompressed_bytes",
+            ResponseCompressedBytes => "response_compressed_bytes",
             BatchOrder => "batch_order",
             BatchObjectCount => "batch_object_count",
             BatchInternalMissingBlobs => "batch_internal_missing_blobs",
@@ -228,6 +237,14 @@ fn log_stats(
 
     scuba.add(ScubaKey::RequestId, request_id(&state));
 
+    let compression = ResponseCompression::try_borrow_from(&state).cloned();
+    if let Some(ref compression) = compression {
+        scuba.add(
+            ScubaKey::ResponseContentEncoding,
+            compression.encoding().as_str(),
+        );
+    }
+
     let ctx = state.try_borrow_mut::<RequestContext>()?;
 
     if let Some(repository) = &ctx.repository {
@@ -261,6 +278,17 @@ fn log_stats(
                 scuba.add(ScubaKey::ResponseBytesSent, bytes_sent);
             }
 
+            if let Some(compression) = compression {
+                scuba.add(
+                    ScubaKey::ResponseUncompressedBytes,
+                    compression.uncompressed_bytes(),
+                );
+                scuba.add(
+                    ScubaKey::ResponseCompressedBytes,
+                    compression.compressed_bytes(),
+                );
+            }
+
             perf_counters.insert_perf_counters(&mut scuba);
 
             scuba.log();
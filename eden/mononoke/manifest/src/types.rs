@@ -13,8 +13,11 @@ use futures_ext::FutureExt as _;
 use futures_old::Future as _;
 use mononoke_types::{
     fsnode::{Fsnode, FsnodeEntry},
+    skeleton_manifest::{SkeletonManifest, SkeletonManifestEntry},
+    trigram_index::{TrigramIndex, TrigramIndexEntry, TrigramIndexFile},
     unode::{ManifestUnode, UnodeEntry},
     ContentId, FileType, FileUnodeId, FsnodeId, MPath, MPathElement, ManifestUnodeId,
+    SkeletonManifestId, TrigramIndexId,
 };
 use serde_derive::{Deserialize, Serialize};
 use std::{
@@ -82,6 +85,62 @@ fn convert_fsnode(fsnode_entry: &FsnodeEntry) -> Entry<FsnodeId, (ContentId, Fil
     }
 }
 
+impl Manifest for SkeletonManifest {
+    type TreeId = SkeletonManifestId;
+    type LeafId = ();
+
+    fn lookup(&self, name: &MPathElement) -> Option<Entry<Self::TreeId, Self::LeafId>> {
+        self.lookup(name).map(convert_skeleton_manifest)
+    }
+
+    fn list(&self) -> Box<dyn Iterator<Item = (MPathElement, Entry<Self::TreeId, Self::LeafId>)>> {
+        let v: Vec<_> = self
+            .list()
+            .map(|(basename, entry)| (basename.clone(), convert_skeleton_manifest(entry)))
+            .collect();
+        Box::new(v.into_iter())
+    }
+}
+
+fn convert_skeleton_manifest(
+    skeleton_entry: &SkeletonManifestEntry,
+) -> Entry<SkeletonManifestId, ()> {
+    match skeleton_entry {
+        SkeletonManifestEntry::File => Entry::Leaf(()),
+        SkeletonManifestEntry::Directory(skeleton_directory) => {
+            Entry::Tree(skeleton_directory.id().clone())
+        }
+    }
+}
+
+impl Manifest for TrigramIndex {
+    type TreeId = TrigramIndexId;
+    type LeafId = TrigramIndexFile;
+
+    fn lookup(&self, name: &MPathElement) -> Option<Entry<Self::TreeId, Self::LeafId>> {
+        self.lookup(name).map(convert_trigram_index)
+    }
+
+    fn list(&self) -> Box<dyn Iterator<Item = (MPathElement, Entry<Self::TreeId, Self::LeafId>)>> {
+        let v: Vec<_> = self
+            .list()
+            .map(|(basename, entry)| (basename.clone(), convert_trigram_index(entry)))
+            .collect();
+        Box::new(v.into_iter())
+    }
+}
+
+fn convert_trigram_index(
+    trigram_entry: &TrigramIndexEntry,
+) -> Entry<TrigramIndexId, TrigramIndexFile> {
+    match trigram_entry {
+        TrigramIndexEntry::File(trigram_file) => Entry::Leaf(trigram_file.clone()),
+        TrigramIndexEntry::Directory(trigram_directory) => {
+            Entry::Tree(trigram_directory.id().clone())
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum Entry<T, L> {
     Tree(T),
@@ -8,14 +8,26 @@
 use anyhow::{format_err, Error};
 use blobrepo::{save_bonsai_changesets, BlobRepo};
 use blobrepo_hg::BlobRepoHg;
+use blobstore::Loadable;
 use bookmarks::{BookmarkName, BookmarkUpdateReason};
 use context::CoreContext;
-use futures::compat::Future01CompatExt;
+use futures::{
+    compat::{Future01CompatExt, Stream01CompatExt},
+    stream, StreamExt, TryStreamExt,
+};
+use itertools::Itertools;
+use manifest::ManifestOps;
 use slog::info;
 
-use mercurial_types::{HgChangesetId, MPath};
-use mononoke_types::{BonsaiChangeset, BonsaiChangesetMut, ChangesetId, DateTime, FileChange};
+use mercurial_types::{HgChangesetId, HgFileNodeId, MPath};
+use mononoke_types::{
+    BonsaiChangeset, BonsaiChangesetMut, ChangesetId, ContentId, DateTime, FileChange, FileType,
+};
 use std::collections::BTreeMap;
+use std::num::NonZeroU64;
+
+/// The number of filenodes loaded concurrently by `get_working_copy_contents`.
+const FILENODE_LOAD_CONCURRENCY: usize = 100;
 
 #[derive(Clone)]
 pub struct ChangesetArgs {
@@ -33,6 +45,19 @@ pub async fn create_and_save_changeset(
     file_changes: BTreeMap<MPath, Option<FileChange>>,
     changeset_args: ChangesetArgs,
 ) -> Result<HgChangesetId, Error> {
+    let bcs_id =
+        create_and_save_bonsai_changeset(ctx, repo, parents, file_changes, changeset_args).await?;
+    generate_hg_changeset(ctx, repo, bcs_id).await
+}
+
+/// Like `create_and_save_changeset`, but doesn't generate the Mercurial changeset.
+pub async fn create_and_save_bonsai_changeset(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    parents: Vec<ChangesetId>,
+    file_changes: BTreeMap<MPath, Option<FileChange>>,
+    changeset_args: ChangesetArgs,
+) -> Result<ChangesetId, Error> {
     let ChangesetArgs {
         author,
         message,
@@ -46,7 +71,83 @@ pub async fn create_and_save_changeset(
     if let Some(bookmark) = maybe_bookmark {
         create_bookmark(ctx, repo, bookmark, bcs_id).await?;
     }
-    generate_hg_changeset(ctx, repo, bcs_id).await
+    Ok(bcs_id)
+}
+
+/// The files in the working copy of a commit, with their types and filenodes.
+pub async fn get_working_copy(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    bcs_id: ChangesetId,
+) -> Result<BTreeMap<MPath, (FileType, HgFileNodeId)>, Error> {
+    let hg_cs_id = repo
+        .get_hg_from_bonsai_changeset(ctx.clone(), bcs_id)
+        .compat()
+        .await?;
+    let hg_cs = hg_cs_id.load(ctx.clone(), repo.blobstore()).await?;
+
+    hg_cs
+        .manifestid()
+        .list_leaf_entries(ctx.clone(), repo.get_blobstore())
+        .compat()
+        .try_collect()
+        .await
+}
+
+/// The files in the working copy of a commit, with their types and content ids.
+/// Unlike filenodes, these are the same for identical files with different histories.
+pub async fn get_working_copy_contents(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    bcs_id: ChangesetId,
+) -> Result<BTreeMap<MPath, (FileType, ContentId)>, Error> {
+    let working_copy = get_working_copy(ctx, repo, bcs_id).await?;
+    stream::iter(working_copy)
+        .map(|(path, (file_type, filenode_id))| async move {
+            let envelope = filenode_id.load(ctx.clone(), repo.blobstore()).await?;
+            Ok::<_, Error>((path, (file_type, envelope.content_id())))
+        })
+        .buffered(FILENODE_LOAD_CONCURRENCY)
+        .try_collect()
+        .await
+}
+
+/// Create a stack of commits on top of `parent_bcs_id` that delete `paths`, with at most
+/// `max_num_of_deletes_in_commit` files deleted in each commit.
+pub(crate) async fn create_delete_stack(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    mut parent_bcs_id: ChangesetId,
+    paths: Vec<MPath>,
+    max_num_of_deletes_in_commit: NonZeroU64,
+    resulting_changeset_args: impl Fn(usize) -> ChangesetArgs,
+) -> Result<Vec<ChangesetId>, Error> {
+    let chunks: Vec<Vec<MPath>> = paths
+        .into_iter()
+        .chunks(max_num_of_deletes_in_commit.get() as usize)
+        .into_iter()
+        .map(|chunk| chunk.collect())
+        .collect();
+
+    let mut res = vec![];
+    for (idx, chunk) in chunks.into_iter().enumerate() {
+        let file_changes = chunk.into_iter().map(|path| (path, None)).collect();
+        parent_bcs_id = create_and_save_bonsai_changeset(
+            ctx,
+            repo,
+            vec![parent_bcs_id],
+            file_changes,
+            resulting_changeset_args(idx),
+        )
+        .await?;
+        info!(
+            ctx.logger(),
+            "Created delete commit #{}: {}", idx, parent_bcs_id
+        );
+        res.push(parent_bcs_id);
+    }
+
+    Ok(res)
 }
 
 async fn save_and_maybe_mark_public(
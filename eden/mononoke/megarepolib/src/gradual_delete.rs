@@ -0,0 +1,124 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{anyhow, Error};
+use blobrepo::BlobRepo;
+use context::CoreContext;
+use mercurial_types::MPath;
+use mononoke_types::ChangesetId;
+use slog::info;
+use std::num::NonZeroU64;
+
+use crate::common::{create_delete_stack, get_working_copy, ChangesetArgs};
+
+/// Create a stack of commits on top of `parent_bcs_id` that gradually delete
+/// `paths`, with at most `max_num_of_deletes_in_commit` files deleted in each
+/// commit. Each path may be a file or a directory, in which case all the files
+/// under it are deleted. It is an error for a path to match no files.
+///
+/// Deleting a large directory in small steps keeps each commit (and the
+/// working copy update it causes) small.
+pub async fn create_gradual_delete<'a>(
+    ctx: &'a CoreContext,
+    repo: &'a BlobRepo,
+    parent_bcs_id: ChangesetId,
+    paths: Vec<MPath>,
+    max_num_of_deletes_in_commit: NonZeroU64,
+    resulting_changeset_args: impl Fn(usize) -> ChangesetArgs,
+) -> Result<Vec<ChangesetId>, Error> {
+    let working_copy = get_working_copy(ctx, repo, parent_bcs_id).await?;
+
+    for path in &paths {
+        if !working_copy.keys().any(|file| path.is_prefix_of(file)) {
+            return Err(anyhow!("{} does not exist in {}", path, parent_bcs_id));
+        }
+    }
+
+    let to_delete: Vec<_> = working_copy
+        .into_iter()
+        .map(|(file, _)| file)
+        .filter(|file| paths.iter().any(|path| path.is_prefix_of(file)))
+        .collect();
+    info!(
+        ctx.logger(),
+        "Deleting {} files of {} in gradual delete commits",
+        to_delete.len(),
+        parent_bcs_id
+    );
+
+    create_delete_stack(
+        ctx,
+        repo,
+        parent_bcs_id,
+        to_delete,
+        max_num_of_deletes_in_commit,
+        resulting_changeset_args,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fbinit::FacebookInit;
+    use mononoke_types::DateTime;
+    use tests_utils::CreateCommitContext;
+
+    #[fbinit::compat_test]
+    async fn test_gradual_delete(fb: FacebookInit) -> Result<(), Error> {
+        let repo = blobrepo_factory::new_memblob_empty(None)?;
+        let ctx = CoreContext::test_mock(fb);
+
+        let root = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file("dir/a", "a")
+            .add_file("dir/b", "b")
+            .add_file("dir/sub/c", "c")
+            .add_file("dir1/d", "d")
+            .add_file("e", "e")
+            .add_file("f", "f")
+            .commit()
+            .await?;
+        let create_cs_args = |num| ChangesetArgs {
+            author: "user".to_string(),
+            message: format!("gradual delete: {}", num),
+            datetime: DateTime::from_rfc3339("1985-04-12T23:20:50.52Z").unwrap(),
+            bookmark: None,
+            mark_public: false,
+        };
+
+        let stack = create_gradual_delete(
+            &ctx,
+            &repo,
+            root,
+            vec![MPath::new("dir")?, MPath::new("e")?],
+            NonZeroU64::new(3).unwrap(),
+            create_cs_args,
+        )
+        .await?;
+        assert_eq!(stack.len(), 2);
+
+        let remaining: Vec<_> = get_working_copy(&ctx, &repo, *stack.last().unwrap())
+            .await?
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(remaining, vec![MPath::new("dir1/d")?, MPath::new("f")?]);
+
+        let missing = create_gradual_delete(
+            &ctx,
+            &repo,
+            root,
+            vec![MPath::new("missing")?],
+            NonZeroU64::new(3).unwrap(),
+            create_cs_args,
+        )
+        .await;
+        assert!(missing.is_err());
+
+        Ok(())
+    }
+}
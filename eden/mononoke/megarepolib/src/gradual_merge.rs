@@ -0,0 +1,382 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{anyhow, Error};
+use blobrepo::BlobRepo;
+use blobstore::Loadable;
+use bookmarks::BookmarkName;
+use context::CoreContext;
+use futures::{compat::Future01CompatExt, future::try_join_all};
+use mercurial_types::MPath;
+use metaconfig_types::PushrebaseFlags;
+use mononoke_types::{ChangesetId, ContentId, FileType};
+use pushrebase::{do_pushrebase_bonsai, OntoBookmarkParams, PushrebaseHook};
+use slog::info;
+use std::collections::{btree_map::Entry, BTreeMap, HashSet};
+use std::iter;
+
+use crate::common::{create_and_save_bonsai_changeset, get_working_copy_contents, ChangesetArgs};
+
+/// The union of the working copies of `commits`. Fails if the commits can't be
+/// merged without conflicts, i.e. if a path is in more than one of them with
+/// different contents or types, or if a path is a file in one and a directory
+/// in another. Files are compared by content, so identical files imported
+/// with different histories don't conflict.
+async fn get_working_copy_union(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    commits: &[ChangesetId],
+) -> Result<BTreeMap<MPath, (FileType, ContentId)>, Error> {
+    let mut union = BTreeMap::new();
+    for bcs_id in commits {
+        for (path, entry) in get_working_copy_contents(ctx, repo, *bcs_id).await? {
+            match union.entry(path) {
+                Entry::Occupied(existing) => {
+                    if existing.get() != &entry {
+                        return Err(anyhow!(
+                            "{} differs between {} and another merged commit",
+                            existing.key(),
+                            bcs_id
+                        ));
+                    }
+                }
+                Entry::Vacant(vacant) => {
+                    vacant.insert(entry);
+                }
+            }
+        }
+    }
+
+    // Paths are sorted, so a file that is also a directory is immediately
+    // followed by a path under it.
+    let mut paths = union.keys().peekable();
+    while let Some(path) = paths.next() {
+        if let Some(next) = paths.peek() {
+            if path.is_prefix_of(*next) {
+                return Err(anyhow!(
+                    "{} is a file in one merged commit and a directory in another",
+                    path
+                ));
+            }
+        }
+    }
+
+    Ok(union)
+}
+
+/// Create a merge commit of `parents`, which must not conflict. The merge
+/// commit doesn't change any files, so its working copy is the union of the
+/// working copies of its parents.
+pub async fn create_merge_commit<'a>(
+    ctx: &'a CoreContext,
+    repo: &'a BlobRepo,
+    parents: Vec<ChangesetId>,
+    resulting_changeset_args: ChangesetArgs,
+) -> Result<ChangesetId, Error> {
+    if parents.len() < 2 {
+        return Err(anyhow!("a merge commit needs at least two parents"));
+    }
+    get_working_copy_union(ctx, repo, &parents).await?;
+
+    let merge_bcs_id = create_and_save_bonsai_changeset(
+        ctx,
+        repo,
+        parents,
+        BTreeMap::new(),
+        resulting_changeset_args,
+    )
+    .await?;
+    info!(ctx.logger(), "Created merge commit {}", merge_bcs_id);
+    Ok(merge_bcs_id)
+}
+
+/// Check that the working copy of `merge_bcs_id` is exactly the union of the
+/// working copies of `sides`.
+pub async fn validate_merge<'a>(
+    ctx: &'a CoreContext,
+    repo: &'a BlobRepo,
+    merge_bcs_id: ChangesetId,
+    sides: &[ChangesetId],
+) -> Result<(), Error> {
+    let expected = get_working_copy_union(ctx, repo, sides).await?;
+    let actual = get_working_copy_contents(ctx, repo, merge_bcs_id).await?;
+
+    for (path, entry) in &expected {
+        match actual.get(path) {
+            Some(actual_entry) if actual_entry == entry => {}
+            Some(_) => {
+                return Err(anyhow!(
+                    "{} in merge {} differs from the merged commits",
+                    path,
+                    merge_bcs_id
+                ));
+            }
+            None => {
+                return Err(anyhow!("{} is missing from merge {}", path, merge_bcs_id));
+            }
+        }
+    }
+    if let Some(path) = actual.keys().find(|path| !expected.contains_key(*path)) {
+        return Err(anyhow!(
+            "{} in merge {} is not in any merged commit",
+            path,
+            merge_bcs_id
+        ));
+    }
+
+    info!(
+        ctx.logger(),
+        "Merge {} is the union of {:?} ({} files)",
+        merge_bcs_id,
+        sides,
+        actual.len()
+    );
+    Ok(())
+}
+
+/// Pushrebase a stack of commits onto `bookmark`, returning the new head of
+/// the bookmark.
+pub async fn pushrebase_commits<'a>(
+    ctx: &'a CoreContext,
+    repo: &'a BlobRepo,
+    bcs_ids: &'a [ChangesetId],
+    bookmark: &'a BookmarkName,
+    flags: &'a PushrebaseFlags,
+    pushrebase_hooks: &'a [Box<dyn PushrebaseHook>],
+) -> Result<ChangesetId, Error> {
+    let pushed: HashSet<_> = try_join_all(
+        bcs_ids
+            .iter()
+            .map(|bcs_id| bcs_id.load(ctx.clone(), repo.blobstore())),
+    )
+    .await?
+    .into_iter()
+    .collect();
+
+    let result = do_pushrebase_bonsai(
+        ctx,
+        repo,
+        flags,
+        &OntoBookmarkParams::new(bookmark.clone()),
+        &pushed,
+        &None,
+        pushrebase_hooks,
+    )
+    .await?;
+
+    info!(
+        ctx.logger(),
+        "Pushrebased {} commits onto {}: {}",
+        bcs_ids.len(),
+        bookmark,
+        result.head
+    );
+    Ok(result.head)
+}
+
+/// Gradually merge `pre_deletion_commit` into `bookmark`, using the stack of
+/// pre-merge delete commits created on top of it by `create_pre_merge_delete`.
+///
+/// The pre-merge delete commits are merged into the bookmark in reverse order,
+/// followed by `pre_deletion_commit` itself, so each merge introduces one
+/// chunk of files. Each merge commit is pushrebased onto the bookmark. Once
+/// done, the working copy of the last merge is validated to be the union of
+/// the bookmark and `pre_deletion_commit`. Returns the last merge commit.
+pub async fn gradual_merge<'a>(
+    ctx: &'a CoreContext,
+    repo: &'a BlobRepo,
+    pre_deletion_commit: ChangesetId,
+    pre_merge_delete_commits: &'a [ChangesetId],
+    bookmark: &'a BookmarkName,
+    flags: &'a PushrebaseFlags,
+    pushrebase_hooks: &'a [Box<dyn PushrebaseHook>],
+    resulting_changeset_args: impl Fn(usize) -> ChangesetArgs,
+) -> Result<ChangesetId, Error> {
+    let to_merge = pre_merge_delete_commits
+        .iter()
+        .rev()
+        .chain(iter::once(&pre_deletion_commit));
+
+    let mut last_merge = None;
+    for (idx, bcs_id) in to_merge.enumerate() {
+        let onto = repo
+            .get_bonsai_bookmark(ctx.clone(), bookmark)
+            .compat()
+            .await?
+            .ok_or_else(|| anyhow!("bookmark {} does not exist", bookmark))?;
+
+        let merge_bcs_id = create_merge_commit(
+            ctx,
+            repo,
+            vec![onto, *bcs_id],
+            resulting_changeset_args(idx),
+        )
+        .await?;
+        let rebased = pushrebase_commits(
+            ctx,
+            repo,
+            &[merge_bcs_id],
+            bookmark,
+            flags,
+            pushrebase_hooks,
+        )
+        .await?;
+        last_merge = Some(rebased);
+    }
+
+    let last_merge = last_merge.ok_or_else(|| anyhow!("no merge commits were created"))?;
+    let merged_into = last_merge
+        .load(ctx.clone(), repo.blobstore())
+        .await?
+        .parents()
+        .next()
+        .ok_or_else(|| anyhow!("merge commit {} has no parents", last_merge))?;
+    validate_merge(ctx, repo, last_merge, &[merged_into, pre_deletion_commit]).await?;
+
+    Ok(last_merge)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::get_working_copy;
+    use crate::pre_merge_delete::create_pre_merge_delete;
+    use fbinit::FacebookInit;
+    use fixtures::linear;
+    use mononoke_types::DateTime;
+    use std::num::NonZeroU64;
+    use tests_utils::{resolve_cs_id, CreateCommitContext};
+
+    fn create_cs_args(num: usize) -> ChangesetArgs {
+        ChangesetArgs {
+            author: "user".to_string(),
+            message: format!("merge step: {}", num),
+            datetime: DateTime::from_rfc3339("1985-04-12T23:20:50.52Z").unwrap(),
+            bookmark: None,
+            mark_public: false,
+        }
+    }
+
+    #[fbinit::compat_test]
+    async fn test_merge_commit(fb: FacebookInit) -> Result<(), Error> {
+        let repo = linear::getrepo(fb).await;
+        let ctx = CoreContext::test_mock(fb);
+
+        let master = resolve_cs_id(&ctx, &repo, "master").await?;
+        let small = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file("small/a", "a")
+            .commit()
+            .await?;
+
+        let merge =
+            create_merge_commit(&ctx, &repo, vec![master, small], create_cs_args(0)).await?;
+        validate_merge(&ctx, &repo, merge, &[master, small]).await?;
+        assert!(validate_merge(&ctx, &repo, merge, &[master]).await.is_err());
+
+        let conflicting = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file("1", "conflicting content")
+            .commit()
+            .await?;
+        let res = create_merge_commit(&ctx, &repo, vec![master, conflicting], create_cs_args(0));
+        assert!(res.await.is_err());
+
+        let file_and_dir = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file("1/a", "a")
+            .commit()
+            .await?;
+        let res = create_merge_commit(&ctx, &repo, vec![master, file_and_dir], create_cs_args(0));
+        assert!(res.await.is_err());
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_merge_identical_content(fb: FacebookInit) -> Result<(), Error> {
+        let repo = linear::getrepo(fb).await;
+        let ctx = CoreContext::test_mock(fb);
+
+        // The same file imported from two histories has different filenodes,
+        // but the histories can still be merged.
+        let first_root = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file("shared", "old")
+            .commit()
+            .await?;
+        let first = CreateCommitContext::new(&ctx, &repo, vec![first_root])
+            .add_file("shared", "same")
+            .add_file("first", "first")
+            .commit()
+            .await?;
+        let second = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file("shared", "same")
+            .add_file("second", "second")
+            .commit()
+            .await?;
+        let first_filenode = get_working_copy(&ctx, &repo, first).await?[&MPath::new("shared")?];
+        let second_filenode = get_working_copy(&ctx, &repo, second).await?[&MPath::new("shared")?];
+        assert_ne!(first_filenode, second_filenode);
+
+        let merge =
+            create_merge_commit(&ctx, &repo, vec![first, second], create_cs_args(0)).await?;
+        validate_merge(&ctx, &repo, merge, &[first, second]).await?;
+        assert_eq!(
+            get_working_copy_contents(&ctx, &repo, merge).await?.len(),
+            3
+        );
+
+        let executable = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file_with_type("shared", "same", FileType::Executable)
+            .commit()
+            .await?;
+        let res = create_merge_commit(&ctx, &repo, vec![first, executable], create_cs_args(0));
+        assert!(res.await.is_err());
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_gradual_merge(fb: FacebookInit) -> Result<(), Error> {
+        let repo = linear::getrepo(fb).await;
+        let ctx = CoreContext::test_mock(fb);
+
+        let master_before = resolve_cs_id(&ctx, &repo, "master").await?;
+        let small = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file("small/a", "a")
+            .add_file("small/b", "b")
+            .add_file("small/c", "c")
+            .commit()
+            .await?;
+        let pre_merge_deletes = create_pre_merge_delete(
+            &ctx,
+            &repo,
+            small,
+            NonZeroU64::new(2).unwrap(),
+            create_cs_args,
+        )
+        .await?;
+        assert_eq!(pre_merge_deletes.len(), 2);
+
+        let bookmark = BookmarkName::new("master")?;
+        let last_merge = gradual_merge(
+            &ctx,
+            &repo,
+            small,
+            &pre_merge_deletes,
+            &bookmark,
+            &PushrebaseFlags::default(),
+            &[],
+            create_cs_args,
+        )
+        .await?;
+
+        assert_eq!(resolve_cs_id(&ctx, &repo, "master").await?, last_merge);
+        let working_copy = get_working_copy(&ctx, &repo, last_merge).await?;
+        assert_eq!(working_copy.len(), 14);
+        validate_merge(&ctx, &repo, last_merge, &[master_before, small]).await?;
+
+        Ok(())
+    }
+}
@@ -27,8 +27,17 @@ use std::collections::BTreeMap;
 use std::num::NonZeroU64;
 
 pub mod common;
+pub mod gradual_delete;
+pub mod gradual_merge;
+pub mod pre_merge_delete;
 use crate::common::{create_and_save_changeset, ChangesetArgs};
 
+pub use crate::gradual_delete::create_gradual_delete;
+pub use crate::gradual_merge::{
+    create_merge_commit, gradual_merge, pushrebase_commits, validate_merge,
+};
+pub use crate::pre_merge_delete::create_pre_merge_delete;
+
 const BUFFER_SIZE: usize = 100;
 const REPORTING_INTERVAL_FILES: usize = 10000;
 
@@ -0,0 +1,95 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use blobrepo::BlobRepo;
+use context::CoreContext;
+use mononoke_types::ChangesetId;
+use slog::info;
+use std::num::NonZeroU64;
+
+use crate::common::{create_delete_stack, get_working_copy, ChangesetArgs};
+
+/// Create a stack of "pre-merge delete" commits on top of `parent_bcs_id`,
+/// which delete all of its files with at most `max_num_of_deletes_in_commit`
+/// files deleted in each commit. The last commit of the stack has an empty
+/// working copy.
+///
+/// Merging the commits of this stack into another repo in reverse order
+/// (see `gradual_merge`) introduces the files of `parent_bcs_id` a chunk at a
+/// time, which keeps each merge small.
+pub async fn create_pre_merge_delete<'a>(
+    ctx: &'a CoreContext,
+    repo: &'a BlobRepo,
+    parent_bcs_id: ChangesetId,
+    max_num_of_deletes_in_commit: NonZeroU64,
+    resulting_changeset_args: impl Fn(usize) -> ChangesetArgs,
+) -> Result<Vec<ChangesetId>, Error> {
+    let paths: Vec<_> = get_working_copy(ctx, repo, parent_bcs_id)
+        .await?
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect();
+    info!(
+        ctx.logger(),
+        "Deleting {} files of {} in pre-merge delete commits",
+        paths.len(),
+        parent_bcs_id
+    );
+
+    create_delete_stack(
+        ctx,
+        repo,
+        parent_bcs_id,
+        paths,
+        max_num_of_deletes_in_commit,
+        resulting_changeset_args,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fbinit::FacebookInit;
+    use fixtures::linear;
+    use mononoke_types::DateTime;
+    use tests_utils::resolve_cs_id;
+
+    #[fbinit::compat_test]
+    async fn test_pre_merge_delete(fb: FacebookInit) -> Result<(), Error> {
+        let repo = linear::getrepo(fb).await;
+        let ctx = CoreContext::test_mock(fb);
+
+        let master = resolve_cs_id(&ctx, &repo, "master").await?;
+        let create_cs_args = |num| ChangesetArgs {
+            author: "user".to_string(),
+            message: format!("pre-merge delete: {}", num),
+            datetime: DateTime::from_rfc3339("1985-04-12T23:20:50.52Z").unwrap(),
+            bookmark: None,
+            mark_public: false,
+        };
+
+        let stack = create_pre_merge_delete(
+            &ctx,
+            &repo,
+            master,
+            NonZeroU64::new(4).unwrap(),
+            create_cs_args,
+        )
+        .await?;
+
+        // 11 files, deleted 4 at a time
+        assert_eq!(stack.len(), 3);
+        let remaining = get_working_copy(&ctx, &repo, stack[0]).await?;
+        assert_eq!(remaining.len(), 7);
+        let remaining = get_working_copy(&ctx, &repo, stack[2]).await?;
+        assert!(remaining.is_empty());
+
+        Ok(())
+    }
+}
@@ -16,6 +16,9 @@ pub enum Section {
     Changeset,
     Manifest,
     Treemanifest,
+    /// The revisions of one directory manifest, sent within the tree manifest
+    /// section of a version 3 changegroup.
+    Tree(MPath),
     Filelog(MPath),
 }
 
@@ -173,6 +173,82 @@ where
     Ok(builder)
 }
 
+/// Build a version 3 changegroup that carries the root and directory
+/// manifests and the filelogs of every changeset, so that a client without
+/// access to the server's tree store (e.g. stock Mercurial) can apply it.
+/// Directory manifests are grouped by path; each group and the filelogs must
+/// list their revisions parents first.
+pub fn full_changegroup_part<CS, MS, TS, FS>(
+    changelogentries: CS,
+    manifestentries: MS,
+    treeentries: TS,
+    filenodeentries: FS,
+) -> Result<PartEncodeBuilder>
+where
+    CS: Stream<Item = (HgNodeHash, HgBlobNode), Error = Error> + Send + 'static,
+    MS: Stream<Item = TreepackPartInput, Error = Error> + Send + 'static,
+    TS: Stream<Item = (MPath, Vec<TreepackPartInput>), Error = Error> + Send + 'static,
+    FS: Stream<Item = (MPath, Vec<FilenodeEntry>), Error = Error> + Send + 'static,
+{
+    let mut builder = PartEncodeBuilder::mandatory(PartHeaderType::Changegroup)?;
+    let version = CgVersion::Cg3Version;
+    builder.add_mparam(
+        "version",
+        BytesNew::copy_from_slice(version.to_str().as_bytes()),
+    )?;
+    builder.add_mparam("treemanifest", "True")?;
+
+    let manifestentries =
+        manifestentries.map(|input| Part::CgChunk(Section::Manifest, convert_tree_entry(input)));
+    let treeentries = treeentries
+        .map(|(path, entries)| {
+            let mut items: Vec<_> = entries
+                .into_iter()
+                .map(|input| Part::CgChunk(Section::Tree(path.clone()), convert_tree_entry(input)))
+                .collect();
+            items.push(Part::SectionEnd(Section::Tree(path)));
+            iter_ok(items)
+        })
+        .flatten();
+    // Filelogs in a cg3 always carry flags, even if no file is stored as LFS.
+    let filenodeentries = filenodeentries.map(|(path, entries)| {
+        let entries = entries
+            .into_iter()
+            .map(|(node, hg_cs_id, blobnode, flags)| {
+                let flags = flags.or(Some(RevFlags::REVIDX_DEFAULT_FLAGS));
+                (node, hg_cs_id, blobnode, flags)
+            })
+            .collect();
+        (path, entries)
+    });
+
+    let changegroup = convert_changeset_stream(changelogentries, version)
+        .chain(once(Ok(Part::SectionEnd(Section::Changeset))))
+        .chain(manifestentries)
+        .chain(once(Ok(Part::SectionEnd(Section::Manifest))))
+        .chain(treeentries)
+        .chain(once(Ok(Part::SectionEnd(Section::Treemanifest))))
+        .chain(convert_file_stream(filenodeentries, version))
+        .chain(once(Ok(Part::End)));
+
+    let cgdata = CgPacker::new(changegroup);
+    builder.set_data_generated(cgdata);
+
+    Ok(builder)
+}
+
+fn convert_tree_entry(input: TreepackPartInput) -> CgDeltaChunk {
+    CgDeltaChunk {
+        node: input.node,
+        p1: input.p1.unwrap_or(NULL_HASH),
+        p2: input.p2.unwrap_or(NULL_HASH),
+        base: NULL_HASH,
+        linknode: input.linknode,
+        delta: Delta::new_fulltext(input.content.to_vec()),
+        flags: Some(RevFlags::REVIDX_DEFAULT_FLAGS),
+    }
+}
+
 fn convert_changeset_stream<S>(
     changelogentries: S,
     version: CgVersion,
@@ -51,6 +51,19 @@ pub trait HgMutationStore: Send + Sync {
         ctx: &CoreContext,
         changeset_ids: HashSet<HgChangesetId>,
     ) -> Result<Vec<HgMutationEntry>>;
+
+    /// Get all successor information for the given changeset ids.
+    ///
+    /// Returns all entries that describe how the commits were later mutated,
+    /// including the entries for successors of their successors.  Following
+    /// these entries from a commit leads to the commits that replaced it.
+    /// Very long histories are truncated, so the latest successors of such
+    /// commits may be missing.
+    async fn all_successors(
+        &self,
+        ctx: &CoreContext,
+        changeset_ids: HashSet<HgChangesetId>,
+    ) -> Result<Vec<HgMutationEntry>>;
 }
 
 #[async_trait]
@@ -71,4 +84,12 @@ impl HgMutationStore for Arc<dyn HgMutationStore> {
     ) -> Result<Vec<HgMutationEntry>> {
         (**self).all_predecessors(ctx, changeset_ids).await
     }
+
+    async fn all_successors(
+        &self,
+        ctx: &CoreContext,
+        changeset_ids: HashSet<HgChangesetId>,
+    ) -> Result<Vec<HgMutationEntry>> {
+        (**self).all_successors(ctx, changeset_ids).await
+    }
 }
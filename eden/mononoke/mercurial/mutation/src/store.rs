@@ -14,7 +14,7 @@ use futures::compat::Future01CompatExt;
 use futures::future;
 use mercurial_types::HgChangesetId;
 use mononoke_types::{DateTime, RepositoryId};
-use slog::debug;
+use slog::{debug, warn};
 use smallvec::SmallVec;
 use sql::{queries, Connection};
 use sql_ext::SqlConnections;
@@ -22,6 +22,13 @@ use sql_ext::SqlConnections;
 use crate::entry::{HgMutationEntry, HgMutationEntrySet, HgMutationEntrySetAdded};
 use crate::HgMutationStore;
 
+/// The maximum number of rounds of successors that are followed when
+/// fetching successors.
+const MAX_SUCCESSOR_DEPTH: usize = 100;
+
+/// The maximum number of successor entries that are fetched at once.
+const MAX_SUCCESSOR_ENTRIES: usize = 10_000;
+
 pub struct SqlHgMutationStore {
     repo_id: RepositoryId,
     connections: SqlConnections,
@@ -396,6 +403,123 @@ impl SqlHgMutationStore {
         }
         Ok(())
     }
+
+    /// Find the successors of the given changesets.
+    ///
+    /// The replica is queried first.  It is only known to be up-to-date for
+    /// the predecessors of a changeset, so the changesets that it has no
+    /// successors for are checked on the master, in case they have been
+    /// mutated recently.  Returns the successors found on the replica and on
+    /// the master separately, so their entries can be fetched from the same
+    /// place.
+    async fn fetch_successor_ids(
+        &self,
+        changesets: &[HgChangesetId],
+    ) -> Result<(HashSet<HgChangesetId>, HashSet<HgChangesetId>)> {
+        let replica_rows = SelectSuccessorsByPredecessor::query(
+            &self.connections.read_connection,
+            &self.repo_id,
+            changesets,
+        )
+        .compat()
+        .await?;
+        let found: HashSet<_> = replica_rows
+            .iter()
+            .map(|(predecessor, _)| *predecessor)
+            .collect();
+        let missing: Vec<_> = changesets
+            .iter()
+            .filter(|changeset| !found.contains(changeset))
+            .cloned()
+            .collect();
+        let master_rows = if missing.is_empty() {
+            Vec::new()
+        } else {
+            SelectSuccessorsByPredecessor::query(
+                &self.connections.read_master_connection,
+                &self.repo_id,
+                missing.as_slice(),
+            )
+            .compat()
+            .await?
+        };
+        Ok((
+            replica_rows
+                .into_iter()
+                .map(|(_, successor)| successor)
+                .collect(),
+            master_rows
+                .into_iter()
+                .map(|(_, successor)| successor)
+                .collect(),
+        ))
+    }
+
+    /// Fetch all entries where the given changesets, or any of their
+    /// successors, are a predecessor and add them to the entry set.
+    ///
+    /// Split successors are followed too, as each of them replaces part of
+    /// the original changeset.  At most `MAX_SUCCESSOR_DEPTH` rounds of
+    /// successors and `MAX_SUCCESSOR_ENTRIES` entries are fetched; if the
+    /// history is larger than that, the entries fetched so far are kept.
+    async fn fetch_all_successors(
+        &self,
+        ctx: &CoreContext,
+        entry_set: &mut HgMutationEntrySet,
+        changesets: HashSet<HgChangesetId>,
+    ) -> Result<()> {
+        let mut seen = changesets.clone();
+        let mut to_fetch: Vec<_> = changesets.into_iter().collect();
+        let mut depth = 0;
+        while !to_fetch.is_empty() {
+            if depth >= MAX_SUCCESSOR_DEPTH || entry_set.entries.len() >= MAX_SUCCESSOR_ENTRIES {
+                warn!(
+                    ctx.logger(),
+                    "Mutation store stopped fetching successors after {} rounds and {} entries",
+                    depth,
+                    entry_set.entries.len(),
+                );
+                break;
+            }
+            depth += 1;
+
+            let (replica_successors, master_successors) =
+                self.fetch_successor_ids(to_fetch.as_slice()).await?;
+            let replica_successors: HashSet<_> = replica_successors
+                .into_iter()
+                .filter(|successor| seen.insert(*successor))
+                .collect();
+            let master_successors: HashSet<_> = master_successors
+                .into_iter()
+                .filter(|successor| seen.insert(*successor))
+                .collect();
+            self.fetch_by_successor(
+                &self.connections.read_connection,
+                entry_set,
+                &replica_successors,
+            )
+            .await?;
+            self.fetch_by_successor(
+                &self.connections.read_master_connection,
+                entry_set,
+                &master_successors,
+            )
+            .await?;
+
+            to_fetch = Vec::with_capacity(replica_successors.len() + master_successors.len());
+            for successor in replica_successors.into_iter().chain(master_successors) {
+                if let Some(entry) = entry_set.entries.get(&successor) {
+                    for split_successor in entry.split() {
+                        if seen.insert(*split_successor) {
+                            to_fetch.push(*split_successor);
+                        }
+                    }
+                }
+                to_fetch.push(successor);
+            }
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -534,6 +658,38 @@ impl HgMutationStore for SqlHgMutationStore {
         );
         Ok(entries)
     }
+
+    async fn all_successors(
+        &self,
+        ctx: &CoreContext,
+        changeset_ids: HashSet<HgChangesetId>,
+    ) -> Result<Vec<HgMutationEntry>> {
+        if changeset_ids.is_empty() {
+            // Nothing to fetch
+            return Ok(Vec::new());
+        }
+
+        let mut entry_set = HgMutationEntrySet::new();
+        let changeset_count = changeset_ids.len();
+        self.fetch_all_successors(ctx, &mut entry_set, changeset_ids)
+            .await?;
+        let entries: Vec<_> = entry_set
+            .entries
+            .into_iter()
+            .map(|(_, entry)| entry)
+            .collect();
+        debug!(
+            ctx.logger(),
+            "Mutation store fetched {} successor entries for {} changesets",
+            entries.len(),
+            changeset_count,
+        );
+        ctx.perf_counters().add_to_counter(
+            PerfCounterType::HgMutationStoreNumFetched,
+            entries.len() as i64,
+        );
+        Ok(entries)
+    }
 }
 
 queries! {
@@ -675,6 +831,15 @@ queries! {
         ORDER BY m.successor, p.seq ASC"
     }
 
+    read SelectSuccessorsByPredecessor(repo_id: RepositoryId, >list cs_id: HgChangesetId) -> (
+        HgChangesetId,
+        HgChangesetId,
+    ) {
+        "SELECT DISTINCT predecessor, successor
+        FROM hg_mutation_preds
+        WHERE repo_id = {repo_id} AND predecessor IN {cs_id}"
+    }
+
     read SelectSplitsBySuccessor(repo_id: RepositoryId, >list cs_id: HgChangesetId) -> (
         HgChangesetId,
         u64,
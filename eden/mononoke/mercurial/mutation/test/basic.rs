@@ -20,7 +20,7 @@ use mononoke_types_mocks::repo::REPO_ZERO;
 use smallvec::smallvec;
 use sql_construct::SqlConstruct;
 
-use crate::util::check_entries;
+use crate::util::{check_entries, check_successor_entries};
 
 fn create_entries() -> HashMap<usize, HgMutationEntry> {
     // Generate the mutation graph:
@@ -288,3 +288,101 @@ async fn add_entries_and_fetch_predecessors(fb: FacebookInit) -> Result<()> {
 
     Ok(())
 }
+
+#[fbinit::compat_test]
+async fn add_entries_and_fetch_successors(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let store = SqlHgMutationStoreBuilder::with_sqlite_in_memory()
+        .unwrap()
+        .with_repo_id(REPO_ZERO);
+
+    let mut entries = create_entries();
+    store
+        .add_entries(
+            &ctx,
+            hashset![make_hg_cs_id(6), make_hg_cs_id(7)],
+            entries.values().cloned().collect(),
+        )
+        .await?;
+
+    check_successor_entries(
+        &store,
+        &ctx,
+        hashset![make_hg_cs_id(1)],
+        &entries,
+        &[2, 4, 5, 6],
+    )
+    .await?;
+    check_successor_entries(
+        &store,
+        &ctx,
+        hashset![make_hg_cs_id(3)],
+        &entries,
+        &[4, 5, 6],
+    )
+    .await?;
+    check_successor_entries(&store, &ctx, hashset![make_hg_cs_id(6)], &entries, &[]).await?;
+    check_successor_entries(&store, &ctx, hashset![make_hg_cs_id(7)], &entries, &[]).await?;
+
+    // Amend one side of the split.  Its successors are successors of the
+    // commit that was split, too.
+    //
+    //   5  --(split)-> 6
+    //                   '--> 7  --(amend)-> 8
+    let new_entry = HgMutationEntry::new(
+        make_hg_cs_id(8),
+        smallvec![make_hg_cs_id(7)],
+        vec![],
+        String::from("amend"),
+        String::from("testuser"),
+        EPOCH_ZERO.clone(),
+        vec![],
+    );
+    store
+        .add_entries(&ctx, hashset![make_hg_cs_id(8)], vec![new_entry.clone()])
+        .await?;
+    entries.insert(8, new_entry);
+
+    check_successor_entries(&store, &ctx, hashset![make_hg_cs_id(5)], &entries, &[6, 8]).await?;
+    check_successor_entries(&store, &ctx, hashset![make_hg_cs_id(7)], &entries, &[8]).await?;
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn fetch_successors_of_long_history(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let store = SqlHgMutationStoreBuilder::with_sqlite_in_memory()
+        .unwrap()
+        .with_repo_id(REPO_ZERO);
+
+    // Amend commit 1 over and over again.
+    let entries: Vec<_> = (2..=200)
+        .map(|index| {
+            HgMutationEntry::new(
+                make_hg_cs_id(index),
+                smallvec![make_hg_cs_id(index - 1)],
+                vec![],
+                String::from("amend"),
+                String::from("testuser"),
+                EPOCH_ZERO.clone(),
+                vec![],
+            )
+        })
+        .collect();
+    store
+        .add_entries(&ctx, (1..=200).map(make_hg_cs_id).collect(), entries)
+        .await?;
+
+    // Only the first 100 amends are followed.
+    let fetched = store
+        .all_successors(&ctx, hashset![make_hg_cs_id(1)])
+        .await?;
+    let mut successors: Vec<_> = fetched.iter().map(|entry| *entry.successor()).collect();
+    successors.sort();
+    let mut expected: Vec<_> = (2..=101).map(make_hg_cs_id).collect();
+    expected.sort();
+    assert_eq!(successors, expected);
+
+    Ok(())
+}
@@ -41,7 +41,26 @@ pub(crate) async fn check_entries(
     entries: &HashMap<usize, HgMutationEntry>,
     indexes: &[usize],
 ) -> Result<()> {
-    let mut fetched_entries = store.all_predecessors(ctx, changeset_ids).await?;
+    let fetched_entries = store.all_predecessors(ctx, changeset_ids).await?;
+    compare_fetched_entries(fetched_entries, entries, indexes)
+}
+
+pub(crate) async fn check_successor_entries(
+    store: &dyn HgMutationStore,
+    ctx: &CoreContext,
+    changeset_ids: HashSet<HgChangesetId>,
+    entries: &HashMap<usize, HgMutationEntry>,
+    indexes: &[usize],
+) -> Result<()> {
+    let fetched_entries = store.all_successors(ctx, changeset_ids).await?;
+    compare_fetched_entries(fetched_entries, entries, indexes)
+}
+
+fn compare_fetched_entries(
+    mut fetched_entries: Vec<HgMutationEntry>,
+    entries: &HashMap<usize, HgMutationEntry>,
+    indexes: &[usize],
+) -> Result<()> {
     let mut expected_entries = get_entries(&entries, indexes);
     fetched_entries.sort_unstable_by(compare_entries);
     expected_entries.sort_unstable_by(compare_entries);
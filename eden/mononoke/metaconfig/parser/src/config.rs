@@ -209,6 +209,7 @@ fn parse_repo_config(
         scuba_local_path_hooks,
         hgsql_name,
         hgsql_globalrevs_name,
+        wireproto_quotas,
         ..
     } = repo_config;
 
@@ -301,6 +302,8 @@ fn parse_repo_config(
 
     let derived_data_config = derived_data_config.convert()?.unwrap_or_default();
 
+    let wireproto_quotas = wireproto_quotas.unwrap_or_default().convert()?;
+
     let hgsql_name = HgsqlName(hgsql_name.unwrap_or_else(|| reponame.to_string()));
 
     let hgsql_globalrevs_name =
@@ -356,6 +359,7 @@ fn parse_repo_config(
         derived_data_config,
         hgsql_name,
         hgsql_globalrevs_name,
+        wireproto_quotas,
     })
 }
 
@@ -407,7 +411,7 @@ mod test {
         PushrebaseFlags, PushrebaseParams, RemoteDatabaseConfig, RemoteMetadataDatabaseConfig,
         ShardableRemoteDatabaseConfig, ShardedRemoteDatabaseConfig, SmallRepoCommitSyncConfig,
         SourceControlServiceMonitoring, SourceControlServiceParams, UnodeVersion,
-        WireprotoLoggingConfig,
+        WireprotoLoggingConfig, WireprotoQuota,
     };
     use mononoke_types::MPath;
     use nonzero_ext::nonzero;
@@ -874,6 +878,7 @@ mod test {
             [derived_data_config]
             derived_data_types=["fsnodes"]
             override_blame_filesize_limit=101
+            blame_ignore_revs_file=".git-blame-ignore-revs"
 
             [derived_data_config.raw_unode_version]
             unode_version_v2 = {}
@@ -943,6 +948,19 @@ mod test {
 
             [source_control_service_monitoring]
             bookmarks_to_report_age= ["master", "master2"]
+
+            [[wireproto_quotas]]
+            name="getbundle"
+            commands=["getbundle"]
+            interval=60
+            max_requests=10
+            max_bytes=1000000
+
+            [[wireproto_quotas]]
+            name="all"
+            interval=10
+            max_concurrent_requests=20
+            log_only=true
         "#;
         let www_content = r#"
             repoid=1
@@ -1037,6 +1055,7 @@ mod test {
                     bookmark: BookmarkName::new("master").unwrap(),
                     commit_limit: 100,
                     microwave_preload: false,
+                    file_content_warmup: false,
                 }),
                 hook_manager_params: Some(HookManagerParams {
                     disable_acl_checker: false,
@@ -1108,7 +1127,7 @@ mod test {
                         main_storage_config,
                         crate::convert::repo::DEFAULT_ARG_SIZE_THRESHOLD,
                     )),
-                    local_path: None,
+                    local_capture: None,
                 },
                 hash_validation_percentage: 0,
                 readonly: RepoReadOnly::ReadWrite,
@@ -1149,9 +1168,30 @@ mod test {
                     scuba_table: None,
                     unode_version: UnodeVersion::V2,
                     override_blame_filesize_limit: Some(101),
+                    blame_ignore_revs_file: Some(MPath::new(".git-blame-ignore-revs").unwrap()),
                 },
                 hgsql_name: HgsqlName("fbsource".to_string()),
                 hgsql_globalrevs_name: HgsqlGlobalrevsName("fbsource".to_string()),
+                wireproto_quotas: vec![
+                    WireprotoQuota {
+                        name: "getbundle".to_string(),
+                        commands: vec!["getbundle".to_string()],
+                        interval: Duration::from_secs(60),
+                        max_requests: Some(10),
+                        max_concurrent_requests: None,
+                        max_bytes: Some(1000000),
+                        log_only: false,
+                    },
+                    WireprotoQuota {
+                        name: "all".to_string(),
+                        commands: vec![],
+                        interval: Duration::from_secs(10),
+                        max_requests: None,
+                        max_concurrent_requests: Some(20),
+                        max_bytes: None,
+                        log_only: true,
+                    },
+                ],
             },
         );
 
@@ -1199,6 +1239,7 @@ mod test {
                 derived_data_config: DerivedDataConfig::default(),
                 hgsql_name: HgsqlName("www-foobar".to_string()),
                 hgsql_globalrevs_name: HgsqlGlobalrevsName("www-barfoo".to_string()),
+                wireproto_quotas: vec![],
             },
         );
         assert_eq!(
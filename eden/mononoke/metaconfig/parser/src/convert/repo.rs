@@ -7,6 +7,8 @@
 
 use std::collections::{BTreeSet, HashMap};
 use std::convert::TryInto;
+use std::num::NonZeroU64;
+use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
 use bookmarks_types::BookmarkName;
@@ -15,7 +17,8 @@ use metaconfig_types::{
     DerivedDataConfig, HookBypass, HookConfig, HookManagerParams, HookParams,
     InfinitepushNamespace, InfinitepushParams, LfsParams, PushParams, PushrebaseFlags,
     PushrebaseParams, ServiceWriteRestrictions, SourceControlServiceMonitoring,
-    SourceControlServiceParams, StorageConfig, UnodeVersion, WireprotoLoggingConfig,
+    SourceControlServiceParams, StorageConfig, UnodeVersion, WireprotoLocalCaptureConfig,
+    WireprotoLoggingConfig, WireprotoQuota,
 };
 use mononoke_types::MPath;
 use regex::Regex;
@@ -23,7 +26,7 @@ use repos::{
     RawBookmarkConfig, RawBundle2ReplayParams, RawCacheWarmupConfig, RawDerivedDataConfig,
     RawHookConfig, RawHookManagerParams, RawInfinitepushParams, RawLfsParams, RawPushParams,
     RawPushrebaseParams, RawServiceWriteRestrictions, RawSourceControlServiceMonitoring,
-    RawSourceControlServiceParams, RawUnodeVersion, RawWireprotoLoggingConfig,
+    RawSourceControlServiceParams, RawUnodeVersion, RawWireprotoLoggingConfig, RawWireprotoQuota,
 };
 
 use crate::convert::Convert;
@@ -40,6 +43,9 @@ pub(crate) fn convert_wireproto_logging_config(
         storage_config: wireproto_storage_config,
         remote_arg_size_threshold,
         local_path,
+        local_sample_rate,
+        local_max_file_size,
+        local_max_files,
     } = raw;
 
     let storage_config_and_threshold = match (wireproto_storage_config, remote_arg_size_threshold) {
@@ -59,10 +65,41 @@ pub(crate) fn convert_wireproto_logging_config(
         })
         .transpose()?;
 
+    let local_capture = match local_path {
+        Some(path) => {
+            let mut local_capture = WireprotoLocalCaptureConfig::new(path);
+            if let Some(sample_rate) = local_sample_rate {
+                local_capture.sample_rate = NonZeroU64::new(sample_rate.try_into()?)
+                    .ok_or_else(|| anyhow!("local_sample_rate must be larger than zero"))?;
+            }
+            if let Some(max_file_size) = local_max_file_size {
+                local_capture.max_file_size = Some(
+                    NonZeroU64::new(max_file_size.try_into()?)
+                        .ok_or_else(|| anyhow!("local_max_file_size must be larger than zero"))?,
+                );
+            }
+            if let Some(max_files) = local_max_files {
+                local_capture.max_files = max_files.try_into()?;
+            }
+            Some(local_capture)
+        }
+        None => {
+            if local_sample_rate.is_some()
+                || local_max_file_size.is_some()
+                || local_max_files.is_some()
+            {
+                return Err(anyhow!(
+                    "Invalid configuration: local wireproto capture is configured, but local_path is not"
+                ));
+            }
+            None
+        }
+    };
+
     Ok(WireprotoLoggingConfig {
         scribe_category,
         storage_config_and_threshold,
-        local_path,
+        local_capture,
     })
 }
 
@@ -78,6 +115,7 @@ impl Convert for RawCacheWarmupConfig {
                 .transpose()?
                 .unwrap_or(200000),
             microwave_preload: self.microwave_preload.unwrap_or(false),
+            file_content_warmup: self.file_content_warmup.unwrap_or(false),
         })
     }
 }
@@ -360,6 +398,47 @@ impl Convert for RawDerivedDataConfig {
             override_blame_filesize_limit: self
                 .override_blame_filesize_limit
                 .map(|limit| limit as u64),
+            blame_ignore_revs_file: self
+                .blame_ignore_revs_file
+                .map(|path| MPath::new(path))
+                .transpose()?,
+        })
+    }
+}
+
+impl Convert for RawWireprotoQuota {
+    type Output = WireprotoQuota;
+
+    fn convert(self) -> Result<Self::Output> {
+        let interval: u64 = self.interval.try_into()?;
+        if interval == 0 {
+            return Err(anyhow!(
+                "wireproto quota {} must have a positive interval",
+                self.name
+            ));
+        }
+
+        let max_requests = self.max_requests.map(|v| v.try_into()).transpose()?;
+        let max_concurrent_requests = self
+            .max_concurrent_requests
+            .map(|v| v.try_into())
+            .transpose()?;
+        let max_bytes = self.max_bytes.map(|v| v.try_into()).transpose()?;
+        if max_requests.is_none() && max_concurrent_requests.is_none() && max_bytes.is_none() {
+            return Err(anyhow!(
+                "wireproto quota {} does not set any limits",
+                self.name
+            ));
+        }
+
+        Ok(WireprotoQuota {
+            name: self.name,
+            commands: self.commands.unwrap_or_default(),
+            interval: Duration::from_secs(interval),
+            max_requests,
+            max_concurrent_requests,
+            max_bytes,
+            log_only: self.log_only.unwrap_or(false),
         })
     }
 }
@@ -92,6 +92,12 @@ impl Convert for RawBlobstoreConfig {
             RawBlobstoreConfig::pack(raw) => BlobConfig::Pack {
                 blobconfig: Box::new(raw.blobstore.convert()?),
             },
+            RawBlobstoreConfig::local_cache(raw) => BlobConfig::LocalCache {
+                blobconfig: Box::new(raw.blobstore.convert()?),
+                path: PathBuf::from(raw.path),
+                max_size: NonZeroU64::new(raw.max_size_bytes.try_into()?)
+                    .ok_or_else(|| anyhow!("local_cache max_size_bytes must be > 0"))?,
+            },
             RawBlobstoreConfig::UnknownField(f) => {
                 return Err(anyhow!("unsupported blobstore configuration ({})", f));
             }
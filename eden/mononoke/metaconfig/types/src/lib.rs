@@ -178,6 +178,8 @@ pub struct RepoConfig {
     /// Name of this repository in hgsql ... for globalrevs. This could, in some cases, not be the
     /// same as HgsqlName.
     pub hgsql_globalrevs_name: HgsqlGlobalrevsName,
+    /// Quotas on the wireproto requests a single client can make
+    pub wireproto_quotas: Vec<WireprotoQuota>,
 }
 
 /// Config for derived data
@@ -193,6 +195,9 @@ pub struct DerivedDataConfig {
     /// size is above the limit. NOTE: if `override_blame_filesize_limit` is None
     /// then a default limit will be used!
     pub override_blame_filesize_limit: Option<u64>,
+    /// Path to a file in the repo which lists commits that blame can be asked
+    /// to ignore, in the same format as git's `.git-blame-ignore-revs`.
+    pub blame_ignore_revs_file: Option<MPath>,
 }
 
 /// What type of unode derived data to generate
@@ -257,6 +262,9 @@ pub struct CacheWarmupParams {
     pub commit_limit: usize,
     /// Whether to use microwave to accelerate cache warmup.
     pub microwave_preload: bool,
+    /// Whether to also fetch the contents of all files. This is only useful with a blobstore
+    /// cache large enough to hold them, such as `BlobConfig::LocalCache`.
+    pub file_content_warmup: bool,
 }
 
 /// Configuration for the hook manager
@@ -720,6 +728,15 @@ pub enum BlobConfig {
         /// The config for the blobstore that is wrapped.
         blobconfig: Box<BlobConfig>,
     },
+    /// A read-through cache on local disk in front of another blobstore
+    LocalCache {
+        /// The config for the blobstore that is wrapped.
+        blobconfig: Box<BlobConfig>,
+        /// Directory to keep the cache in. It must not be shared with other processes.
+        path: PathBuf,
+        /// Maximum size of the cache, in bytes.
+        max_size: NonZeroU64,
+    },
 }
 
 impl BlobConfig {
@@ -737,6 +754,7 @@ impl BlobConfig {
                 .all(BlobConfig::is_local),
             Logging { blobconfig, .. } => blobconfig.is_local(),
             Pack { blobconfig, .. } => blobconfig.is_local(),
+            LocalCache { blobconfig, .. } => blobconfig.is_local(),
         }
     }
 
@@ -1012,8 +1030,8 @@ pub struct WireprotoLoggingConfig {
     /// `storage_config_and_threshold` is not specified then wireproto wireproto arguments will
     /// be inlined
     pub storage_config_and_threshold: Option<(StorageConfig, u64)>,
-    /// Local path where to log replay data that would be sent to Scribe.
-    pub local_path: Option<String>,
+    /// Capture of replay data that would be sent to Scribe to a local file.
+    pub local_capture: Option<WireprotoLocalCaptureConfig>,
 }
 
 impl Default for WireprotoLoggingConfig {
@@ -1021,11 +1039,69 @@ impl Default for WireprotoLoggingConfig {
         Self {
             scribe_category: None,
             storage_config_and_threshold: None,
-            local_path: None,
+            local_capture: None,
         }
     }
 }
 
+/// Configuration for capturing wireproto requests to a local file, in the
+/// format read by fastreplay.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WireprotoLocalCaptureConfig {
+    /// Path of the file to write captured requests to.
+    pub path: String,
+    /// Capture one in this many requests.
+    pub sample_rate: NonZeroU64,
+    /// Rotate the file once it grows beyond this many bytes. If not set,
+    /// the file is never rotated.
+    pub max_file_size: Option<NonZeroU64>,
+    /// Number of rotated files to keep in addition to the current file.
+    pub max_files: usize,
+}
+
+impl WireprotoLocalCaptureConfig {
+    /// Default number of rotated capture files to keep.
+    pub const DEFAULT_MAX_FILES: usize = 10;
+
+    /// Capture every request to `path`, without rotation.
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            sample_rate: NonZeroU64::new(1).unwrap(),
+            max_file_size: None,
+            max_files: Self::DEFAULT_MAX_FILES,
+        }
+    }
+}
+
+/// Quota on the wireproto requests a single client can make.  Usage is tracked
+/// separately for each client identity.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WireprotoQuota {
+    /// Name of the quota, used in errors and monitoring
+    pub name: String,
+    /// Wireproto commands the quota applies to. If empty, the quota applies to
+    /// all commands.
+    pub commands: Vec<String>,
+    /// Interval over which requests and bytes are counted
+    pub interval: Duration,
+    /// Maximum number of requests per client in an interval
+    pub max_requests: Option<u64>,
+    /// Maximum number of requests per client in flight at once
+    pub max_concurrent_requests: Option<u64>,
+    /// Maximum number of bytes served to a client in an interval
+    pub max_bytes: Option<u64>,
+    /// Only log requests that exceed the quota instead of rejecting them
+    pub log_only: bool,
+}
+
+impl WireprotoQuota {
+    /// Whether this quota applies to the given command
+    pub fn applies_to(&self, command: &str) -> bool {
+        self.commands.is_empty() || self.commands.iter().any(|c| c == command)
+    }
+}
+
 /// Source Control Service options
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct SourceControlServiceParams {
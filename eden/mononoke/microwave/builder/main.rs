@@ -135,6 +135,8 @@ async fn do_main<'a>(
                                 bookmark,
                                 commit_limit,
                                 microwave_preload,
+                                // File contents are not part of microwave snapshots.
+                                file_content_warmup: _,
                             } = params;
 
                             let target = cache_warmup_target(&warmup_ctx, &repo, &bookmark).await?;
@@ -143,6 +145,7 @@ async fn do_main<'a>(
                                 target,
                                 commit_limit,
                                 microwave_preload,
+                                file_content_warmup: false,
                             })
                         }
                         None => None,
@@ -0,0 +1,598 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Archives of the files in a changeset, as tar, gzipped tar or zip.
+//!
+//! Archives are produced as a stream, fetching file contents as they are
+//! written, so large archives are never held in memory.  Entries are
+//! ordered by path, which makes the archive for a changeset deterministic,
+//! and allows tar archives to be produced in several chunks, each resuming
+//! where the previous one ended.  The trees are walked in path order, so
+//! resuming from a cursor only loads the trees on the way to it.
+
+use std::collections::btree_map;
+use std::convert::TryFrom;
+use std::io::Write;
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Error;
+use blobrepo::BlobRepo;
+use blobstore::Loadable;
+use bytes::Bytes;
+use context::CoreContext;
+use filestore::{self, FetchKey};
+use flate2::{write::GzEncoder, Compression};
+use futures::compat::{Future01CompatExt, Stream01CompatExt};
+use futures::future;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use mononoke_types::fsnode::{FsnodeEntry, FsnodeFile};
+use mononoke_types::{FileType, MPath, MPathElement};
+
+use crate::changeset::ChangesetContext;
+use crate::errors::MononokeError;
+use crate::path::MononokePath;
+
+mod tar;
+mod zip;
+
+use self::tar::TarWriter;
+use self::zip::ZipWriter;
+
+/// Number of files whose content is fetched ahead of being written.
+const PREFETCH_FILES: usize = 16;
+
+/// Files up to this size are fetched ahead in full.  Larger files are
+/// streamed as they are written.
+const PREFETCH_MAX_SIZE: u64 = 1024 * 1024;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+/// A position in a tar archive, from which it can be resumed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ArchiveCursor {
+    /// After the header and the first `offset` bytes of content of a file.
+    Within(MononokePath, u64),
+    /// After the end of a file.
+    After(MononokePath),
+}
+
+impl ArchiveCursor {
+    /// Encode the cursor as a string, for use as a continuation token.
+    pub fn to_token(&self) -> String {
+        match self {
+            ArchiveCursor::Within(path, offset) => format!("w{}:{}", offset, path),
+            ArchiveCursor::After(path) => format!("a:{}", path),
+        }
+    }
+
+    /// Decode a continuation token produced by `to_token`.
+    pub fn from_token(token: &str) -> Result<Self, MononokeError> {
+        let invalid =
+            || MononokeError::InvalidRequest(format!("invalid archive cursor: {}", token));
+        let (kind, path) = match token.find(':') {
+            Some(index) => (&token[..index], &token[index + 1..]),
+            None => return Err(invalid()),
+        };
+        let path = MononokePath::try_from(path).map_err(|_| invalid())?;
+        if path.as_mpath().is_none() {
+            return Err(invalid());
+        }
+        match kind {
+            "a" => Ok(ArchiveCursor::After(path)),
+            kind if kind.starts_with('w') => {
+                let offset = kind[1..].parse().map_err(|_| invalid())?;
+                Ok(ArchiveCursor::Within(path, offset))
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    fn mpath(&self) -> Option<&MPath> {
+        match self {
+            ArchiveCursor::Within(path, _) | ArchiveCursor::After(path) => path.as_mpath(),
+        }
+    }
+}
+
+/// Part of an archive, as returned by `ChangesetContext::archive_chunk`.
+pub struct ArchiveChunk {
+    pub data: Bytes,
+    /// Where the next chunk starts, or `None` if this is the last chunk.
+    pub continue_after: Option<ArchiveCursor>,
+}
+
+enum ArchiveWriter {
+    Tar(TarWriter),
+    Zip(ZipWriter),
+}
+
+impl ArchiveWriter {
+    fn begin_file(&mut self, path: &[u8], file_type: FileType, size: u64) -> Bytes {
+        match self {
+            ArchiveWriter::Tar(writer) => writer.begin_file(path, file_type, size),
+            ArchiveWriter::Zip(writer) => writer.begin_file(path, file_type, size),
+        }
+    }
+
+    fn resume_file(&mut self, size: u64, offset: u64) -> Result<(), Error> {
+        match self {
+            ArchiveWriter::Tar(writer) => {
+                writer.resume_file(size, offset);
+                Ok(())
+            }
+            ArchiveWriter::Zip(_) => Err(Error::msg("zip archives can't be resumed")),
+        }
+    }
+
+    fn write_data(&mut self, data: Bytes) -> Result<Bytes, Error> {
+        match self {
+            ArchiveWriter::Tar(writer) => writer.write_data(data),
+            ArchiveWriter::Zip(writer) => writer.write_data(data),
+        }
+    }
+
+    fn end_file(&mut self) -> Result<Bytes, Error> {
+        match self {
+            ArchiveWriter::Tar(writer) => writer.end_file(),
+            ArchiveWriter::Zip(writer) => writer.end_file(),
+        }
+    }
+
+    fn symlink(&mut self, path: &[u8], target: &[u8]) -> Result<Bytes, Error> {
+        match self {
+            ArchiveWriter::Tar(writer) => Ok(writer.symlink(path, target)),
+            ArchiveWriter::Zip(writer) => writer.symlink(path, target),
+        }
+    }
+
+    fn finish(&mut self) -> Result<Bytes, Error> {
+        match self {
+            ArchiveWriter::Tar(writer) => Ok(writer.finish()),
+            ArchiveWriter::Zip(writer) => writer.finish(),
+        }
+    }
+}
+
+/// Gzip compression of a stream of data.
+struct Gzip {
+    encoder: GzEncoder<Vec<u8>>,
+}
+
+impl Gzip {
+    fn new() -> Self {
+        Self {
+            encoder: GzEncoder::new(Vec::new(), Compression::default()),
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<Bytes, Error> {
+        self.encoder.write_all(data)?;
+        Ok(Bytes::from(mem::take(self.encoder.get_mut())))
+    }
+
+    fn finish(self) -> Result<Bytes, Error> {
+        Ok(Bytes::from(self.encoder.finish()?))
+    }
+}
+
+/// A walk over the files of a changeset in path order.
+struct FileWalk {
+    ctx: CoreContext,
+    repo: BlobRepo,
+    /// Only files under these paths are included.  `None` includes all
+    /// files.
+    prefixes: Option<Vec<MPath>>,
+    /// Only files after this path are included, and the path itself if the
+    /// flag is set.
+    start: Option<(MPath, bool)>,
+    /// The directories being walked, and their entries that are still to
+    /// be visited.
+    stack: Vec<(
+        Option<MPath>,
+        btree_map::IntoIter<MPathElement, FsnodeEntry>,
+    )>,
+}
+
+impl FileWalk {
+    fn includes_file(&self, path: &MPath) -> bool {
+        let selected = match &self.prefixes {
+            Some(prefixes) => prefixes.iter().any(|prefix| prefix.is_prefix_of(path)),
+            None => true,
+        };
+        let started = match &self.start {
+            Some((start, inclusive)) => path > start || (*inclusive && path == start),
+            None => true,
+        };
+        selected && started
+    }
+
+    fn includes_directory(&self, path: &MPath) -> bool {
+        let selected = match &self.prefixes {
+            Some(prefixes) => prefixes
+                .iter()
+                .any(|prefix| prefix.is_prefix_of(path) || path.is_prefix_of(prefix)),
+            None => true,
+        };
+        // Everything in a directory that sorts before the start, and
+        // doesn't contain it, is before the start too.
+        let started = match &self.start {
+            Some((start, _)) => path > start || path.is_prefix_of(start),
+            None => true,
+        };
+        selected && started
+    }
+
+    async fn next_file(&mut self) -> Result<Option<(MPath, FsnodeFile)>, MononokeError> {
+        loop {
+            let next = match self.stack.last_mut() {
+                Some((dir, entries)) => entries
+                    .next()
+                    .map(|(name, entry)| (MPath::join_opt_element(dir.as_ref(), &name), entry)),
+                None => return Ok(None),
+            };
+            match next {
+                Some((path, FsnodeEntry::File(file))) => {
+                    if self.includes_file(&path) {
+                        return Ok(Some((path, file)));
+                    }
+                }
+                Some((path, FsnodeEntry::Directory(dir))) => {
+                    if self.includes_directory(&path) {
+                        let fsnode = dir
+                            .id()
+                            .load(self.ctx.clone(), self.repo.blobstore())
+                            .await
+                            .map_err(Error::from)?;
+                        self.stack
+                            .push((Some(path), fsnode.into_subentries().into_iter()));
+                    }
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+
+    fn into_stream(self) -> BoxStream<'static, Result<(MPath, FsnodeFile), MononokeError>> {
+        stream::try_unfold(self, |mut walk| async move {
+            Ok::<_, MononokeError>(walk.next_file().await?.map(|file| (file, walk)))
+        })
+        .boxed()
+    }
+}
+
+/// A piece of an archive, and the position after it.  The position is
+/// `None` after the end of the archive.
+struct ArchiveItem {
+    data: Bytes,
+    cursor: Option<ArchiveCursor>,
+}
+
+enum Content {
+    Symlink(Bytes),
+    Data(BoxStream<'static, Result<Bytes, MononokeError>>),
+}
+
+async fn fetch_content(
+    ctx: CoreContext,
+    repo: BlobRepo,
+    file: &FsnodeFile,
+    resume: Option<u64>,
+) -> Result<Content, MononokeError> {
+    let offset = resume.unwrap_or(0);
+    let key = FetchKey::Canonical(*file.content_id());
+    let missing = || MononokeError::from(Error::msg(format!("content not found: {:?}", key)));
+    if *file.file_type() == FileType::Symlink || (offset == 0 && file.size() <= PREFETCH_MAX_SIZE) {
+        let content = filestore::fetch_concat_opt(repo.blobstore(), ctx, &key)
+            .compat()
+            .await?
+            .ok_or_else(missing)?;
+        return Ok(match file.file_type() {
+            FileType::Symlink => Content::Symlink(content),
+            _ => Content::Data(stream::once(future::ok(content)).boxed()),
+        });
+    }
+
+    let size = file.size().saturating_sub(offset);
+    let (stream, _) = filestore::fetch_range_with_size(repo.blobstore(), ctx, &key, offset, size)
+        .compat()
+        .await?
+        .ok_or_else(missing)?;
+    Ok(Content::Data(
+        stream.compat().map_err(MononokeError::from).boxed(),
+    ))
+}
+
+/// The pieces of the archive for a single file.
+fn entry_items(
+    writer: Arc<Mutex<ArchiveWriter>>,
+    path: MPath,
+    file: FsnodeFile,
+    resume: Option<u64>,
+    content: Content,
+) -> BoxStream<'static, Result<ArchiveItem, MononokeError>> {
+    let path_bytes = path.to_vec();
+    let path = MononokePath::new(Some(path));
+    match content {
+        Content::Symlink(target) => {
+            let item = writer
+                .lock()
+                .expect("lock poisoned")
+                .symlink(&path_bytes, &target)
+                .map(|data| ArchiveItem {
+                    data,
+                    cursor: Some(ArchiveCursor::After(path)),
+                })
+                .map_err(MononokeError::from);
+            stream::once(future::ready(item)).boxed()
+        }
+        Content::Data(data) => {
+            let header = {
+                let mut writer = writer.lock().expect("lock poisoned");
+                match resume {
+                    None => Ok(writer.begin_file(&path_bytes, *file.file_type(), file.size())),
+                    Some(offset) => writer
+                        .resume_file(file.size(), offset)
+                        .map(|()| Bytes::new()),
+                }
+            }
+            .map(|data| ArchiveItem {
+                data,
+                cursor: Some(ArchiveCursor::Within(path.clone(), resume.unwrap_or(0))),
+            })
+            .map_err(MononokeError::from);
+
+            let mut position = resume.unwrap_or(0);
+            let content = data.and_then({
+                let writer = writer.clone();
+                let path = path.clone();
+                move |chunk| {
+                    position += chunk.len() as u64;
+                    let item = writer
+                        .lock()
+                        .expect("lock poisoned")
+                        .write_data(chunk)
+                        .map(|data| ArchiveItem {
+                            data,
+                            cursor: Some(ArchiveCursor::Within(path.clone(), position)),
+                        })
+                        .map_err(MononokeError::from);
+                    future::ready(item)
+                }
+            });
+
+            let end = stream::once(future::lazy(move |_| {
+                writer
+                    .lock()
+                    .expect("lock poisoned")
+                    .end_file()
+                    .map(|data| ArchiveItem {
+                        data,
+                        cursor: Some(ArchiveCursor::After(path)),
+                    })
+                    .map_err(MononokeError::from)
+            }));
+
+            stream::once(future::ready(header))
+                .chain(content)
+                .chain(end)
+                .boxed()
+        }
+    }
+}
+
+impl ChangesetContext {
+    /// The files in this changeset under `prefixes` (or all files if
+    /// `prefixes` is `None`), in path order, starting from `start`.
+    async fn archive_files(
+        &self,
+        prefixes: Option<Vec<MononokePath>>,
+        start: Option<(MPath, bool)>,
+    ) -> Result<BoxStream<'static, Result<(MPath, FsnodeFile), MononokeError>>, MononokeError> {
+        let root = self.root_fsnode_id().await?;
+        let repo = self.repo().blob_repo().clone();
+        let fsnode = root
+            .fsnode_id()
+            .load(self.ctx().clone(), repo.blobstore())
+            .await
+            .map_err(Error::from)?;
+        // The root path includes all files.
+        let prefixes = prefixes.and_then(|prefixes| {
+            prefixes
+                .into_iter()
+                .map(MononokePath::into_mpath)
+                .collect::<Option<Vec<_>>>()
+        });
+        let walk = FileWalk {
+            ctx: self.ctx().clone(),
+            repo,
+            prefixes,
+            start,
+            stack: vec![(None, fsnode.into_subentries().into_iter())],
+        };
+        Ok(walk.into_stream())
+    }
+
+    /// The pieces of an archive, starting from `start`.
+    async fn archive_items(
+        &self,
+        format: ArchiveFormat,
+        prefixes: Option<Vec<MononokePath>>,
+        start: Option<ArchiveCursor>,
+    ) -> Result<BoxStream<'static, Result<ArchiveItem, MononokeError>>, MononokeError> {
+        let mtime = self.author_date().await?;
+        let writer = match format {
+            ArchiveFormat::Tar | ArchiveFormat::TarGz => {
+                ArchiveWriter::Tar(TarWriter::new(mtime.timestamp().max(0) as u64))
+            }
+            ArchiveFormat::Zip => ArchiveWriter::Zip(ZipWriter::new(&mtime)),
+        };
+        let writer = Arc::new(Mutex::new(writer));
+
+        let not_found = || {
+            MononokeError::InvalidRequest(String::from(
+                "archive cursor does not refer to a file in this commit",
+            ))
+        };
+        let start_path = match &start {
+            Some(cursor) => Some(cursor.mpath().ok_or_else(not_found)?.clone()),
+            None => None,
+        };
+        let inclusive = matches!(start, Some(ArchiveCursor::Within(..)));
+        let mut files = self
+            .archive_files(prefixes, start_path.clone().map(|path| (path, inclusive)))
+            .await?;
+
+        // A file being resumed must be the first one.
+        let resumed = match start {
+            Some(ArchiveCursor::Within(_, offset)) => match files.try_next().await? {
+                Some((path, file)) if Some(&path) == start_path.as_ref() => {
+                    if offset > file.size() || *file.file_type() == FileType::Symlink {
+                        return Err(MononokeError::InvalidRequest(String::from(
+                            "archive cursor is past the end of the file",
+                        )));
+                    }
+                    Some((path, file, Some(offset)))
+                }
+                _ => return Err(not_found()),
+            },
+            _ => None,
+        };
+
+        let ctx = self.ctx().clone();
+        let repo = self.repo().blob_repo().clone();
+        let entries = stream::iter(resumed.map(Ok))
+            .chain(files.map_ok(|(path, file)| (path, file, None)))
+            .map(move |entry| {
+                let ctx = ctx.clone();
+                let repo = repo.clone();
+                async move {
+                    let (path, file, resume) = entry?;
+                    let content = fetch_content(ctx, repo, &file, resume).await?;
+                    Ok::<_, MononokeError>((path, file, resume, content))
+                }
+            });
+
+        let items = entries
+            .buffered(PREFETCH_FILES)
+            .map_ok({
+                let writer = writer.clone();
+                move |(path, file, resume, content)| {
+                    entry_items(writer.clone(), path, file, resume, content)
+                }
+            })
+            .try_flatten();
+        let trailer = stream::once(future::lazy(move |_| {
+            let data = writer.lock().expect("lock poisoned").finish()?;
+            Ok::<_, MononokeError>(ArchiveItem { data, cursor: None })
+        }));
+
+        Ok(items.chain(trailer).boxed())
+    }
+
+    /// Produce an archive of the files in this changeset under `prefixes`
+    /// (or all files if `prefixes` is `None`).
+    ///
+    /// File types are preserved: executable files are marked as such, and
+    /// symlinks are stored as symlinks.  File modification times are set
+    /// to the changeset's author date.
+    pub async fn archive(
+        &self,
+        format: ArchiveFormat,
+        prefixes: Option<Vec<MononokePath>>,
+    ) -> Result<BoxStream<'static, Result<Bytes, MononokeError>>, MononokeError> {
+        let data = self
+            .archive_items(format, prefixes, None)
+            .await?
+            .map_ok(|item| item.data);
+
+        let data = match format {
+            ArchiveFormat::Tar | ArchiveFormat::Zip => data.boxed(),
+            ArchiveFormat::TarGz => {
+                let gzip = Arc::new(Mutex::new(Some(Gzip::new())));
+                let trailer = stream::once(future::lazy({
+                    let gzip = gzip.clone();
+                    move |_| match gzip.lock().expect("lock poisoned").take() {
+                        Some(gzip) => gzip.finish().map_err(MononokeError::from),
+                        None => Ok(Bytes::new()),
+                    }
+                }));
+                data.and_then(move |data| {
+                    let res = match gzip.lock().expect("lock poisoned").as_mut() {
+                        Some(gzip) => gzip.write(&data).map_err(MononokeError::from),
+                        None => Ok(Bytes::new()),
+                    };
+                    future::ready(res)
+                })
+                .chain(trailer)
+                .boxed()
+            }
+        };
+
+        Ok(data
+            .try_filter(|data| future::ready(!data.is_empty()))
+            .boxed())
+    }
+
+    /// Produce part of a tar archive of the files in this changeset, as
+    /// for `archive`, starting at `after` (or the start of the archive if
+    /// `after` is `None`).
+    ///
+    /// The chunk ends at the first file boundary or content chunk after
+    /// `max_size` bytes.  Concatenating the chunks gives the full archive.
+    /// For gzipped archives, each chunk is compressed separately, and the
+    /// concatenation is a valid multi-member gzip file.
+    ///
+    /// Zip archives can't be produced in chunks, as the directory at the
+    /// end of the archive refers to all files.
+    pub async fn archive_chunk(
+        &self,
+        format: ArchiveFormat,
+        prefixes: Option<Vec<MononokePath>>,
+        after: Option<ArchiveCursor>,
+        max_size: usize,
+    ) -> Result<ArchiveChunk, MononokeError> {
+        if format == ArchiveFormat::Zip {
+            return Err(MononokeError::InvalidRequest(String::from(
+                "zip archives can't be produced in chunks",
+            )));
+        }
+
+        let mut items = self.archive_items(format, prefixes, after.clone()).await?;
+        let mut data = Vec::new();
+        let mut continue_after = after;
+        while let Some(item) = items.try_next().await? {
+            data.extend_from_slice(&item.data);
+            continue_after = item.cursor;
+            if continue_after.is_none() || data.len() >= max_size {
+                break;
+            }
+        }
+
+        let data = match format {
+            ArchiveFormat::TarGz => {
+                let mut gzip = Gzip::new();
+                let mut compressed = gzip.write(&data)?.to_vec();
+                compressed.extend_from_slice(&gzip.finish()?);
+                Bytes::from(compressed)
+            }
+            _ => Bytes::from(data),
+        };
+
+        Ok(ArchiveChunk {
+            data,
+            continue_after,
+        })
+    }
+}
@@ -0,0 +1,191 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Streaming writer for POSIX (pax) tar archives.
+
+use anyhow::{anyhow, Error};
+use bytes::Bytes;
+use mononoke_types::FileType;
+
+const BLOCK_SIZE: u64 = 512;
+const NAME_SIZE: usize = 100;
+/// Largest size that fits in the 11 octal digits of the size field.
+const MAX_OCTAL_SIZE: u64 = 0o77_777_777_777;
+
+fn padding(size: u64) -> usize {
+    ((BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE) as usize
+}
+
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+}
+
+fn write_truncated(field: &mut [u8], value: &[u8]) {
+    let len = value.len().min(field.len());
+    field[..len].copy_from_slice(&value[..len]);
+}
+
+/// Append a pax extended header record: `"<len> <key>=<value>\n"`, where
+/// `<len>` is the length of the whole record, including itself.
+fn pax_record(records: &mut Vec<u8>, key: &str, value: &[u8]) {
+    let base = key.len() + value.len() + 3;
+    let mut len = base + 1;
+    while len != base + len.to_string().len() {
+        len = base + len.to_string().len();
+    }
+    records.extend_from_slice(format!("{} {}=", len, key).as_bytes());
+    records.extend_from_slice(value);
+    records.push(b'\n');
+}
+
+pub(crate) struct TarWriter {
+    mtime: u64,
+    /// Bytes of content still expected for the current file.
+    remaining: u64,
+    /// Padding to write after the current file.
+    padding: usize,
+}
+
+impl TarWriter {
+    pub(crate) fn new(mtime: u64) -> Self {
+        Self {
+            mtime,
+            remaining: 0,
+            padding: 0,
+        }
+    }
+
+    fn block(&self, path: &[u8], typeflag: u8, mode: u64, size: u64, link: &[u8]) -> Vec<u8> {
+        let mut block = vec![0u8; BLOCK_SIZE as usize];
+        write_truncated(&mut block[0..NAME_SIZE], path);
+        write_octal(&mut block[100..108], mode);
+        write_octal(&mut block[108..116], 0);
+        write_octal(&mut block[116..124], 0);
+        write_octal(&mut block[124..136], size.min(MAX_OCTAL_SIZE));
+        write_octal(&mut block[136..148], self.mtime.min(MAX_OCTAL_SIZE));
+        block[156] = typeflag;
+        write_truncated(&mut block[157..257], link);
+        block[257..263].copy_from_slice(b"ustar\0");
+        block[263..265].copy_from_slice(b"00");
+
+        // The checksum is computed with the checksum field set to spaces.
+        block[148..156].copy_from_slice(b"        ");
+        let checksum: u64 = block.iter().map(|b| *b as u64).sum();
+        write_octal(&mut block[148..155], checksum);
+        block
+    }
+
+    fn header(&self, path: &[u8], typeflag: u8, mode: u64, size: u64, link: &[u8]) -> Vec<u8> {
+        let mut records = Vec::new();
+        if path.len() > NAME_SIZE {
+            pax_record(&mut records, "path", path);
+        }
+        if link.len() > NAME_SIZE {
+            pax_record(&mut records, "linkpath", link);
+        }
+        if size > MAX_OCTAL_SIZE {
+            pax_record(&mut records, "size", size.to_string().as_bytes());
+        }
+
+        let mut header = Vec::new();
+        if !records.is_empty() {
+            let len = records.len() as u64;
+            header.extend(self.block(b"././@PaxHeader", b'x', 0o644, len, b""));
+            header.extend(records);
+            header.resize(header.len() + padding(len), 0);
+        }
+        header.extend(self.block(path, typeflag, mode, size, link));
+        header
+    }
+
+    /// Start a regular or executable file of `size` bytes.  The content
+    /// must follow in calls to `write_data`.
+    pub(crate) fn begin_file(&mut self, path: &[u8], file_type: FileType, size: u64) -> Bytes {
+        let mode = match file_type {
+            FileType::Executable => 0o755,
+            _ => 0o644,
+        };
+        self.resume_file(size, 0);
+        Bytes::from(self.header(path, b'0', mode, size, b""))
+    }
+
+    /// Continue a file whose header and first `offset` bytes were written
+    /// by another writer.
+    pub(crate) fn resume_file(&mut self, size: u64, offset: u64) {
+        self.remaining = size.saturating_sub(offset);
+        self.padding = padding(size);
+    }
+
+    /// Write content of the current file.  The content must not be longer
+    /// than the size declared in its header.
+    pub(crate) fn write_data(&mut self, data: Bytes) -> Result<Bytes, Error> {
+        let len = data.len() as u64;
+        if len > self.remaining {
+            return Err(anyhow!(
+                "tar file content is {} bytes longer than its declared size",
+                len - self.remaining
+            ));
+        }
+        self.remaining -= len;
+        Ok(data)
+    }
+
+    /// Finish the current file.  All of the content declared in its header
+    /// must have been written.
+    pub(crate) fn end_file(&mut self) -> Result<Bytes, Error> {
+        if self.remaining != 0 {
+            return Err(anyhow!(
+                "tar file content is {} bytes shorter than its declared size",
+                self.remaining
+            ));
+        }
+        let len = self.padding;
+        self.padding = 0;
+        Ok(Bytes::from(vec![0u8; len]))
+    }
+
+    pub(crate) fn symlink(&mut self, path: &[u8], target: &[u8]) -> Bytes {
+        Bytes::from(self.header(path, b'2', 0o777, 0, target))
+    }
+
+    /// The end-of-archive marker.
+    pub(crate) fn finish(&mut self) -> Bytes {
+        Bytes::from(vec![0u8; 2 * BLOCK_SIZE as usize])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn content_must_match_size() {
+        let mut writer = TarWriter::new(0);
+        let header = writer.begin_file(b"file", FileType::Regular, 5);
+        assert_eq!(header.len(), BLOCK_SIZE as usize);
+        assert_eq!(
+            writer.write_data(Bytes::from("abc")).unwrap().as_ref(),
+            b"abc"
+        );
+        assert!(writer.end_file().is_err());
+
+        writer.begin_file(b"file", FileType::Regular, 5);
+        writer.write_data(Bytes::from("abc")).unwrap();
+        assert!(writer.write_data(Bytes::from("def")).is_err());
+
+        writer.begin_file(b"file", FileType::Regular, 5);
+        writer.write_data(Bytes::from("abcde")).unwrap();
+        assert_eq!(writer.end_file().unwrap().len(), 507);
+
+        // A resumed file only expects the rest of the content.
+        writer.resume_file(5, 3);
+        writer.write_data(Bytes::from("de")).unwrap();
+        assert_eq!(writer.end_file().unwrap().len(), 507);
+    }
+}
@@ -0,0 +1,318 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Streaming writer for zip archives.
+//!
+//! As the archive is written in a single pass, each entry's CRC and sizes
+//! follow its content in a data descriptor.  Zip64 records are used for
+//! entries, offsets and counts that don't fit in the classic format.
+
+use std::io::Write;
+use std::mem;
+
+use anyhow::{anyhow, Error};
+use bytes::Bytes;
+use chrono::{DateTime, Datelike, FixedOffset, Timelike};
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use mononoke_types::FileType;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const ZIP64_END_SIGNATURE: u32 = 0x06064b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+const END_SIGNATURE: u32 = 0x06054b50;
+
+/// Made by Unix (3), spec version 4.5.
+const VERSION_MADE_BY: u16 = (3 << 8) | 45;
+const VERSION_DEFAULT: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+/// Sizes follow in a data descriptor; names are UTF-8.
+const FLAGS: u16 = (1 << 3) | (1 << 11);
+const METHOD_DEFLATE: u16 = 8;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+
+/// Entries that might come close to this size use zip64 sizes.  The
+/// margin allows for incompressible content growing slightly.
+const ZIP64_SIZE_THRESHOLD: u64 = 0xffff_0000;
+const MAX_32: u64 = 0xffff_ffff;
+const MAX_16: u64 = 0xffff;
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Convert a timestamp to MS-DOS (time, date), which is in local time
+/// and can't represent anything before 1980.
+fn dos_datetime(date: &DateTime<FixedOffset>) -> (u16, u16) {
+    if date.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let time = (date.hour() << 11) | (date.minute() << 5) | (date.second() / 2);
+    let day = (((date.year() - 1980) as u32).min(127) << 9) | (date.month() << 5) | date.day();
+    (time as u16, day as u16)
+}
+
+struct CentralEntry {
+    name: Vec<u8>,
+    mode: u32,
+    crc: u32,
+    compressed_size: u64,
+    size: u64,
+    offset: u64,
+    zip64: bool,
+}
+
+struct CurrentEntry {
+    name: Vec<u8>,
+    mode: u32,
+    offset: u64,
+    zip64: bool,
+    crc: Crc,
+    encoder: DeflateEncoder<Vec<u8>>,
+    compressed_size: u64,
+    size: u64,
+}
+
+pub(crate) struct ZipWriter {
+    time: u16,
+    date: u16,
+    offset: u64,
+    entries: Vec<CentralEntry>,
+    current: Option<CurrentEntry>,
+}
+
+impl ZipWriter {
+    pub(crate) fn new(mtime: &DateTime<FixedOffset>) -> Self {
+        let (time, date) = dos_datetime(mtime);
+        Self {
+            time,
+            date,
+            offset: 0,
+            entries: Vec::new(),
+            current: None,
+        }
+    }
+
+    fn emit(&mut self, buf: Vec<u8>) -> Bytes {
+        self.offset += buf.len() as u64;
+        Bytes::from(buf)
+    }
+
+    /// Start a file of `size` bytes.  The content must follow in calls to
+    /// `write_data`.
+    pub(crate) fn begin_file(&mut self, path: &[u8], file_type: FileType, size: u64) -> Bytes {
+        let mode = match file_type {
+            FileType::Regular => 0o100644,
+            FileType::Executable => 0o100755,
+            FileType::Symlink => 0o120777,
+        };
+        let zip64 = size >= ZIP64_SIZE_THRESHOLD || self.offset >= MAX_32;
+
+        let mut buf = Vec::with_capacity(30 + path.len() + 20);
+        put_u32(&mut buf, LOCAL_HEADER_SIGNATURE);
+        put_u16(
+            &mut buf,
+            if zip64 {
+                VERSION_ZIP64
+            } else {
+                VERSION_DEFAULT
+            },
+        );
+        put_u16(&mut buf, FLAGS);
+        put_u16(&mut buf, METHOD_DEFLATE);
+        put_u16(&mut buf, self.time);
+        put_u16(&mut buf, self.date);
+        // CRC and sizes are in the data descriptor.
+        put_u32(&mut buf, 0);
+        let placeholder = if zip64 { MAX_32 as u32 } else { 0 };
+        put_u32(&mut buf, placeholder);
+        put_u32(&mut buf, placeholder);
+        put_u16(&mut buf, path.len() as u16);
+        put_u16(&mut buf, if zip64 { 20 } else { 0 });
+        buf.extend_from_slice(path);
+        if zip64 {
+            put_u16(&mut buf, ZIP64_EXTRA_ID);
+            put_u16(&mut buf, 16);
+            put_u64(&mut buf, 0);
+            put_u64(&mut buf, 0);
+        }
+
+        self.current = Some(CurrentEntry {
+            name: path.to_vec(),
+            mode,
+            offset: self.offset,
+            zip64,
+            crc: Crc::new(),
+            encoder: DeflateEncoder::new(Vec::new(), Compression::default()),
+            compressed_size: 0,
+            size: 0,
+        });
+        self.emit(buf)
+    }
+
+    pub(crate) fn write_data(&mut self, data: Bytes) -> Result<Bytes, Error> {
+        let current = self
+            .current
+            .as_mut()
+            .ok_or_else(|| anyhow!("zip data written outside of a file"))?;
+        current.crc.update(&data);
+        current.size += data.len() as u64;
+        current.encoder.write_all(&data)?;
+        let compressed = mem::take(current.encoder.get_mut());
+        current.compressed_size += compressed.len() as u64;
+        Ok(self.emit(compressed))
+    }
+
+    pub(crate) fn end_file(&mut self) -> Result<Bytes, Error> {
+        let current = self
+            .current
+            .take()
+            .ok_or_else(|| anyhow!("zip file ended without being started"))?;
+        let mut buf = current.encoder.finish()?;
+        let compressed_size = current.compressed_size + buf.len() as u64;
+        let size = current.size;
+        let crc = current.crc.sum();
+
+        put_u32(&mut buf, DATA_DESCRIPTOR_SIGNATURE);
+        put_u32(&mut buf, crc);
+        if current.zip64 {
+            put_u64(&mut buf, compressed_size);
+            put_u64(&mut buf, size);
+        } else if compressed_size >= MAX_32 || size >= MAX_32 {
+            return Err(anyhow!(
+                "zip entry {} is larger than declared",
+                String::from_utf8_lossy(&current.name)
+            ));
+        } else {
+            put_u32(&mut buf, compressed_size as u32);
+            put_u32(&mut buf, size as u32);
+        }
+
+        self.entries.push(CentralEntry {
+            name: current.name,
+            mode: current.mode,
+            crc,
+            compressed_size,
+            size,
+            offset: current.offset,
+            zip64: current.zip64,
+        });
+        Ok(self.emit(buf))
+    }
+
+    /// Symlinks are stored as files containing the link target.
+    pub(crate) fn symlink(&mut self, path: &[u8], target: &[u8]) -> Result<Bytes, Error> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&self.begin_file(path, FileType::Symlink, target.len() as u64));
+        buf.extend_from_slice(&self.write_data(Bytes::copy_from_slice(target))?);
+        buf.extend_from_slice(&self.end_file()?);
+        Ok(Bytes::from(buf))
+    }
+
+    /// Write the central directory.
+    pub(crate) fn finish(&mut self) -> Result<Bytes, Error> {
+        if self.current.is_some() {
+            return Err(anyhow!("zip archive finished inside a file"));
+        }
+
+        let mut buf = Vec::new();
+        let central_offset = self.offset;
+        for entry in self.entries.iter() {
+            let mut extra = Vec::new();
+            if entry.size >= MAX_32 {
+                put_u64(&mut extra, entry.size);
+            }
+            if entry.compressed_size >= MAX_32 {
+                put_u64(&mut extra, entry.compressed_size);
+            }
+            if entry.offset >= MAX_32 {
+                put_u64(&mut extra, entry.offset);
+            }
+
+            put_u32(&mut buf, CENTRAL_HEADER_SIGNATURE);
+            put_u16(&mut buf, VERSION_MADE_BY);
+            put_u16(
+                &mut buf,
+                if entry.zip64 || !extra.is_empty() {
+                    VERSION_ZIP64
+                } else {
+                    VERSION_DEFAULT
+                },
+            );
+            put_u16(&mut buf, FLAGS);
+            put_u16(&mut buf, METHOD_DEFLATE);
+            put_u16(&mut buf, self.time);
+            put_u16(&mut buf, self.date);
+            put_u32(&mut buf, entry.crc);
+            put_u32(&mut buf, entry.compressed_size.min(MAX_32) as u32);
+            put_u32(&mut buf, entry.size.min(MAX_32) as u32);
+            put_u16(&mut buf, entry.name.len() as u16);
+            put_u16(
+                &mut buf,
+                if extra.is_empty() {
+                    0
+                } else {
+                    extra.len() as u16 + 4
+                },
+            );
+            // Comment length, disk number and internal attributes.
+            put_u16(&mut buf, 0);
+            put_u16(&mut buf, 0);
+            put_u16(&mut buf, 0);
+            put_u32(&mut buf, entry.mode << 16);
+            put_u32(&mut buf, entry.offset.min(MAX_32) as u32);
+            buf.extend_from_slice(&entry.name);
+            if !extra.is_empty() {
+                put_u16(&mut buf, ZIP64_EXTRA_ID);
+                put_u16(&mut buf, extra.len() as u16);
+                buf.extend(extra);
+            }
+        }
+
+        let count = self.entries.len() as u64;
+        let central_size = buf.len() as u64;
+        if count >= MAX_16 || central_offset >= MAX_32 || central_size >= MAX_32 {
+            let zip64_end_offset = central_offset + central_size;
+            put_u32(&mut buf, ZIP64_END_SIGNATURE);
+            put_u64(&mut buf, 44);
+            put_u16(&mut buf, VERSION_MADE_BY);
+            put_u16(&mut buf, VERSION_ZIP64);
+            put_u32(&mut buf, 0);
+            put_u32(&mut buf, 0);
+            put_u64(&mut buf, count);
+            put_u64(&mut buf, count);
+            put_u64(&mut buf, central_size);
+            put_u64(&mut buf, central_offset);
+
+            put_u32(&mut buf, ZIP64_LOCATOR_SIGNATURE);
+            put_u32(&mut buf, 0);
+            put_u64(&mut buf, zip64_end_offset);
+            put_u32(&mut buf, 1);
+        }
+
+        put_u32(&mut buf, END_SIGNATURE);
+        put_u16(&mut buf, 0);
+        put_u16(&mut buf, 0);
+        put_u16(&mut buf, count.min(MAX_16) as u16);
+        put_u16(&mut buf, count.min(MAX_16) as u16);
+        put_u32(&mut buf, central_size.min(MAX_32) as u32);
+        put_u32(&mut buf, central_offset.min(MAX_32) as u32);
+        put_u16(&mut buf, 0);
+
+        Ok(self.emit(buf))
+    }
+}
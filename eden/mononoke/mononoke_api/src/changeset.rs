@@ -9,7 +9,9 @@ use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::convert::TryInto;
 use std::fmt;
 use std::future::Future;
+use std::iter;
 use std::pin::Pin;
+use std::str::FromStr;
 
 use anyhow::anyhow;
 use blobrepo_hg::BlobRepoHg;
@@ -25,10 +27,13 @@ use futures::future::{self, try_join, try_join_all, FutureExt, Shared};
 use futures::stream::{self, Stream, StreamExt, TryStreamExt};
 use manifest::{Diff as ManifestDiff, Entry as ManifestEntry, ManifestOps, PathOrPrefix};
 use maplit::hashset;
+use mercurial_mutation::{HgMutationEntry, HgMutationStore};
 use mercurial_types::Globalrev;
 pub use mononoke_types::Generation;
 use mononoke_types::{BonsaiChangeset, FileChange, MPath, MPathElement};
 use reachabilityindex::ReachabilityIndex;
+use regex::bytes::Regex;
+use trigram_index::{find_candidate_files, RootTrigramIndexId, TrigramQuery};
 use unodes::RootUnodeManifestId;
 
 use crate::changeset_path::ChangesetPathContext;
@@ -36,7 +41,7 @@ use crate::changeset_path_diff::ChangesetPathDiffContext;
 use crate::errors::MononokeError;
 use crate::path::MononokePath;
 use crate::repo::RepoContext;
-use crate::specifiers::{ChangesetId, GitSha1, HgChangesetId};
+use crate::specifiers::{ChangesetId, ChangesetSpecifier, GitSha1, HgChangesetId};
 
 #[derive(Clone)]
 pub struct ChangesetContext {
@@ -52,6 +57,103 @@ pub struct ChangesetContext {
         Shared<Pin<Box<dyn Future<Output = Result<RootUnodeManifestId, MononokeError>> + Send>>>,
 }
 
+/// The maximum number of files which may match a content search query.
+/// Searches with more candidate files must use a more specific regex or
+/// restrict the search to some prefixes.
+const MAX_CONTENT_SEARCH_CANDIDATES: usize = 10_000;
+
+/// The number of files fetched concurrently by a content search.  Only
+/// indexed files are fetched, so each is at most `MAX_INDEXED_FILE_SIZE`.
+const CONTENT_SEARCH_CONCURRENCY: usize = 10;
+
+/// The maximum number of changesets which may be listed in the repo's blame
+/// ignore file.
+const MAX_BLAME_IGNORE_REVS: usize = 1_000;
+
+/// The number of blame ignore file entries resolved concurrently.
+const BLAME_IGNORE_REVS_CONCURRENCY: usize = 10;
+
+/// The result of a content search.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ContentSearchResult {
+    pub matches: Vec<ContentSearchMatch>,
+    /// Files which may match, but which were not searched because they
+    /// are binary or too large to be indexed.
+    pub unsearched_files: Vec<MononokePath>,
+}
+
+/// A line of a file which matched a content search.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ContentSearchMatch {
+    pub path: MononokePath,
+    /// The line number of the matching line, starting from 1.
+    pub line_number: usize,
+    pub line: String,
+}
+
+/// A mutation (e.g. an amend, a rebase or a land) which replaced some commits
+/// with a new commit.  Mutations are recorded by Mercurial, so the commits are
+/// identified by their Mercurial IDs, and they may not exist in the repo.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChangesetMutation {
+    /// The commit created by the mutation.
+    pub successor: HgChangesetId,
+    /// The commits replaced by the mutation.
+    pub predecessors: Vec<HgChangesetId>,
+    /// Other commits created by the mutation, if it split the predecessors.
+    pub split: Vec<HgChangesetId>,
+    /// The operation, e.g. "amend" or "rebase".
+    pub op: String,
+    pub user: String,
+    pub time: DateTime<FixedOffset>,
+}
+
+impl From<HgMutationEntry> for ChangesetMutation {
+    fn from(entry: HgMutationEntry) -> Self {
+        ChangesetMutation {
+            successor: *entry.successor(),
+            predecessors: entry.predecessors().to_vec(),
+            split: entry.split().to_vec(),
+            op: entry.op().to_string(),
+            user: entry.user().to_string(),
+            time: entry.time().as_chrono().clone(),
+        }
+    }
+}
+
+impl ChangesetMutation {
+    /// The commits created by `mutations` which haven't been replaced by
+    /// another of the mutations.  For the successors of a commit which was
+    /// amended and then landed, this is the landed commit.
+    pub fn final_successors(mutations: &[ChangesetMutation]) -> Vec<HgChangesetId> {
+        let replaced: HashSet<_> = mutations
+            .iter()
+            .flat_map(|mutation| mutation.predecessors.iter().cloned())
+            .collect();
+        let mut successors = Vec::new();
+        for mutation in mutations {
+            let created = iter::once(&mutation.successor).chain(mutation.split.iter());
+            for successor in created {
+                if !replaced.contains(successor) && !successors.contains(successor) {
+                    successors.push(*successor);
+                }
+            }
+        }
+        successors
+    }
+}
+
+/// Sort mutations oldest first, so that they read as a history.
+fn into_sorted_mutations(entries: Vec<HgMutationEntry>) -> Vec<ChangesetMutation> {
+    let mut mutations: Vec<ChangesetMutation> = entries.into_iter().map(Into::into).collect();
+    mutations.sort_by(|a, b| {
+        a.time
+            .cmp(&b.time)
+            .then_with(|| a.successor.cmp(&b.successor))
+    });
+    mutations
+}
+
 impl fmt::Debug for ChangesetContext {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -149,6 +251,46 @@ impl ChangesetContext {
         Ok(mapping.iter().next().map(|(hg_cs_id, _)| *hg_cs_id))
     }
 
+    /// The mutations which led to this changeset, i.e. the amends, rebases
+    /// and so on that created it from its predecessors, all the way back to
+    /// the commits it was originally created from.
+    pub async fn mutation_predecessors(&self) -> Result<Vec<ChangesetMutation>, MononokeError> {
+        let hg_cs_id = match self.hg_id().await? {
+            Some(hg_cs_id) => hg_cs_id,
+            None => return Ok(Vec::new()),
+        };
+        let entries = self
+            .repo()
+            .blob_repo()
+            .hg_mutation_store()
+            .all_predecessors(self.ctx(), hashset! { hg_cs_id })
+            .await?;
+        Ok(into_sorted_mutations(entries))
+    }
+
+    /// The mutations which replaced this changeset, and the mutations which
+    /// replaced the commits that replaced it, and so on.
+    pub async fn mutation_successors(&self) -> Result<Vec<ChangesetMutation>, MononokeError> {
+        let hg_cs_id = match self.hg_id().await? {
+            Some(hg_cs_id) => hg_cs_id,
+            None => return Ok(Vec::new()),
+        };
+        let entries = self
+            .repo()
+            .blob_repo()
+            .hg_mutation_store()
+            .all_successors(self.ctx(), hashset! { hg_cs_id })
+            .await?;
+        Ok(into_sorted_mutations(entries))
+    }
+
+    /// The commits which this changeset eventually became.  See
+    /// `ChangesetMutation::final_successors`.
+    pub async fn final_successors(&self) -> Result<Vec<HgChangesetId>, MononokeError> {
+        let mutations = self.mutation_successors().await?;
+        Ok(ChangesetMutation::final_successors(&mutations))
+    }
+
     /// The Globalrev for the changeset.
     pub async fn globalrev(&self) -> Result<Option<Globalrev>, MononokeError> {
         let mapping = self
@@ -238,6 +380,48 @@ impl ChangesetContext {
         }
     }
 
+    /// Changesets listed in the repo's blame ignore file as of this
+    /// changeset.  Entries may be either bonsai or Mercurial changeset ids;
+    /// entries which do not resolve to a changeset in this repo are skipped.
+    /// The file may list at most `MAX_BLAME_IGNORE_REVS` changesets.
+    pub async fn blame_ignore_revs(&self) -> Result<HashSet<ChangesetId>, MononokeError> {
+        let config = self.repo().blob_repo().get_derived_data_config();
+        let path = match &config.blame_ignore_revs_file {
+            Some(path) => MononokePath::new(Some(path.clone())),
+            None => return Ok(HashSet::new()),
+        };
+        let content = match ChangesetPathContext::new(self.clone(), path).file().await? {
+            Some(file) => file.content_concat().await?,
+            None => return Ok(HashSet::new()),
+        };
+        let specifiers = String::from_utf8_lossy(content.as_ref())
+            .lines()
+            .filter_map(|line| {
+                let id = line.split('#').next()?.trim();
+                if let Ok(cs_id) = ChangesetId::from_str(id) {
+                    Some(ChangesetSpecifier::Bonsai(cs_id))
+                } else if let Ok(hg_cs_id) = HgChangesetId::from_str(id) {
+                    Some(ChangesetSpecifier::Hg(hg_cs_id))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        if specifiers.len() > MAX_BLAME_IGNORE_REVS {
+            return Err(MononokeError::InvalidRequest(format!(
+                "blame ignore file lists {} changesets, the limit is {}",
+                specifiers.len(),
+                MAX_BLAME_IGNORE_REVS
+            )));
+        }
+        let cs_ids: Vec<_> = stream::iter(specifiers)
+            .map(|specifier| self.repo().resolve_specifier(specifier))
+            .buffered(BLAME_IGNORE_REVS_CONCURRENCY)
+            .try_collect()
+            .await?;
+        Ok(cs_ids.into_iter().flatten().collect())
+    }
+
     /// The IDs of the parents of the changeset.
     pub async fn parents(&self) -> Result<Vec<ChangesetId>, MononokeError> {
         Ok(self.changeset_info().await?.parents().collect())
@@ -604,6 +788,92 @@ impl ChangesetContext {
             .map_err(MononokeError::from))
     }
 
+    /// Search the contents of the text files in this changeset for lines
+    /// matching `regex`, optionally restricted to the files under
+    /// `prefixes`.
+    ///
+    /// The trigram index is used to find the files which may match, and
+    /// only these files are fetched and searched.  Files which are not
+    /// indexed (binary files and files larger than `MAX_INDEXED_FILE_SIZE`)
+    /// are not searched, and are returned as unsearched files instead.
+    /// Returns at most `limit` matching lines, ordered by path and then
+    /// line number.
+    pub async fn search_content(
+        &self,
+        regex: &str,
+        prefixes: Option<Vec<MononokePath>>,
+        limit: usize,
+    ) -> Result<ContentSearchResult, MononokeError> {
+        let matcher = Regex::new(regex)
+            .map_err(|e| MononokeError::InvalidRequest(format!("invalid regex: {}", e)))?;
+        let query = TrigramQuery::from_regex(regex)
+            .map_err(|e| MononokeError::InvalidRequest(format!("invalid regex: {}", e)))?;
+        if limit == 0 {
+            return Ok(ContentSearchResult::default());
+        }
+        let root = RootTrigramIndexId::derive(
+            self.ctx().clone(),
+            self.repo().blob_repo().clone(),
+            self.id,
+        )
+        .compat()
+        .await?;
+        let prefixes = match prefixes {
+            Some(prefixes) => prefixes.into_iter().map(MononokePath::into_mpath).collect(),
+            None => vec![None],
+        };
+        let ctx = self.ctx();
+        let blobstore = self.repo().blob_repo().blobstore();
+        let candidates = find_candidate_files(
+            ctx,
+            blobstore,
+            root.into_trigram_index_id(),
+            prefixes,
+            &query,
+            MAX_CONTENT_SEARCH_CANDIDATES,
+        )
+        .await?
+        .ok_or_else(|| {
+            MononokeError::InvalidRequest(format!(
+                "more than {} files may match '{}', use a more specific regex or prefixes",
+                MAX_CONTENT_SEARCH_CANDIDATES, regex
+            ))
+        })?;
+
+        let mut result = ContentSearchResult {
+            matches: Vec::new(),
+            unsearched_files: candidates
+                .unindexed
+                .into_iter()
+                .map(|path| MononokePath::new(Some(path)))
+                .collect(),
+        };
+        let mut contents = stream::iter(candidates.indexed.into_iter().map(
+            |(path, content_id)| async move {
+                let content = filestore::fetch_concat(blobstore, ctx.clone(), content_id)
+                    .compat()
+                    .await?;
+                Ok::<_, MononokeError>((path, content))
+            },
+        ))
+        .buffered(CONTENT_SEARCH_CONCURRENCY);
+        while let Some((path, content)) = contents.try_next().await? {
+            for (index, line) in content.split(|b| *b == b'\n').enumerate() {
+                if matcher.is_match(line) {
+                    result.matches.push(ContentSearchMatch {
+                        path: MononokePath::new(Some(path.clone())),
+                        line_number: index + 1,
+                        line: String::from_utf8_lossy(line).into_owned(),
+                    });
+                    if result.matches.len() >= limit {
+                        return Ok(result);
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
     /// Returns a stream of `ChangesetContext` for the history of the repository from this commit.
     pub async fn history(
         &self,
@@ -5,12 +5,13 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashSet;
 use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
 
 use anyhow::{format_err, Error};
-use blame::{fetch_blame, BlameError};
+use blame::{fetch_blame, fetch_blame_ignoring, BlameError};
 use blobrepo::BlobRepo;
 use blobstore::Loadable;
 use bytes::Bytes;
@@ -22,10 +23,11 @@ use fastlog::{list_file_history, FastlogError, HistoryAcrossDeletions};
 use filestore::FetchKey;
 use futures::compat::Future01CompatExt;
 use futures::future::{FutureExt, Shared};
-use futures::stream::{Stream, TryStreamExt};
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
 use futures::try_join;
 use futures_old::Future as FutureLegacy;
 use manifest::{Entry, ManifestOps};
+use mononoke_types::unode::UnodeEntry;
 use mononoke_types::{
     Blame, ChangesetId, ContentId, FileType, FileUnodeId, FsnodeId, ManifestUnodeId,
 };
@@ -40,6 +42,10 @@ use crate::path::MononokePath;
 use crate::repo::RepoContext;
 use crate::tree::TreeContext;
 
+/// Number of unodes loaded concurrently when finding the last commits that
+/// changed the entries of a directory.
+const LAST_CHANGED_CONCURRENCY: usize = 100;
+
 pub struct HistoryEntry {
     pub name: String,
     pub changeset_id: ChangesetId,
@@ -177,7 +183,6 @@ impl ChangesetPathContext {
         self.fsnode_id.clone().await
     }
 
-    #[allow(dead_code)]
     async fn unode_id(&self) -> Result<Option<Entry<ManifestUnodeId, FileUnodeId>>, MononokeError> {
         self.unode_id.clone().await
     }
@@ -252,6 +257,57 @@ impl ChangesetPathContext {
         Ok(entry)
     }
 
+    /// Returns the last commit that changed each entry of the directory at
+    /// this path, for the entries from `offset` (in name order) up to at
+    /// most `limit` entries.  For a subdirectory, this is the last commit
+    /// that changed any file beneath it.  Returns an empty list if the path
+    /// is not a directory in this commit.
+    ///
+    /// The last commit to change an entry is the linknode of its unode,
+    /// which is also the first commit in the entry's (fastlog) history.
+    pub async fn list_last_changed(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<(String, ChangesetContext)>, MononokeError> {
+        let manifest_unode_id = match self.unode_id().await? {
+            Some(Entry::Tree(manifest_unode_id)) => manifest_unode_id,
+            _ => return Ok(Vec::new()),
+        };
+        let ctx = self.changeset.ctx().clone();
+        let blobstore = self.repo().blob_repo().blobstore().clone();
+        let manifest_unode = manifest_unode_id.load(ctx.clone(), &blobstore).await?;
+
+        let entries = manifest_unode
+            .subentries()
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(elem, entry)| {
+                let name = String::from_utf8_lossy(elem.as_ref()).to_string();
+                let entry = entry.clone();
+                cloned!(ctx, blobstore);
+                async move {
+                    let linknode = match entry {
+                        UnodeEntry::File(file_unode_id) => {
+                            *file_unode_id.load(ctx, &blobstore).await?.linknode()
+                        }
+                        UnodeEntry::Directory(manifest_unode_id) => {
+                            *manifest_unode_id.load(ctx, &blobstore).await?.linknode()
+                        }
+                    };
+                    Ok::<_, MononokeError>((name, linknode))
+                }
+            })
+            .collect::<Vec<_>>();
+
+        stream::iter(entries)
+            .buffered(LAST_CHANGED_CONCURRENCY)
+            .map_ok(|(name, linknode)| (name, ChangesetContext::new(self.repo().clone(), linknode)))
+            .try_collect()
+            .await
+    }
+
     pub async fn blame(&self) -> Result<(Bytes, Blame), MononokeError> {
         let ctx = self.changeset.ctx().clone();
         let repo = self.changeset.repo().blob_repo().clone();
@@ -261,17 +317,37 @@ impl ChangesetPathContext {
         })?;
 
         fetch_blame(ctx, repo, csid, mpath.clone())
-            .map_err(|error| match error {
-                BlameError::NoSuchPath(_)
-                | BlameError::IsDirectory(_)
-                | BlameError::Rejected(_) => MononokeError::InvalidRequest(error.to_string()),
-                BlameError::DeriveError(e) => MononokeError::from(e),
-                _ => MononokeError::from(Error::from(error)),
-            })
+            .map_err(map_blame_error)
             .compat()
             .await
     }
 
+    /// Returns blame for the file at this path, with lines introduced by the
+    /// `ignored` changesets attributed to the lines they replaced.  If
+    /// `use_ignore_file` is set, the changesets listed in the repo's blame
+    /// ignore file are ignored too.
+    pub async fn blame_ignoring(
+        &self,
+        ignored: impl IntoIterator<Item = ChangesetId>,
+        use_ignore_file: bool,
+    ) -> Result<(Bytes, Blame), MononokeError> {
+        let ctx = self.changeset.ctx().clone();
+        let repo = self.changeset.repo().blob_repo().clone();
+        let csid = self.changeset.id();
+        let mpath = self.path.as_mpath().ok_or_else(|| {
+            MononokeError::InvalidRequest(format!("Blame is not available for directory: `/`"))
+        })?;
+
+        let mut ignored: HashSet<_> = ignored.into_iter().collect();
+        if use_ignore_file {
+            ignored.extend(self.changeset.blame_ignore_revs().await?);
+        }
+
+        fetch_blame_ignoring(ctx, repo, csid, mpath.clone(), ignored)
+            .await
+            .map_err(map_blame_error)
+    }
+
     /// Returns a list of `ChangesetContext` for the file at this path that represents
     /// a history of the path.
     pub async fn history(
@@ -349,6 +425,16 @@ pub enum UnifiedDiffMode {
     OmitContent,
 }
 
+fn map_blame_error(error: BlameError) -> MononokeError {
+    match error {
+        BlameError::NoSuchPath(_) | BlameError::IsDirectory(_) | BlameError::Rejected(_) => {
+            MononokeError::InvalidRequest(error.to_string())
+        }
+        BlameError::DeriveError(e) => MononokeError::from(e),
+        _ => MononokeError::from(Error::from(error)),
+    }
+}
+
 /// Renders the diff (in the git diff format) against some other path.
 /// Provided with copy_info will render the diff as copy or move as requested.
 /// (does not do the copy-tracking on its own)
@@ -7,6 +7,9 @@
 
 use blobstore::LoadableError;
 use derived_data::DeriveError;
+use hooks::HookRejectionInfo;
+use mononoke_types::ChangesetId;
+use pushrebase::PushrebaseConflict;
 use std::backtrace::Backtrace;
 use std::convert::Infallible;
 use std::error::Error as StdError;
@@ -41,6 +44,27 @@ impl StdError for InternalError {
     }
 }
 
+/// A hook that rejected a commit.
+#[derive(Clone, Debug)]
+pub struct HookRejection {
+    pub hook_name: String,
+    pub cs_id: ChangesetId,
+    pub reason: HookRejectionInfo,
+}
+
+fn describe_hook_rejections(rejections: &[HookRejection]) -> String {
+    rejections
+        .iter()
+        .map(|rejection| {
+            format!(
+                "{} for {}: {}",
+                rejection.hook_name, rejection.cs_id, rejection.reason.long_description
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[derive(Clone, Debug, Error)]
 pub enum MononokeError {
     #[error("invalid request: {0}")]
@@ -67,6 +91,10 @@ pub enum MononokeError {
     },
     #[error("not available: {0}")]
     NotAvailable(String),
+    #[error("conflicts while pushrebasing: {0:?}")]
+    PushrebaseConflicts(Vec<PushrebaseConflict>),
+    #[error("hooks failed:\n{}", describe_hook_rejections(.0.as_slice()))]
+    HookFailure(Vec<HookRejection>),
     #[error("internal error: {0}")]
     InternalError(#[source] InternalError),
 }
@@ -9,13 +9,15 @@ use blobrepo::BlobRepo;
 use context::CoreContext;
 use futures::{compat::Stream01CompatExt, TryStream, TryStreamExt};
 use hgproto::GettreepackArgs;
-use mercurial_types::{HgFileNodeId, HgManifestId};
+use mercurial_types::{HgChangesetId, HgFileNodeId, HgManifestId};
 use mononoke_types::MPath;
 use repo_client::gettreepack_entries;
 
+use crate::changeset::ChangesetContext;
 use crate::errors::MononokeError;
 use crate::path::MononokePath;
 use crate::repo::RepoContext;
+use crate::specifiers::ChangesetSpecifier;
 
 use super::{HgFileContext, HgTreeContext};
 
@@ -44,6 +46,16 @@ impl HgRepoContext {
         &self.repo().blob_repo()
     }
 
+    /// Look up a changeset in the repo by `HgChangesetId`.
+    pub async fn changeset(
+        &self,
+        changeset_id: HgChangesetId,
+    ) -> Result<Option<ChangesetContext>, MononokeError> {
+        self.repo
+            .changeset(ChangesetSpecifier::Hg(changeset_id))
+            .await
+    }
+
     /// Look up a file in the repo by `HgFileNodeId`.
     pub async fn file(
         &self,
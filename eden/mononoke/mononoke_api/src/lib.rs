@@ -25,6 +25,7 @@ use metaconfig_parser::RepoConfigs;
 
 use crate::repo::Repo;
 
+pub mod archive;
 pub mod changeset;
 pub mod changeset_path;
 pub mod changeset_path_diff;
@@ -34,23 +35,31 @@ pub mod hg;
 pub mod path;
 pub mod repo;
 pub mod repo_write;
+pub mod revset_query;
 pub mod specifiers;
 pub mod tree;
 
 #[cfg(test)]
 mod test;
 
-pub use crate::changeset::{ChangesetContext, Generation};
+pub use crate::archive::{ArchiveChunk, ArchiveCursor, ArchiveFormat};
+pub use crate::changeset::{
+    ChangesetContext, ChangesetMutation, ContentSearchMatch, ContentSearchResult, Generation,
+};
 pub use crate::changeset_path::{
     unified_diff, ChangesetPathContext, CopyInfo, PathEntry, UnifiedDiff, UnifiedDiffMode,
 };
 pub use crate::changeset_path_diff::ChangesetPathDiffContext;
-pub use crate::errors::MononokeError;
+pub use crate::errors::{HookRejection, MononokeError};
 pub use crate::file::{FileContext, FileId, FileMetadata, FileType};
 pub use crate::path::MononokePath;
 pub use crate::repo::RepoContext;
 pub use crate::repo_write::create_changeset::{CreateChange, CreateCopyInfo};
+pub use crate::repo_write::land_stack::{
+    PushrebaseChangesetPair, PushrebaseConflict, PushrebaseOutcome,
+};
 pub use crate::repo_write::RepoWriteContext;
+pub use crate::revset_query::RevsetQueryResult;
 pub use crate::specifiers::{
     ChangesetId, ChangesetIdPrefix, ChangesetPrefixSpecifier, ChangesetSpecifier,
     ChangesetSpecifierPrefixResolution, Globalrev, HgChangesetId, HgChangesetIdPrefix,
@@ -29,13 +29,16 @@ use fbinit::FacebookInit;
 use filestore::{Alias, FetchKey};
 use futures::compat::{Future01CompatExt, Stream01CompatExt};
 use futures::future::try_join_all;
+use futures::lock::Mutex as AsyncMutex;
 use futures::stream::{StreamExt, TryStreamExt};
 use futures::try_join;
 use futures_old::stream::Stream;
+use hooks::{hook_loader::load_hooks, HookManager};
+use hooks_content_stores::blobrepo_text_only_fetcher;
 use itertools::Itertools;
 use mercurial_types::Globalrev;
 #[cfg(test)]
-use metaconfig_types::{CommitSyncConfig, SourceControlServiceParams};
+use metaconfig_types::{CommitSyncConfig, HookManagerParams, SourceControlServiceParams};
 use metaconfig_types::{CommonConfig, RepoConfig};
 use mononoke_types::{
     hash::{GitSha1, Sha1, Sha256},
@@ -43,7 +46,7 @@ use mononoke_types::{
 };
 use permission_checker::{ArcPermissionChecker, MononokeIdentitySet, PermissionCheckerBuilder};
 use revset::AncestorsNodeStream;
-use scuba_ext::ScubaSampleBuilderExt;
+use scuba_ext::{ScubaSampleBuilder, ScubaSampleBuilderExt};
 use skiplist::{fetch_skiplist_index, SkiplistIndex};
 use slog::{debug, error, Logger};
 #[cfg(test)]
@@ -92,6 +95,9 @@ pub(crate) struct Repo {
     pub(crate) config: RepoConfig,
     pub(crate) repo_permission_checker: ArcPermissionChecker,
     pub(crate) service_permission_checker: ArcPermissionChecker,
+    // Hooks are only needed to land stacks, so the hook manager is built
+    // on first use.
+    pub(crate) hook_manager: AsyncMutex<Option<Arc<HookManager>>>,
 }
 
 #[derive(Clone)]
@@ -217,6 +223,7 @@ impl Repo {
             config,
             repo_permission_checker,
             service_permission_checker,
+            hook_manager: AsyncMutex::new(None),
         })
     }
 
@@ -263,6 +270,9 @@ impl Repo {
                 permit_writes: true,
                 ..Default::default()
             },
+            hook_manager_params: Some(HookManagerParams {
+                disable_acl_checker: true,
+            }),
             ..Default::default()
         };
         let warm_bookmarks_cache = Arc::new(
@@ -283,9 +293,50 @@ impl Repo {
             service_permission_checker: ArcPermissionChecker::from(
                 PermissionCheckerBuilder::always_allow(),
             ),
+            hook_manager: AsyncMutex::new(None),
         })
     }
 
+    #[cfg(test)]
+    /// Use the given hook manager instead of building one from the config.
+    pub(crate) fn with_hook_manager(mut self, hook_manager: HookManager) -> Self {
+        self.hook_manager = AsyncMutex::new(Some(Arc::new(hook_manager)));
+        self
+    }
+
+    /// The hook manager for this repo, built with the hooks from its config
+    /// the first time it is needed.
+    pub(crate) async fn hook_manager(&self, fb: FacebookInit) -> Result<Arc<HookManager>, Error> {
+        let mut hook_manager = self.hook_manager.lock().await;
+        if let Some(hook_manager) = hook_manager.as_ref() {
+            return Ok(hook_manager.clone());
+        }
+
+        let mut hooks_scuba =
+            ScubaSampleBuilder::with_opt_table(fb, self.config.scuba_table_hooks.clone());
+        hooks_scuba.add("repo", self.name.clone());
+        if let Some(hooks_scuba_local_path) = &self.config.scuba_local_path_hooks {
+            hooks_scuba = hooks_scuba.with_log_file(hooks_scuba_local_path)?;
+        }
+
+        let mut new_hook_manager = HookManager::new(
+            fb,
+            blobrepo_text_only_fetcher(self.blob_repo.clone(), self.config.hook_max_file_size),
+            self.config.hook_manager_params.clone().unwrap_or_default(),
+            hooks_scuba,
+        )
+        .await?;
+        load_hooks(
+            fb,
+            &mut new_hook_manager,
+            self.config.clone(),
+            &HashSet::new(),
+        )?;
+        let new_hook_manager = Arc::new(new_hook_manager);
+        *hook_manager = Some(new_hook_manager.clone());
+        Ok(new_hook_manager)
+    }
+
     pub async fn report_monitoring_stats(&self, ctx: &CoreContext) -> Result<(), MononokeError> {
         match self.config.source_control_service_monitoring.as_ref() {
             None => Ok(()),
@@ -586,6 +637,10 @@ impl RepoContext {
         &self.repo.skiplist_index
     }
 
+    pub(crate) async fn hook_manager(&self) -> Result<Arc<HookManager>, MononokeError> {
+        Ok(self.repo.hook_manager(self.ctx.fb).await?)
+    }
+
     /// The commit sync mapping for the referenced repository
     pub(crate) fn synced_commit_mapping(&self) -> &Arc<dyn SyncedCommitMapping> {
         &self.repo.synced_commit_mapping
@@ -754,6 +809,21 @@ impl RepoContext {
         Ok(mapping)
     }
 
+    /// Get the changeset ids of multiple Mercurial changesets.
+    ///
+    /// Mercurial changesets that are not in the repo are omitted.
+    pub async fn many_changeset_ids_from_hg(
+        &self,
+        hg_changesets: Vec<HgChangesetId>,
+    ) -> Result<Vec<(HgChangesetId, ChangesetId)>, MononokeError> {
+        let mapping = self
+            .blob_repo()
+            .get_hg_bonsai_mapping(self.ctx.clone(), hg_changesets)
+            .compat()
+            .await?;
+        Ok(mapping)
+    }
+
     /// Similar to changeset_hg_ids, but returning Git-SHA1s.
     pub async fn changeset_git_sha1s(
         &self,
@@ -11,6 +11,7 @@ use crate::errors::MononokeError;
 use crate::repo::RepoContext;
 
 pub mod create_changeset;
+pub mod land_stack;
 pub mod move_bookmark;
 
 /// Describes the permissions model that is being used to determine if a write is
@@ -0,0 +1,143 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use blobstore::Loadable;
+use bookmarks::BookmarkName;
+use futures::compat::Stream01CompatExt;
+use futures::stream::TryStreamExt;
+use metaconfig_types::BookmarkAttrs;
+use mononoke_types::{BonsaiChangeset, ChangesetId};
+use pushrebase::{do_pushrebase_bonsai, OntoBookmarkParams, PushrebaseError};
+use revset::DifferenceOfUnionsOfAncestorsNodeStream;
+use unbundle::get_pushrebase_hooks;
+
+use crate::errors::{HookRejection, MononokeError};
+use crate::repo_write::RepoWriteContext;
+
+pub use pushrebase::{PushrebaseChangesetPair, PushrebaseConflict};
+
+/// Number of changesets in the stack to load concurrently.
+const LOAD_CONCURRENCY: usize = 100;
+
+/// The result of landing a stack.
+#[derive(Clone, Debug)]
+pub struct PushrebaseOutcome {
+    /// The new head of the bookmark after the stack was landed.
+    pub head: ChangesetId,
+    /// The number of times pushrebase had to retry because the bookmark
+    /// moved while it was rebasing.
+    pub retry_num: usize,
+    /// The mapping from the commits in the stack to the rebased commits.
+    pub rebased_changesets: Vec<PushrebaseChangesetPair>,
+}
+
+impl RepoWriteContext {
+    /// Land a stack of commits onto a bookmark using pushrebase.
+    ///
+    /// The stack is the commits that are ancestors of `head` but not
+    /// ancestors of `base`.  Hooks configured for the bookmark are run on
+    /// the stack before it is rebased.
+    pub async fn land_stack(
+        &self,
+        bookmark: impl AsRef<str>,
+        head: ChangesetId,
+        base: ChangesetId,
+    ) -> Result<PushrebaseOutcome, MononokeError> {
+        let bookmark = bookmark.as_ref();
+        self.check_method_permitted("land_stack")?;
+        self.check_bookmark_modification_permitted(bookmark)?;
+
+        let bookmark = BookmarkName::new(bookmark)?;
+        let bookmark_attrs = BookmarkAttrs::new(self.config().bookmarks.clone());
+
+        // Find the commits in the stack.
+        let changesets: HashSet<BonsaiChangeset> =
+            DifferenceOfUnionsOfAncestorsNodeStream::new_with_excludes(
+                self.ctx().clone(),
+                &self.blob_repo().get_changeset_fetcher(),
+                Arc::new(self.skiplist_index().clone()),
+                vec![head],
+                vec![base],
+            )
+            .compat()
+            .map_err(MononokeError::from)
+            .map_ok(|cs_id| async move {
+                cs_id
+                    .load(self.ctx().clone(), self.blob_repo().blobstore())
+                    .await
+                    .map_err(MononokeError::from)
+            })
+            .try_buffer_unordered(LOAD_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        if changesets.is_empty() {
+            return Err(MononokeError::InvalidRequest(format!(
+                "no commits to land: {} is an ancestor of {}",
+                head, base
+            )));
+        }
+
+        let pushrebase_params = &self.config().pushrebase;
+        if pushrebase_params.block_merges && changesets.iter().any(BonsaiChangeset::is_merge) {
+            return Err(MononokeError::InvalidRequest(String::from(
+                "pushrebase is blocked because the stack contains a merge commit",
+            )));
+        }
+
+        // Run the hooks for the bookmark on the stack.
+        let rejections: Vec<_> = self
+            .hook_manager()
+            .await?
+            .run_hooks_for_bookmark(self.ctx(), changesets.iter(), &bookmark, None)
+            .await?
+            .into_iter()
+            .filter_map(|outcome| {
+                outcome
+                    .into_rejection()
+                    .map(|(hook_name, cs_id, reason)| HookRejection {
+                        hook_name,
+                        cs_id,
+                        reason,
+                    })
+            })
+            .collect();
+        if !rejections.is_empty() {
+            return Err(MononokeError::HookFailure(rejections));
+        }
+
+        // Pushrebase the stack onto the bookmark.
+        let mut flags = pushrebase_params.flags.clone();
+        if let Some(rewritedates) = bookmark_attrs.should_rewrite_dates(&bookmark) {
+            flags.rewritedates = rewritedates;
+        }
+        let pushrebase_hooks = get_pushrebase_hooks(self.blob_repo(), pushrebase_params);
+        let result = do_pushrebase_bonsai(
+            self.ctx(),
+            self.blob_repo(),
+            &flags,
+            &OntoBookmarkParams::new(bookmark),
+            &changesets,
+            &None,
+            pushrebase_hooks.as_slice(),
+        )
+        .await
+        .map_err(|e| match e {
+            PushrebaseError::Conflicts(conflicts) => MononokeError::PushrebaseConflicts(conflicts),
+            e => MononokeError::from(anyhow::Error::from(e)),
+        })?;
+
+        Ok(PushrebaseOutcome {
+            head: result.head,
+            retry_num: result.retry_num.0,
+            rebased_changesets: result.rebased_changesets,
+        })
+    }
+}
@@ -0,0 +1,586 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Revset queries select commits using a small expression language, e.g.
+//! `ancestors(master) - ancestors(release) & author("alice")`.
+//!
+//! Expressions are compiled into the node streams from the `revset` crate,
+//! so results are produced lazily, in descending generation number order.
+//! Predicates such as `author()` can't be enumerated on their own: they
+//! filter the commits of a set they are intersected with.
+
+use std::convert::TryFrom;
+use std::mem;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::Error;
+use cloned::cloned;
+use futures::compat::{Future01CompatExt, Stream01CompatExt};
+use futures::future::{self, BoxFuture, FutureExt, TryFutureExt};
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use futures_old::stream as stream_old;
+use futures_old::Stream as StreamOld;
+use mononoke_types::Generation;
+use reachabilityindex::{LeastCommonAncestorsHint, NodeFrontier};
+use regex::Regex;
+use revset::{
+    BonsaiNodeStream, DifferenceOfUnionsOfAncestorsNodeStream, IntersectNodeStream,
+    RangeNodeStream, SetDifferenceNodeStream, UnionNodeStream,
+};
+
+use crate::changeset::ChangesetContext;
+use crate::errors::MononokeError;
+use crate::path::MononokePath;
+use crate::repo::RepoContext;
+use crate::specifiers::{
+    ChangesetId, ChangesetIdPrefix, ChangesetPrefixSpecifier, ChangesetSpecifierPrefixResolution,
+    HgChangesetIdPrefix,
+};
+
+use self::parser::Expr;
+
+mod parser;
+
+/// The maximum number of commits that the endpoints of `ancestors()` and
+/// `x::y` may resolve to.
+const MAX_ENDPOINTS: usize = 100;
+
+/// How many commits are checked against a predicate concurrently.
+const PREDICATE_CONCURRENCY: usize = 100;
+
+/// A page of the results of a revset query.
+pub struct RevsetQueryResult {
+    pub changesets: Vec<ChangesetContext>,
+    /// If there are more results, a cursor that can be passed as `after` to
+    /// get the next page.
+    pub continue_after: Option<String>,
+}
+
+/// A condition that commits can be filtered by.
+#[derive(Clone)]
+enum Predicate {
+    Author(Regex),
+    File(MononokePath),
+    Draft,
+    Not(Box<Predicate>),
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+}
+
+impl Predicate {
+    fn matches<'a>(
+        &'a self,
+        changeset: &'a ChangesetContext,
+    ) -> BoxFuture<'a, Result<bool, MononokeError>> {
+        async move {
+            match self {
+                Predicate::Author(regex) => Ok(regex.is_match(&changeset.author().await?)),
+                Predicate::File(path) => {
+                    let file_changes = changeset.file_changes().await?;
+                    Ok(match path.as_mpath() {
+                        Some(prefix) => file_changes.keys().any(|path| prefix.is_prefix_of(path)),
+                        None => !file_changes.is_empty(),
+                    })
+                }
+                Predicate::Draft => {
+                    let public = changeset
+                        .repo()
+                        .blob_repo()
+                        .get_phases()
+                        .get_public(changeset.ctx().clone(), vec![changeset.id()], false)
+                        .compat()
+                        .await?;
+                    Ok(!public.contains(&changeset.id()))
+                }
+                Predicate::Not(predicate) => Ok(!predicate.matches(changeset).await?),
+                Predicate::And(left, right) => {
+                    Ok(left.matches(changeset).await? && right.matches(changeset).await?)
+                }
+                Predicate::Or(left, right) => {
+                    Ok(left.matches(changeset).await? || right.matches(changeset).await?)
+                }
+            }
+        }
+        .boxed()
+    }
+}
+
+/// The result of compiling an expression.
+enum Compiled {
+    Set(BonsaiNodeStream),
+    Predicate(Predicate),
+}
+
+fn invalid(reason: impl Into<String>) -> MononokeError {
+    MononokeError::InvalidRequest(format!("invalid revset: {}", reason.into()))
+}
+
+fn check_arg_count(name: &str, args: &[Expr], count: usize) -> Result<(), MononokeError> {
+    if args.len() != count {
+        return Err(invalid(format!(
+            "{}() takes {} argument(s), {} given",
+            name,
+            count,
+            args.len()
+        )));
+    }
+    Ok(())
+}
+
+fn string_arg(name: &str, arg: Expr) -> Result<String, MononokeError> {
+    match arg {
+        Expr::String(value) | Expr::Symbol(value) => Ok(value),
+        _ => Err(invalid(format!("{}() expects a string argument", name))),
+    }
+}
+
+struct Compiler<'a> {
+    repo: &'a RepoContext,
+    /// When continuing after a cursor, only commits up to the generation of
+    /// the cursor are needed, so traversals can start from there.
+    max_generation: Option<Generation>,
+}
+
+impl<'a> Compiler<'a> {
+    fn compile(&self, expr: Expr) -> BoxFuture<'_, Result<Compiled, MononokeError>> {
+        async move {
+            let compiled = match expr {
+                Expr::Symbol(symbol) | Expr::String(symbol) => {
+                    let cs_id = self.resolve_symbol(&symbol).await?;
+                    Compiled::Set(Box::new(stream_old::once::<_, Error>(Ok(cs_id))))
+                }
+                Expr::Ancestors(heads) => Compiled::Set(self.ancestors(*heads).await?),
+                Expr::Range(start, end) => Compiled::Set(self.range(*start, *end).await?),
+                Expr::Union(left, right) => {
+                    match (self.compile(*left).await?, self.compile(*right).await?) {
+                        (Compiled::Set(left), Compiled::Set(right)) => {
+                            Compiled::Set(Box::new(UnionNodeStream::new(
+                                self.repo.ctx().clone(),
+                                &self.repo.blob_repo().get_changeset_fetcher(),
+                                vec![left, right],
+                            )))
+                        }
+                        (Compiled::Predicate(left), Compiled::Predicate(right)) => {
+                            Compiled::Predicate(Predicate::Or(Box::new(left), Box::new(right)))
+                        }
+                        _ => {
+                            return Err(invalid(
+                                "a set of commits can't be combined with a predicate using '|'",
+                            ))
+                        }
+                    }
+                }
+                Expr::Intersection(left, right) => {
+                    match (self.compile(*left).await?, self.compile(*right).await?) {
+                        (Compiled::Set(left), Compiled::Set(right)) => {
+                            Compiled::Set(Box::new(IntersectNodeStream::new(
+                                self.repo.ctx().clone(),
+                                &self.repo.blob_repo().get_changeset_fetcher(),
+                                vec![left, right],
+                            )))
+                        }
+                        (Compiled::Set(set), Compiled::Predicate(predicate))
+                        | (Compiled::Predicate(predicate), Compiled::Set(set)) => {
+                            Compiled::Set(self.filter(set, predicate))
+                        }
+                        (Compiled::Predicate(left), Compiled::Predicate(right)) => {
+                            Compiled::Predicate(Predicate::And(Box::new(left), Box::new(right)))
+                        }
+                    }
+                }
+                Expr::Difference(left, right) => {
+                    match (self.compile(*left).await?, self.compile(*right).await?) {
+                        (Compiled::Set(left), Compiled::Set(right)) => {
+                            Compiled::Set(Box::new(SetDifferenceNodeStream::new(
+                                self.repo.ctx().clone(),
+                                &self.repo.blob_repo().get_changeset_fetcher(),
+                                left,
+                                right,
+                            )))
+                        }
+                        (Compiled::Set(set), Compiled::Predicate(predicate)) => {
+                            Compiled::Set(self.filter(set, Predicate::Not(Box::new(predicate))))
+                        }
+                        (Compiled::Predicate(left), Compiled::Predicate(right)) => {
+                            Compiled::Predicate(Predicate::And(
+                                Box::new(left),
+                                Box::new(Predicate::Not(Box::new(right))),
+                            ))
+                        }
+                        (Compiled::Predicate(_), Compiled::Set(_)) => {
+                            return Err(invalid(
+                                "a set of commits can't be removed from a predicate",
+                            ))
+                        }
+                    }
+                }
+                Expr::Func(name, args) => self.compile_func(name, args).await?,
+            };
+            Ok(compiled)
+        }
+        .boxed()
+    }
+
+    async fn compile_func(&self, name: String, args: Vec<Expr>) -> Result<Compiled, MononokeError> {
+        let mut args = args.into_iter();
+        let compiled = match name.as_str() {
+            "ancestors" => {
+                check_arg_count(&name, args.as_slice(), 1)?;
+                Compiled::Set(self.ancestors(args.next().unwrap()).await?)
+            }
+            "bookmark" => {
+                check_arg_count(&name, args.as_slice(), 1)?;
+                let bookmark = string_arg(&name, args.next().unwrap())?;
+                let changeset = self
+                    .repo
+                    .resolve_bookmark(&bookmark)
+                    .await?
+                    .ok_or_else(|| invalid(format!("bookmark '{}' does not exist", bookmark)))?;
+                Compiled::Set(Box::new(stream_old::once::<_, Error>(Ok(changeset.id()))))
+            }
+            "draft" => {
+                check_arg_count(&name, args.as_slice(), 0)?;
+                Compiled::Predicate(Predicate::Draft)
+            }
+            "public" => {
+                check_arg_count(&name, args.as_slice(), 0)?;
+                Compiled::Predicate(Predicate::Not(Box::new(Predicate::Draft)))
+            }
+            "author" => {
+                check_arg_count(&name, args.as_slice(), 1)?;
+                let pattern = string_arg(&name, args.next().unwrap())?;
+                let regex = Regex::new(&pattern)
+                    .map_err(|e| invalid(format!("invalid author pattern: {}", e)))?;
+                Compiled::Predicate(Predicate::Author(regex))
+            }
+            "file" => {
+                check_arg_count(&name, args.as_slice(), 1)?;
+                let path = string_arg(&name, args.next().unwrap())?;
+                Compiled::Predicate(Predicate::File(MononokePath::try_from(path.as_str())?))
+            }
+            "limit" => {
+                check_arg_count(&name, args.as_slice(), 2)?;
+                // The first commits of the whole set are wanted, so it must
+                // not start from the cursor.
+                let unbounded = self.unbounded();
+                let set = unbounded.set(args.next().unwrap()).await?;
+                let limit = string_arg(&name, args.next().unwrap())?;
+                let limit = u64::from_str(&limit)
+                    .map_err(|_| invalid(format!("invalid limit '{}'", limit)))?;
+                Compiled::Set(Box::new(set.take(limit)))
+            }
+            _ => return Err(invalid(format!("unknown function {}()", name))),
+        };
+        Ok(compiled)
+    }
+
+    fn unbounded(&self) -> Compiler<'a> {
+        Compiler {
+            repo: self.repo,
+            max_generation: None,
+        }
+    }
+
+    /// Compile an expression that must be a set of commits.
+    async fn set(&self, expr: Expr) -> Result<BonsaiNodeStream, MononokeError> {
+        match self.compile(expr).await? {
+            Compiled::Set(set) => Ok(set),
+            Compiled::Predicate(_) => Err(invalid(
+                "predicates such as author() only filter a set of commits, \
+                 and must be intersected with one, e.g. 'ancestors(master) & author(alice)'",
+            )),
+        }
+    }
+
+    /// Compile an expression which is used as an endpoint of a traversal.
+    async fn endpoints(&self, expr: Expr) -> Result<Vec<ChangesetId>, MononokeError> {
+        // Endpoints above the cursor still have ancestors below it, so they
+        // are found without the cursor, and the traversal is seeked instead.
+        let unbounded = self.unbounded();
+        let endpoints: Vec<_> = unbounded
+            .set(expr)
+            .await?
+            .take(MAX_ENDPOINTS as u64 + 1)
+            .collect()
+            .compat()
+            .await?;
+        if endpoints.len() > MAX_ENDPOINTS {
+            return Err(invalid(format!(
+                "the endpoints of ancestors() and '::' can't be more than {} commits",
+                MAX_ENDPOINTS
+            )));
+        }
+        Ok(endpoints)
+    }
+
+    /// Move the heads of a traversal down to the generation of the cursor,
+    /// so that later pages don't walk the commits of earlier pages again.
+    async fn seek(&self, heads: Vec<ChangesetId>) -> Result<Vec<ChangesetId>, MononokeError> {
+        let max_generation = match self.max_generation {
+            Some(max_generation) => max_generation,
+            None => return Ok(heads),
+        };
+        let ctx = self.repo.ctx();
+        let changeset_fetcher = self.repo.blob_repo().get_changeset_fetcher();
+        let frontier: NodeFrontier = future::try_join_all(heads.into_iter().map(|cs_id| {
+            changeset_fetcher
+                .get_generation_number(ctx.clone(), cs_id)
+                .compat()
+                .map_ok(move |generation| (cs_id, generation))
+        }))
+        .await?
+        .into_iter()
+        .collect();
+        let frontier = self
+            .repo
+            .skiplist_index()
+            .lca_hint(ctx, &changeset_fetcher, frontier, max_generation)
+            .await?;
+        Ok(frontier
+            .into_iter()
+            .flat_map(|(_, cs_ids)| cs_ids)
+            .collect())
+    }
+
+    async fn ancestors(&self, heads: Expr) -> Result<BonsaiNodeStream, MononokeError> {
+        let heads = self.seek(self.endpoints(heads).await?).await?;
+        Ok(DifferenceOfUnionsOfAncestorsNodeStream::new_union(
+            self.repo.ctx().clone(),
+            &self.repo.blob_repo().get_changeset_fetcher(),
+            Arc::new(self.repo.skiplist_index().clone()),
+            heads,
+        ))
+    }
+
+    async fn range(&self, start: Expr, end: Expr) -> Result<BonsaiNodeStream, MononokeError> {
+        let (starts, ends) = future::try_join(self.endpoints(start), self.endpoints(end)).await?;
+        if starts.len() * ends.len() > MAX_ENDPOINTS {
+            return Err(invalid(format!(
+                "the endpoints of '::' can't be more than {} pairs of commits",
+                MAX_ENDPOINTS
+            )));
+        }
+        // Seeking may widen the ends, in which case walking from the
+        // original ends is still correct, just slower.
+        let seeked = self.seek(ends.clone()).await?;
+        let ends = if starts.len() * seeked.len() <= MAX_ENDPOINTS {
+            seeked
+        } else {
+            ends
+        };
+        let ctx = self.repo.ctx();
+        let changeset_fetcher = self.repo.blob_repo().get_changeset_fetcher();
+        let mut ranges: Vec<BonsaiNodeStream> = Vec::new();
+        for start in starts.iter() {
+            for end in ends.iter() {
+                ranges.push(Box::new(RangeNodeStream::new(
+                    ctx.clone(),
+                    changeset_fetcher.clone(),
+                    *start,
+                    *end,
+                )));
+            }
+        }
+        Ok(Box::new(UnionNodeStream::new(
+            ctx.clone(),
+            &changeset_fetcher,
+            ranges,
+        )))
+    }
+
+    /// Filter a set of commits by a predicate, keeping the order of the set.
+    fn filter(&self, set: BonsaiNodeStream, predicate: Predicate) -> BonsaiNodeStream {
+        let repo = self.repo.clone();
+        let predicate = Arc::new(predicate);
+        let filtered = set
+            .compat()
+            .map(move |cs_id| {
+                cloned!(repo, predicate);
+                async move {
+                    let cs_id = cs_id?;
+                    let changeset = ChangesetContext::new(repo, cs_id);
+                    let matches = predicate.matches(&changeset).await?;
+                    Ok::<_, Error>((cs_id, matches))
+                }
+            })
+            .buffered(PREDICATE_CONCURRENCY)
+            .try_filter_map(|(cs_id, matches)| {
+                future::ok(if matches { Some(cs_id) } else { None })
+            });
+        Box::new(filtered.boxed().compat())
+    }
+
+    /// Resolve a symbol, which is either a bookmark name, or a Mercurial or
+    /// bonsai commit hash or unique prefix of one.
+    async fn resolve_symbol(&self, symbol: &str) -> Result<ChangesetId, MononokeError> {
+        if let Some(changeset) = self.repo.resolve_bookmark(symbol).await? {
+            return Ok(changeset.id());
+        }
+
+        let mut prefixes: Vec<ChangesetPrefixSpecifier> = Vec::new();
+        if let Ok(prefix) = HgChangesetIdPrefix::from_str(symbol) {
+            prefixes.push(prefix.into());
+        }
+        if let Ok(prefix) = ChangesetIdPrefix::from_str(symbol) {
+            prefixes.push(prefix.into());
+        }
+        for prefix in prefixes {
+            match self.repo.resolve_changeset_id_prefix(prefix).await? {
+                ChangesetSpecifierPrefixResolution::NoMatch => continue,
+                ChangesetSpecifierPrefixResolution::Single(specifier) => {
+                    if let Some(cs_id) = self.repo.resolve_specifier(specifier).await? {
+                        return Ok(cs_id);
+                    }
+                }
+                ChangesetSpecifierPrefixResolution::Multiple(_)
+                | ChangesetSpecifierPrefixResolution::TooMany(_) => {
+                    return Err(invalid(format!(
+                        "'{}' is an ambiguous commit prefix",
+                        symbol
+                    )));
+                }
+            }
+        }
+        Err(invalid(format!(
+            "'{}' is not a bookmark or a commit in this repo",
+            symbol
+        )))
+    }
+}
+
+/// Sort the commits with the same generation number by their ids, so that
+/// the order of the results is stable between queries, which the
+/// pagination cursor relies on.
+fn in_stable_order(
+    items: impl Stream<Item = Result<(Generation, ChangesetId), Error>> + Send + 'static,
+) -> impl Stream<Item = Result<(Generation, ChangesetId), Error>> + Send + 'static {
+    items
+        .map_ok(Some)
+        .chain(stream::once(future::ok(None)))
+        .scan(Vec::new(), |batch, item| {
+            let output = match item {
+                Ok(Some(item)) => {
+                    if batch.last().map_or(false, |(gen, _)| *gen != item.0) {
+                        let mut done = mem::replace(batch, vec![item]);
+                        done.sort();
+                        Ok(done)
+                    } else {
+                        batch.push(item);
+                        Ok(Vec::new())
+                    }
+                }
+                Ok(None) => {
+                    let mut done = mem::take(batch);
+                    done.sort();
+                    Ok(done)
+                }
+                Err(e) => Err(e),
+            };
+            future::ready(Some(output))
+        })
+        .map_ok(|batch| stream::iter(batch.into_iter().map(Ok)))
+        .try_flatten()
+}
+
+fn format_cursor(generation: Generation, cs_id: ChangesetId) -> String {
+    format!("{}:{}", generation.value(), cs_id)
+}
+
+fn parse_cursor(cursor: &str) -> Result<(Generation, ChangesetId), MononokeError> {
+    let invalid_cursor = || MononokeError::InvalidRequest(format!("invalid cursor '{}'", cursor));
+    let mut parts = cursor.splitn(2, ':');
+    let generation = parts
+        .next()
+        .and_then(|generation| u64::from_str(generation).ok())
+        .ok_or_else(invalid_cursor)?;
+    let cs_id = parts
+        .next()
+        .and_then(|cs_id| ChangesetId::from_str(cs_id).ok())
+        .ok_or_else(invalid_cursor)?;
+    Ok((Generation::new(generation), cs_id))
+}
+
+impl RepoContext {
+    /// Find the commits that match a revset expression.
+    ///
+    /// Supported expressions are bookmark names and commit hashes,
+    /// `x::y`, `::y`, `x | y`, `x & y`, `x - y`, and the functions
+    /// `ancestors(x)`, `bookmark(name)`, `limit(x, n)`, and the predicates
+    /// `draft()`, `public()`, `author(regex)` and `file(path)`.
+    ///
+    /// Commits are returned in descending generation number order, at most
+    /// `limit` at a time.  To get the next page of results, pass the
+    /// `continue_after` cursor of the previous page as `after`.
+    pub async fn query_revset(
+        &self,
+        query: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<RevsetQueryResult, MononokeError> {
+        let expr = parser::parse(query)?;
+        let after = after.map(parse_cursor).transpose()?;
+        let compiler = Compiler {
+            repo: self,
+            max_generation: after.map(|(generation, _)| generation),
+        };
+        let set = compiler.set(expr).await?;
+
+        let ctx = self.ctx().clone();
+        let changeset_fetcher = self.blob_repo().get_changeset_fetcher();
+        let with_generations = set
+            .compat()
+            .map(move |cs_id| {
+                cloned!(ctx, changeset_fetcher);
+                async move {
+                    let cs_id = cs_id?;
+                    let generation = changeset_fetcher
+                        .get_generation_number(ctx, cs_id)
+                        .compat()
+                        .await?;
+                    Ok::<_, Error>((generation, cs_id))
+                }
+            })
+            .buffered(PREDICATE_CONCURRENCY);
+
+        // Results are ordered by descending generation, then ascending id.
+        // The set starts at the generation of the cursor, so only the
+        // commits of that generation which were already returned are
+        // skipped here.
+        let mut results: Vec<_> = in_stable_order(with_generations)
+            .try_skip_while(move |(generation, cs_id)| {
+                let seen = match after {
+                    Some((after_generation, after_cs_id)) => {
+                        *generation > after_generation
+                            || (*generation == after_generation && *cs_id <= after_cs_id)
+                    }
+                    None => false,
+                };
+                future::ok(seen)
+            })
+            .take(limit + 1)
+            .try_collect()
+            .await?;
+
+        let continue_after = if results.len() > limit {
+            results.truncate(limit);
+            results
+                .last()
+                .map(|(generation, cs_id)| format_cursor(*generation, *cs_id))
+        } else {
+            None
+        };
+        let changesets = results
+            .into_iter()
+            .map(|(_, cs_id)| ChangesetContext::new(self.clone(), cs_id))
+            .collect();
+        Ok(RevsetQueryResult {
+            changesets,
+            continue_after,
+        })
+    }
+}
@@ -0,0 +1,403 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Parser for revset expressions.
+//!
+//! The grammar, from lowest to highest precedence, is:
+//!
+//! ```text
+//! expr     := and_expr (('|' | '+' | 'or') and_expr)*
+//! and_expr := range (('&' | 'and' | '-') range)*
+//! range    := primary ['::' primary] | '::' primary
+//! primary  := symbol | string | symbol '(' [expr (',' expr)*] ')' | '(' expr ')'
+//! ```
+//!
+//! As in Mercurial, a `-` between two symbol characters is part of the
+//! symbol, so `release-1.0` is a single symbol. Use spaces around `-` for a
+//! difference.
+
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::errors::MononokeError;
+
+/// The maximum length of a revset, in bytes.
+const MAX_QUERY_LENGTH: usize = 4096;
+
+/// The maximum nesting depth of a revset. Each operator, function call and
+/// pair of parentheses is one level. Parsing and compiling the expression
+/// recurse to this depth.
+const MAX_DEPTH: usize = 100;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Expr {
+    /// A bookmark name or a commit hash (or hash prefix).
+    Symbol(String),
+    /// A quoted string.  These are only valid as function arguments.
+    String(String),
+    /// `x::y`, the commits that are descendants of `x` and ancestors of `y`.
+    Range(Box<Expr>, Box<Expr>),
+    /// `::y`, the ancestors of `y`.
+    Ancestors(Box<Expr>),
+    Union(Box<Expr>, Box<Expr>),
+    Intersection(Box<Expr>, Box<Expr>),
+    Difference(Box<Expr>, Box<Expr>),
+    /// A function call, e.g. `author("alice")`.
+    Func(String, Vec<Expr>),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Symbol(String),
+    String(String),
+    LParen,
+    RParen,
+    Comma,
+    DoubleColon,
+    Or,
+    And,
+    Minus,
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.' || c == '/' || c == '@'
+}
+
+fn invalid(reason: impl Into<String>) -> MononokeError {
+    MononokeError::InvalidRequest(format!("invalid revset: {}", reason.into()))
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, MononokeError> {
+    let mut tokens = Vec::new();
+    let mut chars: Peekable<CharIndices> = input.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            '|' | '+' => Token::Or,
+            '&' => Token::And,
+            '-' => Token::Minus,
+            ':' => match chars.next() {
+                Some((_, ':')) => Token::DoubleColon,
+                _ => return Err(invalid(format!("expected '::' at position {}", pos))),
+            },
+            '"' | '\'' => {
+                let quote = c;
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, escaped)) => value.push(escaped),
+                            None => break,
+                        },
+                        Some((_, c)) if c == quote => {
+                            tokens.push(Token::String(value));
+                            break;
+                        }
+                        Some((_, c)) => value.push(c),
+                        None => {
+                            return Err(invalid(format!(
+                                "unterminated string starting at position {}",
+                                pos
+                            )))
+                        }
+                    }
+                }
+                continue;
+            }
+            c if is_symbol_char(c) => {
+                let mut end = pos + c.len_utf8();
+                while let Some(&(next_pos, next)) = chars.peek() {
+                    let inner_minus = next == '-'
+                        && input[next_pos + 1..]
+                            .chars()
+                            .next()
+                            .map_or(false, is_symbol_char);
+                    if !is_symbol_char(next) && !inner_minus {
+                        break;
+                    }
+                    chars.next();
+                    end = next_pos + next.len_utf8();
+                }
+                match &input[pos..end] {
+                    "or" => Token::Or,
+                    "and" => Token::And,
+                    symbol => Token::Symbol(symbol.to_string()),
+                }
+            }
+            c => {
+                return Err(invalid(format!(
+                    "unexpected character '{}' at position {}",
+                    c, pos
+                )))
+            }
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    /// Enter a nested expression, failing if it is nested too deeply.
+    fn nest(&mut self) -> Result<(), MononokeError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(invalid(format!(
+                "expression is nested more than {} levels deep",
+                MAX_DEPTH
+            )));
+        }
+        Ok(())
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), MononokeError> {
+        match self.next() {
+            Some(ref token) if *token == expected => Ok(()),
+            Some(token) => Err(invalid(format!(
+                "expected {:?}, found {:?}",
+                expected, token
+            ))),
+            None => Err(invalid(format!(
+                "expected {:?}, found end of input",
+                expected
+            ))),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, MononokeError> {
+        let depth = self.depth;
+        let mut expr = self.and_expr()?;
+        while let Some(Token::Or) = self.peek() {
+            self.next();
+            self.nest()?;
+            expr = Expr::Union(Box::new(expr), Box::new(self.and_expr()?));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, MononokeError> {
+        let depth = self.depth;
+        let mut expr = self.range()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                    self.nest()?;
+                    expr = Expr::Intersection(Box::new(expr), Box::new(self.range()?));
+                }
+                Some(Token::Minus) => {
+                    self.next();
+                    self.nest()?;
+                    expr = Expr::Difference(Box::new(expr), Box::new(self.range()?));
+                }
+                _ => {
+                    self.depth = depth;
+                    return Ok(expr);
+                }
+            }
+        }
+    }
+
+    fn range(&mut self) -> Result<Expr, MononokeError> {
+        let depth = self.depth;
+        if let Some(Token::DoubleColon) = self.peek() {
+            self.next();
+            self.nest()?;
+            let expr = Expr::Ancestors(Box::new(self.primary()?));
+            self.depth = depth;
+            return Ok(expr);
+        }
+        let start = self.primary()?;
+        if let Some(Token::DoubleColon) = self.peek() {
+            self.next();
+            self.nest()?;
+            match self.peek() {
+                None | Some(Token::RParen) | Some(Token::Comma) | Some(Token::Or)
+                | Some(Token::And) | Some(Token::Minus) => {
+                    return Err(invalid("descendants ('x::') are not supported"));
+                }
+                _ => {}
+            }
+            let end = self.primary()?;
+            self.depth = depth;
+            return Ok(Expr::Range(Box::new(start), Box::new(end)));
+        }
+        Ok(start)
+    }
+
+    fn primary(&mut self) -> Result<Expr, MononokeError> {
+        let depth = self.depth;
+        match self.next() {
+            Some(Token::LParen) => {
+                self.nest()?;
+                let expr = self.expr()?;
+                self.expect(Token::RParen)?;
+                self.depth = depth;
+                Ok(expr)
+            }
+            Some(Token::String(value)) => Ok(Expr::String(value)),
+            Some(Token::Symbol(name)) => {
+                if let Some(Token::LParen) = self.peek() {
+                    self.next();
+                    self.nest()?;
+                    let mut args = Vec::new();
+                    if let Some(Token::RParen) = self.peek() {
+                        self.next();
+                    } else {
+                        loop {
+                            args.push(self.expr()?);
+                            match self.next() {
+                                Some(Token::Comma) => continue,
+                                Some(Token::RParen) => break,
+                                Some(token) => {
+                                    return Err(invalid(format!(
+                                        "expected ',' or ')' in arguments to {}, found {:?}",
+                                        name, token
+                                    )))
+                                }
+                                None => {
+                                    return Err(invalid(format!(
+                                        "unterminated arguments to {}",
+                                        name
+                                    )))
+                                }
+                            }
+                        }
+                    }
+                    self.depth = depth;
+                    Ok(Expr::Func(name, args))
+                } else {
+                    Ok(Expr::Symbol(name))
+                }
+            }
+            Some(token) => Err(invalid(format!("unexpected {:?}", token))),
+            None => Err(invalid("unexpected end of input")),
+        }
+    }
+}
+
+/// Parse a revset expression.
+pub(crate) fn parse(input: &str) -> Result<Expr, MononokeError> {
+    if input.len() > MAX_QUERY_LENGTH {
+        return Err(invalid(format!("longer than {} bytes", MAX_QUERY_LENGTH)));
+    }
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+        depth: 0,
+    };
+    let expr = parser.expr()?;
+    if let Some(token) = parser.peek() {
+        return Err(invalid(format!("unexpected {:?}", token)));
+    }
+    Ok(expr)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sym(name: &str) -> Box<Expr> {
+        Box::new(Expr::Symbol(name.to_string()))
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("master").unwrap(), *sym("master"));
+        assert_eq!(parse("a::b").unwrap(), Expr::Range(sym("a"), sym("b")));
+        assert_eq!(parse("::b").unwrap(), Expr::Ancestors(sym("b")));
+        assert_eq!(
+            parse("a | b & c").unwrap(),
+            Expr::Union(sym("a"), Box::new(Expr::Intersection(sym("b"), sym("c"))))
+        );
+        assert_eq!(
+            parse("(a or b) - c").unwrap(),
+            Expr::Difference(Box::new(Expr::Union(sym("a"), sym("b"))), sym("c"))
+        );
+        assert_eq!(
+            parse("ancestors(master) and author('ali\\'ce')").unwrap(),
+            Expr::Intersection(
+                Box::new(Expr::Func("ancestors".to_string(), vec![*sym("master")])),
+                Box::new(Expr::Func(
+                    "author".to_string(),
+                    vec![Expr::String("ali'ce".to_string())]
+                )),
+            )
+        );
+        assert_eq!(parse("release-1.0").unwrap(), *sym("release-1.0"));
+        assert_eq!(
+            parse("a - b-c").unwrap(),
+            Expr::Difference(sym("a"), sym("b-c"))
+        );
+        assert_eq!(
+            parse("(a)-b").unwrap(),
+            Expr::Difference(sym("a"), sym("b"))
+        );
+        assert_eq!(
+            parse("limit(draft(), 10)").unwrap(),
+            Expr::Func(
+                "limit".to_string(),
+                vec![Expr::Func("draft".to_string(), vec![]), *sym("10")]
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("").is_err());
+        assert!(parse("a::").is_err());
+        assert!(parse("a:b").is_err());
+        assert!(parse("(a").is_err());
+        assert!(parse("a b").is_err());
+        assert!(parse("author('a").is_err());
+        assert!(parse("f(a b)").is_err());
+        assert!(parse("a # b").is_err());
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let nested = |depth: usize| format!("{}a{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(parse(&nested(MAX_DEPTH)).unwrap(), *sym("a"));
+        assert!(parse(&vec!["a"; MAX_DEPTH + 1].join("|")).is_ok());
+        for query in &[
+            nested(MAX_DEPTH + 1),
+            nested(MAX_QUERY_LENGTH),
+            format!(
+                "{}a{}",
+                "f(".repeat(MAX_DEPTH + 1),
+                ")".repeat(MAX_DEPTH + 1)
+            ),
+            vec!["a"; MAX_DEPTH + 2].join("|"),
+            vec!["a"; MAX_DEPTH + 2].join(" - "),
+            vec!["a"; MAX_QUERY_LENGTH].join("|"),
+        ] {
+            match parse(query) {
+                Err(MononokeError::InvalidRequest(_)) => {}
+                result => panic!("unexpected result for {:?}: {:?}", query, result),
+            }
+        }
+    }
+}
@@ -5,7 +5,12 @@
  * GNU General Public License version 2.
  */
 
+mod test_archive;
 mod test_history;
+mod test_last_changed;
+mod test_mutation;
 mod test_repo;
 mod test_repo_create_changeset;
+mod test_repo_land_stack;
 mod test_repo_move_bookmark;
+mod test_revset_query;
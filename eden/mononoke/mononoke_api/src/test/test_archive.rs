@@ -0,0 +1,210 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use anyhow::Result;
+use assert_matches::assert_matches;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use futures::stream::TryStreamExt;
+use mononoke_types::FileType;
+use tests_utils::CreateCommitContext;
+
+use crate::{
+    ArchiveCursor, ArchiveFormat, ChangesetContext, ChangesetSpecifier, MononokeError,
+    MononokePath, Repo, RepoContext,
+};
+
+async fn init_changeset(ctx: &CoreContext) -> Result<ChangesetContext> {
+    let blob_repo = blobrepo_factory::new_memblob_empty(None)?;
+    let large = "x".repeat(2000);
+    let cs_id = CreateCommitContext::new_root(ctx, &blob_repo)
+        .add_file("a", "a\n")
+        .add_file_with_type("bin/run", "#!/bin/sh\n", FileType::Executable)
+        .add_file("dir/b", "b\n")
+        .add_file("dir/large", large)
+        .add_file_with_type("link", "dir/b", FileType::Symlink)
+        .commit()
+        .await?;
+    let repo = Repo::new_test(ctx.clone(), blob_repo).await?;
+    let repo_ctx = RepoContext::new(ctx.clone(), Arc::new(repo)).await?;
+    let cs = repo_ctx
+        .changeset(ChangesetSpecifier::Bonsai(cs_id))
+        .await?
+        .expect("changeset exists");
+    Ok(cs)
+}
+
+/// Parse the (name, typeflag, content or link target) of the entries of
+/// a ustar archive.
+fn parse_tar(data: &[u8]) -> Vec<(String, u8, Vec<u8>)> {
+    let field = |block: &[u8]| {
+        let end = block.iter().position(|b| *b == 0).unwrap_or(block.len());
+        block[..end].to_vec()
+    };
+    let mut entries = Vec::new();
+    let mut pos = 0;
+    while data[pos..pos + 512].iter().any(|b| *b != 0) {
+        let header = &data[pos..pos + 512];
+        let name = String::from_utf8(field(&header[0..100])).unwrap();
+        let size =
+            usize::from_str_radix(std::str::from_utf8(&field(&header[124..136])).unwrap(), 8)
+                .unwrap();
+        let typeflag = header[156];
+        pos += 512;
+        let content = match typeflag {
+            b'2' => field(&header[157..257]),
+            _ => data[pos..pos + size].to_vec(),
+        };
+        pos += (size + 511) / 512 * 512;
+        entries.push((name, typeflag, content));
+    }
+    assert_eq!(data.len(), pos + 1024);
+    entries
+}
+
+#[fbinit::compat_test]
+async fn archive_tar(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let cs = init_changeset(&ctx).await?;
+
+    let data: Vec<u8> = cs
+        .archive(ArchiveFormat::Tar, None)
+        .await?
+        .map_ok(|chunk| chunk.to_vec())
+        .try_concat()
+        .await?;
+    let entries = parse_tar(&data);
+    assert_eq!(
+        entries
+            .iter()
+            .map(|(name, typeflag, _)| (name.as_str(), *typeflag))
+            .collect::<Vec<_>>(),
+        vec![
+            ("a", b'0'),
+            ("bin/run", b'0'),
+            ("dir/b", b'0'),
+            ("dir/large", b'0'),
+            ("link", b'2'),
+        ]
+    );
+    assert_eq!(entries[0].2, b"a\n");
+    assert_eq!(entries[3].2, "x".repeat(2000).into_bytes());
+    assert_eq!(entries[4].2, b"dir/b");
+    // Executable files keep their mode.
+    assert_eq!(&data[512 * 2 + 100..512 * 2 + 107], b"0000755");
+
+    let data: Vec<u8> = cs
+        .archive(
+            ArchiveFormat::Tar,
+            Some(vec![MononokePath::try_from("dir")?]),
+        )
+        .await?
+        .map_ok(|chunk| chunk.to_vec())
+        .try_concat()
+        .await?;
+    let names: Vec<_> = parse_tar(&data)
+        .into_iter()
+        .map(|(name, _, _)| name)
+        .collect();
+    assert_eq!(names, vec!["dir/b", "dir/large"]);
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn archive_chunks(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let cs = init_changeset(&ctx).await?;
+
+    let full: Vec<u8> = cs
+        .archive(ArchiveFormat::Tar, None)
+        .await?
+        .map_ok(|chunk| chunk.to_vec())
+        .try_concat()
+        .await?;
+
+    // Reassemble the archive from small chunks, passing the cursor through
+    // its token each time.
+    let mut chunked = Vec::new();
+    let mut after = None;
+    loop {
+        let chunk = cs
+            .archive_chunk(ArchiveFormat::Tar, None, after, 100)
+            .await?;
+        chunked.extend_from_slice(&chunk.data);
+        match chunk.continue_after {
+            Some(cursor) => after = Some(ArchiveCursor::from_token(&cursor.to_token())?),
+            None => break,
+        }
+    }
+    assert_eq!(chunked, full);
+
+    // Resuming part way through a file.
+    let cursor = ArchiveCursor::Within(MononokePath::try_from("dir/large")?, 1500);
+    let chunk = cs
+        .archive_chunk(ArchiveFormat::Tar, None, Some(cursor), 1)
+        .await?;
+    assert_eq!(chunk.data.as_ref(), "x".repeat(500).as_bytes());
+
+    // Resuming after a file continues with the next one under the prefixes,
+    // even if the file isn't in the archive.
+    let cursor = ArchiveCursor::After(MononokePath::try_from("bin/run")?);
+    let chunk = cs
+        .archive_chunk(
+            ArchiveFormat::Tar,
+            Some(vec![
+                MononokePath::try_from("dir")?,
+                MononokePath::try_from("dir/b")?,
+                MononokePath::try_from("link")?,
+            ]),
+            Some(cursor),
+            1024 * 1024,
+        )
+        .await?;
+    let names: Vec<_> = parse_tar(&chunk.data)
+        .into_iter()
+        .map(|(name, _, _)| name)
+        .collect();
+    assert_eq!(names, vec!["dir/b", "dir/large", "link"]);
+    assert_eq!(chunk.continue_after, None);
+
+    assert_matches!(
+        cs.archive_chunk(ArchiveFormat::Zip, None, None, 100).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    let cursor = ArchiveCursor::Within(MononokePath::try_from("missing")?, 0);
+    assert_matches!(
+        cs.archive_chunk(ArchiveFormat::Tar, None, Some(cursor), 100)
+            .await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn archive_zip(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let cs = init_changeset(&ctx).await?;
+
+    let data: Vec<u8> = cs
+        .archive(ArchiveFormat::Zip, None)
+        .await?
+        .map_ok(|chunk| chunk.to_vec())
+        .try_concat()
+        .await?;
+    assert_eq!(&data[..4], b"PK\x03\x04");
+    let end = &data[data.len() - 22..];
+    assert_eq!(&end[..4], b"PK\x05\x06");
+    // Five entries in the central directory.
+    assert_eq!(&end[10..12], &[5, 0]);
+
+    Ok(())
+}
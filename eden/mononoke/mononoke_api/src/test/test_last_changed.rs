@@ -0,0 +1,87 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use futures::stream::TryStreamExt;
+use tests_utils::CreateCommitContext;
+
+use crate::{ChangesetContext, ChangesetId, ChangesetSpecifier, Repo, RepoContext};
+
+fn last_changed(entries: Vec<(String, ChangesetContext)>) -> Vec<(String, ChangesetId)> {
+    entries
+        .into_iter()
+        .map(|(name, changeset)| (name, changeset.id()))
+        .collect()
+}
+
+#[fbinit::compat_test]
+async fn list_last_changed(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let blob_repo = blobrepo_factory::new_memblob_empty(None)?;
+
+    let first = CreateCommitContext::new_root(&ctx, &blob_repo)
+        .add_file("a", "a")
+        .add_file("dir/b", "b")
+        .add_file("dir/sub/c", "c")
+        .add_file("other/d", "d")
+        .commit()
+        .await?;
+    let second = CreateCommitContext::new(&ctx, &blob_repo, vec![first])
+        .add_file("dir/sub/c", "c2")
+        .commit()
+        .await?;
+    let third = CreateCommitContext::new(&ctx, &blob_repo, vec![second])
+        .add_file("a", "a3")
+        .commit()
+        .await?;
+
+    let repo = Repo::new_test(ctx.clone(), blob_repo).await?;
+    let repo = RepoContext::new(ctx, Arc::new(repo)).await?;
+    let cs = repo
+        .changeset(ChangesetSpecifier::Bonsai(third))
+        .await?
+        .expect("changeset exists");
+
+    let root = cs.path("")?.list_last_changed(0, 100).await?;
+    assert_eq!(
+        last_changed(root),
+        vec![
+            ("a".to_string(), third),
+            ("dir".to_string(), second),
+            ("other".to_string(), first),
+        ]
+    );
+
+    let dir = cs.path("dir")?.list_last_changed(0, 100).await?;
+    assert_eq!(
+        last_changed(dir),
+        vec![("b".to_string(), first), ("sub".to_string(), second)]
+    );
+
+    // Offset and limit select a range of entries by name.
+    let root = cs.path("")?.list_last_changed(1, 1).await?;
+    assert_eq!(last_changed(root), vec![("dir".to_string(), second)]);
+
+    // The last change is the most recent commit in the entry's history.
+    let history: Vec<_> = cs
+        .path("dir/sub")?
+        .history(None, false)
+        .await?
+        .map_ok(|changeset| changeset.id())
+        .try_collect()
+        .await?;
+    assert_eq!(history.first(), Some(&second));
+
+    // Files have no entries.
+    assert!(cs.path("a")?.list_last_changed(0, 100).await?.is_empty());
+
+    Ok(())
+}
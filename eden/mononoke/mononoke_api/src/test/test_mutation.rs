@@ -0,0 +1,107 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use blobrepo_hg::BlobRepoHg;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use futures::compat::Future01CompatExt;
+use maplit::hashset;
+use mercurial_mutation::{HgMutationEntry, HgMutationStore};
+use mononoke_types::DateTime;
+use smallvec::smallvec;
+use tests_utils::CreateCommitContext;
+
+use crate::{ChangesetMutation, ChangesetSpecifier, Repo, RepoContext};
+
+#[fbinit::compat_test]
+async fn commit_mutations(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let blob_repo = blobrepo_factory::new_memblob_empty(None)?;
+
+    // A commit that is amended and then landed:
+    //
+    //   draft --(amend)-> amended --(pushrebase)-> landed
+    let mut hg_cs_ids = Vec::new();
+    for (content, timestamp) in &[("1", 1000), ("2", 2000), ("3", 3000)] {
+        let cs_id = CreateCommitContext::new_root(&ctx, &blob_repo)
+            .add_file("file", *content)
+            .set_author_date(DateTime::from_timestamp(*timestamp, 0)?)
+            .commit()
+            .await?;
+        let hg_cs_id = blob_repo
+            .get_hg_from_bonsai_changeset(ctx.clone(), cs_id)
+            .compat()
+            .await?;
+        hg_cs_ids.push(hg_cs_id);
+    }
+    let (draft, amended, landed) = (hg_cs_ids[0], hg_cs_ids[1], hg_cs_ids[2]);
+
+    let amend = HgMutationEntry::new(
+        amended,
+        smallvec![draft],
+        vec![],
+        String::from("amend"),
+        String::from("testuser"),
+        DateTime::from_timestamp(2000, 0)?,
+        vec![],
+    );
+    let land = HgMutationEntry::new(
+        landed,
+        smallvec![amended],
+        vec![],
+        String::from("pushrebase"),
+        String::from("testuser"),
+        DateTime::from_timestamp(3000, 0)?,
+        vec![],
+    );
+    blob_repo
+        .hg_mutation_store()
+        .add_entries(
+            &ctx,
+            hashset! { draft, amended, landed },
+            vec![amend.clone(), land.clone()],
+        )
+        .await?;
+
+    let repo = Repo::new_test(ctx.clone(), blob_repo).await?;
+    let repo_ctx = RepoContext::new(ctx, Arc::new(repo)).await?;
+    let draft_ctx = repo_ctx
+        .changeset(ChangesetSpecifier::Hg(draft))
+        .await?
+        .expect("changeset exists");
+    let landed_ctx = repo_ctx
+        .changeset(ChangesetSpecifier::Hg(landed))
+        .await?
+        .expect("changeset exists");
+
+    let successors = draft_ctx.mutation_successors().await?;
+    assert_eq!(
+        successors,
+        vec![
+            ChangesetMutation::from(amend.clone()),
+            ChangesetMutation::from(land.clone()),
+        ]
+    );
+    assert_eq!(draft_ctx.final_successors().await?, vec![landed]);
+    assert!(draft_ctx.mutation_predecessors().await?.is_empty());
+
+    let predecessors = landed_ctx.mutation_predecessors().await?;
+    assert_eq!(
+        predecessors,
+        vec![
+            ChangesetMutation::from(amend),
+            ChangesetMutation::from(land)
+        ]
+    );
+    assert!(landed_ctx.mutation_successors().await?.is_empty());
+    assert!(landed_ctx.final_successors().await?.is_empty());
+
+    Ok(())
+}
@@ -298,6 +298,114 @@ async fn commit_find_files(fb: FacebookInit) -> Result<(), Error> {
     Ok(())
 }
 
+#[fbinit::compat_test]
+async fn commit_search_content(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let mononoke = Mononoke::new_test(
+        ctx.clone(),
+        vec![("test".to_string(), many_files_dirs::getrepo(fb).await)],
+    )
+    .await?;
+    let repo = mononoke.repo(ctx, "test").await?.expect("repo exists");
+    let hash = "b0d1bf77898839595ee0f0cba673dd6e3be9dadaaa78bc6dd2dea97ca6bee77e";
+    let cs_id = ChangesetId::from_str(hash)?;
+    let cs = repo
+        .changeset(ChangesetSpecifier::Bonsai(cs_id))
+        .await?
+        .expect("changeset exists");
+
+    let matches: Vec<_> = cs
+        .search_content("content[1-4]", None, 10)
+        .await?
+        .matches
+        .into_iter()
+        .map(|m| (m.path.to_string(), m.line_number, m.line))
+        .collect();
+    assert_eq!(
+        matches,
+        vec![
+            (
+                String::from("dir1/file_1_in_dir1"),
+                1,
+                String::from("content1")
+            ),
+            (
+                String::from("dir1/file_2_in_dir1"),
+                1,
+                String::from("content3")
+            ),
+            (
+                String::from("dir1/subdir1/file_1"),
+                1,
+                String::from("content4")
+            ),
+            (
+                String::from("dir2/file_1_in_dir2"),
+                1,
+                String::from("content2")
+            ),
+        ]
+    );
+
+    // Prefixes and limit
+    let matches: Vec<_> = cs
+        .search_content(
+            "content",
+            Some(vec![MononokePath::try_from("dir1/subdir1")?]),
+            2,
+        )
+        .await?
+        .matches
+        .into_iter()
+        .map(|m| m.path.to_string())
+        .collect();
+    assert_eq!(
+        matches,
+        vec![
+            String::from("dir1/subdir1/file_1"),
+            String::from("dir1/subdir1/subsubdir1/file_1"),
+        ]
+    );
+
+    // Invalid regex
+    assert!(cs.search_content("content(", None, 10).await.is_err());
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn commit_search_content_unsearched(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let blobrepo = new_memblob_empty(None)?;
+    let root = CreateCommitContext::new_root(&ctx, &blobrepo)
+        .add_file("text", "hello world\n")
+        .add_file("binary", "hello\0world\n")
+        .commit()
+        .await?;
+    let mononoke =
+        Mononoke::new_test(ctx.clone(), vec![("test".to_string(), blobrepo.clone())]).await?;
+    let repo = mononoke.repo(ctx, "test").await?.expect("repo exists");
+    let cs = repo
+        .changeset(ChangesetSpecifier::Bonsai(root))
+        .await?
+        .expect("changeset exists");
+
+    // The binary file is not fetched, but it is reported as not searched.
+    let result = cs.search_content("hello", None, 10).await?;
+    let matches: Vec<_> = result
+        .matches
+        .into_iter()
+        .map(|m| (m.path.to_string(), m.line_number))
+        .collect();
+    assert_eq!(matches, vec![(String::from("text"), 1)]);
+    assert_eq!(
+        result.unsearched_files,
+        vec![MononokePath::try_from("binary")?]
+    );
+
+    Ok(())
+}
+
 #[fbinit::compat_test]
 async fn commit_path_exists_and_type(fb: FacebookInit) -> Result<(), Error> {
     let ctx = CoreContext::test_mock(fb);
@@ -0,0 +1,192 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use anyhow::{Error, Result};
+use assert_matches::assert_matches;
+use async_trait::async_trait;
+use bookmarks::{BookmarkName, BookmarkUpdateReason};
+use context::CoreContext;
+use fbinit::FacebookInit;
+use hooks::{ChangesetHook, HookExecution, HookManager, HookRejectionInfo};
+use hooks_content_stores::{FileContentFetcher, InMemoryFileContentFetcher};
+use metaconfig_types::{BookmarkOrRegex, HookConfig, HookManagerParams};
+use mononoke_types::{BonsaiChangeset, ChangesetId};
+use scuba_ext::ScubaSampleBuilder;
+use tests_utils::drawdag::create_from_dag;
+use tests_utils::CreateCommitContext;
+
+use crate::errors::MononokeError;
+use crate::repo::{Repo, RepoContext};
+use crate::specifiers::ChangesetSpecifier;
+
+struct RejectingHook;
+
+#[async_trait]
+impl ChangesetHook for RejectingHook {
+    async fn run<'this: 'cs, 'ctx: 'this, 'cs, 'fetcher: 'cs>(
+        &'this self,
+        _ctx: &'ctx CoreContext,
+        _bookmark: &BookmarkName,
+        _changeset: &'cs BonsaiChangeset,
+        _content_fetcher: &'fetcher dyn FileContentFetcher,
+    ) -> Result<HookExecution, Error> {
+        Ok(HookExecution::Rejected(HookRejectionInfo::new(
+            "rejected by test hook",
+        )))
+    }
+}
+
+async fn init_repo(
+    ctx: &CoreContext,
+    hook_manager: Option<HookManager>,
+) -> Result<(RepoContext, BTreeMap<String, ChangesetId>)> {
+    let blob_repo = blobrepo_factory::new_memblob_empty(None)?;
+    let mut changesets = create_from_dag(
+        ctx,
+        &blob_repo,
+        r##"
+            A-B-C-D
+               \
+                E-F
+        "##,
+    )
+    .await?;
+    // A commit on top of B that conflicts with C.
+    let conflicting = CreateCommitContext::new(ctx, &blob_repo, vec![changesets["B"]])
+        .add_file("C", "conflict")
+        .commit()
+        .await?;
+    changesets.insert("X".to_string(), conflicting);
+
+    let mut txn = blob_repo.update_bookmark_transaction(ctx.clone());
+    txn.force_set(
+        &BookmarkName::new("trunk")?,
+        changesets["D"],
+        BookmarkUpdateReason::TestMove,
+        None,
+    )?;
+    txn.commit().await?;
+
+    let mut repo = Repo::new_test(ctx.clone(), blob_repo).await?;
+    if let Some(hook_manager) = hook_manager {
+        repo = repo.with_hook_manager(hook_manager);
+    }
+    let repo_ctx = RepoContext::new(ctx.clone(), Arc::new(repo)).await?;
+    Ok((repo_ctx, changesets))
+}
+
+#[fbinit::compat_test]
+async fn land_stack(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (repo, changesets) = init_repo(&ctx, None).await?;
+    let repo = repo.write().await?;
+
+    let outcome = repo
+        .land_stack("trunk", changesets["F"], changesets["B"])
+        .await?;
+    let trunk = repo
+        .resolve_bookmark("trunk")
+        .await?
+        .expect("bookmark should be set");
+    assert_eq!(trunk.id(), outcome.head);
+
+    // Both commits in the stack were rebased, and the head of the stack
+    // is now the bookmark.
+    let mut rebased: Vec<_> = outcome
+        .rebased_changesets
+        .iter()
+        .map(|pair| pair.id_old)
+        .collect();
+    rebased.sort();
+    let mut expected = vec![changesets["E"], changesets["F"]];
+    expected.sort();
+    assert_eq!(rebased, expected);
+    let new_head = outcome
+        .rebased_changesets
+        .iter()
+        .find(|pair| pair.id_old == changesets["F"])
+        .expect("head should be rebased")
+        .id_new;
+    assert_eq!(new_head, outcome.head);
+    let old_trunk = repo
+        .changeset(ChangesetSpecifier::Bonsai(changesets["D"]))
+        .await?
+        .expect("changeset exists");
+    assert!(old_trunk.is_ancestor_of(outcome.head).await?);
+
+    // An empty stack is rejected.
+    assert_matches!(
+        repo.land_stack("trunk", changesets["B"], changesets["B"])
+            .await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn land_stack_conflicts(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (repo, changesets) = init_repo(&ctx, None).await?;
+    let repo = repo.write().await?;
+
+    let result = repo
+        .land_stack("trunk", changesets["X"], changesets["B"])
+        .await;
+    assert_matches!(
+        result,
+        Err(MononokeError::PushrebaseConflicts(conflicts)) if conflicts.len() == 1
+    );
+    let trunk = repo
+        .resolve_bookmark("trunk")
+        .await?
+        .expect("bookmark should be set");
+    assert_eq!(trunk.id(), changesets["D"]);
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn land_stack_hook_rejection(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let mut hook_manager = HookManager::new(
+        fb,
+        Box::new(InMemoryFileContentFetcher::new()),
+        HookManagerParams {
+            disable_acl_checker: true,
+        },
+        ScubaSampleBuilder::with_discard(),
+    )
+    .await?;
+    hook_manager.register_changeset_hook("reject", Box::new(RejectingHook), HookConfig::default());
+    hook_manager.set_hooks_for_bookmark(
+        BookmarkOrRegex::Bookmark(BookmarkName::new("trunk")?),
+        vec!["reject".to_string()],
+    );
+    let (repo, changesets) = init_repo(&ctx, Some(hook_manager)).await?;
+    let repo = repo.write().await?;
+
+    let result = repo
+        .land_stack("trunk", changesets["F"], changesets["B"])
+        .await;
+    assert_matches!(
+        result,
+        Err(MononokeError::HookFailure(rejections))
+            if rejections.len() == 2
+                && rejections.iter().all(|rejection| rejection.hook_name == "reject")
+    );
+    let trunk = repo
+        .resolve_bookmark("trunk")
+        .await?
+        .expect("bookmark should be set");
+    assert_eq!(trunk.id(), changesets["D"]);
+
+    Ok(())
+}
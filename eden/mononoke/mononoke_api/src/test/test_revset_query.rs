@@ -0,0 +1,185 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use assert_matches::assert_matches;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use tests_utils::{bookmark, CreateCommitContext};
+
+use crate::{ChangesetId, MononokeError, Repo, RepoContext};
+
+// Generates this commit graph:
+//
+// o "c" (alice, dir/c)  <- main
+// |
+// o "b" (bob, dir/b)
+// |
+// | o "d" (bob, d)  <- feature
+// |/
+// o "a" (alice, a)
+async fn init_repo(ctx: &CoreContext) -> Result<(RepoContext, HashMap<&'static str, ChangesetId>)> {
+    let blob_repo = blobrepo_factory::new_memblob_empty(None)?;
+    let mut changesets = HashMap::new();
+
+    changesets.insert(
+        "a",
+        CreateCommitContext::new_root(ctx, &blob_repo)
+            .add_file("a", "a")
+            .set_author("alice")
+            .commit()
+            .await?,
+    );
+    changesets.insert(
+        "b",
+        CreateCommitContext::new(ctx, &blob_repo, vec![changesets["a"]])
+            .add_file("dir/b", "b")
+            .set_author("bob")
+            .commit()
+            .await?,
+    );
+    changesets.insert(
+        "c",
+        CreateCommitContext::new(ctx, &blob_repo, vec![changesets["b"]])
+            .add_file("dir/c", "c")
+            .set_author("alice")
+            .commit()
+            .await?,
+    );
+    changesets.insert(
+        "d",
+        CreateCommitContext::new(ctx, &blob_repo, vec![changesets["a"]])
+            .add_file("d", "d")
+            .set_author("bob")
+            .commit()
+            .await?,
+    );
+    bookmark(ctx, &blob_repo, "main")
+        .set_to(changesets["c"])
+        .await?;
+    bookmark(ctx, &blob_repo, "feature")
+        .set_to(changesets["d"])
+        .await?;
+
+    let repo = Repo::new_test(ctx.clone(), blob_repo).await?;
+    let repo_ctx = RepoContext::new(ctx.clone(), Arc::new(repo)).await?;
+    Ok((repo_ctx, changesets))
+}
+
+async fn query(repo: &RepoContext, query: &str) -> Result<Vec<ChangesetId>> {
+    let result = repo.query_revset(query, None, 100).await?;
+    assert_eq!(result.continue_after, None);
+    Ok(result.changesets.iter().map(|cs| cs.id()).collect())
+}
+
+#[fbinit::compat_test]
+async fn revset_query(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (repo, changesets) = init_repo(&ctx).await?;
+    let ids = |names: &[&str]| -> Vec<ChangesetId> {
+        names.iter().map(|name| changesets[name]).collect()
+    };
+
+    assert_eq!(query(&repo, "main").await?, ids(&["c"]));
+    assert_eq!(query(&repo, "bookmark(feature)").await?, ids(&["d"]));
+    assert_eq!(query(&repo, "::main").await?, ids(&["c", "b", "a"]));
+    assert_eq!(
+        query(&repo, &format!("{}::main", changesets["a"])).await?,
+        ids(&["c", "b", "a"])
+    );
+    assert_eq!(
+        query(&repo, "ancestors(main) - ancestors(feature)").await?,
+        ids(&["c", "b"])
+    );
+    assert_eq!(
+        query(&repo, "ancestors(main) & ancestors(feature)").await?,
+        ids(&["a"])
+    );
+    assert_eq!(
+        query(&repo, "ancestors(main) & author(alice)").await?,
+        ids(&["c", "a"])
+    );
+    assert_eq!(
+        query(&repo, "ancestors(main) - author('^a')").await?,
+        ids(&["b"])
+    );
+    assert_eq!(
+        query(&repo, "ancestors(main) & file(dir)").await?,
+        ids(&["c", "b"])
+    );
+    assert_eq!(query(&repo, "limit(::main, 2)").await?, ids(&["c", "b"]));
+
+    // Predicates and unknown symbols can't be enumerated.
+    assert!(query(&repo, "author(alice)").await.is_err());
+    assert!(query(&repo, "::main | author(alice)").await.is_err());
+    assert!(query(&repo, "missing").await.is_err());
+    assert!(query(&repo, "unknown(main)").await.is_err());
+
+    // Deeply nested queries are rejected before they are compiled.
+    let nested = format!("{}main{}", "(".repeat(10000), ")".repeat(10000));
+    assert_matches!(
+        repo.query_revset(&nested, None, 100).await.map(|_| ()),
+        Err(MononokeError::InvalidRequest(_))
+    );
+    let chain = vec!["main"; 1000].join(" | ");
+    assert_matches!(
+        repo.query_revset(&chain, None, 100).await.map(|_| ()),
+        Err(MononokeError::InvalidRequest(_))
+    );
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn revset_query_pagination(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (repo, changesets) = init_repo(&ctx).await?;
+
+    // "b" and "d" have the same generation number, so they are ordered by id.
+    let mut same_generation = vec![changesets["b"], changesets["d"]];
+    same_generation.sort();
+
+    let first = repo.query_revset("::main | feature", None, 2).await?;
+    let first_ids: Vec<_> = first.changesets.iter().map(|cs| cs.id()).collect();
+    assert_eq!(first_ids, vec![changesets["c"], same_generation[0]]);
+    let cursor = first.continue_after.expect("more results");
+
+    let second = repo
+        .query_revset("::main | feature", Some(&cursor), 2)
+        .await?;
+    let second_ids: Vec<_> = second.changesets.iter().map(|cs| cs.id()).collect();
+    assert_eq!(second_ids, vec![same_generation[1], changesets["a"]]);
+    assert_eq!(second.continue_after, None);
+
+    // Later pages still count limit() from the top of the set.
+    let first = repo.query_revset("limit(::main, 2)", None, 1).await?;
+    let cursor = first.continue_after.expect("more results");
+    let second = repo
+        .query_revset("limit(::main, 2)", Some(&cursor), 1)
+        .await?;
+    let second_ids: Vec<_> = second.changesets.iter().map(|cs| cs.id()).collect();
+    assert_eq!(second_ids, vec![changesets["b"]]);
+    assert_eq!(second.continue_after, None);
+
+    let range = format!("{}::main", changesets["a"]);
+    let first = repo.query_revset(&range, None, 1).await?;
+    let cursor = first.continue_after.expect("more results");
+    let second = repo.query_revset(&range, Some(&cursor), 2).await?;
+    let second_ids: Vec<_> = second.changesets.iter().map(|cs| cs.id()).collect();
+    assert_eq!(second_ids, vec![changesets["b"], changesets["a"]]);
+    assert_eq!(second.continue_after, None);
+
+    assert!(repo
+        .query_revset("::main", Some("not a cursor"), 2)
+        .await
+        .is_err());
+
+    Ok(())
+}
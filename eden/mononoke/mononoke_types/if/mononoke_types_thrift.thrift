@@ -47,9 +47,12 @@ typedef IdType FileUnodeId (rust.newtype)
 typedef IdType ManifestUnodeId (rust.newtype)
 typedef IdType DeletedManifestId(rust.newtype)
 typedef IdType FsnodeId (rust.newtype)
+typedef IdType SkeletonManifestId (rust.newtype)
+typedef IdType TrigramIndexId (rust.newtype)
 typedef IdType MPathHash (rust.newtype)
 
 typedef IdType ContentMetadataId (rust.newtype)
+typedef IdType ContentTrigramsId (rust.newtype)
 typedef IdType FastlogBatchId (rust.newtype)
 typedef IdType BlameId (rust.newtype)
 
@@ -268,6 +271,84 @@ struct Fsnode {
   2: FsnodeSummary summary,
 }
 
+// Skeleton manifests are manifests that correspond to the shape of the
+// repository: only the names of files and directories are stored, with no
+// content.  Each directory additionally stores a summary of what is below
+// it, including whether any case conflicts exist within it, so that case
+// conflicts anywhere in the tree can be found by only inspecting the
+// subtrees where they are known to be present.
+struct SkeletonManifestDirectory {
+  1: SkeletonManifestId id,
+  2: SkeletonManifestSummary summary,
+}
+
+struct SkeletonManifestSummary {
+  // Counts are u64s stored as i64s
+  1: i64 child_files_count,
+  2: i64 child_dirs_count,
+  3: i64 descendant_files_count,
+  4: i64 descendant_dirs_count,
+  // Whether two of the immediate children of this directory have names
+  // which only differ in case
+  5: bool child_case_conflicts,
+  // Whether any of the subdirectories (recursively) contain case conflicts
+  6: bool descendant_case_conflicts,
+}
+
+struct SkeletonManifestEntry {
+  // Present if this entry is a directory, absent if it is a file
+  1: optional SkeletonManifestDirectory directory,
+}
+
+struct SkeletonManifest {
+  1: map<MPathElement, SkeletonManifestEntry> subentries,
+  2: SkeletonManifestSummary summary,
+}
+
+// Trigram indexes are manifests that allow searching the contents of text
+// files.  Each directory stores a bloom filter of the trigrams (sequences
+// of three bytes) contained in the text files below it, so that searches
+// only need to descend into directories which may contain a match.  The
+// trigrams of each file are stored separately in ContentTrigrams, keyed by
+// the file's content id, and so are shared by all files with the same
+// content.
+struct TrigramIndexFile {
+  1: ContentId content_id,
+  2: FileType file_type,
+  // Whether the trigrams of this file have been indexed.  Binary files and
+  // very large files are not indexed.
+  3: bool indexed,
+}
+
+struct TrigramIndexSummary {
+  // Counts are u64s stored as i64s
+  1: i64 indexed_files_count,
+  2: i64 unindexed_files_count,
+  // Bloom filter of the trigrams of all indexed descendant files
+  3: binary trigram_bloom,
+}
+
+struct TrigramIndexDirectory {
+  1: TrigramIndexId id,
+  2: TrigramIndexSummary summary,
+}
+
+union TrigramIndexEntry {
+  1: TrigramIndexFile File,
+  2: TrigramIndexDirectory Directory,
+}
+
+struct TrigramIndex {
+  1: map<MPathElement, TrigramIndexEntry> subentries,
+  2: TrigramIndexSummary summary,
+}
+
+struct ContentTrigrams {
+  1: ContentId content_id,
+  // Sorted trigrams contained in the content, three bytes each
+  2: binary trigrams,
+}
+
 // Structure that holds a commit graph, usually a history of a file
 // or a directory hence the name. Semantically it stores list of
 // (commit hash, [parent commit hashes]), however it's stored in compressed form
@@ -345,12 +345,7 @@ impl Blame {
         content: C,
         path: MPath,
     ) -> Result<Blame, Error> {
-        // calculating length by diffing with empty content, so number of lines
-        // would be calculcated the same way as xdiff doest it.
-        let length = match diff_hunks(&b""[..], content.as_ref()).first() {
-            None => 0,
-            Some(hunk) => (hunk.add.end - hunk.add.start) as u32,
-        };
+        let length = content_lines_count(content.as_ref());
         Blame::new(vec![BlameRange {
             offset: 0,
             length,
@@ -438,6 +433,40 @@ impl Blame {
         BlameLines::new(&self.ranges)
     }
 
+    /// Build a blame object from a sequence of lines, merging adjacent lines
+    /// which come from consecutive lines of the same origin.
+    pub fn from_lines<'a, I>(lines: I) -> Result<Blame, Error>
+    where
+        I: IntoIterator<Item = (ChangesetId, &'a MPath, u32)>,
+    {
+        let (_length, ranges) = lines.into_iter().fold(
+            (0, Vec::new()),
+            |(mut offset, mut output), (csid, path, origin_offset)| -> (u32, Vec<BlameRange>) {
+                match output.last_mut() {
+                    Some(ref mut last)
+                        if last.csid == csid
+                            && &last.path == path
+                            && last.origin_offset + last.length == origin_offset =>
+                    {
+                        last.length += 1;
+                    }
+                    _ => {
+                        output.push(BlameRange {
+                            offset,
+                            length: 1,
+                            csid,
+                            path: path.clone(),
+                            origin_offset,
+                        });
+                    }
+                }
+                offset += 1;
+                (offset, output)
+            },
+        );
+        Blame::new(ranges)
+    }
+
     pub fn annotate(&self, content: &str) -> Result<String, Error> {
         if content.is_empty() {
             return Ok(String::new());
@@ -464,6 +493,49 @@ impl Blame {
     }
 }
 
+/// Find the line of `parent_content` each line of `content` originates from.
+///
+/// Returns one entry per line of `content`. Lines not touched by the diff map
+/// to their counterpart in the parent. Lines inside a changed hunk are matched
+/// positionally with the removed lines of the same hunk, and lines for which
+/// the hunk has no removed counterpart (pure additions) map to `None`.
+pub fn blame_lines_origins_in_parent<C: AsRef<[u8]>>(
+    parent_content: C,
+    content: C,
+) -> Vec<Option<u32>> {
+    let length = content_lines_count(content.as_ref());
+    let mut origins = Vec::with_capacity(length as usize);
+    // difference between parent line index and line index in unchanged areas
+    let mut shift = 0i64;
+    for Hunk { add, remove } in diff_hunks(parent_content.as_ref(), content.as_ref()) {
+        let (add_start, add_end) = (add.start as u32, add.end as u32);
+        while (origins.len() as u32) < add_start {
+            origins.push(Some((origins.len() as i64 + shift) as u32));
+        }
+        for index in 0..(add_end - add_start) {
+            let origin = remove.start as u32 + index;
+            origins.push(if origin < remove.end as u32 {
+                Some(origin)
+            } else {
+                None
+            });
+        }
+        shift = remove.end as i64 - add.end as i64;
+    }
+    while (origins.len() as u32) < length {
+        origins.push(Some((origins.len() as i64 + shift) as u32));
+    }
+    origins
+}
+
+/// Number of lines in the content, calculated the same way xdiff does it.
+fn content_lines_count(content: &[u8]) -> u32 {
+    match diff_hunks(&b""[..], content).first() {
+        None => 0,
+        Some(hunk) => (hunk.add.end - hunk.add.start) as u32,
+    }
+}
+
 /// Split blame ranges at a specified offset
 fn blame_ranges_split_at(
     ranges: Vec<BlameRange>,
@@ -900,4 +972,58 @@ mod test {
         assert_eq!(b3_reference, b3);
         Ok(())
     }
+
+    #[test]
+    fn test_blame_lines_origins_in_parent() -> Result<(), Error> {
+        let c1 = "one\ntwo\nthree\nfour\n";
+        let c2 = "zero\none\nTWO\nTHREE\nthree and a half\nfour\n";
+
+        let origins = blame_lines_origins_in_parent(c1, c2);
+        assert_eq!(
+            origins,
+            vec![None, Some(0), Some(1), Some(2), None, Some(3)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_blame_from_lines() -> Result<(), Error> {
+        let p0 = MPath::new("path/zero")?;
+        let p1 = MPath::new("path/one")?;
+
+        let blame = Blame::from_lines(vec![
+            (ONES_CSID, &p0, 3),
+            (ONES_CSID, &p0, 4),
+            (ONES_CSID, &p0, 7),
+            (TWOS_CSID, &p1, 8),
+            (TWOS_CSID, &p1, 9),
+        ])?;
+
+        let reference = Blame::new(vec![
+            BlameRange {
+                offset: 0,
+                length: 2,
+                csid: ONES_CSID,
+                path: p0.clone(),
+                origin_offset: 3,
+            },
+            BlameRange {
+                offset: 2,
+                length: 1,
+                csid: ONES_CSID,
+                path: p0.clone(),
+                origin_offset: 7,
+            },
+            BlameRange {
+                offset: 3,
+                length: 2,
+                csid: TWOS_CSID,
+                path: p1.clone(),
+                origin_offset: 8,
+            },
+        ])?;
+
+        assert_eq!(reference, blame);
+        Ok(())
+    }
 }
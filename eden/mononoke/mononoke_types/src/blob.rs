@@ -12,8 +12,9 @@ use blobstore::BlobstoreBytes;
 use bytes::Bytes;
 
 use crate::typed_hash::{
-    ChangesetId, ContentChunkId, ContentId, ContentMetadataId, DeletedManifestId, FastlogBatchId,
-    FileUnodeId, FsnodeId, ManifestUnodeId, RawBundle2Id,
+    ChangesetId, ContentChunkId, ContentId, ContentMetadataId, ContentTrigramsId,
+    DeletedManifestId, FastlogBatchId, FileUnodeId, FsnodeId, ManifestUnodeId, RawBundle2Id,
+    SkeletonManifestId, TrigramIndexId,
 };
 
 /// A serialized blob in memory.
@@ -49,7 +50,10 @@ pub type FileUnodeBlob = Blob<FileUnodeId>;
 pub type ManifestUnodeBlob = Blob<ManifestUnodeId>;
 pub type DeletedManifestBlob = Blob<DeletedManifestId>;
 pub type FsnodeBlob = Blob<FsnodeId>;
+pub type SkeletonManifestBlob = Blob<SkeletonManifestId>;
+pub type TrigramIndexBlob = Blob<TrigramIndexId>;
 pub type ContentMetadataBlob = Blob<ContentMetadataId>;
+pub type ContentTrigramsBlob = Blob<ContentTrigramsId>;
 pub type FastlogBatchBlob = Blob<FastlogBatchId>;
 
 impl<Id> From<Blob<Id>> for BlobstoreBytes {
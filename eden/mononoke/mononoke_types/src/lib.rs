@@ -28,7 +28,9 @@ pub mod hash;
 pub mod path;
 pub mod rawbundle2;
 pub mod repo;
+pub mod skeleton_manifest;
 pub mod sql_types;
+pub mod trigram_index;
 pub mod typed_hash;
 pub mod unode;
 
@@ -48,8 +50,8 @@ pub use rawbundle2::RawBundle2;
 pub use repo::{RepositoryId, REPO_PREFIX_REGEX};
 pub use typed_hash::{
     ChangesetId, ChangesetIdPrefix, ChangesetIdsResolvedFromPrefix, ContentChunkId, ContentId,
-    ContentMetadataId, DeletedManifestId, FastlogBatchId, FileUnodeId, FsnodeId, ManifestUnodeId,
-    MononokeId, RawBundle2Id,
+    ContentMetadataId, ContentTrigramsId, DeletedManifestId, FastlogBatchId, FileUnodeId, FsnodeId,
+    ManifestUnodeId, MononokeId, RawBundle2Id, SkeletonManifestId, TrigramIndexId,
 };
 
 mod macros;
@@ -271,6 +271,13 @@ impl MPathElement {
     pub fn into_thrift(self) -> thrift::MPathElement {
         thrift::MPathElement(Vec::from(self.as_ref()))
     }
+
+    /// Returns the lowercased version of this element, or `None` if the
+    /// element is not valid UTF-8 and so cannot be case folded.
+    pub fn to_lowercase_utf8(&self) -> Option<String> {
+        let element = std::str::from_utf8(self.as_ref()).ok()?;
+        Some(element.to_lowercase())
+    }
 }
 
 impl AsRef<[u8]> for MPathElement {
@@ -0,0 +1,292 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{bail, Context, Result};
+
+use crate::blob::{Blob, BlobstoreValue, SkeletonManifestBlob};
+use crate::errors::ErrorKind;
+use crate::path::MPathElement;
+use crate::thrift;
+use crate::typed_hash::{SkeletonManifestId, SkeletonManifestIdContext};
+
+use fbthrift::compact_protocol;
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+
+// A skeleton manifest is a manifest node containing only the shape of the
+// repository: the names of files and directories, and for directories a
+// summary of the contents below them.
+//
+// The summary for each directory contains:
+// * count of immediate child files and directories
+// * recursive count of descendant files and directories
+// * whether any two of the immediate children have names which only
+//   differ in case
+// * whether any case conflicts exist in any of the subdirectories
+//
+// The case conflict markers mean that case conflicts anywhere in a tree can
+// be found by only inspecting the subtrees which are marked as containing
+// them, rather than walking the whole manifest.
+//
+// As skeleton manifests don't contain any file content information, the
+// same skeleton manifest is shared by all directories with the same shape,
+// and changing the content of a file does not change the skeleton manifest.
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SkeletonManifest {
+    subentries: BTreeMap<MPathElement, SkeletonManifestEntry>,
+    summary: SkeletonManifestSummary,
+}
+
+impl SkeletonManifest {
+    pub fn new(
+        subentries: BTreeMap<MPathElement, SkeletonManifestEntry>,
+        summary: SkeletonManifestSummary,
+    ) -> Self {
+        Self {
+            subentries,
+            summary,
+        }
+    }
+
+    pub fn get_skeleton_manifest_id(&self) -> SkeletonManifestId {
+        *self.clone().into_blob().id()
+    }
+
+    pub fn lookup(&self, basename: &MPathElement) -> Option<&SkeletonManifestEntry> {
+        self.subentries.get(basename)
+    }
+
+    pub fn list(&self) -> impl Iterator<Item = (&MPathElement, &SkeletonManifestEntry)> {
+        self.subentries.iter()
+    }
+
+    pub fn into_subentries(self) -> BTreeMap<MPathElement, SkeletonManifestEntry> {
+        self.subentries
+    }
+
+    pub fn summary(&self) -> &SkeletonManifestSummary {
+        &self.summary
+    }
+
+    /// Returns all pairs of immediate children whose names only differ in
+    /// case.  For each pair, the first name is the one which sorts first.
+    pub fn child_case_conflicts(&self) -> Vec<(&MPathElement, &MPathElement)> {
+        let mut conflicts = Vec::new();
+        if !self.summary.child_case_conflicts {
+            return conflicts;
+        }
+        let mut lowercase = HashMap::new();
+        for name in self.subentries.keys() {
+            if let Some(lower) = name.to_lowercase_utf8() {
+                match lowercase.entry(lower) {
+                    Entry::Occupied(first) => conflicts.push((*first.get(), name)),
+                    Entry::Vacant(vacant) => {
+                        vacant.insert(name);
+                    }
+                }
+            }
+        }
+        conflicts
+    }
+
+    pub(crate) fn from_thrift(t: thrift::SkeletonManifest) -> Result<SkeletonManifest> {
+        let subentries = t
+            .subentries
+            .into_iter()
+            .map(|(basename, entry)| {
+                let basename = MPathElement::from_thrift(basename)?;
+                let entry = SkeletonManifestEntry::from_thrift(entry)?;
+                Ok((basename, entry))
+            })
+            .collect::<Result<_>>()?;
+        let summary = SkeletonManifestSummary::from_thrift(t.summary)?;
+        Ok(SkeletonManifest {
+            subentries,
+            summary,
+        })
+    }
+
+    pub(crate) fn into_thrift(self) -> thrift::SkeletonManifest {
+        let subentries: BTreeMap<_, _> = self
+            .subentries
+            .into_iter()
+            .map(|(basename, entry)| (basename.into_thrift(), entry.into_thrift()))
+            .collect();
+        let summary = self.summary.into_thrift();
+        thrift::SkeletonManifest {
+            subentries,
+            summary,
+        }
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let thrift_tc = compact_protocol::deserialize(bytes)
+            .with_context(|| ErrorKind::BlobDeserializeError("SkeletonManifest".into()))?;
+        Self::from_thrift(thrift_tc)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum SkeletonManifestEntry {
+    File,
+    Directory(SkeletonManifestDirectory),
+}
+
+impl SkeletonManifestEntry {
+    pub(crate) fn from_thrift(t: thrift::SkeletonManifestEntry) -> Result<SkeletonManifestEntry> {
+        match t.directory {
+            Some(directory) => Ok(SkeletonManifestEntry::Directory(
+                SkeletonManifestDirectory::from_thrift(directory)?,
+            )),
+            None => Ok(SkeletonManifestEntry::File),
+        }
+    }
+
+    pub(crate) fn into_thrift(self) -> thrift::SkeletonManifestEntry {
+        match self {
+            SkeletonManifestEntry::File => thrift::SkeletonManifestEntry { directory: None },
+            SkeletonManifestEntry::Directory(directory) => thrift::SkeletonManifestEntry {
+                directory: Some(directory.into_thrift()),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SkeletonManifestDirectory {
+    id: SkeletonManifestId,
+    summary: SkeletonManifestSummary,
+}
+
+impl SkeletonManifestDirectory {
+    pub fn new(id: SkeletonManifestId, summary: SkeletonManifestSummary) -> Self {
+        Self { id, summary }
+    }
+
+    pub fn id(&self) -> &SkeletonManifestId {
+        &self.id
+    }
+
+    pub fn summary(&self) -> &SkeletonManifestSummary {
+        &self.summary
+    }
+
+    pub(crate) fn from_thrift(
+        t: thrift::SkeletonManifestDirectory,
+    ) -> Result<SkeletonManifestDirectory> {
+        let id = SkeletonManifestId::from_thrift(t.id)?;
+        let summary = SkeletonManifestSummary::from_thrift(t.summary)?;
+        Ok(SkeletonManifestDirectory { id, summary })
+    }
+
+    pub(crate) fn into_thrift(self) -> thrift::SkeletonManifestDirectory {
+        thrift::SkeletonManifestDirectory {
+            id: self.id.into_thrift(),
+            summary: self.summary.into_thrift(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct SkeletonManifestSummary {
+    pub child_files_count: u64,
+    pub child_dirs_count: u64,
+    pub descendant_files_count: u64,
+    pub descendant_dirs_count: u64,
+    pub child_case_conflicts: bool,
+    pub descendant_case_conflicts: bool,
+}
+
+impl SkeletonManifestSummary {
+    /// Whether this directory or any of its subdirectories contain a case
+    /// conflict.
+    pub fn has_case_conflicts(&self) -> bool {
+        self.child_case_conflicts || self.descendant_case_conflicts
+    }
+
+    pub(crate) fn from_thrift(t: thrift::SkeletonManifestSummary) -> Result<Self> {
+        if t.child_files_count < 0
+            || t.child_dirs_count < 0
+            || t.descendant_files_count < 0
+            || t.descendant_dirs_count < 0
+        {
+            bail!(ErrorKind::InvalidThrift(
+                "SkeletonManifestSummary".into(),
+                "negative count".into()
+            ));
+        }
+        Ok(SkeletonManifestSummary {
+            child_files_count: t.child_files_count as u64,
+            child_dirs_count: t.child_dirs_count as u64,
+            descendant_files_count: t.descendant_files_count as u64,
+            descendant_dirs_count: t.descendant_dirs_count as u64,
+            child_case_conflicts: t.child_case_conflicts,
+            descendant_case_conflicts: t.descendant_case_conflicts,
+        })
+    }
+
+    pub(crate) fn into_thrift(self) -> thrift::SkeletonManifestSummary {
+        thrift::SkeletonManifestSummary {
+            child_files_count: self.child_files_count as i64,
+            child_dirs_count: self.child_dirs_count as i64,
+            descendant_files_count: self.descendant_files_count as i64,
+            descendant_dirs_count: self.descendant_dirs_count as i64,
+            child_case_conflicts: self.child_case_conflicts,
+            descendant_case_conflicts: self.descendant_case_conflicts,
+        }
+    }
+}
+
+impl BlobstoreValue for SkeletonManifest {
+    type Key = SkeletonManifestId;
+
+    fn into_blob(self) -> SkeletonManifestBlob {
+        let thrift = self.into_thrift();
+        let data = compact_protocol::serialize(&thrift);
+        let mut context = SkeletonManifestIdContext::new();
+        context.update(&data);
+        let id = context.finish();
+        Blob::new(id, data)
+    }
+
+    fn from_blob(blob: Blob<Self::Key>) -> Result<Self> {
+        Self::from_bytes(blob.data().as_ref())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_child_case_conflicts() -> Result<()> {
+        let summary = SkeletonManifestSummary {
+            child_files_count: 5,
+            child_case_conflicts: true,
+            ..Default::default()
+        };
+        let subentries = vec!["a", "b", "B", "c", "A"]
+            .into_iter()
+            .map(|name| Ok((MPathElement::new(name.into())?, SkeletonManifestEntry::File)))
+            .collect::<Result<_>>()?;
+        let skeleton_manifest = SkeletonManifest::new(subentries, summary);
+        assert_eq!(
+            skeleton_manifest.child_case_conflicts(),
+            vec![
+                (
+                    &MPathElement::new("A".into())?,
+                    &MPathElement::new("a".into())?
+                ),
+                (
+                    &MPathElement::new("B".into())?,
+                    &MPathElement::new("b".into())?
+                ),
+            ]
+        );
+        Ok(())
+    }
+}
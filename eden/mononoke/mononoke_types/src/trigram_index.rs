@@ -0,0 +1,504 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{bail, Context, Result};
+
+use crate::blob::{Blob, BlobstoreValue, ContentTrigramsBlob, TrigramIndexBlob};
+use crate::errors::ErrorKind;
+use crate::file_change::FileType;
+use crate::path::MPathElement;
+use crate::thrift;
+use crate::typed_hash::{ContentId, ContentTrigramsId, TrigramIndexId, TrigramIndexIdContext};
+
+use fbthrift::compact_protocol;
+use std::collections::BTreeMap;
+
+// A trigram index is a manifest node which allows searching the contents of
+// the text files in a repository without reading all of them.
+//
+// Each directory contains a summary of the text files below it, including a
+// bloom filter of all of the trigrams (sequences of three bytes) that those
+// files contain.  A search for a string only needs to descend into
+// directories whose bloom filter contains all of the trigrams of the string.
+//
+// The exact trigrams of each file are stored in a separate ContentTrigrams
+// blob, keyed by content id, so that they can be used to rule out individual
+// files.  As these only depend on the content, they are shared between all
+// files with the same content, and only need computing once.
+
+/// Files larger than this are not indexed.
+pub const MAX_INDEXED_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Number of bits in the trigram bloom filter of each directory.
+const TRIGRAM_BLOOM_BITS: u32 = 1 << 14;
+const TRIGRAM_BLOOM_BYTES: usize = (TRIGRAM_BLOOM_BITS / 8) as usize;
+
+/// Convert three bytes into a trigram.
+pub fn trigram(bytes: [u8; 3]) -> u32 {
+    (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32
+}
+
+/// Returns the sorted, deduplicated trigrams of the given bytes.
+pub fn trigrams(bytes: &[u8]) -> Vec<u32> {
+    let mut trigrams: Vec<_> = bytes
+        .windows(3)
+        .map(|w| trigram([w[0], w[1], w[2]]))
+        .collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+/// Returns the trigrams of the content of a file, or `None` if the content
+/// should not be indexed, because it is binary (contains a NUL byte) or is
+/// too large.
+pub fn content_trigrams(content: &[u8]) -> Option<Vec<u32>> {
+    if content.len() as u64 > MAX_INDEXED_FILE_SIZE || content.contains(&0) {
+        return None;
+    }
+    Some(trigrams(content))
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct TrigramIndex {
+    subentries: BTreeMap<MPathElement, TrigramIndexEntry>,
+    summary: TrigramIndexSummary,
+}
+
+impl TrigramIndex {
+    pub fn new(
+        subentries: BTreeMap<MPathElement, TrigramIndexEntry>,
+        summary: TrigramIndexSummary,
+    ) -> Self {
+        Self {
+            subentries,
+            summary,
+        }
+    }
+
+    pub fn get_trigram_index_id(&self) -> TrigramIndexId {
+        *self.clone().into_blob().id()
+    }
+
+    pub fn lookup(&self, basename: &MPathElement) -> Option<&TrigramIndexEntry> {
+        self.subentries.get(basename)
+    }
+
+    pub fn list(&self) -> impl Iterator<Item = (&MPathElement, &TrigramIndexEntry)> {
+        self.subentries.iter()
+    }
+
+    pub fn into_subentries(self) -> BTreeMap<MPathElement, TrigramIndexEntry> {
+        self.subentries
+    }
+
+    pub fn summary(&self) -> &TrigramIndexSummary {
+        &self.summary
+    }
+
+    pub(crate) fn from_thrift(t: thrift::TrigramIndex) -> Result<TrigramIndex> {
+        let subentries = t
+            .subentries
+            .into_iter()
+            .map(|(basename, entry)| {
+                let basename = MPathElement::from_thrift(basename)?;
+                let entry = TrigramIndexEntry::from_thrift(entry)?;
+                Ok((basename, entry))
+            })
+            .collect::<Result<_>>()?;
+        let summary = TrigramIndexSummary::from_thrift(t.summary)?;
+        Ok(TrigramIndex {
+            subentries,
+            summary,
+        })
+    }
+
+    pub(crate) fn into_thrift(self) -> thrift::TrigramIndex {
+        let subentries: BTreeMap<_, _> = self
+            .subentries
+            .into_iter()
+            .map(|(basename, entry)| (basename.into_thrift(), entry.into_thrift()))
+            .collect();
+        let summary = self.summary.into_thrift();
+        thrift::TrigramIndex {
+            subentries,
+            summary,
+        }
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let thrift_tc = compact_protocol::deserialize(bytes)
+            .with_context(|| ErrorKind::BlobDeserializeError("TrigramIndex".into()))?;
+        Self::from_thrift(thrift_tc)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum TrigramIndexEntry {
+    File(TrigramIndexFile),
+    Directory(TrigramIndexDirectory),
+}
+
+impl TrigramIndexEntry {
+    pub(crate) fn from_thrift(t: thrift::TrigramIndexEntry) -> Result<TrigramIndexEntry> {
+        match t {
+            thrift::TrigramIndexEntry::File(file) => Ok(TrigramIndexEntry::File(
+                TrigramIndexFile::from_thrift(file)?,
+            )),
+            thrift::TrigramIndexEntry::Directory(directory) => Ok(TrigramIndexEntry::Directory(
+                TrigramIndexDirectory::from_thrift(directory)?,
+            )),
+            thrift::TrigramIndexEntry::UnknownField(unknown) => bail!(
+                "Unknown field encountered when parsing thrift::TrigramIndexEntry: {}",
+                unknown,
+            ),
+        }
+    }
+
+    pub(crate) fn into_thrift(self) -> thrift::TrigramIndexEntry {
+        match self {
+            TrigramIndexEntry::File(file) => thrift::TrigramIndexEntry::File(file.into_thrift()),
+            TrigramIndexEntry::Directory(directory) => {
+                thrift::TrigramIndexEntry::Directory(directory.into_thrift())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct TrigramIndexFile {
+    content_id: ContentId,
+    file_type: FileType,
+    indexed: bool,
+}
+
+impl TrigramIndexFile {
+    pub fn new(content_id: ContentId, file_type: FileType, indexed: bool) -> Self {
+        Self {
+            content_id,
+            file_type,
+            indexed,
+        }
+    }
+
+    pub fn content_id(&self) -> &ContentId {
+        &self.content_id
+    }
+
+    pub fn file_type(&self) -> &FileType {
+        &self.file_type
+    }
+
+    /// Whether the trigrams of this file have been indexed.  If they have
+    /// not, the file must be searched directly.
+    pub fn indexed(&self) -> bool {
+        self.indexed
+    }
+
+    pub(crate) fn from_thrift(t: thrift::TrigramIndexFile) -> Result<TrigramIndexFile> {
+        let content_id = ContentId::from_thrift(t.content_id)?;
+        let file_type = FileType::from_thrift(t.file_type)?;
+        Ok(TrigramIndexFile {
+            content_id,
+            file_type,
+            indexed: t.indexed,
+        })
+    }
+
+    pub(crate) fn into_thrift(self) -> thrift::TrigramIndexFile {
+        thrift::TrigramIndexFile {
+            content_id: self.content_id.into_thrift(),
+            file_type: self.file_type.into_thrift(),
+            indexed: self.indexed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct TrigramIndexDirectory {
+    id: TrigramIndexId,
+    summary: TrigramIndexSummary,
+}
+
+impl TrigramIndexDirectory {
+    pub fn new(id: TrigramIndexId, summary: TrigramIndexSummary) -> Self {
+        Self { id, summary }
+    }
+
+    pub fn id(&self) -> &TrigramIndexId {
+        &self.id
+    }
+
+    pub fn summary(&self) -> &TrigramIndexSummary {
+        &self.summary
+    }
+
+    pub(crate) fn from_thrift(t: thrift::TrigramIndexDirectory) -> Result<TrigramIndexDirectory> {
+        let id = TrigramIndexId::from_thrift(t.id)?;
+        let summary = TrigramIndexSummary::from_thrift(t.summary)?;
+        Ok(TrigramIndexDirectory { id, summary })
+    }
+
+    pub(crate) fn into_thrift(self) -> thrift::TrigramIndexDirectory {
+        thrift::TrigramIndexDirectory {
+            id: self.id.into_thrift(),
+            summary: self.summary.into_thrift(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct TrigramIndexSummary {
+    pub indexed_files_count: u64,
+    pub unindexed_files_count: u64,
+    pub trigram_bloom: TrigramBloom,
+}
+
+impl TrigramIndexSummary {
+    /// Whether this directory may contain a file containing all of the
+    /// given trigrams.
+    pub fn may_contain_all(&self, trigrams: &[u32]) -> bool {
+        self.unindexed_files_count > 0
+            || (self.indexed_files_count > 0 && self.trigram_bloom.may_contain_all(trigrams))
+    }
+
+    pub(crate) fn from_thrift(t: thrift::TrigramIndexSummary) -> Result<Self> {
+        if t.indexed_files_count < 0 || t.unindexed_files_count < 0 {
+            bail!(ErrorKind::InvalidThrift(
+                "TrigramIndexSummary".into(),
+                "negative count".into()
+            ));
+        }
+        Ok(TrigramIndexSummary {
+            indexed_files_count: t.indexed_files_count as u64,
+            unindexed_files_count: t.unindexed_files_count as u64,
+            trigram_bloom: TrigramBloom::from_bytes(t.trigram_bloom)?,
+        })
+    }
+
+    pub(crate) fn into_thrift(self) -> thrift::TrigramIndexSummary {
+        thrift::TrigramIndexSummary {
+            indexed_files_count: self.indexed_files_count as i64,
+            unindexed_files_count: self.unindexed_files_count as i64,
+            trigram_bloom: self.trigram_bloom.into_bytes(),
+        }
+    }
+}
+
+/// Fixed-size bloom filter of trigrams.
+///
+/// An empty filter is stored with no bits, so that directories without
+/// any indexed files don't pay for the size of the filter.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct TrigramBloom {
+    bits: Vec<u8>,
+}
+
+impl TrigramBloom {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn bit_indexes(trigram: u32) -> [u32; 2] {
+        let shift = 32 - TRIGRAM_BLOOM_BITS.trailing_zeros();
+        [
+            trigram.wrapping_mul(0x9e37_79b1) >> shift,
+            trigram.wrapping_mul(0x85eb_ca6b) >> shift,
+        ]
+    }
+
+    pub fn insert(&mut self, trigram: u32) {
+        if self.bits.is_empty() {
+            self.bits = vec![0; TRIGRAM_BLOOM_BYTES];
+        }
+        for index in Self::bit_indexes(trigram).iter() {
+            self.bits[(index / 8) as usize] |= 1 << (index % 8);
+        }
+    }
+
+    /// Add all of the trigrams in another bloom filter to this one.
+    pub fn union(&mut self, other: &TrigramBloom) {
+        if other.bits.is_empty() {
+            return;
+        }
+        if self.bits.is_empty() {
+            self.bits = other.bits.clone();
+            return;
+        }
+        for (byte, other_byte) in self.bits.iter_mut().zip(other.bits.iter()) {
+            *byte |= other_byte;
+        }
+    }
+
+    pub fn may_contain(&self, trigram: u32) -> bool {
+        !self.bits.is_empty()
+            && Self::bit_indexes(trigram)
+                .iter()
+                .all(|index| self.bits[(index / 8) as usize] & (1 << (index % 8)) != 0)
+    }
+
+    pub fn may_contain_all(&self, trigrams: &[u32]) -> bool {
+        trigrams.iter().all(|trigram| self.may_contain(*trigram))
+    }
+
+    fn from_bytes(bits: Vec<u8>) -> Result<Self> {
+        if !bits.is_empty() && bits.len() != TRIGRAM_BLOOM_BYTES {
+            bail!(ErrorKind::InvalidThrift(
+                "TrigramIndexSummary".into(),
+                format!("invalid trigram bloom size {}", bits.len())
+            ));
+        }
+        Ok(TrigramBloom { bits })
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.bits
+    }
+}
+
+impl BlobstoreValue for TrigramIndex {
+    type Key = TrigramIndexId;
+
+    fn into_blob(self) -> TrigramIndexBlob {
+        let thrift = self.into_thrift();
+        let data = compact_protocol::serialize(&thrift);
+        let mut context = TrigramIndexIdContext::new();
+        context.update(&data);
+        let id = context.finish();
+        Blob::new(id, data)
+    }
+
+    fn from_blob(blob: Blob<Self::Key>) -> Result<Self> {
+        Self::from_bytes(blob.data().as_ref())
+    }
+}
+
+/// The trigrams contained in some file content.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ContentTrigrams {
+    content_id: ContentId,
+    trigrams: Vec<u32>,
+}
+
+impl ContentTrigrams {
+    /// Create the trigrams for some content.  The trigrams must be sorted
+    /// and deduplicated, as returned by `content_trigrams`.
+    pub fn new(content_id: ContentId, trigrams: Vec<u32>) -> Self {
+        Self {
+            content_id,
+            trigrams,
+        }
+    }
+
+    pub fn content_id(&self) -> &ContentId {
+        &self.content_id
+    }
+
+    pub fn trigrams(&self) -> &[u32] {
+        &self.trigrams
+    }
+
+    pub fn contains_all(&self, trigrams: &[u32]) -> bool {
+        trigrams
+            .iter()
+            .all(|trigram| self.trigrams.binary_search(trigram).is_ok())
+    }
+
+    pub(crate) fn from_thrift(t: thrift::ContentTrigrams) -> Result<ContentTrigrams> {
+        if t.trigrams.len() % 3 != 0 {
+            bail!(ErrorKind::InvalidThrift(
+                "ContentTrigrams".into(),
+                "trigrams length is not a multiple of 3".into()
+            ));
+        }
+        let content_id = ContentId::from_thrift(t.content_id)?;
+        let trigrams = t
+            .trigrams
+            .chunks(3)
+            .map(|c| trigram([c[0], c[1], c[2]]))
+            .collect();
+        Ok(ContentTrigrams {
+            content_id,
+            trigrams,
+        })
+    }
+
+    pub(crate) fn into_thrift(self) -> thrift::ContentTrigrams {
+        let mut trigrams = Vec::with_capacity(self.trigrams.len() * 3);
+        for t in self.trigrams {
+            trigrams.extend_from_slice(&t.to_be_bytes()[1..]);
+        }
+        thrift::ContentTrigrams {
+            content_id: self.content_id.into_thrift(),
+            trigrams,
+        }
+    }
+}
+
+impl BlobstoreValue for ContentTrigrams {
+    type Key = ContentTrigramsId;
+
+    fn into_blob(self) -> ContentTrigramsBlob {
+        let id = From::from(self.content_id);
+        let thrift = self.into_thrift();
+        let data = compact_protocol::serialize(&thrift);
+        Blob::new(id, data)
+    }
+
+    fn from_blob(blob: ContentTrigramsBlob) -> Result<Self> {
+        let thrift_tc = compact_protocol::deserialize(blob.data().as_ref())
+            .with_context(|| ErrorKind::BlobDeserializeError("ContentTrigrams".into()))?;
+        Self::from_thrift(thrift_tc)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_content_trigrams() {
+        assert_eq!(
+            content_trigrams(b"abcdabc"),
+            Some(vec![
+                trigram(*b"abc"),
+                trigram(*b"bcd"),
+                trigram(*b"cda"),
+                trigram(*b"dab"),
+            ])
+        );
+        assert_eq!(content_trigrams(b"ab"), Some(vec![]));
+        assert_eq!(content_trigrams(b"abc\0def"), None);
+    }
+
+    #[test]
+    fn test_content_trigrams_roundtrip() -> Result<()> {
+        let content_id = ContentId::from_byte_array([1; 32]);
+        let trigrams = content_trigrams(b"hello world").unwrap();
+        let content_trigrams = ContentTrigrams::new(content_id, trigrams.clone());
+        let blob = content_trigrams.clone().into_blob();
+        assert_eq!(*blob.id(), ContentTrigramsId::from(content_id));
+        let roundtripped = ContentTrigrams::from_blob(blob)?;
+        assert_eq!(roundtripped, content_trigrams);
+        assert!(roundtripped.contains_all(&trigrams[1..4]));
+        assert!(!roundtripped.contains_all(&[trigram(*b"xyz")]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_trigram_bloom() {
+        let mut bloom = TrigramBloom::new();
+        assert!(!bloom.may_contain(trigram(*b"abc")));
+        bloom.insert(trigram(*b"abc"));
+        let mut other = TrigramBloom::new();
+        other.insert(trigram(*b"def"));
+        bloom.union(&other);
+        assert!(bloom.may_contain_all(&[trigram(*b"abc"), trigram(*b"def")]));
+        assert!(!TrigramBloom::new().may_contain_all(&[trigram(*b"abc")]));
+        assert!(TrigramBloom::new().may_contain_all(&[]));
+    }
+}
@@ -28,7 +28,9 @@ use crate::{
     fsnode::Fsnode,
     hash::{Blake2, Blake2Prefix, Context},
     rawbundle2::RawBundle2,
+    skeleton_manifest::SkeletonManifest,
     thrift,
+    trigram_index::{ContentTrigrams, TrigramIndex},
     unode::{FileUnode, ManifestUnode},
 };
 
@@ -103,6 +105,18 @@ pub struct DeletedManifestId(Blake2);
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
 pub struct FsnodeId(Blake2);
 
+/// An identifier for a skeleton manifest
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub struct SkeletonManifestId(Blake2);
+
+/// An identifier for a trigram index
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub struct TrigramIndexId(Blake2);
+
+/// An identifier for the trigrams of some content
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub struct ContentTrigramsId(Blake2);
+
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
 pub struct FastlogBatchId(Blake2);
 
@@ -382,6 +396,20 @@ impl_typed_hash! {
     context_key => "fsnode",
 }
 
+impl_typed_hash! {
+    hash_type => SkeletonManifestId,
+    value_type => SkeletonManifest,
+    context_type => SkeletonManifestIdContext,
+    context_key => "skeletonmanifest",
+}
+
+impl_typed_hash! {
+    hash_type => TrigramIndexId,
+    value_type => TrigramIndex,
+    context_type => TrigramIndexIdContext,
+    context_key => "trigramindex",
+}
+
 impl_typed_hash_no_context! {
     hash_type => ContentMetadataId,
     value_type => ContentMetadata,
@@ -391,6 +419,15 @@ impl_typed_hash_loadable_storable! {
     hash_type => ContentMetadataId,
 }
 
+impl_typed_hash_no_context! {
+    hash_type => ContentTrigramsId,
+    value_type => ContentTrigrams,
+}
+
+impl_typed_hash_loadable_storable! {
+    hash_type => ContentTrigramsId,
+}
+
 impl_typed_hash! {
     hash_type => FastlogBatchId,
     value_type => FastlogBatch,
@@ -427,6 +464,35 @@ impl MononokeId for ContentMetadataId {
     }
 }
 
+impl ContentTrigramsId {
+    const PREFIX: &'static str = "content_trigrams.blake2";
+}
+
+impl From<ContentId> for ContentTrigramsId {
+    fn from(content: ContentId) -> Self {
+        Self { 0: content.0 }
+    }
+}
+
+impl MononokeId for ContentTrigramsId {
+    type Value = ContentTrigrams;
+
+    #[inline]
+    fn blobstore_key(&self) -> String {
+        format!("{}.{}", Self::PREFIX, self.0)
+    }
+
+    #[inline]
+    fn blobstore_key_prefix() -> String {
+        Self::PREFIX.to_string()
+    }
+
+    #[inline]
+    fn sampling_fingerprint(&self) -> u64 {
+        self.0.sampling_fingerprint()
+    }
+}
+
 impl ChangesetIdPrefix {
     pub const fn new(blake2prefix: Blake2Prefix) -> Self {
         ChangesetIdPrefix(blake2prefix)
@@ -514,12 +580,27 @@ mod test {
         let id = FsnodeId::from_byte_array([1; 32]);
         assert_eq!(id.blobstore_key(), format!("fsnode.blake2.{}", id));
 
+        let id = SkeletonManifestId::from_byte_array([1; 32]);
+        assert_eq!(
+            id.blobstore_key(),
+            format!("skeletonmanifest.blake2.{}", id)
+        );
+
+        let id = TrigramIndexId::from_byte_array([1; 32]);
+        assert_eq!(id.blobstore_key(), format!("trigramindex.blake2.{}", id));
+
         let id = ContentMetadataId::from_byte_array([1; 32]);
         assert_eq!(
             id.blobstore_key(),
             format!("content_metadata.blake2.{}", id)
         );
 
+        let id = ContentTrigramsId::from_byte_array([1; 32]);
+        assert_eq!(
+            id.blobstore_key(),
+            format!("content_trigrams.blake2.{}", id)
+        );
+
         let id = FastlogBatchId::from_byte_array([1; 32]);
         assert_eq!(id.blobstore_key(), format!("fastlogbatch.blake2.{}", id));
     }
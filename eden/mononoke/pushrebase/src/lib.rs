@@ -75,6 +75,7 @@ use mononoke_types::{
     Timestamp,
 };
 use revset::RangeNodeStream;
+use skeleton_manifest::{find_case_conflict_with_changes, RootSkeletonManifestId};
 use slog::info;
 use std::cmp::{max, Ordering};
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
@@ -119,6 +120,8 @@ pub enum PushrebaseError {
     Conflicts(Vec<PushrebaseConflict>),
     #[error("PotentialCaseConflict: the change this commit introduces at {0} may conflict with other commits. Rebase and retry.")]
     PotentialCaseConflict(MPath),
+    #[error("CaseConflict: the changes introduce a case conflict between {0} and {1}")]
+    CaseConflict(MPath, MPath),
     #[error("Pushrebase over merge")]
     RebaseOverMerge,
     #[error("Root is too far behind")]
@@ -196,8 +199,8 @@ impl HgReplayData {
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct PushrebaseConflict {
-    left: MPath,
-    right: MPath,
+    pub left: MPath,
+    pub right: MPath,
 }
 
 impl PushrebaseConflict {
@@ -326,6 +329,60 @@ async fn check_filenodes_backfilled<'a>(
     }
 }
 
+/// Check that the pushed commits don't introduce case conflicts into the
+/// tree of the commit they are being rebased onto.  Only the skeleton
+/// manifest of `onto` is needed, so nothing is derived for the pushed
+/// commits.  This is skipped for repos that don't derive skeleton
+/// manifests; the check in `rebase_in_loop` still catches conflicts between
+/// the changed files.
+async fn check_new_case_conflicts(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    root: ChangesetId,
+    onto: ChangesetId,
+    client_bcs: &[BonsaiChangeset],
+) -> Result<(), PushrebaseError> {
+    if !repo
+        .get_derived_data_config()
+        .derived_data_types
+        .contains(RootSkeletonManifestId::NAME)
+    {
+        return Ok(());
+    }
+
+    let onto_skeleton_manifest = RootSkeletonManifestId::derive(ctx.clone(), repo.clone(), onto)
+        .from_err()
+        .compat()
+        .await?;
+
+    // Work out the net effect of the pushed commits, oldest first.  The
+    // range of client commits includes the root, which is skipped.
+    let mut changes = BTreeMap::new();
+    for bcs in client_bcs
+        .iter()
+        .rev()
+        .filter(|bcs| bcs.get_changeset_id() != root)
+    {
+        for (path, change) in bcs.file_changes() {
+            changes.insert(path.clone(), change.is_some());
+        }
+    }
+    let (added, deleted): (Vec<_>, Vec<_>) = changes.into_iter().partition(|(_, added)| *added);
+
+    let conflict = find_case_conflict_with_changes(
+        ctx,
+        repo.blobstore(),
+        onto_skeleton_manifest.into_skeleton_manifest_id(),
+        added.into_iter().map(|(path, _)| path).collect(),
+        deleted.into_iter().map(|(path, _)| path).collect(),
+    )
+    .await?;
+    match conflict {
+        Some((path1, path2)) => Err(PushrebaseError::CaseConflict(path1, path2)),
+        None => Ok(()),
+    }
+}
+
 async fn rebase_in_loop(
     ctx: &CoreContext,
     repo: &BlobRepo,
@@ -366,6 +423,9 @@ async fn rebase_in_loop(
             if let Some(conflict) = conflict {
                 return Err(PushrebaseError::PotentialCaseConflict(conflict));
             }
+
+            check_new_case_conflicts(&ctx, &repo, root, bookmark_val.unwrap_or(root), client_bcs)
+                .await?;
         }
 
         let server_cf = find_changed_files(
@@ -2306,6 +2366,71 @@ mod tests {
         })
     }
 
+    #[fbinit::test]
+    fn pushrebase_case_conflict_with_existing_tree(fb: FacebookInit) -> Result<(), Error> {
+        let mut runtime = tokio_compat::runtime::Runtime::new().unwrap();
+        runtime.block_on_std(async move {
+            let ctx = CoreContext::test_mock(fb);
+            let repo = many_files_dirs::getrepo(fb).await;
+            let root = repo
+                .get_bonsai_from_hg(
+                    ctx.clone(),
+                    HgChangesetId::from_str("2f866e7e549760934e31bf0420a873f65100ad63")?,
+                )
+                .compat()
+                .await?
+                .ok_or(Error::msg("Root is missing"))?;
+
+            // The conflicting directory is already in the root, so only the
+            // skeleton manifest check can find this conflict.
+            let bcs = CreateCommitContext::new(&ctx, &repo, vec![root])
+                .add_file("Dir1/file_1_in_dir1", "data")
+                .commit()
+                .await?;
+
+            let hgcss = hashset![
+                repo.get_hg_from_bonsai_changeset(ctx.clone(), bcs)
+                    .compat()
+                    .await?
+            ];
+
+            let book = master_bookmark();
+            set_bookmark(
+                ctx.clone(),
+                repo.clone(),
+                &book.bookmark,
+                "2f866e7e549760934e31bf0420a873f65100ad63",
+            )
+            .await?;
+
+            let result =
+                do_pushrebase(&ctx, &repo, &Default::default(), &book, &hgcss, &None).await;
+            match result {
+                Err(PushrebaseError::CaseConflict(path1, path2)) => {
+                    assert_eq!(path1, MPath::new("Dir1")?);
+                    assert_eq!(path2, MPath::new("dir1")?);
+                }
+                _ => panic!("push-rebase should have failed with case conflict"),
+            };
+
+            // make sure that it is succeeds with disabled casefolding
+            do_pushrebase(
+                &ctx,
+                &repo,
+                &PushrebaseFlags {
+                    casefolding_check: false,
+                    ..Default::default()
+                },
+                &book,
+                &hgcss,
+                &None,
+            )
+            .await?;
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn pushrebase_intersect_changed() -> Result<(), Error> {
         match intersect_changed_files(
@@ -42,8 +42,8 @@ use mercurial_bundles::{
 use mercurial_revlog::{self, RevlogChangeset};
 use mercurial_types::{
     blobs::{fetch_manifest_envelope, File},
-    FileBytes, HgBlobNode, HgChangesetId, HgFileNodeId, HgManifestId, HgParents, MPath, RevFlags,
-    NULL_CSID,
+    FileBytes, HgBlobNode, HgChangesetId, HgFileNodeId, HgManifestId, HgNodeHash, HgParents, MPath,
+    RevFlags, NULL_CSID,
 };
 use mononoke_types::{hash::Sha256, ChangesetId, ContentId};
 use phases::{Phase, Phases};
@@ -178,16 +178,105 @@ pub async fn create_getbundle_response(
 
     // Phases part has to be after the changegroup part.
     if return_phases {
-        let phase_heads = find_phase_heads(&ctx, &blobrepo, &heads, &phases).await?;
-        parts.push(parts::phases_part(
-            ctx.clone(),
-            old_stream::iter_ok(phase_heads),
-        )?);
+        parts.push(create_phases_part(&ctx, &blobrepo, &heads).await?);
     }
 
     Ok(parts)
 }
 
+/// Create a changegroup part with all commits between `common` and `heads`,
+/// together with their root and directory manifests and their filelogs. Unlike
+/// the getbundle changegroup, this can be applied by a client that does not
+/// fetch trees and files separately, e.g. stock Mercurial via `hg unbundle`.
+pub async fn create_full_changegroup_part(
+    ctx: &CoreContext,
+    blobrepo: &BlobRepo,
+    common: Vec<HgChangesetId>,
+    heads: Vec<HgChangesetId>,
+    lca_hint: Arc<dyn LeastCommonAncestorsHint>,
+    lfs_params: &SessionLfsParams,
+) -> Result<PartEncodeBuilder> {
+    let common: HashSet<_> = common.into_iter().collect();
+    let commits_to_send = find_commits_to_send(ctx, blobrepo, &common, &heads, &lca_hint).await?;
+
+    let hg_cs_ids: Vec<HgChangesetId> = stream::iter(commits_to_send.clone())
+        .map(|bcs_id| {
+            blobrepo
+                .get_hg_from_bonsai_changeset(ctx.clone(), bcs_id)
+                .compat()
+        })
+        .buffered(100)
+        .try_collect()
+        .await?;
+
+    // Commits are ordered oldest first, so every manifest and filelog group
+    // built from them lists parents before children.
+    let (manifests, filenodes) =
+        get_manifests_and_filenodes(ctx, blobrepo, hg_cs_ids, lfs_params).await?;
+
+    let mut root_manifests = vec![];
+    let mut tree_paths = vec![];
+    let mut trees: HashMap<MPath, Vec<_>> = HashMap::new();
+    for (path, mf_id, linknode) in manifests {
+        match path {
+            None => root_manifests.push((None, mf_id, linknode)),
+            Some(path) => {
+                let entries = trees.entry(path.clone()).or_insert_with(|| {
+                    tree_paths.push(path.clone());
+                    vec![]
+                });
+                entries.push((Some(path), mf_id, linknode));
+            }
+        }
+    }
+    let trees: Vec<_> = tree_paths
+        .into_iter()
+        .map(|path| {
+            let manifests = trees.remove(&path).unwrap_or_default();
+            (path, manifests)
+        })
+        .collect();
+
+    let manifest_buffer_size = 100;
+    let blobstore = blobrepo.get_blobstore();
+    let manifest_entries =
+        create_manifest_entries_stream(ctx.clone(), blobstore.clone(), root_manifests)
+            .buffered(manifest_buffer_size);
+    let tree_entries = old_stream::iter_ok(trees)
+        .map({
+            cloned!(ctx);
+            move |(path, manifests)| {
+                create_manifest_entries_stream(ctx.clone(), blobstore.clone(), manifests)
+                    .buffered(manifest_buffer_size)
+                    .collect()
+                    .map(move |entries| (path, entries))
+            }
+        })
+        .buffered(10);
+
+    let changelogentries = create_changelog_entries(ctx, blobrepo, commits_to_send);
+    let filenode_entries = create_filenodes(ctx.clone(), blobrepo.clone(), filenodes);
+
+    parts::full_changegroup_part(
+        changelogentries,
+        manifest_entries,
+        tree_entries,
+        filenode_entries,
+    )
+}
+
+/// Create a part with the phase of each of `heads` and of the first public
+/// ancestors of the draft ones. It has to follow the changegroup part.
+pub async fn create_phases_part(
+    ctx: &CoreContext,
+    blobrepo: &BlobRepo,
+    heads: &[HgChangesetId],
+) -> Result<PartEncodeBuilder> {
+    let phases = blobrepo.get_phases();
+    let phase_heads = find_phase_heads(ctx, blobrepo, heads, &phases).await?;
+    parts::phases_part(ctx.clone(), old_stream::iter_ok(phase_heads))
+}
+
 fn report_draft_commits(ctx: &CoreContext, draft_commits: &HashSet<HgChangesetId>) {
     debug!(
         ctx.logger(),
@@ -322,10 +411,33 @@ async fn create_hg_changeset_part(
     maybe_prepared_filenode_entries: Option<HashMap<MPath, Vec<PreparedFilenodeEntry>>>,
     lfs_params: &SessionLfsParams,
 ) -> Result<PartEncodeBuilder> {
+    let changelogentries = create_changelog_entries(ctx, blobrepo, nodes_to_send);
+
+    let maybe_filenode_entries = match maybe_prepared_filenode_entries {
+        Some(prepared_filenode_entries) => Some(
+            create_filenodes(ctx.clone(), blobrepo.clone(), prepared_filenode_entries).boxify(),
+        ),
+        None => None,
+    };
+
+    let cg_version = if lfs_params.threshold.is_some() {
+        CgVersion::Cg3Version
+    } else {
+        CgVersion::Cg2Version
+    };
+
+    parts::changegroup_part(changelogentries, maybe_filenode_entries, cg_version)
+}
+
+fn create_changelog_entries(
+    ctx: &CoreContext,
+    blobrepo: &BlobRepo,
+    nodes_to_send: Vec<ChangesetId>,
+) -> impl OldStream<Item = (HgNodeHash, HgBlobNode), Error = Error> + Send + 'static {
     let map_chunk_size = 100;
     let load_buffer_size = 1000;
 
-    let changelogentries = stream::iter(nodes_to_send)
+    stream::iter(nodes_to_send)
         .chunks(map_chunk_size)
         .then({
             cloned!(ctx, blobrepo);
@@ -396,22 +508,7 @@ async fn create_hg_changeset_part(
             ))
         })
         .boxed()
-        .compat();
-
-    let maybe_filenode_entries = match maybe_prepared_filenode_entries {
-        Some(prepared_filenode_entries) => Some(
-            create_filenodes(ctx.clone(), blobrepo.clone(), prepared_filenode_entries).boxify(),
-        ),
-        None => None,
-    };
-
-    let cg_version = if lfs_params.threshold.is_some() {
-        CgVersion::Cg3Version
-    } else {
-        CgVersion::Cg2Version
-    };
-
-    parts::changegroup_part(changelogentries, maybe_filenode_entries, cg_version)
+        .compat()
 }
 
 async fn hg_to_bonsai_stream(
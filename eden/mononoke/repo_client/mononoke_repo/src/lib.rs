@@ -16,7 +16,7 @@ use getbundle_response::SessionLfsParams;
 use hooks::HookManager;
 use metaconfig_types::{
     BookmarkAttrs, BookmarkParams, InfinitepushParams, LfsParams, PushParams, PushrebaseParams,
-    RepoReadOnly,
+    RepoReadOnly, WireprotoQuota,
 };
 use mononoke_types::RepositoryId;
 use mutable_counters::MutableCounters;
@@ -37,10 +37,12 @@ use std::{
 use streaming_clone::SqlStreamingChunksFetcher;
 
 pub use builder::MononokeRepoBuilder;
+pub use quotas::{QuotaPermit, QuotaViolation, WireprotoQuotas};
 
 mod builder;
 #[cfg(fbcode_build)]
 mod facebook;
+mod quotas;
 
 #[derive(Clone)]
 pub struct SqlStreamingCloneConfig {
@@ -68,6 +70,8 @@ pub struct MononokeRepo {
     // This field is `None` if we don't want recording to happen
     maybe_reverse_filler_queue: Option<Arc<dyn ReverseFillerQueue>>,
     push_params: PushParams,
+    // Per-client quotas on wireproto requests, shared by all connections
+    wireproto_quotas: Arc<WireprotoQuotas>,
 }
 
 impl MononokeRepo {
@@ -88,6 +92,7 @@ impl MononokeRepo {
         mutable_counters: Arc<dyn MutableCounters>,
         maybe_reverse_filler_queue: Option<Arc<dyn ReverseFillerQueue>>,
         push_params: PushParams,
+        wireproto_quotas: Vec<WireprotoQuota>,
     ) -> Result<Self, Error> {
         let lfs_rolled_out_hostnames = Arc::new(RwLock::new(HashSet::new()));
         if let Some(rollout_smc_tier) = &lfs_params.rollout_smc_tier {
@@ -122,6 +127,7 @@ impl MononokeRepo {
             lfs_rolled_out_hostnames,
             maybe_reverse_filler_queue,
             push_params,
+            wireproto_quotas: Arc::new(WireprotoQuotas::new(wireproto_quotas)),
         })
     }
 
@@ -213,6 +219,10 @@ impl MononokeRepo {
     pub fn lca_hint(&self) -> Arc<dyn LeastCommonAncestorsHint> {
         self.lca_hint.clone()
     }
+
+    pub fn wireproto_quotas(&self) -> &Arc<WireprotoQuotas> {
+        &self.wireproto_quotas
+    }
 }
 
 pub fn streaming_clone(
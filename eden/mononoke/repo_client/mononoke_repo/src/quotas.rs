@@ -0,0 +1,416 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use metaconfig_types::WireprotoQuota;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often usage for clients that are no longer active is forgotten.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Bytes served by a request are counted against its quotas in batches of at
+/// least this size, so that the shared usage isn't locked for every chunk.
+const BYTES_FLUSH_THRESHOLD: u64 = 64 * 1024;
+
+/// Tracks how much each client is using the wireproto quotas of a repo.
+/// This is shared by all connections to the repo.
+pub struct WireprotoQuotas {
+    quotas: Vec<WireprotoQuota>,
+    usage: Mutex<QuotaUsage>,
+}
+
+struct QuotaUsage {
+    /// Usage of each quota, indexed in the same way as the quotas, keyed by
+    /// client identity.
+    clients: Vec<HashMap<String, ClientUsage>>,
+    last_pruned: Instant,
+}
+
+struct ClientUsage {
+    interval_start: Instant,
+    requests: u64,
+    bytes: u64,
+    in_flight: u64,
+}
+
+impl ClientUsage {
+    fn new(now: Instant) -> Self {
+        Self {
+            interval_start: now,
+            requests: 0,
+            bytes: 0,
+            in_flight: 0,
+        }
+    }
+
+    /// Start a new interval if the current one has ended.
+    fn refresh(&mut self, now: Instant, interval: Duration) {
+        if now.duration_since(self.interval_start) >= interval {
+            self.interval_start = now;
+            self.requests = 0;
+            self.bytes = 0;
+        }
+    }
+
+    fn is_idle(&self, now: Instant, interval: Duration) -> bool {
+        self.in_flight == 0 && now.duration_since(self.interval_start) >= interval
+    }
+
+    /// Check whether one more request would exceed the quota, and if so,
+    /// describe why.
+    fn check(&self, quota: &WireprotoQuota) -> Option<String> {
+        let interval = quota.interval.as_secs();
+        if let Some(max_requests) = quota.max_requests {
+            if self.requests >= max_requests {
+                return Some(format!(
+                    "more than {} requests in {}s",
+                    max_requests, interval
+                ));
+            }
+        }
+        if let Some(max_concurrent_requests) = quota.max_concurrent_requests {
+            if self.in_flight >= max_concurrent_requests {
+                return Some(format!(
+                    "more than {} concurrent requests",
+                    max_concurrent_requests
+                ));
+            }
+        }
+        if let Some(max_bytes) = quota.max_bytes {
+            if self.bytes >= max_bytes {
+                return Some(format!(
+                    "more than {} bytes served in {}s",
+                    max_bytes, interval
+                ));
+            }
+        }
+        None
+    }
+
+    /// Check whether the bytes served so far exceed the quota, and if so,
+    /// describe why.
+    fn check_bytes(&self, quota: &WireprotoQuota) -> Option<String> {
+        let max_bytes = quota.max_bytes?;
+        if self.bytes > max_bytes {
+            Some(format!(
+                "more than {} bytes served in {}s",
+                max_bytes,
+                quota.interval.as_secs()
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// A request that exceeds one of the quotas.
+#[derive(Clone, Debug)]
+pub struct QuotaViolation {
+    /// Name of the quota that was exceeded.
+    pub quota: String,
+    /// Identity of the client that exceeded the quota.
+    pub identity: String,
+    /// Description of the limit that was exceeded.
+    pub reason: String,
+    /// Whether the quota is only logged rather than enforced.
+    pub log_only: bool,
+}
+
+impl fmt::Display for QuotaViolation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "{} exceeded quota {}: {}",
+            self.identity, self.quota, self.reason
+        )
+    }
+}
+
+/// Permission to run a request within the quotas. The request counts as in
+/// flight until the permit is dropped.
+pub struct QuotaPermit {
+    quotas: Arc<WireprotoQuotas>,
+    identity: String,
+    applicable: Vec<usize>,
+    tracks_bytes: bool,
+    violations: Vec<QuotaViolation>,
+    /// Bytes served that haven't been counted against the quotas yet.
+    pending_bytes: u64,
+    /// Quotas whose byte limit this request has already been found to exceed.
+    exceeded_bytes: Vec<usize>,
+}
+
+impl WireprotoQuotas {
+    pub fn new(quotas: Vec<WireprotoQuota>) -> Self {
+        let clients = quotas.iter().map(|_| HashMap::new()).collect();
+        Self {
+            quotas,
+            usage: Mutex::new(QuotaUsage {
+                clients,
+                last_pruned: Instant::now(),
+            }),
+        }
+    }
+
+    /// Check the quotas that apply to `command` for the client with the given
+    /// identity.  If none of the enforced quotas would be exceeded, the
+    /// request is counted against all of them and a permit is returned.
+    /// Otherwise, the request is not counted and the violation is returned.
+    pub fn acquire(
+        self: &Arc<Self>,
+        identity: &str,
+        command: &str,
+    ) -> Result<QuotaPermit, QuotaViolation> {
+        let applicable: Vec<usize> = self
+            .quotas
+            .iter()
+            .enumerate()
+            .filter(|(_, quota)| quota.applies_to(command))
+            .map(|(idx, _)| idx)
+            .collect();
+        let tracks_bytes = applicable
+            .iter()
+            .any(|idx| self.quotas[*idx].max_bytes.is_some());
+        let mut violations = Vec::new();
+
+        if !applicable.is_empty() {
+            let now = Instant::now();
+            let mut usage = self.usage.lock().expect("lock poisoned");
+            self.maybe_prune(&mut usage, now);
+
+            for idx in applicable.iter() {
+                let quota = &self.quotas[*idx];
+                let client = usage.clients[*idx]
+                    .entry(identity.to_string())
+                    .or_insert_with(|| ClientUsage::new(now));
+                client.refresh(now, quota.interval);
+                if let Some(reason) = client.check(quota) {
+                    let violation = QuotaViolation {
+                        quota: quota.name.clone(),
+                        identity: identity.to_string(),
+                        reason,
+                        log_only: quota.log_only,
+                    };
+                    if quota.log_only {
+                        violations.push(violation);
+                    } else {
+                        return Err(violation);
+                    }
+                }
+            }
+
+            for idx in applicable.iter() {
+                if let Some(client) = usage.clients[*idx].get_mut(identity) {
+                    client.requests += 1;
+                    client.in_flight += 1;
+                }
+            }
+        }
+
+        Ok(QuotaPermit {
+            quotas: self.clone(),
+            identity: identity.to_string(),
+            applicable,
+            tracks_bytes,
+            violations,
+            pending_bytes: 0,
+            exceeded_bytes: Vec::new(),
+        })
+    }
+
+    fn maybe_prune(&self, usage: &mut QuotaUsage, now: Instant) {
+        if now.duration_since(usage.last_pruned) < PRUNE_INTERVAL {
+            return;
+        }
+        for (quota, clients) in self.quotas.iter().zip(usage.clients.iter_mut()) {
+            clients.retain(|_, client| !client.is_idle(now, quota.interval));
+        }
+        usage.last_pruned = now;
+    }
+}
+
+impl QuotaPermit {
+    /// Violations of log-only quotas by this request.
+    pub fn violations(&self) -> &[QuotaViolation] {
+        &self.violations
+    }
+
+    /// Count bytes served in response to this request against the quotas.
+    /// Bytes are counted in batches, and whatever is left is counted when the
+    /// permit is dropped.
+    ///
+    /// Returns the quotas whose byte limit this request has newly exceeded.
+    /// The request should be aborted if any of them is enforced.
+    pub fn record_bytes(&mut self, bytes: u64) -> Vec<QuotaViolation> {
+        if !self.tracks_bytes {
+            return Vec::new();
+        }
+        self.pending_bytes += bytes;
+        if self.pending_bytes < BYTES_FLUSH_THRESHOLD {
+            return Vec::new();
+        }
+        self.flush_bytes()
+    }
+
+    fn flush_bytes(&mut self) -> Vec<QuotaViolation> {
+        let bytes = std::mem::replace(&mut self.pending_bytes, 0);
+        let mut violations = Vec::new();
+        if bytes == 0 {
+            return violations;
+        }
+        let now = Instant::now();
+        let mut usage = self.quotas.usage.lock().expect("lock poisoned");
+        for idx in self.applicable.iter() {
+            let quota = &self.quotas.quotas[*idx];
+            if let Some(client) = usage.clients[*idx].get_mut(&self.identity) {
+                client.refresh(now, quota.interval);
+                client.bytes += bytes;
+                if self.exceeded_bytes.contains(idx) {
+                    continue;
+                }
+                if let Some(reason) = client.check_bytes(quota) {
+                    self.exceeded_bytes.push(*idx);
+                    violations.push(QuotaViolation {
+                        quota: quota.name.clone(),
+                        identity: self.identity.clone(),
+                        reason,
+                        log_only: quota.log_only,
+                    });
+                }
+            }
+        }
+        violations
+    }
+}
+
+impl Drop for QuotaPermit {
+    fn drop(&mut self) {
+        if self.applicable.is_empty() {
+            return;
+        }
+        self.flush_bytes();
+        let mut usage = self.quotas.usage.lock().expect("lock poisoned");
+        for idx in self.applicable.iter() {
+            if let Some(client) = usage.clients[*idx].get_mut(&self.identity) {
+                client.in_flight = client.in_flight.saturating_sub(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn quota(name: &str, commands: &[&str]) -> WireprotoQuota {
+        WireprotoQuota {
+            name: name.to_string(),
+            commands: commands.iter().map(|c| c.to_string()).collect(),
+            interval: Duration::from_secs(3600),
+            max_requests: None,
+            max_concurrent_requests: None,
+            max_bytes: None,
+            log_only: false,
+        }
+    }
+
+    #[test]
+    fn test_max_requests() {
+        let quotas = Arc::new(WireprotoQuotas::new(vec![WireprotoQuota {
+            max_requests: Some(2),
+            ..quota("getbundle", &["getbundle"])
+        }]));
+
+        assert!(quotas.acquire("alice", "getbundle").is_ok());
+        assert!(quotas.acquire("alice", "getbundle").is_ok());
+        let violation = quotas
+            .acquire("alice", "getbundle")
+            .err()
+            .expect("third request should be rejected");
+        assert_eq!(violation.quota, "getbundle");
+        assert_eq!(violation.identity, "alice");
+
+        // Other clients and other commands are not affected.
+        assert!(quotas.acquire("bob", "getbundle").is_ok());
+        assert!(quotas.acquire("alice", "gettreepack").is_ok());
+    }
+
+    #[test]
+    fn test_max_concurrent_requests() {
+        let quotas = Arc::new(WireprotoQuotas::new(vec![WireprotoQuota {
+            max_concurrent_requests: Some(1),
+            ..quota("all", &[])
+        }]));
+
+        let permit = quotas.acquire("alice", "gettreepack").unwrap();
+        assert!(quotas.acquire("alice", "getpackv1").is_err());
+        drop(permit);
+        assert!(quotas.acquire("alice", "getpackv1").is_ok());
+    }
+
+    #[test]
+    fn test_max_bytes() {
+        let quotas = Arc::new(WireprotoQuotas::new(vec![WireprotoQuota {
+            max_bytes: Some(100),
+            ..quota("getpack", &["getpackv1", "getpackv2"])
+        }]));
+
+        let mut permit = quotas.acquire("alice", "getpackv1").unwrap();
+        assert!(permit.record_bytes(60).is_empty());
+        drop(permit);
+        let mut permit = quotas.acquire("alice", "getpackv2").unwrap();
+        // Small amounts are only counted once the permit is dropped.
+        assert!(permit.record_bytes(60).is_empty());
+        drop(permit);
+        assert!(quotas.acquire("alice", "getpackv1").is_err());
+    }
+
+    #[test]
+    fn test_max_bytes_mid_request() {
+        let quotas = Arc::new(WireprotoQuotas::new(vec![
+            WireprotoQuota {
+                max_bytes: Some(BYTES_FLUSH_THRESHOLD),
+                ..quota("enforced", &[])
+            },
+            WireprotoQuota {
+                max_bytes: Some(1),
+                log_only: true,
+                ..quota("log_only", &[])
+            },
+        ]));
+
+        let mut permit = quotas.acquire("alice", "getpackv1").unwrap();
+        let violations = permit.record_bytes(BYTES_FLUSH_THRESHOLD);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].quota, "log_only");
+        assert!(violations[0].log_only);
+
+        // Each quota is only reported once per request.
+        let violations = permit.record_bytes(BYTES_FLUSH_THRESHOLD);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].quota, "enforced");
+        assert!(!violations[0].log_only);
+        assert!(permit.record_bytes(BYTES_FLUSH_THRESHOLD).is_empty());
+    }
+
+    #[test]
+    fn test_log_only() {
+        let quotas = Arc::new(WireprotoQuotas::new(vec![WireprotoQuota {
+            max_requests: Some(1),
+            log_only: true,
+            ..quota("all", &[])
+        }]));
+
+        let permit = quotas.acquire("alice", "heads").unwrap();
+        assert!(permit.violations().is_empty());
+        let permit = quotas.acquire("alice", "heads").unwrap();
+        assert_eq!(permit.violations().len(), 1);
+        assert!(permit.violations()[0].log_only);
+    }
+}
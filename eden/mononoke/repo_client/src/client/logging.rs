@@ -14,6 +14,7 @@ use futures::future::TryFutureExt;
 use futures_ext::FutureExt;
 use futures_old::{future, Future};
 use futures_stats::{FutureStats, StreamStats};
+use metaconfig_types::WireprotoLocalCaptureConfig;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 #[cfg(fbcode_build)]
 use scribe::ScribeClient;
@@ -22,7 +23,12 @@ use scuba_ext::{
 };
 use stats::prelude::*;
 use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::num::NonZeroU64;
+use std::sync::mpsc::{sync_channel, SyncSender};
 use std::sync::Arc;
+use std::thread;
 use std::time::Duration;
 use time_ext::DurationExt;
 
@@ -34,13 +40,20 @@ define_stats! {
     wireproto_scribe_success: timeseries(Rate, Sum),
     wireproto_scribe_failure: timeseries(Rate, Sum),
     wireproto_serialization_failure: timeseries(Rate, Sum),
+    wireproto_local_capture_success: timeseries(Rate, Sum),
+    wireproto_local_capture_failure: timeseries(Rate, Sum),
 }
 
+/// How many captured requests may be waiting to be written to the local capture file before
+/// new ones are dropped.
+const LOCAL_CAPTURE_QUEUE_SIZE: usize = 1000;
+
 pub struct WireprotoLogging {
     reponame: String,
     scribe_args: Option<(ScribeClientImplementation, String)>,
     blobstore_and_threshold: Option<(Arc<dyn Blobstore>, u64)>,
     scuba_builder: ScubaSampleBuilder,
+    local_capture: Option<LocalCapture>,
 }
 
 impl WireprotoLogging {
@@ -49,25 +62,153 @@ impl WireprotoLogging {
         reponame: String,
         scribe_category: Option<String>,
         blobstore_and_threshold: Option<(Arc<dyn Blobstore>, u64)>,
-        log_file: Option<&str>,
+        local_capture: Option<WireprotoLocalCaptureConfig>,
     ) -> Result<Self, Error> {
         let scribe_args = scribe_category.map(|cat| (ScribeClientImplementation::new(fb), cat));
 
-        // We use a Scuba sample builder to produce samples to log. We also use that to allow
-        // logging to a file: we never log to an actual Scuba category here.
+        // We use a Scuba sample builder to produce samples to log. We never log to an actual
+        // Scuba category here.
         let mut scuba_builder = ScubaSampleBuilder::with_discard();
         scuba_builder.add_common_server_data();
-        if let Some(log_file) = log_file {
-            scuba_builder = scuba_builder.with_log_file(log_file)?;
-        }
+
+        let local_capture = local_capture.map(LocalCapture::new).transpose()?;
 
         Ok(Self {
             reponame,
             scribe_args,
             blobstore_and_threshold,
             scuba_builder,
+            local_capture,
+        })
+    }
+
+    /// Build the sample that is logged for a wireproto request, without its arguments. Its JSON
+    /// form is what fastreplay reads.
+    pub fn sample(
+        &self,
+        ctx: &CoreContext,
+        command: String,
+        session_id: SessionId,
+        duration: Duration,
+    ) -> ScubaSampleBuilder {
+        // Use a ScubaSampleBuilder to build a sample to send in Scribe. Reach into the other
+        // Scuba sample to grab a few datapoints from there as well.
+        let mut builder = self.scuba_builder.clone();
+        builder
+            .add("time", Utc::now().timestamp())
+            .add("command", command)
+            .add("duration", duration.as_micros_unchecked())
+            .add("source_control_server_type", "mononoke")
+            .add("mononoke_session_uuid", session_id.into_string())
+            .add("reponame", self.reponame.clone());
+
+        if let Some(client_hostname) = ctx.session().source_hostname() {
+            builder.add("client_hostname", client_hostname.clone());
+        }
+
+        if let Some(user) = ctx.session().user_unix_name() {
+            builder.add("user", user.clone());
+        }
+
+        builder
+    }
+}
+
+/// Captures a sample of wireproto requests to a local file, one JSON object per line, in the
+/// format that fastreplay reads. The lines are written by a dedicated thread, so that requests
+/// never wait for the filesystem. If that thread falls behind, new lines are dropped.
+struct LocalCapture {
+    sample_rate: NonZeroU64,
+    sender: SyncSender<String>,
+}
+
+impl LocalCapture {
+    fn new(config: WireprotoLocalCaptureConfig) -> Result<Self, Error> {
+        let sample_rate = config.sample_rate;
+        let mut writer = LocalCaptureWriter::new(config)?;
+        let (sender, receiver) = sync_channel::<String>(LOCAL_CAPTURE_QUEUE_SIZE);
+        thread::Builder::new()
+            .name("wireproto_local_capture".to_string())
+            .spawn(move || {
+                for line in receiver {
+                    if writer.write(&line).is_ok() {
+                        STATS::wireproto_local_capture_success.add_value(1);
+                    } else {
+                        STATS::wireproto_local_capture_failure.add_value(1);
+                    }
+                }
+            })?;
+        Ok(Self {
+            sample_rate,
+            sender,
         })
     }
+
+    /// Decide whether the current request should be captured.
+    fn should_capture(&self) -> bool {
+        let sample_rate = self.sample_rate.get();
+        sample_rate == 1 || thread_rng().gen_range(0, sample_rate) == 0
+    }
+
+    /// Queue a line to be written to the capture file.
+    fn write(&self, line: String) -> Result<(), Error> {
+        self.sender.try_send(line)?;
+        Ok(())
+    }
+}
+
+/// Writes captured lines to the local capture file, rotating it once it grows beyond the
+/// configured size.
+struct LocalCaptureWriter {
+    config: WireprotoLocalCaptureConfig,
+    file: File,
+    size: u64,
+}
+
+impl LocalCaptureWriter {
+    fn new(config: WireprotoLocalCaptureConfig) -> Result<Self, Error> {
+        let (file, size) = Self::open(&config.path)?;
+        Ok(Self { config, file, size })
+    }
+
+    fn open(path: &str) -> Result<(File, u64), Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok((file, size))
+    }
+
+    fn write(&mut self, line: &str) -> Result<(), Error> {
+        let len = line.len() as u64 + 1;
+        if let Some(max_file_size) = self.config.max_file_size {
+            if self.size > 0 && self.size + len > max_file_size.get() {
+                self.rotate()?;
+            }
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    /// Move the current file to `path.1`, shifting older files along and dropping the oldest,
+    /// then open a new, empty file at `path`.
+    fn rotate(&mut self) -> Result<(), Error> {
+        let path = &self.config.path;
+        if self.config.max_files == 0 {
+            fs::remove_file(path)?;
+        } else {
+            for n in (1..self.config.max_files).rev() {
+                let from = format!("{}.{}", path, n);
+                if fs::metadata(&from).is_ok() {
+                    fs::rename(&from, format!("{}.{}", path, n + 1))?;
+                }
+            }
+            fs::rename(path, format!("{}.1", path))?;
+        }
+        let (file, size) = Self::open(path)?;
+        self.file = file;
+        self.size = size;
+        Ok(())
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -213,19 +354,12 @@ fn do_wireproto_logging<'a>(
         .map(|a| a.to_string())
         .unwrap_or_else(|| "".to_string());
 
-    // Use a ScubaSampleBuilder to build a sample to send in Scribe. Reach into the other Scuba
-    // sample to grab a few datapoints from there as well.
-    let mut builder = wireproto.scuba_builder.clone();
-    builder
-        .add("command", command)
-        .add("duration", stats.completion_time().as_micros_unchecked())
-        .add("source_control_server_type", "mononoke")
-        .add("mononoke_session_uuid", session_id.into_string())
-        .add("reponame", wireproto.reponame.clone());
-
-    if let Some(client_hostname) = ctx.session().source_hostname() {
-        builder.add("client_hostname", client_hostname.clone());
-    }
+    let mut builder = wireproto.sample(&ctx, command, session_id, stats.completion_time());
+
+    let capture_locally = wireproto
+        .local_capture
+        .as_ref()
+        .map_or(false, |local_capture| local_capture.should_capture());
 
     let f = future::lazy(move || {
         let prepare_fut = match wireproto.blobstore_and_threshold {
@@ -266,26 +400,35 @@ fn do_wireproto_logging<'a>(
 
         prepare_fut
             .map(move |mut builder| {
-                // We use the Scuba sample and log it to Scribe, then we also log in the Scuba
-                // sample, but this is built using discard(), so at most it'll log to a file for
-                // debug / tests.
+                // We use the Scuba sample and log it to Scribe and to the local capture file,
+                // if either is configured.
 
                 let sample = builder.get_sample();
+                let sample_json = match sample.to_json() {
+                    Ok(sample_json) => sample_json.to_string(),
+                    Err(_) => {
+                        STATS::wireproto_serialization_failure.add_value(1);
+                        return;
+                    }
+                };
+
                 // We can't really do anything with the errors, so let's just log them
                 if let Some((ref scribe_client, ref scribe_category)) = wireproto.scribe_args {
-                    if let Ok(sample_json) = sample.to_json() {
-                        let res = scribe_client.offer(scribe_category, &sample_json.to_string());
-                        if res.is_ok() {
-                            STATS::wireproto_scribe_success.add_value(1);
-                        } else {
-                            STATS::wireproto_scribe_failure.add_value(1);
-                        }
+                    let res = scribe_client.offer(scribe_category, &sample_json);
+                    if res.is_ok() {
+                        STATS::wireproto_scribe_success.add_value(1);
                     } else {
-                        STATS::wireproto_serialization_failure.add_value(1);
+                        STATS::wireproto_scribe_failure.add_value(1);
                     }
                 }
 
-                builder.log();
+                if capture_locally {
+                    if let Some(ref local_capture) = wireproto.local_capture {
+                        if local_capture.write(sample_json).is_err() {
+                            STATS::wireproto_local_capture_failure.add_value(1);
+                        }
+                    }
+                }
             })
             .or_else(|_| Ok(()))
     });
@@ -295,3 +438,37 @@ fn do_wireproto_logging<'a>(
 fn generate_random_string(len: usize) -> String {
     thread_rng().sample_iter(&Alphanumeric).take(len).collect()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_local_capture_rotation() -> Result<(), Error> {
+        let dir = TempDir::new("local_capture")?;
+        let path = dir
+            .path()
+            .join("capture.json")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let mut writer = LocalCaptureWriter::new(WireprotoLocalCaptureConfig {
+            path: path.clone(),
+            sample_rate: NonZeroU64::new(1).unwrap(),
+            max_file_size: NonZeroU64::new(10),
+            max_files: 2,
+        })?;
+
+        for line in &["aaaa", "bbbb", "cccc", "dddd", "eeee"] {
+            writer.write(line)?;
+        }
+
+        assert_eq!(fs::read_to_string(&path)?, "eeee\n");
+        assert_eq!(fs::read_to_string(format!("{}.1", path))?, "cccc\ndddd\n");
+        assert_eq!(fs::read_to_string(format!("{}.2", path))?, "aaaa\nbbbb\n");
+        assert!(fs::metadata(format!("{}.3", path)).is_err());
+
+        Ok(())
+    }
+}
@@ -52,7 +52,7 @@ use mercurial_types::{
     RepoPath, NULL_CSID, NULL_HASH,
 };
 use metaconfig_types::RepoReadOnly;
-use mononoke_repo::{MononokeRepo, SqlStreamingCloneConfig};
+use mononoke_repo::{MononokeRepo, QuotaPermit, QuotaViolation, SqlStreamingCloneConfig};
 use rand::{self, Rng};
 use remotefilelog::{
     create_getpack_v1_blob, create_getpack_v2_blob, get_unordered_file_history_for_multiple_nodes,
@@ -110,6 +110,8 @@ define_stats! {
     push_hook_failure: dynamic_timeseries("push_hook_failure.{}.{}", (reponame: String, hook_failure: String); Rate, Sum),
     push_conflicts: dynamic_timeseries("push_conflicts.{}", (reponame: String); Rate, Sum),
     rate_limits_exceeded: dynamic_timeseries("rate_limits_exceeded.{}", (reponame: String); Rate, Sum),
+    quota_exceeded: dynamic_timeseries("quota_exceeded.{}.{}", (reponame: String, quota: String); Rate, Sum),
+    quota_exceeded_log_only: dynamic_timeseries("quota_exceeded_log_only.{}.{}", (reponame: String, quota: String); Rate, Sum),
     push_error: dynamic_timeseries("push_error.{}", (reponame: String); Rate, Sum),
 
     undesired_tree_fetches: timeseries(Sum),
@@ -438,18 +440,70 @@ impl RepoClient {
     where
         F: Future<Item = I, Error = E> + Send + 'static,
         H: FnOnce(CoreContext, CommandLogger) -> F,
+        I: Send + 'static,
+        E: From<Error> + Send + 'static,
     {
         let (ctx, command_logger) = self.start_command(command);
-        with_command_monitor(ctx.clone(), handler(ctx, command_logger)).boxify()
+        let permit = try_boxfuture!(self.acquire_quota(&ctx, command));
+        let fut = Monitor::new(handler(ctx.clone(), command_logger), permit);
+        with_command_monitor(ctx, fut).boxify()
     }
 
-    fn command_stream<S, I, E, H>(&self, command: &str, handler: H) -> BoxStream<I, E>
+    fn command_stream<S, H>(&self, command: &str, handler: H) -> BoxStream<BytesOld, Error>
     where
-        S: Stream<Item = I, Error = E> + Send + 'static,
+        S: Stream<Item = BytesOld, Error = Error> + Send + 'static,
         H: FnOnce(CoreContext, CommandLogger) -> S,
     {
         let (ctx, command_logger) = self.start_command(command);
-        with_command_monitor(ctx.clone(), handler(ctx, command_logger)).boxify()
+        let mut permit = try_boxstream!(self.acquire_quota(&ctx, command));
+        let reponame = self.repo.reponame().clone();
+        let request_name = command.to_string();
+        // Byte limits are checked as the response is sent, so that a single
+        // large response is cut off rather than only blocking later requests.
+        let stream = handler(ctx.clone(), command_logger).and_then({
+            cloned!(ctx);
+            move |bytes| {
+                for violation in permit.record_bytes(bytes.len() as u64) {
+                    report_quota_violation(&ctx, &reponame, &violation);
+                    if !violation.log_only {
+                        return Err(ErrorKind::QuotaExceeded {
+                            request_name: request_name.clone(),
+                            violation,
+                        }
+                        .into());
+                    }
+                }
+                Ok(bytes)
+            }
+        });
+        with_command_monitor(ctx, stream).boxify()
+    }
+
+    /// Check the wireproto quotas for this client before running `command`.
+    fn acquire_quota(&self, ctx: &CoreContext, command: &str) -> Result<QuotaPermit, Error> {
+        // Quotas are tracked per user, falling back to the client's host if the
+        // user isn't known.
+        let identity = non_empty(self.session.user_unix_name())
+            .or_else(|| non_empty(self.session.source_hostname()))
+            .unwrap_or("unknown");
+        let reponame = self.repo.reponame();
+
+        match self.repo.wireproto_quotas().acquire(identity, command) {
+            Ok(permit) => {
+                for violation in permit.violations() {
+                    report_quota_violation(ctx, reponame, violation);
+                }
+                Ok(permit)
+            }
+            Err(violation) => {
+                report_quota_violation(ctx, reponame, &violation);
+                Err(ErrorKind::QuotaExceeded {
+                    request_name: command.to_string(),
+                    violation,
+                }
+                .into())
+            }
+        }
     }
 
     fn start_command(&self, command: &str) -> (CoreContext, CommandLogger) {
@@ -924,6 +978,27 @@ impl RepoClient {
     }
 }
 
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|value| !value.is_empty())
+}
+
+fn report_quota_violation(ctx: &CoreContext, reponame: &str, violation: &QuotaViolation) {
+    let key = (reponame.to_string(), violation.quota.clone());
+    if violation.log_only {
+        STATS::quota_exceeded_log_only.add_value(1, key);
+    } else {
+        STATS::quota_exceeded.add_value(1, key);
+    }
+    info!(ctx.logger(), "{}", violation);
+    let mut scuba = ctx.scuba().clone();
+    scuba
+        .add("quota", violation.quota.clone())
+        .add("quota_identity", violation.identity.clone())
+        .add("quota_reason", violation.reason.clone())
+        .add("quota_log_only", violation.log_only as i32);
+    scuba.log_with_msg("Quota exceeded", None);
+}
+
 fn throttle_stream<F, S, V>(
     session: &SessionContainer,
     metric: Metric,
@@ -16,7 +16,10 @@ use hooks_content_stores::InMemoryFileContentFetcher;
 use manifest::{Entry, ManifestOps};
 use maplit::hashset;
 use mercurial_types::HgFileNodeId;
-use metaconfig_types::{HookManagerParams, InfinitepushParams, LfsParams, PushrebaseParams};
+use metaconfig_types::{
+    HgsqlName, HookManagerParams, InfinitepushParams, LfsParams, PushParams, PushrebaseParams,
+    WireprotoQuota,
+};
 use mononoke_repo::MononokeRepo;
 use mutable_counters::SqlMutableCounters;
 use repo_read_write_status::RepoReadWriteFetcher;
@@ -468,6 +471,27 @@ async fn run_and_check_if_lfs(
     filenode_id: &HgFileNodeId,
     lfs_params: LfsParams,
 ) -> Result<bool, Error> {
+    let repo_client = create_repo_client(ctx, repo, lfs_params, vec![]).await?;
+
+    let bytes = repo_client
+        .getpackv2(stream::iter_ok(vec![(path.clone(), vec![*filenode_id])]).boxify())
+        .concat2()
+        .compat()
+        .await?;
+
+    let lfs_url: &[u8] = b"version https://git-lfs.github.com/spec/v1";
+
+    let found = bytes.windows(lfs_url.len()).any(|w| w == lfs_url);
+
+    Ok(found)
+}
+
+async fn create_repo_client(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    lfs_params: LfsParams,
+    wireproto_quotas: Vec<WireprotoQuota>,
+) -> Result<RepoClient, Error> {
     let pushrebase_params = PushrebaseParams::default();
 
     let mononoke_repo = MononokeRepo::new(
@@ -476,57 +500,115 @@ async fn run_and_check_if_lfs(
         repo.clone(),
         &pushrebase_params,
         vec![],
-        Arc::new(HookManager::new(
-            ctx.fb,
-            Box::new(InMemoryFileContentFetcher::new()),
-            HookManagerParams {
-                disable_acl_checker: true,
-            },
-            ScubaSampleBuilder::with_discard(),
-        )),
+        Arc::new(
+            HookManager::new(
+                ctx.fb,
+                Box::new(InMemoryFileContentFetcher::new()),
+                HookManagerParams {
+                    disable_acl_checker: true,
+                },
+                ScubaSampleBuilder::with_discard(),
+            )
+            .await?,
+        ),
         None,
         lfs_params,
         RepoReadWriteFetcher::new(
             None,
             RepoReadOnly::ReadOnly("".to_string()),
-            "repo".to_string(),
+            HgsqlName("repo".to_string()),
         ),
         InfinitepushParams::default(),
         0,
         Arc::new(SkiplistIndex::new()),
         Arc::new(SqlMutableCounters::with_sqlite_in_memory()?),
+        None,
+        PushParams::default(),
+        wireproto_quotas,
     )
     .await?;
 
-    let logging = LoggingContainer::new(ctx.logger().clone(), ScubaSampleBuilder::with_discard());
+    let logging = LoggingContainer::new(
+        ctx.fb,
+        ctx.logger().clone(),
+        ScubaSampleBuilder::with_discard(),
+    );
 
     let noop_wireproto =
         WireprotoLogging::new(ctx.fb, mononoke_repo.reponame().clone(), None, None, None)?;
 
-    let repo_client = RepoClient::new(
+    Ok(RepoClient::new(
         mononoke_repo,
         ctx.session().clone(),
         logging,
         100,   // hash validation percentage
         false, // Don't preserve raw bundle 2 (we don't push)
-        false, // Don't allow pushes (we don't push)
-        true,  // Support bundle2_listkeys
         Arc::new(noop_wireproto),
         None, // No PushRedirectorArgs
         None, // Don't listen to LiveCommitSyncConfig
-    );
+    ))
+}
 
-    let bytes = repo_client
-        .getpackv2(stream::iter_ok(vec![(path.clone(), vec![*filenode_id])]).boxify())
-        .concat2()
+#[fbinit::compat_test]
+async fn test_quota_max_bytes(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo = blobrepo_factory::new_memblob_empty(None)?;
+    let commit = CreateCommitContext::new_root(&ctx, &repo)
+        .add_file("large", "a".repeat(200_000))
+        .add_file("small", "b")
+        .commit()
+        .await?;
+    let hg_cs_id = repo
+        .get_hg_from_bonsai_changeset(ctx.clone(), commit)
         .compat()
         .await?;
+    let hg_cs = hg_cs_id.load(ctx.clone(), repo.blobstore()).await?;
+    let mut filenodes = vec![];
+    for name in &["large", "small"] {
+        let path = MPath::new(name)?;
+        let entry = hg_cs
+            .manifestid()
+            .find_entry(ctx.clone(), repo.get_blobstore(), Some(path.clone()))
+            .compat()
+            .await?;
+        match entry {
+            Some(Entry::Leaf((_, filenode_id))) => filenodes.push((path, filenode_id)),
+            _ => panic!("{} should be a file", name),
+        }
+    }
 
-    let lfs_url: &[u8] = b"version https://git-lfs.github.com/spec/v1";
+    let quota = WireprotoQuota {
+        name: "getpack_bytes".to_string(),
+        commands: vec![ops::GETPACKV2.to_string()],
+        interval: Duration::from_secs(3600),
+        max_requests: None,
+        max_concurrent_requests: None,
+        max_bytes: Some(1000),
+        log_only: false,
+    };
+    let repo_client = create_repo_client(&ctx, &repo, LfsParams::default(), vec![quota]).await?;
+    let getpack = |(path, filenode_id): &(MPath, HgFileNodeId)| {
+        repo_client
+            .getpackv2(stream::iter_ok(vec![(path.clone(), vec![*filenode_id])]).boxify())
+            .concat2()
+            .compat()
+    };
+    let is_quota_exceeded = |res: Result<BytesOld, Error>| match res {
+        Err(err) => match err.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::QuotaExceeded { violation, .. }) => violation.quota == "getpack_bytes",
+            _ => false,
+        },
+        Ok(_) => false,
+    };
 
-    let found = bytes.windows(lfs_url.len()).any(|w| w == lfs_url);
+    // The large file goes over the limit while it is being sent, so the
+    // response is cut off.
+    assert!(is_quota_exceeded(getpack(&filenodes[0]).await));
 
-    Ok(found)
+    // Once the limit is reached, further requests are rejected up front.
+    assert!(is_quota_exceeded(getpack(&filenodes[1]).await));
+
+    Ok(())
 }
 
 async fn fetch_mfs(
@@ -8,6 +8,7 @@
 use thiserror::Error;
 
 use mercurial_types::{HgNodeHash, RepoPath};
+use mononoke_repo::QuotaViolation;
 
 #[derive(Debug, Error)]
 pub enum ErrorKind {
@@ -19,4 +20,9 @@ pub enum ErrorKind {
     },
     #[error("Request {request_name} was throttled")]
     RequestThrottled { request_name: String },
+    #[error("Request {request_name} was rejected because {violation}")]
+    QuotaExceeded {
+        request_name: String,
+        violation: QuotaViolation,
+    },
 }
@@ -5,12 +5,12 @@
  * GNU General Public License version 2.
  */
 
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 use cloned::cloned;
 use faster_hex::hex_string;
 use futures_util::{future, FutureExt};
-use mononoke_api::{ChangesetContext, ChangesetId, MononokeError, RepoContext};
+use mononoke_api::{ChangesetContext, ChangesetId, HgChangesetId, MononokeError, RepoContext};
 use source_control as thrift;
 
 /// Generate a mapping for a commit's identity into the requested identity
@@ -167,6 +167,45 @@ pub(crate) async fn map_commit_identities(
     Ok(result)
 }
 
+/// Generate mappings for multiple Mercurial commits' identities into the
+/// requested identity schemes.  Commits that don't exist in the repo, for
+/// example draft commits that were never pushed, are identified by their
+/// Mercurial ID only.
+pub(crate) async fn map_hg_commit_identities(
+    repo_ctx: &RepoContext,
+    hg_ids: Vec<HgChangesetId>,
+    schemes: &BTreeSet<thrift::CommitIdentityScheme>,
+) -> Result<
+    BTreeMap<HgChangesetId, BTreeMap<thrift::CommitIdentityScheme, thrift::CommitId>>,
+    MononokeError,
+> {
+    let resolved: HashMap<_, _> = repo_ctx
+        .many_changeset_ids_from_hg(hg_ids.clone())
+        .await?
+        .into_iter()
+        .collect();
+    let cs_ids = resolved.values().cloned().collect();
+    let mut cs_identities = map_commit_identities(repo_ctx, cs_ids, schemes).await?;
+
+    let mut result = BTreeMap::new();
+    for hg_id in hg_ids {
+        let cs_id = resolved.get(&hg_id);
+        let ids = match cs_id.and_then(|cs_id| cs_identities.remove(cs_id)) {
+            Some(ids) => ids,
+            None => {
+                let mut ids = BTreeMap::new();
+                ids.insert(
+                    thrift::CommitIdentityScheme::HG,
+                    thrift::CommitId::hg(hg_id.as_ref().into()),
+                );
+                ids
+            }
+        };
+        result.insert(hg_id, ids);
+    }
+    Ok(result)
+}
+
 /// Trait to extend CommitId with useful functions.
 pub(crate) trait CommitIdExt {
     fn scheme(&self) -> thrift::CommitIdentityScheme;
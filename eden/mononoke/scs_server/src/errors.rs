@@ -15,6 +15,8 @@ use source_control::services::source_control_service as service;
 pub(crate) enum ServiceError {
     Request(thrift::RequestError),
     Internal(thrift::InternalError),
+    PushrebaseConflicts(thrift::PushrebaseConflictsException),
+    HookRejections(thrift::HookRejectionsException),
 }
 
 impl From<thrift::RequestError> for ServiceError {
@@ -56,6 +58,33 @@ impl From<MononokeError> for ServiceError {
                 kind: thrift::RequestErrorKind::NOT_AVAILABLE,
                 reason: error.to_string(),
             }),
+            MononokeError::PushrebaseConflicts(conflicts) => {
+                let reason = MononokeError::PushrebaseConflicts(conflicts.clone()).to_string();
+                let conflicts = conflicts
+                    .into_iter()
+                    .map(|conflict| thrift::PushrebaseConflict {
+                        left: conflict.left.to_string(),
+                        right: conflict.right.to_string(),
+                    })
+                    .collect();
+                Self::PushrebaseConflicts(thrift::PushrebaseConflictsException {
+                    reason,
+                    conflicts,
+                })
+            }
+            MononokeError::HookFailure(rejections) => {
+                let reason = MononokeError::HookFailure(rejections.clone()).to_string();
+                let rejections = rejections
+                    .into_iter()
+                    .map(|rejection| thrift::HookRejection {
+                        hook_name: rejection.hook_name,
+                        cs_id: rejection.cs_id.as_ref().into(),
+                        description: rejection.reason.description.to_string(),
+                        long_description: rejection.reason.long_description,
+                    })
+                    .collect();
+                Self::HookRejections(thrift::HookRejectionsException { reason, rejections })
+            }
             MononokeError::InternalError(error) => {
                 let reason = error.to_string();
                 let backtrace = error
@@ -87,6 +116,11 @@ macro_rules! impl_into_thrift_error {
                 match e {
                     ServiceError::Request(e) => e.into(),
                     ServiceError::Internal(e) => e.into(),
+                    // Only methods that land commits declare these
+                    // exceptions.  Anything else reports them as an
+                    // invalid request.
+                    ServiceError::PushrebaseConflicts(e) => invalid_request(e.reason).into(),
+                    ServiceError::HookRejections(e) => invalid_request(e.reason).into(),
                 }
             }
         }
@@ -97,6 +131,7 @@ impl_into_thrift_error!(service::ListReposExn);
 impl_into_thrift_error!(service::RepoResolveBookmarkExn);
 impl_into_thrift_error!(service::RepoResolveCommitPrefixExn);
 impl_into_thrift_error!(service::RepoListBookmarksExn);
+impl_into_thrift_error!(service::RepoQueryRevsetExn);
 impl_into_thrift_error!(service::RepoCreateCommitExn);
 impl_into_thrift_error!(service::RepoMoveBookmarkExn);
 impl_into_thrift_error!(service::RepoStackInfoExn);
@@ -108,16 +143,32 @@ impl_into_thrift_error!(service::CommitCompareExn);
 impl_into_thrift_error!(service::CommitIsAncestorOfExn);
 impl_into_thrift_error!(service::CommitFindFilesExn);
 impl_into_thrift_error!(service::CommitHistoryExn);
+impl_into_thrift_error!(service::CommitSearchContentExn);
+impl_into_thrift_error!(service::CommitArchiveExn);
 impl_into_thrift_error!(service::CommitListDescendantBookmarksExn);
+impl_into_thrift_error!(service::CommitMutationHistoryExn);
+impl_into_thrift_error!(service::CommitSuccessorsExn);
 impl_into_thrift_error!(service::CommitPathInfoExn);
 impl_into_thrift_error!(service::CommitPathBlameExn);
 impl_into_thrift_error!(service::CommitPathHistoryExn);
+impl_into_thrift_error!(service::CommitPathListLastChangedExn);
 impl_into_thrift_error!(service::TreeListExn);
 impl_into_thrift_error!(service::FileExistsExn);
 impl_into_thrift_error!(service::FileInfoExn);
 impl_into_thrift_error!(service::FileContentChunkExn);
 impl_into_thrift_error!(service::CommitLookupXrepoExn);
 
+impl From<ServiceError> for service::RepoLandStackExn {
+    fn from(e: ServiceError) -> Self {
+        match e {
+            ServiceError::Request(e) => e.into(),
+            ServiceError::Internal(e) => e.into(),
+            ServiceError::PushrebaseConflicts(e) => e.into(),
+            ServiceError::HookRejections(e) => e.into(),
+        }
+    }
+}
+
 pub(crate) fn invalid_request(reason: impl ToString) -> thrift::RequestError {
     thrift::RequestError {
         kind: thrift::RequestErrorKind::INVALID_REQUEST,
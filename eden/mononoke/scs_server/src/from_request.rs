@@ -14,8 +14,9 @@ use chrono::{DateTime, FixedOffset, TimeZone};
 use faster_hex::hex_string;
 use mononoke_api::specifiers::{GitSha1, Globalrev};
 use mononoke_api::{
-    ChangesetId, ChangesetIdPrefix, ChangesetPrefixSpecifier, ChangesetSpecifier, CopyInfo,
-    CreateCopyInfo, FileId, FileType, HgChangesetId, HgChangesetIdPrefix, MononokePath, TreeId,
+    ArchiveFormat, ChangesetId, ChangesetIdPrefix, ChangesetPrefixSpecifier, ChangesetSpecifier,
+    CopyInfo, CreateCopyInfo, FileId, FileType, HgChangesetId, HgChangesetIdPrefix, MononokePath,
+    TreeId,
 };
 use mononoke_types::hash::{Sha1, Sha256};
 use source_control as thrift;
@@ -154,6 +155,20 @@ impl_from_request_binary_id!(FileId, "file id");
 impl_from_request_binary_id!(Sha1, "sha-1");
 impl_from_request_binary_id!(Sha256, "sha-256");
 
+impl FromRequest<thrift::ArchiveFormat> for ArchiveFormat {
+    fn from_request(format: &thrift::ArchiveFormat) -> Result<Self, thrift::RequestError> {
+        match format {
+            &thrift::ArchiveFormat::TAR => Ok(ArchiveFormat::Tar),
+            &thrift::ArchiveFormat::TAR_GZ => Ok(ArchiveFormat::TarGz),
+            &thrift::ArchiveFormat::ZIP => Ok(ArchiveFormat::Zip),
+            &val => Err(errors::invalid_request(format!(
+                "unsupported archive format ({})",
+                val
+            ))),
+        }
+    }
+}
+
 impl FromRequest<thrift::RepoCreateCommitParamsFileType> for FileType {
     fn from_request(
         file_type: &thrift::RepoCreateCommitParamsFileType,
@@ -5,6 +5,7 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::{TryFrom, TryInto};
 use std::sync::Arc;
 
@@ -13,12 +14,15 @@ use futures::compat::Stream01CompatExt;
 use futures::stream::{self, StreamExt, TryStreamExt};
 use futures::{future, try_join};
 use mononoke_api::{
-    unified_diff, ChangesetContext, ChangesetId, ChangesetSpecifier, CopyInfo, MononokeError,
-    MononokePath, UnifiedDiffMode,
+    unified_diff, ArchiveCursor, ArchiveFormat, ChangesetContext, ChangesetId, ChangesetMutation,
+    ChangesetSpecifier, CopyInfo, HgChangesetId, MononokeError, MononokePath, RepoContext,
+    UnifiedDiffMode,
 };
 use source_control as thrift;
 
-use crate::commit_id::{map_commit_identities, map_commit_identity, CommitIdExt};
+use crate::commit_id::{
+    map_commit_identities, map_commit_identity, map_hg_commit_identities, CommitIdExt,
+};
 use crate::errors;
 use crate::from_request::{check_range_and_convert, validate_timestamp, FromRequest};
 use crate::history::collect_history;
@@ -320,6 +324,99 @@ impl SourceControlServiceImpl {
         Ok(thrift::CommitFindFilesResponse { files })
     }
 
+    /// Returns lines of text files that match a regex
+    pub(crate) async fn commit_search_content(
+        &self,
+        ctx: CoreContext,
+        commit: thrift::CommitSpecifier,
+        params: thrift::CommitSearchContentParams,
+    ) -> Result<thrift::CommitSearchContentResponse, errors::ServiceError> {
+        let (_repo, changeset) = self.repo_changeset(ctx, &commit).await?;
+        let limit: usize = check_range_and_convert(
+            "limit",
+            params.limit,
+            0..=source_control::COMMIT_SEARCH_CONTENT_MAX_LIMIT,
+        )?;
+        let prefixes: Option<Vec<_>> = match params.prefixes {
+            Some(prefixes) => Some(
+                prefixes
+                    .into_iter()
+                    .map(|prefix| {
+                        MononokePath::try_from(&prefix).map_err(|e| {
+                            errors::invalid_request(format!("invalid prefix '{}': {}", prefix, e))
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            None => None,
+        };
+
+        let result = changeset
+            .search_content(&params.regex, prefixes, limit)
+            .await?;
+        let matches = result
+            .matches
+            .into_iter()
+            .map(|m| thrift::ContentSearchMatch {
+                path: m.path.to_string(),
+                line_number: m.line_number as i64,
+                line: m.line,
+            })
+            .collect();
+        let unsearched_files = result
+            .unsearched_files
+            .into_iter()
+            .map(|path| path.to_string())
+            .collect();
+        Ok(thrift::CommitSearchContentResponse {
+            matches,
+            unsearched_files,
+        })
+    }
+
+    /// Returns part of an archive of the files in a commit.  Tar archives
+    /// are returned in chunks of about `max_size` bytes, and the caller
+    /// continues from `continue_after` until it is not set.
+    pub(crate) async fn commit_archive(
+        &self,
+        ctx: CoreContext,
+        commit: thrift::CommitSpecifier,
+        params: thrift::CommitArchiveParams,
+    ) -> Result<thrift::CommitArchiveResponse, errors::ServiceError> {
+        let (_repo, changeset) = self.repo_changeset(ctx, &commit).await?;
+        let format = ArchiveFormat::from_request(&params.format)?;
+        let max_size: usize = check_range_and_convert(
+            "max_size",
+            params.max_size,
+            1..=source_control::COMMIT_ARCHIVE_MAX_SIZE,
+        )?;
+        let prefixes: Option<Vec<_>> = match params.paths {
+            Some(paths) => Some(
+                paths
+                    .into_iter()
+                    .map(|path| {
+                        MononokePath::try_from(&path).map_err(|e| {
+                            errors::invalid_request(format!("invalid path '{}': {}", path, e))
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            None => None,
+        };
+        let after = match params.after {
+            Some(after) => Some(ArchiveCursor::from_token(&after)?),
+            None => None,
+        };
+
+        let chunk = changeset
+            .archive_chunk(format, prefixes, after, max_size)
+            .await?;
+        Ok(thrift::CommitArchiveResponse {
+            data: chunk.data.to_vec(),
+            continue_after: chunk.continue_after.map(|cursor| cursor.to_token()),
+        })
+    }
+
     /// Returns the history of a commit
     pub(crate) async fn commit_history(
         &self,
@@ -374,6 +471,45 @@ impl SourceControlServiceImpl {
         Ok(thrift::CommitHistoryResponse { history })
     }
 
+    /// Returns the mutations (amends, rebases, etc.) that created a commit.
+    pub(crate) async fn commit_mutation_history(
+        &self,
+        ctx: CoreContext,
+        commit: thrift::CommitSpecifier,
+        params: thrift::CommitMutationHistoryParams,
+    ) -> Result<thrift::CommitMutationHistoryResponse, errors::ServiceError> {
+        let (repo, changeset) = self.repo_changeset(ctx, &commit).await?;
+        let mutations = changeset.mutation_predecessors().await?;
+        let mutations = map_mutations(&repo, mutations, &params.identity_schemes).await?;
+        Ok(thrift::CommitMutationHistoryResponse { mutations })
+    }
+
+    /// Returns the mutations that replaced a commit, and the commits it was
+    /// eventually replaced by.  This can be used to find the commit that an
+    /// amended draft commit was landed as.
+    pub(crate) async fn commit_successors(
+        &self,
+        ctx: CoreContext,
+        commit: thrift::CommitSpecifier,
+        params: thrift::CommitSuccessorsParams,
+    ) -> Result<thrift::CommitSuccessorsResponse, errors::ServiceError> {
+        let (repo, changeset) = self.repo_changeset(ctx, &commit).await?;
+        let mutations = changeset.mutation_successors().await?;
+        let final_successors = ChangesetMutation::final_successors(&mutations);
+        let (mutations, mut final_successor_ids) = try_join!(
+            map_mutations(&repo, mutations, &params.identity_schemes),
+            map_hg_commit_identities(&repo, final_successors.clone(), &params.identity_schemes),
+        )?;
+        let final_successors = final_successors
+            .iter()
+            .filter_map(|hg_id| final_successor_ids.remove(hg_id))
+            .collect();
+        Ok(thrift::CommitSuccessorsResponse {
+            mutations,
+            final_successors,
+        })
+    }
+
     pub(crate) async fn commit_list_descendant_bookmarks(
         &self,
         ctx: CoreContext,
@@ -486,3 +622,36 @@ impl SourceControlServiceImpl {
         }
     }
 }
+
+/// Convert mutations into their thrift representation, mapping the commits
+/// involved into the requested identity schemes.
+async fn map_mutations(
+    repo: &RepoContext,
+    mutations: Vec<ChangesetMutation>,
+    identity_schemes: &BTreeSet<thrift::CommitIdentityScheme>,
+) -> Result<Vec<thrift::CommitMutation>, MononokeError> {
+    let mut hg_ids = Vec::new();
+    for mutation in mutations.iter() {
+        hg_ids.push(mutation.successor);
+        hg_ids.extend(mutation.predecessors.iter().cloned());
+        hg_ids.extend(mutation.split.iter().cloned());
+    }
+    hg_ids.sort();
+    hg_ids.dedup();
+    let id_mapping = map_hg_commit_identities(repo, hg_ids, identity_schemes).await?;
+    let get_ids =
+        |hg_id: &HgChangesetId| id_mapping.get(hg_id).cloned().unwrap_or_else(BTreeMap::new);
+
+    Ok(mutations
+        .into_iter()
+        .map(|mutation| thrift::CommitMutation {
+            successor: get_ids(&mutation.successor),
+            predecessors: mutation.predecessors.iter().map(get_ids).collect(),
+            split: mutation.split.iter().map(get_ids).collect(),
+            op: mutation.op,
+            user: mutation.user,
+            date: mutation.time.timestamp(),
+            tz: mutation.time.offset().local_minus_utc(),
+        })
+        .collect())
+}
@@ -8,6 +8,7 @@
 use context::CoreContext;
 use dedupmap::DedupMap;
 use futures::future;
+use futures::stream::{self, StreamExt, TryStreamExt};
 use mononoke_api::{ChangesetSpecifier, MononokeError, PathEntry};
 use source_control as thrift;
 use std::borrow::Cow;
@@ -15,11 +16,14 @@ use std::collections::{BTreeSet, HashMap};
 
 use crate::commit_id::map_commit_identities;
 use crate::errors;
-use crate::from_request::{check_range_and_convert, validate_timestamp};
+use crate::from_request::{check_range_and_convert, validate_timestamp, FromRequest};
 use crate::history::collect_history;
-use crate::into_response::IntoResponse;
+use crate::into_response::{AsyncIntoResponse, IntoResponse};
 use crate::source_control_impl::SourceControlServiceImpl;
 
+// Number of commits whose information is fetched concurrently.
+const COMMIT_INFO_CONCURRENCY: usize = 100;
+
 impl SourceControlServiceImpl {
     /// Returns information about the file or directory at a path in a commit.
     pub(crate) async fn commit_path_info(
@@ -109,7 +113,23 @@ impl SourceControlServiceImpl {
         // Map all the changeset IDs into the requested identity schemes.  Keep a mapping of
         // which bonsai changeset ID corresponds to which mapped commit ID index, so we can look
         // them up later.
-        let (content, blame) = path.blame().await?;
+        let ignored = future::try_join_all(params.ignore_revisions.iter().flatten().map(|id| {
+            let repo = &repo;
+            async move {
+                let specifier = ChangesetSpecifier::from_request(id)?;
+                let cs_id = repo.resolve_specifier(specifier).await?.ok_or_else(|| {
+                    errors::commit_not_found(format!(
+                        "repo={} commit={}",
+                        commit_path.commit.repo.name,
+                        id.to_string()
+                    ))
+                })?;
+                Ok::<_, errors::ServiceError>(cs_id)
+            }
+        }))
+        .await?;
+        let use_ignore_file = params.use_ignore_file.unwrap_or(false);
+        let (content, blame) = path.blame_ignoring(ignored, use_ignore_file).await?;
         let csids: Vec<_> = blame
             .ranges()
             .iter()
@@ -249,4 +269,32 @@ impl SourceControlServiceImpl {
 
         Ok(thrift::CommitPathHistoryResponse { history })
     }
+
+    /// Returns the last commit that changed each entry of a directory.
+    pub(crate) async fn commit_path_list_last_changed(
+        &self,
+        ctx: CoreContext,
+        commit_path: thrift::CommitPathSpecifier,
+        params: thrift::CommitPathListLastChangedParams,
+    ) -> Result<thrift::CommitPathListLastChangedResponse, errors::ServiceError> {
+        let (_repo, changeset) = self.repo_changeset(ctx, &commit_path.commit).await?;
+        let path = changeset.path(&commit_path.path)?;
+        let offset: usize = check_range_and_convert("offset", params.offset, 0..)?;
+        let limit: usize = check_range_and_convert(
+            "limit",
+            params.limit,
+            0..=source_control::COMMIT_PATH_LIST_LAST_CHANGED_MAX_LIMIT,
+        )?;
+
+        let identity_schemes = &params.identity_schemes;
+        let entries = stream::iter(path.list_last_changed(offset, limit).await?)
+            .map(|(name, changeset)| async move {
+                let commit = (changeset, identity_schemes).into_response().await?;
+                Ok::<_, errors::ServiceError>(thrift::CommitPathLastChangedEntry { name, commit })
+            })
+            .buffered(COMMIT_INFO_CONCURRENCY)
+            .try_collect()
+            .await?;
+        Ok(thrift::CommitPathListLastChangedResponse { entries })
+    }
 }
@@ -17,8 +17,8 @@ use futures_util::stream::FuturesOrdered;
 use futures_util::TryStreamExt;
 use maplit::btreemap;
 use mononoke_api::{
-    ChangesetPrefixSpecifier, ChangesetSpecifier, ChangesetSpecifierPrefixResolution, CreateChange,
-    CreateCopyInfo, FileId, FileType, MononokePath,
+    ChangesetId, ChangesetPrefixSpecifier, ChangesetSpecifier, ChangesetSpecifierPrefixResolution,
+    CreateChange, CreateCopyInfo, FileId, FileType, MononokePath,
 };
 use mononoke_types::hash::{Sha1, Sha256};
 use source_control as thrift;
@@ -170,6 +170,37 @@ impl SourceControlServiceImpl {
         })
     }
 
+    /// Query commits using a revset expression.
+    ///
+    /// Returns the matching commits in descending generation order.
+    pub(crate) async fn repo_query_revset(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoQueryRevsetParams,
+    ) -> Result<thrift::RepoQueryRevsetResponse, errors::ServiceError> {
+        let limit: usize = check_range_and_convert(
+            "limit",
+            params.limit,
+            1..=source_control::REPO_QUERY_REVSET_MAX_LIMIT,
+        )?;
+        let repo = self.repo(ctx, &repo).await?;
+        let result = repo
+            .query_revset(&params.query, params.after.as_deref(), limit)
+            .await?;
+        let ids = result.changesets.iter().map(|cs| cs.id()).collect();
+        let id_mapping = map_commit_identities(&repo, ids, &params.identity_schemes).await?;
+        let commits = result
+            .changesets
+            .iter()
+            .map(|cs| id_mapping.get(&cs.id()).cloned().unwrap_or_default())
+            .collect();
+        Ok(thrift::RepoQueryRevsetResponse {
+            commits,
+            continue_after: result.continue_after,
+        })
+    }
+
     /// Create a new commit.
     pub(crate) async fn repo_create_commit(
         &self,
@@ -433,4 +464,55 @@ impl SourceControlServiceImpl {
             .await?;
         Ok(thrift::RepoMoveBookmarkResponse {})
     }
+
+    /// Land a stack of commits onto a bookmark using pushrebase.
+    ///
+    /// Returns the new head of the bookmark and the mapping from the
+    /// commits in the stack to the commits they were rebased as.
+    pub(crate) async fn repo_land_stack(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoLandStackParams,
+    ) -> Result<thrift::RepoLandStackResponse, errors::ServiceError> {
+        let repo = self.repo(ctx, &repo).await?;
+        let repo = match params.service_identity {
+            Some(service_identity) => repo.service_write(service_identity).await?,
+            None => repo.write().await?,
+        };
+        let head_specifier = ChangesetSpecifier::from_request(&params.head)?;
+        let base_specifier = ChangesetSpecifier::from_request(&params.base)?;
+        let (head, base) = try_join(
+            repo.changeset(head_specifier),
+            repo.changeset(base_specifier),
+        )
+        .await?;
+        let head = head.ok_or_else(|| errors::commit_not_found(params.head.to_string()))?;
+        let base = base.ok_or_else(|| errors::commit_not_found(params.base.to_string()))?;
+
+        let outcome = repo
+            .land_stack(&params.bookmark, head.id(), base.id())
+            .await?;
+
+        let mut ids = vec![outcome.head];
+        for pair in outcome.rebased_changesets.iter() {
+            ids.push(pair.id_old);
+            ids.push(pair.id_new);
+        }
+        let id_mapping = map_commit_identities(&repo, ids, &params.identity_schemes).await?;
+        let ids = |cs_id: ChangesetId| id_mapping.get(&cs_id).cloned().unwrap_or_default();
+        let rebased_commits = outcome
+            .rebased_changesets
+            .iter()
+            .map(|pair| thrift::RebasedCommit {
+                old_ids: ids(pair.id_old),
+                new_ids: ids(pair.id_new),
+            })
+            .collect();
+        Ok(thrift::RepoLandStackResponse {
+            head: ids(outcome.head),
+            rebased_commits,
+            retry_num: outcome.retry_num as i64,
+        })
+    }
 }
@@ -47,6 +47,15 @@ impl AddScubaParams for thrift::RepoMoveBookmarkParams {
     }
 }
 
+impl AddScubaParams for thrift::RepoLandStackParams {
+    fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
+        scuba.add("bookmark_name", self.bookmark.as_str());
+        scuba.add("commit", self.head.to_string());
+        scuba.add("param_base", self.base.to_string());
+        self.identity_schemes.add_scuba_params(scuba);
+    }
+}
+
 impl AddScubaParams for thrift::RepoListBookmarksParams {
     fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
         scuba.add("param_include_scratch", self.include_scratch as i32);
@@ -59,6 +68,17 @@ impl AddScubaParams for thrift::RepoListBookmarksParams {
     }
 }
 
+impl AddScubaParams for thrift::RepoQueryRevsetParams {
+    fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
+        scuba.add("param_query", self.query.as_str());
+        scuba.add("param_limit", self.limit);
+        if let Some(after) = &self.after {
+            scuba.add("param_after", after.as_str());
+        }
+        self.identity_schemes.add_scuba_params(scuba);
+    }
+}
+
 impl AddScubaParams for thrift::RepoResolveBookmarkParams {
     fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
         scuba.add("bookmark_name", self.bookmark_name.as_str());
@@ -109,6 +129,29 @@ impl AddScubaParams for thrift::CommitFindFilesParams {
     }
 }
 
+impl AddScubaParams for thrift::CommitArchiveParams {
+    fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
+        scuba.add("param_format", self.format.to_string());
+        scuba.add("param_max_size", self.max_size);
+        if let Some(paths) = &self.paths {
+            scuba.add("param_paths", paths.iter().collect::<ScubaValue>());
+        }
+        if let Some(after) = &self.after {
+            scuba.add("param_after", after.as_str());
+        }
+    }
+}
+
+impl AddScubaParams for thrift::CommitSearchContentParams {
+    fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
+        scuba.add("param_regex", self.regex.as_str());
+        scuba.add("param_limit", self.limit);
+        if let Some(prefixes) = &self.prefixes {
+            scuba.add("param_prefixes", prefixes.iter().collect::<ScubaValue>());
+        }
+    }
+}
+
 impl AddScubaParams for thrift::CommitInfoParams {
     fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
         self.identity_schemes.add_scuba_params(scuba);
@@ -160,6 +203,18 @@ impl AddScubaParams for thrift::CommitListDescendantBookmarksParams {
     }
 }
 
+impl AddScubaParams for thrift::CommitMutationHistoryParams {
+    fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
+        self.identity_schemes.add_scuba_params(scuba);
+    }
+}
+
+impl AddScubaParams for thrift::CommitSuccessorsParams {
+    fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
+        self.identity_schemes.add_scuba_params(scuba);
+    }
+}
+
 impl AddScubaParams for thrift::CommitLookupXRepoParams {
     fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
         scuba.add("other_repo", self.other_repo.name.as_str());
@@ -171,6 +226,12 @@ impl AddScubaParams for thrift::CommitPathBlameParams {
     fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
         scuba.add("param_format", self.format.to_string());
         self.identity_schemes.add_scuba_params(scuba);
+        if let Some(ignore_revisions) = &self.ignore_revisions {
+            scuba.add("param_ignore_revisions_count", ignore_revisions.len());
+        }
+        if let Some(use_ignore_file) = self.use_ignore_file {
+            scuba.add("param_use_ignore_file", use_ignore_file);
+        }
     }
 }
 
@@ -193,6 +254,14 @@ impl AddScubaParams for thrift::CommitPathHistoryParams {
     }
 }
 
+impl AddScubaParams for thrift::CommitPathListLastChangedParams {
+    fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
+        scuba.add("param_offset", self.offset);
+        scuba.add("param_limit", self.limit);
+        self.identity_schemes.add_scuba_params(scuba);
+    }
+}
+
 impl AddScubaParams for thrift::CommitPathInfoParams {}
 
 impl AddScubaParams for thrift::FileContentChunkParams {
@@ -298,6 +298,14 @@ fn log_result<T>(ctx: CoreContext, stats: &FutureStats, result: &Result<T, error
             internal_failure = 1;
             ("INTERNAL_ERROR", Some(format!("{:?}", e)))
         }
+        Err(errors::ServiceError::PushrebaseConflicts(e)) => {
+            invalid_request = 1;
+            ("PUSHREBASE_CONFLICTS", Some(format!("{:?}", e)))
+        }
+        Err(errors::ServiceError::HookRejections(e)) => {
+            invalid_request = 1;
+            ("HOOK_REJECTIONS", Some(format!("{:?}", e)))
+        }
     };
 
     STATS::total_request_success.add_value(success);
@@ -393,6 +401,11 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::RepoListBookmarksParams,
         ) -> Result<thrift::RepoListBookmarksResponse, service::RepoListBookmarksExn>;
 
+        async fn repo_query_revset(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoQueryRevsetParams,
+        ) -> Result<thrift::RepoQueryRevsetResponse, service::RepoQueryRevsetExn>;
+
         async fn commit_common_base_with(
             commit: thrift::CommitSpecifier,
             params: thrift::CommitCommonBaseWithParams,
@@ -428,16 +441,36 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::CommitFindFilesParams,
         ) -> Result<thrift::CommitFindFilesResponse, service::CommitFindFilesExn>;
 
+        async fn commit_archive(
+            commit: thrift::CommitSpecifier,
+            params: thrift::CommitArchiveParams,
+        ) -> Result<thrift::CommitArchiveResponse, service::CommitArchiveExn>;
+
         async fn commit_history(
             commit: thrift::CommitSpecifier,
             params: thrift::CommitHistoryParams,
         ) -> Result<thrift::CommitHistoryResponse, service::CommitHistoryExn>;
 
+        async fn commit_search_content(
+            commit: thrift::CommitSpecifier,
+            params: thrift::CommitSearchContentParams,
+        ) -> Result<thrift::CommitSearchContentResponse, service::CommitSearchContentExn>;
+
         async fn commit_list_descendant_bookmarks(
             commit: thrift::CommitSpecifier,
             params: thrift::CommitListDescendantBookmarksParams,
         ) -> Result<thrift::CommitListDescendantBookmarksResponse, service::CommitListDescendantBookmarksExn>;
 
+        async fn commit_mutation_history(
+            commit: thrift::CommitSpecifier,
+            params: thrift::CommitMutationHistoryParams,
+        ) -> Result<thrift::CommitMutationHistoryResponse, service::CommitMutationHistoryExn>;
+
+        async fn commit_successors(
+            commit: thrift::CommitSpecifier,
+            params: thrift::CommitSuccessorsParams,
+        ) -> Result<thrift::CommitSuccessorsResponse, service::CommitSuccessorsExn>;
+
         async fn commit_lookup_xrepo(
             commit: thrift::CommitSpecifier,
             params: thrift::CommitLookupXRepoParams,
@@ -458,6 +491,11 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::CommitPathHistoryParams,
         ) -> Result<thrift::CommitPathHistoryResponse, service::CommitPathHistoryExn>;
 
+        async fn commit_path_list_last_changed(
+            commit_path: thrift::CommitPathSpecifier,
+            params: thrift::CommitPathListLastChangedParams,
+        ) -> Result<thrift::CommitPathListLastChangedResponse, service::CommitPathListLastChangedExn>;
+
         async fn tree_list(
             tree: thrift::TreeSpecifier,
             params: thrift::TreeListParams,
@@ -492,5 +530,10 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             repo: thrift::RepoSpecifier,
             params: thrift::RepoMoveBookmarkParams,
         ) -> Result<thrift::RepoMoveBookmarkResponse, service::RepoMoveBookmarkExn>;
+
+        async fn repo_land_stack(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoLandStackParams,
+        ) -> Result<thrift::RepoLandStackResponse, service::RepoLandStackExn>;
     }
 }
@@ -62,6 +62,8 @@ define_perf_counters! {
         BytesSent,
         CachelibHits,
         CachelibMisses,
+        DiskCacheHits,
+        DiskCacheMisses,
         GetbundleFilenodesTotalWeight,
         GetbundleNumCommits,
         GetbundleNumDrafts,
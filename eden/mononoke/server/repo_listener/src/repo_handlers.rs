@@ -371,7 +371,7 @@ fn create_wireproto_logging(
     let WireprotoLoggingConfig {
         storage_config_and_threshold,
         scribe_category,
-        local_path,
+        local_capture,
     } = wireproto_logging_config;
     let blobstore_fut = match storage_config_and_threshold {
         Some((storage_config, threshold)) => {
@@ -409,7 +409,7 @@ fn create_wireproto_logging(
                 reponame,
                 scribe_category,
                 blobstore_and_threshold,
-                local_path.as_ref().map(|p| p.as_ref()),
+                local_capture,
             )
         })
         .left_future()
@@ -0,0 +1,51 @@
+# Copyright (c) Facebook, Inc. and its affiliates.
+#
+# This software may be used and distributed according to the terms of the
+# GNU General Public License found in the LICENSE file in the root
+# directory of this source tree.
+
+  $ . "${TEST_FIXTURES}/library.sh"
+
+setup configuration
+  $ setup_common_config
+  $ cd "$TESTTMP"
+
+setup a repo with files in nested directories
+  $ hg init repo-hg
+  $ cd repo-hg
+  $ setup_hg_server
+  $ mkdir -p dir/sub
+  $ echo a > a
+  $ echo b > dir/b
+  $ hg commit -Aqm "first"
+  $ echo c > dir/sub/c
+  $ echo b2 > dir/b
+  $ hg commit -Aqm "second"
+  $ hg mv -q a dir/sub/a
+  $ hg commit -qm "third"
+  $ hg bookmark master_bookmark -r tip
+  $ hg log -T '{node} {desc}\n' > "$TESTTMP/expected"
+  $ cd "$TESTTMP"
+  $ blobimport repo-hg/.hg repo
+
+export everything reachable from master_bookmark
+  $ mononoke_admin export-bundle --head master_bookmark --phases "$TESTTMP/full.hg" 2>&1 | grep Wrote
+  * Wrote * bytes to $TESTTMP/full.hg (glob)
+
+apply it to a fresh repo without any extensions
+  $ HGRCPATH= hg init stock
+  $ HGRCPATH= hg -R stock unbundle -q "$TESTTMP/full.hg"
+  $ grep treemanifest stock/.hg/requires
+  treemanifest
+  $ HGRCPATH= hg -R stock log -T '{node} {desc}\n' > "$TESTTMP/actual"
+  $ diff "$TESTTMP/expected" "$TESTTMP/actual"
+  $ HGRCPATH= hg -R stock log -T '{phase}\n' -r tip
+  public
+  $ HGRCPATH= hg -R stock verify -q
+  $ HGRCPATH= hg -R stock update -q tip
+  $ cat stock/dir/b stock/dir/sub/a stock/dir/sub/c
+  b2
+  a
+  c
+  $ test -e stock/a
+  [1]
@@ -157,6 +157,7 @@ fn dump_with_extension(node_type: NodeType) -> bool {
         NodeType::BonsaiPhaseMapping => false,
         NodeType::PublishedBookmarks => false,
         NodeType::BonsaiFsnodeMapping => false,
+        NodeType::BonsaiSkeletonManifestMapping => false,
         // Hg
         NodeType::HgBonsaiMapping => false,
         NodeType::HgChangeset => false,
@@ -169,6 +170,7 @@ fn dump_with_extension(node_type: NodeType) -> bool {
         NodeType::AliasContentMapping => true,
         // Derived Data
         NodeType::Fsnode => false,
+        NodeType::SkeletonManifest => false,
     }
 }
 
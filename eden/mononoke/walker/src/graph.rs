@@ -15,7 +15,9 @@ use mercurial_types::{
     blobs::{BlobManifest, HgBlobChangeset},
     FileBytes, HgChangesetId, HgFileEnvelope, HgFileNodeId, HgManifestId,
 };
-use mononoke_types::{fsnode::Fsnode, FsnodeId};
+use mononoke_types::{
+    fsnode::Fsnode, skeleton_manifest::SkeletonManifest, FsnodeId, SkeletonManifestId,
+};
 use mononoke_types::{
     BonsaiChangeset, ChangesetId, ContentId, ContentMetadata, MPath, MPathHash, MononokeId,
 };
@@ -96,6 +98,8 @@ enum NodeType {
     // Derived data
     BonsaiFsnodeMapping,
     Fsnode,
+    BonsaiSkeletonManifestMapping,
+    SkeletonManifest,
 }
 }
 
@@ -128,6 +132,10 @@ impl NodeType {
             // Derived data
             NodeType::BonsaiFsnodeMapping => Some(EdgeType::RootToBonsaiFsnodeMapping),
             NodeType::Fsnode => Some(EdgeType::RootToFsnode),
+            NodeType::BonsaiSkeletonManifestMapping => {
+                Some(EdgeType::RootToBonsaiSkeletonManifestMapping)
+            }
+            NodeType::SkeletonManifest => Some(EdgeType::RootToSkeletonManifest),
         }
     }
 }
@@ -239,6 +247,8 @@ pub enum Node {
     // Derived data
     BonsaiFsnodeMapping(ChangesetId),
     Fsnode((WrappedPath, FsnodeId)),
+    BonsaiSkeletonManifestMapping(ChangesetId),
+    SkeletonManifest((WrappedPath, SkeletonManifestId)),
 }
 
 // Some Node types are accessible by more than one type of edge, this allows us to restrict the paths
@@ -264,6 +274,8 @@ enum EdgeType {
     // Derived data Roots
     RootToBonsaiFsnodeMapping,
     RootToFsnode,
+    RootToBonsaiSkeletonManifestMapping,
+    RootToSkeletonManifest,
     // Bonsai
     BookmarkToBonsaiChangeset,
     BookmarkToBonsaiHgMapping,
@@ -275,6 +287,7 @@ enum EdgeType {
     PublishedBookmarksToBonsaiChangeset,
     PublishedBookmarksToBonsaiHgMapping,
     BonsaiChangesetToBonsaiFsnodeMapping,
+    BonsaiChangesetToBonsaiSkeletonManifestMapping,
     // Hg
     HgBonsaiMappingToBonsaiChangeset,
     HgChangesetToHgParent,
@@ -296,6 +309,8 @@ enum EdgeType {
     // Derived data
     BonsaiToRootFsnode,
     FsnodeToChildFsnode,
+    BonsaiToRootSkeletonManifest,
+    SkeletonManifestToChildSkeletonManifest,
 }
 }
 
@@ -329,6 +344,8 @@ impl EdgeType {
             // Derived data Roots
             EdgeType::RootToBonsaiFsnodeMapping => None,
             EdgeType::RootToFsnode => None,
+            EdgeType::RootToBonsaiSkeletonManifestMapping => None,
+            EdgeType::RootToSkeletonManifest => None,
             // Bonsai
             EdgeType::BookmarkToBonsaiChangeset => Some(NodeType::Bookmark),
             EdgeType::BookmarkToBonsaiHgMapping => Some(NodeType::Bookmark),
@@ -340,6 +357,9 @@ impl EdgeType {
             EdgeType::PublishedBookmarksToBonsaiChangeset => Some(NodeType::PublishedBookmarks),
             EdgeType::PublishedBookmarksToBonsaiHgMapping => Some(NodeType::PublishedBookmarks),
             EdgeType::BonsaiChangesetToBonsaiFsnodeMapping => Some(NodeType::BonsaiChangeset),
+            EdgeType::BonsaiChangesetToBonsaiSkeletonManifestMapping => {
+                Some(NodeType::BonsaiChangeset)
+            }
             // Hg
             EdgeType::HgBonsaiMappingToBonsaiChangeset => Some(NodeType::HgBonsaiMapping),
             EdgeType::HgChangesetToHgParent => Some(NodeType::HgChangeset),
@@ -361,6 +381,8 @@ impl EdgeType {
             // Derived data
             EdgeType::BonsaiToRootFsnode => Some(NodeType::BonsaiFsnodeMapping),
             EdgeType::FsnodeToChildFsnode => Some(NodeType::Fsnode),
+            EdgeType::BonsaiToRootSkeletonManifest => Some(NodeType::BonsaiSkeletonManifestMapping),
+            EdgeType::SkeletonManifestToChildSkeletonManifest => Some(NodeType::SkeletonManifest),
         }
     }
     pub fn outgoing_type(&self) -> NodeType {
@@ -384,6 +406,10 @@ impl EdgeType {
             // Derived data Roots
             EdgeType::RootToBonsaiFsnodeMapping => NodeType::BonsaiFsnodeMapping,
             EdgeType::RootToFsnode => NodeType::Fsnode,
+            EdgeType::RootToBonsaiSkeletonManifestMapping => {
+                NodeType::BonsaiSkeletonManifestMapping
+            }
+            EdgeType::RootToSkeletonManifest => NodeType::SkeletonManifest,
             // Bonsai
             EdgeType::BookmarkToBonsaiChangeset => NodeType::BonsaiChangeset,
             EdgeType::BookmarkToBonsaiHgMapping => NodeType::BonsaiHgMapping,
@@ -395,6 +421,9 @@ impl EdgeType {
             EdgeType::PublishedBookmarksToBonsaiChangeset => NodeType::BonsaiChangeset,
             EdgeType::PublishedBookmarksToBonsaiHgMapping => NodeType::BonsaiHgMapping,
             EdgeType::BonsaiChangesetToBonsaiFsnodeMapping => NodeType::BonsaiFsnodeMapping,
+            EdgeType::BonsaiChangesetToBonsaiSkeletonManifestMapping => {
+                NodeType::BonsaiSkeletonManifestMapping
+            }
             // Hg
             EdgeType::HgBonsaiMappingToBonsaiChangeset => NodeType::BonsaiChangeset,
             EdgeType::HgChangesetToHgParent => NodeType::HgChangeset,
@@ -416,6 +445,8 @@ impl EdgeType {
             // Derived data
             EdgeType::BonsaiToRootFsnode => NodeType::Fsnode,
             EdgeType::FsnodeToChildFsnode => NodeType::Fsnode,
+            EdgeType::BonsaiToRootSkeletonManifest => NodeType::SkeletonManifest,
+            EdgeType::SkeletonManifestToChildSkeletonManifest => NodeType::SkeletonManifest,
         }
     }
 }
@@ -455,6 +486,8 @@ pub enum NodeData {
     // Derived data
     BonsaiFsnodeMapping(Option<FsnodeId>),
     Fsnode(Fsnode),
+    BonsaiSkeletonManifestMapping(Option<SkeletonManifestId>),
+    SkeletonManifest(SkeletonManifest),
 }
 
 impl Node {
@@ -480,6 +513,8 @@ impl Node {
             // Derived data
             Node::BonsaiFsnodeMapping(_) => NodeType::BonsaiFsnodeMapping,
             Node::Fsnode(_) => NodeType::Fsnode,
+            Node::BonsaiSkeletonManifestMapping(_) => NodeType::BonsaiSkeletonManifestMapping,
+            Node::SkeletonManifest(_) => NodeType::SkeletonManifest,
         }
     }
 
@@ -505,6 +540,8 @@ impl Node {
             // Derived data
             Node::BonsaiFsnodeMapping(k) => k.blobstore_key(),
             Node::Fsnode((_, k)) => k.blobstore_key(),
+            Node::BonsaiSkeletonManifestMapping(k) => k.blobstore_key(),
+            Node::SkeletonManifest((_, k)) => k.blobstore_key(),
         }
     }
 
@@ -530,6 +567,8 @@ impl Node {
             // Derived data
             Node::BonsaiFsnodeMapping(_) => None,
             Node::Fsnode((p, _)) => Some(&p),
+            Node::BonsaiSkeletonManifestMapping(_) => None,
+            Node::SkeletonManifest((p, _)) => Some(&p),
         }
     }
 
@@ -556,6 +595,8 @@ impl Node {
             // Derived data
             Node::BonsaiFsnodeMapping(k) => Some(k.sampling_fingerprint()),
             Node::Fsnode((_, k)) => Some(k.sampling_fingerprint()),
+            Node::BonsaiSkeletonManifestMapping(k) => Some(k.sampling_fingerprint()),
+            Node::SkeletonManifest((_, k)) => Some(k.sampling_fingerprint()),
         }
     }
 }
@@ -13,7 +13,7 @@ use filestore::Alias;
 use mercurial_types::{HgChangesetId, HgFileNodeId, HgManifestId};
 use mononoke_types::{
     hash::{GitSha1, Sha1, Sha256},
-    ChangesetId, ContentId, FsnodeId, MPath,
+    ChangesetId, ContentId, FsnodeId, MPath, SkeletonManifestId,
 };
 use std::str::FromStr;
 
@@ -120,6 +120,14 @@ pub fn parse_node(s: &str) -> Result<Node, Error> {
             let id = FsnodeId::from_str(parts[0])?;
             Node::Fsnode((WrappedPath::from(mpath), id))
         }
+        NodeType::BonsaiSkeletonManifestMapping => {
+            Node::BonsaiSkeletonManifestMapping(ChangesetId::from_str(&parts.join(NODE_SEP))?)
+        }
+        NodeType::SkeletonManifest => {
+            let mpath = check_and_build_mpath(node_type, parts)?;
+            let id = SkeletonManifestId::from_str(parts[0])?;
+            Node::SkeletonManifest((WrappedPath::from(mpath), id))
+        }
     };
     Ok(node)
 }
@@ -240,6 +248,26 @@ mod tests {
                     .get_type()
                 );
             }
+            NodeType::BonsaiSkeletonManifestMapping => {
+                assert_eq!(
+                    node_type,
+                    &parse_node(&format!(
+                        "BonsaiSkeletonManifestMapping{}{}",
+                        NODE_SEP, SAMPLE_BLAKE2
+                    ))?
+                    .get_type()
+                );
+            }
+            NodeType::SkeletonManifest => {
+                assert_eq!(
+                    node_type,
+                    &parse_node(&format!(
+                        "SkeletonManifest{}{}{}{}",
+                        NODE_SEP, SAMPLE_BLAKE2, NODE_SEP, SAMPLE_PATH
+                    ))?
+                    .get_type()
+                );
+            }
         };
         Ok(v)
     }
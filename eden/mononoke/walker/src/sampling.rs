@@ -70,6 +70,7 @@ fn filter_repo_path(node_type: NodeType, path: Option<&'_ WrappedPath>) -> Optio
         NodeType::BonsaiPhaseMapping => None,
         NodeType::PublishedBookmarks => None,
         NodeType::BonsaiFsnodeMapping => None,
+        NodeType::BonsaiSkeletonManifestMapping => None,
         // Hg
         NodeType::HgBonsaiMapping => None,
         NodeType::HgChangeset => None,
@@ -82,6 +83,7 @@ fn filter_repo_path(node_type: NodeType, path: Option<&'_ WrappedPath>) -> Optio
         NodeType::AliasContentMapping => path,
         // Derived Data
         NodeType::Fsnode => path,
+        NodeType::SkeletonManifest => path,
     }
 }
 
@@ -133,6 +133,7 @@ const DEEP_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     EdgeType::PublishedBookmarksToBonsaiChangeset,
     EdgeType::PublishedBookmarksToBonsaiHgMapping,
     EdgeType::BonsaiChangesetToBonsaiFsnodeMapping,
+    EdgeType::BonsaiChangesetToBonsaiSkeletonManifestMapping,
     // Hg
     EdgeType::HgBonsaiMappingToBonsaiChangeset,
     EdgeType::HgChangesetToHgParent,
@@ -154,6 +155,8 @@ const DEEP_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     // Derived data
     EdgeType::BonsaiToRootFsnode,
     EdgeType::FsnodeToChildFsnode,
+    EdgeType::BonsaiToRootSkeletonManifest,
+    EdgeType::SkeletonManifestToChildSkeletonManifest,
 ];
 
 // Does not recurse into history, edges to parents excluded
@@ -166,6 +169,7 @@ const SHALLOW_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     EdgeType::PublishedBookmarksToBonsaiChangeset,
     EdgeType::PublishedBookmarksToBonsaiHgMapping,
     EdgeType::BonsaiChangesetToBonsaiFsnodeMapping,
+    EdgeType::BonsaiChangesetToBonsaiSkeletonManifestMapping,
     // Hg
     EdgeType::HgBonsaiMappingToBonsaiChangeset,
     EdgeType::HgChangesetToHgManifest,
@@ -182,6 +186,8 @@ const SHALLOW_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     // Derived data
     EdgeType::BonsaiToRootFsnode,
     EdgeType::FsnodeToChildFsnode,
+    EdgeType::BonsaiToRootSkeletonManifest,
+    EdgeType::SkeletonManifestToChildSkeletonManifest,
 ];
 
 // Types that can result in loading hg data.  Useful for excludes.
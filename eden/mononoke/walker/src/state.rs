@@ -11,7 +11,7 @@ use array_init::array_init;
 use context::CoreContext;
 use dashmap::DashMap;
 use mercurial_types::{HgChangesetId, HgFileNodeId, HgManifestId};
-use mononoke_types::{ChangesetId, ContentId, FsnodeId, MPathHash};
+use mononoke_types::{ChangesetId, ContentId, FsnodeId, MPathHash, SkeletonManifestId};
 use phases::Phase;
 use std::{
     cmp,
@@ -59,6 +59,7 @@ pub struct WalkState {
     visited_hg_filenode: DashMap<(Option<MPathHash>, HgFileNodeId), ()>,
     visited_hg_manifest: DashMap<(Option<MPathHash>, HgManifestId), ()>,
     visited_fsnode: DashMap<(Option<MPathHash>, FsnodeId), ()>,
+    visited_skeleton_manifest: DashMap<(Option<MPathHash>, SkeletonManifestId), ()>,
     visit_count: [AtomicUsize; NodeType::MAX_ORDINAL + 1],
 }
 
@@ -93,6 +94,7 @@ impl WalkState {
             visited_hg_filenode: DashMap::new(),
             visited_hg_manifest: DashMap::new(),
             visited_fsnode: DashMap::new(),
+            visited_skeleton_manifest: DashMap::new(),
             visit_count: array_init(|_i| AtomicUsize::new(0)),
         }
     }
@@ -123,6 +125,7 @@ impl WalkState {
             Node::HgFileEnvelope(id) => self.visited_hg_file_envelope.insert(*id, ()).is_none(),
             Node::FileContent(content_id) => self.visited_file.insert(*content_id, ()).is_none(),
             Node::Fsnode(k) => record_with_path(&self.visited_fsnode, k),
+            Node::SkeletonManifest(k) => record_with_path(&self.visited_skeleton_manifest, k),
             _ => true,
         }
     }
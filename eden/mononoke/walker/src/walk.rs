@@ -31,9 +31,13 @@ use itertools::{Either, Itertools};
 use mercurial_types::{
     FileBytes, HgChangesetId, HgEntryId, HgFileNodeId, HgManifest, HgManifestId, RepoPath,
 };
-use mononoke_types::{fsnode::FsnodeEntry, ChangesetId, ContentId, FsnodeId, MPath};
+use mononoke_types::{
+    fsnode::FsnodeEntry, skeleton_manifest::SkeletonManifestEntry, ChangesetId, ContentId,
+    FsnodeId, MPath, SkeletonManifestId,
+};
 use phases::{HeadsFetcher, Phase, Phases};
 use scuba_ext::ScubaSampleBuilder;
+use skeleton_manifest::RootSkeletonManifestId;
 use slog::warn;
 use std::{
     collections::{HashMap, HashSet},
@@ -239,6 +243,10 @@ async fn bonsai_changeset_step(
         EdgeType::BonsaiChangesetToBonsaiFsnodeMapping,
         Node::BonsaiFsnodeMapping(*bcs_id),
     ));
+    recurse.push(OutgoingEdge::new(
+        EdgeType::BonsaiChangesetToBonsaiSkeletonManifestMapping,
+        Node::BonsaiSkeletonManifestMapping(*bcs_id),
+    ));
     Ok(StepOutput(NodeData::BonsaiChangeset(bcs), recurse))
 }
 
@@ -624,6 +632,68 @@ async fn fsnode_step(
     Ok(StepOutput(NodeData::Fsnode(fsnode), edges))
 }
 
+async fn bonsai_to_skeleton_manifest_mapping_step(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    bcs_id: &ChangesetId,
+    enable_derive: bool,
+) -> Result<StepOutput, Error> {
+    let is_derived = RootSkeletonManifestId::is_derived(&ctx, &repo, &bcs_id).await?;
+
+    if is_derived || enable_derive {
+        let root_skeleton_manifest_id =
+            RootSkeletonManifestId::derive(ctx.clone(), repo.clone(), *bcs_id)
+                .map_err(Error::from)
+                .compat()
+                .await?;
+        let skeleton_manifest_id = *root_skeleton_manifest_id.skeleton_manifest_id();
+
+        Ok(StepOutput(
+            NodeData::BonsaiSkeletonManifestMapping(Some(skeleton_manifest_id)),
+            vec![OutgoingEdge::new(
+                EdgeType::BonsaiToRootSkeletonManifest,
+                Node::SkeletonManifest((WrappedPath::Root, skeleton_manifest_id)),
+            )],
+        ))
+    } else {
+        Ok(StepOutput(
+            NodeData::BonsaiSkeletonManifestMapping(None),
+            vec![],
+        ))
+    }
+}
+
+async fn skeleton_manifest_step(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    path: WrappedPath,
+    skeleton_manifest_id: &SkeletonManifestId,
+) -> Result<StepOutput, Error> {
+    let skeleton_manifest = skeleton_manifest_id
+        .load(ctx.clone(), &repo.get_blobstore())
+        .map_err(Error::from)
+        .await?;
+
+    let mut edges = vec![];
+    for (child, skeleton_entry) in skeleton_manifest.list() {
+        // Skeleton manifests do not have anything to visit for files
+        if let SkeletonManifestEntry::Directory(dir) = skeleton_entry {
+            let skeleton_manifest_id = dir.id();
+            let mpath_opt = WrappedPath::from(MPath::join_element_opt(path.as_ref(), Some(child)));
+
+            edges.push(OutgoingEdge::new(
+                EdgeType::SkeletonManifestToChildSkeletonManifest,
+                Node::SkeletonManifest((mpath_opt, *skeleton_manifest_id)),
+            ));
+        }
+    }
+
+    Ok(StepOutput(
+        NodeData::SkeletonManifest(skeleton_manifest),
+        edges,
+    ))
+}
+
 /// Expand nodes where check for a type is used as a check for other types.
 /// e.g. to make sure metadata looked up/considered for files.
 pub fn expand_checked_nodes(children: &mut Vec<OutgoingEdge>) -> () {
@@ -825,6 +895,12 @@ where
             bonsai_to_fsnode_mapping_step(&ctx, &repo, &cs_id, enable_derive).await
         }
         Node::Fsnode((path, fsnode_id)) => fsnode_step(&ctx, &repo, path, &fsnode_id).await,
+        Node::BonsaiSkeletonManifestMapping(cs_id) => {
+            bonsai_to_skeleton_manifest_mapping_step(&ctx, &repo, &cs_id, enable_derive).await
+        }
+        Node::SkeletonManifest((path, skeleton_manifest_id)) => {
+            skeleton_manifest_step(&ctx, &repo, path, &skeleton_manifest_id).await
+        }
     };
 
     let edge_label = walk_item.label;
@@ -174,7 +174,9 @@ Your running Eden server is more than 45 days old.  You should run
 
 const NULL_COMMIT: [u8; 20] = [0; 20];
 
-fn needs_morestatus_extension(hg_dir: &Path, p2: &[u8; 20]) -> bool {
+/// Returns true if the working copy is in a state (merge, rebase, etc.) that the Python
+/// implementation reports on.
+pub fn needs_morestatus_extension(hg_dir: &Path, p2: &[u8; 20]) -> bool {
     if p2 != &NULL_COMMIT {
         return true;
     }
@@ -413,6 +415,48 @@ impl PrintConfig {
         io: &mut IO,
     ) -> Result<u8> {
         let groups = group_entries(&repo_root, &status, &dirstate_data)?;
+        self.print_groups(&groups, &dirstate_data.copymap, relativizer, use_color, io)?;
+
+        if status.errors.is_empty() {
+            Ok(0)
+        } else {
+            io.write_err("Encountered errors computing status for some paths:\n")?;
+            for (path_str, error) in &status.errors {
+                let path = Path::new(str::from_utf8(path_str)?);
+                io.write_err(format!(
+                    "  {}: {}\n",
+                    &relativizer.relativize(&path.to_path_buf()).display(),
+                    error,
+                ))?;
+            }
+            Ok(1)
+        }
+    }
+
+    /// Print status computed without EdenFS, in the same format as the EdenFS fast path.
+    pub fn print_grouped_status(
+        &self,
+        repo_root: &Path,
+        cwd: &Path,
+        groups: &GroupedEntries,
+        copymap: &HashMap<PathBuf, PathBuf>,
+        io: &mut IO,
+    ) -> Result<u8> {
+        let use_color = should_colorize_output(&io::stdout());
+        let relativizer = PathRelativizer::new(cwd, repo_root);
+        let relativizer = HgStatusPathRelativizer::new(self.root_relative, relativizer);
+        self.print_groups(groups, copymap, &relativizer, use_color, io)?;
+        Ok(0)
+    }
+
+    fn print_groups(
+        &self,
+        groups: &GroupedEntries,
+        copymap: &HashMap<PathBuf, PathBuf>,
+        relativizer: &HgStatusPathRelativizer,
+        use_color: bool,
+        io: &mut IO,
+    ) -> Result<(), io::Error> {
         let endl = self.endl;
 
         let mut print_group =
@@ -449,7 +493,7 @@ impl PrintConfig {
                         endl
                     ))?;
                     if self.copies {
-                        if let Some(ref p) = dirstate_data.copymap.get(path) {
+                        if let Some(ref p) = copymap.get(path) {
                             io.write(format!(
                                 "  {}{}",
                                 &relativizer.relativize(p).display(),
@@ -487,22 +531,7 @@ impl PrintConfig {
             self.status_types.ignored,
             &groups.ignored,
         )?;
-        print_group(PrintGroup::Clean, self.status_types.clean, &groups.clean)?;
-
-        if status.errors.is_empty() {
-            Ok(0)
-        } else {
-            io.write_err("Encountered errors computing status for some paths:\n")?;
-            for (path_str, error) in &status.errors {
-                let path = Path::new(str::from_utf8(path_str)?);
-                io.write_err(format!(
-                    "  {}: {}\n",
-                    &relativizer.relativize(&path.to_path_buf()).display(),
-                    error,
-                ))?;
-            }
-            Ok(1)
-        }
+        print_group(PrintGroup::Clean, self.status_types.clean, &groups.clean)
     }
 }
 
@@ -527,15 +556,16 @@ enum PrintGroup {
     Clean,
 }
 
+/// Paths relative to the repository root, grouped by their status.
 #[derive(Default)]
-struct GroupedEntries {
-    modified: Vec<PathBuf>,
-    added: Vec<PathBuf>,
-    removed: Vec<PathBuf>,
-    deleted: Vec<PathBuf>,
-    unknown: Vec<PathBuf>,
-    ignored: Vec<PathBuf>,
-    clean: Vec<PathBuf>,
+pub struct GroupedEntries {
+    pub modified: Vec<PathBuf>,
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    pub deleted: Vec<PathBuf>,
+    pub unknown: Vec<PathBuf>,
+    pub ignored: Vec<PathBuf>,
+    pub clean: Vec<PathBuf>,
 }
 
 fn group_entries(
@@ -6,7 +6,8 @@
  */
 
 use crate::commands::{FormatterOpts, WalkOpts};
-use anyhow::Result;
+use anyhow::{bail, Result};
+use bytes::Bytes;
 use clidispatch::{
     command::{CommandTable, Register},
     errors,
@@ -14,8 +15,36 @@ use clidispatch::{
     repo::Repo,
 };
 use cliparser::define_flags;
-
-use edenfs_client::status::{maybe_status_fastpath, PrintConfig, PrintConfigStatusTypes};
+use configparser::hg::ConfigSetHgExt;
+use dag::ops::{DagAlgorithm, IdConvert, PrefixLookup};
+use dag::{Id, Vertex};
+use hgcommits::{ReadCommitText, RevlogCommits};
+use manifest_tree::{TreeManifest, TreeStore};
+use parking_lot::Mutex;
+use pathmatcher::{
+    expand_curly_brackets, normalize_glob, plain_to_glob, GitignoreMatcher, TreeMatcher,
+};
+use revisionstore::{ContentStore, ContentStoreBuilder, HgIdDataStore};
+use treestate::store::BlockId;
+use treestate::treestate::TreeState;
+use types::{HgId, Key, RepoPath, RepoPathBuf};
+use util::path::expand_path;
+use workingcopy::filesystem::{HgModifiedTime, PhysicalFileSystem};
+use workingcopy::status::{Status, StatusMatcher};
+
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::str::{self, FromStr};
+use std::sync::Arc;
+
+use edenfs_client::status::{
+    maybe_status_fastpath, needs_morestatus_extension, GroupedEntries, PrintConfig,
+    PrintConfigStatusTypes,
+};
 
 /// Return the main command table including all Rust commands.
 pub(crate) fn register(table: &mut CommandTable) {
@@ -179,22 +208,75 @@ define_flags! {
 }
 
 fn status(opts: StatusOpts, io: &mut IO, repo: Repo) -> Result<u8> {
+    if let Some(reason) = unsupported_opts(&opts) {
+        tracing::debug!("native status is not used: {}", reason);
+        return Err(errors::FallbackToPython.into());
+    }
+    if !repo.config().get_or("status", "use-rust", || true)? {
+        return Err(errors::FallbackToPython.into());
+    }
+
     let rev_check = opts.rev.is_empty() || (opts.rev.len() == 1 && opts.rev[0] == ".");
 
-    let args_check = opts.args.is_empty() || (opts.args.len() == 1 && opts.args[0] == "re:.");
+    let walk_check = opts.walk_opts.include.is_empty() && opts.walk_opts.exclude.is_empty();
 
-    if opts.all
-        || !opts.change.is_empty()
-        || !opts.terse.is_empty()
-        || !rev_check
-        || !opts.walk_opts.include.is_empty()
-        || !opts.walk_opts.exclude.is_empty()
-        || !opts.formatter_opts.template.is_empty()
-        || !args_check
-    {
-        return Err(errors::FallbackToPython.into());
+    let cwd = std::env::current_dir()?;
+    if repo.path().join(".eden").exists() {
+        if !rev_check || !walk_check || opts.all || !opts.change.is_empty() {
+            return Err(errors::FallbackToPython.into());
+        }
+        return maybe_status_fastpath(repo.path(), &cwd, print_config(&opts), io);
+    }
+
+    // Anything the native status cannot handle, including errors like
+    // unreadable files or data missing from local stores, is left to the
+    // Python implementation, which reports them properly.
+    let status = match native_status(&opts, &cwd, &repo) {
+        Ok(status) => status,
+        Err(err) => {
+            tracing::debug!("native status is not used: {}", err);
+            return Err(errors::FallbackToPython.into());
+        }
+    };
+
+    let groups = GroupedEntries {
+        modified: to_paths(&status.modified),
+        added: to_paths(&status.added),
+        removed: to_paths(&status.removed),
+        deleted: to_paths(&status.deleted),
+        unknown: to_paths(&status.unknown),
+        ignored: to_paths(&status.ignored),
+        clean: to_paths(&status.clean),
+    };
+    let copymap = status
+        .copymap
+        .iter()
+        .map(|(dest, source)| (PathBuf::from(dest.as_str()), PathBuf::from(source.as_str())))
+        .collect();
+    print_config(&opts).print_grouped_status(repo.path(), &cwd, &groups, &copymap, io)
+}
+
+/// Options whose output is only implemented in Python. Returns the reason
+/// the native status cannot handle them.
+fn unsupported_opts(opts: &StatusOpts) -> Option<&'static str> {
+    if !opts.terse.is_empty() {
+        Some("--terse needs every file of a directory to abbreviate it")
+    } else if !opts.formatter_opts.template.is_empty() {
+        Some("templates are rendered by Python")
+    } else if !(opts.args.is_empty() || (opts.args.len() == 1 && opts.args[0] == "re:.")) {
+        // Python supports every pattern kind, warns about missing explicit
+        // files and prints paths relative to the cwd for them.
+        Some("file patterns are handled by Python")
+    } else if opts.rev.len() > 2 {
+        Some("more than two --rev are resolved as revsets")
+    } else if !opts.change.is_empty() && !opts.rev.is_empty() {
+        Some("--change and --rev together are reported as an error by Python")
+    } else {
+        None
     }
+}
 
+fn print_config(opts: &StatusOpts) -> PrintConfig {
     let StatusOpts {
         modified,
         added,
@@ -204,9 +286,19 @@ fn status(opts: StatusOpts, io: &mut IO, repo: Repo) -> Result<u8> {
         unknown,
         ignored,
         ..
-    } = opts;
+    } = *opts;
 
-    let status_types = if modified || added || removed || deleted || clean || unknown || ignored {
+    let status_types = if opts.all {
+        PrintConfigStatusTypes {
+            modified: true,
+            added: true,
+            removed: true,
+            deleted: true,
+            clean: true,
+            unknown: true,
+            ignored: true,
+        }
+    } else if modified || added || removed || deleted || clean || unknown || ignored {
         PrintConfigStatusTypes {
             modified,
             added,
@@ -227,14 +319,639 @@ fn status(opts: StatusOpts, io: &mut IO, repo: Repo) -> Result<u8> {
             ignored: false,
         }
     };
-    let print_config = PrintConfig {
+    PrintConfig {
         status_types,
         no_status: opts.no_status,
-        copies: opts.copies,
+        // Like Python, --all implies --copies and --no-status disables them.
+        copies: (opts.copies || opts.all) && !opts.no_status,
         endl: if opts.print0 { '\0' } else { '\n' },
         root_relative: opts.root_relative,
+    }
+}
+
+/// Compute status for a working copy that is not backed by EdenFS, using the
+/// treestate, the working copy and local stores only.
+fn native_status(opts: &StatusOpts, cwd: &Path, repo: &Repo) -> Result<Status> {
+    let dot_hg_path = repo.dot_hg_path();
+    let requirements = fs::read_to_string(dot_hg_path.join("requires"))?;
+    let store_path = repo.shared_dot_hg_path().join("store");
+    let store_requirements =
+        fs::read_to_string(store_path.join("requires")).unwrap_or_else(|_| String::new());
+    if !requirements.lines().any(|r| r == "remotefilelog")
+        || !requirements.lines().any(|r| r == "treestate")
+        || store_requirements.lines().any(|r| r == "zstorecommitdata")
+    {
+        bail!("unsupported repo requirements");
+    }
+    if dot_hg_path.join("sparse").exists() {
+        bail!("sparse checkouts are not supported");
+    }
+
+    let dirstate = read_treestate_dirstate(dot_hg_path)?;
+    if needs_morestatus_extension(dot_hg_path, dirstate.p2.as_ref().try_into()?) {
+        bail!("unfinished operations are reported by Python");
+    }
+
+    // Resolve the revisions before doing any work.
+    let commits = RevlogCommits::new(&store_path)?;
+    let commit_pair = resolve_commit_pair(opts, &commits, &store_path, dirstate.p1)?;
+    let other = match opts.rev.as_slice() {
+        [rev] => match resolve_rev(&commits, &store_path, dirstate.p1, rev)? {
+            other if other == dirstate.p1 => None,
+            other => Some(other),
+        },
+        _ => None,
     };
 
-    let cwd = std::env::current_dir()?;
-    maybe_status_fastpath(repo.path(), &cwd, print_config, io)
+    let config = repo.config();
+    let filestore = Arc::new(
+        ContentStoreBuilder::new(config)
+            .local_path(&store_path)
+            .build()?,
+    );
+    let treestore: Arc<dyn TreeStore + Send + Sync> = Arc::new(ManifestStore(
+        ContentStoreBuilder::new(config)
+            .local_path(&store_path)
+            .suffix("manifests")
+            .build()?,
+    ));
+    let list_ignored = opts.ignored || opts.all;
+    let list_clean = opts.clean || opts.all;
+    let copies = (opts.copies || opts.all) && !opts.no_status;
+    let matcher = Arc::new(walk_matcher(opts, repo.path(), cwd)?);
+
+    // Comparing two commits does not involve the working copy.
+    if let Some((from, to)) = commit_pair {
+        if copies {
+            bail!("copies between commits are not supported");
+        }
+        let from_manifest = commit_manifest(&commits, treestore.clone(), from)?;
+        let to_manifest = commit_manifest(&commits, treestore, to)?;
+        return Status::between(&from_manifest, &to_manifest, &matcher, list_clean);
+    }
+
+    let p1_manifest = commit_manifest(&commits, treestore.clone(), dirstate.p1)?;
+    let other_manifest = match other {
+        Some(other) => Some(commit_manifest(&commits, treestore.clone(), other)?),
+        None => None,
+    };
+
+    let mut ignore_paths = Vec::new();
+    for name in config.keys("ui") {
+        if name == "ignore" || name.starts_with("ignore.") {
+            if let Some(path) = config.get("ui", &name) {
+                ignore_paths.push(repo.path().join(expand_path(path)));
+            }
+        }
+    }
+    let ignore = Rc::new(GitignoreMatcher::new(
+        repo.path(),
+        ignore_paths.iter().map(|p| p.as_path()).collect(),
+    ));
+
+    // Files with the same mtime as the last treestate write might have been
+    // modified after it within the same second, so they always need a lookup.
+    let treestate_path = dot_hg_path.join("treestate").join(&dirstate.filename);
+    let last_write: HgModifiedTime = fs::metadata(&treestate_path)?.modified()?.try_into()?;
+    let treestate = Arc::new(Mutex::new(TreeState::open(
+        &treestate_path,
+        Some(BlockId(dirstate.rootid)),
+    )?));
+
+    let filesystem = PhysicalFileSystem::new(repo.path().to_path_buf())?;
+    let mut pending_changes = filesystem
+        .pending_changes(
+            treestate.clone(),
+            StatusMatcher::new(matcher.clone(), ignore.clone(), list_ignored),
+            false,
+            last_write,
+        )
+        .compare_content(p1_manifest.clone(), filestore.clone());
+    let status = Status::from_pending_changes(
+        &filesystem,
+        &mut pending_changes,
+        treestate.clone(),
+        &matcher,
+        &ignore,
+        list_ignored,
+        list_clean,
+    )?;
+
+    // Like Python, saving files found clean by lookups is optional.
+    if pending_changes.resolved_lookups() > 0 {
+        if let Err(err) = write_treestate(dot_hg_path, &dirstate, &treestate) {
+            tracing::debug!("cannot write treestate: {}", err);
+        }
+    }
+
+    match other_manifest {
+        None => Ok(status),
+        Some(other_manifest) => {
+            if copies {
+                bail!("copies relative to another revision are not supported");
+            }
+            status.compare_with(
+                &filesystem,
+                &p1_manifest,
+                &other_manifest,
+                filestore,
+                &matcher,
+                list_clean,
+            )
+        }
+    }
+}
+
+/// Build a matcher from -I and -X patterns. Only `glob:` (the default) and
+/// `path:` patterns are supported.
+fn walk_matcher(opts: &StatusOpts, root: &Path, cwd: &Path) -> Result<TreeMatcher> {
+    if opts.walk_opts.include.is_empty() && opts.walk_opts.exclude.is_empty() {
+        return Ok(TreeMatcher::always());
+    }
+    let cwd = match cwd.strip_prefix(root)?.to_str() {
+        Some(cwd) => cwd.replace('\\', "/"),
+        None => bail!("cwd is not valid UTF-8"),
+    };
+
+    let mut rules = Vec::new();
+    for pattern in opts.walk_opts.include.iter() {
+        rules.extend(pattern_to_rules(pattern, &cwd)?);
+    }
+    if rules.is_empty() {
+        rules.push("**".to_string());
+    }
+    for pattern in opts.walk_opts.exclude.iter() {
+        rules.extend(
+            pattern_to_rules(pattern, &cwd)?
+                .into_iter()
+                .map(|rule| format!("!{}", rule)),
+        );
+    }
+    Ok(TreeMatcher::from_rules(rules.iter())?)
+}
+
+/// Convert an include or exclude pattern to [`TreeMatcher`] rules. Like
+/// Mercurial patterns, they also match everything under matched directories.
+fn pattern_to_rules(pattern: &str, cwd: &str) -> Result<Vec<String>> {
+    let globs = if pattern.starts_with("path:") {
+        vec![plain_to_glob(normalize_path("", &pattern[5..])?.as_str())]
+    } else {
+        let pattern = if pattern.starts_with("glob:") {
+            &pattern[5..]
+        } else if pattern.contains(':') {
+            bail!("unsupported pattern kind: {}", pattern);
+        } else {
+            pattern
+        };
+        expand_curly_brackets(pattern)
+            .into_iter()
+            .map(|glob| normalize_path(cwd, &glob).map(|glob| normalize_glob(&glob)))
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    let mut rules = Vec::with_capacity(globs.len() * 2);
+    for glob in globs.into_iter() {
+        if glob.is_empty() {
+            rules.push("**".to_string());
+        } else {
+            rules.push(format!("{}/**", glob));
+            rules.push(glob);
+        }
+    }
+    Ok(rules)
+}
+
+/// Join `path` to `base` and resolve `.` and `..` components. Paths escaping
+/// the repository are rejected.
+fn normalize_path(base: &str, path: &str) -> Result<String> {
+    if path.starts_with('/') {
+        bail!("absolute paths are not supported: {}", path);
+    }
+    let mut components: Vec<&str> = Vec::new();
+    for component in base.split('/').chain(path.split('/')) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                if components.pop().is_none() {
+                    bail!("{} is outside the repository", path);
+                }
+            }
+            component => components.push(component),
+        }
+    }
+    Ok(components.join("/"))
+}
+
+fn to_paths(paths: &BTreeSet<RepoPathBuf>) -> Vec<PathBuf> {
+    paths.iter().map(|p| PathBuf::from(p.as_str())).collect()
+}
+
+/// Read the manifest of a commit from the changelog.
+fn commit_manifest(
+    commits: &RevlogCommits,
+    store: Arc<dyn TreeStore + Send + Sync>,
+    node: HgId,
+) -> Result<TreeManifest> {
+    if node.is_null() {
+        return Ok(TreeManifest::ephemeral(store));
+    }
+    let text = match commits.get_commit_raw_text(&Vertex::copy_from(node.as_ref()))? {
+        Some(text) => text,
+        None => bail!("commit {} is not found", node),
+    };
+    let manifest = match text.split(|&b| b == b'\n').next() {
+        Some(line) => HgId::from_str(str::from_utf8(line)?)?,
+        None => bail!("commit {} has no manifest", node),
+    };
+    Ok(TreeManifest::durable(store, manifest))
+}
+
+/// Trees from the local store.
+struct ManifestStore(ContentStore);
+
+impl TreeStore for ManifestStore {
+    fn get(&self, path: &RepoPath, hgid: HgId) -> Result<Bytes> {
+        let key = Key::new(path.to_owned(), hgid);
+        match self.0.get(&key)? {
+            Some(data) => Ok(data.into()),
+            None => bail!("tree {} at '{}' is not found", hgid, path),
+        }
+    }
+
+    fn insert(&self, _path: &RepoPath, _hgid: HgId, _data: Bytes) -> Result<()> {
+        bail!("insert is not implemented")
+    }
+}
+
+/// Resolve a revision for `--rev`. Like `repo.lookup`, this accepts `.`,
+/// `null`, revision numbers, full hashes, bookmarks, remote bookmarks and
+/// unambiguous hash prefixes. Anything else, like revsets, is left to Python.
+fn resolve_rev(commits: &RevlogCommits, store_path: &Path, p1: HgId, rev: &str) -> Result<HgId> {
+    match rev {
+        "." => return Ok(p1),
+        "null" => return Ok(*HgId::null_id()),
+        _ => {}
+    }
+    if let Ok(num) = rev.parse::<u64>() {
+        if num.to_string() == rev {
+            if let Ok(vertex) = commits.vertex_name(Id(num)) {
+                return Ok(HgId::from_slice(vertex.as_ref())?);
+            }
+        }
+    }
+    if rev.len() == HgId::hex_len() {
+        if let Ok(id) = HgId::from_str(rev) {
+            if commits.contains_vertex_name(&Vertex::copy_from(id.as_ref()))? {
+                return Ok(id);
+            }
+        }
+    }
+    if let Some(id) = lookup_bookmark(&store_path.join("bookmarks"), None, rev)? {
+        return Ok(id);
+    }
+    if let Some(id) = lookup_bookmark(&store_path.join("remotenames"), Some("bookmarks"), rev)? {
+        return Ok(id);
+    }
+    if rev.bytes().all(|b| b.is_ascii_hexdigit()) {
+        let vertexes = commits.vertexes_by_hex_prefix(rev.as_bytes(), 2)?;
+        if vertexes.len() == 1 {
+            return Ok(HgId::from_slice(vertexes[0].as_ref())?);
+        }
+    }
+    bail!("unsupported revision: {}", rev)
+}
+
+/// Resolve the commits compared by `--change` or by two `--rev`, which do
+/// not involve the working copy. Returns `None` for other comparisons.
+fn resolve_commit_pair(
+    opts: &StatusOpts,
+    commits: &RevlogCommits,
+    store_path: &Path,
+    p1: HgId,
+) -> Result<Option<(HgId, HgId)>> {
+    if !opts.change.is_empty() {
+        let to = resolve_rev(commits, store_path, p1, &opts.change)?;
+        let from = match commits
+            .parent_names(Vertex::copy_from(to.as_ref()))?
+            .first()
+        {
+            Some(parent) => HgId::from_slice(parent.as_ref())?,
+            None => *HgId::null_id(),
+        };
+        Ok(Some((from, to)))
+    } else if opts.rev.len() == 2 {
+        let from = resolve_rev(commits, store_path, p1, &opts.rev[0])?;
+        let to = resolve_rev(commits, store_path, p1, &opts.rev[1])?;
+        Ok(Some((from, to)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Look up a name in a file of `<hex> <name>` lines, or `<hex> <type> <name>`
+/// lines if `nametype` is given.
+fn lookup_bookmark(path: &Path, nametype: Option<&str>, name: &str) -> Result<Option<HgId>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    for line in content.lines() {
+        let mut parts = line.trim().splitn(2, ' ');
+        let (hex, rest) = match (parts.next(), parts.next()) {
+            (Some(hex), Some(rest)) => (hex, rest),
+            _ => continue,
+        };
+        let found = match nametype {
+            None => rest,
+            Some(nametype) => {
+                let mut parts = rest.splitn(2, ' ');
+                match (parts.next(), parts.next()) {
+                    (Some(found_type), Some(found)) if found_type == nametype => found,
+                    _ => continue,
+                }
+            }
+        };
+        if found == name {
+            return Ok(Some(HgId::from_str(hex)?));
+        }
+    }
+    Ok(None)
+}
+
+/// Mercurial's working copy lock, taken in its legacy symlink form, which the
+/// Python implementation respects. The lock is released on drop.
+struct WorkingCopyLock(PathBuf);
+
+impl WorkingCopyLock {
+    /// Take the lock without waiting. Returns `None` if it is held elsewhere.
+    #[cfg(unix)]
+    fn try_lock(dot_hg_path: &Path) -> Result<Option<Self>> {
+        let path = dot_hg_path.join("wlock");
+        let info = format!("{}:{}", lock_namespace()?, std::process::id());
+        match std::os::unix::fs::symlink(&info, &path) {
+            Ok(()) => Ok(Some(WorkingCopyLock(path))),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn try_lock(_dot_hg_path: &Path) -> Result<Option<Self>> {
+        Ok(None)
+    }
+}
+
+impl Drop for WorkingCopyLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// The namespace part of lock info, matching `lockinfo.getcurrentnamespace`
+/// in Python so it can tell whether the lock is stale.
+#[cfg(unix)]
+fn lock_namespace() -> Result<String> {
+    let mut namespace = hostname::get()?.to_string_lossy().into_owned();
+    if cfg!(target_os = "linux") {
+        use std::os::unix::fs::MetadataExt;
+        if let Ok(metadata) = fs::metadata("/proc/self/ns/pid") {
+            namespace.push_str(&format!("/{:x}", metadata.ino()));
+        }
+    }
+    Ok(namespace)
+}
+
+/// Write the in-memory changes of the treestate and point `.hg/dirstate` at
+/// the new root. Nothing is written if the working copy lock is held
+/// elsewhere, or if the dirstate changed since it was read.
+fn write_treestate(
+    dot_hg_path: &Path,
+    dirstate: &TreestateDirstate,
+    treestate: &Mutex<TreeState>,
+) -> Result<()> {
+    let _lock = match WorkingCopyLock::try_lock(dot_hg_path)? {
+        Some(lock) => lock,
+        None => return Ok(()),
+    };
+    if read_treestate_dirstate(dot_hg_path)? != *dirstate {
+        return Ok(());
+    }
+
+    let BlockId(rootid) = treestate.lock().flush()?;
+
+    let mut content = Vec::new();
+    content.extend_from_slice(dirstate.p1.as_ref());
+    content.extend_from_slice(dirstate.p2.as_ref());
+    content.extend_from_slice(TREESTATE_HEADER);
+    content
+        .extend_from_slice(format!("filename={}\0rootid={}", dirstate.filename, rootid).as_bytes());
+    if dirstate.threshold > 0 {
+        content.extend_from_slice(format!("\0threshold={}", dirstate.threshold).as_bytes());
+    }
+
+    let mut file = tempfile::NamedTempFile::new_in(dot_hg_path)?;
+    file.write_all(&content)?;
+    file.persist(dot_hg_path.join("dirstate"))?;
+    Ok(())
+}
+
+const TREESTATE_HEADER: &[u8] = b"\ntreestate\n\0";
+
+#[derive(Debug, PartialEq)]
+struct TreestateDirstate {
+    p1: HgId,
+    p2: HgId,
+    filename: String,
+    rootid: u64,
+    threshold: u64,
+}
+
+/// Parse `.hg/dirstate` pointing to a treestate file.
+fn read_treestate_dirstate(dot_hg_path: &Path) -> Result<TreestateDirstate> {
+    let content = fs::read(dot_hg_path.join("dirstate"))?;
+    let len = HgId::len();
+    let header_end = len * 2 + TREESTATE_HEADER.len();
+    if content.len() < header_end || &content[len * 2..header_end] != TREESTATE_HEADER {
+        bail!("dirstate is not in treestate format");
+    }
+
+    let mut filename = None;
+    let mut rootid = None;
+    let mut threshold = 0;
+    for entry in content[header_end..].split(|&b| b == 0) {
+        let entry = str::from_utf8(entry)?;
+        if let Some(pos) = entry.find('=') {
+            match &entry[..pos] {
+                "filename" => filename = Some(entry[pos + 1..].to_string()),
+                "rootid" => rootid = Some(entry[pos + 1..].parse::<u64>()?),
+                "threshold" => threshold = entry[pos + 1..].parse::<u64>()?,
+                _ => {}
+            }
+        }
+    }
+
+    match (filename, rootid) {
+        (Some(filename), Some(rootid)) if rootid > 0 => Ok(TreestateDirstate {
+            p1: HgId::from_slice(&content[..len])?,
+            p2: HgId::from_slice(&content[len..len * 2])?,
+            filename,
+            rootid,
+            threshold,
+        }),
+        _ => bail!("dirstate has no treestate root"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hgcommits::{AppendCommits, HgCommit};
+    use tempfile::tempdir;
+    use treestate::filestate::{FileStateV2, StateFlags};
+
+    fn node(hex: &str) -> HgId {
+        HgId::from_str(&format!("{:0<40}", hex)).unwrap()
+    }
+
+    fn file_state() -> FileStateV2 {
+        FileStateV2 {
+            mode: 0o100644,
+            size: 1,
+            mtime: 1,
+            state: StateFlags::EXIST_P1 | StateFlags::EXIST_NEXT,
+            copied: None,
+        }
+    }
+
+    /// Write a treestate with one file and `.hg/dirstate` pointing at it.
+    fn init_dirstate(dot_hg_path: &Path) -> Result<TreestateDirstate> {
+        fs::create_dir(dot_hg_path.join("treestate"))?;
+        let mut treestate = TreeState::open(dot_hg_path.join("treestate").join("tree"), None)?;
+        treestate.insert("a", &file_state())?;
+        let BlockId(rootid) = treestate.flush()?;
+
+        let mut content = Vec::new();
+        content.extend_from_slice(node("1").as_ref());
+        content.extend_from_slice(HgId::null_id().as_ref());
+        content.extend_from_slice(TREESTATE_HEADER);
+        content.extend_from_slice(format!("filename=tree\0rootid={}", rootid).as_bytes());
+        fs::write(dot_hg_path.join("dirstate"), content)?;
+        read_treestate_dirstate(dot_hg_path)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_working_copy_lock() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("wlock");
+
+        let lock = WorkingCopyLock::try_lock(dir.path())?.expect("lock should be free");
+        let info = fs::read_link(&path)?;
+        let suffix = format!(":{}", std::process::id());
+        assert!(info.to_string_lossy().ends_with(&suffix));
+        assert!(WorkingCopyLock::try_lock(dir.path())?.is_none());
+
+        drop(lock);
+        assert!(fs::symlink_metadata(&path).is_err());
+        assert!(WorkingCopyLock::try_lock(dir.path())?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_treestate_dirstate() -> Result<()> {
+        let dir = tempdir()?;
+        let dirstate = init_dirstate(dir.path())?;
+        assert_eq!(dirstate.p1, node("1"));
+        assert_eq!(dirstate.p2, *HgId::null_id());
+        assert_eq!(dirstate.filename, "tree");
+        assert_eq!(dirstate.threshold, 0);
+
+        fs::write(dir.path().join("dirstate"), vec![0; 40])?;
+        assert!(read_treestate_dirstate(dir.path()).is_err());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_treestate() -> Result<()> {
+        let dir = tempdir()?;
+        let dot_hg_path = dir.path();
+        let dirstate = init_dirstate(dot_hg_path)?;
+        let treestate_path = dot_hg_path.join("treestate").join("tree");
+        let open = |rootid| -> Result<Mutex<TreeState>> {
+            Ok(Mutex::new(TreeState::open(
+                &treestate_path,
+                Some(BlockId(rootid)),
+            )?))
+        };
+
+        // Nothing is written while the lock is held elsewhere.
+        let treestate = open(dirstate.rootid)?;
+        treestate.lock().insert("b", &file_state())?;
+        {
+            let _lock = WorkingCopyLock::try_lock(dot_hg_path)?.unwrap();
+            write_treestate(dot_hg_path, &dirstate, &treestate)?;
+        }
+        assert_eq!(read_treestate_dirstate(dot_hg_path)?, dirstate);
+
+        // Otherwise the new root is written, and the lock is released.
+        write_treestate(dot_hg_path, &dirstate, &treestate)?;
+        let written = read_treestate_dirstate(dot_hg_path)?;
+        assert_ne!(written.rootid, dirstate.rootid);
+        assert_eq!(
+            (written.p1, written.p2, &written.filename),
+            (dirstate.p1, dirstate.p2, &dirstate.filename)
+        );
+        assert!(open(written.rootid)?.lock().get("b")?.is_some());
+        assert!(fs::symlink_metadata(dot_hg_path.join("wlock")).is_err());
+
+        // Nothing is written if the dirstate changed since it was read.
+        let treestate = open(dirstate.rootid)?;
+        treestate.lock().insert("c", &file_state())?;
+        write_treestate(dot_hg_path, &dirstate, &treestate)?;
+        assert_eq!(read_treestate_dirstate(dot_hg_path)?, written);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_rev() -> Result<()> {
+        let dir = tempdir()?;
+        let store_path = dir.path();
+        let (a, b, c) = (node("aa"), node("bb"), node("ab"));
+        let mut commits = RevlogCommits::new(store_path)?;
+        let vertex = |id: HgId| Vertex::copy_from(id.as_ref());
+        let commit = |id: HgId, parents: Vec<HgId>| HgCommit {
+            vertex: vertex(id),
+            parents: parents.into_iter().map(vertex).collect(),
+            raw_text: Default::default(),
+        };
+        commits.add_commits(&[commit(a, vec![]), commit(b, vec![a]), commit(c, vec![a])])?;
+        commits.flush(&[])?;
+        let commits = RevlogCommits::new(store_path)?;
+
+        fs::write(store_path.join("bookmarks"), format!("{} book\n", b))?;
+        fs::write(
+            store_path.join("remotenames"),
+            format!("{} bookmarks remote/master\n", c),
+        )?;
+
+        let resolve = |rev: &str| resolve_rev(&commits, store_path, b, rev);
+        assert_eq!(resolve(".")?, b);
+        assert_eq!(resolve("null")?, *HgId::null_id());
+        assert_eq!(resolve("0")?, a);
+        assert_eq!(resolve("2")?, c);
+        assert_eq!(resolve(&a.to_hex())?, a);
+        assert_eq!(resolve("book")?, b);
+        assert_eq!(resolve("remote/master")?, c);
+        assert_eq!(resolve("bb")?, b);
+
+        // Ambiguous prefixes, unknown names and revsets are left to Python.
+        assert!(resolve("a").is_err());
+        assert!(resolve("3").is_err());
+        assert!(resolve("remote/other").is_err());
+        assert!(resolve("desc(foo)").is_err());
+        Ok(())
+    }
 }
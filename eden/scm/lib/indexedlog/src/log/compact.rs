@@ -0,0 +1,175 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::errors::{IoResultExt, ResultExt};
+use crate::index;
+use crate::lock::ScopedDirLock;
+use crate::log::{self, GenericPath, Log, OpenOptions, META_FILE, PRIMARY_START_OFFSET};
+use crate::utils::{self, mmap_path};
+use std::io::{self, Write};
+use std::sync::Arc;
+
+// Compaction
+impl OpenOptions {
+    /// Rewrite the [`Log`] at the given directory so it only keeps entries
+    /// that `predicate` returns `true` for. Entries keep their order.
+    ///
+    /// The primary log and indexes are written to new files of the next
+    /// generation. They are published by a single atomic metadata write, so
+    /// the log is either compacted or unchanged if the process crashes.
+    /// Existing [`Log`]s keep reading the old files, and reload on the next
+    /// `sync`, since the epoch is bumped. Their pending in-memory entries are
+    /// preserved. Files of the previous generation are kept for readers that
+    /// are still loading them. Older generations are removed.
+    ///
+    /// Return message useful for human consumption.
+    pub fn compact(
+        &self,
+        dir: impl Into<GenericPath>,
+        mut predicate: impl FnMut(&[u8]) -> bool,
+    ) -> crate::Result<String> {
+        let dir = dir.into();
+        let dir = match dir.as_opt_path() {
+            Some(dir) => dir,
+            None => return Ok(format!("{:?} is not on disk. Nothing to compact.\n", &dir)),
+        };
+
+        let result: crate::Result<_> = (|| {
+            if !dir.exists() {
+                return Ok(format!("{:?} does not exist. Nothing to compact.\n", dir));
+            }
+
+            let lock = ScopedDirLock::new(dir)?;
+
+            // Indexes are rebuilt from scratch. Do not load them.
+            let log = self
+                .clone()
+                .create(false)
+                .index_defs(Vec::new())
+                .open_with_lock(&dir.into(), &lock)
+                .context("cannot open log for compaction")?;
+
+            // Copy the accepted entries as-is, so they keep their
+            // checksums and compression.
+            let tmp_primary = tempfile::NamedTempFile::new_in(dir)
+                .context(dir, "cannot create tempfile for compacted log")?;
+            let header = &log.disk_buf[..PRIMARY_START_OFFSET as usize];
+            let mut new_len = header.len() as u64;
+            let mut entry_count = 0;
+            let mut kept_count = 0;
+            {
+                let mut writer = io::BufWriter::new(tmp_primary.as_file());
+                writer
+                    .write_all(header)
+                    .context(tmp_primary.path(), "cannot write")?;
+                let mut iter = log.iter();
+                loop {
+                    let offset = iter.next_offset;
+                    let data = match iter.next_bytes() {
+                        None => break,
+                        Some(data) => data?,
+                    };
+                    entry_count += 1;
+                    if predicate(&data) {
+                        let entry = &log.disk_buf[offset as usize..iter.next_offset as usize];
+                        writer
+                            .write_all(entry)
+                            .context(tmp_primary.path(), "cannot write")?;
+                        new_len += entry.len() as u64;
+                        kept_count += 1;
+                    }
+                }
+                writer.flush().context(tmp_primary.path(), "cannot write")?;
+            }
+
+            if kept_count == entry_count {
+                return Ok(format!(
+                    "Kept all {} entries, {} bytes in log. Nothing to compact.\n",
+                    entry_count, new_len
+                ));
+            }
+
+            let mut message = format!(
+                "Kept {} of {} entries, {} of {} bytes in log\n",
+                kept_count, entry_count, new_len, log.meta.primary_len
+            );
+
+            if self.fsync {
+                tmp_primary
+                    .as_file()
+                    .sync_all()
+                    .context(tmp_primary.path(), "cannot fsync")?;
+            }
+            let _ = utils::fix_perm_file(tmp_primary.as_file(), false);
+
+            // Build indexes for the compacted log.
+            let mut tmp_indexes = Vec::with_capacity(self.index_defs.len());
+            {
+                let disk_buf = mmap_path(tmp_primary.path(), new_len)?;
+                for def in self.index_defs.iter() {
+                    let tmp = tempfile::NamedTempFile::new_in(dir).context(dir, || {
+                        format!("cannot create tempfile for rebuilding index {:?}", def.name)
+                    })?;
+                    let index_len = {
+                        let mut index = index::OpenOptions::new()
+                            .key_buf(Some(Arc::new(disk_buf.clone())))
+                            .open(tmp.path())?;
+                        Log::update_index_for_on_disk_entry_unchecked(
+                            &log.dir, &mut index, def, &disk_buf, new_len, &log.zstd,
+                        )?;
+                        index.flush()?
+                    };
+                    let _ = utils::fix_perm_file(tmp.as_file(), false);
+                    tmp_indexes.push((def, tmp, index_len));
+                }
+            }
+
+            // Name the files after the next generation, so files used by the
+            // current metadata are not touched. Leftovers of an interrupted
+            // compaction are not referred by metadata and get replaced.
+            let mut meta = log.meta.clone();
+            drop(log);
+            meta.generation += 1;
+            meta.primary_len = new_len;
+            meta.indexes.clear();
+            // Bump epoch since this is a non-append-only change.
+            meta.epoch = meta.epoch.wrapping_add(1);
+
+            let primary_path = dir.join(meta.primary_filename());
+            tmp_primary.persist(&primary_path).map_err(|e| {
+                crate::Error::wrap(Box::new(e), "cannot persist tempfile for compacted log")
+            })?;
+            for (def, tmp, index_len) in tmp_indexes {
+                let path = dir.join(meta.index_filename(def));
+                if self.fsync {
+                    tmp.as_file()
+                        .sync_all()
+                        .context(tmp.path(), "cannot fsync")?;
+                }
+                tmp.persist(&path).map_err(|e| {
+                    crate::Error::wrap(Box::new(e), || {
+                        format!("cannot persist tempfile for rebuilt index {:?}", def.name)
+                    })
+                })?;
+                meta.indexes.insert(def.metaname(), index_len);
+                message += &format!("Rebuilt index {:?}\n", def.name);
+            }
+
+            // Publish the compacted log.
+            let meta_path = dir.join(META_FILE);
+            meta.write_file(&meta_path, self.fsync)
+                .context("while publishing compacted log")?;
+
+            let generation = meta.generation;
+            log::remove_generation_files(dir, |g| g + 1 < generation);
+
+            Ok(message)
+        })();
+
+        result.context(|| format!("in log::OpenOptions::compact({:?})", dir))
+    }
+}
@@ -0,0 +1,125 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use minibytes::Bytes;
+use std::collections::hash_map::{Entry, HashMap};
+use std::io;
+use std::sync::Mutex;
+use zstd::block::{Compressor, Decompressor};
+
+/// zstd state used by a [`Log`] to compress and decompress entries.
+///
+/// The compression contexts are created on demand and reused across
+/// entries, so the dictionary is only copied once per [`Log`].
+///
+/// Entries decompressed to be borrowed from the [`Log`] are kept in a cache,
+/// keyed by entry offset, until the [`Log`] changes its buffers.
+pub(crate) struct Zstd {
+    /// Compression level for new entries. `None` if new entries are not
+    /// compressed.
+    level: Option<i32>,
+
+    /// Dictionary used by all compressed entries in the [`Log`].
+    dictionary: Option<Bytes>,
+
+    compressor: Mutex<Option<Compressor>>,
+    decompressor: Mutex<Option<Decompressor>>,
+
+    // (UNSAFE NOTICE)
+    //
+    // Slices borrowed from the [`Log`] point into the boxes of this map.
+    // This is sound because:
+    // 1. A box is never removed or replaced while the map is shared.
+    //    Only `clear_cache(&mut self)` removes boxes, and the [`Log`] only
+    //    calls it from methods taking `&mut self`, so no borrowed slice is
+    //    alive at that time.
+    // 2. Growing the map moves the boxes, not the heap data they own.
+    decompressed: Mutex<HashMap<u64, Box<[u8]>>>,
+}
+
+impl Zstd {
+    pub(crate) fn new(level: Option<i32>, dictionary: Option<Bytes>) -> Self {
+        Self {
+            level,
+            dictionary,
+            compressor: Mutex::new(None),
+            decompressor: Mutex::new(None),
+            decompressed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Compress an entry.
+    ///
+    /// Return `None` if new entries are not compressed, or if compression
+    /// does not make the entry smaller.
+    pub(crate) fn compress(&self, data: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        let level = match self.level {
+            Some(level) => level,
+            None => return Ok(None),
+        };
+        let mut compressor = self.compressor.lock().unwrap();
+        let compressor = compressor.get_or_insert_with(|| match self.dictionary {
+            Some(ref dict) => Compressor::with_dict(dict.to_vec()),
+            None => Compressor::new(),
+        });
+        let compressed = compressor.compress(data, level)?;
+        if compressed.len() < data.len() {
+            Ok(Some(compressed))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Decompress an entry that is `len` bytes long when uncompressed.
+    pub(crate) fn decompress(&self, data: &[u8], len: usize) -> io::Result<Vec<u8>> {
+        let mut decompressor = self.decompressor.lock().unwrap();
+        let decompressor = decompressor.get_or_insert_with(|| match self.dictionary {
+            Some(ref dict) => Decompressor::with_dict(dict.to_vec()),
+            None => Decompressor::new(),
+        });
+        let decompressed = decompressor.decompress(data, len)?;
+        if decompressed.len() != len {
+            let msg = format!(
+                "decompressed {} bytes, expected {} bytes",
+                decompressed.len(),
+                len
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        }
+        Ok(decompressed)
+    }
+
+    /// Decompress the entry at `offset`, keeping the result so it can be
+    /// borrowed for as long as `self`.
+    pub(crate) fn decompress_cached(
+        &self,
+        offset: u64,
+        data: &[u8],
+        len: usize,
+    ) -> io::Result<&[u8]> {
+        let mut decompressed = self.decompressed.lock().unwrap();
+        let entry: &[u8] = match decompressed.entry(offset) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(self.decompress(data, len)?.into_boxed_slice()),
+        };
+        // See the UNSAFE NOTICE on `decompressed`.
+        Ok(unsafe { &*(entry as *const [u8]) })
+    }
+
+    /// Drop the decompressed entries. Must be called before entry offsets of
+    /// the [`Log`] are reused.
+    pub(crate) fn clear_cache(&mut self) {
+        self.decompressed.get_mut().unwrap().clear();
+    }
+}
+
+impl Clone for Zstd {
+    fn clone(&self) -> Self {
+        // Contexts are not shared. They will be re-created on demand.
+        Self::new(self.level, self.dictionary.clone())
+    }
+}
@@ -27,10 +27,26 @@ pub struct LogMetadata {
 
     /// Once set. Indicate this LogMetadata shouldn't be read.
     pub(crate) poisoned: Option<&'static str>,
+
+    /// Whether entries can be zstd compressed.
+    ///
+    /// Older versions of this library do not know compressed entries. They
+    /// refuse to read the metadata if this is set.
+    pub(crate) zstd: bool,
+
+    /// Generation of the primary log and index files.
+    ///
+    /// Compaction writes the compacted files under a new generation, so
+    /// files referred by the current metadata are never rewritten.
+    pub(crate) generation: u64,
 }
 
 impl LogMetadata {
     const HEADER: &'static [u8] = b"meta\0";
+    // Header of metadata using features unknown to older versions.
+    const HEADER_V2: &'static [u8] = b"met2\0";
+    const FLAG_ZSTD: u64 = 1;
+    const FLAG_GENERATION: u64 = 2;
     const POISONED_HEADER: &'static [u8] = b"pois\0";
 
     /// Read metadata from a reader.
@@ -44,7 +60,8 @@ impl LogMetadata {
             let msg = String::from_utf8_lossy(&message_bytes);
             return Err(io::Error::new(io::ErrorKind::AddrNotAvailable, msg));
         }
-        if header != Self::HEADER {
+        let is_v2 = header == Self::HEADER_V2;
+        if header != Self::HEADER && !is_v2 {
             let msg = "invalid metadata header";
             return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
         }
@@ -80,11 +97,25 @@ impl LogMetadata {
         // format. So not being able to read it (because EOF) is not fatal.
         let epoch = reader.read_vlq().unwrap_or_default();
 
+        let flags: u64 = if is_v2 { reader.read_vlq()? } else { 0 };
+        if flags & !(Self::FLAG_ZSTD | Self::FLAG_GENERATION) != 0 {
+            let msg = format!("unsupported metadata flags: {}", flags);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        }
+        let zstd = flags & Self::FLAG_ZSTD != 0;
+        let generation = if flags & Self::FLAG_GENERATION != 0 {
+            reader.read_vlq()?
+        } else {
+            0
+        };
+
         Ok(Self {
             primary_len,
             indexes,
             epoch,
             poisoned: None,
+            zstd,
+            generation,
         })
     }
 
@@ -107,7 +138,23 @@ impl LogMetadata {
             buf.write_vlq(*len)?;
         }
         buf.write_vlq(self.epoch)?;
-        writer.write_all(Self::HEADER)?;
+        let mut flags = 0;
+        if self.zstd {
+            flags |= Self::FLAG_ZSTD;
+        }
+        if self.generation != 0 {
+            flags |= Self::FLAG_GENERATION;
+        }
+        let header = if flags != 0 {
+            buf.write_vlq(flags)?;
+            if self.generation != 0 {
+                buf.write_vlq(self.generation)?;
+            }
+            Self::HEADER_V2
+        } else {
+            Self::HEADER
+        };
+        writer.write_all(header)?;
         writer.write_vlq(xxhash(&buf))?;
         writer.write_vlq(buf.len())?;
         writer.write_all(&buf)?;
@@ -139,6 +186,8 @@ impl LogMetadata {
             indexes: BTreeMap::new(),
             epoch: utils::epoch(),
             poisoned: None,
+            zstd: false,
+            generation: 0,
         }
     }
 
@@ -149,8 +198,20 @@ impl LogMetadata {
             indexes: BTreeMap::new(),
             epoch: 0,
             poisoned: Some(message),
+            zstd: false,
+            generation: 0,
         }
     }
+
+    /// Name of the primary log file.
+    pub(crate) fn primary_filename(&self) -> String {
+        super::generation_filename(self.generation, super::PRIMARY_FILE)
+    }
+
+    /// Name of the file of the given index.
+    pub(crate) fn index_filename(&self, def: &super::IndexDef) -> String {
+        super::generation_filename(self.generation, &def.filename())
+    }
 }
 
 #[cfg(test)]
@@ -160,9 +221,9 @@ mod tests {
     use tempfile::tempdir;
 
     quickcheck! {
-        fn test_roundtrip_meta(primary_len: u64, indexes: BTreeMap<String, u64>, epoch: u64) -> bool {
+        fn test_roundtrip_meta(primary_len: u64, indexes: BTreeMap<String, u64>, epoch: u64, zstd: bool, generation: u64) -> bool {
             let mut buf = Vec::new();
-            let meta = LogMetadata { primary_len, indexes, epoch, poisoned: None };
+            let meta = LogMetadata { primary_len, indexes, epoch, poisoned: None, zstd, generation };
             meta.write(&mut buf).expect("write");
             let mut cur = Cursor::new(buf);
             let meta_read = LogMetadata::read(&mut cur).expect("read");
@@ -171,7 +232,7 @@ mod tests {
 
         fn test_roundtrip_meta_file(primary_len: u64, indexes: BTreeMap<String, u64>, epoch: u64) -> bool {
             let dir = tempdir().unwrap();
-            let meta = LogMetadata { primary_len, indexes, epoch, poisoned: None };
+            let meta = LogMetadata { primary_len, indexes, epoch, poisoned: None, zstd: false, generation: 0 };
             let path = dir.path().join("meta");
             meta.write_file(&path, false).expect("write_file");
             let meta_read = LogMetadata::read_file(&path).expect("read_file");
@@ -179,4 +240,27 @@ mod tests {
         }
 
     }
+
+    #[test]
+    fn test_zstd_meta_header() {
+        let mut meta = LogMetadata::new_with_primary_len(12);
+        let mut buf = Vec::new();
+        meta.write(&mut buf).unwrap();
+        assert!(buf.starts_with(LogMetadata::HEADER));
+
+        // Older versions only accept HEADER.
+        meta.zstd = true;
+        let mut buf = Vec::new();
+        meta.write(&mut buf).unwrap();
+        assert!(buf.starts_with(LogMetadata::HEADER_V2));
+        assert_eq!(LogMetadata::read(&buf[..]).unwrap(), meta);
+
+        // Compacted logs use new file names older versions do not know.
+        meta.zstd = false;
+        meta.generation = 3;
+        let mut buf = Vec::new();
+        meta.write(&mut buf).unwrap();
+        assert!(buf.starts_with(LogMetadata::HEADER_V2));
+        assert_eq!(LogMetadata::read(&buf[..]).unwrap(), meta);
+    }
 }
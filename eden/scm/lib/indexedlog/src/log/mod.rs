@@ -15,17 +15,22 @@
 //
 // Primary log:
 //   LOG := HEADER + ENTRY_LIST
-//   HEADER := 'log\0'
+//   HEADER := 'indexedlog0\0' | 'indexedlog1\0' (if entries can be compressed)
 //   ENTRY_LIST := '' | ENTRY_LIST + ENTRY
 //   ENTRY := ENTRY_FLAGS + LEN(CONTENT) + CHECKSUM + CONTENT
 //   CHECKSUM := '' | XXHASH64(CONTENT) | XXHASH32(CONTENT)
+//   CONTENT := DATA | LEN(DATA) + ZSTD(DATA) (if ENTRY_FLAGS has zstd)
 //
 // Metadata:
 //   META := HEADER + XXHASH64(DATA) + LEN(DATA) + DATA
-//   HEADER := 'meta\0'
-//   DATA := LEN(LOG) + LEN(INDEXES) + INDEXES
+//   HEADER := 'meta\0' | 'met2\0' (if FLAGS is not 0)
+//   DATA := LEN(LOG) + LEN(INDEXES) + INDEXES + EPOCH + FLAGS (if 'met2')
 //   INDEXES := '' | INDEXES + INDEX
 //   INDEX := LEN(NAME) + NAME + INDEX_LOGIC_LEN
+//   FLAGS := 1 (if entries can be zstd compressed)
+//
+// Zstd dictionary (optional, written once when the log is created):
+//   ZSTD_DICTIONARY := DICT
 //
 // Indexes:
 //   See `index.rs`.
@@ -51,6 +56,8 @@ use tracing::debug_span;
 use tracing::trace;
 use vlqencoding::{VLQDecodeAt, VLQEncode};
 
+mod compact;
+mod compression;
 mod meta;
 mod open_options;
 mod path;
@@ -58,21 +65,29 @@ mod repair;
 #[cfg(test)]
 mod tests;
 
+use self::compression::Zstd;
 pub use self::meta::LogMetadata;
 pub use open_options::{
-    ChecksumType, FlushFilterContext, FlushFilterFunc, FlushFilterOutput, IndexDef, IndexOutput,
-    OpenOptions,
+    ChecksumType, Compression, FlushFilterContext, FlushFilterFunc, FlushFilterOutput, IndexDef,
+    IndexOutput, OpenOptions,
 };
 pub use path::GenericPath;
 
 // Constants about file names
 pub(crate) const PRIMARY_FILE: &str = "log";
 const PRIMARY_HEADER: &[u8] = b"indexedlog0\0";
+// Header of logs created with compression. Only these logs get compressed
+// entries, so logs created without compression stay readable by older
+// versions.
+const PRIMARY_HEADER_V1: &[u8] = b"indexedlog1\0";
 const PRIMARY_START_OFFSET: u64 = 12; // PRIMARY_HEADER.len() as u64;
 pub(crate) const META_FILE: &str = "meta";
+const ZSTD_DICTIONARY_FILE: &str = "zstd-dictionary";
+const GENERATION_PREFIX: &str = "gen";
 
 const ENTRY_FLAG_HAS_XXHASH64: u32 = 1;
 const ENTRY_FLAG_HAS_XXHASH32: u32 = 2;
+const ENTRY_FLAG_ZSTD: u32 = 4;
 
 // 1MB index checksum. This makes checksum file within one block (4KB) for 512MB index.
 const INDEX_CHECKSUM_CHUNK_SIZE_LOGARITHM: u32 = 20;
@@ -106,6 +121,7 @@ pub struct Log {
     // probably fine considering index corruptions are rare.
     index_corrupted: bool,
     open_options: OpenOptions,
+    zstd: Zstd,
 }
 
 /// Iterator over all entries in a [`Log`].
@@ -240,13 +256,10 @@ impl Log {
 
             let offset = self.meta.primary_len + self.mem_buf.len() as u64;
 
-            // Design note: Currently checksum_type is the only thing that decides
-            // entry_flags.  Entry flags is not designed to just cover different
-            // checksum types.  For example, if we'd like to introduce transparent
-            // compression (maybe not a good idea since it can be more cleanly built
-            // at an upper layer), or some other ways to store data (ex. reference
-            // to other data, or fixed length data), they can probably be done by
-            // extending the entry type.
+            // Design note: Entry flags is not designed to just cover different
+            // checksum types. Other ways to store data (ex. reference to other
+            // data, or fixed length data) can probably be done by extending the
+            // entry type, like compression.
             let mut entry_flags = 0;
             entry_flags |= match checksum_type {
                 ChecksumType::Xxhash64 => ENTRY_FLAG_HAS_XXHASH64,
@@ -254,25 +267,49 @@ impl Log {
                 ChecksumType::Auto => unreachable!(),
             };
 
+            let compressed = self
+                .zstd
+                .compress(data)
+                .map_err(|e| crate::Error::wrap(Box::new(e), "cannot compress entry"))?
+                .map(|compressed| {
+                    // Put the length in the content so it is covered by the checksum.
+                    let mut content = Vec::with_capacity(compressed.len() + 10);
+                    content.write_vlq(data.len()).unwrap();
+                    content.extend_from_slice(&compressed);
+                    content
+                });
+            let content = match compressed {
+                Some(ref compressed) => {
+                    entry_flags |= ENTRY_FLAG_ZSTD;
+                    &compressed[..]
+                }
+                None => data,
+            };
+
             self.mem_buf.write_vlq(entry_flags).infallible()?;
-            self.mem_buf.write_vlq(data.len()).infallible()?;
+            self.mem_buf.write_vlq(content.len()).infallible()?;
 
             match checksum_type {
                 ChecksumType::Xxhash64 => {
                     self.mem_buf
-                        .write_u64::<LittleEndian>(xxhash(data))
+                        .write_u64::<LittleEndian>(xxhash(content))
                         .infallible()?;
                 }
                 ChecksumType::Xxhash32 => {
                     self.mem_buf
-                        .write_u32::<LittleEndian>(xxhash32(data))
+                        .write_u32::<LittleEndian>(xxhash32(content))
                         .infallible()?;
                 }
                 ChecksumType::Auto => unreachable!(),
             };
-            let data_offset = self.meta.primary_len + self.mem_buf.len() as u64;
+            let data_offset = if compressed.is_some() {
+                // Index keys cannot refer to compressed content.
+                None
+            } else {
+                Some(self.meta.primary_len + self.mem_buf.len() as u64)
+            };
 
-            self.mem_buf.write_all(data).infallible()?;
+            self.mem_buf.write_all(content).infallible()?;
             self.update_indexes_for_in_memory_entry(data, offset, data_offset)?;
 
             if let Some(threshold) = self.open_options.auto_sync_threshold {
@@ -306,6 +343,7 @@ impl Log {
                 index.clear_dirty();
             }
             self.mem_buf.clear();
+            self.zstd.clear_cache();
             self.update_indexes_for_on_disk_entries()?;
             Ok(())
         })();
@@ -369,6 +407,7 @@ impl Log {
             indexes,
             index_corrupted: false,
             open_options: self.open_options.clone(),
+            zstd: self.zstd.clone(),
         };
 
         if !copy_dirty {
@@ -422,7 +461,7 @@ impl Log {
 
             // Read-only fast path - no need to take directory lock.
             if self.mem_buf.is_empty() {
-                if let Ok(meta) = Self::load_or_create_meta(&self.dir, false) {
+                if let Ok(meta) = Self::load_or_create_meta(&self.dir, None) {
                     let changed = self.meta != meta;
                     let truncated = self.meta.epoch != meta.epoch;
                     if !truncated {
@@ -458,7 +497,7 @@ impl Log {
             let lock = ScopedDirLock::new(&dir)?;
 
             // Step 1: Reload metadata to get the latest view of the files.
-            let mut meta = Self::load_or_create_meta(&self.dir, false)?;
+            let mut meta = Self::load_or_create_meta(&self.dir, None)?;
             let changed = self.meta != meta;
             let truncated = self.meta.epoch != meta.epoch;
             if !truncated {
@@ -514,7 +553,11 @@ impl Log {
             }
 
             // Step 2: Append to the primary log.
-            let primary_path = self.dir.as_opt_path().unwrap().join(PRIMARY_FILE);
+            let primary_path = self
+                .dir
+                .as_opt_path()
+                .unwrap()
+                .join(meta.primary_filename());
             let mut primary_file = fs::OpenOptions::new()
                 .read(true)
                 .write(true)
@@ -597,6 +640,7 @@ impl Log {
             self.disk_buf = disk_buf;
             self.indexes = indexes;
             self.meta = meta;
+            self.zstd.clear_cache();
 
             // Step 4: Update the indexes. Optionally flush them.
             self.update_indexes_for_on_disk_entries()?;
@@ -703,7 +747,7 @@ impl Log {
 
                 let _lock = ScopedDirLock::new(&dir)?;
 
-                let meta = Self::load_or_create_meta(&self.dir, false)?;
+                let meta = Self::load_or_create_meta(&self.dir, None)?;
                 if self.meta != meta {
                     return Err(crate::Error::programming(
                         "race detected, callsite responsible for preventing races",
@@ -818,6 +862,7 @@ impl Log {
                             def,
                             &self.disk_buf,
                             self.meta.primary_len,
+                            &self.zstd,
                         )?;
                         index.flush()?
                     };
@@ -832,7 +877,7 @@ impl Log {
 
                     let _ = utils::fix_perm_file(tmp.as_file(), false);
 
-                    let path = dir.join(def.filename());
+                    let path = dir.join(self.meta.index_filename(def));
                     tmp.persist(&path).map_err(|e| {
                         crate::Error::wrap(Box::new(e), || {
                             format!("cannot persist tempfile to replace index {:?}", name)
@@ -971,6 +1016,18 @@ impl Log {
             .context(|| format!("  Log.dir = {:?}", self.dir))
     }
 
+    /// Test if the given index has entries for `key`, without reading them.
+    pub(crate) fn contains_key(&self, index_id: usize, key: &[u8]) -> crate::Result<bool> {
+        self.maybe_return_index_error()?;
+        let index = self.get_index(index_id)?;
+        let mut values = index.get(&key)?.values(index);
+        match values.next() {
+            None => Ok(false),
+            Some(Err(err)) => Err(err),
+            Some(Ok(_)) => Ok(true),
+        }
+    }
+
     /// Return an iterator for all entries.
     pub fn iter(&self) -> LogIter {
         LogIter {
@@ -1014,12 +1071,13 @@ impl Log {
     ///
     /// `offset` is the logical start offset of the entry.
     /// `data_offset` is the logical start offset of the real data (skips
-    /// length, and checksum header in the entry).
+    /// length, and checksum header in the entry), or `None` if the entry is
+    /// compressed.
     fn update_indexes_for_in_memory_entry(
         &mut self,
         data: &[u8],
         offset: u64,
-        data_offset: u64,
+        data_offset: Option<u64>,
     ) -> crate::Result<()> {
         let result = self.update_indexes_for_in_memory_entry_unchecked(data, offset, data_offset);
         self.maybe_set_index_error(result)
@@ -1029,16 +1087,24 @@ impl Log {
         &mut self,
         data: &[u8],
         offset: u64,
-        data_offset: u64,
+        data_offset: Option<u64>,
     ) -> crate::Result<()> {
         for (index, def) in self.indexes.iter_mut().zip(&self.open_options.index_defs) {
             for index_output in (def.func)(data) {
                 match index_output {
                     IndexOutput::Reference(range) => {
                         assert!(range.start <= range.end && range.end <= data.len() as u64);
-                        let start = range.start + data_offset;
-                        let end = range.end + data_offset;
-                        let key = InsertKey::Reference((start, end - start));
+                        let key = match data_offset {
+                            Some(data_offset) => {
+                                let start = range.start + data_offset;
+                                let end = range.end + data_offset;
+                                InsertKey::Reference((start, end - start))
+                            }
+                            // The key cannot refer to compressed content.
+                            None => {
+                                InsertKey::Embed(&data[range.start as usize..range.end as usize])
+                            }
+                        };
                         index.insert_advanced(key, InsertValue::Prepend(offset))?;
                     }
                     IndexOutput::Owned(key) => {
@@ -1074,6 +1140,7 @@ impl Log {
                 def,
                 &self.disk_buf,
                 self.meta.primary_len,
+                &self.zstd,
             )?;
         }
         Ok(())
@@ -1085,6 +1152,7 @@ impl Log {
         def: &IndexDef,
         disk_buf: &Bytes,
         primary_len: u64,
+        zstd: &Zstd,
     ) -> crate::Result<usize> {
         // The index meta is used to store the next offset the index should be built.
         let mut offset = Self::get_index_log_len(index, true)?;
@@ -1100,14 +1168,25 @@ impl Log {
             })?
         {
             count += 1;
-            let data = entry_result.data;
+            let decompressed;
+            let data = if entry_result.uncompressed_len.is_some() {
+                decompressed = Self::decompress_entry(path, zstd, offset, &entry_result)?;
+                &decompressed[..]
+            } else {
+                entry_result.data
+            };
             for index_output in (def.func)(data) {
                 match index_output {
                     IndexOutput::Reference(range) => {
                         assert!(range.start <= range.end && range.end <= data.len() as u64);
-                        let start = range.start + entry_result.data_offset;
-                        let end = range.end + entry_result.data_offset;
-                        let key = InsertKey::Reference((start, end - start));
+                        let key = if entry_result.uncompressed_len.is_some() {
+                            // The key cannot refer to compressed content.
+                            InsertKey::Embed(&data[range.start as usize..range.end as usize])
+                        } else {
+                            let start = range.start + entry_result.data_offset;
+                            let end = range.end + entry_result.data_offset;
+                            InsertKey::Reference((start, end - start))
+                        };
 
                         index.insert_advanced(key, InsertValue::Prepend(offset))?;
                     }
@@ -1131,42 +1210,47 @@ impl Log {
         Ok(count)
     }
 
-    /// Read [`LogMetadata`] from the given directory. If `create` is `true`,
-    /// create an empty one on demand.
+    /// Read [`LogMetadata`] from the given directory. If `create` is set,
+    /// create an empty one on demand, for a log using the given compression.
     ///
     /// The caller should ensure the directory exists and take a lock on it to
     /// avoid filesystem races.
     pub(crate) fn load_or_create_meta(
         path: &GenericPath,
-        create: bool,
+        create: Option<&Compression>,
     ) -> crate::Result<LogMetadata> {
         Self::load_or_create_meta_internal(path, create, false)
     }
 
     /// Used by MultiLog. Write a dummy "meta" file that prevents accidental reading.
-    pub(crate) fn load_or_create_shared_meta(path: &GenericPath) -> crate::Result<LogMetadata> {
-        Self::load_or_create_meta_internal(path, true, true)
+    pub(crate) fn load_or_create_shared_meta(
+        path: &GenericPath,
+        compression: &Compression,
+    ) -> crate::Result<LogMetadata> {
+        Self::load_or_create_meta_internal(path, Some(compression), true)
     }
 
     pub(crate) fn load_or_create_meta_internal(
         path: &GenericPath,
-        create: bool,
+        create: Option<&Compression>,
         is_shared: bool,
     ) -> crate::Result<LogMetadata> {
         match path.read_meta() {
             Err(err) => {
-                if err.io_error_kind() == io::ErrorKind::NotFound && create {
+                if let (io::ErrorKind::NotFound, Some(compression)) = (err.io_error_kind(), create)
+                {
                     let dir = path.as_opt_path().unwrap();
                     // Create (and truncate) the primary log and indexes.
                     let primary_path = dir.join(PRIMARY_FILE);
                     let mut primary_file =
                         File::create(&primary_path).context(&primary_path, "cannot create")?;
                     primary_file
-                        .write_all(PRIMARY_HEADER)
+                        .write_all(primary_header(compression))
                         .context(&primary_path, "cannot write")?;
                     let _ = utils::fix_perm_file(&primary_file, false);
+                    write_zstd_dictionary(dir, compression)?;
                     // Start from empty file and indexes.
-                    let meta = LogMetadata::new_with_primary_len(PRIMARY_START_OFFSET);
+                    let meta = new_meta(PRIMARY_START_OFFSET, compression);
                     // An empty meta file is easy to recreate. No need to use fsync.
                     path.write_meta(&meta, false)?;
                     if is_shared {
@@ -1214,7 +1298,7 @@ impl Log {
         fsync: bool,
     ) -> crate::Result<(Bytes, Vec<Index>)> {
         let primary_buf = match dir.as_opt_path() {
-            Some(dir) => mmap_path(&dir.join(PRIMARY_FILE), meta.primary_len)?,
+            Some(dir) => mmap_path(&dir.join(meta.primary_filename()), meta.primary_len)?,
             None => Bytes::new(),
         };
 
@@ -1234,6 +1318,7 @@ impl Log {
                     let index_len = meta.indexes.get(&def.metaname()).cloned().unwrap_or(0);
                     indexes.push(Self::load_index(
                         dir,
+                        meta,
                         &def,
                         index_len,
                         key_buf.clone(),
@@ -1250,7 +1335,7 @@ impl Log {
                 for (index, def) in indexes.iter().zip(index_defs) {
                     let index_len = meta.indexes.get(&def.metaname()).cloned().unwrap_or(0);
                     let index = if index_len > Self::get_index_log_len(index, true).unwrap_or(0) {
-                        Self::load_index(dir, &def, index_len, key_buf.clone(), fsync)?
+                        Self::load_index(dir, meta, &def, index_len, key_buf.clone(), fsync)?
                     } else {
                         let mut index = index.try_clone()?;
                         index.key_buf = key_buf.clone();
@@ -1264,6 +1349,36 @@ impl Log {
         Ok((primary_buf, indexes))
     }
 
+    /// Prepare zstd state for entries of a log loaded with the given metadata.
+    ///
+    /// New entries are only compressed if the log was created with
+    /// compression. See [`OpenOptions::compression`].
+    fn load_zstd(
+        dir: &GenericPath,
+        meta: &LogMetadata,
+        compression: &Compression,
+    ) -> crate::Result<Zstd> {
+        if !meta.zstd {
+            return Ok(Zstd::new(None, None));
+        }
+        let level = compression.zstd().map(|(level, _dictionary)| level);
+        let dictionary = match dir.as_opt_path() {
+            Some(dir) => {
+                let path = dir.join(ZSTD_DICTIONARY_FILE);
+                match fs::read(&path) {
+                    Ok(dictionary) => Some(Bytes::from(dictionary)),
+                    // The log was created without a dictionary.
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+                    Err(err) => return Err(err).context(&path, "cannot read zstd dictionary"),
+                }
+            }
+            None => compression
+                .zstd()
+                .and_then(|(_level, dictionary)| dictionary),
+        };
+        Ok(Zstd::new(level, dictionary))
+    }
+
     /// Return the reference to the [`GenericPath`] used to crate the [`Log`].
     pub fn path(&self) -> &GenericPath {
         &self.dir
@@ -1272,6 +1387,7 @@ impl Log {
     /// Load a single index.
     fn load_index(
         dir: &GenericPath,
+        meta: &LogMetadata,
         def: &IndexDef,
         len: u64,
         buf: Arc<dyn ReadonlyBuffer + Send + Sync>,
@@ -1279,7 +1395,7 @@ impl Log {
     ) -> crate::Result<Index> {
         match dir.as_opt_path() {
             Some(dir) => {
-                let path = dir.join(def.filename());
+                let path = dir.join(meta.index_filename(def));
                 index::OpenOptions::new()
                     .checksum_chunk_size_logarithm(INDEX_CHECKSUM_CHUNK_SIZE_LOGARITHM)
                     .logical_len(Some(len))
@@ -1298,6 +1414,9 @@ impl Log {
     /// Read the entry at the given offset. Return `None` if offset is out of bound, or the content
     /// of the data, the real offset of the data, and the next offset. Raise errors if
     /// integrity-check failed.
+    ///
+    /// Compressed entries are not decompressed. Use [`Log::entry_slice`] or
+    /// [`Log::entry_bytes`] to read them.
     fn read_entry(&self, offset: u64) -> crate::Result<Option<EntryResult>> {
         let result = if offset < self.meta.primary_len {
             Self::read_entry_from_buf(&self.dir, &self.disk_buf, offset)?
@@ -1312,6 +1431,57 @@ impl Log {
         Ok(result)
     }
 
+    /// Get the content of an entry at `offset` read by [`Log::read_entry`].
+    ///
+    /// Compressed entries are decompressed and kept in memory until the
+    /// [`Log`] is changed by [`Log::sync`] or [`Log::clear_dirty`].
+    fn entry_slice<'a>(
+        &'a self,
+        offset: u64,
+        entry_result: EntryResult<'a>,
+    ) -> crate::Result<&'a [u8]> {
+        match entry_result.uncompressed_len {
+            Some(len) => self
+                .zstd
+                .decompress_cached(offset, entry_result.data, len as usize)
+                .map_err(|e| Self::decompress_error(&self.dir, offset, e)),
+            None => Ok(entry_result.data),
+        }
+    }
+
+    /// Get the content of an entry at `offset` read by [`Log::read_entry`],
+    /// as [`Bytes`]. Compressed entries are decompressed without being kept
+    /// in memory by the [`Log`].
+    fn entry_bytes(&self, offset: u64, entry_result: EntryResult) -> crate::Result<Bytes> {
+        if entry_result.uncompressed_len.is_some() {
+            let data = Self::decompress_entry(&self.dir, &self.zstd, offset, &entry_result)?;
+            Ok(Bytes::from(data))
+        } else {
+            Ok(self.slice_to_bytes(entry_result.data))
+        }
+    }
+
+    /// Decompress the content of a compressed entry at `offset`.
+    fn decompress_entry(
+        path: &GenericPath,
+        zstd: &Zstd,
+        offset: u64,
+        entry_result: &EntryResult,
+    ) -> crate::Result<Vec<u8>> {
+        let len = entry_result.uncompressed_len.unwrap_or_default() as usize;
+        zstd.decompress(entry_result.data, len)
+            .map_err(|e| Self::decompress_error(path, offset, e))
+    }
+
+    fn decompress_error(path: &GenericPath, offset: u64, err: io::Error) -> crate::Error {
+        let msg = format!("cannot decompress entry at {}", offset);
+        match path.as_opt_path() {
+            Some(path) => crate::Error::corruption(path, msg),
+            None => crate::Error::path(Path::new("<memory>"), msg),
+        }
+        .source(err)
+    }
+
     /// Read an entry at the given offset of the given buffer. Verify its integrity. Return the
     /// data, the real data offset, and the next entry offset. Return None if the offset is at
     /// the end of the buffer.  Raise errors if there are integrity check issues.
@@ -1397,15 +1567,29 @@ impl Log {
             // Tested above. Therefore unreachable.
             _ => unreachable!(),
         };
-        if verified {
-            Ok(Some(EntryResult {
-                data,
-                data_offset: offset,
-                next_offset: end,
-            }))
-        } else {
-            Err(data_error(format!("integrity check failed at {}", offset)))
+        if !verified {
+            return Err(data_error(format!("integrity check failed at {}", offset)));
         }
+
+        // Compressed content starts with the uncompressed length.
+        let (data, data_offset, uncompressed_len) = if entry_flags & ENTRY_FLAG_ZSTD != 0 {
+            let (len, vlq_len): (u64, _) = data.read_vlq_at(0).map_err(|e| {
+                crate::Error::wrap(Box::new(e), || {
+                    format!("cannot read uncompressed length at {}", offset)
+                })
+                .mark_corruption()
+            })?;
+            (&data[vlq_len..], offset + vlq_len as u64, Some(len))
+        } else {
+            (data, offset, None)
+        };
+
+        Ok(Some(EntryResult {
+            data,
+            data_offset,
+            next_offset: end,
+            uncompressed_len,
+        }))
     }
 
     /// Wrapper around a `Result` returned by an index write operation.
@@ -1473,6 +1657,89 @@ impl Log {
     }
 }
 
+/// Header of the primary log for a new log using the given compression.
+pub(crate) fn primary_header(compression: &Compression) -> &'static [u8] {
+    match compression {
+        Compression::None => PRIMARY_HEADER,
+        Compression::Zstd { .. } => PRIMARY_HEADER_V1,
+    }
+}
+
+/// Name of a primary log or index file of the given generation. See
+/// [`LogMetadata::generation`].
+pub(crate) fn generation_filename(generation: u64, name: &str) -> String {
+    if generation == 0 {
+        name.to_string()
+    } else {
+        format!("{}{}-{}", GENERATION_PREFIX, generation, name)
+    }
+}
+
+/// Parse the generation of a primary log or index file name. Return `None`
+/// for other files.
+pub(crate) fn parse_generation_filename(name: &str) -> Option<(u64, &str)> {
+    match name.strip_prefix(GENERATION_PREFIX) {
+        Some(rest) => {
+            let (generation, name) = rest.split_at(rest.find('-')?);
+            Some((generation.parse().ok()?, &name[1..]))
+        }
+        None if name == PRIMARY_FILE || name.starts_with(open_options::INDEX_FILE_PREFIX) => {
+            Some((0, name))
+        }
+        None => None,
+    }
+}
+
+/// Remove primary log and index files in `dir` of generations selected by
+/// `should_remove`. Errors are ignored, since the files might still be
+/// mapped by other processes on Windows.
+pub(crate) fn remove_generation_files(dir: &Path, should_remove: impl Fn(u64) -> bool) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let name = entry.file_name();
+        let generation = match name.to_str().and_then(parse_generation_filename) {
+            Some((generation, _name)) => generation,
+            None => continue,
+        };
+        if should_remove(generation) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// [`LogMetadata`] for a new log using the given compression.
+pub(crate) fn new_meta(primary_len: u64, compression: &Compression) -> LogMetadata {
+    let mut meta = LogMetadata::new_with_primary_len(primary_len);
+    meta.zstd = compression.zstd().is_some();
+    meta
+}
+
+/// Write the zstd dictionary for a new log at `dir` using the given
+/// compression. Remove the dictionary of a previous log at `dir`, if the
+/// compression does not use a dictionary.
+///
+/// The dictionary never changes after that, so it is stored in its own file
+/// instead of [`LogMetadata`], which is rewritten by every `sync`.
+pub(crate) fn write_zstd_dictionary(dir: &Path, compression: &Compression) -> crate::Result<()> {
+    let path = dir.join(ZSTD_DICTIONARY_FILE);
+    match compression.zstd() {
+        Some((_level, Some(dictionary))) => {
+            // Entries cannot be read without the dictionary. Always fsync.
+            utils::atomic_write_plain(&path, &dictionary, true)?;
+        }
+        _ => match fs::remove_file(&path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                return Err(err).context(&path, "cannot remove stale zstd dictionary");
+            }
+            _ => {}
+        },
+    }
+    Ok(())
+}
+
 // Error-related utilities
 
 impl Log {
@@ -1516,6 +1783,8 @@ struct EntryResult<'a> {
     data: &'a [u8],
     data_offset: u64,
     next_offset: u64,
+    /// Set if `data` is compressed.
+    uncompressed_len: Option<u64>,
 }
 
 impl<'a> EntryResult<'a> {
@@ -1527,6 +1796,7 @@ impl<'a> EntryResult<'a> {
             // So it does not need to be changed.
             data_offset: self.data_offset,
             next_offset: self.next_offset + offset,
+            uncompressed_len: self.uncompressed_len,
         }
     }
 }
@@ -1535,6 +1805,31 @@ impl<'a> Iterator for LogLookupIter<'a> {
     type Item = crate::Result<&'a [u8]>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        self.next_with(Log::entry_slice)
+    }
+}
+
+impl<'a> LogLookupIter<'a> {
+    /// A convenient way to get data.
+    pub fn into_vec(self) -> crate::Result<Vec<&'a [u8]>> {
+        self.collect()
+    }
+
+    /// Convert to an iterator of [`Bytes`]. Unlike this iterator, the
+    /// returned iterator does not keep decompressed entries in memory.
+    pub fn into_bytes(mut self) -> impl Iterator<Item = crate::Result<Bytes>> + 'a {
+        std::iter::from_fn(move || self.next_bytes())
+    }
+
+    /// Read the next entry as [`Bytes`].
+    pub(crate) fn next_bytes(&mut self) -> Option<crate::Result<Bytes>> {
+        self.next_with(Log::entry_bytes)
+    }
+
+    fn next_with<T>(
+        &mut self,
+        read: impl FnOnce(&'a Log, u64, EntryResult<'a>) -> crate::Result<T>,
+    ) -> Option<crate::Result<T>> {
         if self.errored {
             return None;
         }
@@ -1544,55 +1839,79 @@ impl<'a> Iterator for LogLookupIter<'a> {
                 self.errored = true;
                 Some(Err(err))
             }
-            Some(Ok(offset)) => match self
-                .log
-                .read_entry(offset)
-                .context("in LogLookupIter::next")
-            {
-                Ok(Some(entry)) => Some(Ok(entry.data)),
-                Ok(None) => None,
-                Err(err) => {
-                    // Do not set this iterator to an error state. It's possible
-                    // that the index iterator still provides valid data, and
-                    // only the "log" portion is corrupted.
-                    //
-                    // The index iterator is finite if integrity check is turned
-                    // on. So trust it and don't worry about infinite iteration
-                    // here.
-                    Some(Err(err))
+            Some(Ok(offset)) => {
+                let log = self.log;
+                match log
+                    .read_entry(offset)
+                    .and_then(|entry| entry.map(|entry| read(log, offset, entry)).transpose())
+                    .context("in LogLookupIter::next")
+                {
+                    Ok(Some(data)) => Some(Ok(data)),
+                    Ok(None) => None,
+                    Err(err) => {
+                        // Do not set this iterator to an error state. It's possible
+                        // that the index iterator still provides valid data, and
+                        // only the "log" portion is corrupted.
+                        //
+                        // The index iterator is finite if integrity check is turned
+                        // on. So trust it and don't worry about infinite iteration
+                        // here.
+                        Some(Err(err))
+                    }
                 }
-            },
+            }
         }
     }
 }
 
-impl<'a> LogLookupIter<'a> {
-    /// A convenient way to get data.
-    pub fn into_vec(self) -> crate::Result<Vec<&'a [u8]>> {
-        self.collect()
-    }
-}
-
 impl<'a> Iterator for LogIter<'a> {
     type Item = crate::Result<&'a [u8]>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        self.next_with(Log::entry_slice)
+    }
+}
+
+impl<'a> LogIter<'a> {
+    /// Convert to an iterator of [`Bytes`]. Unlike this iterator, the
+    /// returned iterator does not keep decompressed entries in memory.
+    pub fn into_bytes(mut self) -> impl Iterator<Item = crate::Result<Bytes>> + 'a {
+        std::iter::from_fn(move || self.next_bytes())
+    }
+
+    /// Read the next entry as [`Bytes`].
+    pub(crate) fn next_bytes(&mut self) -> Option<crate::Result<Bytes>> {
+        self.next_with(Log::entry_bytes)
+    }
+
+    fn next_with<T>(
+        &mut self,
+        read: impl FnOnce(&'a Log, u64, EntryResult<'a>) -> crate::Result<T>,
+    ) -> Option<crate::Result<T>> {
         if self.errored {
             return None;
         }
+        let offset = self.next_offset;
         match self
             .log
-            .read_entry(self.next_offset)
+            .read_entry(offset)
+            .and_then(|entry| match entry {
+                Some(entry_result) => {
+                    assert!(entry_result.next_offset > offset);
+                    let next_offset = entry_result.next_offset;
+                    Ok(Some((read(self.log, offset, entry_result)?, next_offset)))
+                }
+                None => Ok(None),
+            })
             .context("in LogIter::next")
         {
             Err(e) => {
                 self.errored = true;
                 Some(Err(e))
             }
-            Ok(Some(entry_result)) => {
-                assert!(entry_result.next_offset > self.next_offset);
-                self.next_offset = entry_result.next_offset;
-                Some(Ok(entry_result.data))
+            Ok(Some((data, next_offset))) => {
+                self.next_offset = next_offset;
+                Some(Ok(data))
             }
             Ok(None) => None,
         }
@@ -1653,7 +1972,7 @@ impl Debug for Log {
         loop {
             let offset = iter.next_offset;
             count += 1;
-            match iter.next() {
+            match iter.next_bytes() {
                 None => break,
                 Some(Ok(bytes)) => {
                     if count > 1 {
@@ -8,14 +8,15 @@
 use crate::errors::ResultExt;
 use crate::index::Index;
 use crate::lock::ScopedDirLock;
-use crate::log::{GenericPath, Log, LogMetadata, PRIMARY_START_OFFSET};
+use crate::log::{self, GenericPath, Log, PRIMARY_START_OFFSET};
+use minibytes::Bytes;
 use std::borrow::Cow;
 use std::fmt::{self, Debug};
 use std::ops::Range;
 
 use tracing::debug_span;
 
-const INDEX_FILE_PREFIX: &str = "index2-";
+pub(crate) const INDEX_FILE_PREFIX: &str = "index2-";
 const META_PREFIX: &str = "2-";
 
 /// Definition of an index. It includes: name, function to extract index keys,
@@ -106,12 +107,32 @@ pub enum ChecksumType {
     Xxhash32,
 }
 
+/// How entries are compressed.
+#[derive(Clone, PartialEq)]
+pub enum Compression {
+    /// Store entries as-is.
+    None,
+
+    /// Compress entries using zstd at the given level.
+    ///
+    /// If `dictionary` is set, it is stored in a file next to the [`Log`]
+    /// when the [`Log`] is created, and is used for all entries of that
+    /// [`Log`]. Use
+    /// [`Compression::train_zstd_dictionary`] to train one from typical
+    /// entries. A dictionary helps a lot if entries are small and similar.
+    Zstd {
+        level: i32,
+        dictionary: Option<Bytes>,
+    },
+}
+
 /// Options used to configured how an [`Log`] is opened.
 #[derive(Clone)]
 pub struct OpenOptions {
     pub(crate) index_defs: Vec<IndexDef>,
     pub(crate) create: bool,
     pub(crate) checksum_type: ChecksumType,
+    pub(crate) compression: Compression,
     pub(crate) flush_filter: Option<FlushFilterFunc>,
     pub(crate) fsync: bool,
     pub(crate) auto_sync_threshold: Option<u64>,
@@ -222,11 +243,13 @@ impl OpenOptions {
     /// `fsync` is initially `false`.
     /// `index_defs` is initially empty.
     /// `auto_sync_threshold` is initially `None`.
+    /// `compression` is initially [`Compression::None`].
     pub fn new() -> Self {
         Self {
             create: false,
             index_defs: Vec::new(),
             checksum_type: ChecksumType::Auto,
+            compression: Compression::None,
             flush_filter: None,
             fsync: false,
             auto_sync_threshold: None,
@@ -287,6 +310,26 @@ impl OpenOptions {
         self
     }
 
+    /// Sets how entries are compressed.
+    ///
+    /// Compression only applies to a [`Log`] created with it. An existing
+    /// [`Log`] created without compression keeps storing entries as-is, so
+    /// it stays readable by older versions of this library. Older versions
+    /// refuse to read a [`Log`] created with compression.
+    ///
+    /// Index functions and iterators always see uncompressed entries.
+    /// Entries decompressed by [`Log::iter`] or [`Log::lookup`] are kept in
+    /// memory until the next [`Log::sync`]. Use `into_bytes` on the
+    /// iterators, for example,
+    /// [`LogIter::into_bytes`](crate::log::LogIter::into_bytes), to read many
+    /// entries without keeping them.
+    ///
+    /// See [`Compression`] for details.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
     /// Sets the flush filter function.
     ///
     /// The function will be called at [`Log::sync`] time, if there are
@@ -349,7 +392,7 @@ impl OpenOptions {
     pub(crate) fn create_in_memory(&self, dir: GenericPath) -> crate::Result<Log> {
         assert!(dir.as_opt_path().is_none());
         let result: crate::Result<_> = (|| {
-            let meta = LogMetadata::new_with_primary_len(PRIMARY_START_OFFSET);
+            let meta = log::new_meta(PRIMARY_START_OFFSET, &self.compression);
             let mem_buf = Box::pin(Vec::new());
             let (disk_buf, indexes) = Log::load_log_and_indexes(
                 &dir,
@@ -359,6 +402,7 @@ impl OpenOptions {
                 None,
                 self.fsync,
             )?;
+            let zstd = Log::load_zstd(&dir, &meta, &self.compression)?;
             Ok(Log {
                 dir,
                 disk_buf,
@@ -367,6 +411,7 @@ impl OpenOptions {
                 indexes,
                 index_corrupted: false,
                 open_options: self.clone(),
+                zstd,
             })
         })();
 
@@ -393,17 +438,17 @@ impl OpenOptions {
         let create = self.create;
 
         // Do a lock-less load_or_create_meta to avoid the flock overhead.
-        let meta = Log::load_or_create_meta(dir, false).or_else(|err| {
+        let meta = Log::load_or_create_meta(dir, None).or_else(|err| {
             if create {
                 dir.mkdir()
                     .context("cannot mkdir after failing to read metadata")
                     .source(err)?;
                 // Make sure check and write happens atomically.
                 if lock.is_some() {
-                    Log::load_or_create_meta(dir, true)
+                    Log::load_or_create_meta(dir, Some(&self.compression))
                 } else {
                     let _lock = dir.lock()?;
-                    Log::load_or_create_meta(dir, true)
+                    Log::load_or_create_meta(dir, Some(&self.compression))
                 }
             } else {
                 Err(err).context(|| format!("cannot open Log at {:?}", &dir))
@@ -419,6 +464,7 @@ impl OpenOptions {
             reuse_indexes,
             self.fsync,
         )?;
+        let zstd = Log::load_zstd(dir, &meta, &self.compression)?;
         let mut log = Log {
             dir: dir.clone(),
             disk_buf,
@@ -427,6 +473,7 @@ impl OpenOptions {
             indexes,
             index_corrupted: false,
             open_options: self.clone(),
+            zstd,
         };
         log.update_indexes_for_on_disk_entries()?;
         let lagging_index_ids = log.lagging_index_ids();
@@ -477,6 +524,51 @@ impl IndexOutput {
     }
 }
 
+impl Compression {
+    /// Train a zstd dictionary of at most `max_size` bytes from sample
+    /// entries.
+    pub fn train_zstd_dictionary<S: AsRef<[u8]>>(
+        samples: &[S],
+        max_size: usize,
+    ) -> crate::Result<Bytes> {
+        let dictionary = zstd::dict::from_samples(samples, max_size).map_err(|e| {
+            crate::Error::wrap(Box::new(e), || {
+                format!(
+                    "cannot train zstd dictionary from {} samples",
+                    samples.len()
+                )
+            })
+        })?;
+        Ok(dictionary.into())
+    }
+
+    /// The zstd compression level and dictionary, if entries are compressed.
+    pub(crate) fn zstd(&self) -> Option<(i32, Option<Bytes>)> {
+        match self {
+            Compression::None => None,
+            Compression::Zstd { level, dictionary } => {
+                let dictionary = dictionary.clone().filter(|d| !d.is_empty());
+                Some((*level, dictionary))
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Compression::None => write!(f, "None"),
+            Compression::Zstd { level, dictionary } => {
+                write!(f, "Zstd {{ level: {}, dictionary: ", level)?;
+                match dictionary {
+                    Some(dictionary) => write!(f, "<{} bytes> }}", dictionary.len()),
+                    None => write!(f, "None }}"),
+                }
+            }
+        }
+    }
+}
+
 impl fmt::Debug for OpenOptions {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "OpenOptions {{ ")?;
@@ -488,6 +580,7 @@ impl fmt::Debug for OpenOptions {
         write!(f, "fsync: {}, ", self.fsync)?;
         write!(f, "create: {}, ", self.create)?;
         write!(f, "checksum_type: {:?}, ", self.checksum_type)?;
+        write!(f, "compression: {:?}, ", self.compression)?;
         write!(f, "auto_sync_threshold: {:?}, ", self.auto_sync_threshold)?;
         let flush_filter_desc = match self.flush_filter {
             Some(ref _buf) => "Some(_)",
@@ -8,8 +8,8 @@
 use crate::errors::{IoResultExt, ResultExt};
 use crate::lock::ScopedDirLock;
 use crate::log::{
-    GenericPath, LogMetadata, OpenOptions, META_FILE, PRIMARY_FILE, PRIMARY_HEADER,
-    PRIMARY_START_OFFSET,
+    self, GenericPath, LogMetadata, OpenOptions, META_FILE, PRIMARY_FILE, PRIMARY_HEADER,
+    PRIMARY_HEADER_V1, PRIMARY_START_OFFSET,
 };
 use crate::repair::OpenOptionsRepair;
 use crate::utils::{self, mmap_path};
@@ -43,16 +43,24 @@ impl OpenOptions {
 
             let lock = ScopedDirLock::new(dir)?;
 
-            let primary_path = dir.join(PRIMARY_FILE);
             let meta_path = dir.join(META_FILE);
 
+            // If metadata cannot be read, use the latest generation of
+            // files, written by the last compaction.
+            let generation = match LogMetadata::read_file(&meta_path) {
+                Ok(meta) => meta.generation,
+                Err(_) => latest_generation(dir),
+            };
+            let primary_path = dir.join(log::generation_filename(generation, PRIMARY_FILE));
+
             // Make sure the header of the primary log file is okay.
-            (|| -> crate::Result<()> {
+            // Entries can be compressed if the log was created with compression.
+            let zstd = (|| -> crate::Result<bool> {
                 #[allow(clippy::never_loop)]
-                let header_corrupted = loop {
+                let header = loop {
                     if let Err(e) = primary_path.metadata() {
                         if e.kind() == io::ErrorKind::NotFound {
-                            break true;
+                            break None;
                         }
                     }
                     let mut file = fs::OpenOptions::new()
@@ -60,23 +68,24 @@ impl OpenOptions {
                         .open(&primary_path)
                         .context(&primary_path, "cannot open for read")?;
                     let mut buf = [0; PRIMARY_START_OFFSET as usize];
-                    break match file.read_exact(&mut buf) {
-                        Ok(_) => buf != PRIMARY_HEADER,
-                        Err(_) => true,
-                    };
+                    break file.read_exact(&mut buf).ok().map(|_| buf);
                 };
-                if header_corrupted {
-                    let mut file = fs::OpenOptions::new()
-                        .write(true)
-                        .create(true)
-                        .open(&primary_path)
-                        .context(&primary_path, "cannot open for write")?;
-                    file.write_all(PRIMARY_HEADER)
-                        .context(&primary_path, "cannot re-write header")?;
-                    let _ = utils::fix_perm_file(&file, false);
-                    message += "Fixed header in log\n";
+                match header {
+                    Some(buf) if buf == PRIMARY_HEADER => Ok(false),
+                    Some(buf) if buf == PRIMARY_HEADER_V1 => Ok(true),
+                    _ => {
+                        let mut file = fs::OpenOptions::new()
+                            .write(true)
+                            .create(true)
+                            .open(&primary_path)
+                            .context(&primary_path, "cannot open for write")?;
+                        file.write_all(log::primary_header(&self.compression))
+                            .context(&primary_path, "cannot re-write header")?;
+                        let _ = utils::fix_perm_file(&file, false);
+                        message += "Fixed header in log\n";
+                        Ok(self.compression.zstd().is_some())
+                    }
                 }
-                Ok(())
             })()
             .context("while making sure log has the right header")?;
 
@@ -111,7 +120,12 @@ impl OpenOptions {
                     }
                     Err(meta_err) => {
                         // Attempt to rebuild metadata.
-                        let meta = LogMetadata::new_with_primary_len(primary_len);
+                        // Entries can be compressed if the log was created
+                        // with compression. The zstd dictionary is kept in
+                        // its own file.
+                        let mut meta = LogMetadata::new_with_primary_len(primary_len);
+                        meta.zstd = zstd;
+                        meta.generation = generation;
                         meta.write_file(&meta_path, self.fsync)
                             .context("while recreating meta")
                             .source(meta_err)?;
@@ -141,7 +155,7 @@ impl OpenOptions {
 
             // Read entries until hitting a checksum error.
             let mut entry_count = 0;
-            while let Some(Ok(_)) = iter.next() {
+            while let Some(Ok(_)) = iter.next_bytes() {
                 entry_count += 1;
             }
 
@@ -206,6 +220,7 @@ impl OpenOptions {
                 log.meta.indexes.clear();
                 log.meta.epoch = log.meta.epoch.wrapping_add(1);
                 log.disk_buf = mmap_path(&primary_path, valid_len)?;
+                log.zstd.clear_cache();
 
                 log.meta
                     .write_file(&meta_path, log.open_options.fsync)
@@ -228,6 +243,24 @@ impl OpenOptions {
     }
 }
 
+/// Find the latest generation of primary log files in `dir`.
+fn latest_generation(dir: &Path) -> u64 {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name();
+            match log::parse_generation_filename(name.to_str()?) {
+                Some((generation, PRIMARY_FILE)) => Some(generation),
+                _ => None,
+            }
+        })
+        .max()
+        .unwrap_or(0)
+}
+
 impl OpenOptionsRepair for OpenOptions {
     fn open_options_repair(&self, dir: impl AsRef<Path>) -> crate::Result<String> {
         OpenOptions::repair(self, dir.as_ref())
@@ -253,13 +286,15 @@ impl OpenOptions {
             let lock = ScopedDirLock::new(dir)?;
 
             // Replace the metadata to an empty state.
-            let meta = LogMetadata::new_with_primary_len(PRIMARY_START_OFFSET);
+            log::write_zstd_dictionary(dir, &self.compression)?;
+            let meta = log::new_meta(PRIMARY_START_OFFSET, &self.compression);
             let meta_path = dir.join(META_FILE);
             meta.write_file(&meta_path, self.fsync)?;
 
             // Replace the primary log.
             let primary_path = dir.join(PRIMARY_FILE);
-            utils::atomic_write_plain(&primary_path, PRIMARY_HEADER, self.fsync)?;
+            let header = log::primary_header(&self.compression);
+            utils::atomic_write_plain(&primary_path, header, self.fsync)?;
 
             // Replace indexes so they become empty.
             let log = self
@@ -269,6 +304,9 @@ impl OpenOptions {
                 .context("cannot open")?;
             log.rebuild_indexes_with_lock(true, &lock)?;
 
+            // Files of compacted generations are no longer referred.
+            log::remove_generation_files(dir, |generation| generation != 0);
+
             Ok(())
         })();
 
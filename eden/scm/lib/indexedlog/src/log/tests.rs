@@ -148,6 +148,172 @@ fn test_checksum_type() {
     );
 }
 
+#[test]
+fn test_compression() {
+    let dir = tempdir().unwrap();
+    let log_path = dir.path().join("log");
+    let open_opts = OpenOptions::new()
+        .index_defs(get_index_defs(0))
+        .compression(Compression::Zstd {
+            level: 3,
+            dictionary: None,
+        })
+        .create(true);
+
+    // Compressible entries are compressed. Short entries are not.
+    let entries: Vec<Vec<u8>> = vec![b"abcd".to_vec(), vec![b'x'; 1000], b"xyz".repeat(300)];
+    let mut log = open_opts.open(&log_path).unwrap();
+    for entry in entries.iter() {
+        log.append(entry).unwrap();
+    }
+
+    let lookup = |log: &Log, index_id: usize, key: &[u8]| -> Vec<Vec<u8>> {
+        let iter = log.lookup(index_id, key).unwrap();
+        let entries: Vec<&[u8]> = iter.into_vec().unwrap();
+        entries.into_iter().map(|entry| entry.to_vec()).collect()
+    };
+    let check = |log: &Log| {
+        assert_eq!(log.iter().collect::<Result<Vec<_>, _>>().unwrap(), entries);
+        assert_eq!(
+            log.iter()
+                .into_bytes()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            entries
+        );
+        // Index functions see uncompressed entries.
+        assert_eq!(lookup(log, 0, b"bc"), vec![b"abcd".to_vec()]);
+        // Every occurrence of a key in an entry is indexed.
+        assert_eq!(lookup(log, 0, b"yz"), vec![entries[2].clone(); 300]);
+        assert_eq!(lookup(log, 1, b"xxx"), vec![entries[1].clone(); 998]);
+        assert_eq!(
+            log.lookup(0, b"yz")
+                .unwrap()
+                .into_bytes()
+                .next()
+                .unwrap()
+                .unwrap(),
+            entries[2]
+        );
+        let (key, iter) = log
+            .lookup_prefix(1, b"xx")
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(key.as_ref(), b"xxx");
+        assert_eq!(iter.into_vec().unwrap()[0], &entries[1][..]);
+    };
+    check(&log);
+    let size = log.sync().unwrap();
+    assert!(
+        size < 200,
+        "log should be compressed, but has {} bytes",
+        size
+    );
+    check(&log);
+
+    // Older versions refuse to read the log.
+    let meta = utils::atomic_read(&log_path.join(META_FILE)).unwrap();
+    assert!(meta.starts_with(b"met2\0"));
+
+    // Reading does not require the compression option.
+    let log = OpenOptions::new()
+        .index_defs(get_index_defs(0))
+        .open(&log_path)
+        .unwrap();
+    check(&log);
+
+    // Rebuilt indexes work too.
+    log.rebuild_indexes(true).unwrap();
+    let log = open_opts.open(&log_path).unwrap();
+    check(&log);
+
+    // In-memory logs support compression.
+    let mut log = open_opts.open(()).unwrap();
+    for entry in entries.iter() {
+        log.append(entry).unwrap();
+    }
+    check(&log);
+}
+
+#[test]
+fn test_compression_dictionary() {
+    let dir = tempdir().unwrap();
+    let log_path = dir.path().join("log");
+
+    let entry = |i: usize| {
+        format!(
+            "{{\"name\": \"entry{}\", \"kind\": \"file\", \"size\": {}}}",
+            i,
+            i * 7
+        )
+    };
+    let samples: Vec<String> = (0..1000).map(entry).collect();
+    let dictionary = Compression::train_zstd_dictionary(&samples, 4096).unwrap();
+    let open_opts = OpenOptions::new()
+        .compression(Compression::Zstd {
+            level: 3,
+            dictionary: Some(dictionary.clone()),
+        })
+        .create(true);
+
+    let mut log = open_opts.open(&log_path).unwrap();
+    for i in 2000..2100 {
+        log.append(entry(i)).unwrap();
+    }
+    log.sync().unwrap();
+
+    // The dictionary is stored once in its own file, not in the metadata.
+    let stored = fs::read(log_path.join("zstd-dictionary")).unwrap();
+    assert_eq!(&stored[..], &dictionary[..]);
+    let meta = utils::atomic_read(&log_path.join(META_FILE)).unwrap();
+    assert!(meta.len() < 100, "meta has {} bytes", meta.len());
+
+    // The dictionary is read from its file.
+    let log = Log::open(&log_path, Vec::new()).unwrap();
+    let expected: Vec<Vec<u8>> = (2000..2100).map(|i| entry(i).into_bytes()).collect();
+    assert_eq!(
+        log.iter()
+            .into_bytes()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn test_compression_existing_log() {
+    let dir = tempdir().unwrap();
+    let log_path = dir.path().join("log");
+    let data = vec![b'x'; 1000];
+
+    let mut log = Log::open(&log_path, Vec::new()).unwrap();
+    log.append(&data).unwrap();
+    log.sync().unwrap();
+
+    // Logs created without compression stay uncompressed, so older readers
+    // can still read them.
+    let mut log = OpenOptions::new()
+        .compression(Compression::Zstd {
+            level: 3,
+            dictionary: None,
+        })
+        .open(&log_path)
+        .unwrap();
+    log.append(&data).unwrap();
+    assert!(log.sync().unwrap() > 2000);
+    assert_eq!(
+        log.iter().collect::<Result<Vec<_>, _>>().unwrap(),
+        vec![&data[..], &data[..]]
+    );
+
+    let primary = fs::read(log_path.join(PRIMARY_FILE)).unwrap();
+    assert_eq!(&primary[..PRIMARY_START_OFFSET as usize], PRIMARY_HEADER);
+    let meta = utils::atomic_read(&log_path.join(META_FILE)).unwrap();
+    assert!(meta.starts_with(b"meta\0"));
+}
+
 #[test]
 fn test_iter_and_iter_dirty() {
     let dir = tempdir().unwrap();
@@ -958,6 +1124,47 @@ fn test_repair() {
     );
 }
 
+#[test]
+fn test_repair_compressed() {
+    let dir = tempdir().unwrap();
+    let samples: Vec<Vec<u8>> = (0..1000u32)
+        .map(|i| format!("entry {} {}", i, "abc".repeat(i as usize % 5)).into_bytes())
+        .collect();
+    let dictionary = Compression::train_zstd_dictionary(&samples, 4096).unwrap();
+    let open_opts = OpenOptions::new()
+        .compression(Compression::Zstd {
+            level: 3,
+            dictionary: Some(dictionary),
+        })
+        .create(true);
+    let entries = [vec![b'a'; 100], vec![b'b'; 100], vec![b'c'; 100]];
+    {
+        let mut log = open_opts.open(dir.path()).unwrap();
+        for entry in entries.iter() {
+            log.append(entry).unwrap();
+        }
+        log.sync().unwrap();
+    }
+
+    // Corrupt the last entry, and the metadata.
+    pwrite(&dir.path().join(PRIMARY_FILE), -1, b"x");
+    utils::atomic_write(dir.path().join(META_FILE), "xxxxxx", false).unwrap();
+    assert!(Log::open(dir.path(), Vec::new()).is_err());
+
+    // Repair does not need the compression option. The dictionary is kept
+    // in its own file.
+    let message = OpenOptions::new().repair(dir.path()).unwrap();
+    assert!(message.contains("Rebuilt metadata"), "{}", message);
+    let log = Log::open(dir.path(), Vec::new()).unwrap();
+    assert_eq!(
+        log.iter()
+            .into_bytes()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap(),
+        &entries[..2]
+    );
+}
+
 #[test]
 fn test_repair_noop() {
     // Repair does nothing if the Log can be read out without issues.
@@ -1294,6 +1501,183 @@ Rebuilt index "c""#
     delete_content();
 }
 
+#[test]
+fn test_compact() {
+    let dir = tempdir().unwrap();
+    let open_opts = OpenOptions::new()
+        .create(true)
+        .index_defs(get_index_defs(0));
+    let mut log = open_opts.open(dir.path()).unwrap();
+    for data in [b"abc", b"bcd", b"abe", b"cde"].iter() {
+        log.append(data).unwrap();
+    }
+    log.sync().unwrap();
+
+    // A reader, and a writer with pending entries, opened before compaction.
+    let mut reader = open_opts.open(dir.path()).unwrap();
+    let mut writer = open_opts.open(dir.path()).unwrap();
+    writer.append(b"abf").unwrap();
+
+    let message = open_opts
+        .compact(dir.path(), |data| data[0] != b'b')
+        .unwrap();
+    assert_eq!(
+        message,
+        "Kept 3 of 4 entries, 39 of 48 bytes in log\nRebuilt index \"x\"\nRebuilt index \"y\"\n"
+    );
+
+    // The reader can still read the old files.
+    assert_eq!(reader.iter().count(), 4);
+    assert_eq!(
+        reader.lookup(1, b"bcd").unwrap().into_vec().unwrap(),
+        [b"bcd"]
+    );
+
+    let log = open_opts.open(dir.path()).unwrap();
+    assert_eq!(
+        log.iter().collect::<Result<Vec<_>, _>>().unwrap(),
+        [b"abc", b"abe", b"cde"]
+    );
+    assert_eq!(
+        log.lookup(0, b"ab").unwrap().into_vec().unwrap(),
+        [b"abe", b"abc"]
+    );
+    assert_eq!(log.lookup(0, b"bc").unwrap().into_vec().unwrap(), [b"abc"]);
+    assert!(log
+        .lookup(1, b"bcd")
+        .unwrap()
+        .into_vec()
+        .unwrap()
+        .is_empty());
+
+    // The writer keeps its pending entry.
+    writer.sync().unwrap();
+    reader.sync().unwrap();
+    for log in [writer, reader].iter() {
+        assert_eq!(
+            log.iter().collect::<Result<Vec<_>, _>>().unwrap(),
+            [b"abc", b"abe", b"cde", b"abf"]
+        );
+        assert_eq!(
+            log.lookup(0, b"ab").unwrap().into_vec().unwrap(),
+            [b"abf", b"abe", b"abc"]
+        );
+    }
+
+    // Nothing changes if all entries are kept.
+    let meta_before = LogMetadata::read_file(dir.path().join(META_FILE)).unwrap();
+    let message = open_opts.compact(dir.path(), |_| true).unwrap();
+    assert!(message.contains("Nothing to compact"), "{}", message);
+    let meta_after = LogMetadata::read_file(dir.path().join(META_FILE)).unwrap();
+    assert_eq!(meta_before, meta_after);
+}
+
+#[test]
+fn test_compact_generations() {
+    let dir = tempdir().unwrap();
+    let open_opts = OpenOptions::new()
+        .create(true)
+        .index_defs(get_index_defs(0));
+    let mut log = open_opts.open(dir.path()).unwrap();
+    for data in [b"abc", b"bcd", b"abe", b"cde"].iter() {
+        log.append(data).unwrap();
+    }
+    log.sync().unwrap();
+    let exists = |name: &str| dir.path().join(name).exists();
+    let read_meta = || LogMetadata::read_file(dir.path().join(META_FILE)).unwrap();
+    let entries = || {
+        let log = open_opts.open(dir.path()).unwrap();
+        let entries = log.iter().collect::<Result<Vec<_>, _>>().unwrap();
+        entries.into_iter().map(|e| e.to_vec()).collect::<Vec<_>>()
+    };
+
+    // Files of an interrupted compaction are not referred by metadata.
+    let primary_before = fs::read(dir.path().join(PRIMARY_FILE)).unwrap();
+    fs::write(dir.path().join("gen1-log"), b"xxxx").unwrap();
+    fs::write(dir.path().join("gen1-index2-x"), b"xxxx").unwrap();
+    assert_eq!(entries().len(), 4);
+
+    // Compaction writes new files, and leaves the current ones untouched.
+    open_opts
+        .compact(dir.path(), |data| data[0] != b'b')
+        .unwrap();
+    assert_eq!(read_meta().generation, 1);
+    assert_eq!(
+        fs::read(dir.path().join(PRIMARY_FILE)).unwrap(),
+        primary_before
+    );
+    assert_eq!(entries(), [b"abc", b"abe", b"cde"]);
+
+    // Files of the previous generation are kept for readers loading them.
+    // Older ones are removed.
+    open_opts
+        .compact(dir.path(), |data| data[0] != b'c')
+        .unwrap();
+    assert_eq!(read_meta().generation, 2);
+    assert!(exists("gen1-log"));
+    assert!(!exists(PRIMARY_FILE));
+    assert!(!exists("index2-x"));
+    assert_eq!(entries(), [b"abc", b"abe"]);
+
+    // Repair uses the latest generation if metadata is lost.
+    fs::remove_file(dir.path().join(META_FILE)).unwrap();
+    open_opts.repair(dir.path()).unwrap();
+    assert_eq!(read_meta().generation, 2);
+    assert_eq!(entries(), [b"abc", b"abe"]);
+
+    // delete_content starts over from the first generation.
+    open_opts.delete_content(dir.path()).unwrap();
+    assert_eq!(read_meta().generation, 0);
+    assert!(!exists("gen1-log"));
+    assert!(!exists("gen2-log"));
+    assert!(entries().is_empty());
+}
+
+#[test]
+fn test_compact_compressed() {
+    let dir = tempdir().unwrap();
+    let samples: Vec<Vec<u8>> = (0..1000u32)
+        .map(|i| format!("entry {} {}", i, "abc".repeat(i as usize % 5)).into_bytes())
+        .collect();
+    let dictionary = Compression::train_zstd_dictionary(&samples, 4096).unwrap();
+    let open_opts = OpenOptions::new()
+        .compression(Compression::Zstd {
+            level: 3,
+            dictionary: Some(dictionary),
+        })
+        .index_defs(vec![IndexDef::new("k", |_| {
+            vec![IndexOutput::Reference(0..1)]
+        })])
+        .create(true);
+    let entries = [vec![b'a'; 100], vec![b'b'; 100], vec![b'c'; 100]];
+    let mut log = open_opts.open(dir.path()).unwrap();
+    for entry in entries.iter() {
+        log.append(entry).unwrap();
+    }
+    log.sync().unwrap();
+
+    open_opts
+        .compact(dir.path(), |data| data[0] != b'b')
+        .unwrap();
+    let log = open_opts.open(dir.path()).unwrap();
+    assert_eq!(
+        log.iter()
+            .into_bytes()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap(),
+        [&entries[0], &entries[2]]
+    );
+    assert_eq!(
+        log.lookup(0, b"c")
+            .unwrap()
+            .into_bytes()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap(),
+        [&entries[2]]
+    );
+    assert_eq!(log.lookup(0, b"b").unwrap().count(), 0);
+}
+
 #[test]
 fn test_zero_data() {
     // Emulating the case where meta was written, but log was zeroed out.
@@ -38,6 +38,11 @@ pub struct OpenOptions {
 /// accesses the first [`Log`]. [`Log`]s can also be moved out of this
 /// struct by [`MultiLog::detach_logs`].
 ///
+/// Unlike [`RotateLog`](crate::rotate::RotateLog), the [`Log`]s store
+/// different data with different indexes, so lookups are not merged across
+/// them. Prefix and range lookups are done on a single [`Log`], for example,
+/// `multilog[0].lookup_prefix(...)`, and see the data of the centric metadata.
+///
 /// [`MultiLog`] makes sure the data consistency on disk but not always
 /// in memory. In case [`MultiLog::write_meta`] is not called or is not
 /// successful, but [`Log::sync`] was called. The data in [`Log`] might
@@ -110,7 +115,10 @@ impl OpenOptions {
                 if !multimeta.metas.contains_key(name_ref) {
                     // Create a new Log if it does not exist in MultiMeta.
                     utils::mkdir_p(&fspath)?;
-                    let meta = log::Log::load_or_create_shared_meta(&fspath.as_path().into())?;
+                    let meta = log::Log::load_or_create_shared_meta(
+                        &fspath.as_path().into(),
+                        &opts.compression,
+                    )?;
                     let meta = Arc::new(Mutex::new(meta));
                     multimeta.metas.insert(name.to_string(), meta);
                 }
@@ -404,6 +412,44 @@ mod tests {
         assert_eq!(mlog2[1].iter().count(), 1);
     }
 
+    #[test]
+    fn test_lookup_prefix_and_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path();
+        let index_def = log::IndexDef::new("first-two-bytes", |_| {
+            vec![log::IndexOutput::Reference(0..2)]
+        });
+        let mopts = OpenOptions::from_name_opts(vec![
+            ("a", log::OpenOptions::new().index_defs(vec![index_def])),
+            ("b", log::OpenOptions::new()),
+        ]);
+        let keys = |iter: log::LogRangeIter| -> Vec<Vec<u8>> {
+            iter.map(|item| item.unwrap().0.to_vec()).collect()
+        };
+
+        let mut mlog = mopts.open(path).unwrap();
+        mlog[0].append(b"ab1").unwrap();
+        mlog[0].append(b"ac1").unwrap();
+        mlog[0].append(b"bb1").unwrap();
+        mlog.sync().unwrap();
+
+        // Entries synced without writing the multimeta are not visible.
+        let lock = mlog.lock().unwrap();
+        mlog[0].append(b"aa2").unwrap();
+        mlog[0].sync().unwrap();
+        drop(lock);
+
+        let mlog2 = mopts.open(path).unwrap();
+        assert_eq!(
+            keys(mlog2[0].lookup_prefix(0, b"a").unwrap()),
+            vec![b"ab", b"ac"]
+        );
+        assert_eq!(
+            keys(mlog2[0].lookup_range(0, &b"ac"[..]..).unwrap()),
+            vec![b"ac", b"bb"]
+        );
+    }
+
     #[test]
     fn test_wrong_locks_cause_errors() {
         let dir = tempfile::tempdir().unwrap();
@@ -14,9 +14,13 @@ use crate::repair::OpenOptionsRepair;
 use crate::utils;
 use minibytes::Bytes;
 use once_cell::sync::OnceCell;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::fmt;
 use std::fs;
 use std::io;
+use std::ops::{Bound, RangeBounds};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 use tracing::{debug_span, trace};
@@ -96,6 +100,17 @@ impl OpenOptions {
         self
     }
 
+    /// Sets how entries are compressed.
+    ///
+    /// Compression applies to [`Log`]s created after it is set, so existing
+    /// data gets compressed as logs rotate.
+    ///
+    /// See [log::Compression] for details.
+    pub fn compression(mut self, compression: log::Compression) -> Self {
+        self.log_open_options = self.log_open_options.compression(compression);
+        self
+    }
+
     /// Set whether create the [`RotateLog`] structure if it does not exist.
     pub fn create(mut self, create: bool) -> Self {
         self.log_open_options = self.log_open_options.create(create);
@@ -294,6 +309,26 @@ impl OpenOptions {
         })()
         .context(|| format!("in rotate::OpenOptions::repair({:?})", dir))
     }
+
+    /// Compact the log with the given id in the specified directory, so it
+    /// only keeps entries that `predicate` returns `true` for.
+    ///
+    /// This just calls into [`log::OpenOptions::compact`] while holding the
+    /// directory lock, so the log is not rotated away meanwhile.
+    pub fn compact(
+        &self,
+        dir: impl AsRef<Path>,
+        id: u8,
+        predicate: impl FnMut(&[u8]) -> bool,
+    ) -> crate::Result<String> {
+        let dir = dir.as_ref();
+        (|| -> crate::Result<_> {
+            let _lock = ScopedDirLock::new(dir)?;
+            let log_path = dir.join(id.to_string());
+            self.log_open_options.compact(&log_path, predicate)
+        })()
+        .context(|| format!("in rotate::OpenOptions::compact({:?}, {})", dir, id))
+    }
 }
 
 impl OpenOptionsRepair for OpenOptions {
@@ -383,6 +418,82 @@ impl RotateLog {
             .context(|| format!("  RotateLog.dir = {:?}", self.dir))
     }
 
+    /// Look up keys and entries using the given prefix.
+    /// The `index_id` is the index of `index_defs` stored in [`OpenOptions`].
+    ///
+    /// Return an iterator that yields `(key, iter)`, where `key` is the full
+    /// key, `iter` is [`RotateLogLookupIter`] that allows iteration through
+    /// matched entries. See [`RotateLogRangeIter`] for details.
+    pub fn lookup_prefix(
+        &self,
+        index_id: usize,
+        prefix: impl AsRef<[u8]>,
+    ) -> crate::Result<RotateLogRangeIter<'_>> {
+        let prefix = prefix.as_ref().to_vec();
+        let context = format!("in RotateLog::lookup_prefix({}, {:?})", index_id, &prefix);
+        Ok(RotateLogRangeIter::new(
+            self,
+            index_id,
+            context,
+            Box::new(move |log| log.lookup_prefix(index_id, &prefix)),
+        ))
+    }
+
+    /// Look up keys and entries by querying a specified index about a
+    /// specified range.
+    /// The `index_id` is the index of `index_defs` stored in [`OpenOptions`].
+    ///
+    /// Return an iterator that yields `(key, iter)`, where `key` is the full
+    /// key, `iter` is [`RotateLogLookupIter`] that allows iteration through
+    /// entries matching that key. See [`RotateLogRangeIter`] for details.
+    pub fn lookup_range<'a>(
+        &self,
+        index_id: usize,
+        range: impl RangeBounds<&'a [u8]>,
+    ) -> crate::Result<RotateLogRangeIter<'_>> {
+        let start = to_owned_bound(range.start_bound());
+        let end = to_owned_bound(range.end_bound());
+        let context = format!(
+            "in RotateLog::lookup_range({}, {:?} to {:?})",
+            index_id, &start, &end,
+        );
+        Ok(RotateLogRangeIter::new(
+            self,
+            index_id,
+            context,
+            Box::new(move |log| {
+                log.lookup_range(
+                    index_id,
+                    (to_borrowed_bound(&start), to_borrowed_bound(&end)),
+                )
+            }),
+        ))
+    }
+
+    /// Look up keys and entries using the given hex prefix.
+    /// The length of the hex string can be odd.
+    ///
+    /// Return an iterator that yields `(key, iter)`, where `key` is the full
+    /// key, `iter` is [`RotateLogLookupIter`] that allows iteration through
+    /// matched entries. See [`RotateLogRangeIter`] for details.
+    pub fn lookup_prefix_hex(
+        &self,
+        index_id: usize,
+        hex_prefix: impl AsRef<[u8]>,
+    ) -> crate::Result<RotateLogRangeIter<'_>> {
+        let prefix = hex_prefix.as_ref().to_vec();
+        let context = format!(
+            "in RotateLog::lookup_prefix_hex({}, {:?})",
+            index_id, &prefix
+        );
+        Ok(RotateLogRangeIter::new(
+            self,
+            index_id,
+            context,
+            Box::new(move |log| log.lookup_prefix_hex(index_id, &prefix)),
+        ))
+    }
+
     /// Read latest data from disk. Write in-memory entries to disk.
     ///
     /// Return the index of the latest [`Log`].
@@ -600,6 +711,17 @@ impl RotateLog {
         logs.into_iter().rev().flat_map(|log| log.iter())
     }
 
+    /// Iterate over all the entries as [`Bytes`].
+    ///
+    /// Unlike [`RotateLog::iter`], this does not keep decompressed entries
+    /// in memory.
+    pub fn iter_bytes(&self) -> impl Iterator<Item = crate::Result<Bytes>> + '_ {
+        let logs = self.logs();
+        logs.into_iter()
+            .rev()
+            .flat_map(|log| log.iter().into_bytes())
+    }
+
     /// Iterate over all dirty entries.
     pub fn iter_dirty(&mut self) -> impl Iterator<Item = crate::Result<&[u8]>> {
         self.writable_log().iter_dirty()
@@ -682,15 +804,31 @@ impl<'a> Iterator for RotateLogLookupIter<'a> {
     type Item = crate::Result<&'a [u8]>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.end {
-            return None;
-        }
-        match self.inner_iter.next() {
-            None => {
-                if self.log_index + 1 >= self.log_rotate.logs.len() {
-                    self.end = true;
-                    None
-                } else {
+        self.next_with(|iter| iter.next())
+    }
+}
+
+impl<'a> RotateLogLookupIter<'a> {
+    /// Convert to an iterator of [`Bytes`]. Unlike this iterator, the
+    /// returned iterator does not keep decompressed entries in memory.
+    pub fn into_bytes(mut self) -> impl Iterator<Item = crate::Result<Bytes>> + 'a {
+        std::iter::from_fn(move || self.next_with(|iter| iter.next_bytes()))
+    }
+
+    fn next_with<T>(
+        &mut self,
+        next: impl Fn(&mut log::LogLookupIter<'a>) -> Option<crate::Result<T>>,
+    ) -> Option<crate::Result<T>> {
+        loop {
+            if self.end {
+                return None;
+            }
+            match next(&mut self.inner_iter) {
+                None => {
+                    if self.log_index + 1 >= self.log_rotate.logs.len() {
+                        self.end = true;
+                        return None;
+                    }
                     // Try the next log
                     self.log_index += 1;
                     match self.log_rotate.load_log(self.log_index) {
@@ -714,18 +852,155 @@ impl<'a> Iterator for RotateLogLookupIter<'a> {
                             }
                         }
                     }
-                    self.next()
                 }
+                Some(Err(err)) => {
+                    self.end = true;
+                    return Some(Err(err));
+                }
+                Some(Ok(data)) => return Some(Ok(data)),
+            }
+        }
+    }
+}
+
+/// Iterator over keys and [`RotateLogLookupIter`], filtered by an index
+/// prefix or range.
+///
+/// Keys of all [`Log`]s are merged, so they are yielded in sorted order. A
+/// key that exists in multiple [`Log`]s is only yielded once, and its
+/// [`RotateLogLookupIter`] yields entries from the newest [`Log`] having it
+/// to the oldest one.
+///
+/// All [`Log`]s are loaded when the first key is requested.
+pub struct RotateLogRangeIter<'a> {
+    log_rotate: &'a RotateLog,
+    index_id: usize,
+    // Run the prefix or range query on a single Log.
+    query: Box<dyn Fn(&'a Log) -> crate::Result<log::LogRangeIter<'a>> + 'a>,
+    // Used in error messages.
+    context: String,
+    // Whether `inner_iters` were created.
+    started: bool,
+    // Range iterators of all Logs. The 'latest' Log has index 0.
+    inner_iters: Vec<log::LogRangeIter<'a>>,
+    // Entries of the next key of each Log, if that Log has more keys.
+    next_values: Vec<Option<log::LogLookupIter<'a>>>,
+    // The next key of each Log, with the Log index. The smallest key is at
+    // the top. For equal keys, the newest Log is at the top.
+    next_keys: BinaryHeap<Reverse<(Cow<'a, [u8]>, usize)>>,
+    errored: bool,
+}
+
+impl<'a> RotateLogRangeIter<'a> {
+    fn new(
+        log_rotate: &'a RotateLog,
+        index_id: usize,
+        context: String,
+        query: Box<dyn Fn(&'a Log) -> crate::Result<log::LogRangeIter<'a>> + 'a>,
+    ) -> Self {
+        Self {
+            log_rotate,
+            index_id,
+            query,
+            context,
+            started: false,
+            inner_iters: Vec::new(),
+            next_values: Vec::new(),
+            next_keys: BinaryHeap::new(),
+            errored: false,
+        }
+    }
+
+    /// Run the query on all Logs and read their first keys.
+    fn start(&mut self) -> crate::Result<()> {
+        self.started = true;
+        while let Some(log) = self.log_rotate.load_log(self.inner_iters.len())? {
+            self.inner_iters.push((self.query)(log)?);
+            self.next_values.push(None);
+            self.advance(self.inner_iters.len() - 1)?;
+        }
+        Ok(())
+    }
+
+    /// Read the next key of the Log at `log_index`.
+    fn advance(&mut self, log_index: usize) -> crate::Result<()> {
+        if let Some(item) = self.inner_iters[log_index].next() {
+            let (key, values) = item?;
+            self.next_values[log_index] = Some(values);
+            self.next_keys.push(Reverse((key, log_index)));
+        }
+        Ok(())
+    }
+
+    fn next_internal(&mut self) -> crate::Result<Option<(Cow<'a, [u8]>, RotateLogLookupIter<'a>)>> {
+        if !self.started {
+            self.start()?;
+        }
+        let (key, log_index) = match self.next_keys.pop() {
+            None => return Ok(None),
+            Some(Reverse(next)) => next,
+        };
+        let inner_iter = self.next_values[log_index].take().unwrap();
+        self.advance(log_index)?;
+
+        // Older Logs having the same key are visited by the RotateLogLookupIter.
+        while let Some(Reverse((next_key, _))) = self.next_keys.peek() {
+            if next_key != &key {
+                break;
             }
-            Some(Err(err)) => {
-                self.end = true;
+            let Reverse((_, older_index)) = self.next_keys.pop().unwrap();
+            self.next_values[older_index] = None;
+            self.advance(older_index)?;
+        }
+
+        let iter = RotateLogLookupIter {
+            inner_iter,
+            end: false,
+            log_rotate: self.log_rotate,
+            log_index,
+            index_id: self.index_id,
+            key: Bytes::from(key.to_vec()),
+        };
+        Ok(Some((key, iter)))
+    }
+}
+
+impl<'a> Iterator for RotateLogRangeIter<'a> {
+    type Item = crate::Result<(Cow<'a, [u8]>, RotateLogLookupIter<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.errored {
+            return None;
+        }
+        match self.next_internal() {
+            Ok(item) => item.map(Ok),
+            Err(err) => {
+                self.errored = true;
+                let err = err
+                    .message(self.context.clone())
+                    .message(format!("  RotateLog.dir = {:?}", self.log_rotate.dir));
                 Some(Err(err))
             }
-            Some(Ok(slice)) => Some(Ok(slice)),
         }
     }
 }
 
+fn to_owned_bound(bound: Bound<&&[u8]>) -> Bound<Vec<u8>> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key.to_vec()),
+        Bound::Excluded(key) => Bound::Excluded(key.to_vec()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+fn to_borrowed_bound(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(key) => Bound::Included(&key[..]),
+        Bound::Excluded(key) => Bound::Excluded(&key[..]),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
 fn create_empty_log(
     dir: Option<&Path>,
     open_options: &OpenOptions,
@@ -1045,6 +1320,81 @@ mod tests {
         assert_eq!(iter(&rotate2), vec![b"a2"]);
     }
 
+    #[test]
+    fn test_lookup_prefix_and_range_rotated() {
+        let dir = tempdir().unwrap();
+        let opts = OpenOptions::new()
+            .create(true)
+            .max_bytes_per_log(1)
+            .max_log_count(3)
+            .index("first-two-bytes", |_| vec![IndexOutput::Reference(0..2)]);
+
+        // Keys are spread across Logs. "ab" exists in all of them.
+        let rotate = opts.clone().open(&dir).unwrap();
+        let rotate_mem = opts.clone().create_in_memory().unwrap();
+        for rotate in &mut [rotate, rotate_mem] {
+            rotate.append(b"ab1").unwrap();
+            rotate.append(b"ac1").unwrap();
+            rotate.sync().unwrap();
+            rotate.append(b"ab2").unwrap();
+            rotate.append(b"bb2").unwrap();
+            rotate.sync().unwrap();
+            rotate.append(b"ab3").unwrap();
+            rotate.append(b"aa3").unwrap();
+
+            // Keys are not duplicated. Entries are newest first.
+            let collect = |iter: RotateLogRangeIter| -> Vec<(Vec<u8>, Vec<Vec<u8>>)> {
+                iter.map(|item| {
+                    let (key, iter) = item.unwrap();
+                    let values = iter.map(|v| v.unwrap().to_vec()).collect();
+                    (key.to_vec(), values)
+                })
+                .collect()
+            };
+            let entries = |key: &[u8], values: &[&[u8]]| {
+                let values = values.iter().map(|v| v.to_vec()).collect();
+                (key.to_vec(), values)
+            };
+
+            assert_eq!(
+                collect(rotate.lookup_prefix(0, b"a").unwrap()),
+                vec![
+                    entries(b"aa", &[b"aa3"]),
+                    entries(b"ab", &[b"ab3", b"ab2", b"ab1"]),
+                    entries(b"ac", &[b"ac1"]),
+                ]
+            );
+            assert_eq!(
+                collect(rotate.lookup_prefix_hex(0, b"62").unwrap()),
+                vec![entries(b"bb", &[b"bb2"])]
+            );
+            assert_eq!(
+                collect(rotate.lookup_range(0, &b"ab"[..]..&b"bb"[..]).unwrap()),
+                vec![
+                    entries(b"ab", &[b"ab3", b"ab2", b"ab1"]),
+                    entries(b"ac", &[b"ac1"]),
+                ]
+            );
+            // Keys are sorted across Logs.
+            assert_eq!(
+                collect(rotate.lookup_range(0, ..).unwrap())
+                    .into_iter()
+                    .map(|(key, _)| key)
+                    .collect::<Vec<_>>(),
+                vec![b"aa", b"ab", b"ac", b"bb"]
+            );
+            assert!(collect(rotate.lookup_prefix(0, b"c").unwrap()).is_empty());
+        }
+
+        // Older Logs are loaded by the first `next`. Errors loading them are reported.
+        // On disk, "1/" has "ab2" and "bb2". "0/" has "ab1" and "ac1".
+        utils::atomic_write(dir.path().join("0").join("meta"), "foo", false).unwrap();
+        let rotate = opts.open(&dir).unwrap();
+        let mut iter = rotate.lookup_prefix(0, b"a").unwrap();
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
     #[test]
     fn test_lookup_truncated_meta() {
         // Look up or iteration should work with rotated logs.
@@ -1255,6 +1605,102 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compression() {
+        let dir = tempdir().unwrap();
+        let open_opts = OpenOptions::new()
+            .create(true)
+            .max_bytes_per_log(100)
+            .index("first-byte", |_| vec![IndexOutput::Reference(0..1)]);
+
+        // Existing logs are not compressed. Logs created later are.
+        let mut rotate = open_opts.clone().open(&dir).unwrap();
+        rotate.append(vec![b'a'; 1000]).unwrap();
+        assert_eq!(rotate.sync().unwrap(), 1);
+
+        let compression = log::Compression::Zstd {
+            level: 3,
+            dictionary: None,
+        };
+        let mut rotate = open_opts.compression(compression).open(&dir).unwrap();
+        rotate.append(vec![b'b'; 1000]).unwrap();
+        assert_eq!(rotate.sync().unwrap(), 2);
+        rotate.append(vec![b'c'; 1000]).unwrap();
+        assert_eq!(rotate.sync().unwrap(), 2);
+
+        let size = |name: &str| {
+            fs::metadata(dir.path().join(name).join("log"))
+                .unwrap()
+                .len()
+        };
+        assert!(size("1") > 1000);
+        assert!(size("2") < 100);
+
+        // Compressed entries are decompressed by all iterators.
+        let b = vec![b'b'; 1000];
+        let c = vec![b'c'; 1000];
+        assert_eq!(
+            rotate.iter().collect::<crate::Result<Vec<_>>>().unwrap(),
+            vec![&b[..], &c[..]]
+        );
+        assert_eq!(
+            rotate
+                .lookup(0, &b"c"[..])
+                .unwrap()
+                .collect::<crate::Result<Vec<_>>>()
+                .unwrap(),
+            vec![&c[..]]
+        );
+        assert_eq!(
+            rotate
+                .iter_bytes()
+                .collect::<crate::Result<Vec<_>>>()
+                .unwrap(),
+            vec![&b[..], &c[..]]
+        );
+        assert_eq!(
+            rotate
+                .lookup(0, &b"c"[..])
+                .unwrap()
+                .into_bytes()
+                .collect::<crate::Result<Vec<_>>>()
+                .unwrap(),
+            vec![&c[..]]
+        );
+        rotate.append(vec![b'd'; 1000]).unwrap();
+        assert_eq!(
+            rotate
+                .iter_dirty()
+                .collect::<crate::Result<Vec<_>>>()
+                .unwrap(),
+            vec![&vec![b'd'; 1000][..]]
+        );
+    }
+
+    #[test]
+    fn test_compact() {
+        let dir = tempdir().unwrap();
+        let open_opts = OpenOptions::new()
+            .create(true)
+            .index("first-byte", |_| vec![IndexOutput::Reference(0..1)]);
+        let mut rotate = open_opts.open(&dir).unwrap();
+        rotate.append(b"a1").unwrap();
+        rotate.append(b"b1").unwrap();
+        rotate.append(b"a2").unwrap();
+        rotate.sync().unwrap();
+        rotate.force_rotate().unwrap();
+        rotate.append(b"b2").unwrap();
+        rotate.sync().unwrap();
+
+        let message = open_opts.compact(&dir, 0, |data| data[0] != b'b').unwrap();
+        assert!(message.starts_with("Kept 2 of 3 entries"), "{}", message);
+
+        let rotate = open_opts.open(&dir).unwrap();
+        assert_eq!(iter(&rotate), vec![b"a1", b"a2", b"b2"]);
+        assert_eq!(lookup(&rotate, b"a"), vec![b"a2", b"a1"]);
+        assert_eq!(lookup(&rotate, b"b"), vec![b"b2"]);
+    }
+
     #[test]
     fn test_recover_from_empty_logs() {
         let dir = tempdir().unwrap();
@@ -86,6 +86,7 @@ pub struct StoreOpenOptions {
     max_log_count: Option<u8>,
     max_bytes_per_log: Option<u64>,
     indexes: Vec<IndexDef>,
+    compression: log::Compression,
 }
 
 impl StoreOpenOptions {
@@ -95,6 +96,7 @@ impl StoreOpenOptions {
             max_log_count: None,
             max_bytes_per_log: None,
             indexes: Vec::new(),
+            compression: log::Compression::None,
         }
     }
 
@@ -117,6 +119,13 @@ impl StoreOpenOptions {
         self
     }
 
+    /// Compress entries of logs created by the `Store`. Entries are decompressed transparently
+    /// on lookup.
+    pub fn compression(mut self, compression: log::Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
     /// When the in-memory buffer exceeds `threshold`, it's automatically flushed to disk.
     pub fn auto_sync_threshold(mut self, threshold: u64) -> Self {
         self.auto_sync_threshold = Some(threshold);
@@ -134,6 +143,7 @@ impl StoreOpenOptions {
                 .fsync(true)
                 .index_defs(self.indexes)
                 .auto_sync_threshold(self.auto_sync_threshold)
+                .compression(self.compression)
                 .open(path.as_ref())?,
         ))
     }
@@ -146,7 +156,8 @@ impl StoreOpenOptions {
         let mut opts = rotate::OpenOptions::new()
             .create(true)
             .auto_sync_threshold(self.auto_sync_threshold)
-            .index_defs(self.indexes);
+            .index_defs(self.indexes)
+            .compression(self.compression);
 
         if let Some(max_log_count) = self.max_log_count {
             opts = opts.max_log_count(max_log_count);
@@ -200,6 +211,34 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_compressed() -> Result<()> {
+        let entry = [&b"aa"[..], &[b'x'; 1000][..]].concat();
+        for &local in &[true, false] {
+            let dir = TempDir::new()?;
+            let opts = StoreOpenOptions::new()
+                .index("hex", |_| vec![IndexOutput::Reference(0..2)])
+                .compression(log::Compression::Zstd {
+                    level: 3,
+                    dictionary: None,
+                });
+            let mut store = if local {
+                opts.local(&dir)?
+            } else {
+                opts.shared(&dir)?
+            };
+
+            store.append(&entry)?;
+            store.flush()?;
+
+            assert_eq!(
+                store.lookup(0, b"aa")?.collect::<Result<Vec<_>>>()?,
+                vec![&entry[..]]
+            );
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_local_no_rotate() -> Result<()> {
         let dir = TempDir::new()?;
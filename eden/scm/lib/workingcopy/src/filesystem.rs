@@ -8,26 +8,36 @@
 use std::{
     collections::HashSet,
     convert::{TryFrom, TryInto},
-    fs::Metadata,
+    fs::{self, Metadata},
     path::PathBuf,
     sync::Arc,
     time::SystemTime,
 };
 
-use anyhow::{Error, Result};
+use anyhow::{format_err, Error, Result};
 use parking_lot::Mutex;
 
+use manifest::{FileMetadata, FileType, Manifest};
+use manifest_tree::TreeManifest;
 use pathmatcher::Matcher;
+use revisionstore::{ContentStore, RemoteDataStore, StoreKey};
 use treestate::filestate::StateFlags;
 use treestate::tree::VisitorResult;
 use treestate::treestate::TreeState;
-use types::{RepoPath, RepoPathBuf};
+use types::{Key, RepoPath, RepoPathBuf};
 use vfs::{is_executable, is_symlink, VFS};
 
 use crate::walker::{WalkEntry, WalkError, Walker};
 
+pub(crate) const TRACKED: StateFlags = StateFlags::from_bits_truncate(
+    StateFlags::EXIST_P1.bits() | StateFlags::EXIST_P2.bits() | StateFlags::EXIST_NEXT.bits(),
+);
+
+pub(crate) const NORMAL: StateFlags =
+    StateFlags::from_bits_truncate(StateFlags::EXIST_P1.bits() | StateFlags::EXIST_NEXT.bits());
+
 /// Represents a file modification time in Mercurial, in seconds since the unix epoch.
-#[derive(PartialEq)]
+#[derive(PartialEq, PartialOrd)]
 pub struct HgModifiedTime(u64);
 
 impl From<u64> for HgModifiedTime {
@@ -71,6 +81,10 @@ impl PhysicalFileSystem {
         })
     }
 
+    pub(crate) fn vfs(&self) -> &VFS {
+        &self.vfs
+    }
+
     pub fn pending_changes<M: Matcher + Clone>(
         &self,
         treestate: Arc<Mutex<TreeState>>,
@@ -89,7 +103,10 @@ impl PhysicalFileSystem {
             seen: HashSet::new(),
             lookups: vec![],
             tree_iter: None,
+            lookup_iter: None,
+            lookup_source: None,
             last_write,
+            resolved_lookups: 0,
         }
     }
 }
@@ -104,7 +121,10 @@ pub struct PendingChanges<M: Matcher + Clone> {
     seen: HashSet<RepoPathBuf>,
     lookups: Vec<RepoPathBuf>,
     tree_iter: Option<Box<dyn Iterator<Item = Result<PendingChangeResult>> + Send>>,
+    lookup_iter: Option<Box<dyn Iterator<Item = Result<PendingChangeResult>> + Send>>,
+    lookup_source: Option<(TreeManifest, ContentComparer)>,
     last_write: HgModifiedTime,
+    resolved_lookups: usize,
 }
 
 #[derive(PartialEq)]
@@ -137,6 +157,24 @@ pub enum PendingChangeResult {
 }
 
 impl<M: Matcher + Clone> PendingChanges<M> {
+    /// Resolve files that cannot be decided by their metadata alone by
+    /// comparing their content with the parent manifest. Without this, such
+    /// files are not reported.
+    ///
+    /// Files found to be clean are updated in the treestate, so they don't
+    /// need a lookup again once the treestate is written.
+    pub fn compare_content(mut self, parent: TreeManifest, store: Arc<ContentStore>) -> Self {
+        let comparer = ContentComparer::new(self.vfs.clone(), store);
+        self.lookup_source = Some((parent, comparer));
+        self
+    }
+
+    /// The number of files whose lookup found them clean and which were
+    /// updated in the treestate.
+    pub fn resolved_lookups(&self) -> usize {
+        self.resolved_lookups
+    }
+
     fn is_changed(&mut self, path: &RepoPath, metadata: &Metadata) -> Result<bool> {
         let mut treestate = self.treestate.lock();
         let state = treestate.get(path)?;
@@ -322,7 +360,93 @@ impl<M: Matcher + Clone> PendingChanges<M> {
     }
 
     fn next_lookup(&mut self) -> Option<Result<PendingChangeResult>> {
-        None
+        if self.lookup_iter.is_none() {
+            self.lookup_iter = Some(Box::new(self.get_lookup_entries().into_iter()));
+        }
+
+        self.lookup_iter.as_mut().unwrap().next()
+    }
+
+    fn get_lookup_entries(&mut self) -> Vec<Result<PendingChangeResult>> {
+        let mut results = vec![];
+        let (parent, comparer) = match self.lookup_source.as_ref() {
+            Some(source) => source,
+            None => return results,
+        };
+
+        let mut lookups = std::mem::replace(&mut self.lookups, vec![]);
+        lookups.sort();
+        lookups.dedup();
+
+        let mut files = Vec::with_capacity(lookups.len());
+        for path in lookups.into_iter() {
+            match parent.get_file(&path) {
+                Ok(Some(metadata)) => files.push((path, metadata)),
+                // Lookups only come from p1, but be conservative if the treestate and the
+                // manifest disagree.
+                Ok(None) => results.push(Ok(PendingChangeResult::File(ChangeType::Changed(path)))),
+                Err(e) => results.push(Err(e)),
+            }
+        }
+
+        if let Err(e) = comparer.prefetch(&files) {
+            results.push(Err(e));
+            return results;
+        }
+
+        let mut clean = Vec::new();
+        for (path, metadata) in files.into_iter() {
+            match comparer.compare(path.clone(), &metadata) {
+                Ok(Some(change)) => results.push(Ok(PendingChangeResult::File(change))),
+                Ok(None) => clean.push(path),
+                Err(e) => results.push(Err(e)),
+            }
+        }
+
+        if let Err(e) = self.mark_clean(clean) {
+            results.push(Err(e));
+        }
+        results
+    }
+
+    /// Record the current size and mtime of files found to be clean in the treestate, like
+    /// Python's `dirstate.normal`. Files modified in the current second are left as they are,
+    /// since a later change within the same second would not change their mtime.
+    fn mark_clean(&mut self, paths: Vec<RepoPathBuf>) -> Result<()> {
+        let now: HgModifiedTime = SystemTime::now().try_into()?;
+        let mut treestate = self.treestate.lock();
+        for path in paths.into_iter() {
+            let mut state = match treestate.get(&path)? {
+                Some(state) if state.state & TRACKED == NORMAL => state.clone(),
+                _ => continue,
+            };
+            let metadata = match self.vfs.metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            let mtime: HgModifiedTime = metadata.modified()?.try_into()?;
+            if mtime >= now {
+                continue;
+            }
+            let size: Result<i32, _> = metadata.len().try_into();
+            let mtime: Result<i32, _> = mtime.0.try_into();
+            let (size, mtime) = match (size, mtime) {
+                (Ok(size), Ok(mtime)) => (size, mtime),
+                _ => continue,
+            };
+
+            state.size = size;
+            state.mtime = mtime;
+            state.state.remove(StateFlags::NEED_CHECK);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                state.mode = metadata.permissions().mode();
+            }
+            treestate.insert(&path, &state)?;
+            self.resolved_lookups += 1;
+        }
+        Ok(())
     }
 }
 
@@ -351,7 +475,163 @@ impl<M: Matcher + Clone> Iterator for PendingChanges<M> {
     }
 }
 
+/// Compares files in the working copy with their content in a commit.
+pub(crate) struct ContentComparer {
+    vfs: VFS,
+    store: Arc<ContentStore>,
+}
+
+impl ContentComparer {
+    pub(crate) fn new(vfs: VFS, store: Arc<ContentStore>) -> Self {
+        ContentComparer { vfs, store }
+    }
+
+    /// Fetch the content of the given files so later comparisons don't fetch them one by one.
+    pub(crate) fn prefetch(&self, files: &[(RepoPathBuf, FileMetadata)]) -> Result<()> {
+        let keys: Vec<StoreKey> = files
+            .iter()
+            .map(|(path, metadata)| StoreKey::from(Key::new(path.clone(), metadata.hgid)))
+            .collect();
+        self.store.prefetch(&keys)
+    }
+
+    /// Returns `None` if the file in the working copy has the same flags and content as described
+    /// by `expected`.
+    pub(crate) fn compare(
+        &self,
+        path: RepoPathBuf,
+        expected: &FileMetadata,
+    ) -> Result<Option<ChangeType>> {
+        // If it's missing, or not a normal file or a symlink, consider it deleted, like the tree
+        // stage does.
+        let metadata = match self.vfs.metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(_) => return Ok(Some(ChangeType::Deleted(path))),
+        };
+        let file_type = metadata.file_type();
+        if !file_type.is_file() && !file_type.is_symlink() {
+            return Ok(Some(ChangeType::Deleted(path)));
+        }
+
+        let symlink = self.vfs.supports_symlinks() && is_symlink(&metadata);
+        let symlink_different =
+            self.vfs.supports_symlinks() && symlink != (expected.file_type == FileType::Symlink);
+        let exec_different = self.vfs.supports_executables()
+            && !symlink
+            && is_executable(&metadata) != (expected.file_type == FileType::Executable);
+        if symlink_different || exec_different {
+            return Ok(Some(ChangeType::Changed(path)));
+        }
+
+        let key = Key::new(path.clone(), expected.hgid);
+        let expected_content = match self.store.get_file_content(&key)? {
+            Some(content) => content,
+            None => return Err(format_err!("cannot find content of '{}' in store", path)),
+        };
+
+        let abs_path = self.vfs.join(&path);
+        let same = if symlink {
+            // Symlinks are stored as their target.
+            let target = fs::read_link(&abs_path)?;
+            target.to_str().map(|t| t.as_bytes()) == Some(&expected_content[..])
+        } else {
+            metadata.len() == expected_content.len() as u64
+                && fs::read(&abs_path)?[..] == expected_content[..]
+        };
+
+        if same {
+            Ok(None)
+        } else {
+            Ok(Some(ChangeType::Changed(path)))
+        }
+    }
+}
+
 fn normalize(path: RepoPathBuf) -> RepoPathBuf {
     // TODO: Support path normalization on case insensitive file systems
     path
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use filetime::{set_file_mtime, FileTime};
+    use manifest_tree::testutil::make_tree_manifest;
+    use pathmatcher::AlwaysMatcher;
+    use revisionstore::testutil::{delta, make_config};
+    use revisionstore::HgIdMutableDeltaStore;
+    use tempfile::tempdir;
+    use treestate::filestate::FileStateV2;
+    use types::testutil::key;
+
+    #[test]
+    fn test_compare_content() -> Result<()> {
+        let root = tempdir()?;
+        let statedir = tempdir()?;
+        let cachedir = tempdir()?;
+        let localdir = tempdir()?;
+
+        // Both files keep their size but not their mtime, so their content
+        // is compared with the parent.
+        for (path, content) in &[("clean", "same\n"), ("modified", "new\n")] {
+            let path = root.path().join(path);
+            fs::write(&path, content)?;
+            set_file_mtime(&path, FileTime::from_unix_time(1_000_000, 0))?;
+        }
+        let mut treestate = TreeState::open(statedir.path().join("tree"), None)?;
+        let mut state = FileStateV2 {
+            mode: 0o100644,
+            size: 5,
+            mtime: 0,
+            state: NORMAL | StateFlags::NEED_CHECK,
+            copied: None,
+        };
+        treestate.insert("clean", &state)?;
+        state.size = 4;
+        state.state = NORMAL;
+        treestate.insert("modified", &state)?;
+        let treestate = Arc::new(Mutex::new(treestate));
+
+        let parent = make_tree_manifest(&[("clean", "1"), ("modified", "2")]);
+        let store = ContentStore::new(&localdir, &make_config(&cachedir))?;
+        store.add(
+            &delta("same\n", None, key("clean", "1")),
+            &Default::default(),
+        )?;
+        store.add(
+            &delta("old\n", None, key("modified", "2")),
+            &Default::default(),
+        )?;
+
+        let fs = PhysicalFileSystem::new(root.path().to_path_buf())?;
+        let mut pending_changes = fs
+            .pending_changes(
+                treestate.clone(),
+                Arc::new(AlwaysMatcher::new()),
+                false,
+                2_000_000u64.into(),
+            )
+            .compare_content(parent, Arc::new(store));
+        let changes = (&mut pending_changes)
+            .map(|change| match change? {
+                PendingChangeResult::File(ChangeType::Changed(path)) => Ok(path.to_string()),
+                PendingChangeResult::File(ChangeType::Deleted(path)) => {
+                    Ok(format!("deleted {}", path))
+                }
+                PendingChangeResult::SeenDirectory(path) => Ok(format!("directory {}", path)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(changes, vec!["modified".to_string()]);
+
+        // The clean file is updated in the treestate, so it does not need a
+        // lookup next time.
+        assert_eq!(pending_changes.resolved_lookups(), 1);
+        let state = treestate.lock().get("clean")?.cloned().unwrap();
+        assert_eq!((state.size, state.mtime), (5, 1_000_000));
+        assert!(!state.state.intersects(StateFlags::NEED_CHECK));
+        let state = treestate.lock().get("modified")?.cloned().unwrap();
+        assert_eq!((state.size, state.mtime), (4, 0));
+        Ok(())
+    }
+}
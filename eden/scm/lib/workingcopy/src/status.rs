@@ -0,0 +1,548 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    rc::Rc,
+    sync::Arc,
+};
+
+use anyhow::Result;
+use parking_lot::Mutex;
+
+use manifest::{DiffType, Manifest};
+use manifest_tree::TreeManifest;
+use pathmatcher::{DirectoryMatch, GitignoreMatcher, Matcher};
+use revisionstore::ContentStore;
+use treestate::filestate::StateFlags;
+use treestate::tree::VisitorResult;
+use treestate::treestate::TreeState;
+use types::{RepoPath, RepoPathBuf};
+
+use crate::filesystem::{
+    ChangeType, ContentComparer, PendingChangeResult, PhysicalFileSystem, NORMAL, TRACKED,
+};
+
+/// Files in the working copy grouped by their status. Each group is sorted.
+#[derive(Debug, Default, PartialEq)]
+pub struct Status {
+    pub modified: BTreeSet<RepoPathBuf>,
+    pub added: BTreeSet<RepoPathBuf>,
+    pub removed: BTreeSet<RepoPathBuf>,
+    pub deleted: BTreeSet<RepoPathBuf>,
+    pub unknown: BTreeSet<RepoPathBuf>,
+    pub ignored: BTreeSet<RepoPathBuf>,
+    pub clean: BTreeSet<RepoPathBuf>,
+    /// Maps copy destinations to their sources.
+    pub copymap: BTreeMap<RepoPathBuf, RepoPathBuf>,
+}
+
+/// Matcher used to walk the working copy for status. Directories that are
+/// entirely ignored are skipped, unless ignored files are listed.
+///
+/// Files are not filtered by the ignore rules, so tracked files in ignored
+/// directories are still checked by the tree stage of [`PendingChanges`].
+///
+/// [`PendingChanges`]: crate::filesystem::PendingChanges
+#[derive(Clone)]
+pub struct StatusMatcher<M> {
+    matcher: M,
+    ignore: Option<Rc<GitignoreMatcher>>,
+}
+
+impl<M: Matcher> StatusMatcher<M> {
+    pub fn new(matcher: M, ignore: Rc<GitignoreMatcher>, list_ignored: bool) -> Self {
+        let ignore = if list_ignored { None } else { Some(ignore) };
+        StatusMatcher { matcher, ignore }
+    }
+}
+
+impl<M: Matcher> Matcher for StatusMatcher<M> {
+    fn matches_directory(&self, path: &RepoPath) -> DirectoryMatch {
+        if let Some(ignore) = self.ignore.as_ref() {
+            if ignore.matches_directory(path) == DirectoryMatch::Everything {
+                return DirectoryMatch::Nothing;
+            }
+        }
+        self.matcher.matches_directory(path)
+    }
+
+    fn matches_file(&self, path: &RepoPath) -> bool {
+        self.matcher.matches_file(path)
+    }
+}
+
+/// Tracked files that are not simply "in p1 and in the working copy", i.e. added, removed,
+/// merged or from the other parent. Their status does not depend on their content.
+fn is_nonnormal(flags: StateFlags) -> bool {
+    flags.intersects(TRACKED) && (flags & TRACKED) != NORMAL
+}
+
+impl Status {
+    /// Compute the status of the working copy relative to its first parent.
+    ///
+    /// `pending_changes` should come from [`PhysicalFileSystem::pending_changes`] using the same
+    /// `matcher`, with content comparison enabled so files needing a lookup are resolved.
+    ///
+    /// Untracked files matched by `ignore` are reported as ignored if `list_ignored` is set, and
+    /// skipped otherwise.
+    pub fn from_pending_changes<M: Matcher, I: Matcher>(
+        fs: &PhysicalFileSystem,
+        pending_changes: impl Iterator<Item = Result<PendingChangeResult>>,
+        treestate: Arc<Mutex<TreeState>>,
+        matcher: &M,
+        ignore: &I,
+        list_ignored: bool,
+        list_clean: bool,
+    ) -> Result<Status> {
+        let mut status = Status::default();
+        let mut seen = HashSet::new();
+
+        // PendingChanges locks the treestate while iterating. Only lock it
+        // for each lookup here.
+        for change in pending_changes {
+            let (path, exists) = match change? {
+                PendingChangeResult::File(ChangeType::Changed(path)) => (path, true),
+                PendingChangeResult::File(ChangeType::Deleted(path)) => (path, false),
+                PendingChangeResult::SeenDirectory(_) => continue,
+            };
+            let flags = match treestate.lock().get(path.as_repo_path())? {
+                Some(state) => state.state,
+                None => StateFlags::empty(),
+            };
+
+            if flags.intersects(TRACKED) {
+                status.insert_tracked(path.clone(), flags, exists);
+            } else if exists {
+                if !ignore.matches_file(&path) {
+                    status.unknown.insert(path.clone());
+                } else if list_ignored {
+                    status.ignored.insert(path.clone());
+                }
+            }
+            seen.insert(path);
+        }
+
+        // Added, removed and merged files, and copies, are decided by the
+        // treestate alone. They might not be reported by pending changes if
+        // their metadata looks unchanged.
+        let mut nonnormal = Vec::new();
+        treestate.lock().visit(
+            &mut |components, state| {
+                let path = RepoPathBuf::from_utf8(components.concat())?;
+                nonnormal.push((path, state.state, state.copied.clone()));
+                Ok(VisitorResult::NotChanged)
+            },
+            &|_path, dir| match dir.get_aggregated_state() {
+                None => true,
+                Some(state) => {
+                    state
+                        .union
+                        .intersects(StateFlags::EXIST_P2 | StateFlags::COPIED)
+                        || !state.intersection.contains(NORMAL)
+                }
+            },
+            &|_path, file| is_nonnormal(file.state) || file.state.intersects(StateFlags::COPIED),
+        )?;
+
+        for (path, flags, copied) in nonnormal.into_iter() {
+            if !matcher.matches_file(&path) {
+                continue;
+            }
+            if let Some(copied) = copied {
+                if flags.intersects(StateFlags::COPIED) {
+                    let source = RepoPathBuf::from_utf8(copied.into_vec())?;
+                    status.copymap.insert(path.clone(), source);
+                }
+            }
+            if !seen.contains(&path) {
+                let exists = match fs.vfs().metadata(&path) {
+                    Ok(metadata) => metadata.is_file() || metadata.file_type().is_symlink(),
+                    Err(_) => false,
+                };
+                status.insert_tracked(path, flags, exists);
+            }
+        }
+
+        if list_clean {
+            let mut tracked = Vec::new();
+            treestate.lock().visit(
+                &mut |components, _| {
+                    let path = RepoPathBuf::from_utf8(components.concat())?;
+                    tracked.push(path);
+                    Ok(VisitorResult::NotChanged)
+                },
+                &|_path, dir| match dir.get_aggregated_state() {
+                    None => true,
+                    Some(state) => state.union.contains(NORMAL),
+                },
+                &|_path, file| file.state.contains(NORMAL),
+            )?;
+            for path in tracked.into_iter() {
+                if matcher.matches_file(&path) && !status.is_changed(&path) {
+                    status.clean.insert(path);
+                }
+            }
+        }
+
+        Ok(status)
+    }
+
+    /// Turn a status relative to the first parent `p1` into a status
+    /// relative to `other`.
+    ///
+    /// Files changed in the working copy are compared with `other` by
+    /// content. Other files are compared by their nodes in the manifests.
+    /// Copies are dropped since they are relative to the first parent.
+    pub fn compare_with<M: Matcher>(
+        self,
+        fs: &PhysicalFileSystem,
+        p1: &TreeManifest,
+        other: &TreeManifest,
+        store: Arc<ContentStore>,
+        matcher: &M,
+        list_clean: bool,
+    ) -> Result<Status> {
+        let mut candidates: BTreeSet<RepoPathBuf> = self
+            .modified
+            .iter()
+            .chain(self.added.iter())
+            .chain(self.removed.iter())
+            .cloned()
+            .collect();
+        for entry in other.diff(p1, matcher) {
+            candidates.insert(entry?.path);
+        }
+
+        let mut status = Status {
+            deleted: self.deleted,
+            ignored: self.ignored,
+            ..Default::default()
+        };
+        let mut to_compare = Vec::new();
+        for path in candidates.iter() {
+            if status.deleted.contains(path) {
+                continue;
+            }
+            let in_working_copy = !self.removed.contains(path);
+            let changed = self.modified.contains(path) || self.added.contains(path);
+            match other.get_file(path)? {
+                None => {
+                    if changed || (in_working_copy && p1.get_file(path)?.is_some()) {
+                        status.added.insert(path.clone());
+                    }
+                }
+                Some(theirs) => {
+                    if !in_working_copy {
+                        status.removed.insert(path.clone());
+                    } else if changed {
+                        to_compare.push((path.clone(), theirs));
+                    } else {
+                        match p1.get_file(path)? {
+                            None => {
+                                status.removed.insert(path.clone());
+                            }
+                            Some(ours) if ours != theirs => {
+                                status.modified.insert(path.clone());
+                            }
+                            Some(_) => {
+                                if list_clean {
+                                    status.clean.insert(path.clone());
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let comparer = ContentComparer::new(fs.vfs().clone(), store);
+        comparer.prefetch(&to_compare)?;
+        for (path, theirs) in to_compare.into_iter() {
+            match comparer.compare(path.clone(), &theirs)? {
+                Some(ChangeType::Changed(_)) => {
+                    status.modified.insert(path);
+                }
+                Some(ChangeType::Deleted(_)) => {
+                    status.deleted.insert(path);
+                }
+                None => {
+                    if list_clean {
+                        status.clean.insert(path);
+                    }
+                }
+            }
+        }
+
+        // Untracked files that exist in `other` are reported as removed.
+        status.unknown = self
+            .unknown
+            .into_iter()
+            .filter(|path| !status.removed.contains(path))
+            .collect();
+
+        if list_clean {
+            for file in other.files(matcher) {
+                let path = file?.path;
+                if !candidates.contains(&path) && !status.deleted.contains(&path) {
+                    status.clean.insert(path);
+                }
+            }
+        }
+
+        Ok(status)
+    }
+
+    /// Compute the status of the commit with manifest `to` relative to the
+    /// commit with manifest `from`, like `--change` or two `--rev`s.
+    ///
+    /// Only modified, added, removed and clean files are reported, and
+    /// copies are not tracked.
+    pub fn between<M: Matcher>(
+        from: &TreeManifest,
+        to: &TreeManifest,
+        matcher: &M,
+        list_clean: bool,
+    ) -> Result<Status> {
+        let mut status = Status::default();
+        for entry in to.diff(from, matcher) {
+            let entry = entry?;
+            match entry.diff_type {
+                DiffType::LeftOnly(_) => status.added.insert(entry.path),
+                DiffType::RightOnly(_) => status.removed.insert(entry.path),
+                DiffType::Changed(_, _) => status.modified.insert(entry.path),
+            };
+        }
+
+        if list_clean {
+            for file in to.files(matcher) {
+                let path = file?.path;
+                if !status.added.contains(&path) && !status.modified.contains(&path) {
+                    status.clean.insert(path);
+                }
+            }
+        }
+
+        Ok(status)
+    }
+
+    fn insert_tracked(&mut self, path: RepoPathBuf, flags: StateFlags, exists: bool) {
+        if !flags.intersects(StateFlags::EXIST_NEXT) {
+            self.removed.insert(path);
+        } else if !exists {
+            self.deleted.insert(path);
+        } else if !flags.intersects(StateFlags::EXIST_P1 | StateFlags::EXIST_P2) {
+            self.added.insert(path);
+        } else {
+            self.modified.insert(path);
+        }
+    }
+
+    fn is_changed(&self, path: &RepoPath) -> bool {
+        self.modified.contains(path)
+            || self.added.contains(path)
+            || self.removed.contains(path)
+            || self.deleted.contains(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::{create_dir_all, write, File};
+    use std::path::Path;
+
+    use manifest_tree::testutil::make_tree_manifest;
+    use pathmatcher::{AlwaysMatcher, TreeMatcher};
+    use revisionstore::testutil::{delta, make_config};
+    use revisionstore::HgIdMutableDeltaStore;
+    use tempfile::tempdir;
+    use treestate::filestate::FileStateV2;
+    use types::testutil::key;
+
+    fn state(flags: StateFlags) -> FileStateV2 {
+        FileStateV2 {
+            mode: 0o100644,
+            size: 0,
+            mtime: 0,
+            state: flags,
+            copied: None,
+        }
+    }
+
+    fn touch(root: &Path, path: &str) {
+        let path = root.join(path);
+        create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap();
+    }
+
+    fn paths(paths: &[&str]) -> BTreeSet<RepoPathBuf> {
+        paths
+            .iter()
+            .map(|p| RepoPathBuf::from_string(p.to_string()).unwrap())
+            .collect()
+    }
+
+    fn changed(path: &str) -> Result<PendingChangeResult> {
+        let path = RepoPathBuf::from_string(path.to_string()).unwrap();
+        Ok(PendingChangeResult::File(ChangeType::Changed(path)))
+    }
+
+    fn deleted(path: &str) -> Result<PendingChangeResult> {
+        let path = RepoPathBuf::from_string(path.to_string()).unwrap();
+        Ok(PendingChangeResult::File(ChangeType::Deleted(path)))
+    }
+
+    #[test]
+    fn test_status_from_pending_changes() -> Result<()> {
+        let root = tempdir()?;
+        let statedir = tempdir()?;
+        let mut treestate = TreeState::open(statedir.path().join("tree"), None)?;
+
+        let normal = StateFlags::EXIST_P1 | StateFlags::EXIST_NEXT;
+        let mut copied = state(StateFlags::EXIST_NEXT | StateFlags::COPIED);
+        copied.copied = Some(b"clean".to_vec().into_boxed_slice());
+        treestate.insert("clean", &state(normal))?;
+        treestate.insert("modified", &state(normal))?;
+        treestate.insert("merged", &state(normal | StateFlags::EXIST_P2))?;
+        treestate.insert("added", &state(StateFlags::EXIST_NEXT))?;
+        treestate.insert("added_missing", &state(StateFlags::EXIST_NEXT))?;
+        treestate.insert("copied", &copied)?;
+        treestate.insert("removed", &state(StateFlags::EXIST_P1))?;
+        treestate.insert("deleted", &state(normal))?;
+        let treestate = Arc::new(Mutex::new(treestate));
+
+        for path in &[
+            "clean", "modified", "merged", "added", "copied", "removed", "unknown", "a.o",
+        ] {
+            touch(root.path(), path);
+        }
+        let fs = PhysicalFileSystem::new(root.path().to_path_buf())?;
+
+        let pending_changes = vec![
+            changed("modified"),
+            changed("added"),
+            changed("copied"),
+            changed("unknown"),
+            changed("a.o"),
+            deleted("deleted"),
+        ];
+        let ignore = TreeMatcher::from_rules(["*.o"].iter())?;
+        let status = Status::from_pending_changes(
+            &fs,
+            pending_changes.into_iter(),
+            treestate,
+            &AlwaysMatcher::new(),
+            &ignore,
+            true,
+            true,
+        )?;
+
+        assert_eq!(status.modified, paths(&["merged", "modified"]));
+        assert_eq!(status.added, paths(&["added", "copied"]));
+        assert_eq!(status.removed, paths(&["removed"]));
+        assert_eq!(status.deleted, paths(&["added_missing", "deleted"]));
+        assert_eq!(status.unknown, paths(&["unknown"]));
+        assert_eq!(status.ignored, paths(&["a.o"]));
+        assert_eq!(status.clean, paths(&["clean"]));
+        assert_eq!(
+            status.copymap.into_iter().collect::<Vec<_>>(),
+            vec![(
+                RepoPathBuf::from_string("copied".to_string())?,
+                RepoPathBuf::from_string("clean".to_string())?
+            )]
+        );
+        Ok(())
+    }
+
+    /// Manifests of the first parent and of another commit.
+    fn manifests() -> (TreeManifest, TreeManifest) {
+        let p1 = make_tree_manifest(&[
+            ("same", "1"),
+            ("changed_in_other", "2"),
+            ("only_p1", "3"),
+            ("modified_back", "4"),
+            ("deleted", "5"),
+            ("removed", "6"),
+        ]);
+        let other = make_tree_manifest(&[
+            ("same", "1"),
+            ("changed_in_other", "7"),
+            ("only_other", "8"),
+            ("modified_back", "9"),
+            ("deleted", "5"),
+            ("removed", "6"),
+        ]);
+        (p1, other)
+    }
+
+    #[test]
+    fn test_compare_with() -> Result<()> {
+        let root = tempdir()?;
+        let cachedir = tempdir()?;
+        let localdir = tempdir()?;
+
+        // "modified_back" was changed in the working copy to its content in
+        // the other commit.
+        write(root.path().join("modified_back"), "other\n")?;
+        let fs = PhysicalFileSystem::new(root.path().to_path_buf())?;
+        let store = ContentStore::new(&localdir, &make_config(&cachedir))?;
+        store.add(
+            &delta("other\n", None, key("modified_back", "9")),
+            &Default::default(),
+        )?;
+
+        let (p1, other) = manifests();
+        let status = Status {
+            modified: paths(&["modified_back"]),
+            added: paths(&["added"]),
+            removed: paths(&["removed"]),
+            deleted: paths(&["deleted"]),
+            unknown: paths(&["only_other"]),
+            ..Default::default()
+        };
+        let status = status.compare_with(
+            &fs,
+            &p1,
+            &other,
+            Arc::new(store),
+            &AlwaysMatcher::new(),
+            true,
+        )?;
+
+        assert_eq!(status.modified, paths(&["changed_in_other"]));
+        assert_eq!(status.added, paths(&["added", "only_p1"]));
+        assert_eq!(status.removed, paths(&["only_other", "removed"]));
+        assert_eq!(status.deleted, paths(&["deleted"]));
+        assert_eq!(status.unknown, paths(&[]));
+        assert_eq!(status.clean, paths(&["modified_back", "same"]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_status_between() -> Result<()> {
+        let (p1, other) = manifests();
+        let status = Status::between(&p1, &other, &AlwaysMatcher::new(), true)?;
+
+        assert_eq!(
+            status.modified,
+            paths(&["changed_in_other", "modified_back"])
+        );
+        assert_eq!(status.added, paths(&["only_other"]));
+        assert_eq!(status.removed, paths(&["only_p1"]));
+        assert_eq!(status.clean, paths(&["deleted", "removed", "same"]));
+        assert!(status.deleted.is_empty() && status.unknown.is_empty());
+
+        let matcher = TreeMatcher::from_rules(["only_*"].iter())?;
+        let status = Status::between(&p1, &other, &matcher, false)?;
+        assert_eq!(status.added, paths(&["only_other"]));
+        assert_eq!(status.removed, paths(&["only_p1"]));
+        assert!(status.modified.is_empty() && status.clean.is_empty());
+        Ok(())
+    }
+}
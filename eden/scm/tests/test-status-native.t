@@ -0,0 +1,153 @@
+#chg-compatible
+
+The native status of working copies that are not backed by EdenFS must match
+the Python status. status.use-rust=false forces the Python implementation.
+
+  $ . "$TESTDIR/library.sh"
+  $ setconfig format.dirstate=2 remotefilelog.debug=false treemanifest.sendtrees=true
+
+  $ hginit master
+  $ cd master
+  $ cat >> .hg/hgrc <<EOF
+  > [treemanifest]
+  > server=True
+  > [remotefilelog]
+  > server=True
+  > shallowtrees=True
+  > EOF
+  $ mkdir dir
+  $ echo a > a
+  $ echo b > b
+  $ echo c > c
+  $ echo d > dir/d
+  $ echo e > dir/e
+  $ hg commit -qAm first
+  $ echo b2 > b
+  $ hg rm -q c
+  $ echo f > f
+  $ hg commit -qAm second
+  $ cd ..
+
+  $ hgcloneshallow ssh://user@dummy/master shallow -q
+  fetching tree '' *, found via * (glob) (?)
+  * trees fetched over * (glob) (?)
+  $ cd shallow
+
+  $ compare() {
+  >   hg status --config status.use-rust=false "$@" > "$TESTTMP/python.out"
+  >   hg status "$@" > "$TESTTMP/native.out"
+  >   diff "$TESTTMP/python.out" "$TESTTMP/native.out" && cat "$TESTTMP/native.out"
+  > }
+
+A clean working copy
+
+  $ compare
+  $ compare -A
+  C a
+  C b
+  C dir/d
+  C dir/e
+  C f
+
+Changes in the working copy
+
+  $ echo a2 > a
+  $ echo g > g
+  $ hg add -q g
+  $ hg rm -q dir/d
+  $ rm dir/e
+  $ echo u > unknown
+  $ compare
+  M a
+  A g
+  R dir/d
+  ! dir/e
+  ? unknown
+  $ compare -mA
+  M a
+  A g
+  R dir/d
+  ! dir/e
+  ? unknown
+  C b
+  C f
+  $ compare -n0 | tr '\0' '\n'
+  a
+  g
+  dir/d
+  dir/e
+  unknown
+
+Include and exclude patterns
+
+  $ compare -I dir
+  R dir/d
+  ! dir/e
+  $ compare -X 'glob:dir/*' -X unknown
+  M a
+  A g
+  $ compare -I 'path:a' -I 'glob:*.txt'
+  M a
+
+Status relative to another revision
+
+  $ compare --rev 0
+  M a
+  M b
+  A f
+  A g
+  R c
+  R dir/d
+  ! dir/e
+  ? unknown
+  $ compare --rev 0 -I dir
+  R dir/d
+  ! dir/e
+  $ compare --rev .
+  M a
+  A g
+  R dir/d
+  ! dir/e
+  ? unknown
+
+Status between two revisions
+
+  $ compare --change 1
+  M b
+  A f
+  R c
+  $ compare --rev 0 --rev 1
+  M b
+  A f
+  R c
+  $ compare --rev 1 --rev 0 -mardc
+  M b
+  A c
+  R f
+  C a
+  C dir/d
+  C dir/e
+  $ compare --change 0 -X dir
+  A a
+  A b
+  A c
+
+Files found clean by a lookup are written back to the treestate
+
+  $ hg revert -q --all --no-backup
+  $ compare
+  ? g
+  ? unknown
+  $ touch -t 200001010000 b
+  $ cp .hg/dirstate "$TESTTMP/dirstate.before"
+  $ hg status
+  ? g
+  ? unknown
+  $ cmp -s .hg/dirstate "$TESTTMP/dirstate.before" || echo written
+  written
+  $ compare -c
+  C a
+  C b
+  C dir/d
+  C dir/e
+  C f